@@ -165,6 +165,7 @@ pub struct DebugSessionSummary {
     pub recording_target_count: Option<u32>,
     pub recording_count: u32,
     pub current_line: Option<u32>,
+    pub current_source: Option<String>,
     pub created_unix_ms: u64,
     pub updated_unix_ms: u64,
     pub message: Option<String>,
@@ -188,14 +189,25 @@ pub struct DebugSessionDetail {
     pub start_command_id: String,
     pub stop_command_id: Option<String>,
     pub current_line: Option<u32>,
+    pub current_source: Option<String>,
     pub source_flavor: Option<String>,
     pub source_code: Option<String>,
+    pub source_files: Vec<DebugSourceFile>,
     pub breakpoints: Vec<u32>,
     pub created_unix_ms: u64,
     pub updated_unix_ms: u64,
     pub attached_unix_ms: Option<u64>,
     pub message: Option<String>,
     pub last_output: Option<String>,
+    /// Most recent logpoint messages reported by the edge, oldest first.
+    pub log_messages: Vec<String>,
+}
+
+/// An imported module's text, shown when the debugger stops inside it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DebugSourceFile {
+    pub name: String,
+    pub text: String,
 }
 
 #[derive(Clone, Debug)]
@@ -217,8 +229,10 @@ struct DebugSessionRecord {
     start_command_id: String,
     stop_command_id: Option<String>,
     current_line: Option<u32>,
+    current_source: Option<String>,
     source_flavor: Option<String>,
     source_code: Option<String>,
+    source_files: Vec<DebugSourceFile>,
     breakpoints: HashSet<u32>,
     created_unix_ms: u64,
     updated_unix_ms: u64,
@@ -226,6 +240,7 @@ struct DebugSessionRecord {
     last_resume_command_unix_ms: Option<u64>,
     message: Option<String>,
     last_output: Option<String>,
+    log_messages: Vec<String>,
     replay_states: HashMap<String, VmRecordingReplayState>,
 }
 
@@ -244,6 +259,7 @@ impl DebugSessionRecord {
             recording_target_count: self.recording_target_count,
             recording_count: self.recordings.len() as u32,
             current_line: self.current_line,
+            current_source: self.current_source.clone(),
             created_unix_ms: self.created_unix_ms,
             updated_unix_ms: self.updated_unix_ms,
             message: self.message.clone(),
@@ -270,14 +286,17 @@ impl DebugSessionRecord {
             start_command_id: self.start_command_id.clone(),
             stop_command_id: self.stop_command_id.clone(),
             current_line: self.current_line,
+            current_source: self.current_source.clone(),
             source_flavor: self.source_flavor.clone(),
             source_code: self.source_code.clone(),
+            source_files: self.source_files.clone(),
             breakpoints,
             created_unix_ms: self.created_unix_ms,
             updated_unix_ms: self.updated_unix_ms,
             attached_unix_ms: self.attached_unix_ms,
             message: self.message.clone(),
             last_output: self.last_output.clone(),
+            log_messages: self.log_messages.clone(),
         }
     }
 
@@ -322,8 +341,10 @@ impl DebugSessionRecord {
             start_command_id: self.start_command_id.clone(),
             stop_command_id: self.stop_command_id.clone(),
             current_line: self.current_line,
+            current_source: self.current_source.clone(),
             source_flavor: self.source_flavor.clone(),
             source_code: self.source_code.clone(),
+            source_files: self.source_files.clone(),
             breakpoints,
             created_unix_ms: self.created_unix_ms,
             updated_unix_ms: self.updated_unix_ms,
@@ -331,6 +352,7 @@ impl DebugSessionRecord {
             last_resume_command_unix_ms: self.last_resume_command_unix_ms,
             message: self.message.clone(),
             last_output: self.last_output.clone(),
+            log_messages: self.log_messages.clone(),
             replay_states,
         }
     }
@@ -368,8 +390,10 @@ impl DebugSessionRecord {
             start_command_id: value.start_command_id,
             stop_command_id: value.stop_command_id,
             current_line: value.current_line,
+            current_source: value.current_source,
             source_flavor: value.source_flavor,
             source_code: value.source_code,
+            source_files: value.source_files,
             breakpoints: value.breakpoints.into_iter().collect(),
             created_unix_ms: value.created_unix_ms,
             updated_unix_ms: value.updated_unix_ms,
@@ -377,6 +401,7 @@ impl DebugSessionRecord {
             last_resume_command_unix_ms: value.last_resume_command_unix_ms,
             message: value.message,
             last_output: value.last_output,
+            log_messages: value.log_messages,
             replay_states,
         }
     }
@@ -447,10 +472,14 @@ struct PersistedDebugSessionRecord {
     #[serde(default)]
     current_line: Option<u32>,
     #[serde(default)]
+    current_source: Option<String>,
+    #[serde(default)]
     source_flavor: Option<String>,
     #[serde(default)]
     source_code: Option<String>,
     #[serde(default)]
+    source_files: Vec<DebugSourceFile>,
+    #[serde(default)]
     breakpoints: Vec<u32>,
     created_unix_ms: u64,
     updated_unix_ms: u64,
@@ -463,6 +492,8 @@ struct PersistedDebugSessionRecord {
     #[serde(default)]
     last_output: Option<String>,
     #[serde(default)]
+    log_messages: Vec<String>,
+    #[serde(default)]
     replay_states: HashMap<String, PersistedReplayState>,
 }
 
@@ -670,7 +701,7 @@ impl ControllerState {
                 .and_then(|session_id| guard.get(session_id).map(DebugSessionRecord::to_detail));
             (sessions, selected_session)
         };
-        sessions.sort_by(|lhs, rhs| rhs.updated_unix_ms.cmp(&lhs.updated_unix_ms));
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.updated_unix_ms));
         DebugSessionsStreamSnapshot {
             kind: "snapshot",
             sessions,
@@ -1584,6 +1615,8 @@ struct CreateDebugSessionRequest {
     #[serde(default)]
     tcp_addr: Option<String>,
     #[serde(default)]
+    dap_addr: Option<String>,
+    #[serde(default)]
     header_name: Option<String>,
     #[serde(default)]
     stop_on_entry: Option<bool>,
@@ -1598,6 +1631,7 @@ struct DebugCommandResponse {
     phase: DebugSessionPhase,
     output: String,
     current_line: Option<u32>,
+    current_source: Option<String>,
     attached: bool,
 }
 
@@ -1609,10 +1643,33 @@ enum DebugCommandRequest {
     Next,
     Continue,
     Out,
-    SelectRecording { recording_id: String },
-    BreakLine { line: u32 },
-    ClearLine { line: u32 },
-    PrintVar { name: String },
+    SelectRecording {
+        recording_id: String,
+    },
+    BreakLine {
+        line: u32,
+        #[serde(default)]
+        condition: Option<String>,
+        #[serde(default)]
+        hit_count: Option<u32>,
+        #[serde(default)]
+        log_message: Option<String>,
+    },
+    ClearLine {
+        line: u32,
+    },
+    PrintVar {
+        name: String,
+    },
+    Eval {
+        expr: String,
+        #[serde(default)]
+        flavor: Option<String>,
+    },
+    SetVar {
+        name: String,
+        expr: String,
+    },
     Locals,
     Stack,
 }
@@ -1692,6 +1749,8 @@ struct EnqueueStartDebugRequest {
     #[serde(default)]
     tcp_addr: Option<String>,
     #[serde(default)]
+    dap_addr: Option<String>,
+    #[serde(default)]
     header_name: Option<String>,
     #[serde(default)]
     stop_on_entry: Option<bool>,
@@ -2011,6 +2070,37 @@ fn resolve_edge_debug_source(
     )
 }
 
+/// Texts of the modules a debug session's source imports, taken from the
+/// debug info of a fresh compile so stops inside them can be shown.
+fn resolve_debug_source_files(
+    source_flavor: Option<&str>,
+    source_code: Option<&str>,
+) -> Vec<DebugSourceFile> {
+    let Some(source_code) = source_code else {
+        return Vec::new();
+    };
+    let flavor = parse_ui_flavor(source_flavor)
+        .map(|(item, _)| item)
+        .unwrap_or(SourceFlavor::RustScript);
+    let Ok(compiled) = compile_source_with_flavor(source_code, flavor) else {
+        return Vec::new();
+    };
+    let Some(debug) = compiled.program.debug else {
+        return Vec::new();
+    };
+    debug
+        .sources
+        .into_iter()
+        .skip(1)
+        .filter_map(|source| {
+            source.text.map(|text| DebugSourceFile {
+                name: source.name,
+                text,
+            })
+        })
+        .collect()
+}
+
 fn map_summary(edge_id: &str, record: &EdgeRecord) -> EdgeSummary {
     let has_pending_apply = record
         .pending_commands
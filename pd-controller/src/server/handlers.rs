@@ -373,7 +373,9 @@ pub(super) async fn rpc_poll_handler(
     let debug_session_active = request.telemetry.debug_session_active;
     let debug_session_attached = request.telemetry.debug_session_attached;
     let debug_session_current_line = request.telemetry.debug_session_current_line;
+    let debug_session_current_source = request.telemetry.debug_session_current_source.clone();
     let debug_session_request_id = request.telemetry.debug_session_request_id.clone();
+    let debug_session_log_messages = request.telemetry.debug_session_log_messages.clone();
     let (resolved_edge_id, command) = {
         let mut guard = state.inner.write().await;
         let edge_id = guard.resolve_or_create_edge_id(&request.edge_id);
@@ -411,9 +413,11 @@ pub(super) async fn rpc_poll_handler(
                 session.phase.clone(),
                 session.request_id.clone(),
                 session.current_line,
+                session.current_source.clone(),
                 session.attached_unix_ms,
                 session.last_resume_command_unix_ms,
                 session.message.clone(),
+                session.log_messages.clone(),
             );
             if matches!(
                 session.phase,
@@ -434,9 +438,13 @@ pub(super) async fn rpc_poll_handler(
             if let Some(request_id) = debug_session_request_id.clone() {
                 session.request_id = Some(request_id);
             }
+            if debug_session_active {
+                session.log_messages = debug_session_log_messages.clone();
+            }
             if !debug_session_active {
                 session.phase = DebugSessionPhase::Stopped;
                 session.current_line = None;
+                session.current_source = None;
                 session.last_resume_command_unix_ms = None;
                 session.updated_unix_ms = now_unix_ms();
                 session.message = Some("debug session is no longer active on edge".to_string());
@@ -450,6 +458,7 @@ pub(super) async fn rpc_poll_handler(
                 }
                 if let Some(line) = debug_session_current_line {
                     session.current_line = Some(line);
+                    session.current_source = debug_session_current_source.clone();
                 }
                 session.updated_unix_ms = now_unix_ms();
                 session.message = Some("debugger attached".to_string());
@@ -463,6 +472,7 @@ pub(super) async fn rpc_poll_handler(
                 }
                 session.phase = DebugSessionPhase::WaitingForAttach;
                 session.current_line = None;
+                session.current_source = None;
                 session.updated_unix_ms = now;
             }
             let after = (
@@ -470,9 +480,11 @@ pub(super) async fn rpc_poll_handler(
                 session.phase.clone(),
                 session.request_id.clone(),
                 session.current_line,
+                session.current_source.clone(),
                 session.attached_unix_ms,
                 session.last_resume_command_unix_ms,
                 session.message.clone(),
+                session.log_messages.clone(),
             );
             if after != before {
                 debug_sessions_changed = true;
@@ -738,7 +750,7 @@ pub(super) async fn list_debug_sessions_handler(
             .map(DebugSessionRecord::to_summary)
             .collect::<Vec<_>>()
     };
-    sessions.sort_by(|lhs, rhs| rhs.updated_unix_ms.cmp(&lhs.updated_unix_ms));
+    sessions.sort_by_key(|session| std::cmp::Reverse(session.updated_unix_ms));
     Json(DebugSessionListResponse { sessions })
 }
 
@@ -767,6 +779,15 @@ pub(super) async fn create_debug_session_handler(
         .map(str::trim)
         .filter(|value| !value.is_empty())
         .map(str::to_string);
+    let dap_addr = request
+        .dap_addr
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string);
+    if tcp_addr.is_some() && dap_addr.is_some() {
+        return Err(bad_request("tcp_addr and dap_addr cannot both be set"));
+    }
     let request_path = request
         .request_path
         .as_deref()
@@ -809,6 +830,7 @@ pub(super) async fn create_debug_session_handler(
         let (source_flavor, source_code) = resolve_edge_debug_source(&guard, &resolved_edge_id);
         (resolved_edge_id, edge_name, source_flavor, source_code)
     };
+    let source_files = resolve_debug_source_files(source_flavor.as_deref(), source_code.as_deref());
 
     let now = now_unix_ms();
     let command_id = state.next_command_id();
@@ -828,6 +850,11 @@ pub(super) async fn create_debug_session_handler(
         } else {
             None
         },
+        dap_addr: if mode == DebugSessionMode::Interactive {
+            dap_addr
+        } else {
+            None
+        },
         header_name: if mode == DebugSessionMode::Interactive {
             requested_header_name.clone()
         } else {
@@ -837,6 +864,7 @@ pub(super) async fn create_debug_session_handler(
         mode: mode.clone(),
         request_path: request_path.clone(),
         record_count: Some(record_count),
+        source_flavor: source_flavor.clone(),
     };
     let _queued = state
         .enqueue_command(resolved_edge_id.clone(), command)
@@ -864,8 +892,10 @@ pub(super) async fn create_debug_session_handler(
         start_command_id: command_id.clone(),
         stop_command_id: None,
         current_line: None,
+        current_source: None,
         source_flavor,
         source_code,
+        source_files,
         breakpoints: HashSet::new(),
         created_unix_ms: now,
         updated_unix_ms: now,
@@ -876,6 +906,7 @@ pub(super) async fn create_debug_session_handler(
             DebugSessionMode::Recording => "start-recording command queued".to_string(),
         }),
         last_output: None,
+        log_messages: Vec::new(),
         replay_states: HashMap::new(),
     };
 
@@ -912,6 +943,7 @@ pub(super) async fn stop_debug_session_handler(
             session.stop_command_id = Some(command_id);
             session.phase = DebugSessionPhase::Stopped;
             session.current_line = None;
+            session.current_source = None;
             session.last_resume_command_unix_ms = None;
             session.message = if session.mode == DebugSessionMode::Recording {
                 Some("recording session stop requested".to_string())
@@ -1010,7 +1042,17 @@ pub(super) async fn run_debug_command_handler(
                     "recording selection is only available for recording sessions",
                 ));
             }
-            DebugCommandRequest::BreakLine { line } => RemoteDebugCommand::BreakLine { line },
+            DebugCommandRequest::BreakLine {
+                line,
+                condition,
+                hit_count,
+                log_message,
+            } => RemoteDebugCommand::BreakLine {
+                line,
+                condition,
+                hit_count,
+                log_message,
+            },
             DebugCommandRequest::ClearLine { line } => RemoteDebugCommand::ClearLine { line },
             DebugCommandRequest::PrintVar { name } => {
                 if name.trim().is_empty() {
@@ -1020,6 +1062,21 @@ pub(super) async fn run_debug_command_handler(
                     name: name.trim().to_string(),
                 }
             }
+            DebugCommandRequest::Eval { expr, flavor } => {
+                if expr.trim().is_empty() {
+                    return Err(bad_request("expression cannot be empty"));
+                }
+                RemoteDebugCommand::Eval { expr, flavor }
+            }
+            DebugCommandRequest::SetVar { name, expr } => {
+                if name.trim().is_empty() || expr.trim().is_empty() {
+                    return Err(bad_request("variable name and expression cannot be empty"));
+                }
+                RemoteDebugCommand::SetVar {
+                    name: name.trim().to_string(),
+                    expr,
+                }
+            }
             DebugCommandRequest::Locals => RemoteDebugCommand::Locals,
             DebugCommandRequest::Stack => RemoteDebugCommand::Stack,
         };
@@ -1060,15 +1117,11 @@ pub(super) async fn run_debug_command_handler(
             let mut sessions = state.debug_sessions.write().await;
             if let Some(session) = sessions.get_mut(&session_id) {
                 match request_for_state {
-                    DebugCommandRequest::BreakLine { line } => {
-                        if session.breakpoints.insert(line) {
-                            debug_sessions_changed = true;
-                        }
+                    DebugCommandRequest::BreakLine { line, .. } => {
+                        debug_sessions_changed |= session.breakpoints.insert(line);
                     }
                     DebugCommandRequest::ClearLine { line } => {
-                        if session.breakpoints.remove(&line) {
-                            debug_sessions_changed = true;
-                        }
+                        debug_sessions_changed |= session.breakpoints.remove(&line);
                     }
                     _ => {}
                 }
@@ -1123,7 +1176,19 @@ pub(super) async fn run_debug_command_handler(
         DebugCommandRequest::Next => "next".to_string(),
         DebugCommandRequest::Continue => "continue".to_string(),
         DebugCommandRequest::Out => "out".to_string(),
-        DebugCommandRequest::BreakLine { line } => format!("break line {line}"),
+        DebugCommandRequest::BreakLine {
+            line,
+            condition,
+            hit_count,
+            log_message,
+        } => {
+            if condition.is_some() || hit_count.is_some() || log_message.is_some() {
+                return Err(bad_request(
+                    "conditions, hit counts and log messages need an interactive session",
+                ));
+            }
+            format!("break line {line}")
+        }
         DebugCommandRequest::ClearLine { line } => format!("clear line {line}"),
         DebugCommandRequest::PrintVar { name } => {
             if name.trim().is_empty() {
@@ -1131,6 +1196,11 @@ pub(super) async fn run_debug_command_handler(
             }
             format!("print {}", name.trim())
         }
+        DebugCommandRequest::Eval { .. } | DebugCommandRequest::SetVar { .. } => {
+            return Err(bad_request(
+                "evaluating expressions needs an interactive session",
+            ));
+        }
         DebugCommandRequest::Locals => "locals".to_string(),
         DebugCommandRequest::Stack => "stack".to_string(),
     };
@@ -1164,7 +1234,7 @@ pub(super) async fn run_debug_command_handler(
         let replay = run_recording_replay_command(&recording, replay_state, &command_text);
 
         match request_for_state {
-            DebugCommandRequest::BreakLine { line } => {
+            DebugCommandRequest::BreakLine { line, .. } => {
                 session.breakpoints.insert(line);
             }
             DebugCommandRequest::ClearLine { line } => {
@@ -1175,6 +1245,7 @@ pub(super) async fn run_debug_command_handler(
 
         session.phase = DebugSessionPhase::ReplayReady;
         session.current_line = replay.current_line;
+        session.current_source = replay.current_source.clone();
         session.selected_recording_id = Some(target_recording_id);
         session.request_id = stored_recording.request_id.clone();
         session.updated_unix_ms = now_unix_ms();
@@ -1188,6 +1259,7 @@ pub(super) async fn run_debug_command_handler(
             phase: session.phase.clone(),
             output: replay.output,
             current_line: replay.current_line,
+            current_source: replay.current_source,
             attached: !replay.exited,
         }
     };
@@ -1319,6 +1391,15 @@ pub(super) async fn enqueue_start_debug_handler(
         .map(str::trim)
         .filter(|value| !value.is_empty())
         .map(str::to_string);
+    let dap_addr = request
+        .dap_addr
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string);
+    if tcp_addr.is_some() && dap_addr.is_some() {
+        return Err(bad_request("tcp_addr and dap_addr cannot both be set"));
+    }
     let request_path = request
         .request_path
         .as_deref()
@@ -1334,6 +1415,12 @@ pub(super) async fn enqueue_start_debug_handler(
     if mode == DebugSessionMode::Recording && record_count == 0 {
         return Err(bad_request("record_count must be >= 1"));
     }
+    let source_flavor = {
+        let guard = state.inner.read().await;
+        guard
+            .resolve_edge_id(&edge_id)
+            .and_then(|resolved| resolve_edge_debug_source(&guard, &resolved).0)
+    };
     let session_id = Uuid::new_v4().to_string();
     let command = ControlPlaneCommand::StartDebugSession {
         command_id: request
@@ -1345,6 +1432,11 @@ pub(super) async fn enqueue_start_debug_handler(
         } else {
             None
         },
+        dap_addr: if mode == DebugSessionMode::Interactive {
+            dap_addr
+        } else {
+            None
+        },
         header_name: if mode == DebugSessionMode::Interactive {
             request.header_name
         } else {
@@ -1354,6 +1446,7 @@ pub(super) async fn enqueue_start_debug_handler(
         mode,
         request_path,
         record_count: Some(record_count),
+        source_flavor,
     };
     let queued = state.enqueue_command(edge_id, command).await;
     Ok((StatusCode::ACCEPTED, Json(queued)))
@@ -1495,6 +1588,8 @@ async fn process_debug_session_result(
                         session.tcp_addr = addr;
                     }
                     session.current_line = reported_status.and_then(|item| item.current_line);
+                    session.current_source =
+                        reported_status.and_then(|item| item.current_source.clone());
                     if session.mode == DebugSessionMode::Interactive {
                         session.message = Some(
                             "debug session active on edge; waiting for a matching request to attach"
@@ -1560,6 +1655,7 @@ async fn process_debug_session_result(
                                 session.last_resume_command_unix_ms = None;
                                 if let Some(line) = remote.current_line {
                                     session.current_line = Some(line);
+                                    session.current_source = remote.current_source.clone();
                                 }
                                 session.message = Some("debugger attached".to_string());
                             } else {
@@ -1576,6 +1672,7 @@ async fn process_debug_session_result(
                                 phase: session.phase.clone(),
                                 output: remote.output.clone(),
                                 current_line: session.current_line,
+                                current_source: session.current_source.clone(),
                                 attached: remote.attached,
                             });
                         }
@@ -1658,13 +1755,14 @@ async fn process_debug_session_result(
                 items.sort_by_key(|item| item.sequence);
             }
 
-            let initial_line = STANDARD
+            let initial_location = STANDARD
                 .decode(recording_base64.as_bytes())
                 .ok()
                 .and_then(|bytes| VmRecording::decode(&bytes).ok())
-                .and_then(|recording| {
+                .map(|recording| {
                     let mut state = VmRecordingReplayState::default();
-                    run_recording_replay_command(&recording, &mut state, "where").current_line
+                    let replay = run_recording_replay_command(&recording, &mut state, "where");
+                    (replay.current_line, replay.current_source)
                 });
 
             let mut stop_command_to_queue: Option<(String, String)> = None;
@@ -1713,8 +1811,11 @@ async fn process_debug_session_result(
                     .replay_states
                     .insert(recording_id.clone(), VmRecordingReplayState::default());
             }
-            if session.current_line.is_none() {
-                session.current_line = initial_line;
+            if session.current_line.is_none()
+                && let Some((line, source)) = initial_location
+            {
+                session.current_line = line;
+                session.current_source = source;
             }
             if *completed {
                 session.phase = DebugSessionPhase::Stopped;
@@ -3888,6 +3888,9 @@ pub(super) fn source_for_flavor(bundle: &UiSourceBundle, flavor: SourceFlavor) -
         SourceFlavor::JavaScript => bundle.javascript.clone(),
         SourceFlavor::Lua => bundle.lua.clone(),
         SourceFlavor::Scheme => bundle.scheme.clone(),
+        // Blocks render to the high-level flavors only, and `parse_ui_flavor`
+        // never selects assembly.
+        SourceFlavor::Assembly => String::new(),
     }
 }
 
@@ -42,7 +42,9 @@ fn empty_telemetry() -> TelemetrySnapshot {
         debug_session_active: false,
         debug_session_attached: false,
         debug_session_current_line: None,
+        debug_session_current_source: None,
         debug_session_request_id: None,
+        debug_session_log_messages: Vec::new(),
         data_requests_total: 0,
         vm_execution_errors_total: 0,
         program_apply_success_total: 0,
@@ -11,7 +11,13 @@ import {
   Square
 } from "lucide-react";
 
-import { debugPhaseClasses, debugPhaseLabel, formatUnixMs, monacoLanguageForFlavor } from "@/app/helpers";
+import {
+  debugPhaseClasses,
+  debugPhaseLabel,
+  debugSessionViewSource,
+  formatUnixMs,
+  monacoLanguageForFlavor
+} from "@/app/helpers";
 import { ensureRustScriptLanguage } from "@/app/monaco/rustscriptLanguage";
 import { RowActionMenu } from "@/app/components/RowActionMenu";
 import type {
@@ -87,6 +93,7 @@ export function DebugSessionsView({
   debugHoverValue,
   onSelectRecording
 }: DebugSessionsViewProps) {
+  const viewSource = debugSessionViewSource(selectedDebugSession);
   let activeCount = 0;
   let waitingCount = 0;
   let stoppedCount = 0;
@@ -494,6 +501,33 @@ export function DebugSessionsView({
                 </div>
               ) : null}
 
+              {selectedDebugSession.phase === "attached" ? (
+                <div className="space-y-1">
+                  <Label htmlFor="debug-eval">Evaluate</Label>
+                  <Input
+                    id="debug-eval"
+                    className="font-mono text-xs"
+                    placeholder="total + 1, or total = 0 to change a local"
+                    disabled={debugCommandLoading}
+                    onKeyDown={(event) => {
+                      if (event.key !== "Enter") {
+                        return;
+                      }
+                      const text = event.currentTarget.value.trim();
+                      if (!text) {
+                        return;
+                      }
+                      const assignment = /^([A-Za-z_][A-Za-z0-9_]*)\s*=(?!=)\s*(.+)$/.exec(text);
+                      void runDebugCommand(
+                        assignment
+                          ? { kind: "set_var", name: assignment[1], expr: assignment[2] }
+                          : { kind: "eval", expr: text }
+                      );
+                    }}
+                  />
+                </div>
+              ) : null}
+
               {selectedDebugSession.last_output ? (
                 <div className="rounded-md border bg-background/70 p-2">
                   <div className="mb-1 text-[11px] uppercase tracking-wide text-muted-foreground">Last Debugger Output</div>
@@ -501,14 +535,26 @@ export function DebugSessionsView({
                 </div>
               ) : null}
 
-              {selectedDebugSession.source_code ? (
+              {selectedDebugSession.log_messages?.length ? (
+                <div className="rounded-md border bg-background/70 p-2">
+                  <div className="mb-1 text-[11px] uppercase tracking-wide text-muted-foreground">Logpoint Output</div>
+                  <pre className="max-h-[180px] overflow-auto whitespace-pre-wrap text-xs">{selectedDebugSession.log_messages.join("\n")}</pre>
+                </div>
+              ) : null}
+
+              {viewSource.text ? (
                 <div className="rounded-md border bg-slate-950 text-slate-100">
+                  {viewSource.name ? (
+                    <div className="border-b border-slate-800 px-3 py-1 font-mono text-[11px] text-slate-300">
+                      {viewSource.name}
+                    </div>
+                  ) : null}
                   <div className="h-[68vh]">
                     <Editor
                       beforeMount={ensureRustScriptLanguage}
                       onMount={onDebugEditorMount}
                       language={monacoLanguageForFlavor(selectedDebugSession.source_flavor)}
-                      value={selectedDebugSession.source_code}
+                      value={viewSource.text}
                       theme="vs"
                       options={{
                         readOnly: true,
@@ -547,4 +593,4 @@ export function DebugSessionsView({
       </Card>
     </div>
   );
-}
+}
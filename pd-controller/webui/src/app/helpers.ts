@@ -1,4 +1,5 @@
 import type {
+  DebugSessionDetail,
   DebugSessionMode,
   DebugSessionPhase,
   EdgeSummary,
@@ -250,6 +251,24 @@ export function monacoLanguageForFlavor(flavor: SourceFlavor | string | null | u
   return "rustscript";
 }
 
+// Stops inside an imported module report that module's name; show its text
+// instead of the session's main source when the controller has it.
+export function debugSessionViewSource(session: DebugSessionDetail | null): {
+  name: string | null;
+  text: string | null;
+} {
+  if (!session) {
+    return { name: null, text: null };
+  }
+  const module = session.current_source
+    ? (session.source_files ?? []).find((file) => file.name === session.current_source)
+    : undefined;
+  if (module) {
+    return { name: module.name, text: module.text };
+  }
+  return { name: null, text: session.source_code };
+}
+
 export type LineSeries = {
   key: string;
   stroke: string;
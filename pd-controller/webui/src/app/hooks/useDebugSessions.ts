@@ -2,7 +2,7 @@ import { type OnMount } from "@monaco-editor/react";
 import { useCallback, useEffect, useMemo, useRef, useState } from "react";
 import type * as Monaco from "monaco-editor";
 
-import { looksLikeIdentifier } from "@/app/helpers";
+import { debugSessionViewSource, looksLikeIdentifier } from "@/app/helpers";
 import { ensureRustScriptLanguage } from "@/app/monaco/rustscriptLanguage";
 import type {
   DebugCommandRequest,
@@ -252,6 +252,9 @@ export function useDebugSessions({ onError, edgeSummaries, showDebugSessionsSect
           throw new Error(await response.text());
         }
         const result = (await response.json()) as DebugCommandResponse;
+        if (request.kind === "set_var") {
+          debugHoverCacheRef.current.clear();
+        }
         if (refresh) {
           await Promise.all([loadDebugSessions(), loadDebugSessionDetail(selectedDebugSessionId)]);
         }
@@ -526,7 +529,8 @@ export function useDebugSessions({ onError, edgeSummaries, showDebugSessionsSect
   useEffect(() => {
     const editor = debugEditorRef.current;
     const monaco = debugMonacoRef.current;
-    if (!editor || !monaco || !selectedDebugSession?.source_code) {
+    const viewSource = debugSessionViewSource(selectedDebugSession);
+    if (!editor || !monaco || !selectedDebugSession || !viewSource.text) {
       if (editor) {
         debugDecorationIdsRef.current = editor.deltaDecorations(debugDecorationIdsRef.current, []);
       }
@@ -545,7 +549,9 @@ export function useDebugSessions({ onError, edgeSummaries, showDebugSessionsSect
         }
       });
     }
-    for (const line of selectedDebugSession.breakpoints) {
+    // Line breakpoints are set against the main source only.
+    const breakpoints = viewSource.name ? [] : selectedDebugSession.breakpoints;
+    for (const line of breakpoints) {
       decorations.push({
         range: new monaco.Range(line, 1, line, 1),
         options: {
@@ -556,7 +562,7 @@ export function useDebugSessions({ onError, edgeSummaries, showDebugSessionsSect
       });
     }
     debugDecorationIdsRef.current = editor.deltaDecorations(debugDecorationIdsRef.current, decorations);
-  }, [debugEditorReadyTick, selectedDebugSession?.source_code, selectedDebugSession?.current_line, selectedDebugSession?.breakpoints]);
+  }, [debugEditorReadyTick, selectedDebugSession]);
 
   useEffect(() => {
     debugHoverCacheRef.current.clear();
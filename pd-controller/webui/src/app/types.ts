@@ -142,6 +142,7 @@ export type TelemetrySnapshot = {
   debug_session_active: boolean;
   debug_session_attached: boolean;
   debug_session_current_line: number | null;
+  debug_session_current_source?: string | null;
   debug_session_request_id: string | null;
   data_requests_total: number;
   vm_execution_errors_total: number;
@@ -217,11 +218,17 @@ export type DebugSessionSummary = {
   recording_target_count: number | null;
   recording_count: number;
   current_line: number | null;
+  current_source: string | null;
   created_unix_ms: number;
   updated_unix_ms: number;
   message: string | null;
 };
 
+export type DebugSourceFile = {
+  name: string;
+  text: string;
+};
+
 export type DebugSessionDetail = {
   session_id: string;
   edge_id: string;
@@ -239,14 +246,17 @@ export type DebugSessionDetail = {
   start_command_id: string;
   stop_command_id: string | null;
   current_line: number | null;
+  current_source: string | null;
   source_flavor: string | null;
   source_code: string | null;
+  source_files: DebugSourceFile[];
   breakpoints: number[];
   created_unix_ms: number;
   updated_unix_ms: number;
   attached_unix_ms: number | null;
   message: string | null;
   last_output: string | null;
+  log_messages: string[];
 };
 
 export type DebugSessionListResponse = {
@@ -263,6 +273,7 @@ export type DebugCommandResponse = {
   phase: DebugSessionPhase;
   output: string;
   current_line: number | null;
+  current_source: string | null;
   attached: boolean;
 };
 
@@ -273,9 +284,11 @@ export type DebugCommandRequest =
   | { kind: "continue" }
   | { kind: "out" }
   | { kind: "select_recording"; recording_id: string }
-  | { kind: "break_line"; line: number }
+  | { kind: "break_line"; line: number; condition?: string; hit_count?: number; log_message?: string }
   | { kind: "clear_line"; line: number }
   | { kind: "print_var"; name: string }
+  | { kind: "eval"; expr: string; flavor?: string }
+  | { kind: "set_var"; name: string; expr: string }
   | { kind: "locals" }
   | { kind: "stack" };
 
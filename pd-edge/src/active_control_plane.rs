@@ -182,11 +182,13 @@ async fn execute_command(
         ControlPlaneCommand::StartDebugSession {
             session_id,
             tcp_addr,
+            dap_addr,
             header_name,
             stop_on_entry,
             mode,
             request_path,
             record_count,
+            source_flavor,
             ..
         } => {
             let header_name = header_name.unwrap_or_else(|| DEFAULT_DEBUG_NONCE_HEADER.to_string());
@@ -205,10 +207,12 @@ async fn execute_command(
                 header_name: request_header_name,
                 header_value: nonce.clone(),
                 tcp_addr,
+                dap_addr,
                 stop_on_entry: stop_on_entry.unwrap_or(true),
                 mode: mode.clone(),
                 request_path,
                 record_count: record_count.unwrap_or(1),
+                source_flavor,
             };
             match start_debug_session(&state.debug_session, request) {
                 Ok(status) => CommandResultPayload::StartDebugSession {
@@ -242,6 +246,7 @@ async fn execute_command(
                 response: Some(RemoteDebugCommandResponse {
                     output: response.output,
                     current_line: response.current_line,
+                    current_source: response.current_source,
                     attached: response.attached,
                 }),
                 message: None,
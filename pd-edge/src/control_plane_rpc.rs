@@ -67,6 +67,8 @@ pub enum ControlPlaneCommand {
         #[serde(default)]
         tcp_addr: Option<String>,
         #[serde(default)]
+        dap_addr: Option<String>,
+        #[serde(default)]
         header_name: Option<String>,
         #[serde(default)]
         stop_on_entry: Option<bool>,
@@ -76,6 +78,8 @@ pub enum ControlPlaneCommand {
         request_path: Option<String>,
         #[serde(default)]
         record_count: Option<u32>,
+        #[serde(default)]
+        source_flavor: Option<String>,
     },
     DebugCommand {
         command_id: String,
@@ -123,9 +127,30 @@ pub enum RemoteDebugCommand {
     Next,
     Continue,
     Out,
-    BreakLine { line: u32 },
-    ClearLine { line: u32 },
-    PrintVar { name: String },
+    BreakLine {
+        line: u32,
+        #[serde(default)]
+        condition: Option<String>,
+        #[serde(default)]
+        hit_count: Option<u32>,
+        #[serde(default)]
+        log_message: Option<String>,
+    },
+    ClearLine {
+        line: u32,
+    },
+    PrintVar {
+        name: String,
+    },
+    Eval {
+        expr: String,
+        #[serde(default)]
+        flavor: Option<String>,
+    },
+    SetVar {
+        name: String,
+        expr: String,
+    },
     Locals,
     Stack,
 }
@@ -134,6 +159,8 @@ pub enum RemoteDebugCommand {
 pub struct RemoteDebugCommandResponse {
     pub output: String,
     pub current_line: Option<u32>,
+    #[serde(default)]
+    pub current_source: Option<String>,
     pub attached: bool,
 }
 
@@ -8,7 +8,10 @@ use axum::http::{HeaderMap, HeaderName, StatusCode};
 use base64::{Engine as _, engine::general_purpose::STANDARD};
 use serde::{Deserialize, Serialize};
 use tracing::{info, warn};
-use vm::{DebugCommandBridge, DebugCommandBridgeError, Debugger, Vm, VmResult, VmStatus};
+use vm::{
+    DapHandle, DapSession, DebugCommandBridge, DebugCommandBridgeError, Debugger, SourceFlavor, Vm,
+    VmResult, VmStatus,
+};
 
 use crate::{
     control_plane_rpc::{DebugSessionMode, RemoteDebugCommand, RemoteDebugCommandResponse},
@@ -33,6 +36,10 @@ pub struct StartDebugSessionRequest {
     pub header_value: Option<String>,
     #[serde(default)]
     pub tcp_addr: Option<String>,
+    /// Address of a Debug Adapter Protocol server an editor attaches to,
+    /// instead of the remote command bridge.
+    #[serde(default)]
+    pub dap_addr: Option<String>,
     #[serde(default = "default_stop_on_entry")]
     pub stop_on_entry: bool,
     #[serde(default)]
@@ -41,6 +48,10 @@ pub struct StartDebugSessionRequest {
     pub request_path: Option<String>,
     #[serde(default = "default_record_count")]
     pub record_count: u32,
+    /// Flavor the program was written in, which breakpoint conditions and
+    /// log messages are compiled as.
+    #[serde(default)]
+    pub source_flavor: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -49,16 +60,23 @@ pub struct DebugSessionStatus {
     pub attached: bool,
     pub current_line: Option<u32>,
     #[serde(default)]
+    pub current_source: Option<String>,
+    #[serde(default)]
     pub request_id: Option<String>,
     pub header_name: Option<String>,
     pub header_value: Option<String>,
     pub tcp_addr: Option<String>,
+    #[serde(default)]
+    pub dap_addr: Option<String>,
     pub stop_on_entry: Option<bool>,
     pub mode: Option<DebugSessionMode>,
     pub request_path: Option<String>,
     pub target_recordings: Option<u32>,
     pub captured_recordings: Option<u32>,
     pub completed: Option<bool>,
+    /// Most recent logpoint messages, oldest first.
+    #[serde(default)]
+    pub log_messages: Vec<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -129,7 +147,7 @@ impl std::fmt::Display for DebugSessionError {
             DebugSessionError::RemoteCommandsUnavailable => {
                 write!(
                     f,
-                    "remote debug commands are unavailable for tcp debugger sessions"
+                    "remote debug commands are unavailable for tcp and dap debugger sessions"
                 )
             }
             DebugSessionError::CommandTimeout => {
@@ -206,6 +224,11 @@ pub fn start_debug_session(
                 );
                 DebugSessionError::InvalidHeaderName
             })?;
+            if request.tcp_addr.is_some() && request.dap_addr.is_some() {
+                return Err(DebugSessionError::InvalidTcpAddress(
+                    "tcp_addr and dap_addr cannot both be set".to_string(),
+                ));
+            }
             let (mut debugger, transport) = if let Some(addr) = request.tcp_addr.as_deref() {
                 match Debugger::with_tcp(addr) {
                     Ok(debugger) => (
@@ -220,6 +243,28 @@ pub fn start_debug_session(
                         )));
                     }
                 }
+            } else if let Some(addr) = request.dap_addr.as_deref() {
+                let session = DapSession::bind(addr).map_err(|err| {
+                    DebugSessionError::InvalidTcpAddress(format!(
+                        "failed to bind dap server on {addr}: {err}"
+                    ))
+                })?;
+                let bound = session
+                    .local_addr()
+                    .map(|addr| addr.to_string())
+                    .unwrap_or_else(|_| addr.to_string());
+                let handle = session.handle().map_err(|err| {
+                    DebugSessionError::InvalidTcpAddress(format!(
+                        "failed to bind dap server on {addr}: {err}"
+                    ))
+                })?;
+                (
+                    Debugger::with_dap(session),
+                    InteractiveTransport::Dap {
+                        addr: bound,
+                        handle,
+                    },
+                )
             } else {
                 let bridge = DebugCommandBridge::new();
                 (
@@ -230,6 +275,17 @@ pub fn start_debug_session(
             if request.stop_on_entry {
                 debugger.stop_on_entry();
             }
+            if let Some(name) = request.source_flavor.as_deref() {
+                match SourceFlavor::from_name(name)
+                    .filter(|flavor| *flavor != SourceFlavor::Assembly)
+                {
+                    Some(flavor) => debugger.set_source_flavor(flavor),
+                    None => warn!(
+                        "{} unknown source flavor '{name}'; breakpoint conditions use the default",
+                        category_debug()
+                    ),
+                }
+            }
             (
                 Some(header_name),
                 Some(header_value),
@@ -316,12 +372,14 @@ pub fn run_debug_command(
         return Ok(RemoteDebugCommandResponse {
             output: format!("sent '{command_text}'"),
             current_line: None,
+            current_source: None,
             attached: false,
         });
     }
     Ok(RemoteDebugCommandResponse {
         output: response.output,
         current_line: response.current_line,
+        current_source: response.current_source,
         attached: response.attached,
     })
 }
@@ -522,6 +580,7 @@ struct DebugSession {
 enum InteractiveTransport {
     Remote { bridge: DebugCommandBridge },
     Tcp { addr: String },
+    Dap { addr: String, handle: DapHandle },
 }
 
 enum DebugSessionState {
@@ -549,7 +608,30 @@ fn debug_command_text(command: &RemoteDebugCommand) -> Result<(String, bool), De
         RemoteDebugCommand::Next => Ok(("next".to_string(), true)),
         RemoteDebugCommand::Continue => Ok(("continue".to_string(), true)),
         RemoteDebugCommand::Out => Ok(("out".to_string(), true)),
-        RemoteDebugCommand::BreakLine { line } => Ok((format!("break line {line}"), false)),
+        RemoteDebugCommand::BreakLine {
+            line,
+            condition,
+            hit_count,
+            log_message,
+        } => {
+            let mut text = format!("break line {line}");
+            if let Some(count) = hit_count {
+                if *count == 0 {
+                    return Err(DebugSessionError::InvalidCommand(
+                        "hit_count must be >= 1".to_string(),
+                    ));
+                }
+                text.push_str(&format!(" hits {count}"));
+            }
+            if let Some(condition) = non_empty_option(condition.as_deref())? {
+                // Braces keep a local named `log` inside the condition.
+                text.push_str(&format!(" if {{{condition}}}"));
+            }
+            if let Some(message) = non_empty_option(log_message.as_deref())? {
+                text.push_str(&format!(" log {message}"));
+            }
+            Ok((text, false))
+        }
         RemoteDebugCommand::ClearLine { line } => Ok((format!("clear line {line}"), false)),
         RemoteDebugCommand::PrintVar { name } => {
             if name.trim().is_empty() {
@@ -559,44 +641,122 @@ fn debug_command_text(command: &RemoteDebugCommand) -> Result<(String, bool), De
             }
             Ok((format!("print {}", name.trim()), false))
         }
+        RemoteDebugCommand::Eval { expr, flavor } => {
+            let expr = expression_text(expr)?;
+            match flavor
+                .as_deref()
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+            {
+                Some(name) => {
+                    if SourceFlavor::from_name(name)
+                        .filter(|flavor| *flavor != SourceFlavor::Assembly)
+                        .is_none()
+                    {
+                        return Err(DebugSessionError::InvalidCommand(format!(
+                            "unsupported expression flavor '{name}'"
+                        )));
+                    }
+                    Ok((format!("eval --flavor {name} {expr}"), false))
+                }
+                None => Ok((format!("eval {expr}"), false)),
+            }
+        }
+        RemoteDebugCommand::SetVar { name, expr } => {
+            let name = name.trim();
+            if name.is_empty() || name.contains(|ch: char| ch.is_whitespace() || ch == '=') {
+                return Err(DebugSessionError::InvalidCommand(
+                    "variable name must be a single identifier".to_string(),
+                ));
+            }
+            Ok((format!("set {name} = {}", expression_text(expr)?), false))
+        }
         RemoteDebugCommand::Locals => Ok(("locals".to_string(), false)),
         RemoteDebugCommand::Stack => Ok(("stack".to_string(), false)),
     }
 }
 
+/// Trims a breakpoint option, treating blank as absent. Commands are sent
+/// to the debugger as one line, so options cannot span lines.
+fn non_empty_option(value: Option<&str>) -> Result<Option<&str>, DebugSessionError> {
+    let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) else {
+        return Ok(None);
+    };
+    if value.contains(['\n', '\r']) {
+        return Err(DebugSessionError::InvalidCommand(
+            "breakpoint options cannot contain line breaks".to_string(),
+        ));
+    }
+    Ok(Some(value))
+}
+
+/// Trims an `eval`/`set` expression, which must be non-empty and fit on the
+/// single line sent to the debugger.
+fn expression_text(expr: &str) -> Result<&str, DebugSessionError> {
+    let expr = expr.trim();
+    if expr.is_empty() {
+        return Err(DebugSessionError::InvalidCommand(
+            "expression cannot be empty".to_string(),
+        ));
+    }
+    if expr.contains(['\n', '\r']) {
+        return Err(DebugSessionError::InvalidCommand(
+            "expressions cannot contain line breaks".to_string(),
+        ));
+    }
+    Ok(expr)
+}
+
 impl DebugSessionStatus {
     fn inactive() -> Self {
         Self {
             active: false,
             attached: false,
             current_line: None,
+            current_source: None,
             request_id: None,
             header_name: None,
             header_value: None,
             tcp_addr: None,
+            dap_addr: None,
             stop_on_entry: None,
             mode: None,
             request_path: None,
             target_recordings: None,
             captured_recordings: None,
             completed: None,
+            log_messages: Vec::new(),
         }
     }
 
     fn from_session(session: &DebugSession) -> Self {
         match &session.state {
             DebugSessionState::Interactive { transport, .. } => {
-                let (attached, current_line, tcp_addr) = match transport {
-                    InteractiveTransport::Remote { bridge } => {
-                        let bridge_status = bridge.status();
-                        (bridge_status.attached, bridge_status.current_line, None)
-                    }
-                    InteractiveTransport::Tcp { addr } => (false, None, Some(addr.clone())),
-                };
+                let (attached, current_line, current_source, tcp_addr, dap_addr, log_messages) =
+                    match transport {
+                        InteractiveTransport::Remote { bridge } => {
+                            let bridge_status = bridge.status();
+                            (
+                                bridge_status.attached,
+                                bridge_status.current_line,
+                                bridge_status.current_source,
+                                None,
+                                None,
+                                bridge_status.log_messages,
+                            )
+                        }
+                        InteractiveTransport::Tcp { addr } => {
+                            (false, None, None, Some(addr.clone()), None, Vec::new())
+                        }
+                        InteractiveTransport::Dap { addr, .. } => {
+                            (false, None, None, None, Some(addr.clone()), Vec::new())
+                        }
+                    };
                 Self {
                     active: true,
                     attached,
                     current_line,
+                    current_source,
                     request_id: session.request_id(),
                     header_name: session
                         .header_name
@@ -604,12 +764,14 @@ impl DebugSessionStatus {
                         .map(|value| value.as_str().to_string()),
                     header_value: session.header_value.clone(),
                     tcp_addr,
+                    dap_addr,
                     stop_on_entry: Some(session.stop_on_entry),
                     mode: Some(session.mode.clone()),
                     request_path: session.request_path.clone(),
                     target_recordings: None,
                     captured_recordings: None,
                     completed: None,
+                    log_messages,
                 }
             }
             DebugSessionState::Recording { runtime } => {
@@ -618,6 +780,7 @@ impl DebugSessionStatus {
                     active: true,
                     attached: false,
                     current_line: None,
+                    current_source: None,
                     request_id: session.request_id(),
                     header_name: session
                         .header_name
@@ -625,12 +788,14 @@ impl DebugSessionStatus {
                         .map(|value| value.as_str().to_string()),
                     header_value: session.header_value.clone(),
                     tcp_addr: None,
+                    dap_addr: None,
                     stop_on_entry: Some(session.stop_on_entry),
                     mode: Some(session.mode.clone()),
                     request_path: session.request_path.clone(),
                     target_recordings: Some(runtime.target_count),
                     captured_recordings: Some(runtime.captured_count),
                     completed: Some(runtime.completed),
+                    log_messages: Vec::new(),
                 }
             }
         }
@@ -657,17 +822,19 @@ impl DebugSession {
         match &self.state {
             DebugSessionState::Interactive { transport, .. } => match transport {
                 InteractiveTransport::Remote { bridge } => Some(bridge),
-                InteractiveTransport::Tcp { .. } => None,
+                InteractiveTransport::Tcp { .. } | InteractiveTransport::Dap { .. } => None,
             },
             DebugSessionState::Recording { .. } => None,
         }
     }
 
     fn close(&self) {
-        if let DebugSessionState::Interactive { transport, .. } = &self.state
-            && let InteractiveTransport::Remote { bridge } = transport
-        {
-            bridge.close();
+        if let DebugSessionState::Interactive { transport, .. } = &self.state {
+            match transport {
+                InteractiveTransport::Remote { bridge } => bridge.close(),
+                InteractiveTransport::Dap { handle, .. } => handle.close(),
+                InteractiveTransport::Tcp { .. } => {}
+            }
         }
     }
 }
@@ -698,10 +865,12 @@ mod tests {
             header_name: Some("bad header".to_string()),
             header_value: Some("x".to_string()),
             tcp_addr: None,
+            dap_addr: None,
             stop_on_entry: true,
             mode: DebugSessionMode::Interactive,
             request_path: None,
             record_count: 1,
+            source_flavor: None,
         };
         let err = start_debug_session(&store, request).expect_err("request should be invalid");
         assert!(matches!(err, DebugSessionError::InvalidHeaderName));
@@ -715,12 +884,128 @@ mod tests {
             header_name: Some("x-debug".to_string()),
             header_value: Some("".to_string()),
             tcp_addr: None,
+            dap_addr: None,
             stop_on_entry: true,
             mode: DebugSessionMode::Interactive,
             request_path: None,
             record_count: 1,
+            source_flavor: None,
         };
         let err = start_debug_session(&store, request).expect_err("request should be invalid");
         assert!(matches!(err, DebugSessionError::EmptyHeaderValue));
     }
+
+    #[test]
+    fn tcp_and_dap_addresses_are_exclusive() {
+        let store = new_debug_session_store();
+        let request = StartDebugSessionRequest {
+            session_id: "test".to_string(),
+            header_name: Some("x-debug".to_string()),
+            header_value: Some("x".to_string()),
+            tcp_addr: Some("127.0.0.1:0".to_string()),
+            dap_addr: Some("127.0.0.1:0".to_string()),
+            stop_on_entry: true,
+            mode: DebugSessionMode::Interactive,
+            request_path: None,
+            record_count: 1,
+            source_flavor: None,
+        };
+        let err = start_debug_session(&store, request).expect_err("request should be invalid");
+        assert!(matches!(err, DebugSessionError::InvalidTcpAddress(_)));
+    }
+
+    #[test]
+    fn dap_sessions_report_their_address_and_reject_remote_commands() {
+        let store = new_debug_session_store();
+        let request = StartDebugSessionRequest {
+            session_id: "test".to_string(),
+            header_name: Some("x-debug".to_string()),
+            header_value: Some("x".to_string()),
+            tcp_addr: None,
+            dap_addr: Some("127.0.0.1:0".to_string()),
+            stop_on_entry: false,
+            mode: DebugSessionMode::Interactive,
+            request_path: None,
+            record_count: 1,
+            source_flavor: None,
+        };
+        let status = start_debug_session(&store, request).expect("dap session should start");
+        let addr = status
+            .dap_addr
+            .expect("status should carry the dap address");
+        assert!(!addr.ends_with(":0"), "expected the bound port, got {addr}");
+        assert!(status.tcp_addr.is_none());
+        let err = run_debug_command(&store, RemoteDebugCommand::Where)
+            .expect_err("dap sessions have no command bridge");
+        assert!(matches!(err, DebugSessionError::RemoteCommandsUnavailable));
+        assert!(stop_debug_session(&store));
+    }
+
+    #[test]
+    fn break_line_command_carries_breakpoint_options() {
+        let command = RemoteDebugCommand::BreakLine {
+            line: 7,
+            condition: Some(" count > 2 ".to_string()),
+            hit_count: Some(3),
+            log_message: Some("count={count}".to_string()),
+        };
+        let (text, resume) = debug_command_text(&command).expect("command should be valid");
+        assert_eq!(text, "break line 7 hits 3 if {count > 2} log count={count}");
+        assert!(!resume);
+
+        let multiline = RemoteDebugCommand::BreakLine {
+            line: 7,
+            condition: None,
+            hit_count: None,
+            log_message: Some("a\nb".to_string()),
+        };
+        assert!(matches!(
+            debug_command_text(&multiline),
+            Err(DebugSessionError::InvalidCommand(_))
+        ));
+    }
+
+    #[test]
+    fn eval_and_set_var_commands_stay_on_one_line() {
+        let eval = RemoteDebugCommand::Eval {
+            expr: " count + 1 ".to_string(),
+            flavor: Some("lua".to_string()),
+        };
+        assert_eq!(
+            debug_command_text(&eval).expect("eval should be valid"),
+            ("eval --flavor lua count + 1".to_string(), false)
+        );
+        let set = RemoteDebugCommand::SetVar {
+            name: "count".to_string(),
+            expr: "count * 2".to_string(),
+        };
+        assert_eq!(
+            debug_command_text(&set).expect("set should be valid"),
+            ("set count = count * 2".to_string(), false)
+        );
+
+        for invalid in [
+            RemoteDebugCommand::Eval {
+                expr: "a\nb".to_string(),
+                flavor: None,
+            },
+            RemoteDebugCommand::Eval {
+                expr: "1".to_string(),
+                flavor: Some("assembly".to_string()),
+            },
+            RemoteDebugCommand::SetVar {
+                name: "a b".to_string(),
+                expr: "1".to_string(),
+            },
+            RemoteDebugCommand::SetVar {
+                name: "count".to_string(),
+                expr: " ".to_string(),
+            },
+        ] {
+            assert!(matches!(
+                debug_command_text(&invalid),
+                Err(DebugSessionError::InvalidCommand(_))
+            ));
+        }
+    }
 }
@@ -77,7 +77,11 @@ pub struct TelemetrySnapshot {
     #[serde(default)]
     pub debug_session_current_line: Option<u32>,
     #[serde(default)]
+    pub debug_session_current_source: Option<String>,
+    #[serde(default)]
     pub debug_session_request_id: Option<String>,
+    #[serde(default)]
+    pub debug_session_log_messages: Vec<String>,
     pub data_requests_total: u64,
     pub vm_execution_errors_total: u64,
     pub program_apply_success_total: u64,
@@ -202,7 +206,9 @@ impl SharedState {
             debug_session_active: debug_status.active,
             debug_session_attached: debug_status.attached,
             debug_session_current_line: debug_status.current_line,
+            debug_session_current_source: debug_status.current_source,
             debug_session_request_id: debug_status.request_id,
+            debug_session_log_messages: debug_status.log_messages,
             data_requests_total: self
                 .runtime_metrics
                 .data_requests_total
@@ -532,9 +538,9 @@ async fn data_plane_handler(State(state): State<SharedState>, request: Request)
                     0,
                 );
             }
-            Err(VmExecutionError::Vm(err)) => {
+            Err(VmExecutionError::Vm(report)) => {
                 state.record_vm_execution_error();
-                warn!("{} vm execution error: {err}", category_program());
+                warn!("{} vm execution error\n{report}", category_program());
                 return finalize_data_plane_response(
                     &state,
                     started,
@@ -1115,7 +1121,8 @@ async fn access_log_middleware(request: Request, next: Next) -> Response<Body> {
 #[derive(Debug)]
 enum VmExecutionError {
     HostRegistration(vm::VmError),
-    Vm(vm::VmError),
+    /// Rendered error with the failing source line and logical call stack.
+    Vm(String),
     NotHalted(VmStatus),
     TaskJoin(tokio::task::JoinError),
 }
@@ -1151,7 +1158,7 @@ async fn execute_vm_for_request(
             &request_id,
             &mut vm,
         )
-        .map_err(VmExecutionError::Vm)?;
+        .map_err(|err| VmExecutionError::Vm(vm::render_vm_error(&vm, &err)))?;
         if status != VmStatus::Halted {
             return Err(VmExecutionError::NotHalted(status));
         }
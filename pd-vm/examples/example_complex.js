@@ -1,4 +1,4 @@
-import * as string from "../stdlib/rss/strings.rss";
+import * as string from "std/strings";
 import { add_one } from "vm";
 
 // Complex JavaScript flavor example: loop + stdlib + host + closure.
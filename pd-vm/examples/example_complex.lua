@@ -1,5 +1,5 @@
 local add_one = require("vm").add_one
-local string = require("../stdlib/rss/strings.rss")
+local string = require("std.strings")
 
 -- Complex Lua flavor example: loop + stdlib + host + closure + string method lowering.
 local total = 0
@@ -1,6 +1,6 @@
-(import (prefix "../stdlib/rss/strings.rss" string:))
-(import (prefix-in string2: "../stdlib/rss/strings.rss"))
-(import (only "../stdlib/rss/strings.rss" non_empty))
+(import (prefix (std strings) string:))
+(import (prefix-in string2: (std strings)))
+(import (only (std strings) non_empty))
 (require (only-in "vm" add_one))
 
 ; Complex Scheme flavor example: loops + stdlib + host + closure + syntax coverage.
@@ -31,6 +31,7 @@ fn parse_flavor(raw: &str) -> SourceFlavor {
         "javascript" | "js" => SourceFlavor::JavaScript,
         "lua" => SourceFlavor::Lua,
         "scheme" | "scm" => SourceFlavor::Scheme,
+        "assembly" | "pda" => SourceFlavor::Assembly,
         _ => SourceFlavor::RustScript,
     }
 }
@@ -134,6 +135,7 @@ mod tests {
         assert_eq!(parse_flavor("js"), SourceFlavor::JavaScript);
         assert_eq!(parse_flavor("scm"), SourceFlavor::Scheme);
         assert_eq!(parse_flavor("lua"), SourceFlavor::Lua);
+        assert_eq!(parse_flavor("pda"), SourceFlavor::Assembly);
         assert_eq!(parse_flavor("rss"), SourceFlavor::RustScript);
     }
 
@@ -1,7 +1,11 @@
 ﻿use std::collections::HashMap;
 
-use crate::debug_info::DebugInfoBuilder;
-use crate::{OpCode, Program, Value};
+use crate::compiler::source_map::SourceId;
+use crate::debug_info::{
+    ArgInfo, DebugFunction, DebugInfo, DebugInfoBuilder, DebugSource, InlineFrame, LineInfo,
+    LocalInfo,
+};
+use crate::{HostImport, OpCode, Program, Value};
 
 pub struct BytecodeBuilder {
     code: Vec<u8>,
@@ -68,6 +72,23 @@ impl Assembler {
         self.debug.set_source(source);
     }
 
+    pub fn add_source(&mut self, name: String, text: Option<String>) -> SourceId {
+        self.debug.add_source(name, text)
+    }
+
+    /// Source that lines marked from now on belong to.
+    pub fn set_current_source(&mut self, source_id: SourceId) {
+        self.debug.set_current_source(source_id);
+    }
+
+    pub fn current_source(&self) -> SourceId {
+        self.debug.current_source()
+    }
+
+    pub fn last_marked_line(&self) -> Option<u32> {
+        self.debug.last_line()
+    }
+
     pub fn mark_line(&mut self, line: u32) {
         let offset = self.code.len() as u32;
         self.debug.mark_line(offset, line);
@@ -77,10 +98,27 @@ impl Assembler {
         self.debug.add_function(name, args);
     }
 
+    /// Starts the code range of an inlined call of `function` made from
+    /// `call_line` of the current source.
+    pub fn enter_inline_frame(&mut self, function: String, call_line: u32) {
+        let offset = self.code.len() as u32;
+        self.debug.enter_inline(function, call_line, offset);
+    }
+
+    pub fn exit_inline_frame(&mut self) {
+        let offset = self.code.len() as u32;
+        self.debug.exit_inline(offset);
+    }
+
     pub fn add_local(&mut self, name: String, index: u8) {
         self.debug.add_local(name, index);
     }
 
+    /// Names a local of the innermost open inlined call.
+    pub fn add_inline_local(&mut self, name: String, index: u8) {
+        self.debug.add_inline_local(name, index);
+    }
+
     pub fn add_constant(&mut self, value: Value) -> u32 {
         match value {
             Value::Int(number) => {
@@ -128,6 +166,32 @@ impl Assembler {
         }
     }
 
+    /// Appends `value` even when an equal constant exists, so a listing's
+    /// constant table is rebuilt index for index. Later `add_constant` calls
+    /// reuse the first index of each value.
+    pub fn append_constant(&mut self, value: Value) -> u32 {
+        let index = self.constants.len() as u32;
+        match &value {
+            Value::Int(number) => {
+                self.int_constants.entry(*number).or_insert(index);
+            }
+            Value::Float(number) => {
+                self.float_constants
+                    .entry(number.to_bits())
+                    .or_insert(index);
+            }
+            Value::Bool(flag) => {
+                self.bool_constants.entry(*flag).or_insert(index);
+            }
+            Value::String(text) => {
+                self.string_constants.entry(text.clone()).or_insert(index);
+            }
+            _ => {}
+        }
+        self.constants.push(value);
+        index
+    }
+
     pub fn push_const(&mut self, value: Value) -> u32 {
         let index = self.add_constant(value);
         self.ldc(index);
@@ -270,6 +334,11 @@ impl Assembler {
         self.emit_opcode(OpCode::Shr);
     }
 
+    /// Emits bytes as they are, for code the listing could not decode.
+    pub fn emit_raw(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
     fn emit_opcode(&mut self, opcode: OpCode) {
         self.code.push(opcode as u8);
     }
@@ -442,6 +511,31 @@ enum AsmSection {
     Code,
 }
 
+/// How `assemble` fills in the program's debug info.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AsmDebugMode {
+    /// The assembly text is the source and each instruction marks its line.
+    Implicit,
+    /// `.debug none`: the program carries no debug info.
+    Off,
+    /// `.debug explicit`, or any of `.source`, `.line`, `.function` and
+    /// `.inline`: the debug info is exactly what the directives describe.
+    Explicit,
+}
+
+#[derive(Default)]
+struct AsmDebugInfo {
+    sources: Vec<DebugSource>,
+    lines: Vec<LineInfo>,
+    functions: Vec<DebugFunction>,
+    locals: Vec<LocalInfo>,
+    inline_frames: Vec<InlineFrame>,
+    open_inline_frames: Vec<u32>,
+}
+
+/// Parses the textual assembly format. Its grammar is documented in the
+/// crate README, and [`crate::disassemble_program`] emits it, so a listing
+/// assembles back to the program it was made from.
 pub fn assemble(source: &str) -> Result<Program, AsmParseError> {
     let mut assembler = Assembler::new();
     assembler.set_source(source.to_string());
@@ -449,6 +543,9 @@ pub fn assemble(source: &str) -> Result<Program, AsmParseError> {
     let mut locals: HashMap<String, u8> = HashMap::new();
     let mut next_local: u8 = 0;
     let mut section = AsmSection::Code;
+    let mut imports = Vec::new();
+    let mut debug_mode = AsmDebugMode::Implicit;
+    let mut debug = AsmDebugInfo::default();
 
     for (line_idx, raw_line) in source.lines().enumerate() {
         let line_no = line_idx + 1;
@@ -458,97 +555,138 @@ pub fn assemble(source: &str) -> Result<Program, AsmParseError> {
         }
 
         if line.ends_with(':') {
-            return Err(AsmParseError {
-                line: line_no,
-                message: "label definitions must use '.label NAME'".to_string(),
-            });
+            return Err(asm_error(
+                line_no,
+                "label definitions must use '.label NAME'",
+            ));
         }
 
         if let Some(rest) = line.strip_prefix('.') {
-            let mut parts = rest.split_whitespace();
-            let directive = parts.next().unwrap_or("").to_ascii_lowercase();
-            match directive.as_str() {
+            let tokens = split_tokens(rest, line_no)?;
+            let directive = tokens
+                .first()
+                .map(|token| token.to_ascii_lowercase())
+                .unwrap_or_default();
+            let args = tokens.get(1..).unwrap_or_default();
+            let arity = match directive.as_str() {
                 "data" => {
                     section = AsmSection::Data;
+                    0
                 }
                 "code" => {
                     section = AsmSection::Code;
+                    0
                 }
                 "label" => {
-                    let name = next_token(&mut parts, line_no, "label name")?;
+                    let name = arg(args, 0, line_no, "label name")?;
                     if section != AsmSection::Code {
-                        return Err(AsmParseError {
-                            line: line_no,
-                            message: "labels are only valid in code section".to_string(),
-                        });
+                        return Err(asm_error(line_no, "labels are only valid in code section"));
                     }
-                    assembler.label(name).map_err(|err| AsmParseError {
-                        line: line_no,
-                        message: format!("label error: {err:?}"),
-                    })?;
+                    assembler
+                        .label(name)
+                        .map_err(|err| asm_error(line_no, format!("label error: {err:?}")))?;
+                    1
                 }
                 "const" => {
-                    let name = next_token(&mut parts, line_no, "const name")?;
+                    let name = arg(args, 0, line_no, "const name")?;
                     if consts.contains_key(name) {
-                        return Err(AsmParseError {
-                            line: line_no,
-                            message: format!("duplicate const '{name}'"),
-                        });
+                        return Err(asm_error(line_no, format!("duplicate const '{name}'")));
                     }
-                    let rest = rest_after_n_tokens(line, 2).unwrap_or("");
-                    if rest.is_empty() {
-                        return Err(AsmParseError {
-                            line: line_no,
-                            message: "missing const value".to_string(),
-                        });
-                    }
-                    let value = parse_literal(rest, line_no)?;
-                    let index = assembler.add_constant(value);
+                    let value = parse_literal(arg(args, 1, line_no, "const value")?, line_no)?;
+                    let index = assembler.append_constant(value);
                     consts.insert(name.to_string(), index);
+                    2
                 }
                 "local" => {
-                    let name = next_token(&mut parts, line_no, "local name")?;
-                    if locals.contains_key(name) {
-                        return Err(AsmParseError {
-                            line: line_no,
-                            message: format!("duplicate local '{name}'"),
-                        });
+                    let name = parse_name(arg(args, 0, line_no, "local name")?, line_no)?;
+                    if locals.contains_key(&name) {
+                        return Err(asm_error(line_no, format!("duplicate local '{name}'")));
                     }
 
-                    let index = if let Some(token) = parts.next() {
+                    let index = if let Some(token) = args.get(1) {
                         parse_u8(token, line_no)?
                     } else {
                         let index = next_local;
-                        next_local = next_local.checked_add(1).ok_or(AsmParseError {
-                            line: line_no,
-                            message: "local index overflow".to_string(),
-                        })?;
+                        next_local = next_local
+                            .checked_add(1)
+                            .ok_or_else(|| asm_error(line_no, "local index overflow"))?;
                         index
                     };
-                    locals.insert(name.to_string(), index);
+                    locals.insert(name.clone(), index);
+                    debug.locals.push(LocalInfo { name, index });
+                    args.len().clamp(1, 2)
+                }
+                "import" => {
+                    let name = parse_name(arg(args, 0, line_no, "import name")?, line_no)?;
+                    let arity = parse_u8(arg(args, 1, line_no, "import arity")?, line_no)?;
+                    imports.push(HostImport { name, arity });
+                    2
+                }
+                "byte" => {
+                    if section != AsmSection::Code {
+                        return Err(asm_error(line_no, "bytes are only valid in code section"));
+                    }
+                    if args.is_empty() {
+                        return Err(asm_error(line_no, "missing byte value"));
+                    }
+                    let bytes = args
+                        .iter()
+                        .map(|token| parse_byte(token, line_no))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    assembler.emit_raw(&bytes);
+                    args.len()
+                }
+                "debug" => {
+                    let mode = arg(args, 0, line_no, "debug mode")?;
+                    debug_mode = match mode.to_ascii_lowercase().as_str() {
+                        "none" if debug_mode != AsmDebugMode::Explicit => AsmDebugMode::Off,
+                        "explicit" if debug_mode != AsmDebugMode::Off => AsmDebugMode::Explicit,
+                        "none" | "explicit" => {
+                            return Err(asm_error(
+                                line_no,
+                                "'.debug none' conflicts with explicit debug directives",
+                            ));
+                        }
+                        other => {
+                            return Err(asm_error(
+                                line_no,
+                                format!("unknown debug mode '{other}', expected none or explicit"),
+                            ));
+                        }
+                    };
+                    1
+                }
+                "source" | "line" | "function" | "inline" | "endinline" => {
+                    if debug_mode == AsmDebugMode::Off {
+                        return Err(asm_error(
+                            line_no,
+                            format!("'.{directive}' conflicts with '.debug none'"),
+                        ));
+                    }
+                    debug_mode = AsmDebugMode::Explicit;
+                    parse_debug_directive(
+                        &directive,
+                        args,
+                        line_no,
+                        assembler.position(),
+                        &mut debug,
+                    )?
                 }
                 other => {
-                    return Err(AsmParseError {
-                        line: line_no,
-                        message: format!("unknown directive '.{other}'"),
-                    });
+                    return Err(asm_error(line_no, format!("unknown directive '.{other}'")));
                 }
-            }
+            };
 
-            if parts.next().is_some() {
-                return Err(AsmParseError {
-                    line: line_no,
-                    message: "unexpected extra tokens".to_string(),
-                });
+            if args.len() > arity {
+                return Err(asm_error(line_no, "unexpected extra tokens"));
             }
             continue;
         }
 
         let mut parts = line.split_whitespace();
-        let op = parts.next().ok_or_else(|| AsmParseError {
-            line: line_no,
-            message: "missing opcode".to_string(),
-        })?;
+        let op = parts
+            .next()
+            .ok_or_else(|| asm_error(line_no, "missing opcode"))?;
         let op = op.to_ascii_lowercase();
 
         if section == AsmSection::Data {
@@ -556,17 +694,11 @@ pub fn assemble(source: &str) -> Result<Program, AsmParseError> {
                 "const" => {
                     let name = next_token(&mut parts, line_no, "const name")?;
                     if consts.contains_key(name) {
-                        return Err(AsmParseError {
-                            line: line_no,
-                            message: format!("duplicate const '{name}'"),
-                        });
+                        return Err(asm_error(line_no, format!("duplicate const '{name}'")));
                     }
                     let rest = rest_after_n_tokens(line, 2).unwrap_or("");
                     if rest.is_empty() {
-                        return Err(AsmParseError {
-                            line: line_no,
-                            message: "missing const value".to_string(),
-                        });
+                        return Err(asm_error(line_no, "missing const value"));
                     }
                     let value = parse_literal(rest, line_no)?;
                     let index = assembler.add_constant(value);
@@ -575,27 +707,21 @@ pub fn assemble(source: &str) -> Result<Program, AsmParseError> {
                 "string" => {
                     let name = next_token(&mut parts, line_no, "string name")?;
                     if consts.contains_key(name) {
-                        return Err(AsmParseError {
-                            line: line_no,
-                            message: format!("duplicate const '{name}'"),
-                        });
+                        return Err(asm_error(line_no, format!("duplicate const '{name}'")));
                     }
                     let rest = rest_after_n_tokens(line, 2).unwrap_or("");
                     if rest.is_empty() {
-                        return Err(AsmParseError {
-                            line: line_no,
-                            message: "missing string literal".to_string(),
-                        });
+                        return Err(asm_error(line_no, "missing string literal"));
                     }
                     let value = Value::String(parse_string_literal(rest, line_no)?);
                     let index = assembler.add_constant(value);
                     consts.insert(name.to_string(), index);
                 }
                 other => {
-                    return Err(AsmParseError {
-                        line: line_no,
-                        message: format!("unexpected opcode '{other}' in data section"),
-                    });
+                    return Err(asm_error(
+                        line_no,
+                        format!("unexpected opcode '{other}' in data section"),
+                    ));
                 }
             }
             continue;
@@ -699,20 +825,258 @@ pub fn assemble(source: &str) -> Result<Program, AsmParseError> {
         }
     }
 
-    assembler.finish_program().map_err(|err| AsmParseError {
-        line: 0,
-        message: format!("assembler error: {err:?}"),
+    if !debug.open_inline_frames.is_empty() {
+        return Err(asm_error(
+            source.lines().count(),
+            "'.inline' without a matching '.endinline'",
+        ));
+    }
+    let mut program = assembler
+        .finish_program()
+        .map_err(|err| asm_error(0, format!("assembler error: {err:?}")))?;
+    program.imports = imports;
+    match debug_mode {
+        AsmDebugMode::Implicit => {}
+        AsmDebugMode::Off => program.debug = None,
+        AsmDebugMode::Explicit => {
+            program.debug = Some(DebugInfo {
+                sources: debug.sources,
+                lines: debug.lines,
+                functions: debug.functions,
+                locals: debug.locals,
+                inline_frames: debug.inline_frames,
+            });
+        }
+    }
+    Ok(program)
+}
+
+/// Applies a `.source`, `.line`, `.function`, `.inline` or `.endinline`
+/// directive at code offset `offset` and returns how many arguments it took.
+fn parse_debug_directive(
+    directive: &str,
+    args: &[String],
+    line_no: usize,
+    offset: u32,
+    debug: &mut AsmDebugInfo,
+) -> Result<usize, AsmParseError> {
+    match directive {
+        "source" => {
+            let id = parse_u32(arg(args, 0, line_no, "source id")?, line_no)?;
+            if id as usize != debug.sources.len() {
+                return Err(asm_error(
+                    line_no,
+                    format!("expected source id {}, found {id}", debug.sources.len()),
+                ));
+            }
+            let name = parse_name(arg(args, 1, line_no, "source name")?, line_no)?;
+            let text = args
+                .get(2)
+                .map(|token| parse_string_literal(token, line_no))
+                .transpose()?;
+            debug.sources.push(DebugSource { name, text });
+            Ok(3)
+        }
+        "line" => {
+            let source_id = parse_source_id(arg(args, 0, line_no, "source id")?, line_no, debug)?;
+            let line = parse_u32(arg(args, 1, line_no, "line number")?, line_no)?;
+            let column = args
+                .get(2)
+                .map(|token| parse_u32(token, line_no))
+                .transpose()?
+                .unwrap_or(0);
+            debug.lines.push(LineInfo {
+                offset,
+                source_id,
+                line,
+                column,
+            });
+            Ok(3)
+        }
+        "function" => {
+            let name = parse_name(arg(args, 0, line_no, "function name")?, line_no)?;
+            let function_args = args[1..]
+                .iter()
+                .enumerate()
+                .map(|(index, token)| parse_function_arg(token, index, line_no))
+                .collect::<Result<Vec<_>, _>>()?;
+            debug.functions.push(DebugFunction {
+                name,
+                args: function_args,
+            });
+            Ok(args.len())
+        }
+        "inline" => {
+            let function = parse_name(arg(args, 0, line_no, "inlined function")?, line_no)?;
+            let call_source =
+                parse_source_id(arg(args, 1, line_no, "call source id")?, line_no, debug)?;
+            let call_line = parse_u32(arg(args, 2, line_no, "call line")?, line_no)?;
+            let locals = args
+                .iter()
+                .skip(3)
+                .map(|token| parse_inline_local(token, line_no))
+                .collect::<Result<Vec<_>, _>>()?;
+            let index = debug.inline_frames.len() as u32;
+            debug.inline_frames.push(InlineFrame {
+                start: offset,
+                end: offset,
+                function,
+                call_source,
+                call_line,
+                parent: debug.open_inline_frames.last().copied(),
+                locals,
+            });
+            debug.open_inline_frames.push(index);
+            Ok(args.len())
+        }
+        "endinline" => {
+            let index = debug
+                .open_inline_frames
+                .pop()
+                .ok_or_else(|| asm_error(line_no, "'.endinline' without an open '.inline'"))?;
+            debug.inline_frames[index as usize].end = offset;
+            Ok(0)
+        }
+        other => Err(asm_error(line_no, format!("unknown directive '.{other}'"))),
+    }
+}
+
+fn parse_source_id(
+    token: &str,
+    line_no: usize,
+    debug: &AsmDebugInfo,
+) -> Result<SourceId, AsmParseError> {
+    let id = parse_u32(token, line_no)?;
+    if id as usize >= debug.sources.len() {
+        return Err(asm_error(
+            line_no,
+            format!("unknown source id {id}, declare it with '.source' first"),
+        ));
+    }
+    Ok(id)
+}
+
+/// Parses a `.function` argument, `NAME` or `NAME@POSITION`; the position
+/// defaults to the argument's place in the list.
+fn parse_function_arg(token: &str, index: usize, line_no: usize) -> Result<ArgInfo, AsmParseError> {
+    let (name, position) = match token.rsplit_once('@') {
+        Some((name, position))
+            if !name.is_empty()
+                && !position.is_empty()
+                && position.bytes().all(|byte| byte.is_ascii_digit()) =>
+        {
+            (name, parse_u8(position, line_no)?)
+        }
+        _ => (
+            token,
+            u8::try_from(index).map_err(|_| asm_error(line_no, "too many function arguments"))?,
+        ),
+    };
+    Ok(ArgInfo {
+        name: parse_name(name, line_no)?,
+        position,
     })
 }
 
+/// `name@slot`, a local of an inlined call.
+fn parse_inline_local(token: &str, line_no: usize) -> Result<LocalInfo, AsmParseError> {
+    let Some((name, index)) = token.rsplit_once('@') else {
+        return Err(asm_error(
+            line_no,
+            format!("expected inlined local 'name@slot', found '{token}'"),
+        ));
+    };
+    Ok(LocalInfo {
+        name: parse_name(name, line_no)?,
+        index: parse_u8(index, line_no)?,
+    })
+}
+
+fn asm_error(line: usize, message: impl Into<String>) -> AsmParseError {
+    AsmParseError {
+        line,
+        message: message.into(),
+    }
+}
+
+/// Cuts the line at the first `;`, `#` or `//` outside a string literal.
 fn strip_comments(line: &str) -> &str {
-    let hash_idx = line.find('#');
-    let slash_idx = line.find("//");
-    match (hash_idx, slash_idx) {
-        (Some(h), Some(s)) => &line[..h.min(s)],
-        (Some(h), None) => &line[..h],
-        (None, Some(s)) => &line[..s],
-        (None, None) => line,
+    let bytes = line.as_bytes();
+    let mut in_string = false;
+    let mut escaped = false;
+    for (idx, byte) in bytes.iter().enumerate() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if *byte == b'\\' {
+                escaped = true;
+            } else if *byte == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b';' | b'#' => return &line[..idx],
+            b'/' if bytes.get(idx + 1) == Some(&b'/') => return &line[..idx],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Splits a directive into whitespace-separated tokens, keeping a quoted
+/// string, quotes included, as one token.
+fn split_tokens(line: &str, line_no: usize) -> Result<Vec<String>, AsmParseError> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for ch in line.chars() {
+        if in_string {
+            current.push(ch);
+            if escaped {
+                escaped = false;
+            } else if ch == '\\' {
+                escaped = true;
+            } else if ch == '"' {
+                in_string = false;
+            }
+        } else if ch.is_whitespace() {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+        } else {
+            in_string = ch == '"';
+            current.push(ch);
+        }
+    }
+    if in_string {
+        return Err(asm_error(line_no, "unterminated string literal"));
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+fn arg<'a>(
+    args: &'a [String],
+    index: usize,
+    line_no: usize,
+    what: &str,
+) -> Result<&'a str, AsmParseError> {
+    args.get(index)
+        .map(String::as_str)
+        .ok_or_else(|| asm_error(line_no, format!("missing {what}")))
+}
+
+/// A bare name, or a quoted string for names with spaces or comment characters.
+fn parse_name(token: &str, line_no: usize) -> Result<String, AsmParseError> {
+    if token.starts_with('"') {
+        parse_string_literal(token, line_no)
+    } else {
+        Ok(token.to_string())
     }
 }
 
@@ -734,6 +1098,24 @@ fn parse_u8(token: &str, line_no: usize) -> Result<u8, AsmParseError> {
     })
 }
 
+fn parse_u32(token: &str, line_no: usize) -> Result<u32, AsmParseError> {
+    token.parse::<u32>().map_err(|_| AsmParseError {
+        line: line_no,
+        message: format!("invalid u32 '{token}'"),
+    })
+}
+
+fn parse_byte(token: &str, line_no: usize) -> Result<u8, AsmParseError> {
+    let parsed = match token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+    {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => token.parse::<u8>().ok(),
+    };
+    parsed.ok_or_else(|| asm_error(line_no, format!("invalid byte '{token}'")))
+}
+
 fn parse_u16(token: &str, line_no: usize) -> Result<u16, AsmParseError> {
     token.parse::<u16>().map_err(|_| AsmParseError {
         line: line_no,
@@ -753,7 +1135,9 @@ fn parse_literal(token: &str, line_no: usize) -> Result<Value, AsmParseError> {
     if token.starts_with('"') {
         return Ok(Value::String(parse_string_literal(token, line_no)?));
     }
-    if token.eq_ignore_ascii_case("true") {
+    if token.eq_ignore_ascii_case("null") {
+        Ok(Value::Null)
+    } else if token.eq_ignore_ascii_case("true") {
         Ok(Value::Bool(true))
     } else if token.eq_ignore_ascii_case("false") {
         Ok(Value::Bool(false))
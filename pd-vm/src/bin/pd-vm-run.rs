@@ -5,28 +5,40 @@ use std::path::{Path, PathBuf};
 use rustyline::DefaultEditor;
 use rustyline::error::ReadlineError;
 use vm::{
-    CallOutcome, Debugger, DisassembleOptions, FunctionDecl, HostFunction, SourceMap,
-    SourcePathError, Value, Vm, VmError, VmRecording, VmStatus, compile_source,
-    compile_source_file, disassemble_vmbc_with_options, encode_program, render_source_error,
-    render_vm_error, replay_recording_stdio,
+    CallOutcome, DapSession, DapStartKind, Debugger, DisassembleOptions, FunctionDecl,
+    HostFunction, Program, SourceFlavor, SourceMap, SourcePathError, TranspileError, Value, Vm,
+    VmError, VmRecording, VmStatus, compile_source_file_with_search_paths,
+    compile_source_with_flavor, decode_program, decompile_program, disassemble_program,
+    disassemble_vmbc_with_options, encode_program, render_source_error, render_vm_error,
+    replay_recording_stdio, transpile_bindings, transpile_source,
+    transpile_source_file_with_search_paths,
 };
 
 const DEFAULT_SOURCE: &str = "examples/example.rss";
+/// Extra module directories, separated like `PATH`, searched after any
+/// `--module-path` directories.
+const MODULE_PATH_ENV: &str = "PD_VM_PATH";
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct CliConfig {
     source: Option<String>,
     emit_vmbc_path: Option<String>,
     disasm_vmbc_path: Option<String>,
+    decompile_vmbc_path: Option<String>,
+    transpile_flavor: Option<SourceFlavor>,
     record_path: Option<String>,
     view_recording_path: Option<String>,
     show_source: bool,
     repl: bool,
+    repl_flavor: Option<SourceFlavor>,
     debug: bool,
     tcp_addr: Option<String>,
+    dap_addr: Option<String>,
     stop_on_entry: bool,
     jit_dump: bool,
     jit_hot_loop_threshold: Option<u32>,
+    rng_seed: Option<u64>,
+    module_paths: Vec<PathBuf>,
     help: bool,
 }
 
@@ -36,15 +48,21 @@ impl Default for CliConfig {
             source: None,
             emit_vmbc_path: None,
             disasm_vmbc_path: None,
+            decompile_vmbc_path: None,
+            transpile_flavor: None,
             record_path: None,
             view_recording_path: None,
             show_source: false,
             repl: false,
+            repl_flavor: None,
             debug: false,
             tcp_addr: None,
+            dap_addr: None,
             stop_on_entry: true,
             jit_dump: false,
             jit_hot_loop_threshold: None,
+            rng_seed: None,
+            module_paths: Vec::new(),
             help: false,
         }
     }
@@ -66,7 +84,7 @@ fn run_main() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
     if cli.repl {
-        return run_repl();
+        return run_repl(&cli);
     }
     if let Some(input_path) = cli.disasm_vmbc_path.as_ref() {
         let bytes = std::fs::read(input_path)?;
@@ -79,15 +97,60 @@ fn run_main() -> Result<(), Box<dyn std::error::Error>> {
         print!("{listing}");
         return Ok(());
     }
+    if let Some(input_path) = cli.decompile_vmbc_path.as_ref() {
+        let bytes = std::fs::read(input_path)?;
+        let program = decode_program(&bytes)?;
+        print!("{}", decompile_program(&program)?);
+        return Ok(());
+    }
+    if let Some(flavor) = cli.transpile_flavor {
+        let source_path = resolve_source_path(cli.source.as_deref())?;
+        let mut search_paths = cli.module_paths.clone();
+        search_paths.extend(module_search_paths_from_env());
+        let source = transpile_source_file_with_search_paths(&source_path, search_paths, flavor)
+            .map_err(|err| match err {
+                TranspileError::Source(err) => {
+                    io::Error::other(render_source_path_error(&source_path, &err))
+                }
+                other => io::Error::other(other.to_string()),
+            })?;
+        print!("{source}");
+        return Ok(());
+    }
     if let Some(recording_path) = cli.view_recording_path.as_ref() {
         let recording = VmRecording::load_from_file(recording_path)?;
         replay_recording_stdio(&recording);
         return Ok(());
     }
 
-    let source_path = resolve_source_path(cli.source.as_deref())?;
-    let compiled = compile_source_file(&source_path)
-        .map_err(|err| io::Error::other(render_source_path_error(&source_path, &err)))?;
+    // Under --dap a launch configuration may name the program to run.
+    let (source_arg, mut dap_session) = match cli.dap_addr.as_deref() {
+        Some(addr) => {
+            let mut session = DapSession::bind(addr)?;
+            println!("[debug] dap server listening on {addr}");
+            let start = session.accept()?;
+            let program = match start.kind {
+                DapStartKind::Launch => start.program.or_else(|| cli.source.clone()),
+                DapStartKind::Attach => cli.source.clone().or(start.program),
+            };
+            (program, Some(session))
+        }
+        None => (cli.source.clone(), None),
+    };
+    let source_path = resolve_source_path(source_arg.as_deref())?;
+    // `--module-path` directories are searched before those in PD_VM_PATH.
+    let mut search_paths = cli.module_paths.clone();
+    search_paths.extend(module_search_paths_from_env());
+    let compiled =
+        compile_source_file_with_search_paths(&source_path, search_paths).map_err(|err| {
+            reject_dap_start(
+                dap_session.as_mut(),
+                render_source_path_error(&source_path, &err),
+            )
+        })?;
+    for warning in &compiled.warnings {
+        eprintln!("{}: {warning}", source_path.display());
+    }
     if let Some(output_path) = cli.emit_vmbc_path.as_ref() {
         let encoded = encode_program(&compiled.program)?;
         std::fs::write(output_path, &encoded)?;
@@ -101,7 +164,11 @@ fn run_main() -> Result<(), Box<dyn std::error::Error>> {
         jit_config.hot_loop_threshold = hot_loop;
         vm.set_jit_config(jit_config);
     }
-    register_functions(&mut vm, &compiled.functions)?;
+    if let Some(seed) = cli.rng_seed {
+        vm.set_rng_seed(seed);
+    }
+    register_functions(&mut vm, &compiled.functions)
+        .map_err(|err| reject_dap_start(dap_session.as_mut(), err.to_string()))?;
 
     if let Some(record_path) = cli.record_path.as_ref() {
         let program = recording_program.expect("recording mode should clone program");
@@ -127,14 +194,18 @@ fn run_main() -> Result<(), Box<dyn std::error::Error>> {
             .ok_or_else(|| io::Error::other("recording state unavailable"))?;
         recording.save_to_file(record_path)?;
         println!(
-            "recording saved to {} (frames={})",
+            "recording saved to {} (frames={}, seed={})",
             record_path,
-            recording.frames.len()
+            recording.frames.len(),
+            vm.rng_seed()
         );
         return Ok(());
     }
 
-    let mut debugger = if cli.debug {
+    let mut debugger = if let Some(session) = dap_session.take() {
+        // The editor's `stopOnEntry` decides whether a DAP session stops on entry.
+        Some(Debugger::with_dap(session))
+    } else if cli.debug {
         let mut debugger = if let Some(addr) = &cli.tcp_addr {
             println!("[debug] tcp debugger listening on {addr}");
             Debugger::with_tcp(addr)?
@@ -151,8 +222,14 @@ fn run_main() -> Result<(), Box<dyn std::error::Error>> {
 
     loop {
         let status = if let Some(debugger) = debugger.as_mut() {
-            vm.run_with_debugger(debugger)
-                .map_err(|err| io::Error::other(render_vm_error(&vm, &err)))?
+            match vm.run_with_debugger(debugger) {
+                Ok(status) => status,
+                Err(err) => {
+                    let message = render_vm_error(&vm, &err);
+                    debugger.end_session(Some(&message));
+                    return Err(io::Error::other(message).into());
+                }
+            }
         } else {
             vm.run()
                 .map_err(|err| io::Error::other(render_vm_error(&vm, &err)))?
@@ -169,12 +246,24 @@ fn run_main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
     }
+    if let Some(debugger) = debugger.as_mut() {
+        debugger.end_session(None);
+    }
     if cli.jit_dump {
         println!("{}", vm.dump_jit_info());
     }
     Ok(())
 }
 
+/// Fails a pending DAP launch or attach with `message`, which is also
+/// returned as the error that ends the run.
+fn reject_dap_start(session: Option<&mut DapSession>, message: String) -> io::Error {
+    if let Some(session) = session {
+        session.reject(&message);
+    }
+    io::Error::other(message)
+}
+
 fn render_source_path_error(source_path: &Path, err: &SourcePathError) -> String {
     match err {
         SourcePathError::Source(vm::SourceError::Parse(parse)) => {
@@ -241,6 +330,15 @@ fn parse_cli_args(args: &[String]) -> Result<CliConfig, String> {
                 cfg.tcp_addr = Some(addr);
                 index += 2;
             }
+            "--dap" => {
+                cfg.debug = true;
+                let addr = args
+                    .get(index + 1)
+                    .ok_or_else(|| "missing value for --dap".to_string())?
+                    .clone();
+                cfg.dap_addr = Some(addr);
+                index += 2;
+            }
             "--stop-on-entry" => {
                 cfg.debug = true;
                 cfg.stop_on_entry = true;
@@ -265,6 +363,23 @@ fn parse_cli_args(args: &[String]) -> Result<CliConfig, String> {
                 cfg.jit_hot_loop_threshold = Some(value);
                 index += 2;
             }
+            "--seed" => {
+                let raw = args
+                    .get(index + 1)
+                    .ok_or_else(|| "missing value for --seed".to_string())?;
+                let value = raw
+                    .parse::<u64>()
+                    .map_err(|_| format!("invalid --seed value '{raw}'"))?;
+                cfg.rng_seed = Some(value);
+                index += 2;
+            }
+            "--module-path" => {
+                let path = args
+                    .get(index + 1)
+                    .ok_or_else(|| "missing value for --module-path".to_string())?;
+                cfg.module_paths.push(PathBuf::from(path));
+                index += 2;
+            }
             "--emit-vmbc" => {
                 let path = args
                     .get(index + 1)
@@ -279,6 +394,20 @@ fn parse_cli_args(args: &[String]) -> Result<CliConfig, String> {
                 cfg.disasm_vmbc_path = Some(path.clone());
                 index += 2;
             }
+            "--decompile" => {
+                let path = args
+                    .get(index + 1)
+                    .ok_or_else(|| "missing value for --decompile".to_string())?;
+                cfg.decompile_vmbc_path = Some(path.clone());
+                index += 2;
+            }
+            "--transpile" => {
+                let flavor = args
+                    .get(index + 1)
+                    .ok_or_else(|| "missing value for --transpile".to_string())?;
+                cfg.transpile_flavor = Some(parse_flavor_arg("--transpile", flavor)?);
+                index += 2;
+            }
             "--record" => {
                 let path = args
                     .get(index + 1)
@@ -301,6 +430,13 @@ fn parse_cli_args(args: &[String]) -> Result<CliConfig, String> {
                 cfg.repl = true;
                 index += 1;
             }
+            "--flavor" => {
+                let flavor = args
+                    .get(index + 1)
+                    .ok_or_else(|| "missing value for --flavor".to_string())?;
+                cfg.repl_flavor = Some(parse_flavor_arg("--flavor", flavor)?);
+                index += 2;
+            }
             value if value.starts_with('-') => {
                 return Err(format!("unknown flag '{value}'"));
             }
@@ -314,6 +450,9 @@ fn parse_cli_args(args: &[String]) -> Result<CliConfig, String> {
         }
     }
 
+    if cfg.dap_addr.is_some() && cfg.tcp_addr.is_some() {
+        return Err("--dap cannot be combined with --tcp".to_string());
+    }
     if cfg.repl {
         if cfg.source.is_some() {
             return Err("repl mode does not accept a source path".to_string());
@@ -321,15 +460,17 @@ fn parse_cli_args(args: &[String]) -> Result<CliConfig, String> {
         if cfg.debug
             || cfg.tcp_addr.is_some()
             || cfg.jit_dump
-            || cfg.jit_hot_loop_threshold.is_some()
             || cfg.emit_vmbc_path.is_some()
             || cfg.record_path.is_some()
             || cfg.view_recording_path.is_some()
         {
             return Err(
-                "repl mode cannot be combined with debug/jit/emit-vmbc runtime flags".to_string(),
+                "repl mode cannot be combined with debug/jit-dump/emit-vmbc/record flags"
+                    .to_string(),
             );
         }
+    } else if cfg.repl_flavor.is_some() {
+        return Err("--flavor requires repl mode".to_string());
     }
     if cfg.disasm_vmbc_path.is_some() {
         if cfg.source.is_some() {
@@ -340,6 +481,7 @@ fn parse_cli_args(args: &[String]) -> Result<CliConfig, String> {
             || cfg.tcp_addr.is_some()
             || cfg.jit_dump
             || cfg.jit_hot_loop_threshold.is_some()
+            || cfg.rng_seed.is_some()
             || cfg.emit_vmbc_path.is_some()
             || cfg.record_path.is_some()
             || cfg.view_recording_path.is_some()
@@ -352,6 +494,47 @@ fn parse_cli_args(args: &[String]) -> Result<CliConfig, String> {
     } else if cfg.show_source {
         return Err("--show-source requires --disasm-vmbc".to_string());
     }
+    if cfg.decompile_vmbc_path.is_some() {
+        if cfg.source.is_some() {
+            return Err("decompile mode does not accept a source path".to_string());
+        }
+        if cfg.repl
+            || cfg.debug
+            || cfg.tcp_addr.is_some()
+            || cfg.jit_dump
+            || cfg.jit_hot_loop_threshold.is_some()
+            || cfg.rng_seed.is_some()
+            || cfg.emit_vmbc_path.is_some()
+            || cfg.disasm_vmbc_path.is_some()
+            || cfg.record_path.is_some()
+            || cfg.view_recording_path.is_some()
+        {
+            return Err(
+                "decompile mode cannot be combined with repl/debug/jit/emit/disasm flags"
+                    .to_string(),
+            );
+        }
+    }
+    if cfg.transpile_flavor.is_some() && cfg.source.is_none() {
+        return Err("transpile mode requires a source path".to_string());
+    }
+    if cfg.transpile_flavor.is_some()
+        && (cfg.repl
+            || cfg.debug
+            || cfg.tcp_addr.is_some()
+            || cfg.jit_dump
+            || cfg.jit_hot_loop_threshold.is_some()
+            || cfg.rng_seed.is_some()
+            || cfg.emit_vmbc_path.is_some()
+            || cfg.disasm_vmbc_path.is_some()
+            || cfg.decompile_vmbc_path.is_some()
+            || cfg.record_path.is_some()
+            || cfg.view_recording_path.is_some())
+    {
+        return Err(
+            "transpile mode cannot be combined with repl/debug/jit/emit/disasm flags".to_string(),
+        );
+    }
     if cfg.record_path.is_some()
         && (cfg.debug
             || cfg.tcp_addr.is_some()
@@ -373,6 +556,7 @@ fn parse_cli_args(args: &[String]) -> Result<CliConfig, String> {
             || cfg.tcp_addr.is_some()
             || cfg.jit_dump
             || cfg.jit_hot_loop_threshold.is_some()
+            || cfg.rng_seed.is_some()
             || cfg.emit_vmbc_path.is_some()
             || cfg.disasm_vmbc_path.is_some()
             || cfg.record_path.is_some()
@@ -402,6 +586,16 @@ fn resolve_source_path(arg: Option<&str>) -> Result<PathBuf, io::Error> {
     Ok(Path::new(env!("CARGO_MANIFEST_DIR")).join(provided))
 }
 
+fn module_search_paths_from_env() -> Vec<PathBuf> {
+    std::env::var_os(MODULE_PATH_ENV)
+        .map(|value| {
+            std::env::split_paths(&value)
+                .filter(|path| !path.as_os_str().is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 fn register_functions(vm: &mut Vm, functions: &[FunctionDecl]) -> Result<(), io::Error> {
     for decl in functions {
         match decl.name.as_str() {
@@ -423,30 +617,55 @@ fn register_functions(vm: &mut Vm, functions: &[FunctionDecl]) -> Result<(), io:
     Ok(())
 }
 
+fn parse_flavor_arg(flag: &str, name: &str) -> Result<SourceFlavor, String> {
+    SourceFlavor::from_name(name)
+        .filter(|flavor| *flavor != SourceFlavor::Assembly)
+        .ok_or_else(|| {
+            format!(
+                "unknown {flag} flavor '{name}' (expected rustscript, javascript, lua or scheme)"
+            )
+        })
+}
+
 fn print_usage() {
     println!("Usage:");
     println!("  pd-vm-run                  (defaults to REPL)");
     println!("  pd-vm-run [source_path]");
-    println!("  pd-vm-run --repl");
+    println!("  pd-vm-run --repl [--flavor <rss|js|lua|scm>] [--jit-hot-loop <n>] [--seed <n>]");
     println!("  pd-vm-run repl");
     println!("  pd-vm-run --emit-vmbc <output.vmbc> [source_path]");
     println!("  pd-vm-run --disasm-vmbc <input.vmbc> [--show-source]");
-    println!("  pd-vm-run --record <output.pdr> [source_path]");
+    println!("  pd-vm-run --decompile <input.vmbc>   (print RustScript source)");
+    println!(
+        "  pd-vm-run --transpile <rss|js|lua|scm> <source_path>   (print source in another flavor)"
+    );
+    println!("  pd-vm-run --record <output.pdr> [--seed <n>] [source_path]");
     println!("  pd-vm-run --view-record <input.pdr>");
     println!("  pd-vm-run --debug [--stop-on-entry|--no-stop-on-entry] [source_path]");
     println!("  pd-vm-run --debug --tcp <addr> [source_path]");
+    println!(
+        "  pd-vm-run --dap <addr> [source_path]   (Debug Adapter Protocol; launch may name the program)"
+    );
     println!(
         "  pd-vm-run [--jit-hot-loop <n>] [--jit-dump] [--emit-vmbc <output.vmbc>] [source_path]"
     );
     println!("  pd-vm-run debug [--tcp <addr>] [source_path]");
+    println!("  pd-vm-run --seed <n> [source_path]   (fix the rand::/uuid:: builtin seed)");
+    println!(
+        "  pd-vm-run --module-path <dir> [source_path]   (repeatable; searched before PD_VM_PATH)"
+    );
 }
 
-fn run_repl() -> Result<(), Box<dyn std::error::Error>> {
-    println!("pd-vm REPL (RustScript)");
-    println!("history: up/down arrows, commands: .help, .quit, .cancel");
+fn run_repl(cli: &CliConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let mut session = ReplSession::new(cli.repl_flavor.unwrap_or(SourceFlavor::RustScript));
+    session.jit_hot_loop_threshold = cli.jit_hot_loop_threshold;
+    session.rng_seed = cli.rng_seed;
+    session.search_paths = cli.module_paths.clone();
+    session.search_paths.extend(module_search_paths_from_env());
+    println!("pd-vm REPL ({:?})", session.flavor);
+    println!("history: up/down arrows, commands: :help, :quit, :cancel");
     println!("state: locals persist across entries");
     let mut editor = DefaultEditor::new()?;
-    let mut session = ReplSession::default();
     let mut pending_input = String::new();
     loop {
         let prompt = if pending_input.is_empty() {
@@ -461,13 +680,19 @@ fn run_repl() -> Result<(), Box<dyn std::error::Error>> {
                     if trimmed.is_empty() {
                         continue;
                     }
-                    if let Some(action) = handle_repl_command(trimmed) {
-                        if action == ReplAction::Break {
-                            break;
+                    if let Some(command) = parse_repl_command(trimmed) {
+                        let _ = editor.add_history_entry(trimmed);
+                        match command {
+                            Ok(command) => {
+                                if run_repl_command(&mut session, command) == ReplAction::Break {
+                                    break;
+                                }
+                            }
+                            Err(message) => println!("{message}"),
                         }
                         continue;
                     }
-                } else if trimmed == ".cancel" {
+                } else if matches!(parse_repl_command(trimmed), Some(Ok(ReplCommand::Cancel))) {
                     pending_input.clear();
                     println!("pending input cleared");
                     continue;
@@ -477,7 +702,7 @@ fn run_repl() -> Result<(), Box<dyn std::error::Error>> {
                     pending_input.push('\n');
                 }
                 pending_input.push_str(line.trim_end());
-                if !is_repl_input_complete(&pending_input) {
+                if !is_repl_input_complete(&pending_input, session.flavor) {
                     continue;
                 }
 
@@ -488,37 +713,7 @@ fn run_repl() -> Result<(), Box<dyn std::error::Error>> {
                 }
 
                 let _ = editor.add_history_entry(&snippet);
-                let compiled = match compile_repl_snippet(&snippet, &session.locals) {
-                    Ok(compiled) => compiled,
-                    Err(err) => {
-                        println!("{}", render_repl_compile_error(&snippet, &err));
-                        continue;
-                    }
-                };
-                let mut vm = Vm::with_locals(compiled.program, compiled.locals);
-                if let Err(err) = register_functions(&mut vm, &compiled.functions) {
-                    println!("{err}");
-                    continue;
-                }
-                loop {
-                    match vm.run() {
-                        Ok(VmStatus::Halted) => {
-                            sync_repl_session(&vm, &mut session);
-                            if let Some(value) = vm.stack().last() {
-                                println!("=> {}", format_value(value));
-                            } else {
-                                println!("=> <empty>");
-                            }
-                            break;
-                        }
-                        Ok(VmStatus::Yielded) => continue,
-                        Err(err) => {
-                            sync_repl_session(&vm, &mut session);
-                            println!("{}", render_vm_error(&vm, &err));
-                            break;
-                        }
-                    }
-                }
+                print_repl_result(session.eval(&snippet, true));
             }
             Err(ReadlineError::Interrupted) => {
                 if pending_input.is_empty() {
@@ -540,25 +735,132 @@ fn run_repl() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-#[derive(Default)]
+fn print_repl_result(result: Result<Option<Value>, String>) {
+    match result {
+        Ok(Some(value)) => println!("=> {}", format_value(&value)),
+        Ok(None) => println!("=> <empty>"),
+        Err(message) => println!("{message}"),
+    }
+}
+
 struct ReplSession {
+    flavor: SourceFlavor,
     locals: BTreeMap<String, Value>,
+    /// Inputs that ran to completion, as compiled, for `:save`.
+    history: Vec<String>,
+    /// Bytecode and JIT state of the last input, for `:disasm` and `:jit`.
+    last_program: Option<Program>,
+    last_jit_info: Option<String>,
+    jit_hot_loop_threshold: Option<u32>,
+    rng_seed: Option<u64>,
+    search_paths: Vec<PathBuf>,
 }
 
-fn sync_repl_session(vm: &Vm, session: &mut ReplSession) {
-    let Some(debug) = vm.debug_info() else {
-        return;
-    };
-    let mut next = BTreeMap::new();
-    for local in &debug.locals {
-        let Some(value) = vm.locals().get(local.index as usize) else {
-            continue;
+impl ReplSession {
+    fn new(flavor: SourceFlavor) -> Self {
+        Self {
+            flavor,
+            locals: BTreeMap::new(),
+            history: Vec::new(),
+            last_program: None,
+            last_jit_info: None,
+            jit_hot_loop_threshold: None,
+            rng_seed: None,
+            search_paths: Vec::new(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.locals.clear();
+        self.history.clear();
+        self.last_program = None;
+        self.last_jit_info = None;
+    }
+
+    /// Compiles and runs `input` against the session's locals and returns
+    /// the value it left on top of the stack. Only a `commit`ted input
+    /// updates the locals, history, and last program.
+    fn eval(&mut self, input: &str, commit: bool) -> Result<Option<Value>, String> {
+        let (compiled, accepted) = compile_repl_snippet(input, self.flavor, &self.locals)
+            .map_err(|err| render_repl_compile_error(input, &err))?;
+        let program = commit.then(|| compiled.program.clone());
+        let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+        if let Some(hot_loop) = self.jit_hot_loop_threshold {
+            let mut jit_config = vm.jit_config().clone();
+            jit_config.hot_loop_threshold = hot_loop;
+            vm.set_jit_config(jit_config);
+        }
+        if let Some(seed) = self.rng_seed {
+            vm.set_rng_seed(seed);
+        }
+        register_functions(&mut vm, &compiled.functions).map_err(|err| err.to_string())?;
+        let result = loop {
+            match vm.run() {
+                Ok(VmStatus::Halted) => break Ok(vm.stack().last().cloned()),
+                Ok(VmStatus::Yielded) => continue,
+                Err(err) => break Err(render_vm_error(&vm, &err)),
+            }
+        };
+        if commit {
+            self.sync_locals(&vm);
+            self.last_program = program;
+            self.last_jit_info = Some(vm.dump_jit_info());
+            if result.is_ok() {
+                self.history.push(accepted);
+            }
+        }
+        result
+    }
+
+    /// Runs the file at `path` as one input. A file in another flavor is
+    /// transpiled to the session's flavor first.
+    fn load(&mut self, path: &Path) -> Result<Option<Value>, String> {
+        let flavor = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(SourceFlavor::from_extension);
+        let source = match flavor {
+            Some(flavor) if flavor != self.flavor => transpile_source_file_with_search_paths(
+                path,
+                self.search_paths.clone(),
+                self.flavor,
+            )
+            .map_err(|err| format!("{}: {err}", path.display()))?,
+            _ => {
+                std::fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?
+            }
+        };
+        self.eval(&source, true)
+    }
+
+    /// Writes the session's inputs to `path`, transpiled to the flavor its
+    /// extension names.
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let mut source = self.history.join("\n");
+        source.push('\n');
+        let to = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(SourceFlavor::from_extension)
+            .unwrap_or(self.flavor);
+        if to != self.flavor {
+            source = transpile_source(&source, self.flavor, to).map_err(|err| err.to_string())?;
+        }
+        std::fs::write(path, source).map_err(|err| format!("{}: {err}", path.display()))
+    }
+
+    fn sync_locals(&mut self, vm: &Vm) {
+        let Some(debug) = vm.debug_info() else {
+            return;
         };
-        if is_repl_serializable_value(value) {
-            next.insert(local.name.clone(), value.clone());
+        let mut next = BTreeMap::new();
+        for local in &debug.locals {
+            if let Some(value) = vm.locals().get(local.index as usize) {
+                next.insert(local.name.clone(), value.clone());
+            }
         }
+        self.locals = next;
     }
-    session.locals = next;
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -567,50 +869,147 @@ enum ReplAction {
     Break,
 }
 
-fn handle_repl_command(line: &str) -> Option<ReplAction> {
-    match line {
-        ".quit" | ".exit" => Some(ReplAction::Break),
-        ".cancel" => {
-            println!("no pending input");
-            Some(ReplAction::Continue)
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ReplCommand {
+    Help,
+    Quit,
+    Cancel,
+    Load(PathBuf),
+    Disasm,
+    Jit,
+    Type(String),
+    Reset,
+    Save(PathBuf),
+}
+
+/// Parses a `:command` line; the older `.command` spelling is accepted too.
+/// Returns `None` for lines that are source input.
+fn parse_repl_command(line: &str) -> Option<Result<ReplCommand, String>> {
+    let rest = line.strip_prefix(':').or_else(|| line.strip_prefix('.'))?;
+    if !rest.starts_with(|ch: char| ch.is_ascii_alphabetic()) {
+        return None;
+    }
+    let (name, arg) = rest
+        .split_once(char::is_whitespace)
+        .map(|(name, arg)| (name, arg.trim()))
+        .unwrap_or((rest, ""));
+    let command = match name {
+        "help" => ReplCommand::Help,
+        "quit" | "exit" => ReplCommand::Quit,
+        "cancel" => ReplCommand::Cancel,
+        "disasm" => ReplCommand::Disasm,
+        "jit" => ReplCommand::Jit,
+        "reset" => ReplCommand::Reset,
+        "load" | "save" | "type" if arg.is_empty() => {
+            let operand = if name == "type" { "expr" } else { "file" };
+            return Some(Err(format!("usage: :{name} <{operand}>")));
         }
-        ".help" => {
+        "load" => ReplCommand::Load(PathBuf::from(arg)),
+        "save" => ReplCommand::Save(PathBuf::from(arg)),
+        "type" => ReplCommand::Type(arg.to_string()),
+        _ => return Some(Err(format!("unknown command: {line}"))),
+    };
+    if !arg.is_empty()
+        && !matches!(
+            command,
+            ReplCommand::Load(_) | ReplCommand::Save(_) | ReplCommand::Type(_)
+        )
+    {
+        return Some(Err(format!(":{name} takes no arguments")));
+    }
+    Some(Ok(command))
+}
+
+fn run_repl_command(session: &mut ReplSession, command: ReplCommand) -> ReplAction {
+    match command {
+        ReplCommand::Quit => return ReplAction::Break,
+        ReplCommand::Cancel => println!("no pending input"),
+        ReplCommand::Help => {
             println!("commands:");
-            println!("  .help      show commands");
-            println!("  .quit      quit repl");
-            println!("  .exit      quit repl");
-            println!("  .cancel    clear pending multiline input");
-            Some(ReplAction::Continue)
+            println!("  :help         show commands");
+            println!("  :quit         quit repl (also :exit)");
+            println!("  :cancel       clear pending multiline input");
+            println!("  :load <file>  run a source file in the session");
+            println!("  :disasm       disassemble the last input");
+            println!("  :jit          show the last input's JIT traces");
+            println!("  :type <expr>  show the type of an expression");
+            println!("  :reset        forget all locals and inputs");
+            println!("  :save <file>  write the session's inputs as a source file");
         }
-        _ if line.starts_with('.') => {
-            println!("unknown command: {line}");
-            Some(ReplAction::Continue)
+        ReplCommand::Load(path) => print_repl_result(session.load(&path)),
+        ReplCommand::Disasm => match &session.last_program {
+            Some(program) => print!("{}", disassemble_program(program)),
+            None => println!("no input has run yet"),
+        },
+        ReplCommand::Jit => match &session.last_jit_info {
+            Some(info) => print!("{info}"),
+            None => println!("no input has run yet"),
+        },
+        ReplCommand::Type(expr) => match session.eval(&expr, false) {
+            Ok(Some(value)) => println!("{}", value_type_name(&value)),
+            Ok(None) => println!("<empty>"),
+            Err(message) => println!("{message}"),
+        },
+        ReplCommand::Reset => {
+            session.reset();
+            println!("session reset");
         }
-        _ => None,
-    }
+        ReplCommand::Save(path) => match session.save(&path) {
+            Ok(()) => println!(
+                "saved {} inputs to {}",
+                session.history.len(),
+                path.display()
+            ),
+            Err(message) => println!("{message}"),
+        },
+    }
+    ReplAction::Continue
 }
 
+/// Compiles `input` after a prelude that redeclares the session's locals.
+/// Returns the program and the input as compiled, which may have gained a
+/// trailing `;`.
 fn compile_repl_snippet(
     input: &str,
+    flavor: SourceFlavor,
     locals: &BTreeMap<String, Value>,
-) -> Result<vm::CompiledProgram, vm::SourceError> {
+) -> Result<(vm::CompiledProgram, String), vm::SourceError> {
     let trimmed = input.trim_end();
-    let source = build_repl_source(trimmed, locals);
-    match compile_source(&source) {
-        Ok(compiled) => Ok(compiled),
+    let bindings = locals
+        .iter()
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect::<Vec<_>>();
+    // Only assembly has no bindings, and the REPL never runs it.
+    let prelude = transpile_bindings(&bindings, flavor).unwrap_or_default();
+    let prelude_lines = prelude.lines().count();
+    match compile_source_with_flavor(&format!("{prelude}{trimmed}"), flavor) {
+        Ok(compiled) => Ok((compiled, trimmed.to_string())),
         Err(first_err) => {
-            if trimmed.ends_with(';') {
-                return Err(remap_repl_source_error(first_err, locals.len()));
+            let first_err = remap_repl_source_error(first_err, prelude_lines);
+            let statement_flavor =
+                matches!(flavor, SourceFlavor::RustScript | SourceFlavor::JavaScript);
+            if !statement_flavor || trimmed.ends_with(';') {
+                return Err(first_err);
             }
             let fallback = format!("{trimmed};");
-            let fallback_source = build_repl_source(&fallback, locals);
-            compile_source(&fallback_source)
-                .map_err(|_| remap_repl_source_error(first_err, locals.len()))
+            compile_source_with_flavor(&format!("{prelude}{fallback}"), flavor)
+                .map(|compiled| (compiled, fallback))
+                .map_err(|_| first_err)
         }
     }
 }
 
-fn is_repl_input_complete(input: &str) -> bool {
+fn is_repl_input_complete(input: &str, flavor: SourceFlavor) -> bool {
+    match flavor {
+        SourceFlavor::Lua => is_lua_input_complete(input),
+        SourceFlavor::Scheme => is_scheme_input_complete(input),
+        _ => is_brace_input_complete(input, flavor == SourceFlavor::JavaScript),
+    }
+}
+
+/// RustScript and JavaScript input is complete once its brackets and strings
+/// close and it does not end in an operator.
+fn is_brace_input_complete(input: &str, javascript: bool) -> bool {
     #[derive(Clone, Copy, Debug, PartialEq, Eq)]
     enum Delimiter {
         Paren,
@@ -620,7 +1019,7 @@ fn is_repl_input_complete(input: &str) -> bool {
 
     let mut stack: Vec<Delimiter> = Vec::new();
     let mut chars = input.chars().peekable();
-    let mut in_string = false;
+    let mut in_string: Option<char> = None;
     let mut escaped = false;
     let mut in_line_comment = false;
     let mut in_block_comment = false;
@@ -643,16 +1042,16 @@ fn is_repl_input_complete(input: &str) -> bool {
             }
             continue;
         }
-        if in_string {
+        if let Some(quote) = in_string {
             if escaped {
                 escaped = false;
                 continue;
             }
             match ch {
                 '\\' => escaped = true,
-                '"' => {
-                    in_string = false;
-                    code.push('"');
+                _ if ch == quote => {
+                    in_string = None;
+                    code.push(quote);
                 }
                 _ => {}
             }
@@ -677,8 +1076,12 @@ fn is_repl_input_complete(input: &str) -> bool {
 
         match ch {
             '"' => {
-                in_string = true;
-                code.push('"');
+                in_string = Some(ch);
+                code.push(ch);
+            }
+            '\'' | '`' if javascript => {
+                in_string = Some(ch);
+                code.push(ch);
             }
             '(' => {
                 stack.push(Delimiter::Paren);
@@ -714,7 +1117,7 @@ fn is_repl_input_complete(input: &str) -> bool {
         }
     }
 
-    if in_string || in_block_comment || !stack.is_empty() {
+    if in_string.is_some() || in_block_comment || !stack.is_empty() {
         return false;
     }
 
@@ -732,23 +1135,180 @@ fn is_repl_input_complete(input: &str) -> bool {
         .any(|token| trimmed.ends_with(token))
 }
 
-fn build_repl_source(input: &str, locals: &BTreeMap<String, Value>) -> String {
-    if locals.is_empty() {
-        return input.to_string();
+/// Lua input is complete once every `function`, `if`, `do`, and `repeat`
+/// block is closed by `end` or `until`, its brackets and strings close, and
+/// it does not end in an operator.
+fn is_lua_input_complete(input: &str) -> bool {
+    let chars: Vec<char> = input.chars().collect();
+    let mut blocks = 0i32;
+    let mut brackets = 0i32;
+    let mut code = String::with_capacity(input.len());
+    let mut index = 0usize;
+
+    while let Some(&ch) = chars.get(index) {
+        if ch.is_ascii_alphabetic() || ch == '_' {
+            let start = index;
+            while chars
+                .get(index)
+                .is_some_and(|ch| ch.is_ascii_alphanumeric() || *ch == '_')
+            {
+                index += 1;
+            }
+            let word: String = chars[start..index].iter().collect();
+            match word.as_str() {
+                "function" | "if" | "do" | "repeat" => blocks += 1,
+                "end" | "until" => blocks -= 1,
+                _ => {}
+            }
+            code.push_str(&word);
+            continue;
+        }
+        if ch == '-' && chars.get(index + 1) == Some(&'-') {
+            index += 2;
+            if let Some(level) = lua_long_bracket_level(&chars[index..]) {
+                let Some(end) = lua_long_bracket_end(&chars, index + level + 2, level) else {
+                    return false;
+                };
+                index = end;
+            } else {
+                while chars.get(index).is_some_and(|ch| *ch != '\n') {
+                    index += 1;
+                }
+            }
+            code.push(' ');
+            continue;
+        }
+        if let Some(level) = lua_long_bracket_level(&chars[index..]) {
+            let Some(end) = lua_long_bracket_end(&chars, index + level + 2, level) else {
+                return false;
+            };
+            index = end;
+            code.push_str("\"\"");
+            continue;
+        }
+        if ch == '"' || ch == '\'' {
+            index += 1;
+            let mut escaped = false;
+            loop {
+                let Some(&next) = chars.get(index) else {
+                    return false;
+                };
+                index += 1;
+                if escaped {
+                    escaped = false;
+                } else if next == '\\' {
+                    escaped = true;
+                } else if next == ch {
+                    break;
+                }
+            }
+            code.push_str("\"\"");
+            continue;
+        }
+        match ch {
+            '(' | '[' | '{' => brackets += 1,
+            ')' | ']' | '}' => brackets -= 1,
+            _ => {}
+        }
+        code.push(ch);
+        index += 1;
+    }
+
+    if blocks > 0 || brackets > 0 {
+        return false;
+    }
+    let trimmed = code.trim_end();
+    let last_word = trimmed
+        .rsplit(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_'))
+        .next()
+        .unwrap_or_default();
+    if matches!(last_word, "and" | "or" | "not") {
+        return false;
+    }
+    const TRAILING_INCOMPLETE_TOKENS: [&str; 19] = [
+        "..", "==", "~=", "<=", ">=", "=", ",", ".", ":", "+", "-", "*", "/", "%", "^", "#", "<",
+        ">", "&",
+    ];
+    !TRAILING_INCOMPLETE_TOKENS
+        .iter()
+        .any(|token| trimmed.ends_with(token))
+}
+
+/// The level of the Lua long bracket (`[[`, `[==[`) `chars` starts with.
+fn lua_long_bracket_level(chars: &[char]) -> Option<usize> {
+    if chars.first() != Some(&'[') {
+        return None;
     }
+    let level = chars[1..].iter().take_while(|ch| **ch == '=').count();
+    (chars.get(level + 1) == Some(&'[')).then_some(level)
+}
 
-    let mut source = String::new();
-    for (name, value) in locals {
-        if let Some(literal) = render_repl_value_literal(value) {
-            source.push_str("let ");
-            source.push_str(name);
-            source.push_str(" = ");
-            source.push_str(&literal);
-            source.push_str(";\n");
+/// The index just past the `]]` (or `]==]`) closing a long bracket of
+/// `level`, searching from `from`.
+fn lua_long_bracket_end(chars: &[char], from: usize, level: usize) -> Option<usize> {
+    (from..chars.len()).find_map(|start| {
+        let close = chars.get(start..start + level + 2)?;
+        let matches = close[0] == ']'
+            && close[level + 1] == ']'
+            && close[1..=level].iter().all(|ch| *ch == '=');
+        matches.then_some(start + level + 2)
+    })
+}
+
+/// Scheme input is complete once its parentheses, strings, and block
+/// comments close.
+fn is_scheme_input_complete(input: &str) -> bool {
+    let mut depth = 0i32;
+    let mut chars = input.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            ';' => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+            '"' => {
+                let mut escaped = false;
+                loop {
+                    let Some(next) = chars.next() else {
+                        return false;
+                    };
+                    if escaped {
+                        escaped = false;
+                    } else if next == '\\' {
+                        escaped = true;
+                    } else if next == '"' {
+                        break;
+                    }
+                }
+            }
+            '#' if chars.peek() == Some(&'|') => {
+                chars.next();
+                let mut closed = false;
+                while let Some(next) = chars.next() {
+                    if next == '|' && chars.peek() == Some(&'#') {
+                        chars.next();
+                        closed = true;
+                        break;
+                    }
+                }
+                if !closed {
+                    return false;
+                }
+            }
+            // A character literal such as `#\(`.
+            '#' if chars.peek() == Some(&'\\') => {
+                chars.next();
+                chars.next();
+            }
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            _ => {}
         }
     }
-    source.push_str(input);
-    source
+    depth <= 0
 }
 
 fn remap_repl_source_error(error: vm::SourceError, prelude_lines: usize) -> vm::SourceError {
@@ -778,74 +1338,18 @@ fn render_repl_compile_error(snippet: &str, err: &vm::SourceError) -> String {
     }
 }
 
-fn render_repl_value_literal(value: &Value) -> Option<String> {
-    match value {
-        Value::Null => None,
-        Value::Int(number) => {
-            if *number == i64::MIN {
-                None
-            } else {
-                Some(number.to_string())
-            }
-        }
-        Value::Float(_) => None,
-        Value::Bool(flag) => Some(flag.to_string()),
-        Value::String(text) => Some(format!("\"{}\"", escape_repl_string_literal(text))),
-        Value::Array(items) => {
-            let mut rendered = Vec::with_capacity(items.len());
-            for item in items {
-                rendered.push(render_repl_value_literal(item)?);
-            }
-            Some(format!("[{}]", rendered.join(", ")))
-        }
-        Value::Map(entries) => {
-            let mut rendered = Vec::with_capacity(entries.len());
-            for (key, item) in entries {
-                let rendered_key = render_repl_map_key_literal(key)?;
-                let rendered_value = render_repl_value_literal(item)?;
-                rendered.push(format!("{rendered_key}: {rendered_value}"));
-            }
-            Some(format!("{{{}}}", rendered.join(", ")))
-        }
-    }
-}
-
-fn render_repl_map_key_literal(value: &Value) -> Option<String> {
+fn value_type_name(value: &Value) -> &'static str {
     match value {
-        Value::Null => None,
-        Value::Int(number) => {
-            if *number == i64::MIN {
-                None
-            } else {
-                Some(number.to_string())
-            }
-        }
-        Value::Bool(flag) => Some(flag.to_string()),
-        Value::String(text) => Some(format!("\"{}\"", escape_repl_string_literal(text))),
-        Value::Float(_) | Value::Array(_) | Value::Map(_) => None,
+        Value::Null => "null",
+        Value::Int(_) => "int",
+        Value::Float(_) => "float",
+        Value::Bool(_) => "bool",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Map(_) => "map",
     }
 }
 
-fn escape_repl_string_literal(value: &str) -> String {
-    let mut escaped = String::with_capacity(value.len());
-    for ch in value.chars() {
-        match ch {
-            '\\' => escaped.push_str("\\\\"),
-            '"' => escaped.push_str("\\\""),
-            '\n' => escaped.push_str("\\n"),
-            '\r' => escaped.push_str("\\r"),
-            '\t' => escaped.push_str("\\t"),
-            '\0' => escaped.push_str("\\0"),
-            other => escaped.push(other),
-        }
-    }
-    escaped
-}
-
-fn is_repl_serializable_value(value: &Value) -> bool {
-    render_repl_value_literal(value).is_some()
-}
-
 struct PrintFunction;
 
 impl HostFunction for PrintFunction {
@@ -906,9 +1410,13 @@ fn format_value(value: &Value) -> String {
 #[cfg(test)]
 mod tests {
     use std::collections::BTreeMap;
+    use std::path::PathBuf;
 
-    use super::parse_cli_args;
-    use vm::Value;
+    use super::{
+        ReplCommand, ReplSession, compile_repl_snippet, is_repl_input_complete, parse_cli_args,
+        parse_repl_command, value_type_name,
+    };
+    use vm::{SourceFlavor, Value};
 
     fn s(value: &str) -> String {
         value.to_string()
@@ -943,6 +1451,23 @@ mod tests {
         assert_eq!(cfg.source.as_deref(), Some("examples/example.lua"));
     }
 
+    #[test]
+    fn parse_cli_dap_with_optional_source() {
+        let cfg = parse_cli_args(&[s("--dap"), s("127.0.0.1:4711")]).expect("parse should succeed");
+        assert!(cfg.debug);
+        assert_eq!(cfg.dap_addr.as_deref(), Some("127.0.0.1:4711"));
+        assert!(cfg.source.is_none());
+
+        let err = parse_cli_args(&[
+            s("--dap"),
+            s("127.0.0.1:4711"),
+            s("--tcp"),
+            s("127.0.0.1:9002"),
+        ])
+        .expect_err("dap and tcp should conflict");
+        assert!(err.contains("--dap cannot be combined with --tcp"));
+    }
+
     #[test]
     fn parse_cli_legacy_debug_command() {
         let cfg =
@@ -971,6 +1496,23 @@ mod tests {
         assert_eq!(cfg.source.as_deref(), Some("examples/example.rss"));
     }
 
+    #[test]
+    fn parse_cli_module_paths_accumulate() {
+        let cfg = parse_cli_args(&[
+            s("--module-path"),
+            s("lib"),
+            s("--module-path"),
+            s("vendor/rss"),
+            s("examples/example.rss"),
+        ])
+        .expect("parse should succeed");
+        assert_eq!(
+            cfg.module_paths,
+            vec![PathBuf::from("lib"), PathBuf::from("vendor/rss")]
+        );
+        assert_eq!(cfg.source.as_deref(), Some("examples/example.rss"));
+    }
+
     #[test]
     fn parse_cli_emit_vmbc_path() {
         let cfg = parse_cli_args(&[
@@ -1024,6 +1566,50 @@ mod tests {
         assert!(err.contains("does not accept a source path"));
     }
 
+    #[test]
+    fn parse_cli_decompile_vmbc_path() {
+        let cfg = parse_cli_args(&[s("--decompile"), s("out/program.vmbc")])
+            .expect("parse should succeed");
+        assert_eq!(cfg.decompile_vmbc_path.as_deref(), Some("out/program.vmbc"));
+        assert!(cfg.source.is_none());
+    }
+
+    #[test]
+    fn parse_cli_decompile_requires_path() {
+        let err = parse_cli_args(&[s("--decompile")]).expect_err("parse should fail");
+        assert!(err.contains("missing value for --decompile"));
+    }
+
+    #[test]
+    fn parse_cli_decompile_rejects_runtime_flags() {
+        let err = parse_cli_args(&[s("--decompile"), s("program.vmbc"), s("--debug")])
+            .expect_err("parse should fail");
+        assert!(err.contains("decompile mode cannot be combined"));
+    }
+
+    #[test]
+    fn parse_cli_transpile_flavor() {
+        let cfg = parse_cli_args(&[s("--transpile"), s("lua"), s("examples/example.rss")])
+            .expect("parse should succeed");
+        assert_eq!(cfg.transpile_flavor, Some(SourceFlavor::Lua));
+        assert_eq!(cfg.source.as_deref(), Some("examples/example.rss"));
+        let cfg = parse_cli_args(&[s("--transpile"), s("SCM"), s("examples/example.rss")])
+            .expect("parse should succeed");
+        assert_eq!(cfg.transpile_flavor, Some(SourceFlavor::Scheme));
+    }
+
+    #[test]
+    fn parse_cli_transpile_rejects_unknown_flavors_and_missing_sources() {
+        let err = parse_cli_args(&[s("--transpile"), s("pda"), s("examples/example.rss")])
+            .expect_err("parse should fail");
+        assert!(err.contains("unknown --transpile flavor 'pda'"));
+        let err = parse_cli_args(&[s("--transpile"), s("js")]).expect_err("parse should fail");
+        assert!(err.contains("transpile mode requires a source path"));
+        let err = parse_cli_args(&[s("--transpile"), s("js"), s("a.rss"), s("--debug")])
+            .expect_err("parse should fail");
+        assert!(err.contains("transpile mode cannot be combined"));
+    }
+
     #[test]
     fn parse_cli_record_path() {
         let cfg = parse_cli_args(&[s("--record"), s("out/run.pdr"), s("examples/example.rss")])
@@ -1047,6 +1633,26 @@ mod tests {
         assert!(err.contains("record mode"));
     }
 
+    #[test]
+    fn parse_cli_record_with_seed() {
+        let cfg = parse_cli_args(&[
+            s("--record"),
+            s("out/run.pdr"),
+            s("--seed"),
+            s("42"),
+            s("examples/example.rss"),
+        ])
+        .expect("parse should succeed");
+        assert_eq!(cfg.rng_seed, Some(42));
+        assert_eq!(cfg.record_path.as_deref(), Some("out/run.pdr"));
+    }
+
+    #[test]
+    fn parse_cli_rejects_invalid_seed() {
+        let err = parse_cli_args(&[s("--seed"), s("abc")]).expect_err("parse should fail");
+        assert!(err.contains("invalid --seed value"));
+    }
+
     #[test]
     fn parse_cli_repl_flag() {
         let cfg = parse_cli_args(&[s("--repl")]).expect("parse should succeed");
@@ -1073,19 +1679,74 @@ mod tests {
         assert!(err.contains("cannot be combined"));
     }
 
+    #[test]
+    fn parse_cli_repl_flavor() {
+        let cfg =
+            parse_cli_args(&[s("--repl"), s("--flavor"), s("lua")]).expect("parse should succeed");
+        assert!(cfg.repl);
+        assert_eq!(cfg.repl_flavor, Some(SourceFlavor::Lua));
+        let cfg = parse_cli_args(&[
+            s("repl"),
+            s("--flavor"),
+            s("scm"),
+            s("--jit-hot-loop"),
+            s("2"),
+        ])
+        .expect("parse should succeed");
+        assert_eq!(cfg.repl_flavor, Some(SourceFlavor::Scheme));
+        assert_eq!(cfg.jit_hot_loop_threshold, Some(2));
+    }
+
+    #[test]
+    fn parse_cli_flavor_requires_repl() {
+        let err = parse_cli_args(&[s("--flavor"), s("js"), s("examples/example.js")])
+            .expect_err("parse should fail");
+        assert!(err.contains("--flavor requires repl mode"));
+        let err =
+            parse_cli_args(&[s("--repl"), s("--flavor"), s("pda")]).expect_err("parse should fail");
+        assert!(err.contains("unknown --flavor flavor 'pda'"));
+    }
+
+    #[test]
+    fn repl_commands_parse_with_either_prefix() {
+        assert_eq!(parse_repl_command("1 + 2;"), None);
+        assert_eq!(parse_repl_command("::continue::"), None);
+        assert_eq!(parse_repl_command(".quit"), Some(Ok(ReplCommand::Quit)));
+        assert_eq!(parse_repl_command(":exit"), Some(Ok(ReplCommand::Quit)));
+        assert_eq!(
+            parse_repl_command(":type x + 1"),
+            Some(Ok(ReplCommand::Type("x + 1".to_string())))
+        );
+        assert_eq!(
+            parse_repl_command(":save  session.rss "),
+            Some(Ok(ReplCommand::Save(PathBuf::from("session.rss"))))
+        );
+        assert_eq!(
+            parse_repl_command(":load"),
+            Some(Err("usage: :load <file>".to_string()))
+        );
+        assert_eq!(
+            parse_repl_command(":reset all"),
+            Some(Err(":reset takes no arguments".to_string()))
+        );
+        assert!(matches!(parse_repl_command(":bogus"), Some(Err(_))));
+    }
+
     #[test]
     fn repl_compile_falls_back_to_expression_semicolon() {
-        let compiled =
-            super::compile_repl_snippet("1 + 2", &BTreeMap::new()).expect("compile should succeed");
+        let (compiled, accepted) =
+            compile_repl_snippet("1 + 2", SourceFlavor::RustScript, &BTreeMap::new())
+                .expect("compile should succeed");
         assert_eq!(compiled.locals, 0);
+        assert_eq!(accepted, "1 + 2;");
     }
 
     #[test]
     fn repl_compile_uses_persisted_locals() {
         let mut locals = BTreeMap::new();
         locals.insert("x".to_string(), Value::Int(41));
-        let compiled =
-            super::compile_repl_snippet("x + 1", &locals).expect("compile should succeed");
+        let (compiled, _) = compile_repl_snippet("x + 1", SourceFlavor::RustScript, &locals)
+            .expect("compile should succeed");
         assert!(compiled.locals >= 1);
     }
 
@@ -1093,41 +1754,194 @@ mod tests {
     fn repl_compile_remaps_parse_error_line_numbers() {
         let mut locals = BTreeMap::new();
         locals.insert("x".to_string(), Value::Int(1));
-        match super::compile_repl_snippet("let y = ;", &locals) {
+        match compile_repl_snippet("let y = ;", SourceFlavor::RustScript, &locals) {
             Err(vm::SourceError::Parse(parse)) => assert_eq!(parse.line, 1),
             Err(other) => panic!("expected parse error, got {other}"),
             Ok(_) => panic!("expected parse error, got successful compile"),
         }
     }
 
+    #[test]
+    fn repl_session_keeps_bindings_in_every_flavor() {
+        let inputs = [
+            (
+                SourceFlavor::RustScript,
+                "let xs = [1, 2.5, null];",
+                "xs.length + 39",
+            ),
+            (
+                SourceFlavor::JavaScript,
+                "let xs = [1, 2.5, null];",
+                "xs.length + 39",
+            ),
+            (SourceFlavor::Lua, "local xs = {1, 2, 3}", "#xs + 39"),
+            (
+                SourceFlavor::Scheme,
+                "(define item-count 3)",
+                "(+ item-count 39)",
+            ),
+        ];
+        for (flavor, first, second) in inputs {
+            let mut session = ReplSession::new(flavor);
+            session.eval(first, true).expect("first input should run");
+            assert_eq!(
+                session.eval(second, true),
+                Ok(Some(Value::Int(42))),
+                "{flavor:?}"
+            );
+            assert_eq!(session.history.len(), 2);
+        }
+    }
+
+    #[test]
+    fn repl_type_does_not_commit() {
+        let mut session = ReplSession::new(SourceFlavor::RustScript);
+        session
+            .eval("let x = 1.5;", true)
+            .expect("input should run");
+        let value = session
+            .eval("x", false)
+            .expect("type should run")
+            .expect("x leaves a value");
+        assert_eq!(value_type_name(&value), "float");
+        assert_eq!(session.history, vec!["let x = 1.5;".to_string()]);
+        assert!(session.last_program.is_some());
+
+        session.reset();
+        assert!(session.locals.is_empty());
+        assert!(session.history.is_empty());
+        assert!(session.last_program.is_none());
+    }
+
+    #[test]
+    fn repl_save_and_load_cross_flavors() {
+        let dir = std::env::temp_dir().join(format!("pd-vm-repl-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir should be created");
+        let module = dir.join("setup.js");
+        std::fs::write(&module, "let base = 40;\nlet names = [\"a\", \"b\"];\n")
+            .expect("setup file should be written");
+
+        let mut session = ReplSession::new(SourceFlavor::Lua);
+        session.load(&module).expect("load should run");
+        session
+            .eval("local total = base + #names", true)
+            .expect("input should run");
+        let saved = dir.join("session.rss");
+        session.save(&saved).expect("save should succeed");
+
+        let compiled = vm::compile_source_file(&saved).expect("saved session should compile");
+        let mut vm = compiled.into_vm();
+        vm.run().expect("saved session should run");
+        let debug = vm.debug_info().expect("debug info");
+        let total = debug
+            .locals
+            .iter()
+            .find(|local| local.name == "total")
+            .expect("total should be a local");
+        assert_eq!(vm.locals()[total.index as usize], Value::Int(42));
+        std::fs::remove_dir_all(&dir).expect("temp dir should be removed");
+    }
+
     #[test]
     fn repl_input_complete_for_balanced_match_block() {
         let input = "let b = match a {\n    Option::Some(String) => 2,\n    _ => 3,\n};";
-        assert!(super::is_repl_input_complete(input));
+        assert!(is_repl_input_complete(input, SourceFlavor::RustScript));
     }
 
     #[test]
     fn repl_input_incomplete_for_open_brace() {
-        assert!(!super::is_repl_input_complete("let b = match a {"));
+        assert!(!is_repl_input_complete(
+            "let b = match a {",
+            SourceFlavor::RustScript
+        ));
     }
 
     #[test]
     fn repl_input_incomplete_for_unclosed_string() {
-        assert!(!super::is_repl_input_complete("let s = \"hello"));
+        assert!(!is_repl_input_complete(
+            "let s = \"hello",
+            SourceFlavor::RustScript
+        ));
+        assert!(!is_repl_input_complete(
+            "let s = `a {",
+            SourceFlavor::JavaScript
+        ));
+        assert!(is_repl_input_complete(
+            "let s = '{';",
+            SourceFlavor::JavaScript
+        ));
     }
 
     #[test]
     fn repl_input_incomplete_for_unclosed_block_comment() {
-        assert!(!super::is_repl_input_complete("let a = 1; /* comment"));
+        assert!(!is_repl_input_complete(
+            "let a = 1; /* comment",
+            SourceFlavor::RustScript
+        ));
     }
 
     #[test]
     fn repl_input_ignores_comment_delimiters() {
-        assert!(super::is_repl_input_complete("// {\nlet a = 1;"));
+        assert!(is_repl_input_complete(
+            "// {\nlet a = 1;",
+            SourceFlavor::RustScript
+        ));
     }
 
     #[test]
     fn repl_input_incomplete_for_trailing_operator() {
-        assert!(!super::is_repl_input_complete("let a = 1 +"));
+        assert!(!is_repl_input_complete(
+            "let a = 1 +",
+            SourceFlavor::RustScript
+        ));
+    }
+
+    #[test]
+    fn repl_lua_input_waits_for_block_ends() {
+        assert!(!is_repl_input_complete(
+            "for i = 1, 3 do",
+            SourceFlavor::Lua
+        ));
+        assert!(!is_repl_input_complete(
+            "local function f(a)\n  if a then",
+            SourceFlavor::Lua
+        ));
+        assert!(is_repl_input_complete(
+            "local function f(a)\n  if a then return 1 elseif b then return 2 end\nend",
+            SourceFlavor::Lua
+        ));
+        assert!(is_repl_input_complete(
+            "local s = \"end\" -- do",
+            SourceFlavor::Lua
+        ));
+        assert!(!is_repl_input_complete("local s = [[do", SourceFlavor::Lua));
+        assert!(!is_repl_input_complete(
+            "local ok = a and",
+            SourceFlavor::Lua
+        ));
+        assert!(is_repl_input_complete(
+            "local command = 1",
+            SourceFlavor::Lua
+        ));
+    }
+
+    #[test]
+    fn repl_scheme_input_waits_for_closing_parens() {
+        assert!(!is_repl_input_complete(
+            "(define (f a)\n  (+ a 1)",
+            SourceFlavor::Scheme
+        ));
+        assert!(is_repl_input_complete(
+            "(define s \")(\") ; (",
+            SourceFlavor::Scheme
+        ));
+        assert!(is_repl_input_complete(
+            "(display #\\()",
+            SourceFlavor::Scheme
+        ));
+        assert!(!is_repl_input_complete(
+            "#| (define x 1) ",
+            SourceFlavor::Scheme
+        ));
     }
 }
@@ -27,13 +27,89 @@ pub(crate) enum BuiltinFunction {
     ToString = 23,
     TypeOf = 24,
     Assert = 25,
+    RandInt = 26,
+    RandFloat = 27,
+    RandChoice = 28,
+    RandShuffle = 29,
+    UuidV4 = 30,
+    ArraySort = 31,
+    ArraySortByKeys = 32,
+    ArrayBinarySearch = 33,
+    ArrayIndexOf = 34,
+    ArrayContains = 35,
+    ArrayDedup = 36,
+    ArrayRemove = 37,
+    ArrayInsert = 38,
+    ArrayPop = 39,
+    MapRemove = 40,
+    MapHasKey = 41,
+    MapValues = 42,
+    MapEntries = 43,
+    Format = 44,
+    LuaFind = 45,
+    LuaMatch = 46,
+    LuaGmatch = 47,
+    LuaGsub = 48,
+    LuaSetMetatable = 49,
+    LuaGetMetatable = 50,
+    LuaRawGet = 51,
+    LuaRawSet = 52,
+    LuaRawEqual = 53,
+    LuaMetamethod = 54,
+    LuaIndexOwner = 55,
+    LuaNewIndex = 56,
+    LuaError = 57,
+    LuaIndex = 58,
+    LuaPlain = 59,
 }
 
 pub(crate) const BUILTIN_CALL_BASE: u16 = 0xFFE0;
 /// Number of builtins in the main range (indices 0..22 above BUILTIN_CALL_BASE).
-/// ToString, TypeOf, and Assert live at special indices below BUILTIN_CALL_BASE.
+/// ToString, TypeOf, Assert, and later additions live at special indices below BUILTIN_CALL_BASE.
 pub(crate) const BUILTIN_CALL_COUNT: u16 = 23;
 
+/// Builtins placed below BUILTIN_CALL_BASE; entry N uses call index `BUILTIN_CALL_BASE - 1 - N`.
+/// New builtins are appended here so existing call indices stay stable in serialized programs.
+const BUILTIN_LOW_RANGE: [BuiltinFunction; 37] = [
+    BuiltinFunction::Assert,
+    BuiltinFunction::TypeOf,
+    BuiltinFunction::ToString,
+    BuiltinFunction::RandInt,
+    BuiltinFunction::RandFloat,
+    BuiltinFunction::RandChoice,
+    BuiltinFunction::RandShuffle,
+    BuiltinFunction::UuidV4,
+    BuiltinFunction::ArraySort,
+    BuiltinFunction::ArraySortByKeys,
+    BuiltinFunction::ArrayBinarySearch,
+    BuiltinFunction::ArrayIndexOf,
+    BuiltinFunction::ArrayContains,
+    BuiltinFunction::ArrayDedup,
+    BuiltinFunction::ArrayRemove,
+    BuiltinFunction::ArrayInsert,
+    BuiltinFunction::ArrayPop,
+    BuiltinFunction::MapRemove,
+    BuiltinFunction::MapHasKey,
+    BuiltinFunction::MapValues,
+    BuiltinFunction::MapEntries,
+    BuiltinFunction::Format,
+    BuiltinFunction::LuaFind,
+    BuiltinFunction::LuaMatch,
+    BuiltinFunction::LuaGmatch,
+    BuiltinFunction::LuaGsub,
+    BuiltinFunction::LuaSetMetatable,
+    BuiltinFunction::LuaGetMetatable,
+    BuiltinFunction::LuaRawGet,
+    BuiltinFunction::LuaRawSet,
+    BuiltinFunction::LuaRawEqual,
+    BuiltinFunction::LuaMetamethod,
+    BuiltinFunction::LuaIndexOwner,
+    BuiltinFunction::LuaNewIndex,
+    BuiltinFunction::LuaError,
+    BuiltinFunction::LuaIndex,
+    BuiltinFunction::LuaPlain,
+];
+
 impl BuiltinFunction {
     pub(crate) fn name(self) -> &'static str {
         match self {
@@ -63,9 +139,97 @@ impl BuiltinFunction {
             BuiltinFunction::ToString => "__to_string",
             BuiltinFunction::TypeOf => "type_of",
             BuiltinFunction::Assert => "assert",
+            BuiltinFunction::RandInt => "rand_int",
+            BuiltinFunction::RandFloat => "rand_float",
+            BuiltinFunction::RandChoice => "rand_choice",
+            BuiltinFunction::RandShuffle => "rand_shuffle",
+            BuiltinFunction::UuidV4 => "uuid_v4",
+            BuiltinFunction::ArraySort => "array_sort",
+            BuiltinFunction::ArraySortByKeys => "array_sort_by_keys",
+            BuiltinFunction::ArrayBinarySearch => "array_binary_search",
+            BuiltinFunction::ArrayIndexOf => "array_index_of",
+            BuiltinFunction::ArrayContains => "array_contains",
+            BuiltinFunction::ArrayDedup => "array_dedup",
+            BuiltinFunction::ArrayRemove => "array_remove",
+            BuiltinFunction::ArrayInsert => "array_insert",
+            BuiltinFunction::ArrayPop => "array_pop",
+            BuiltinFunction::MapRemove => "map_remove",
+            BuiltinFunction::MapHasKey => "map_has_key",
+            BuiltinFunction::MapValues => "map_values",
+            BuiltinFunction::MapEntries => "map_entries",
+            BuiltinFunction::Format => "format",
+            BuiltinFunction::LuaFind => "lua_find",
+            BuiltinFunction::LuaMatch => "lua_match",
+            BuiltinFunction::LuaGmatch => "lua_gmatch",
+            BuiltinFunction::LuaGsub => "lua_gsub",
+            BuiltinFunction::LuaSetMetatable => "lua_setmetatable",
+            BuiltinFunction::LuaGetMetatable => "lua_getmetatable",
+            BuiltinFunction::LuaRawGet => "lua_rawget",
+            BuiltinFunction::LuaRawSet => "lua_rawset",
+            BuiltinFunction::LuaRawEqual => "lua_rawequal",
+            BuiltinFunction::LuaMetamethod => "lua_metamethod",
+            BuiltinFunction::LuaIndexOwner => "lua_index_owner",
+            BuiltinFunction::LuaNewIndex => "lua_newindex",
+            BuiltinFunction::LuaError => "lua_error",
+            BuiltinFunction::LuaIndex => "lua_index",
+            BuiltinFunction::LuaPlain => "lua_plain",
         }
     }
 
+    /// The `namespace::member` name source code calls this builtin by, for
+    /// builtins that live in a namespace.
+    pub(crate) fn source_path(self) -> Option<&'static str> {
+        Some(match self {
+            BuiltinFunction::IoOpen => "io::open",
+            BuiltinFunction::IoPopen => "io::popen",
+            BuiltinFunction::IoReadAll => "io::read_all",
+            BuiltinFunction::IoReadLine => "io::read_line",
+            BuiltinFunction::IoWrite => "io::write",
+            BuiltinFunction::IoFlush => "io::flush",
+            BuiltinFunction::IoClose => "io::close",
+            BuiltinFunction::IoExists => "io::exists",
+            BuiltinFunction::ReIsMatch => "re::is_match",
+            BuiltinFunction::ReFind => "re::find",
+            BuiltinFunction::ReReplace => "re::replace",
+            BuiltinFunction::ReSplit => "re::split",
+            BuiltinFunction::ReCaptures => "re::captures",
+            BuiltinFunction::RandInt => "rand::int",
+            BuiltinFunction::RandFloat => "rand::float",
+            BuiltinFunction::RandChoice => "rand::choice",
+            BuiltinFunction::RandShuffle => "rand::shuffle",
+            BuiltinFunction::UuidV4 => "uuid::v4",
+            BuiltinFunction::ArraySort => "array::sort",
+            BuiltinFunction::ArraySortByKeys => "array::sort_by_keys",
+            BuiltinFunction::ArrayBinarySearch => "array::binary_search",
+            BuiltinFunction::ArrayIndexOf => "array::index_of",
+            BuiltinFunction::ArrayContains => "array::contains",
+            BuiltinFunction::ArrayDedup => "array::dedup",
+            BuiltinFunction::ArrayRemove => "array::remove",
+            BuiltinFunction::ArrayInsert => "array::insert",
+            BuiltinFunction::ArrayPop => "array::pop",
+            BuiltinFunction::MapRemove => "map::remove",
+            BuiltinFunction::MapHasKey => "map::has_key",
+            BuiltinFunction::MapValues => "map::values",
+            BuiltinFunction::MapEntries => "map::entries",
+            BuiltinFunction::LuaFind => "lua::find",
+            BuiltinFunction::LuaMatch => "lua::match",
+            BuiltinFunction::LuaGmatch => "lua::gmatch",
+            BuiltinFunction::LuaGsub => "lua::gsub",
+            BuiltinFunction::LuaSetMetatable => "lua::setmetatable",
+            BuiltinFunction::LuaGetMetatable => "lua::getmetatable",
+            BuiltinFunction::LuaRawGet => "lua::rawget",
+            BuiltinFunction::LuaRawSet => "lua::rawset",
+            BuiltinFunction::LuaRawEqual => "lua::rawequal",
+            BuiltinFunction::LuaMetamethod => "lua::metamethod",
+            BuiltinFunction::LuaIndexOwner => "lua::index_owner",
+            BuiltinFunction::LuaNewIndex => "lua::newindex",
+            BuiltinFunction::LuaError => "lua::error",
+            BuiltinFunction::LuaIndex => "lua::index",
+            BuiltinFunction::LuaPlain => "lua::plain",
+            _ => return None,
+        })
+    }
+
     pub(crate) fn arity(self) -> u8 {
         match self {
             BuiltinFunction::Len => 1,
@@ -94,27 +258,57 @@ impl BuiltinFunction {
             BuiltinFunction::ToString => 1,
             BuiltinFunction::TypeOf => 1,
             BuiltinFunction::Assert => 1,
+            BuiltinFunction::RandInt => 2,
+            BuiltinFunction::RandFloat => 0,
+            BuiltinFunction::RandChoice => 1,
+            BuiltinFunction::RandShuffle => 1,
+            BuiltinFunction::UuidV4 => 0,
+            BuiltinFunction::ArraySort => 1,
+            BuiltinFunction::ArraySortByKeys => 2,
+            BuiltinFunction::ArrayBinarySearch => 2,
+            BuiltinFunction::ArrayIndexOf => 2,
+            BuiltinFunction::ArrayContains => 2,
+            BuiltinFunction::ArrayDedup => 1,
+            BuiltinFunction::ArrayRemove => 2,
+            BuiltinFunction::ArrayInsert => 3,
+            BuiltinFunction::ArrayPop => 1,
+            BuiltinFunction::MapRemove => 2,
+            BuiltinFunction::MapHasKey => 2,
+            BuiltinFunction::MapValues => 1,
+            BuiltinFunction::MapEntries => 1,
+            BuiltinFunction::Format => 2,
+            BuiltinFunction::LuaFind => 4,
+            BuiltinFunction::LuaMatch => 3,
+            BuiltinFunction::LuaGmatch => 3,
+            BuiltinFunction::LuaGsub => 4,
+            BuiltinFunction::LuaSetMetatable => 2,
+            BuiltinFunction::LuaGetMetatable => 1,
+            BuiltinFunction::LuaRawGet => 2,
+            BuiltinFunction::LuaRawSet => 3,
+            BuiltinFunction::LuaRawEqual => 2,
+            BuiltinFunction::LuaMetamethod => 3,
+            BuiltinFunction::LuaIndexOwner => 2,
+            BuiltinFunction::LuaNewIndex => 3,
+            BuiltinFunction::LuaError => 1,
+            BuiltinFunction::LuaIndex => 2,
+            BuiltinFunction::LuaPlain => 2,
         }
     }
 
     pub(crate) fn call_index(self) -> u16 {
-        match self {
-            BuiltinFunction::ToString => BUILTIN_CALL_BASE - 3,
-            BuiltinFunction::TypeOf => BUILTIN_CALL_BASE - 2,
-            BuiltinFunction::Assert => BUILTIN_CALL_BASE - 1,
-            _ => BUILTIN_CALL_BASE + self as u16,
+        if let Some(position) = BUILTIN_LOW_RANGE
+            .iter()
+            .position(|builtin| *builtin == self)
+        {
+            return BUILTIN_CALL_BASE - 1 - position as u16;
         }
+        BUILTIN_CALL_BASE + self as u16
     }
 
     pub(crate) fn from_call_index(index: u16) -> Option<Self> {
-        if index == BUILTIN_CALL_BASE - 3 {
-            return Some(BuiltinFunction::ToString);
-        }
-        if index == BUILTIN_CALL_BASE - 2 {
-            return Some(BuiltinFunction::TypeOf);
-        }
-        if index == BUILTIN_CALL_BASE - 1 {
-            return Some(BuiltinFunction::Assert);
+        if index < BUILTIN_CALL_BASE {
+            let position = (BUILTIN_CALL_BASE - 1).checked_sub(index)?;
+            return BUILTIN_LOW_RANGE.get(position as usize).copied();
         }
         let offset = index.checked_sub(BUILTIN_CALL_BASE)?;
         if offset >= BUILTIN_CALL_COUNT {
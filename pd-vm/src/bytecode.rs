@@ -9,12 +9,101 @@ pub enum Value {
     Map(Vec<(Value, Value)>),
 }
 
+impl Value {
+    /// Total order across all value kinds, used by the sort and search builtins.
+    ///
+    /// Kinds order as null < bool < number < string < array < map. Ints and floats compare
+    /// numerically and exactly; among equal numbers an int sorts before a float and `-0.0`
+    /// before `0.0`. Every NaN, whatever its sign, sorts after all other numbers. Arrays and
+    /// maps compare element-wise.
+    pub fn total_cmp(&self, other: &Value) -> std::cmp::Ordering {
+        use std::cmp::Ordering;
+
+        fn kind_rank(value: &Value) -> u8 {
+            match value {
+                Value::Null => 0,
+                Value::Bool(_) => 1,
+                Value::Int(_) | Value::Float(_) => 2,
+                Value::String(_) => 3,
+                Value::Array(_) => 4,
+                Value::Map(_) => 5,
+            }
+        }
+
+        match (self, other) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            (Value::Bool(lhs), Value::Bool(rhs)) => lhs.cmp(rhs),
+            (Value::Int(lhs), Value::Int(rhs)) => lhs.cmp(rhs),
+            (Value::Float(lhs), Value::Float(rhs)) => match (lhs.is_nan(), rhs.is_nan()) {
+                (false, false) => lhs
+                    .partial_cmp(rhs)
+                    .unwrap_or(Ordering::Equal)
+                    .then_with(|| lhs.total_cmp(rhs)),
+                (true, true) => lhs.total_cmp(rhs),
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+            },
+            (Value::Int(lhs), Value::Float(rhs)) => int_float_cmp(*lhs, *rhs).then(Ordering::Less),
+            (Value::Float(lhs), Value::Int(rhs)) => {
+                int_float_cmp(*rhs, *lhs).reverse().then(Ordering::Greater)
+            }
+            (Value::String(lhs), Value::String(rhs)) => lhs.cmp(rhs),
+            (Value::Array(lhs), Value::Array(rhs)) => {
+                for (left, right) in lhs.iter().zip(rhs) {
+                    let ordering = left.total_cmp(right);
+                    if ordering != Ordering::Equal {
+                        return ordering;
+                    }
+                }
+                lhs.len().cmp(&rhs.len())
+            }
+            (Value::Map(lhs), Value::Map(rhs)) => {
+                for ((left_key, left_value), (right_key, right_value)) in lhs.iter().zip(rhs) {
+                    let ordering = left_key
+                        .total_cmp(right_key)
+                        .then_with(|| left_value.total_cmp(right_value));
+                    if ordering != Ordering::Equal {
+                        return ordering;
+                    }
+                }
+                lhs.len().cmp(&rhs.len())
+            }
+            _ => kind_rank(self).cmp(&kind_rank(other)),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct HostImport {
     pub name: String,
     pub arity: u8,
 }
 
+/// Compares an int with a float exactly, without rounding the int to a float;
+/// NaN sorts after every int.
+fn int_float_cmp(int: i64, float: f64) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+
+    // 2^63: the first float above every i64.
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+    if float.is_nan() || float >= LIMIT {
+        return Ordering::Less;
+    }
+    if float < -LIMIT {
+        return Ordering::Greater;
+    }
+    let whole = float.trunc();
+    int.cmp(&(whole as i64)).then_with(|| {
+        if float > whole {
+            Ordering::Less
+        } else if float < whole {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    })
+}
+
 #[derive(Clone, Debug)]
 pub struct Program {
     pub constants: Vec<Value>,
@@ -118,6 +207,37 @@ impl OpCode {
         }
     }
 
+    /// Opcodes are numbered densely from `0x00`, so a byte past the last one is
+    /// not an opcode.
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        const ALL: [OpCode; 23] = [
+            OpCode::Nop,
+            OpCode::Ret,
+            OpCode::Ldc,
+            OpCode::Add,
+            OpCode::Sub,
+            OpCode::Mul,
+            OpCode::Div,
+            OpCode::Neg,
+            OpCode::Ceq,
+            OpCode::Clt,
+            OpCode::Cgt,
+            OpCode::Br,
+            OpCode::Brfalse,
+            OpCode::Pop,
+            OpCode::Dup,
+            OpCode::Ldloc,
+            OpCode::Stloc,
+            OpCode::Call,
+            OpCode::Shl,
+            OpCode::Shr,
+            OpCode::Mod,
+            OpCode::And,
+            OpCode::Or,
+        ];
+        ALL.get(byte as usize).copied()
+    }
+
     pub fn parse_mnemonic(op: &str) -> Option<Self> {
         match op {
             "nop" => Some(OpCode::Nop),
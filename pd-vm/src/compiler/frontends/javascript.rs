@@ -1,1028 +1,2282 @@
-use super::super::{ParseError, STDLIB_PRINT_NAME};
-use super::{is_ident_continue, is_ident_start};
-use crate::compiler::source_map::{LineSpanMapping, LoweredSource};
-use std::collections::HashSet;
-
-pub(super) fn lower(source: &str) -> Result<LoweredSource, ParseError> {
-    reject_js_direct_builtin_calls(source)?;
-    let console_rewritten = rewrite_console_log_calls(source);
-    let typeof_rewritten = rewrite_js_typeof_operator(&console_rewritten);
-    let keyword_rewritten = rewrite_keywords(&typeof_rewritten, |ident| match ident {
-        "function" => Some("fn"),
-        "const" => Some("let"),
-        _ => None,
-    });
-
-    let mut lines = Vec::new();
-    let mut line_map = Vec::new();
-    let mut in_import_block = false;
-    let mut import_block = String::new();
-    let mut vm_import_emitted = false;
-    let mut vm_namespace_aliases = HashSet::new();
-    for (index, raw_line) in keyword_rewritten.lines().enumerate() {
-        let line_no = index + 1;
-        let trimmed = raw_line.trim();
-        if in_import_block {
-            if !import_block.is_empty() {
-                import_block.push(' ');
-            }
-            import_block.push_str(trimmed);
-            if !vm_import_emitted && is_js_vm_import_block(&import_block) {
-                if let Some(alias) = parse_js_vm_namespace_alias_from_import_block(&import_block) {
-                    vm_namespace_aliases.insert(alias);
-                }
-                lines.push("use vm::*;".to_string());
-                line_map.push(line_no);
-                vm_import_emitted = true;
-            } else {
-                lines.push(String::new());
-                line_map.push(line_no);
-            }
-            if trimmed.contains(" from ") || trimmed.ends_with(';') {
-                in_import_block = false;
-                import_block.clear();
-            }
-            continue;
-        }
-        if trimmed.starts_with("import ") {
-            import_block.clear();
-            import_block.push_str(trimmed);
-            if !vm_import_emitted && is_js_vm_import_block(&import_block) {
-                if let Some(alias) = parse_js_vm_namespace_alias_from_import_block(&import_block) {
-                    vm_namespace_aliases.insert(alias);
-                }
-                lines.push("use vm::*;".to_string());
-                line_map.push(line_no);
-                vm_import_emitted = true;
-            } else {
-                lines.push(String::new());
-                line_map.push(line_no);
-            }
-            if !trimmed.contains(" from ") && !trimmed.ends_with(';') {
-                in_import_block = true;
-            }
-            continue;
+use super::super::parser::{self, Token, TokenKind as RssToken};
+use super::super::{ParseError, STDLIB_PRINT_NAME, ir::FrontendIr};
+use super::javascript_classes::{ClassRegistry, THIS_NAME};
+use super::javascript_lexer::JS_SOURCE_ID;
+use super::javascript_parser::{
+    BinaryOp, Class, Element, Expr, ExprKind, Function, FunctionBody, Import, ImportClause,
+    MethodKind, ObjectMember, Pattern, PatternElement, PropertyKey, Stmt, StmtKind, UnaryOp,
+    parse_program,
+};
+use super::{SORT_BY_HELPER, SORT_BY_HELPER_NAME, is_builtin_namespace, parse_token_stream};
+use crate::compiler::source_map::Span;
+use std::collections::{HashMap, HashSet};
+
+const VM_MODULE_SPEC: &str = "vm";
+const REST_PARAM_NAME: &str = "__js_rest";
+const OBJECT_ASSIGN_HELPER_NAME: &str = "__js_object_assign";
+
+// Object spread copies every entry of its source over the map built so far;
+// maps have no `+`, so `{ ...a, b: 1 }` lowers onto this helper.
+const OBJECT_ASSIGN_HELPER: &str = r#"fn __js_object_assign(target, source) {
+    let merged = target;
+    for (key, value) in source {
+        merged[key] = value;
+    }
+    merged;
+}"#;
+
+/// Parses the ES subset and lowers its syntax tree into the shared parser's
+/// token stream, so every token keeps the line and span of the JavaScript
+/// construct it came from.
+///
+/// The IR is not built directly because the RustScript parser owns the
+/// lowering state it depends on: local slot allocation, closure captures,
+/// return scopes, function indices, and module imports. Building token
+/// values directly never prints or re-lexes source text.
+pub(super) fn lower_to_ir(source: &str) -> Result<FrontendIr, ParseError> {
+    let program = parse_program(source)?;
+    let mut lowerer = Lowerer::new();
+    collect_signatures(&program, &mut lowerer.signatures);
+    lowerer.classes = ClassRegistry::scan(&program)?;
+    for stmt in &program {
+        lowerer.lower_stmt(stmt)?;
+    }
+    let uses_classes = !lowerer.classes.is_empty();
+    let mut ir = parse_token_stream(lowerer.finish(source)?)?;
+    if uses_classes {
+        super::table_slot::hide_reserved_slot(&mut ir, false);
+    }
+    Ok(ir)
+}
+
+struct Lowerer {
+    tokens: Vec<Token>,
+    line: usize,
+    span: Span,
+    vm_aliases: HashSet<String>,
+    vm_imported: bool,
+    uses_sort_helper: bool,
+    uses_object_assign_helper: bool,
+    functions: Vec<FunctionContext>,
+    /// Parameter shapes of the functions declared in the program, so calls
+    /// can pad missing arguments and collect rest arguments.
+    signatures: HashMap<String, Signature>,
+    classes: ClassRegistry,
+    temp_count: usize,
+}
+
+#[derive(Clone, Copy)]
+pub(super) struct Signature {
+    pub(super) params: usize,
+    pub(super) rest: bool,
+}
+
+impl Signature {
+    pub(super) fn of(function: &Function) -> Self {
+        Self {
+            params: function.params.len(),
+            rest: function.rest.is_some(),
         }
-        if is_js_vm_require_line(raw_line) {
-            if let Some(alias) = parse_js_vm_require_namespace_alias(raw_line) {
-                vm_namespace_aliases.insert(alias);
-            }
-            if !vm_import_emitted {
-                lines.push("use vm::*;".to_string());
-                line_map.push(line_no);
-                vm_import_emitted = true;
-            } else {
-                lines.push(String::new());
-                line_map.push(line_no);
-            }
-            continue;
-        }
-        if is_js_external_decl_line(raw_line) {
-            lines.push(String::new());
-            line_map.push(line_no);
-            continue;
-        }
-        let namespace_rewritten = rewrite_js_vm_namespace_calls(raw_line, &vm_namespace_aliases);
-        lines.push(rewrite_js_arrow_line(&namespace_rewritten, line_no)?);
-        line_map.push(line_no);
-    }
-    Ok(LoweredSource {
-        text: lines.join("\n"),
-        mapping: LineSpanMapping {
-            lowered_to_original_line: line_map,
-        },
-    })
+    }
+}
+
+#[derive(Clone, Default)]
+struct FunctionContext {
+    /// Set while lowering a block-bodied sort comparator, whose `return x`
+    /// statements become `return x < 0` for the `less(a, b)` sort helper.
+    returns_less: bool,
+    returns: Returns,
+    /// Whether `this` is bound: inside class members and the arrow functions
+    /// nested in them.
+    binds_this: bool,
+    /// The class whose member is being lowered, for `super` lookups.
+    class: Option<String>,
 }
 
-fn reject_js_direct_builtin_calls(source: &str) -> Result<(), ParseError> {
-    let bytes = source.as_bytes();
-    let mut i = 0usize;
-    let mut line = 1usize;
-    let mut in_string: Option<u8> = None;
-    let mut escaped = false;
-    let mut in_line_comment = false;
-    let mut in_block_comment = false;
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum Returns {
+    #[default]
+    Value,
+    /// Constructors return the instance they built.
+    Instance,
+    /// Methods return `[result, this]` for the caller to write back.
+    Pair,
+}
+
+/// An instance method call whose receiver may need writing back.
+enum ClassCall<'a> {
+    /// `receiver.method(args)`, dispatched on the receiver's class tag.
+    Send {
+        receiver: &'a Expr,
+        method: &'a str,
+        args: &'a [Element],
+    },
+    /// `super.method(args)`, resolved against the parent class.
+    Super {
+        method: &'a str,
+        args: &'a [Element],
+        call: &'a Expr,
+    },
+}
 
-    while i < bytes.len() {
-        let b = bytes[i];
+impl Lowerer {
+    fn new() -> Self {
+        Self {
+            tokens: Vec::new(),
+            line: 1,
+            span: Span::new(JS_SOURCE_ID, 0, 0),
+            vm_aliases: HashSet::new(),
+            vm_imported: false,
+            uses_sort_helper: false,
+            uses_object_assign_helper: false,
+            functions: Vec::new(),
+            signatures: HashMap::new(),
+            classes: ClassRegistry::default(),
+            temp_count: 0,
+        }
+    }
 
-        if in_line_comment {
-            if b == b'\n' {
-                in_line_comment = false;
-                line += 1;
+    fn finish(mut self, source: &str) -> Result<Vec<Token>, ParseError> {
+        let end = Span::new(JS_SOURCE_ID, source.len(), source.len());
+        let last_line = source.matches('\n').count() + 1;
+        self.tokens.push(Token {
+            kind: RssToken::Eof,
+            line: last_line,
+            span: end,
+        });
+        let mut helpers = Vec::new();
+        if self.uses_sort_helper {
+            helpers.push(SORT_BY_HELPER.to_string());
+        }
+        if self.uses_object_assign_helper {
+            helpers.push(OBJECT_ASSIGN_HELPER.to_string());
+        }
+        helpers.push(self.classes.emit_helpers());
+        let mut tokens = Vec::new();
+        for helper in &helpers {
+            let mut helper = parser::tokenize(helper, JS_SOURCE_ID)?;
+            helper.pop();
+            for token in &mut helper {
+                token.line = 1;
+                token.span = Span::new(JS_SOURCE_ID, 0, 0);
             }
-            i += 1;
-            continue;
+            tokens.extend(helper);
         }
+        tokens.extend(self.tokens);
+        Ok(tokens)
+    }
+
+    fn next_temp(&mut self, prefix: &str) -> String {
+        self.temp_count += 1;
+        format!("__js_{prefix}_{}", self.temp_count)
+    }
+
+    fn at(&mut self, line: usize, span: Span) {
+        self.line = line;
+        self.span = span;
+    }
 
-        if in_block_comment {
-            if b == b'\n' {
-                line += 1;
-                i += 1;
-                continue;
+    fn push(&mut self, kind: RssToken) {
+        self.tokens.push(Token {
+            kind,
+            line: self.line,
+            span: self.span,
+        });
+    }
+
+    fn push_ident(&mut self, name: &str) {
+        self.push(RssToken::Ident(name.to_string()));
+    }
+
+    fn push_path_separator(&mut self) {
+        self.push(RssToken::Colon);
+        self.push(RssToken::Colon);
+    }
+
+    fn lower_stmt(&mut self, stmt: &Stmt) -> Result<(), ParseError> {
+        self.at(stmt.line, stmt.span);
+        match &stmt.kind {
+            StmtKind::Empty => {}
+            StmtKind::Import(import) => self.lower_import(import),
+            StmtKind::Declare { name, params } => {
+                self.push(RssToken::Pub);
+                self.push(RssToken::Fn);
+                self.push_ident(name);
+                self.push_params(params);
+                self.push(RssToken::Semicolon);
+            }
+            StmtKind::Let(declarators) => {
+                for declarator in declarators {
+                    let hoisted = match &declarator.init {
+                        Some(init) => self.hoist_class_call(init)?,
+                        None => None,
+                    };
+                    self.at(declarator.line, declarator.span);
+                    let Some(init) = hoisted.as_ref().or(declarator.init.as_ref()) else {
+                        self.bind_target(&declarator.target, true, |this| {
+                            this.push(RssToken::Null);
+                            Ok(())
+                        })?;
+                        continue;
+                    };
+                    match (&declarator.target, &init.kind) {
+                        (Pattern::Target(_), _) => {
+                            self.bind_target(&declarator.target, true, |this| {
+                                this.lower_expr(init)
+                            })?;
+                        }
+                        (pattern, ExprKind::Ident(source)) => {
+                            self.lower_pattern(pattern, source, true)?;
+                        }
+                        (pattern, _) => {
+                            let source = self.declare_temp("destructure", init)?;
+                            self.lower_pattern(pattern, &source, true)?;
+                        }
+                    }
+                }
+            }
+            StmtKind::Function(function) => self.lower_function_decl(function)?,
+            StmtKind::Class(class) => self.lower_class(class)?,
+            StmtKind::Expr(expr) => self.lower_expr_stmt(expr)?,
+            StmtKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.push(RssToken::If);
+                self.lower_paren_expr(condition)?;
+                self.lower_block(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.push(RssToken::Else);
+                    self.lower_block(else_branch)?;
+                }
+            }
+            StmtKind::While { condition, body } => {
+                self.push(RssToken::While);
+                self.lower_paren_expr(condition)?;
+                self.lower_block(body)?;
             }
-            if b == b'*' && i + 1 < bytes.len() && bytes[i + 1] == b'/' {
-                in_block_comment = false;
-                i += 2;
-                continue;
+            StmtKind::For {
+                init,
+                condition,
+                update,
+                body,
+            } => self.lower_for(
+                stmt,
+                init.as_deref(),
+                condition.as_ref(),
+                update.as_ref(),
+                body,
+            )?,
+            StmtKind::ForOf {
+                binding,
+                iterable,
+                body,
+            } => self.lower_for_of(binding, iterable, body)?,
+            StmtKind::ForIn { name, object, body } => {
+                self.push(RssToken::For);
+                self.push_key_binding(name);
+                self.lower_paren_expr(object)?;
+                self.lower_block(body)?;
+            }
+            StmtKind::Block(stmts) => {
+                // Bindings are function-scoped once lowered, so a bare block
+                // contributes only its statements.
+                for stmt in stmts {
+                    self.lower_stmt(stmt)?;
+                }
+            }
+            StmtKind::Break => {
+                self.push(RssToken::Break);
+                self.push(RssToken::Semicolon);
+            }
+            StmtKind::Continue => {
+                self.push(RssToken::Continue);
+                self.push(RssToken::Semicolon);
+            }
+            StmtKind::Return(value) => {
+                let context = self.functions.last().cloned().unwrap_or_default();
+                let hoisted = match value {
+                    Some(value) => self.hoist_class_call(value)?,
+                    None => None,
+                };
+                let value = hoisted.as_ref().or(value.as_ref());
+                self.at(stmt.line, stmt.span);
+                self.push(RssToken::Return);
+                match (context.returns, value) {
+                    (Returns::Instance, _) => self.push_ident(THIS_NAME),
+                    (Returns::Pair, value) => {
+                        self.push(RssToken::LBracket);
+                        match value {
+                            Some(value) => self.lower_expr(value)?,
+                            None => self.push(RssToken::Null),
+                        }
+                        self.push(RssToken::Comma);
+                        self.push_ident(THIS_NAME);
+                        self.push(RssToken::RBracket);
+                    }
+                    (Returns::Value, Some(value)) if context.returns_less => {
+                        self.lower_less_than_zero(value)?
+                    }
+                    (Returns::Value, Some(value)) => self.lower_expr(value)?,
+                    (Returns::Value, None) if context.returns_less => self.push(RssToken::False),
+                    (Returns::Value, None) => {}
+                }
+                self.push(RssToken::Semicolon);
             }
-            i += 1;
-            continue;
         }
+        Ok(())
+    }
 
-        if let Some(delim) = in_string {
-            if escaped {
-                escaped = false;
-            } else if b == b'\\' {
-                escaped = true;
-            } else if b == delim {
-                in_string = None;
-            } else if b == b'\n' {
-                line += 1;
+    fn lower_import(&mut self, import: &Import) {
+        // Module imports are resolved by the source loader, which prepends
+        // declarations and rewrites namespaced call sites before lowering.
+        if import.spec != VM_MODULE_SPEC {
+            return;
+        }
+        if !self.vm_imported {
+            self.vm_imported = true;
+            self.push(RssToken::Use);
+            self.push_ident(VM_MODULE_SPEC);
+            self.push_path_separator();
+            self.push(RssToken::Star);
+            self.push(RssToken::Semicolon);
+        }
+        match &import.clause {
+            ImportClause::Bare => {}
+            ImportClause::Namespace(alias) => {
+                self.vm_aliases.insert(alias.clone());
+            }
+            ImportClause::Named(names) => {
+                self.push(RssToken::Use);
+                self.push_ident(VM_MODULE_SPEC);
+                self.push_path_separator();
+                self.push(RssToken::LBrace);
+                for (index, (imported, local)) in names.iter().enumerate() {
+                    if index > 0 {
+                        self.push(RssToken::Comma);
+                    }
+                    self.push_ident(imported);
+                    self.push(RssToken::As);
+                    self.push_ident(local);
+                }
+                self.push(RssToken::RBrace);
+                self.push(RssToken::Semicolon);
             }
-            i += 1;
-            continue;
         }
+    }
 
-        if b == b'/' && i + 1 < bytes.len() && bytes[i + 1] == b'/' {
-            in_line_comment = true;
-            i += 2;
-            continue;
+    fn lower_function_decl(&mut self, function: &Function) -> Result<(), ParseError> {
+        let name = function.name.as_deref().unwrap_or_default();
+        if !self.functions.is_empty() {
+            // Functions only exist at compile time, so a nested declaration is
+            // the same binding as a block-bodied closure.
+            self.push(RssToken::Let);
+            self.push_ident(name);
+            self.push(RssToken::Equal);
+            self.lower_closure(function, false)?;
+            self.push(RssToken::Semicolon);
+            return Ok(());
         }
+        if function.exported {
+            self.push(RssToken::Pub);
+        }
+        self.lower_named_function(name, function, false, FunctionContext::default())
+    }
 
-        if b == b'/' && i + 1 < bytes.len() && bytes[i + 1] == b'*' {
-            in_block_comment = true;
-            i += 2;
-            continue;
+    /// Lowers `fn name([this, ]params) { body }`.
+    fn lower_named_function(
+        &mut self,
+        name: &str,
+        function: &Function,
+        receives_this: bool,
+        context: FunctionContext,
+    ) -> Result<(), ParseError> {
+        self.at(function.line, function.span);
+        self.push(RssToken::Fn);
+        self.push_ident(name);
+        self.push(RssToken::LParen);
+        if receives_this {
+            self.push_ident(THIS_NAME);
+            if !param_names(function).is_empty() {
+                self.push(RssToken::Comma);
+            }
         }
+        self.push_param_list(function);
+        self.push(RssToken::RParen);
+        let FunctionBody::Block(stmts, end_line, end_span) = &function.body else {
+            unreachable!("named functions always have block bodies");
+        };
+        self.push(RssToken::LBrace);
+        self.lower_function_stmts(function, stmts, context, *end_line, *end_span)?;
+        self.at(*end_line, *end_span);
+        self.push(RssToken::RBrace);
+        Ok(())
+    }
 
-        if b == b'"' || b == b'\'' || b == b'`' {
-            in_string = Some(b);
-            escaped = false;
-            i += 1;
-            continue;
+    /// Lowers a function body, after its parameter prologue, and the value it
+    /// yields when it falls off its end: `undefined`, `false` for sort
+    /// comparators, or the instance for class members.
+    fn lower_function_stmts(
+        &mut self,
+        function: &Function,
+        stmts: &[Stmt],
+        context: FunctionContext,
+        end_line: usize,
+        end_span: Span,
+    ) -> Result<(), ParseError> {
+        let returns = context.returns;
+        let returns_less = context.returns_less;
+        self.functions.push(context);
+        self.lower_param_prologue(function)?;
+        for stmt in stmts {
+            self.lower_stmt(stmt)?;
+        }
+        self.functions.pop();
+        if matches!(
+            stmts.last().map(|stmt| &stmt.kind),
+            Some(StmtKind::Return(_))
+        ) {
+            return Ok(());
         }
+        self.at(end_line, end_span);
+        match returns {
+            Returns::Value if returns_less => self.push(RssToken::False),
+            Returns::Value => self.push(RssToken::Null),
+            Returns::Instance => self.push_ident(THIS_NAME),
+            Returns::Pair => {
+                self.push(RssToken::LBracket);
+                self.push(RssToken::Null);
+                self.push(RssToken::Comma);
+                self.push_ident(THIS_NAME);
+                self.push(RssToken::RBracket);
+            }
+        }
+        self.push(RssToken::Semicolon);
+        Ok(())
+    }
 
-        if b == b'\n' {
-            line += 1;
-            i += 1;
-            continue;
+    /// Arrow functions keep the `this` of the method around them; other
+    /// functions have none.
+    fn closure_context(&self, function: &Function, returns_less: bool) -> FunctionContext {
+        let enclosing = self.functions.last().filter(|_| function.arrow);
+        FunctionContext {
+            returns_less,
+            returns: Returns::Value,
+            binds_this: enclosing.is_some_and(|context| context.binds_this),
+            class: enclosing.and_then(|context| context.class.clone()),
         }
+    }
 
-        if !is_ident_start(b as char) {
-            i += 1;
-            continue;
+    fn lower_closure(&mut self, function: &Function, returns_less: bool) -> Result<(), ParseError> {
+        let context = self.closure_context(function, returns_less);
+        self.at(function.line, function.span);
+        self.push(RssToken::Pipe);
+        self.push_param_list(function);
+        self.push(RssToken::Pipe);
+        match &function.body {
+            FunctionBody::Expr(body) => {
+                // Defaults and destructured parameters need statements, so the
+                // expression becomes the value of a block body.
+                let prologue = has_param_prologue(function);
+                if prologue {
+                    self.push(RssToken::FatArrow);
+                    self.push(RssToken::LBrace);
+                }
+                self.functions.push(context);
+                if prologue {
+                    self.lower_param_prologue(function)?;
+                    self.at(body.line, body.span);
+                }
+                if returns_less {
+                    self.lower_less_than_zero(body)?;
+                } else {
+                    self.lower_paren_expr(body)?;
+                }
+                self.functions.pop();
+                if prologue {
+                    self.push(RssToken::Semicolon);
+                    self.push(RssToken::RBrace);
+                }
+                Ok(())
+            }
+            FunctionBody::Block(stmts, end_line, end_span) => {
+                self.push(RssToken::FatArrow);
+                self.push(RssToken::LBrace);
+                self.lower_function_stmts(function, stmts, context, *end_line, *end_span)?;
+                self.at(*end_line, *end_span);
+                self.push(RssToken::RBrace);
+                Ok(())
+            }
         }
+    }
 
-        let start = i;
-        i += 1;
-        while i < bytes.len() && is_ident_continue(bytes[i] as char) {
-            i += 1;
+    /// Lowers a top-level class into one function per constructor, method,
+    /// getter, and static method; `javascript_classes` emits the dispatch.
+    fn lower_class(&mut self, class: &Class) -> Result<(), ParseError> {
+        if !self.functions.is_empty() || !self.classes.declares(class) {
+            return Err(lower_error(
+                class.line,
+                class.span,
+                "classes must be declared at the top level",
+            ));
         }
-        let ident = &source[start..i];
-        if !is_forbidden_js_builtin_name(ident) {
-            continue;
+        let member_context = |returns| FunctionContext {
+            returns_less: false,
+            returns,
+            binds_this: true,
+            class: Some(class.name.clone()),
+        };
+        if let Some(constructor) = &class.constructor {
+            let (name, _) = self
+                .classes
+                .resolve_constructor(&class.name)
+                .expect("declared constructors resolve to themselves");
+            self.lower_named_function(&name, constructor, true, member_context(Returns::Instance))?;
         }
-
-        let prev_non_ws = previous_non_whitespace(bytes, start);
-        if prev_non_ws.is_some_and(|idx| bytes[idx] == b'.') {
-            continue;
+        for method in &class.methods {
+            let (name, _) = self
+                .classes
+                .resolve_member(&class.name, &method.name, method.kind)
+                .expect("declared members resolve to themselves");
+            let (receives_this, context) = match method.kind {
+                MethodKind::Instance => (true, member_context(Returns::Pair)),
+                MethodKind::Getter => (true, member_context(Returns::Value)),
+                MethodKind::Static => (false, FunctionContext::default()),
+            };
+            self.lower_named_function(&name, &method.function, receives_this, context)?;
         }
+        Ok(())
+    }
 
-        let next = skip_js_whitespace(bytes, i);
-        if next < bytes.len() && bytes[next] == b'(' {
-            let hint = js_builtin_syntax_hint(ident);
-            return Err(ParseError {
-                span: None,
-                code: None,
-                line,
-                message: format!(
-                    "direct builtin call '{ident}(...)' is not exposed in JavaScript frontend; {hint}"
-                ),
-            });
+    fn push_params(&mut self, params: &[String]) {
+        self.push(RssToken::LParen);
+        for (index, param) in params.iter().enumerate() {
+            if index > 0 {
+                self.push(RssToken::Comma);
+            }
+            self.push_ident(param);
         }
+        self.push(RssToken::RParen);
     }
 
-    Ok(())
-}
-
-fn is_forbidden_js_builtin_name(name: &str) -> bool {
-    matches!(
-        name,
-        "len"
-            | "slice"
-            | "concat"
-            | "array_new"
-            | "array_push"
-            | "map_new"
-            | "get"
-            | "set"
-            | "count"
-            | "__to_string"
-            | "type_of"
-            | "io_open"
-            | "io_popen"
-            | "io_read_all"
-            | "io_read_line"
-            | "io_write"
-            | "io_flush"
-            | "io_close"
-            | "io_exists"
-            | "re_is_match"
-            | "re_find"
-            | "re_replace"
-            | "re_split"
-            | "re_captures"
-    )
-}
-
-fn js_builtin_syntax_hint(name: &str) -> &'static str {
-    match name {
-        "len" | "count" => "use '.length' syntax",
-        "type_of" => "use 'typeof value'",
-        "get" => "use index/member access syntax ('obj[key]' or 'obj.member')",
-        "set" => "use assignment syntax ('obj[key] = value')",
-        "concat" => "use '+' for string/array concatenation",
-        "slice" => "use slice index syntax ('value[start:end]')",
-        "io_open" | "io_popen" | "io_read_all" | "io_read_line" | "io_write" | "io_flush"
-        | "io_close" | "io_exists" => "use io namespace syntax (for example 'io::open(...)')",
-        "re_is_match" | "re_find" | "re_replace" | "re_split" | "re_captures" => {
-            "use re namespace syntax (for example 're::match(pattern, text, \"i\")')"
+    /// Pushes the lowered parameter names. Destructured parameters get a
+    /// positional name their pattern is unpacked from, and the rest parameter
+    /// becomes one trailing array parameter.
+    fn push_param_list(&mut self, function: &Function) {
+        for (index, name) in param_names(function).iter().enumerate() {
+            if index > 0 {
+                self.push(RssToken::Comma);
+            }
+            self.push_ident(name);
         }
-        _ => "use frontend language syntax instead of VM builtin helpers",
     }
-}
 
-fn rewrite_js_typeof_operator(source: &str) -> String {
-    const TYPEOF: &[u8] = b"typeof";
-
-    let bytes = source.as_bytes();
-    let mut out = String::with_capacity(source.len());
-    let mut i = 0usize;
-    let mut in_string: Option<u8> = None;
-    let mut escaped = false;
-    let mut in_line_comment = false;
-    let mut in_block_comment = false;
-
-    while i < bytes.len() {
-        let b = bytes[i];
-
-        if in_block_comment {
-            out.push(b as char);
-            if b == b'*' && i + 1 < bytes.len() && bytes[i + 1] == b'/' {
-                out.push('/');
-                i += 2;
-                in_block_comment = false;
-                continue;
-            }
-            i += 1;
-            continue;
-        }
-
-        if in_line_comment {
-            out.push(b as char);
-            if b == b'\n' {
-                in_line_comment = false;
-            }
-            i += 1;
-            continue;
-        }
-
-        if let Some(delim) = in_string {
-            out.push(b as char);
-            if escaped {
-                escaped = false;
-            } else if b == b'\\' {
-                escaped = true;
-            } else if b == delim {
-                in_string = None;
-            }
-            i += 1;
-            continue;
-        }
-
-        if b == b'/' && i + 1 < bytes.len() && bytes[i + 1] == b'/' {
-            out.push('/');
-            out.push('/');
-            i += 2;
-            in_line_comment = true;
-            continue;
-        }
-
-        if b == b'/' && i + 1 < bytes.len() && bytes[i + 1] == b'*' {
-            out.push('/');
-            out.push('*');
-            i += 2;
-            in_block_comment = true;
-            continue;
-        }
-
-        if b == b'"' || b == b'\'' || b == b'`' {
-            out.push(b as char);
-            in_string = Some(b);
-            escaped = false;
-            i += 1;
-            continue;
-        }
-
-        let is_ident_boundary = i == 0 || !is_ident_continue(bytes[i - 1] as char);
-        let prev_non_ws = previous_non_whitespace(bytes, i);
-        let is_member_name = prev_non_ws.is_some_and(|idx| bytes[idx] == b'.');
-        if is_ident_boundary
-            && !is_member_name
-            && i + TYPEOF.len() <= bytes.len()
-            && &bytes[i..i + TYPEOF.len()] == TYPEOF
-            && (i + TYPEOF.len() == bytes.len()
-                || !is_ident_continue(bytes[i + TYPEOF.len()] as char))
-        {
-            let operand_start = skip_js_whitespace(bytes, i + TYPEOF.len());
-            if let Some(operand_end) = parse_js_typeof_operand_end(source, operand_start) {
-                let operand = source[operand_start..operand_end].trim();
-                out.push_str("type(");
-                out.push_str(operand);
-                out.push(')');
-                i = operand_end;
-                continue;
+    /// Applies parameter defaults and unpacks destructured parameters at the
+    /// top of a function body.
+    fn lower_param_prologue(&mut self, function: &Function) -> Result<(), ParseError> {
+        let names = param_names(function);
+        for (param, name) in function.params.iter().zip(&names) {
+            if let Some(default) = &param.default {
+                self.lower_default_check(name, default)?;
             }
+            if !matches!(param.pattern, Pattern::Target(_)) {
+                self.lower_pattern(&param.pattern, name, true)?;
+            }
+        }
+        if let Some(rest) = &function.rest
+            && !matches!(rest, Pattern::Target(_))
+        {
+            self.lower_pattern(rest, REST_PARAM_NAME, true)?;
         }
+        Ok(())
+    }
 
-        out.push(b as char);
-        i += 1;
+    /// Pushes `if (name == null) { name = default; }`.
+    fn lower_default_check(&mut self, name: &str, default: &Expr) -> Result<(), ParseError> {
+        self.at(default.line, default.span);
+        self.push(RssToken::If);
+        self.push(RssToken::LParen);
+        self.push_ident(name);
+        self.push(RssToken::EqualEqual);
+        self.push(RssToken::Null);
+        self.push(RssToken::RParen);
+        self.push(RssToken::LBrace);
+        self.push_ident(name);
+        self.push(RssToken::Equal);
+        self.lower_expr(default)?;
+        self.push(RssToken::Semicolon);
+        self.push(RssToken::RBrace);
+        Ok(())
     }
 
-    out
-}
+    /// Evaluates `value` once into a fresh temporary and returns its name.
+    fn declare_temp(&mut self, prefix: &str, value: &Expr) -> Result<String, ParseError> {
+        let name = self.next_temp(prefix);
+        self.at(value.line, value.span);
+        self.push(RssToken::Let);
+        self.push_ident(&name);
+        self.push(RssToken::Equal);
+        self.lower_expr(value)?;
+        self.push(RssToken::Semicolon);
+        Ok(name)
+    }
 
-fn parse_js_typeof_operand_end(source: &str, start: usize) -> Option<usize> {
-    let bytes = source.as_bytes();
-    if start >= bytes.len() {
-        return None;
+    /// Binds `value` to a pattern target: `let name = value;` for
+    /// declarations, `target = value;` for assignment patterns, or a further
+    /// destructuring of the value for nested patterns.
+    fn bind_target(
+        &mut self,
+        pattern: &Pattern,
+        declare: bool,
+        value: impl FnOnce(&mut Self) -> Result<(), ParseError>,
+    ) -> Result<(), ParseError> {
+        let Pattern::Target(target) = pattern else {
+            let (line, span) = (self.line, self.span);
+            let source = self.next_temp("destructure");
+            self.push(RssToken::Let);
+            self.push_ident(&source);
+            self.push(RssToken::Equal);
+            value(self)?;
+            self.push(RssToken::Semicolon);
+            self.at(line, span);
+            return self.lower_pattern(pattern, &source, declare);
+        };
+        if declare {
+            let ExprKind::Ident(name) = &target.kind else {
+                unreachable!("declarations only bind names");
+            };
+            self.push(RssToken::Let);
+            self.push_ident(name);
+        } else {
+            let (line, span) = (self.line, self.span);
+            self.lower_assign_target(target)?;
+            self.at(line, span);
+        }
+        self.push(RssToken::Equal);
+        value(self)?;
+        self.push(RssToken::Semicolon);
+        Ok(())
     }
 
-    let mut cursor = match bytes[start] {
-        b'(' => parse_js_balanced_segment_end(source, start, b'(', b')')?,
-        b'"' | b'\'' | b'`' => parse_js_string_end(source, start)?,
-        b if is_ident_start(b as char) => {
-            let mut end = start + 1;
-            while end < bytes.len() && is_ident_continue(bytes[end] as char) {
-                end += 1;
+    /// Unpacks the variable `source` into `pattern`. Missing keys and
+    /// out-of-range indexes read as `undefined`, which selects a default.
+    fn lower_pattern(
+        &mut self,
+        pattern: &Pattern,
+        source: &str,
+        declare: bool,
+    ) -> Result<(), ParseError> {
+        match pattern {
+            Pattern::Target(_) => self.bind_target(pattern, declare, |this| {
+                this.push_ident(source);
+                Ok(())
+            }),
+            Pattern::Array { elements, rest } => {
+                for (index, element) in elements.iter().enumerate() {
+                    if let Some(element) = element {
+                        self.lower_pattern_element(element, declare, |this| {
+                            this.push_ident(source);
+                            this.push(RssToken::Question);
+                            this.push(RssToken::Dot);
+                            this.push(RssToken::LBracket);
+                            this.push(RssToken::Int(index as i64));
+                            this.push(RssToken::RBracket);
+                            Ok(())
+                        })?;
+                    }
+                }
+                if let Some(rest) = rest {
+                    self.bind_target(rest, declare, |this| {
+                        this.push(RssToken::LParen);
+                        this.push_ident(source);
+                        this.push(RssToken::RParen);
+                        this.push(RssToken::LBracket);
+                        this.push(RssToken::Int(elements.len() as i64));
+                        this.push(RssToken::Colon);
+                        this.push(RssToken::RBracket);
+                        Ok(())
+                    })?;
+                }
+                Ok(())
             }
-            end
-        }
-        b if b.is_ascii_digit() => {
-            let mut end = start + 1;
-            while end < bytes.len() && (bytes[end].is_ascii_digit() || bytes[end] == b'.') {
-                end += 1;
+            Pattern::Object { properties, rest } => {
+                for property in properties {
+                    self.lower_pattern_element(&property.element, declare, |this| {
+                        this.push_ident(source);
+                        this.push(RssToken::Question);
+                        this.push(RssToken::Dot);
+                        this.push(RssToken::LBracket);
+                        this.lower_property_key(&property.key)?;
+                        this.push(RssToken::RBracket);
+                        Ok(())
+                    })?;
+                }
+                if let Some(rest) = rest {
+                    let target = Pattern::Target(rest.as_ref().clone());
+                    self.bind_target(&target, declare, |this| {
+                        // map::remove(map::remove(source, "a"), "b")
+                        for _ in properties {
+                            this.push_namespace_call_start("map", "remove");
+                        }
+                        this.push_ident(source);
+                        for property in properties {
+                            this.push(RssToken::Comma);
+                            this.lower_property_key(&property.key)?;
+                            this.push(RssToken::RParen);
+                        }
+                        Ok(())
+                    })?;
+                }
+                Ok(())
             }
-            end
         }
-        _ => return None,
-    };
+    }
 
-    loop {
-        let ws = skip_js_whitespace(bytes, cursor);
-        if ws >= bytes.len() {
-            return Some(ws);
+    fn lower_pattern_element(
+        &mut self,
+        element: &PatternElement,
+        declare: bool,
+        value: impl FnOnce(&mut Self) -> Result<(), ParseError>,
+    ) -> Result<(), ParseError> {
+        let Some(default) = &element.default else {
+            return self.bind_target(&element.pattern, declare, value);
+        };
+        if declare && let Pattern::Target(target) = &element.pattern {
+            let ExprKind::Ident(name) = &target.kind else {
+                unreachable!("declarations only bind names");
+            };
+            self.bind_target(&element.pattern, true, value)?;
+            return self.lower_default_check(name, default);
         }
+        let (line, span) = (self.line, self.span);
+        let temp = self.next_temp("destructure");
+        self.push(RssToken::Let);
+        self.push_ident(&temp);
+        self.push(RssToken::Equal);
+        value(self)?;
+        self.push(RssToken::Semicolon);
+        self.lower_default_check(&temp, default)?;
+        self.at(line, span);
+        self.lower_pattern(&element.pattern, &temp, declare)
+    }
 
-        if bytes[ws] == b'.' {
-            let member_start = skip_js_whitespace(bytes, ws + 1);
-            if member_start < bytes.len() && is_ident_start(bytes[member_start] as char) {
-                let mut member_end = member_start + 1;
-                while member_end < bytes.len() && is_ident_continue(bytes[member_end] as char) {
-                    member_end += 1;
-                }
-                cursor = member_end;
-                continue;
+    fn lower_property_key(&mut self, key: &PropertyKey) -> Result<(), ParseError> {
+        match key {
+            PropertyKey::Name(name) | PropertyKey::String(name) => {
+                self.push(RssToken::String(name.clone()))
             }
-            return Some(ws);
+            PropertyKey::Int(value) => self.push(RssToken::Int(*value)),
+            PropertyKey::Computed(key) => self.lower_expr(key)?,
         }
+        Ok(())
+    }
 
-        if bytes[ws] == b'[' {
-            cursor = parse_js_balanced_segment_end(source, ws, b'[', b']')?;
-            continue;
+    fn lower_block(&mut self, stmts: &[Stmt]) -> Result<(), ParseError> {
+        self.push(RssToken::LBrace);
+        for stmt in stmts {
+            self.lower_stmt(stmt)?;
         }
+        self.push(RssToken::RBrace);
+        Ok(())
+    }
 
-        if bytes[ws] == b'(' {
-            cursor = parse_js_balanced_segment_end(source, ws, b'(', b')')?;
-            continue;
+    fn lower_for(
+        &mut self,
+        stmt: &Stmt,
+        init: Option<&Stmt>,
+        condition: Option<&Expr>,
+        update: Option<&Expr>,
+        body: &[Stmt],
+    ) -> Result<(), ParseError> {
+        self.push(RssToken::For);
+        self.push(RssToken::LParen);
+        match init.map(|init| &init.kind) {
+            None => self.push(RssToken::Null),
+            Some(StmtKind::Let(declarators)) => {
+                let [declarator] = declarators.as_slice() else {
+                    return Err(lower_error(
+                        stmt.line,
+                        stmt.span,
+                        "for loops support a single declaration in the initializer",
+                    ));
+                };
+                let Pattern::Target(Expr {
+                    kind: ExprKind::Ident(name),
+                    ..
+                }) = &declarator.target
+                else {
+                    return Err(lower_error(
+                        declarator.line,
+                        declarator.span,
+                        "destructuring is not supported in for loop initializers",
+                    ));
+                };
+                self.at(declarator.line, declarator.span);
+                self.push(RssToken::Let);
+                self.push_ident(name);
+                self.push(RssToken::Equal);
+                match &declarator.init {
+                    Some(value) => self.lower_expr(value)?,
+                    None => self.push(RssToken::Null),
+                }
+            }
+            Some(StmtKind::Expr(expr)) => self.lower_for_clause(expr)?,
+            Some(_) => {
+                return Err(lower_error(
+                    stmt.line,
+                    stmt.span,
+                    "unsupported for loop initializer",
+                ));
+            }
+        }
+        self.push(RssToken::Semicolon);
+        match condition {
+            Some(condition) => self.lower_expr(condition)?,
+            None => self.push(RssToken::True),
         }
+        self.push(RssToken::Semicolon);
+        match update {
+            Some(update) => self.lower_for_clause(update)?,
+            None => self.push(RssToken::Null),
+        }
+        self.push(RssToken::RParen);
+        self.lower_block(body)
+    }
 
-        return Some(ws);
+    /// Lowers a for-loop initializer or update, where only plain variables
+    /// can be assigned.
+    fn lower_for_clause(&mut self, expr: &Expr) -> Result<(), ParseError> {
+        match &expr.kind {
+            ExprKind::Assign { target, .. } | ExprKind::Update { target, .. }
+                if !matches!(target.kind, ExprKind::Ident(_)) =>
+            {
+                Err(lower_error(
+                    expr.line,
+                    expr.span,
+                    "for loop clauses can only assign plain variables",
+                ))
+            }
+            ExprKind::Assign { .. } | ExprKind::Update { .. } => self.lower_assignment(expr),
+            _ => self.lower_expr(expr),
+        }
     }
-}
 
-fn parse_js_balanced_segment_end(source: &str, start: usize, open: u8, close: u8) -> Option<usize> {
-    let bytes = source.as_bytes();
-    if start >= bytes.len() || bytes[start] != open {
-        return None;
+    fn lower_for_of(
+        &mut self,
+        binding: &Pattern,
+        iterable: &Expr,
+        body: &[Stmt],
+    ) -> Result<(), ParseError> {
+        self.push(RssToken::For);
+        if let Pattern::Target(Expr {
+            kind: ExprKind::Ident(name),
+            ..
+        }) = binding
+        {
+            if let Some(object) = object_helper_arg(iterable, "keys") {
+                self.push_key_binding(name);
+                self.lower_paren_expr(object)?;
+            } else {
+                self.push_ident(name);
+                self.push_ident("in");
+                let values = object_helper_arg(iterable, "values");
+                self.lower_paren_expr(values.unwrap_or(iterable))?;
+            }
+            return self.lower_block(body);
+        }
+        if let (Some(object), Some((key, value))) =
+            (object_helper_arg(iterable, "entries"), entry_names(binding))
+        {
+            self.push(RssToken::LParen);
+            self.push_ident(key);
+            self.push(RssToken::Comma);
+            self.push_ident(value);
+            self.push(RssToken::RParen);
+            self.push_ident("in");
+            self.lower_paren_expr(object)?;
+            return self.lower_block(body);
+        }
+        // Any other pattern unpacks each item at the top of the body.
+        let item = self.next_temp("item");
+        self.push_ident(&item);
+        self.push_ident("in");
+        self.lower_paren_expr(iterable)?;
+        self.push(RssToken::LBrace);
+        self.lower_pattern(binding, &item, true)?;
+        for stmt in body {
+            self.lower_stmt(stmt)?;
+        }
+        self.push(RssToken::RBrace);
+        Ok(())
     }
 
-    let mut i = start;
-    let mut depth = 0usize;
-    let mut in_string: Option<u8> = None;
-    let mut escaped = false;
+    /// Pushes `(name, _) in`, binding only the keys of a for-in loop.
+    fn push_key_binding(&mut self, name: &str) {
+        self.push(RssToken::LParen);
+        self.push_ident(name);
+        self.push(RssToken::Comma);
+        self.push_ident("_");
+        self.push(RssToken::RParen);
+        self.push_ident("in");
+    }
 
-    while i < bytes.len() {
-        let b = bytes[i];
-        if let Some(delim) = in_string {
-            if escaped {
-                escaped = false;
-            } else if b == b'\\' {
-                escaped = true;
-            } else if b == delim {
-                in_string = None;
+    fn lower_expr_stmt(&mut self, expr: &Expr) -> Result<(), ParseError> {
+        if let Some(call) = self.class_call(expr) {
+            // The result is unused; only the receiver write-back matters.
+            match self.write_back_target(&call) {
+                Some(target) => {
+                    self.push_write_back_target(target)?;
+                    self.at(expr.line, expr.span);
+                    self.push(RssToken::Equal);
+                    self.lower_class_call_pair(&call)?;
+                    self.push(RssToken::LBracket);
+                    self.push(RssToken::Int(1));
+                    self.push(RssToken::RBracket);
+                }
+                None => self.lower_class_call_pair(&call)?,
             }
-            i += 1;
-            continue;
+            self.push(RssToken::Semicolon);
+            return Ok(());
         }
-
-        if b == b'"' || b == b'\'' || b == b'`' {
-            in_string = Some(b);
-            escaped = false;
-            i += 1;
-            continue;
+        match &expr.kind {
+            ExprKind::Assign {
+                target,
+                op: None,
+                value,
+            } if let Some(hoisted) = self.hoist_class_call(value)? => {
+                let assign = Expr::new(
+                    ExprKind::Assign {
+                        target: target.clone(),
+                        op: None,
+                        value: Box::new(hoisted),
+                    },
+                    expr.line,
+                    expr.span,
+                );
+                self.lower_assignment(&assign)?;
+            }
+            ExprKind::Assign { .. } | ExprKind::Update { .. } => self.lower_assignment(expr)?,
+            ExprKind::Call { callee, args } if matches!(callee.kind, ExprKind::Super) => {
+                self.lower_super_constructor_call(expr, args)?;
+            }
+            ExprKind::Destructure { pattern, value } => {
+                let source = self.declare_temp("destructure", value)?;
+                self.at(expr.line, expr.span);
+                return self.lower_pattern(pattern, &source, false);
+            }
+            ExprKind::Call { callee, args } if self.lower_mutating_method(callee, args)? => {}
+            _ => self.lower_expr(expr)?,
         }
+        self.push(RssToken::Semicolon);
+        Ok(())
+    }
 
-        if b == open {
-            depth += 1;
-            i += 1;
-            continue;
+    /// Lowers `target = value`, compound assignments, and `++`/`--` into a
+    /// plain assignment without its terminator.
+    fn lower_assignment(&mut self, expr: &Expr) -> Result<(), ParseError> {
+        let (target, op, value) = match &expr.kind {
+            ExprKind::Assign { target, op, value } => (target, *op, Some(value)),
+            ExprKind::Update { target, increment } => (
+                target,
+                Some(if *increment {
+                    BinaryOp::Add
+                } else {
+                    BinaryOp::Sub
+                }),
+                None,
+            ),
+            _ => unreachable!("only assignments are lowered here"),
+        };
+        self.lower_assign_target(target)?;
+        self.at(expr.line, expr.span);
+        self.push(RssToken::Equal);
+        match op {
+            Some(op) => {
+                self.push(RssToken::LParen);
+                self.lower_expr(target)?;
+                self.push(binary_token(op));
+                match value {
+                    Some(value) => self.lower_paren_expr(value)?,
+                    None => self.push(RssToken::Int(1)),
+                }
+                self.push(RssToken::RParen);
+            }
+            None => {
+                if let Some(value) = value {
+                    self.lower_expr(value)?;
+                }
+            }
         }
+        Ok(())
+    }
 
-        if b == close {
-            depth = depth.saturating_sub(1);
-            i += 1;
-            if depth == 0 {
-                return Some(i);
+    fn lower_assign_target(&mut self, target: &Expr) -> Result<(), ParseError> {
+        self.at(target.line, target.span);
+        match &target.kind {
+            ExprKind::Ident(name) => self.push_ident(name),
+            ExprKind::This => self.lower_expr(target)?,
+            ExprKind::Member {
+                object, property, ..
+            } if is_plain_receiver(object) => {
+                self.lower_expr(object)?;
+                self.push(RssToken::Dot);
+                self.push_ident(property);
+            }
+            ExprKind::Index { object, index, .. } if is_plain_receiver(object) => {
+                self.lower_expr(object)?;
+                self.push(RssToken::LBracket);
+                self.lower_expr(index)?;
+                self.push(RssToken::RBracket);
+            }
+            _ => {
+                return Err(lower_error(
+                    target.line,
+                    target.span,
+                    "only 'name', 'name.member', and 'name[index]' can be assigned",
+                ));
             }
-            continue;
         }
-
-        i += 1;
+        Ok(())
     }
 
-    None
-}
-
-fn parse_js_string_end(source: &str, start: usize) -> Option<usize> {
-    let bytes = source.as_bytes();
-    if start >= bytes.len() {
-        return None;
-    }
-    let quote = bytes[start];
-    if quote != b'"' && quote != b'\'' && quote != b'`' {
-        return None;
-    }
-    let mut i = start + 1;
-    let mut escaped = false;
-    while i < bytes.len() {
-        let b = bytes[i];
-        if escaped {
-            escaped = false;
-        } else if b == b'\\' {
-            escaped = true;
-        } else if b == quote {
-            return Some(i + 1);
+    /// Recognizes `receiver.method(args)` calls of a class method and
+    /// `super.method(args)`.
+    fn class_call<'a>(&self, expr: &'a Expr) -> Option<ClassCall<'a>> {
+        let ExprKind::Call { callee, args } = &expr.kind else {
+            return None;
+        };
+        let ExprKind::Member {
+            object,
+            property,
+            optional: false,
+        } = &callee.kind
+        else {
+            return None;
+        };
+        match &object.kind {
+            ExprKind::Super => Some(ClassCall::Super {
+                method: property,
+                args,
+                call: expr,
+            }),
+            ExprKind::Ident(name)
+                if name == "console" || name == "Object" || self.classes.is_class(name) =>
+            {
+                None
+            }
+            _ if self.vm_member_path(object, property).is_some() => None,
+            _ if self.classes.has_method(property) => Some(ClassCall::Send {
+                receiver: object,
+                method: property,
+                args,
+            }),
+            _ => None,
         }
-        i += 1;
     }
-    None
-}
 
-fn skip_js_whitespace(bytes: &[u8], mut index: usize) -> usize {
-    while index < bytes.len() && bytes[index].is_ascii_whitespace() {
-        index += 1;
+    /// The variable a method call writes its receiver back to, when the
+    /// receiver is one: `this`, `name`, `name.member`, or `name[index]`.
+    fn write_back_target<'a>(&self, call: &ClassCall<'a>) -> Option<&'a Expr> {
+        match call {
+            ClassCall::Send { receiver, .. } => match &receiver.kind {
+                ExprKind::Ident(_) | ExprKind::This => Some(receiver),
+                ExprKind::Member {
+                    object,
+                    optional: false,
+                    ..
+                }
+                | ExprKind::Index {
+                    object,
+                    optional: false,
+                    ..
+                } if is_plain_receiver(object) => Some(receiver),
+                _ => None,
+            },
+            ClassCall::Super { call, .. } => Some(call),
+        }
     }
-    index
-}
 
-fn previous_non_whitespace(bytes: &[u8], mut index: usize) -> Option<usize> {
-    while index > 0 {
-        index -= 1;
-        if !bytes[index].is_ascii_whitespace() {
-            return Some(index);
+    fn push_write_back_target(&mut self, target: &Expr) -> Result<(), ParseError> {
+        if matches!(target.kind, ExprKind::Call { .. }) {
+            // `super.method(...)` writes back to `this`.
+            self.push_ident(THIS_NAME);
+            return Ok(());
         }
+        self.lower_assign_target(target)
     }
-    None
-}
 
-fn is_js_external_decl_line(line: &str) -> bool {
-    let trimmed = line.trim();
-    if trimmed.is_empty() {
-        return false;
+    /// Pushes a class method call yielding `[result, receiver]`.
+    fn lower_class_call_pair(&mut self, call: &ClassCall) -> Result<(), ParseError> {
+        match call {
+            ClassCall::Send {
+                receiver,
+                method,
+                args,
+            } => {
+                let helper = self.classes.send(method);
+                self.push_ident(&helper);
+                self.push(RssToken::LParen);
+                self.lower_expr(receiver)?;
+                self.push(RssToken::Comma);
+                self.lower_array_literal(args)?;
+                self.push(RssToken::RParen);
+            }
+            ClassCall::Super { method, args, call } => {
+                let context = self.functions.last().cloned().unwrap_or_default();
+                let resolved = context
+                    .class
+                    .as_deref()
+                    .filter(|_| context.binds_this)
+                    .and_then(|class| self.classes.parent(class))
+                    .and_then(|parent| {
+                        self.classes
+                            .resolve_member(parent, method, MethodKind::Instance)
+                    });
+                let Some((function, signature)) = resolved else {
+                    return Err(lower_error(
+                        call.line,
+                        call.span,
+                        &format!("'super.{method}(...)' does not name a parent class method"),
+                    ));
+                };
+                self.push_ident(&function);
+                self.push(RssToken::LParen);
+                self.push_ident(THIS_NAME);
+                self.lower_known_args(signature, args, true)?;
+            }
+        }
+        Ok(())
     }
 
-    if trimmed.starts_with("import ") {
-        return true;
+    /// For `let x = obj.method()`, `x = obj.method()`, and
+    /// `return obj.method()`: evaluates the call into a temporary, writes the
+    /// receiver back, and returns the expression reading the result.
+    fn hoist_class_call(&mut self, value: &Expr) -> Result<Option<Expr>, ParseError> {
+        let Some(call) = self.class_call(value) else {
+            return Ok(None);
+        };
+        let Some(target) = self.write_back_target(&call) else {
+            return Ok(None);
+        };
+        let pair = self.next_temp("pair");
+        self.at(value.line, value.span);
+        self.push(RssToken::Let);
+        self.push_ident(&pair);
+        self.push(RssToken::Equal);
+        self.lower_class_call_pair(&call)?;
+        self.push(RssToken::Semicolon);
+        self.push_write_back_target(target)?;
+        self.at(value.line, value.span);
+        self.push(RssToken::Equal);
+        self.push_ident(&pair);
+        self.push(RssToken::LBracket);
+        self.push(RssToken::Int(1));
+        self.push(RssToken::RBracket);
+        self.push(RssToken::Semicolon);
+        let read = |kind| Expr::new(kind, value.line, value.span);
+        Ok(Some(read(ExprKind::Index {
+            object: Box::new(read(ExprKind::Ident(pair))),
+            index: Box::new(read(ExprKind::Int(0))),
+            optional: false,
+        })))
     }
 
-    if !(trimmed.starts_with("let ")
-        || trimmed.starts_with("const ")
-        || trimmed.starts_with("var "))
-    {
-        return false;
+    /// Lowers `super(args)` in a derived constructor into a call of the
+    /// nearest ancestor constructor on the instance under construction.
+    fn lower_super_constructor_call(
+        &mut self,
+        call: &Expr,
+        args: &[Element],
+    ) -> Result<(), ParseError> {
+        let context = self.functions.last().cloned().unwrap_or_default();
+        let parent = context
+            .class
+            .as_deref()
+            .filter(|_| context.returns == Returns::Instance)
+            .and_then(|class| self.classes.parent(class));
+        let Some(parent) = parent else {
+            return Err(lower_error(
+                call.line,
+                call.span,
+                "'super(...)' is only valid in the constructor of a derived class",
+            ));
+        };
+        let Some((function, signature)) = self.classes.resolve_constructor(parent) else {
+            // No ancestor declares a constructor, so there is nothing to run.
+            self.push(RssToken::Null);
+            return Ok(());
+        };
+        self.push_ident(THIS_NAME);
+        self.push(RssToken::Equal);
+        self.push_ident(&function);
+        self.push(RssToken::LParen);
+        self.push_ident(THIS_NAME);
+        self.lower_known_args(signature, args, true)
     }
 
-    trimmed.contains("require(")
-}
+    /// Pushes a fresh instance of `class`: an empty map tagged in its `null`
+    /// slot.
+    fn push_instance(&mut self, class: &str) {
+        self.push(RssToken::LBrace);
+        self.push(RssToken::LBracket);
+        self.push(RssToken::Null);
+        self.push(RssToken::RBracket);
+        self.push(RssToken::Colon);
+        self.push(RssToken::String(class.to_string()));
+        self.push(RssToken::RBrace);
+    }
 
-fn is_js_vm_require_line(line: &str) -> bool {
-    let trimmed = line.trim();
-    if trimmed.is_empty() {
-        return false;
+    /// Lowers the in-place array methods `sort`, `splice`, and `push` into a
+    /// reassignment of their receiver. Returns false for any other call.
+    fn lower_mutating_method(
+        &mut self,
+        callee: &Expr,
+        args: &[Element],
+    ) -> Result<bool, ParseError> {
+        let ExprKind::Member {
+            object: target,
+            property,
+            optional: false,
+        } = &callee.kind
+        else {
+            return Ok(false);
+        };
+        if !matches!(property.as_str(), "sort" | "splice" | "push")
+            || self.classes.has_method(property)
+            || matches!(target.kind, ExprKind::Super)
+            || self.is_vm_alias(target)
+            || matches!(&target.kind, ExprKind::Ident(name) if name == "console" || name == "Object")
+        {
+            return Ok(false);
+        }
+        let error = |message: &str| lower_error(callee.line, callee.span, message);
+        self.lower_assign_target(target)?;
+        self.at(callee.line, callee.span);
+        self.push(RssToken::Equal);
+        match (property.as_str(), args) {
+            ("sort", []) => {
+                self.push_namespace_call_start("array", "sort");
+                self.lower_expr(target)?;
+                self.push(RssToken::RParen);
+            }
+            ("sort", [Element::Item(compare)]) => {
+                self.uses_sort_helper = true;
+                self.push_ident(SORT_BY_HELPER_NAME);
+                self.push(RssToken::LParen);
+                self.lower_expr(target)?;
+                self.push(RssToken::Comma);
+                self.lower_comparator(compare)?;
+                self.push(RssToken::RParen);
+            }
+            ("sort", _) => {
+                return Err(error(
+                    "Array.prototype.sort expects at most one comparator argument",
+                ));
+            }
+            ("splice", [Element::Item(start), Element::Item(count), items @ ..]) => {
+                match (&count.kind, items) {
+                    (ExprKind::Int(1), []) => {
+                        self.push_namespace_call_start("array", "remove");
+                        self.lower_expr(target)?;
+                        self.push(RssToken::Comma);
+                        self.lower_expr(start)?;
+                        self.push(RssToken::RParen);
+                    }
+                    (ExprKind::Int(0), [Element::Item(item)]) => {
+                        self.push_namespace_call_start("array", "insert");
+                        self.lower_expr(target)?;
+                        self.push(RssToken::Comma);
+                        self.lower_expr(start)?;
+                        self.push(RssToken::Comma);
+                        self.lower_expr(item)?;
+                        self.push(RssToken::RParen);
+                    }
+                    _ => {
+                        // target[:start] + [items...] + target[start + count:]
+                        self.lower_paren_expr(target)?;
+                        self.push(RssToken::LBracket);
+                        self.push(RssToken::Colon);
+                        self.lower_expr(start)?;
+                        self.push(RssToken::RBracket);
+                        self.push(RssToken::Plus);
+                        self.lower_array_literal(items)?;
+                        self.push(RssToken::Plus);
+                        self.lower_paren_expr(target)?;
+                        self.push(RssToken::LBracket);
+                        self.push(RssToken::LParen);
+                        self.lower_paren_expr(start)?;
+                        self.push(RssToken::Plus);
+                        self.lower_paren_expr(count)?;
+                        self.push(RssToken::RParen);
+                        self.push(RssToken::Colon);
+                        self.push(RssToken::RBracket);
+                    }
+                }
+            }
+            ("splice", _) => {
+                return Err(error(
+                    "Array.prototype.splice expects 'start, deleteCount[, ...items]'",
+                ));
+            }
+            (_, items) => {
+                self.lower_paren_expr(target)?;
+                self.push(RssToken::Plus);
+                self.lower_array_literal(items)?;
+            }
+        }
+        Ok(true)
     }
-    trimmed.contains("require(\"vm\")") || trimmed.contains("require('vm')")
-}
 
-fn is_js_vm_import_block(block: &str) -> bool {
-    let trimmed = block.trim();
-    if !trimmed.starts_with("import ") {
-        return false;
+    /// Lowers a `(a, b) => number` comparator into the `less(a, b)` predicate
+    /// the sort helper expects.
+    fn lower_comparator(&mut self, compare: &Expr) -> Result<(), ParseError> {
+        match &compare.kind {
+            ExprKind::Function(function) => self.lower_closure(function, true),
+            ExprKind::Ident(name) => {
+                self.at(compare.line, compare.span);
+                self.push(RssToken::Pipe);
+                self.push_ident("lhs");
+                self.push(RssToken::Comma);
+                self.push_ident("rhs");
+                self.push(RssToken::Pipe);
+                self.push(RssToken::LParen);
+                self.push_ident(name);
+                self.push(RssToken::LParen);
+                self.push_ident("lhs");
+                self.push(RssToken::Comma);
+                self.push_ident("rhs");
+                self.push(RssToken::RParen);
+                self.push(RssToken::Less);
+                self.push(RssToken::Int(0));
+                self.push(RssToken::RParen);
+                Ok(())
+            }
+            _ => Err(lower_error(
+                compare.line,
+                compare.span,
+                "sort comparators must be a function or a function name",
+            )),
+        }
     }
-    if let Some(from_idx) = trimmed.find(" from ") {
-        let tail = &trimmed[from_idx + " from ".len()..];
-        return extract_quoted_literal(tail).is_some_and(|(spec, _)| spec == "vm");
+
+    fn lower_less_than_zero(&mut self, value: &Expr) -> Result<(), ParseError> {
+        self.push(RssToken::LParen);
+        self.lower_paren_expr(value)?;
+        self.push(RssToken::Less);
+        self.push(RssToken::Int(0));
+        self.push(RssToken::RParen);
+        Ok(())
     }
-    let tail = &trimmed["import ".len()..];
-    extract_quoted_literal(tail).is_some_and(|(spec, _)| spec == "vm")
-}
 
-fn parse_js_vm_namespace_alias_from_import_block(block: &str) -> Option<String> {
-    let trimmed = block.trim();
-    if !is_js_vm_import_block(trimmed) {
-        return None;
+    fn lower_paren_expr(&mut self, expr: &Expr) -> Result<(), ParseError> {
+        self.push(RssToken::LParen);
+        self.lower_expr(expr)?;
+        self.push(RssToken::RParen);
+        Ok(())
     }
-    let from_idx = trimmed.find(" from ")?;
-    let head = trimmed["import ".len()..from_idx].trim();
-    let alias = head.strip_prefix("* as ")?;
-    let alias = alias.trim();
-    if is_valid_ident(alias) {
-        Some(alias.to_string())
-    } else {
-        None
+
+    /// Lowers an array literal. Spread elements concatenate, so
+    /// `[a, ...xs, b]` becomes `([a] + (xs) + [b])`.
+    fn lower_array_literal(&mut self, items: &[Element]) -> Result<(), ParseError> {
+        if !items.iter().any(|item| matches!(item, Element::Spread(_))) {
+            self.push(RssToken::LBracket);
+            self.lower_elements(items)?;
+            self.push(RssToken::RBracket);
+            return Ok(());
+        }
+        self.push(RssToken::LParen);
+        // Leading with a plain array keeps the result an array even when the
+        // first spread operand is not one.
+        let mut rest = items;
+        let plain = rest
+            .iter()
+            .position(|item| matches!(item, Element::Spread(_)))
+            .unwrap_or(rest.len());
+        self.push(RssToken::LBracket);
+        self.lower_elements(&rest[..plain])?;
+        self.push(RssToken::RBracket);
+        rest = &rest[plain..];
+        while let [Element::Spread(spread), tail @ ..] = rest {
+            self.push(RssToken::Plus);
+            self.lower_paren_expr(spread)?;
+            let plain = tail
+                .iter()
+                .position(|item| matches!(item, Element::Spread(_)))
+                .unwrap_or(tail.len());
+            if plain > 0 {
+                self.push(RssToken::Plus);
+                self.push(RssToken::LBracket);
+                self.lower_elements(&tail[..plain])?;
+                self.push(RssToken::RBracket);
+            }
+            rest = &tail[plain..];
+        }
+        self.push(RssToken::RParen);
+        Ok(())
     }
-}
 
-fn parse_js_vm_require_namespace_alias(line: &str) -> Option<String> {
-    let trimmed = line.trim().trim_end_matches(';').trim();
-    let rest = if let Some(rest) = trimmed.strip_prefix("let ") {
-        rest
-    } else if let Some(rest) = trimmed.strip_prefix("const ") {
-        rest
-    } else if let Some(rest) = trimmed.strip_prefix("var ") {
-        rest
-    } else {
-        return None;
-    };
-    let (name, rhs) = rest.split_once('=')?;
-    let name = name.trim();
-    if !is_valid_ident(name) {
-        return None;
+    /// Lowers comma-separated array elements without spreads; holes read as
+    /// `undefined`.
+    fn lower_elements(&mut self, items: &[Element]) -> Result<(), ParseError> {
+        for (index, item) in items.iter().enumerate() {
+            if index > 0 {
+                self.push(RssToken::Comma);
+            }
+            self.lower_element(item)?;
+        }
+        Ok(())
     }
-    let (spec, remainder) = parse_js_require_call(rhs.trim())?;
-    if spec == "vm" && remainder.is_empty() {
-        Some(name.to_string())
-    } else {
-        None
+
+    fn lower_element(&mut self, item: &Element) -> Result<(), ParseError> {
+        match item {
+            Element::Item(item) => self.lower_expr(item),
+            Element::Hole => {
+                self.push(RssToken::Null);
+                Ok(())
+            }
+            Element::Spread(spread) => Err(lower_error(
+                spread.line,
+                spread.span,
+                "spread arguments are only supported when calling functions declared in this file",
+            )),
+        }
     }
-}
 
-fn parse_js_require_call(input: &str) -> Option<(String, String)> {
-    let mut rest = input.trim().strip_prefix("require")?.trim_start();
-    rest = rest.strip_prefix('(')?.trim_start();
-    let quote = rest.chars().next()?;
-    if quote != '"' && quote != '\'' {
-        return None;
+    fn lower_args(&mut self, args: &[Element]) -> Result<(), ParseError> {
+        self.push(RssToken::LParen);
+        self.lower_elements(args)?;
+        self.push(RssToken::RParen);
+        Ok(())
     }
-    rest = &rest[quote.len_utf8()..];
-    let mut end = None;
-    for (idx, ch) in rest.char_indices() {
-        if ch == quote {
-            end = Some(idx);
-            break;
+
+    /// Lowers the arguments of a call to a function declared in the program.
+    /// Missing arguments are passed as `undefined`, trailing ones are packed
+    /// into the rest parameter, and spread arguments are read back by index
+    /// from the concatenated argument array.
+    fn lower_known_args(
+        &mut self,
+        signature: Signature,
+        args: &[Element],
+        after_receiver: bool,
+    ) -> Result<(), ParseError> {
+        if !after_receiver {
+            self.push(RssToken::LParen);
         }
+        let mut count = usize::from(after_receiver);
+        let mut separate = |this: &mut Self| {
+            if count > 0 {
+                this.push(RssToken::Comma);
+            }
+            count += 1;
+        };
+        if let Some(spread) = args.iter().find_map(|arg| match arg {
+            Element::Spread(spread) => Some(spread),
+            _ => None,
+        }) {
+            if !args.iter().all(|arg| match arg {
+                Element::Item(arg) | Element::Spread(arg) => is_repeatable(arg),
+                Element::Hole => true,
+            }) {
+                return Err(lower_error(
+                    spread.line,
+                    spread.span,
+                    "calls with spread arguments only take variables and literals",
+                ));
+            }
+            for index in 0..signature.params {
+                separate(self);
+                self.push(RssToken::LParen);
+                self.lower_array_literal(args)?;
+                self.push(RssToken::RParen);
+                self.push(RssToken::Question);
+                self.push(RssToken::Dot);
+                self.push(RssToken::LBracket);
+                self.push(RssToken::Int(index as i64));
+                self.push(RssToken::RBracket);
+            }
+            if signature.rest {
+                separate(self);
+                self.push(RssToken::LParen);
+                self.lower_array_literal(args)?;
+                self.push(RssToken::RParen);
+                self.push(RssToken::LBracket);
+                self.push(RssToken::Int(signature.params as i64));
+                self.push(RssToken::Colon);
+                self.push(RssToken::RBracket);
+            }
+            self.push(RssToken::RParen);
+            return Ok(());
+        }
+
+        let positional = if signature.rest {
+            args.len().min(signature.params)
+        } else {
+            args.len()
+        };
+        for arg in &args[..positional] {
+            separate(self);
+            self.lower_element(arg)?;
+        }
+        for _ in positional..signature.params {
+            separate(self);
+            self.push(RssToken::Null);
+        }
+        if signature.rest {
+            separate(self);
+            self.push(RssToken::LBracket);
+            self.lower_elements(&args[positional..])?;
+            self.push(RssToken::RBracket);
+        }
+        self.push(RssToken::RParen);
+        Ok(())
     }
-    let end = end?;
-    let spec = rest[..end].to_string();
-    let tail = rest[end + quote.len_utf8()..].trim_start();
-    if !tail.starts_with(')') {
-        return None;
-    }
-    let remainder = tail[1..].trim().to_string();
-    Some((spec, remainder))
-}
 
-fn rewrite_js_vm_namespace_calls(line: &str, vm_namespace_aliases: &HashSet<String>) -> String {
-    if vm_namespace_aliases.is_empty() {
-        return line.to_string();
-    }
-
-    let bytes = line.as_bytes();
-    let mut out = String::with_capacity(line.len());
-    let mut i = 0usize;
-    let mut in_string: Option<u8> = None;
-    let mut escaped = false;
-    let mut in_line_comment = false;
-
-    while i < bytes.len() {
-        let b = bytes[i];
-
-        if in_line_comment {
-            out.push(b as char);
-            i += 1;
-            continue;
-        }
-
-        if let Some(delim) = in_string {
-            out.push(b as char);
-            if escaped {
-                escaped = false;
-            } else if b == b'\\' {
-                escaped = true;
-            } else if b == delim {
-                in_string = None;
-            }
-            i += 1;
-            continue;
-        }
-
-        if b == b'/' && i + 1 < bytes.len() && bytes[i + 1] == b'/' {
-            out.push('/');
-            out.push('/');
-            i += 2;
-            in_line_comment = true;
-            continue;
-        }
-
-        if b == b'"' || b == b'\'' || b == b'`' {
-            out.push(b as char);
-            in_string = Some(b);
-            escaped = false;
-            i += 1;
-            continue;
-        }
-
-        if is_ident_start(b as char) {
-            let start = i;
-            i += 1;
-            while i < bytes.len() && is_ident_continue(bytes[i] as char) {
-                i += 1;
-            }
-            let ident = &line[start..i];
-            if vm_namespace_aliases.contains(ident) {
-                let mut j = i;
-                while j < bytes.len()
-                    && bytes[j].is_ascii_whitespace()
-                    && bytes[j] != b'\n'
-                    && bytes[j] != b'\r'
+    fn lower_expr(&mut self, expr: &Expr) -> Result<(), ParseError> {
+        self.at(expr.line, expr.span);
+        match &expr.kind {
+            ExprKind::Null => self.push(RssToken::Null),
+            ExprKind::Bool(true) => self.push(RssToken::True),
+            ExprKind::Bool(false) => self.push(RssToken::False),
+            ExprKind::Int(value) => self.push(RssToken::Int(*value)),
+            ExprKind::Float(value) => self.push(RssToken::Float(*value)),
+            ExprKind::String(value) => self.push(RssToken::String(value.clone())),
+            ExprKind::Template {
+                quasis,
+                substitutions,
+            } => {
+                if substitutions.is_empty() {
+                    self.push(RssToken::String(quasis.concat()));
+                    return Ok(());
+                }
+                let template = quasis
+                    .iter()
+                    .map(|quasi| quasi.replace('{', "{{").replace('}', "}}"))
+                    .collect::<Vec<_>>()
+                    .join("{}");
+                self.push_ident("format");
+                self.push(RssToken::Bang);
+                self.push(RssToken::LParen);
+                self.push(RssToken::String(template));
+                for substitution in substitutions {
+                    self.push(RssToken::Comma);
+                    self.lower_expr(substitution)?;
+                }
+                self.push(RssToken::RParen);
+            }
+            ExprKind::Ident(name) => self.push_ident(name),
+            ExprKind::This => {
+                if !self
+                    .functions
+                    .last()
+                    .is_some_and(|context| context.binds_this)
                 {
-                    j += 1;
+                    return Err(lower_error(
+                        expr.line,
+                        expr.span,
+                        "'this' is only available in class constructors, methods, and getters, and in arrow functions inside them",
+                    ));
                 }
-                if j < bytes.len() && bytes[j] == b'.' {
-                    let mut k = j;
-                    let mut segments = Vec::<String>::new();
-                    loop {
-                        if k >= bytes.len() || bytes[k] != b'.' {
-                            break;
-                        }
-                        k += 1;
-                        while k < bytes.len()
-                            && bytes[k].is_ascii_whitespace()
-                            && bytes[k] != b'\n'
-                            && bytes[k] != b'\r'
-                        {
-                            k += 1;
-                        }
-                        if k >= bytes.len() || !is_ident_start(bytes[k] as char) {
-                            segments.clear();
-                            break;
-                        }
-                        let member_start = k;
-                        k += 1;
-                        while k < bytes.len() && is_ident_continue(bytes[k] as char) {
-                            k += 1;
-                        }
-                        segments.push(line[member_start..k].to_string());
-                        let mut next = k;
-                        while next < bytes.len()
-                            && bytes[next].is_ascii_whitespace()
-                            && bytes[next] != b'\n'
-                            && bytes[next] != b'\r'
-                        {
-                            next += 1;
-                        }
-                        if next < bytes.len() && bytes[next] == b'.' {
-                            k = next;
-                            continue;
-                        }
-                        k = next;
-                        break;
-                    }
-                    if !segments.is_empty() && k < bytes.len() && bytes[k] == b'(' {
-                        if segments.len() == 1 {
-                            out.push_str(&segments[0]);
-                        } else {
-                            out.push_str("vm::");
-                            out.push_str(&segments.join("::"));
-                        }
-                        i = k;
-                        continue;
+                self.push_ident(THIS_NAME);
+            }
+            ExprKind::Super => {
+                return Err(lower_error(
+                    expr.line,
+                    expr.span,
+                    "'super' can only call the parent constructor or a parent method",
+                ));
+            }
+            ExprKind::New { class, args } => {
+                if !self.classes.is_class(class) {
+                    return Err(lower_error(
+                        expr.line,
+                        expr.span,
+                        &format!("'new {class}(...)' needs a class declared in this file"),
+                    ));
+                }
+                match self.classes.resolve_constructor(class) {
+                    Some((function, signature)) => {
+                        self.push_ident(&function);
+                        self.push(RssToken::LParen);
+                        self.push_instance(class);
+                        self.lower_known_args(signature, args, true)?;
                     }
+                    None => self.push_instance(class),
+                }
+            }
+            ExprKind::Path(_) => {
+                return Err(lower_error(
+                    expr.line,
+                    expr.span,
+                    "namespace paths can only be called",
+                ));
+            }
+            ExprKind::Array(items) => self.lower_array_literal(items)?,
+            ExprKind::Object(members) => self.lower_object(members)?,
+            ExprKind::Member {
+                object,
+                property,
+                optional: false,
+            } if self.classes.has_getter(property) => {
+                let helper = self.classes.get(property);
+                self.push_ident(&helper);
+                self.push(RssToken::LParen);
+                self.lower_expr(object)?;
+                self.push(RssToken::RParen);
+            }
+            ExprKind::Member {
+                object,
+                property,
+                optional,
+            } => {
+                self.lower_postfix_object(object)?;
+                if *optional {
+                    self.push(RssToken::Question);
+                }
+                self.push(RssToken::Dot);
+                self.push_ident(property);
+            }
+            ExprKind::Index {
+                object,
+                index,
+                optional,
+            } => {
+                self.lower_postfix_object(object)?;
+                if *optional {
+                    self.push(RssToken::Question);
+                    self.push(RssToken::Dot);
                 }
+                self.push(RssToken::LBracket);
+                self.lower_expr(index)?;
+                self.push(RssToken::RBracket);
+            }
+            ExprKind::Slice { object, start, end } => {
+                self.lower_slice(object, start.as_deref(), end.as_deref())?
+            }
+            ExprKind::Call { callee, args } => self.lower_call(expr, callee, args)?,
+            ExprKind::Unary { op, operand } => match op {
+                UnaryOp::Plus => self.lower_expr(operand)?,
+                UnaryOp::TypeOf => {
+                    self.push_ident("type");
+                    self.lower_paren_expr(operand)?;
+                }
+                UnaryOp::Not | UnaryOp::Neg => {
+                    self.push(RssToken::LParen);
+                    self.push(if *op == UnaryOp::Not {
+                        RssToken::Bang
+                    } else {
+                        RssToken::Minus
+                    });
+                    self.lower_expr(operand)?;
+                    self.push(RssToken::RParen);
+                }
+            },
+            ExprKind::Binary { op, lhs, rhs } => {
+                // `a <= b` is `!(a > b)` and `a >= b` is `!(a < b)`.
+                let negated = matches!(op, BinaryOp::LtEq | BinaryOp::GtEq);
+                self.push(RssToken::LParen);
+                if negated {
+                    self.push(RssToken::Bang);
+                    self.push(RssToken::LParen);
+                }
+                self.lower_expr(lhs)?;
+                self.at(expr.line, expr.span);
+                self.push(binary_token(*op));
+                self.lower_expr(rhs)?;
+                if negated {
+                    self.push(RssToken::RParen);
+                }
+                self.push(RssToken::RParen);
+            }
+            ExprKind::Conditional {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                self.push(RssToken::LParen);
+                self.push(RssToken::If);
+                self.lower_paren_expr(condition)?;
+                self.push(RssToken::FatArrow);
+                self.push(RssToken::LBrace);
+                self.lower_expr(then_expr)?;
+                self.push(RssToken::RBrace);
+                self.push(RssToken::Else);
+                self.push(RssToken::FatArrow);
+                self.push(RssToken::LBrace);
+                self.lower_expr(else_expr)?;
+                self.push(RssToken::RBrace);
+                self.push(RssToken::RParen);
+            }
+            ExprKind::Function(function) => self.lower_closure(function, false)?,
+            ExprKind::Assign { .. } | ExprKind::Update { .. } | ExprKind::Destructure { .. } => {
+                return Err(lower_error(
+                    expr.line,
+                    expr.span,
+                    "assignments and '++'/'--' are only supported as statements",
+                ));
             }
-            out.push_str(ident);
-            continue;
         }
-
-        out.push(b as char);
-        i += 1;
+        Ok(())
     }
 
-    out
-}
+    /// Lowers the receiver of a member, index, or slice access, wrapping
+    /// anything that is not already a postfix chain in parentheses.
+    fn lower_postfix_object(&mut self, object: &Expr) -> Result<(), ParseError> {
+        match object.kind {
+            ExprKind::Ident(_)
+            | ExprKind::This
+            | ExprKind::Member { .. }
+            | ExprKind::Index { .. }
+            | ExprKind::Call { .. } => self.lower_expr(object),
+            _ => self.lower_paren_expr(object),
+        }
+    }
 
-fn is_valid_ident(input: &str) -> bool {
-    let mut chars = input.chars();
-    let Some(first) = chars.next() else {
-        return false;
-    };
-    is_ident_start(first) && chars.all(is_ident_continue)
-}
+    fn lower_slice(
+        &mut self,
+        object: &Expr,
+        start: Option<&Expr>,
+        end: Option<&Expr>,
+    ) -> Result<(), ParseError> {
+        self.lower_paren_expr(object)?;
+        self.push(RssToken::LBracket);
+        if let Some(start) = start {
+            self.lower_expr(start)?;
+        }
+        self.push(RssToken::Colon);
+        if let Some(end) = end {
+            self.lower_expr(end)?;
+        }
+        self.push(RssToken::RBracket);
+        Ok(())
+    }
 
-fn extract_quoted_literal(input: &str) -> Option<(&str, &str)> {
-    let bytes = input.as_bytes();
-    let mut start_idx = None;
-    let mut quote = b'"';
-    for (idx, byte) in bytes.iter().enumerate() {
-        if *byte == b'"' || *byte == b'\'' {
-            start_idx = Some(idx);
-            quote = *byte;
-            break;
+    /// Lowers an object literal. Spread members merge through the object
+    /// assign helper, so `{ ...a, b: 1 }` becomes
+    /// `__js_object_assign(__js_object_assign({}, a), { "b": 1 })`.
+    fn lower_object(&mut self, members: &[ObjectMember]) -> Result<(), ParseError> {
+        let mut groups: Vec<&[ObjectMember]> = Vec::new();
+        let mut rest = members;
+        while let Some(spread) = rest
+            .iter()
+            .position(|member| matches!(member, ObjectMember::Spread(_)))
+        {
+            groups.push(&rest[..spread]);
+            groups.push(&rest[spread..=spread]);
+            rest = &rest[spread + 1..];
+        }
+        groups.push(rest);
+        // The first literal group seeds the result; empty later groups add
+        // nothing.
+        let seed = groups[0];
+        let merges = groups[1..]
+            .iter()
+            .filter(|group| !group.is_empty())
+            .collect::<Vec<_>>();
+        if !merges.is_empty() {
+            self.uses_object_assign_helper = true;
+        }
+        for _ in &merges {
+            self.push_ident(OBJECT_ASSIGN_HELPER_NAME);
+            self.push(RssToken::LParen);
         }
+        self.lower_object_literal(seed)?;
+        for group in merges {
+            self.push(RssToken::Comma);
+            match group {
+                [ObjectMember::Spread(source)] => self.lower_expr(source)?,
+                _ => self.lower_object_literal(group)?,
+            }
+            self.push(RssToken::RParen);
+        }
+        Ok(())
     }
-    let start = start_idx?;
-    let mut i = start + 1;
-    while i < bytes.len() {
-        if bytes[i] == quote {
-            return Some((&input[start + 1..i], &input[i + 1..]));
+
+    fn lower_object_literal(&mut self, members: &[ObjectMember]) -> Result<(), ParseError> {
+        self.push(RssToken::LBrace);
+        for (index, member) in members.iter().enumerate() {
+            if index > 0 {
+                self.push(RssToken::Comma);
+            }
+            match member {
+                ObjectMember::Property { key, value } => {
+                    match key {
+                        PropertyKey::Computed(key) => {
+                            self.push(RssToken::LBracket);
+                            self.lower_expr(key)?;
+                            self.push(RssToken::RBracket);
+                        }
+                        _ => self.lower_property_key(key)?,
+                    }
+                    self.push(RssToken::Colon);
+                    self.lower_expr(value)?;
+                }
+                ObjectMember::ShorthandDefault { name, .. } => {
+                    return Err(lower_error(
+                        name.line,
+                        name.span,
+                        "'{ name = value }' is only valid in a destructuring pattern",
+                    ));
+                }
+                ObjectMember::Spread(_) => unreachable!("spreads are merged by lower_object"),
+            }
         }
-        i += 1;
+        self.push(RssToken::RBrace);
+        Ok(())
     }
-    None
-}
 
-fn rewrite_keywords<F>(source: &str, mut rewrite: F) -> String
-where
-    F: FnMut(&str) -> Option<&'static str>,
-{
-    let mut out = String::with_capacity(source.len());
-    let mut chars = source.chars().peekable();
-    let mut in_string = false;
-    let mut escaped = false;
-    let mut in_line_comment = false;
-    let mut in_block_comment = false;
-    while let Some(ch) = chars.next() {
-        if in_line_comment {
-            out.push(ch);
-            if ch == '\n' {
-                in_line_comment = false;
-            }
-            continue;
-        }
-
-        if in_block_comment {
-            out.push(ch);
-            if ch == '*' && chars.peek().copied() == Some('/') {
-                out.push('/');
-                let _ = chars.next();
-                in_block_comment = false;
-            }
-            continue;
-        }
-
-        if in_string {
-            out.push(ch);
-            if escaped {
-                escaped = false;
-            } else if ch == '\\' {
-                escaped = true;
-            } else if ch == '"' {
-                in_string = false;
-            }
-            continue;
-        }
-
-        if ch == '/' {
-            if chars.peek().copied() == Some('/') {
-                out.push('/');
-                out.push('/');
-                let _ = chars.next();
-                in_line_comment = true;
-                continue;
-            }
-            if chars.peek().copied() == Some('*') {
-                out.push('/');
-                out.push('*');
-                let _ = chars.next();
-                in_block_comment = true;
-                continue;
-            }
-        }
-
-        if ch == '"' {
-            in_string = true;
-            out.push(ch);
-            continue;
-        }
-
-        if is_ident_start(ch) {
-            let mut ident = String::new();
-            ident.push(ch);
-            while let Some(next) = chars.peek().copied() {
-                if is_ident_continue(next) {
-                    ident.push(next);
-                    let _ = chars.next();
-                } else {
-                    break;
+    fn lower_call(
+        &mut self,
+        call: &Expr,
+        callee: &Expr,
+        args: &[Element],
+    ) -> Result<(), ParseError> {
+        match &callee.kind {
+            ExprKind::Ident(name) => {
+                if is_forbidden_js_builtin_name(name) {
+                    let hint = js_builtin_syntax_hint(name);
+                    return Err(lower_error(
+                        callee.line,
+                        callee.span,
+                        &format!(
+                            "direct builtin call '{name}(...)' is not exposed in JavaScript frontend; {hint}"
+                        ),
+                    ));
+                }
+                self.push_ident(name);
+                if let Some(signature) = self.signatures.get(name).copied() {
+                    return self.lower_known_args(signature, args, false);
                 }
             }
-            if let Some(value) = rewrite(&ident) {
-                out.push_str(value);
-            } else {
-                out.push_str(&ident);
+            ExprKind::Path(segments) => {
+                for (index, segment) in segments.iter().enumerate() {
+                    if index > 0 {
+                        self.push_path_separator();
+                    }
+                    self.push_ident(segment);
+                }
+            }
+            ExprKind::Member {
+                object,
+                property,
+                optional: false,
+            } => {
+                if let Some(class_call) = self.class_call(call) {
+                    self.lower_class_call_pair(&class_call)?;
+                    self.push(RssToken::LBracket);
+                    self.push(RssToken::Int(0));
+                    self.push(RssToken::RBracket);
+                    return Ok(());
+                }
+                return self.lower_method_call(call, object, property, args);
+            }
+            ExprKind::Super => {
+                return Err(lower_error(
+                    callee.line,
+                    callee.span,
+                    "'super(...)' must be a statement of its own",
+                ));
+            }
+            _ => {
+                return Err(lower_error(
+                    callee.line,
+                    callee.span,
+                    "only named functions, builtin namespaces, and vm host functions can be called",
+                ));
             }
-        } else {
-            out.push(ch);
         }
+        self.lower_args(args)
     }
-    out
-}
-
-fn rewrite_console_log_calls(source: &str) -> String {
-    let bytes = source.as_bytes();
-    let mut out = String::with_capacity(source.len());
-    let mut i = 0usize;
-    let mut in_string = false;
-    let mut escaped = false;
-    let mut in_line_comment = false;
-    let mut in_block_comment = false;
-    const CONSOLE_DOT_LOG: &[u8] = b"console.log";
-
-    while i < bytes.len() {
-        let b = bytes[i];
 
-        if in_block_comment {
-            out.push(b as char);
-            if b == b'*' && i + 1 < bytes.len() && bytes[i + 1] == b'/' {
-                out.push('/');
-                i += 2;
-                in_block_comment = false;
-                continue;
+    fn lower_method_call(
+        &mut self,
+        call: &Expr,
+        object: &Expr,
+        property: &str,
+        args: &[Element],
+    ) -> Result<(), ParseError> {
+        if let Some(path) = self.vm_member_path(object, property) {
+            self.push_ident(VM_MODULE_SPEC);
+            for segment in path {
+                self.push_path_separator();
+                self.push_ident(&segment);
             }
-            i += 1;
-            continue;
+            return self.lower_args(args);
+        }
+        if let ExprKind::Ident(class) = &object.kind
+            && self.classes.is_class(class)
+        {
+            let Some((function, signature)) =
+                self.classes
+                    .resolve_member(class, property, MethodKind::Static)
+            else {
+                return Err(lower_error(
+                    call.line,
+                    call.span,
+                    &format!("class '{class}' has no static method '{property}'"),
+                ));
+            };
+            self.push_ident(&function);
+            return self.lower_known_args(signature, args, false);
         }
 
-        if in_line_comment {
-            out.push(b as char);
-            if b == b'\n' {
-                in_line_comment = false;
+        let receiver = match &object.kind {
+            ExprKind::Ident(name) => Some(name.as_str()),
+            _ => None,
+        };
+        match (receiver, property, args) {
+            (Some("console"), "log", _) => {
+                self.push_ident(STDLIB_PRINT_NAME);
+                self.lower_args(args)?;
+            }
+            (Some("Object"), "keys", [Element::Item(object)]) => {
+                self.lower_paren_expr(object)?;
+                self.push(RssToken::Dot);
+                self.push_ident("keys");
+            }
+            (Some("Object"), "values" | "entries", [Element::Item(object)]) => {
+                self.push_namespace_call_start("map", property);
+                self.lower_expr(object)?;
+                self.push(RssToken::RParen);
+            }
+            (_, "slice", []) => self.lower_slice(object, None, None)?,
+            (_, "slice", [Element::Item(start)]) => self.lower_slice(object, Some(start), None)?,
+            (_, "slice", [Element::Item(start), Element::Item(end)]) => {
+                self.lower_slice(object, Some(start), Some(end))?;
+            }
+            // `map.has_key(m, k)` is the plain-JavaScript spelling of the
+            // `map::has_key(m, k)` builtin namespace call.
+            (Some(namespace), _, _) if is_builtin_namespace(namespace) => {
+                self.push_ident(namespace);
+                self.push_path_separator();
+                self.push_ident(property);
+                return self.lower_args(args);
+            }
+            _ => {
+                return Err(lower_error(
+                    call.line,
+                    call.span,
+                    &format!(
+                        "method call '.{property}(...)' is not supported in the JavaScript subset"
+                    ),
+                ));
             }
-            i += 1;
-            continue;
         }
+        Ok(())
+    }
 
-        if in_string {
-            out.push(b as char);
-            if escaped {
-                escaped = false;
-            } else if b == b'\\' {
-                escaped = true;
-            } else if b == b'"' {
-                in_string = false;
+    /// Returns the host function path of `alias.a.b` when `alias` names the
+    /// `vm` module, so `vm.http.get(...)` calls the `http::get` host function.
+    fn vm_member_path(&self, object: &Expr, property: &str) -> Option<Vec<String>> {
+        let mut path = vec![property.to_string()];
+        let mut current = object;
+        loop {
+            match &current.kind {
+                ExprKind::Member {
+                    object,
+                    property,
+                    optional: false,
+                } => {
+                    path.push(property.clone());
+                    current = object;
+                }
+                ExprKind::Ident(name) if self.vm_aliases.contains(name) => {
+                    path.reverse();
+                    return Some(path);
+                }
+                _ => return None,
             }
-            i += 1;
-            continue;
         }
+    }
 
-        if b == b'/' && i + 1 < bytes.len() && bytes[i + 1] == b'/' {
-            out.push('/');
-            out.push('/');
-            i += 2;
-            in_line_comment = true;
-            continue;
-        }
+    fn is_vm_alias(&self, expr: &Expr) -> bool {
+        matches!(&expr.kind, ExprKind::Ident(name) if self.vm_aliases.contains(name))
+    }
 
-        if b == b'/' && i + 1 < bytes.len() && bytes[i + 1] == b'*' {
-            out.push('/');
-            out.push('*');
-            i += 2;
-            in_block_comment = true;
-            continue;
-        }
+    fn push_namespace_call_start(&mut self, namespace: &str, member: &str) {
+        self.push_ident(namespace);
+        self.push_path_separator();
+        self.push_ident(member);
+        self.push(RssToken::LParen);
+    }
+}
 
-        if b == b'"' {
-            out.push('"');
-            i += 1;
-            in_string = true;
-            continue;
+fn binary_token(op: BinaryOp) -> RssToken {
+    match op {
+        BinaryOp::Add => RssToken::Plus,
+        BinaryOp::Sub => RssToken::Minus,
+        BinaryOp::Mul => RssToken::Star,
+        BinaryOp::Div => RssToken::Slash,
+        BinaryOp::Mod => RssToken::Percent,
+        BinaryOp::Eq => RssToken::EqualEqual,
+        BinaryOp::NotEq => RssToken::BangEqual,
+        BinaryOp::Lt | BinaryOp::GtEq => RssToken::Less,
+        BinaryOp::Gt | BinaryOp::LtEq => RssToken::Greater,
+        BinaryOp::And => RssToken::AmpersandAmpersand,
+        BinaryOp::Or => RssToken::PipePipe,
+    }
+}
+
+/// Returns `x` for an `Object.<helper>(x)` call.
+fn object_helper_arg<'a>(expr: &'a Expr, helper: &str) -> Option<&'a Expr> {
+    let ExprKind::Call { callee, args } = &expr.kind else {
+        return None;
+    };
+    let ExprKind::Member {
+        object, property, ..
+    } = &callee.kind
+    else {
+        return None;
+    };
+    match (&object.kind, args.as_slice()) {
+        (ExprKind::Ident(name), [Element::Item(arg)]) if name == "Object" && property == helper => {
+            Some(arg)
         }
+        _ => None,
+    }
+}
 
-        let is_ident_boundary = i == 0 || !is_ident_continue(bytes[i - 1] as char);
-        if is_ident_boundary
-            && i + CONSOLE_DOT_LOG.len() <= bytes.len()
-            && &bytes[i..i + CONSOLE_DOT_LOG.len()] == CONSOLE_DOT_LOG
-        {
-            let mut j = i + CONSOLE_DOT_LOG.len();
-            while j < bytes.len()
-                && bytes[j].is_ascii_whitespace()
-                && bytes[j] != b'\n'
-                && bytes[j] != b'\r'
-            {
-                j += 1;
+/// Records the parameter shape of every named function and every
+/// `let name = function/arrow` binding, at any nesting depth.
+fn collect_signatures(stmts: &[Stmt], signatures: &mut HashMap<String, Signature>) {
+    for stmt in stmts {
+        match &stmt.kind {
+            StmtKind::Function(function) => {
+                if let Some(name) = &function.name {
+                    signatures.insert(name.clone(), Signature::of(function));
+                }
+                if let FunctionBody::Block(body, ..) = &function.body {
+                    collect_signatures(body, signatures);
+                }
             }
-            if j < bytes.len() && bytes[j] == b'(' {
-                out.push_str(STDLIB_PRINT_NAME);
-                i += CONSOLE_DOT_LOG.len();
-                continue;
+            StmtKind::Let(declarators) => {
+                for declarator in declarators {
+                    if let (
+                        Pattern::Target(Expr {
+                            kind: ExprKind::Ident(name),
+                            ..
+                        }),
+                        Some(Expr {
+                            kind: ExprKind::Function(function),
+                            ..
+                        }),
+                    ) = (&declarator.target, &declarator.init)
+                    {
+                        signatures.insert(name.clone(), Signature::of(function));
+                        if let FunctionBody::Block(body, ..) = &function.body {
+                            collect_signatures(body, signatures);
+                        }
+                    }
+                }
             }
+            StmtKind::Class(class) => {
+                let members = class
+                    .constructor
+                    .iter()
+                    .chain(class.methods.iter().map(|method| &method.function));
+                for function in members {
+                    if let FunctionBody::Block(body, ..) = &function.body {
+                        collect_signatures(body, signatures);
+                    }
+                }
+            }
+            StmtKind::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                collect_signatures(then_branch, signatures);
+                if let Some(else_branch) = else_branch {
+                    collect_signatures(else_branch, signatures);
+                }
+            }
+            StmtKind::While { body, .. }
+            | StmtKind::For { body, .. }
+            | StmtKind::ForOf { body, .. }
+            | StmtKind::ForIn { body, .. }
+            | StmtKind::Block(body) => collect_signatures(body, signatures),
+            _ => {}
         }
+    }
+}
 
-        out.push(b as char);
-        i += 1;
+/// Returns the lowered parameter names of a function, rest parameter last.
+pub(super) fn param_names(function: &Function) -> Vec<String> {
+    let mut names = function
+        .params
+        .iter()
+        .enumerate()
+        .map(|(index, param)| match &param.pattern {
+            Pattern::Target(Expr {
+                kind: ExprKind::Ident(name),
+                ..
+            }) => name.clone(),
+            _ => format!("__js_param_{index}"),
+        })
+        .collect::<Vec<_>>();
+    match &function.rest {
+        Some(Pattern::Target(Expr {
+            kind: ExprKind::Ident(name),
+            ..
+        })) => names.push(name.clone()),
+        Some(_) => names.push(REST_PARAM_NAME.to_string()),
+        None => {}
     }
+    names
+}
 
-    out
+/// `this` or a plain variable, the receivers an assignment can write through.
+fn is_plain_receiver(expr: &Expr) -> bool {
+    matches!(expr.kind, ExprKind::Ident(_) | ExprKind::This)
 }
 
-fn rewrite_js_arrow_line(line: &str, line_no: usize) -> Result<String, ParseError> {
-    let Some(arrow_index) = line.find("=>") else {
-        return Ok(line.to_string());
+fn has_param_prologue(function: &Function) -> bool {
+    function
+        .params
+        .iter()
+        .any(|param| param.default.is_some() || !matches!(param.pattern, Pattern::Target(_)))
+        || function
+            .rest
+            .as_ref()
+            .is_some_and(|rest| !matches!(rest, Pattern::Target(_)))
+}
+
+/// Returns `(key, value)` for a `[key, value]` binding over `Object.entries`.
+fn entry_names(pattern: &Pattern) -> Option<(&str, &str)> {
+    let Pattern::Array {
+        elements,
+        rest: None,
+    } = pattern
+    else {
+        return None;
     };
+    fn name(element: &Option<PatternElement>) -> Option<&str> {
+        match element {
+            Some(PatternElement {
+                pattern:
+                    Pattern::Target(Expr {
+                        kind: ExprKind::Ident(name),
+                        ..
+                    }),
+                default: None,
+            }) => Some(name.as_str()),
+            _ => None,
+        }
+    }
+    match elements.as_slice() {
+        [key, value] => Some((name(key)?, name(value)?)),
+        _ => None,
+    }
+}
 
-    let left = &line[..arrow_index];
-    let right = line[arrow_index + 2..].trim_start();
-    if right.starts_with('{') {
-        return Err(ParseError {
-            span: None,
-            code: None,
-            line: line_no,
-            message: "arrow closures with block bodies are not supported in this subset"
-                .to_string(),
-        });
+/// Whether evaluating `expr` more than once is unobservable, which lets a
+/// spread call re-read its argument array per parameter.
+fn is_repeatable(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Null
+        | ExprKind::Bool(_)
+        | ExprKind::Int(_)
+        | ExprKind::Float(_)
+        | ExprKind::String(_)
+        | ExprKind::Ident(_) => true,
+        ExprKind::Member { object, .. } => is_repeatable(object),
+        ExprKind::Index { object, index, .. } => is_repeatable(object) && is_repeatable(index),
+        ExprKind::Array(items) => items.iter().all(|item| match item {
+            Element::Item(item) | Element::Spread(item) => is_repeatable(item),
+            Element::Hole => true,
+        }),
+        _ => false,
     }
+}
 
-    let left_trimmed = left.trim_end();
-    let (prefix, params_text) = if left_trimmed.ends_with(')') {
-        let mut depth = 0usize;
-        let mut open_index = None;
-        for (idx, ch) in left_trimmed.char_indices().rev() {
-            match ch {
-                ')' => depth += 1,
-                '(' => {
-                    if depth == 0 {
-                        return Err(ParseError {
-                            span: None,
-                            code: None,
-                            line: line_no,
-                            message: "malformed arrow closure parameters".to_string(),
-                        });
-                    }
-                    depth -= 1;
-                    if depth == 0 {
-                        open_index = Some(idx);
-                        break;
-                    }
-                }
-                _ => {}
-            }
-        }
-        let open = open_index.ok_or(ParseError {
-            span: None,
-            code: None,
-            line: line_no,
-            message: "could not find '(' for arrow closure parameters".to_string(),
-        })?;
-        (
-            &left_trimmed[..open],
-            &left_trimmed[open + 1..left_trimmed.len() - 1],
-        )
-    } else {
-        let mut split_index = 0usize;
-        for (idx, ch) in left_trimmed.char_indices().rev() {
-            if !(ch.is_ascii_alphanumeric() || ch == '_') {
-                split_index = idx + ch.len_utf8();
-                break;
-            }
-        }
-        (&left_trimmed[..split_index], &left_trimmed[split_index..])
-    };
+fn lower_error(line: usize, span: Span, message: &str) -> ParseError {
+    ParseError {
+        span: Some(span),
+        code: None,
+        line,
+        message: message.to_string(),
+    }
+}
 
-    let params = params_text.trim();
-    if params.is_empty() {
-        Ok(format!("{prefix}| | {right}"))
-    } else {
-        Ok(format!("{prefix}|{params}| {right}"))
+fn is_forbidden_js_builtin_name(name: &str) -> bool {
+    matches!(
+        name,
+        "len"
+            | "slice"
+            | "concat"
+            | "array_new"
+            | "array_push"
+            | "map_new"
+            | "get"
+            | "set"
+            | "count"
+            | "__to_string"
+            | "type_of"
+            | "io_open"
+            | "io_popen"
+            | "io_read_all"
+            | "io_read_line"
+            | "io_write"
+            | "io_flush"
+            | "io_close"
+            | "io_exists"
+            | "re_is_match"
+            | "re_find"
+            | "re_replace"
+            | "re_split"
+            | "re_captures"
+            | "rand_int"
+            | "rand_float"
+            | "rand_choice"
+            | "rand_shuffle"
+            | "uuid_v4"
+            | "array_sort"
+            | "array_sort_by_keys"
+            | "array_binary_search"
+            | "array_index_of"
+            | "array_contains"
+            | "array_dedup"
+            | "array_remove"
+            | "array_insert"
+            | "array_pop"
+            | "map_remove"
+            | "map_has_key"
+            | "map_values"
+            | "map_entries"
+    )
+}
+
+fn js_builtin_syntax_hint(name: &str) -> &'static str {
+    match name {
+        "len" | "count" => "use '.length' syntax",
+        "type_of" => "use 'typeof value'",
+        "get" => "use index/member access syntax ('obj[key]' or 'obj.member')",
+        "set" => "use assignment syntax ('obj[key] = value')",
+        "concat" => "use '+' for string/array concatenation",
+        "slice" => "use slice index syntax ('value[start:end]')",
+        "io_open" | "io_popen" | "io_read_all" | "io_read_line" | "io_write" | "io_flush"
+        | "io_close" | "io_exists" => "use io namespace syntax (for example 'io::open(...)')",
+        "re_is_match" | "re_find" | "re_replace" | "re_split" | "re_captures" => {
+            "use re namespace syntax (for example 're::match(pattern, text, \"i\")')"
+        }
+        "rand_int" | "rand_float" | "rand_choice" | "rand_shuffle" | "uuid_v4" => {
+            "use rand/uuid namespace syntax (for example 'rand::int(1, 6)' or 'uuid::v4()')"
+        }
+        "array_sort"
+        | "array_sort_by_keys"
+        | "array_binary_search"
+        | "array_index_of"
+        | "array_contains"
+        | "array_dedup"
+        | "array_remove"
+        | "array_insert"
+        | "array_pop"
+        | "map_remove"
+        | "map_has_key"
+        | "map_values"
+        | "map_entries" => {
+            "use array/map namespace syntax (for example 'array::sort(values)' or 'map::has_key(m, k)')"
+        }
+        _ => "use frontend language syntax instead of VM builtin helpers",
     }
 }
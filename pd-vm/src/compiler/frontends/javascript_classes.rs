@@ -0,0 +1,298 @@
+//! JavaScript classes.
+//!
+//! Instances are maps whose class name rides in the reserved `null`-keyed
+//! slot, which programs declaring a class hide from lengths, key listings,
+//! iteration, and printing (see `table_slot`). Each
+//! constructor, method, getter, and static method lowers to a RustScript
+//! function named after its class, such as `__js_Point_method_norm`.
+//!
+//! Constructors take the instance as a leading parameter and return it.
+//! Methods do the same but return `[result, this]`, and call sites write the
+//! second element back to the receiver, which is how `this` mutations survive
+//! value semantics. Functions are not VM values, so instance method calls and
+//! getter reads go through generated helpers that compare the receiver's class
+//! tag against every class providing that member, inherited ones included.
+
+use std::collections::BTreeSet;
+
+use super::super::ParseError;
+use super::javascript::{Signature, param_names};
+use super::javascript_parser::{Class, Function, MethodKind, Stmt, StmtKind};
+use crate::compiler::source_map::Span;
+
+/// The lowered name of `this` inside constructors, methods, and getters.
+pub(super) const THIS_NAME: &str = "__js_this";
+
+struct ClassInfo {
+    name: String,
+    span: Span,
+    parent: Option<String>,
+    constructor: Option<ClassFunction>,
+    members: Vec<(String, MethodKind, ClassFunction)>,
+}
+
+struct ClassFunction {
+    /// Lowered parameter names, rest parameter last.
+    params: Vec<String>,
+    signature: Signature,
+}
+
+impl ClassFunction {
+    fn new(function: &Function) -> Self {
+        Self {
+            params: param_names(function),
+            signature: Signature::of(function),
+        }
+    }
+}
+
+#[derive(Default)]
+pub(super) struct ClassRegistry {
+    classes: Vec<ClassInfo>,
+    sends: BTreeSet<String>,
+    gets: BTreeSet<String>,
+}
+
+impl ClassRegistry {
+    /// Records every top-level class, so members resolve regardless of
+    /// declaration order.
+    pub(super) fn scan(program: &[Stmt]) -> Result<Self, ParseError> {
+        let mut registry = Self::default();
+        for stmt in program {
+            let StmtKind::Class(class) = &stmt.kind else {
+                continue;
+            };
+            if registry.find(&class.name).is_some() {
+                return Err(class_error(
+                    class,
+                    &format!("class '{}' is already declared", class.name),
+                ));
+            }
+            registry.classes.push(ClassInfo {
+                name: class.name.clone(),
+                span: class.span,
+                parent: class.parent.clone(),
+                constructor: class.constructor.as_ref().map(ClassFunction::new),
+                members: class
+                    .methods
+                    .iter()
+                    .map(|method| {
+                        (
+                            method.name.clone(),
+                            method.kind,
+                            ClassFunction::new(&method.function),
+                        )
+                    })
+                    .collect(),
+            });
+        }
+        for stmt in program {
+            let StmtKind::Class(class) = &stmt.kind else {
+                continue;
+            };
+            let mut ancestor = class.parent.as_deref();
+            let mut depth = 0;
+            while let Some(name) = ancestor {
+                let Some(info) = registry.find(name) else {
+                    return Err(class_error(
+                        class,
+                        &format!("class '{name}' is not declared in this file"),
+                    ));
+                };
+                depth += 1;
+                if depth > registry.classes.len() {
+                    return Err(class_error(
+                        class,
+                        &format!("class '{}' inherits from itself", class.name),
+                    ));
+                }
+                ancestor = info.parent.as_deref();
+            }
+        }
+        Ok(registry)
+    }
+
+    fn find(&self, name: &str) -> Option<&ClassInfo> {
+        self.classes.iter().find(|class| class.name == name)
+    }
+
+    /// Whether `class` is one of the top-level declarations the scan found.
+    pub(super) fn declares(&self, class: &Class) -> bool {
+        self.find(&class.name)
+            .is_some_and(|info| info.span == class.span)
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+
+    pub(super) fn is_class(&self, name: &str) -> bool {
+        self.find(name).is_some()
+    }
+
+    pub(super) fn parent(&self, class: &str) -> Option<&str> {
+        self.find(class)?.parent.as_deref()
+    }
+
+    pub(super) fn has_method(&self, name: &str) -> bool {
+        self.has_member(name, MethodKind::Instance)
+    }
+
+    pub(super) fn has_getter(&self, name: &str) -> bool {
+        self.has_member(name, MethodKind::Getter)
+    }
+
+    fn has_member(&self, name: &str, kind: MethodKind) -> bool {
+        self.classes.iter().any(|class| {
+            class
+                .members
+                .iter()
+                .any(|(member, member_kind, _)| member == name && *member_kind == kind)
+        })
+    }
+
+    /// The constructor `new class(...)` runs: the class's own, or the nearest
+    /// ancestor's. Returns its function name and signature.
+    pub(super) fn resolve_constructor(&self, class: &str) -> Option<(String, Signature)> {
+        let mut current = self.find(class);
+        while let Some(info) = current {
+            if let Some(constructor) = &info.constructor {
+                return Some((constructor_name(&info.name), constructor.signature));
+            }
+            current = info.parent.as_deref().and_then(|parent| self.find(parent));
+        }
+        None
+    }
+
+    /// Looks `name` up along the class chain starting at `class`.
+    pub(super) fn resolve_member(
+        &self,
+        class: &str,
+        name: &str,
+        kind: MethodKind,
+    ) -> Option<(String, Signature)> {
+        let mut current = self.find(class);
+        while let Some(info) = current {
+            if let Some((_, _, function)) = info
+                .members
+                .iter()
+                .find(|(member, member_kind, _)| member == name && *member_kind == kind)
+            {
+                return Some((member_name(&info.name, name, kind), function.signature));
+            }
+            current = info.parent.as_deref().and_then(|parent| self.find(parent));
+        }
+        None
+    }
+
+    /// Records a dynamically dispatched method call and returns the helper
+    /// taking `(receiver, args)` and yielding `[result, receiver]`.
+    pub(super) fn send(&mut self, method: &str) -> String {
+        self.sends.insert(method.to_string());
+        format!("__js_send_{method}")
+    }
+
+    /// Records a dynamically dispatched getter read and returns its helper.
+    pub(super) fn get(&mut self, getter: &str) -> String {
+        self.gets.insert(getter.to_string());
+        format!("__js_get_{getter}")
+    }
+
+    /// Prototypes for every class function followed by the dispatch helpers.
+    pub(super) fn emit_helpers(&self) -> String {
+        let mut out = Vec::new();
+        for class in &self.classes {
+            if let Some(constructor) = &class.constructor {
+                out.push(prototype(
+                    &constructor_name(&class.name),
+                    true,
+                    &constructor.params,
+                ));
+            }
+            for (name, kind, function) in &class.members {
+                out.push(prototype(
+                    &member_name(&class.name, name, *kind),
+                    *kind != MethodKind::Static,
+                    &function.params,
+                ));
+            }
+        }
+        for method in &self.sends {
+            out.push(format!("fn __js_send_{method}(receiver, args) {{"));
+            push_tag_lookup(&mut out);
+            for class in &self.classes {
+                let Some((function, signature)) =
+                    self.resolve_member(&class.name, method, MethodKind::Instance)
+                else {
+                    continue;
+                };
+                let mut args = vec!["receiver".to_string()];
+                args.extend((0..signature.params).map(|index| format!("args?.[{index}]")));
+                if signature.rest {
+                    args.push(format!("args[{}:]", signature.params));
+                }
+                out.push(format!("    if tag == \"{}\" {{", class.name));
+                out.push(format!("        return {function}({});", args.join(", ")));
+                out.push("    }".to_string());
+            }
+            out.push("    [null, receiver];".to_string());
+            out.push("}".to_string());
+        }
+        for getter in &self.gets {
+            out.push(format!("fn __js_get_{getter}(receiver) {{"));
+            push_tag_lookup(&mut out);
+            for class in &self.classes {
+                let Some((function, _)) =
+                    self.resolve_member(&class.name, getter, MethodKind::Getter)
+                else {
+                    continue;
+                };
+                out.push(format!("    if tag == \"{}\" {{", class.name));
+                out.push(format!("        return {function}(receiver);"));
+                out.push("    }".to_string());
+            }
+            // Objects without the getter read the plain field.
+            out.push(format!("    receiver.{getter};"));
+            out.push("}".to_string());
+        }
+        out.join("\n")
+    }
+}
+
+fn constructor_name(class: &str) -> String {
+    format!("__js_{class}_constructor")
+}
+
+fn member_name(class: &str, name: &str, kind: MethodKind) -> String {
+    let kind = match kind {
+        MethodKind::Instance => "method",
+        MethodKind::Static => "static",
+        MethodKind::Getter => "get",
+    };
+    format!("__js_{class}_{kind}_{name}")
+}
+
+fn prototype(name: &str, receives_this: bool, params: &[String]) -> String {
+    let mut all = Vec::new();
+    if receives_this {
+        all.push(THIS_NAME.to_string());
+    }
+    all.extend(params.iter().cloned());
+    format!("fn {name}({});", all.join(", "))
+}
+
+fn push_tag_lookup(out: &mut Vec<String>) {
+    out.push("    let tag = null;".to_string());
+    out.push("    if type(receiver) == \"map\" {".to_string());
+    out.push("        tag = receiver?.[null];".to_string());
+    out.push("    }".to_string());
+}
+
+fn class_error(class: &Class, message: &str) -> ParseError {
+    ParseError {
+        span: Some(class.span),
+        code: None,
+        line: class.line,
+        message: message.to_string(),
+    }
+}
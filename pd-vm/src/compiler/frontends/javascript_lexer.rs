@@ -0,0 +1,411 @@
+use super::super::ParseError;
+use crate::compiler::source_map::{SourceId, Span};
+
+/// JavaScript sources are always the primary source of a compile, so spans
+/// point into source 0 like the other frontends' mapped diagnostics.
+pub(super) const JS_SOURCE_ID: SourceId = 0;
+
+// Longest punctuators first so `===` is not split into `==` and `=`.
+const PUNCTUATORS: &[&str] = &[
+    "===", "!==", "...", "**=", "=>", "==", "!=", "<=", ">=", "&&", "||", "??", "?.", "++", "--",
+    "+=", "-=", "*=", "/=", "%=", "**", "::", "{", "}", "(", ")", "[", "]", ";", ",", "<", ">",
+    "+", "-", "*", "/", "%", "=", "!", "?", ":", ".", "&", "|", "^", "~",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum TokenKind {
+    Ident(String),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Template(Template),
+    Punct(&'static str),
+    Eof,
+}
+
+/// A template literal split into its cooked text pieces and the token streams
+/// of the `${...}` substitutions between them (`quasis.len() == substitutions.len() + 1`).
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct Template {
+    pub(super) quasis: Vec<String>,
+    pub(super) substitutions: Vec<Vec<Token>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct Token {
+    pub(super) kind: TokenKind,
+    pub(super) line: usize,
+    pub(super) span: Span,
+}
+
+pub(super) fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+    let mut lexer = Lexer::new(source);
+    let mut tokens = Vec::new();
+    loop {
+        let token = lexer.next_token()?;
+        let is_eof = matches!(token.kind, TokenKind::Eof);
+        tokens.push(token);
+        if is_eof {
+            break;
+        }
+    }
+    Ok(tokens)
+}
+
+struct Lexer<'a> {
+    source: &'a str,
+    offset: usize,
+    line: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            offset: 0,
+            line: 1,
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Token, ParseError> {
+        self.skip_whitespace_and_comments()?;
+        let line = self.line;
+        let start = self.offset;
+        let Some(ch) = self.current() else {
+            return Ok(Token {
+                kind: TokenKind::Eof,
+                line,
+                span: Span::new(JS_SOURCE_ID, start, start),
+            });
+        };
+
+        let kind = match ch {
+            '"' | '\'' => TokenKind::String(self.consume_string(ch)?),
+            '`' => TokenKind::Template(self.consume_template()?),
+            c if c.is_ascii_digit() => self.consume_number()?,
+            '.' if self.peek(1).is_some_and(|next| next.is_ascii_digit()) => {
+                self.consume_number()?
+            }
+            c if is_js_ident_start(c) => {
+                while self.current().is_some_and(is_js_ident_continue) {
+                    self.advance();
+                }
+                TokenKind::Ident(self.source[start..self.offset].to_string())
+            }
+            _ => {
+                let rest = &self.source[start..];
+                let Some(punct) = PUNCTUATORS
+                    .iter()
+                    .copied()
+                    // `a?.5:b` is a conditional, not optional chaining.
+                    .filter(|punct| {
+                        *punct != "?."
+                            || !rest.get(2..).is_some_and(|tail| {
+                                tail.starts_with(|next: char| next.is_ascii_digit())
+                            })
+                    })
+                    .find(|punct| rest.starts_with(punct))
+                else {
+                    self.advance();
+                    return Err(self.error(start, format!("unexpected character '{ch}'")));
+                };
+                self.offset += punct.len();
+                TokenKind::Punct(punct)
+            }
+        };
+
+        Ok(Token {
+            kind,
+            line,
+            span: Span::new(JS_SOURCE_ID, start, self.offset),
+        })
+    }
+
+    fn current(&self) -> Option<char> {
+        self.source[self.offset..].chars().next()
+    }
+
+    fn peek(&self, ahead: usize) -> Option<char> {
+        self.source[self.offset..].chars().nth(ahead)
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let ch = self.current()?;
+        if ch == '\n' {
+            self.line += 1;
+        }
+        self.offset += ch.len_utf8();
+        Some(ch)
+    }
+
+    fn error(&self, start: usize, message: String) -> ParseError {
+        let line = self.source[..start].matches('\n').count() + 1;
+        ParseError {
+            span: Some(Span::new(
+                JS_SOURCE_ID,
+                start,
+                self.offset.max(start + 1).min(self.source.len()),
+            )),
+            code: None,
+            line,
+            message,
+        }
+    }
+
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), ParseError> {
+        loop {
+            while self.current().is_some_and(char::is_whitespace) {
+                self.advance();
+            }
+            let rest = &self.source[self.offset..];
+            if rest.starts_with("//") || (self.offset == 0 && rest.starts_with("#!")) {
+                while self.current().is_some_and(|ch| ch != '\n') {
+                    self.advance();
+                }
+                continue;
+            }
+            if rest.starts_with("/*") {
+                let start = self.offset;
+                self.offset += 2;
+                loop {
+                    if self.source[self.offset..].starts_with("*/") {
+                        self.offset += 2;
+                        break;
+                    }
+                    if self.advance().is_none() {
+                        return Err(self.error(start, "unterminated block comment".to_string()));
+                    }
+                }
+                continue;
+            }
+            return Ok(());
+        }
+    }
+
+    fn consume_number(&mut self) -> Result<TokenKind, ParseError> {
+        let start = self.offset;
+        let rest = &self.source[start..];
+        let radix = match rest.get(..2) {
+            Some("0x" | "0X") => 16,
+            Some("0o" | "0O") => 8,
+            Some("0b" | "0B") => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            self.offset += 2;
+            let digits_start = self.offset;
+            while self
+                .current()
+                .is_some_and(|ch| ch.is_digit(radix) || ch == '_')
+            {
+                self.advance();
+            }
+            let digits = self.source[digits_start..self.offset].replace('_', "");
+            return i64::from_str_radix(&digits, radix)
+                .map(TokenKind::Int)
+                .map_err(|_| self.error(start, "invalid integer literal".to_string()));
+        }
+
+        let mut is_float = false;
+        self.consume_digits();
+        if self.current() == Some('.') && self.peek(1).is_none_or(|next| next != '.') {
+            is_float = true;
+            self.advance();
+            self.consume_digits();
+        }
+        if matches!(self.current(), Some('e' | 'E')) {
+            let sign = usize::from(matches!(self.peek(1), Some('+' | '-')));
+            if self.peek(1 + sign).is_some_and(|ch| ch.is_ascii_digit()) {
+                is_float = true;
+                for _ in 0..=sign {
+                    self.advance();
+                }
+                self.consume_digits();
+            }
+        }
+        if self.current().is_some_and(is_js_ident_start) {
+            self.advance();
+            return Err(self.error(
+                start,
+                "identifier starts immediately after number".to_string(),
+            ));
+        }
+
+        let text = self.source[start..self.offset].replace('_', "");
+        if is_float {
+            text.parse::<f64>()
+                .map(TokenKind::Float)
+                .map_err(|_| self.error(start, "invalid number literal".to_string()))
+        } else {
+            text.parse::<i64>()
+                .map(TokenKind::Int)
+                .map_err(|_| self.error(start, "integer literal out of range".to_string()))
+        }
+    }
+
+    fn consume_digits(&mut self) {
+        while self
+            .current()
+            .is_some_and(|ch| ch.is_ascii_digit() || ch == '_')
+        {
+            self.advance();
+        }
+    }
+
+    fn consume_string(&mut self, quote: char) -> Result<String, ParseError> {
+        let start = self.offset;
+        self.advance();
+        let mut out = String::new();
+        loop {
+            match self.current() {
+                None | Some('\n') => {
+                    return Err(self.error(start, "unterminated string literal".to_string()));
+                }
+                Some(ch) if ch == quote => {
+                    self.advance();
+                    return Ok(out);
+                }
+                Some('\\') => self.consume_escape(&mut out)?,
+                Some(ch) => {
+                    out.push(ch);
+                    self.advance();
+                }
+            }
+        }
+    }
+
+    fn consume_template(&mut self) -> Result<Template, ParseError> {
+        let start = self.offset;
+        self.advance();
+        let mut quasis = Vec::new();
+        let mut substitutions = Vec::new();
+        let mut text = String::new();
+        loop {
+            match self.current() {
+                None => {
+                    return Err(self.error(start, "unterminated template literal".to_string()));
+                }
+                Some('`') => {
+                    self.advance();
+                    quasis.push(text);
+                    return Ok(Template {
+                        quasis,
+                        substitutions,
+                    });
+                }
+                Some('\\') => self.consume_escape(&mut text)?,
+                Some('$') if self.peek(1) == Some('{') => {
+                    let substitution_start = self.offset;
+                    self.offset += 2;
+                    quasis.push(std::mem::take(&mut text));
+                    substitutions.push(self.consume_substitution(substitution_start)?);
+                }
+                Some(ch) => {
+                    text.push(ch);
+                    self.advance();
+                }
+            }
+        }
+    }
+
+    /// Lexes the tokens of one `${...}` substitution up to its closing brace,
+    /// leaving an `Eof` marker at the brace so it can be parsed on its own.
+    fn consume_substitution(&mut self, start: usize) -> Result<Vec<Token>, ParseError> {
+        let mut tokens = Vec::new();
+        let mut depth = 0usize;
+        loop {
+            let token = self.next_token()?;
+            match token.kind {
+                TokenKind::Eof => {
+                    return Err(
+                        self.error(start, "unterminated '${' in template literal".to_string())
+                    );
+                }
+                TokenKind::Punct("{") => depth += 1,
+                TokenKind::Punct("}") if depth == 0 => {
+                    tokens.push(Token {
+                        kind: TokenKind::Eof,
+                        ..token
+                    });
+                    return Ok(tokens);
+                }
+                TokenKind::Punct("}") => depth -= 1,
+                _ => {}
+            }
+            tokens.push(token);
+        }
+    }
+
+    fn consume_escape(&mut self, out: &mut String) -> Result<(), ParseError> {
+        let start = self.offset;
+        self.advance();
+        let Some(escaped) = self.advance() else {
+            return Err(self.error(start, "unterminated escape sequence".to_string()));
+        };
+        let ch = match escaped {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'v' => '\u{b}',
+            '0' if !self.current().is_some_and(|ch| ch.is_ascii_digit()) => '\0',
+            // A backslash before a line break continues the literal.
+            '\n' => return Ok(()),
+            '\r' => {
+                if self.current() == Some('\n') {
+                    self.advance();
+                }
+                return Ok(());
+            }
+            'x' => self.consume_hex_escape(start, 2)?,
+            'u' if self.current() == Some('{') => {
+                self.advance();
+                let digits_start = self.offset;
+                while self.current().is_some_and(|ch| ch.is_ascii_hexdigit()) {
+                    self.advance();
+                }
+                let digits = &self.source[digits_start..self.offset];
+                if self.advance() != Some('}') {
+                    return Err(self.error(start, "invalid unicode escape".to_string()));
+                }
+                u32::from_str_radix(digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| self.error(start, "invalid unicode escape".to_string()))?
+            }
+            'u' => self.consume_hex_escape(start, 4)?,
+            other if other.is_ascii_digit() => {
+                return Err(self.error(
+                    start,
+                    "octal escape sequences are not supported".to_string(),
+                ));
+            }
+            other => other,
+        };
+        out.push(ch);
+        Ok(())
+    }
+
+    fn consume_hex_escape(&mut self, start: usize, len: usize) -> Result<char, ParseError> {
+        let digits_start = self.offset;
+        for _ in 0..len {
+            if !self.current().is_some_and(|ch| ch.is_ascii_hexdigit()) {
+                return Err(self.error(start, "invalid hexadecimal escape".to_string()));
+            }
+            self.advance();
+        }
+        u32::from_str_radix(&self.source[digits_start..self.offset], 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error(start, "invalid hexadecimal escape".to_string()))
+    }
+}
+
+fn is_js_ident_start(ch: char) -> bool {
+    ch.is_alphabetic() || ch == '_' || ch == '$'
+}
+
+fn is_js_ident_continue(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_' || ch == '$'
+}
@@ -0,0 +1,1815 @@
+use super::super::ParseError;
+use super::javascript_lexer::{JS_SOURCE_ID, Template, Token, TokenKind, tokenize};
+use crate::compiler::source_map::Span;
+
+// Reserved words that can never name a binding; they are still valid member
+// and property names (`obj.typeof`, `{ default: 1 }`).
+const RESERVED_WORDS: &[&str] = &[
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "import",
+    "in",
+    "instanceof",
+    "let",
+    "new",
+    "null",
+    "return",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+#[derive(Clone, Debug)]
+pub(super) struct Stmt {
+    pub(super) kind: StmtKind,
+    pub(super) line: usize,
+    pub(super) span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub(super) enum StmtKind {
+    Empty,
+    Import(Import),
+    /// `declare function name(params);`, the body-less declaration the module
+    /// loader prepends for functions imported from RustScript modules.
+    Declare {
+        name: String,
+        params: Vec<String>,
+    },
+    Let(Vec<Declarator>),
+    Function(Function),
+    Class(Class),
+    Expr(Expr),
+    If {
+        condition: Expr,
+        then_branch: Vec<Stmt>,
+        else_branch: Option<Vec<Stmt>>,
+    },
+    While {
+        condition: Expr,
+        body: Vec<Stmt>,
+    },
+    For {
+        init: Option<Box<Stmt>>,
+        condition: Option<Expr>,
+        update: Option<Expr>,
+        body: Vec<Stmt>,
+    },
+    ForOf {
+        binding: Pattern,
+        iterable: Expr,
+        body: Vec<Stmt>,
+    },
+    ForIn {
+        name: String,
+        object: Expr,
+        body: Vec<Stmt>,
+    },
+    Block(Vec<Stmt>),
+    Break,
+    Continue,
+    Return(Option<Expr>),
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct Import {
+    pub(super) spec: String,
+    pub(super) clause: ImportClause,
+}
+
+#[derive(Clone, Debug)]
+pub(super) enum ImportClause {
+    /// `import "spec"` or a bare `require("spec")`.
+    Bare,
+    /// `import * as name from "spec"`, `import name from "spec"`, or
+    /// `const name = require("spec")`.
+    Namespace(String),
+    /// `import { a, b as c } from "spec"` or `const { a, b: c } = require("spec")`,
+    /// as `(imported, local)` pairs.
+    Named(Vec<(String, String)>),
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct Declarator {
+    pub(super) target: Pattern,
+    pub(super) init: Option<Expr>,
+    pub(super) line: usize,
+    pub(super) span: Span,
+}
+
+/// A binding or assignment target. Declarations and parameters only produce
+/// identifier targets; assignment patterns may also target members and indexes.
+#[derive(Clone, Debug)]
+pub(super) enum Pattern {
+    Target(Expr),
+    Object {
+        properties: Vec<PatternProperty>,
+        rest: Option<Box<Expr>>,
+    },
+    Array {
+        elements: Vec<Option<PatternElement>>,
+        rest: Option<Box<Pattern>>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct PatternProperty {
+    pub(super) key: PropertyKey,
+    pub(super) element: PatternElement,
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct PatternElement {
+    pub(super) pattern: Pattern,
+    pub(super) default: Option<Expr>,
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct Function {
+    pub(super) name: Option<String>,
+    pub(super) params: Vec<PatternElement>,
+    /// The `...rest` parameter collecting trailing arguments into an array.
+    pub(super) rest: Option<Pattern>,
+    pub(super) body: FunctionBody,
+    pub(super) exported: bool,
+    /// Arrow functions keep the `this` of their enclosing method.
+    pub(super) arrow: bool,
+    pub(super) line: usize,
+    pub(super) span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct Class {
+    pub(super) name: String,
+    pub(super) parent: Option<String>,
+    pub(super) constructor: Option<Function>,
+    pub(super) methods: Vec<Method>,
+    pub(super) line: usize,
+    pub(super) span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct Method {
+    pub(super) name: String,
+    pub(super) kind: MethodKind,
+    pub(super) function: Function,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum MethodKind {
+    Instance,
+    Static,
+    Getter,
+}
+
+#[derive(Clone, Debug)]
+pub(super) enum FunctionBody {
+    Expr(Box<Expr>),
+    /// Statements plus the line and span of the closing brace, where a body
+    /// that falls off its end yields `undefined`.
+    Block(Vec<Stmt>, usize, Span),
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct Expr {
+    pub(super) kind: ExprKind,
+    pub(super) line: usize,
+    pub(super) span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub(super) enum ExprKind {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Template {
+        quasis: Vec<String>,
+        substitutions: Vec<Expr>,
+    },
+    Ident(String),
+    This,
+    /// `super`, which is only valid as `super(...)` or `super.method(...)`.
+    Super,
+    /// `new Class(...)`.
+    New {
+        class: String,
+        args: Vec<Element>,
+    },
+    /// `ns::member` paths reaching builtin namespaces such as `io::open`.
+    Path(Vec<String>),
+    Array(Vec<Element>),
+    Object(Vec<ObjectMember>),
+    Member {
+        object: Box<Expr>,
+        property: String,
+        optional: bool,
+    },
+    Index {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        optional: bool,
+    },
+    Slice {
+        object: Box<Expr>,
+        start: Option<Box<Expr>>,
+        end: Option<Box<Expr>>,
+    },
+    Call {
+        callee: Box<Expr>,
+        args: Vec<Element>,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Conditional {
+        condition: Box<Expr>,
+        then_expr: Box<Expr>,
+        else_expr: Box<Expr>,
+    },
+    Function(Box<Function>),
+    Assign {
+        target: Box<Expr>,
+        op: Option<BinaryOp>,
+        value: Box<Expr>,
+    },
+    /// `[a, b] = value` or `({ a, b } = value)`; only valid as a statement.
+    Destructure {
+        pattern: Box<Pattern>,
+        value: Box<Expr>,
+    },
+    /// `x++`, `++x`, `x--`, and `--x`; only valid as statements.
+    Update {
+        target: Box<Expr>,
+        increment: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum UnaryOp {
+    Not,
+    Neg,
+    Plus,
+    TypeOf,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    NotEq,
+    Lt,
+    Gt,
+    LtEq,
+    GtEq,
+    And,
+    Or,
+}
+
+/// An array literal element or call argument.
+#[derive(Clone, Debug)]
+pub(super) enum Element {
+    Item(Expr),
+    Spread(Expr),
+    /// An elided array element (`[a, , b]`).
+    Hole,
+}
+
+#[derive(Clone, Debug)]
+pub(super) enum ObjectMember {
+    Property {
+        key: PropertyKey,
+        value: Expr,
+    },
+    /// `{ name = default }`, which is only valid once the literal is read
+    /// back as an assignment pattern.
+    ShorthandDefault {
+        name: Expr,
+        default: Expr,
+    },
+    Spread(Expr),
+}
+
+#[derive(Clone, Debug)]
+pub(super) enum PropertyKey {
+    Name(String),
+    String(String),
+    Int(i64),
+    Computed(Expr),
+}
+
+impl Expr {
+    pub(super) fn new(kind: ExprKind, line: usize, span: Span) -> Self {
+        Self { kind, line, span }
+    }
+}
+
+pub(super) fn parse_program(source: &str) -> Result<Vec<Stmt>, ParseError> {
+    let mut parser = JsParser::new(tokenize(source)?);
+    let mut stmts = Vec::new();
+    while !parser.at_eof() {
+        stmts.push(parser.parse_stmt()?);
+    }
+    Ok(stmts)
+}
+
+struct JsParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl JsParser {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self { tokens, pos: 0 }
+    }
+
+    fn parse_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.current().clone();
+        let kind = match &start.kind {
+            TokenKind::Punct(";") => {
+                self.pos += 1;
+                StmtKind::Empty
+            }
+            TokenKind::Punct("{") => StmtKind::Block(self.parse_block()?),
+            TokenKind::Ident(word) => match word.as_str() {
+                "import" if !self.peek_is_punct(1, "(") => {
+                    self.pos += 1;
+                    StmtKind::Import(self.parse_import()?)
+                }
+                "export" => {
+                    self.pos += 1;
+                    if self.check_ident("function") {
+                        let mut function = self.parse_function_decl()?;
+                        function.exported = true;
+                        StmtKind::Function(function)
+                    } else if self.check_ident("let")
+                        || self.check_ident("const")
+                        || self.check_ident("var")
+                    {
+                        return self.parse_stmt();
+                    } else {
+                        return Err(self.error_here(
+                            "only 'export function' and exported declarations are supported",
+                        ));
+                    }
+                }
+                "declare" if self.peek_is_ident(1, "function") => {
+                    self.pos += 2;
+                    let name = self.expect_binding_name("expected function name")?;
+                    let params_start = self.current().clone();
+                    let (params, rest) = self.parse_params()?;
+                    let names = params
+                        .iter()
+                        .map(|param| match param {
+                            PatternElement {
+                                pattern: Pattern::Target(target),
+                                default: None,
+                            } => match &target.kind {
+                                ExprKind::Ident(name) => Some(name.clone()),
+                                _ => None,
+                            },
+                            _ => None,
+                        })
+                        .collect::<Option<Vec<_>>>();
+                    let Some(params) = names.filter(|_| rest.is_none()) else {
+                        return Err(self.error_at(
+                            &params_start,
+                            "declared functions only take plain parameter names",
+                        ));
+                    };
+                    self.consume_terminator()?;
+                    StmtKind::Declare { name, params }
+                }
+                "function" => StmtKind::Function(self.parse_function_decl()?),
+                "let" | "const" | "var" => {
+                    self.pos += 1;
+                    let kind = self.parse_declaration()?;
+                    self.consume_terminator()?;
+                    kind
+                }
+                "if" => self.parse_if()?,
+                "while" => {
+                    self.pos += 1;
+                    let condition = self.parse_paren_condition("while")?;
+                    let body = self.parse_body()?;
+                    StmtKind::While { condition, body }
+                }
+                "for" => self.parse_for()?,
+                "break" | "continue" => {
+                    self.pos += 1;
+                    if matches!(self.current().kind, TokenKind::Ident(_))
+                        && self.current().line == start.line
+                    {
+                        return Err(self.error_here("labeled break/continue is not supported"));
+                    }
+                    self.consume_terminator()?;
+                    if word == "break" {
+                        StmtKind::Break
+                    } else {
+                        StmtKind::Continue
+                    }
+                }
+                "return" => {
+                    self.pos += 1;
+                    let value = if self.check_punct(";")
+                        || self.check_punct("}")
+                        || self.at_eof()
+                        || self.current().line > start.line
+                    {
+                        None
+                    } else {
+                        Some(self.parse_expr()?)
+                    };
+                    self.consume_terminator()?;
+                    StmtKind::Return(value)
+                }
+                "class" => StmtKind::Class(self.parse_class()?),
+                "do" | "switch" | "try" | "throw" | "with" | "debugger" => {
+                    return Err(self.error_here(&format!(
+                        "'{word}' statements are not supported in the JavaScript subset"
+                    )));
+                }
+                _ => self.parse_expr_stmt()?,
+            },
+            _ => self.parse_expr_stmt()?,
+        };
+        Ok(Stmt {
+            kind,
+            line: start.line,
+            span: self.span_from(start.span),
+        })
+    }
+
+    fn parse_expr_stmt(&mut self) -> Result<StmtKind, ParseError> {
+        let expr = self.parse_expr()?;
+        self.consume_terminator()?;
+        if let Some(spec) = require_spec(&expr) {
+            return Ok(StmtKind::Import(Import {
+                spec,
+                clause: ImportClause::Bare,
+            }));
+        }
+        Ok(StmtKind::Expr(expr))
+    }
+
+    fn parse_block(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        self.expect_punct("{", "expected '{'")?;
+        let mut stmts = Vec::new();
+        while !self.check_punct("}") {
+            if self.at_eof() {
+                return Err(self.error_here("unexpected end of input in block"));
+            }
+            stmts.push(self.parse_stmt()?);
+        }
+        self.pos += 1;
+        Ok(stmts)
+    }
+
+    /// Parses a loop or branch body, which is either a block or one statement.
+    fn parse_body(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        if self.check_punct("{") {
+            return self.parse_block();
+        }
+        Ok(vec![self.parse_stmt()?])
+    }
+
+    fn parse_paren_condition(&mut self, keyword: &str) -> Result<Expr, ParseError> {
+        self.expect_punct("(", &format!("expected '(' after '{keyword}'"))?;
+        let condition = self.parse_expr()?;
+        self.expect_punct(")", &format!("expected ')' after {keyword} condition"))?;
+        Ok(condition)
+    }
+
+    fn parse_if(&mut self) -> Result<StmtKind, ParseError> {
+        self.pos += 1;
+        let condition = self.parse_paren_condition("if")?;
+        let then_branch = self.parse_body()?;
+        let else_branch = if self.check_ident("else") {
+            self.pos += 1;
+            Some(self.parse_body()?)
+        } else {
+            None
+        };
+        Ok(StmtKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    fn parse_for(&mut self) -> Result<StmtKind, ParseError> {
+        self.pos += 1;
+        self.expect_punct("(", "expected '(' after 'for'")?;
+        let declares =
+            self.check_ident("let") || self.check_ident("const") || self.check_ident("var");
+        if declares {
+            self.pos += 1;
+        }
+
+        if declares && (self.check_punct("[") || self.check_punct("{")) {
+            let pattern_start = self.pos;
+            let binding = self.parse_binding_pattern()?;
+            if self.match_ident("of") {
+                let iterable = self.parse_expr()?;
+                self.expect_punct(")", "expected ')' after for-of iterable")?;
+                let body = self.parse_body()?;
+                return Ok(StmtKind::ForOf {
+                    binding,
+                    iterable,
+                    body,
+                });
+            }
+            if self.check_ident("in") {
+                return Err(self.error_here("'for...in' binds a single key name"));
+            }
+            // A destructuring initializer of a C-style loop; reparse it below.
+            self.pos = pattern_start;
+        }
+
+        if let TokenKind::Ident(name) = &self.current().kind
+            && (self.peek_is_ident(1, "of") || self.peek_is_ident(1, "in"))
+        {
+            let name = name.clone();
+            self.ensure_binding_name(&name)?;
+            let target = self.current().clone();
+            let is_of = self.peek_is_ident(1, "of");
+            self.pos += 2;
+            let iterable = self.parse_expr()?;
+            self.expect_punct(")", "expected ')' after for-of/for-in iterable")?;
+            let body = self.parse_body()?;
+            return Ok(if is_of {
+                StmtKind::ForOf {
+                    binding: Pattern::Target(Expr::new(
+                        ExprKind::Ident(name),
+                        target.line,
+                        target.span,
+                    )),
+                    iterable,
+                    body,
+                }
+            } else {
+                StmtKind::ForIn {
+                    name,
+                    object: iterable,
+                    body,
+                }
+            });
+        }
+
+        let init = if self.check_punct(";") {
+            None
+        } else {
+            let start = self.current().clone();
+            let kind = if declares {
+                self.parse_declaration()?
+            } else {
+                StmtKind::Expr(self.parse_expr()?)
+            };
+            Some(Box::new(Stmt {
+                kind,
+                line: start.line,
+                span: self.span_from(start.span),
+            }))
+        };
+        self.expect_punct(";", "expected ';' after for initializer")?;
+        let condition = if self.check_punct(";") {
+            None
+        } else {
+            Some(self.parse_expr()?)
+        };
+        self.expect_punct(";", "expected ';' after for condition")?;
+        let update = if self.check_punct(")") {
+            None
+        } else {
+            Some(self.parse_expr()?)
+        };
+        self.expect_punct(")", "expected ')' after for clauses")?;
+        let body = self.parse_body()?;
+        Ok(StmtKind::For {
+            init,
+            condition,
+            update,
+            body,
+        })
+    }
+
+    /// Parses the declarators after `let`/`const`/`var`. A `require(...)`
+    /// initializer turns the declaration into an import.
+    fn parse_declaration(&mut self) -> Result<StmtKind, ParseError> {
+        let mut declarators = Vec::new();
+        loop {
+            let start = self.current().clone();
+            let target = self.parse_binding_pattern()?;
+            let init = if self.match_punct("=") {
+                Some(self.parse_assignment()?)
+            } else {
+                None
+            };
+            if let Some(spec) = init.as_ref().and_then(require_spec) {
+                if !declarators.is_empty() || self.check_punct(",") {
+                    return Err(self.error_at(&start, "require(...) must be declared on its own"));
+                }
+                let Some(clause) = require_binding_clause(&target) else {
+                    return Err(self.error_at(
+                        &start,
+                        "require(...) can only be bound to a name or to '{ name, name: alias }'",
+                    ));
+                };
+                return Ok(StmtKind::Import(Import { spec, clause }));
+            }
+            if init.is_none() && !matches!(target, Pattern::Target(_)) {
+                return Err(self.error_here("destructuring declarations need an initializer"));
+            }
+            declarators.push(Declarator {
+                target,
+                init,
+                line: start.line,
+                span: self.span_from(start.span),
+            });
+            if !self.match_punct(",") {
+                break;
+            }
+        }
+        Ok(StmtKind::Let(declarators))
+    }
+
+    /// Parses a binding name or an object/array destructuring pattern.
+    fn parse_binding_pattern(&mut self) -> Result<Pattern, ParseError> {
+        if self.match_punct("[") {
+            let mut elements = Vec::new();
+            let mut rest = None;
+            while !self.check_punct("]") {
+                if self.match_punct(",") {
+                    elements.push(None);
+                    continue;
+                }
+                if self.match_punct("...") {
+                    rest = Some(Box::new(self.parse_binding_pattern()?));
+                    break;
+                }
+                elements.push(Some(self.parse_binding_element()?));
+                if !self.match_punct(",") {
+                    break;
+                }
+            }
+            self.expect_punct("]", "expected ']' after array pattern")?;
+            return Ok(Pattern::Array { elements, rest });
+        }
+        if self.match_punct("{") {
+            let mut properties = Vec::new();
+            let mut rest = None;
+            while !self.check_punct("}") {
+                if self.match_punct("...") {
+                    rest = Some(Box::new(self.parse_binding_name()?));
+                    break;
+                }
+                let key_token = self.current().clone();
+                let key = self.parse_property_key()?;
+                let element = if self.match_punct(":") {
+                    self.parse_binding_element()?
+                } else {
+                    let PropertyKey::Name(name) = &key else {
+                        return Err(self.error_here("expected ':' after pattern key"));
+                    };
+                    if RESERVED_WORDS.contains(&name.as_str()) {
+                        return Err(
+                            self.error_at(&key_token, &format!("unexpected keyword '{name}'"))
+                        );
+                    }
+                    let target = Expr::new(
+                        ExprKind::Ident(name.clone()),
+                        key_token.line,
+                        key_token.span,
+                    );
+                    PatternElement {
+                        pattern: Pattern::Target(target),
+                        default: self.parse_default_value()?,
+                    }
+                };
+                properties.push(PatternProperty { key, element });
+                if !self.match_punct(",") {
+                    break;
+                }
+            }
+            self.expect_punct("}", "expected '}' after object pattern")?;
+            return Ok(Pattern::Object { properties, rest });
+        }
+        Ok(Pattern::Target(self.parse_binding_name()?))
+    }
+
+    fn parse_binding_element(&mut self) -> Result<PatternElement, ParseError> {
+        Ok(PatternElement {
+            pattern: self.parse_binding_pattern()?,
+            default: self.parse_default_value()?,
+        })
+    }
+
+    fn parse_default_value(&mut self) -> Result<Option<Expr>, ParseError> {
+        if self.match_punct("=") {
+            return Ok(Some(self.parse_assignment()?));
+        }
+        Ok(None)
+    }
+
+    fn parse_binding_name(&mut self) -> Result<Expr, ParseError> {
+        let start = self.current().clone();
+        let name = self.expect_binding_name("expected binding name")?;
+        Ok(Expr::new(ExprKind::Ident(name), start.line, start.span))
+    }
+
+    fn parse_object_binding_names(&mut self) -> Result<Vec<(String, String)>, ParseError> {
+        self.expect_punct("{", "expected '{'")?;
+        let mut names = Vec::new();
+        while !self.check_punct("}") {
+            let imported = self.expect_property_name()?;
+            let local = if self.match_punct(":") || self.match_ident("as") {
+                self.expect_binding_name("expected binding name")?
+            } else {
+                imported.clone()
+            };
+            names.push((imported, local));
+            if !self.match_punct(",") {
+                break;
+            }
+        }
+        self.expect_punct("}", "expected '}' after binding names")?;
+        Ok(names)
+    }
+
+    fn parse_import(&mut self) -> Result<Import, ParseError> {
+        if let TokenKind::String(spec) = &self.current().kind {
+            let spec = spec.clone();
+            self.pos += 1;
+            self.consume_terminator()?;
+            return Ok(Import {
+                spec,
+                clause: ImportClause::Bare,
+            });
+        }
+        let clause = if self.match_punct("*") {
+            if !self.match_ident("as") {
+                return Err(self.error_here("expected 'as' after 'import *'"));
+            }
+            ImportClause::Namespace(self.expect_binding_name("expected namespace name")?)
+        } else if self.check_punct("{") {
+            ImportClause::Named(self.parse_object_binding_names()?)
+        } else {
+            ImportClause::Namespace(self.expect_binding_name("expected import binding")?)
+        };
+        if !self.match_ident("from") {
+            return Err(self.error_here("expected 'from' after import clause"));
+        }
+        let TokenKind::String(spec) = &self.current().kind else {
+            return Err(self.error_here("expected module specifier string after 'from'"));
+        };
+        let spec = spec.clone();
+        self.pos += 1;
+        self.consume_terminator()?;
+        Ok(Import { spec, clause })
+    }
+
+    fn parse_function_decl(&mut self) -> Result<Function, ParseError> {
+        let start = self.current().clone();
+        self.pos += 1;
+        let name = self.expect_binding_name("expected function name after 'function'")?;
+        let (params, rest) = self.parse_params()?;
+        let body = self.parse_function_block()?;
+        Ok(Function {
+            name: Some(name),
+            params,
+            rest,
+            body,
+            exported: false,
+            arrow: false,
+            line: start.line,
+            span: self.span_from(start.span),
+        })
+    }
+
+    /// Parses `class Name [extends Parent] { ... }` with a constructor,
+    /// instance and static methods, and getters.
+    fn parse_class(&mut self) -> Result<Class, ParseError> {
+        let start = self.current().clone();
+        self.pos += 1;
+        let name = self.expect_binding_name("expected class name after 'class'")?;
+        let parent = if self.match_ident("extends") {
+            Some(self.expect_binding_name("expected parent class name after 'extends'")?)
+        } else {
+            None
+        };
+        self.expect_punct("{", "expected '{' before class body")?;
+        let mut constructor = None;
+        let mut methods: Vec<Method> = Vec::new();
+        while !self.check_punct("}") {
+            if self.match_punct(";") {
+                continue;
+            }
+            let member_start = self.current().clone();
+            let is_static = self.check_ident("static") && !self.peek_is_punct(1, "(");
+            if is_static {
+                self.pos += 1;
+            }
+            let getter = self.check_ident("get") && !self.peek_is_punct(1, "(");
+            if getter {
+                self.pos += 1;
+            } else if (self.check_ident("set") || self.check_ident("async"))
+                && !self.peek_is_punct(1, "(")
+            {
+                let TokenKind::Ident(word) = &self.current().kind else {
+                    unreachable!("checked identifier");
+                };
+                return Err(self.error_here(&format!(
+                    "'{word}' class members are not supported in the JavaScript subset"
+                )));
+            } else if self.check_punct("*") || self.check_punct("#") {
+                return Err(
+                    self.error_here("generator and private class members are not supported")
+                );
+            }
+            if is_static && getter {
+                return Err(self.error_at(&member_start, "static getters are not supported"));
+            }
+            let name_token = self.current().clone();
+            let member_name = self.expect_property_name()?;
+            if !self.check_punct("(") {
+                return Err(self
+                    .error_here("class fields are not supported; assign them in the constructor"));
+            }
+            let (params, rest) = self.parse_params()?;
+            let body = self.parse_function_block()?;
+            let function = Function {
+                name: Some(member_name.clone()),
+                params,
+                rest,
+                body,
+                exported: false,
+                arrow: false,
+                line: member_start.line,
+                span: self.span_from(member_start.span),
+            };
+            if member_name == "constructor" && !is_static {
+                if getter {
+                    return Err(self.error_at(&name_token, "the constructor cannot be a getter"));
+                }
+                if constructor.is_some() {
+                    return Err(self.error_at(&name_token, "a class has only one constructor"));
+                }
+                constructor = Some(function);
+                continue;
+            }
+            let kind = if is_static {
+                MethodKind::Static
+            } else if getter {
+                MethodKind::Getter
+            } else {
+                MethodKind::Instance
+            };
+            if kind == MethodKind::Getter
+                && (!function.params.is_empty() || function.rest.is_some())
+            {
+                return Err(self.error_at(&name_token, "getters take no parameters"));
+            }
+            if methods.iter().any(|method| {
+                method.name == member_name && (method.kind == MethodKind::Static) == is_static
+            }) {
+                return Err(self.error_at(
+                    &name_token,
+                    &format!("duplicate class member '{member_name}'"),
+                ));
+            }
+            methods.push(Method {
+                name: member_name,
+                kind,
+                function,
+            });
+        }
+        self.expect_punct("}", "expected '}' after class body")?;
+        Ok(Class {
+            name,
+            parent,
+            constructor,
+            methods,
+            line: start.line,
+            span: self.span_from(start.span),
+        })
+    }
+
+    /// Parses `(a, { b } = {}, ...rest)` into the positional parameters and
+    /// the optional rest parameter.
+    fn parse_params(&mut self) -> Result<(Vec<PatternElement>, Option<Pattern>), ParseError> {
+        self.expect_punct("(", "expected '(' before parameters")?;
+        let mut params = Vec::new();
+        let mut rest = None;
+        while !self.check_punct(")") {
+            if self.match_punct("...") {
+                rest = Some(self.parse_binding_pattern()?);
+                break;
+            }
+            params.push(self.parse_binding_element()?);
+            if !self.match_punct(",") {
+                break;
+            }
+        }
+        self.expect_punct(")", "expected ')' after parameters")?;
+        Ok((params, rest))
+    }
+
+    fn parse_function_block(&mut self) -> Result<FunctionBody, ParseError> {
+        let stmts = self.parse_block()?;
+        let close = &self.tokens[self.pos - 1];
+        Ok(FunctionBody::Block(stmts, close.line, close.span))
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.parse_assignment()?;
+        if self.check_punct(",") {
+            return Err(self.error_here("the comma operator is not supported"));
+        }
+        Ok(expr)
+    }
+
+    fn parse_assignment(&mut self) -> Result<Expr, ParseError> {
+        if let Some(arrow) = self.try_parse_arrow()? {
+            return Ok(arrow);
+        }
+        let start = self.current().clone();
+        let target = self.parse_conditional()?;
+        let op = match &self.current().kind {
+            TokenKind::Punct("=") => None,
+            TokenKind::Punct("+=") => Some(BinaryOp::Add),
+            TokenKind::Punct("-=") => Some(BinaryOp::Sub),
+            TokenKind::Punct("*=") => Some(BinaryOp::Mul),
+            TokenKind::Punct("/=") => Some(BinaryOp::Div),
+            TokenKind::Punct("%=") => Some(BinaryOp::Mod),
+            TokenKind::Punct("**=") => {
+                return Err(self.error_here("the '**' operator is not supported"));
+            }
+            _ => return Ok(target),
+        };
+        if op.is_none() && matches!(target.kind, ExprKind::Array(_) | ExprKind::Object(_)) {
+            let pattern = expr_to_pattern(target)?;
+            self.pos += 1;
+            let value = self.parse_assignment()?;
+            return Ok(Expr::new(
+                ExprKind::Destructure {
+                    pattern: Box::new(pattern),
+                    value: Box::new(value),
+                },
+                start.line,
+                self.span_from(start.span),
+            ));
+        }
+        if !is_assignable(&target) {
+            return Err(self.error_at(&start, "invalid assignment target"));
+        }
+        self.pos += 1;
+        let value = self.parse_assignment()?;
+        Ok(Expr::new(
+            ExprKind::Assign {
+                target: Box::new(target),
+                op,
+                value: Box::new(value),
+            },
+            start.line,
+            self.span_from(start.span),
+        ))
+    }
+
+    fn try_parse_arrow(&mut self) -> Result<Option<Expr>, ParseError> {
+        let start = self.current().clone();
+        let (params, rest) = match &start.kind {
+            TokenKind::Ident(name) if self.peek_is_punct(1, "=>") => {
+                let name = name.clone();
+                self.ensure_binding_name(&name)?;
+                self.pos += 1;
+                let target = Expr::new(ExprKind::Ident(name), start.line, start.span);
+                let param = PatternElement {
+                    pattern: Pattern::Target(target),
+                    default: None,
+                };
+                (vec![param], None)
+            }
+            TokenKind::Punct("(") if self.arrow_follows_parens() => self.parse_params()?,
+            TokenKind::Ident(word) if word == "async" => {
+                return Err(self.error_here("async functions are not supported"));
+            }
+            _ => return Ok(None),
+        };
+        if self.current().line > self.tokens[self.pos - 1].line {
+            return Err(self.error_here("line break is not allowed before '=>'"));
+        }
+        self.expect_punct("=>", "expected '=>' after arrow parameters")?;
+        let body = if self.check_punct("{") {
+            self.parse_function_block()?
+        } else {
+            FunctionBody::Expr(Box::new(self.parse_assignment()?))
+        };
+        let span = self.span_from(start.span);
+        Ok(Some(Expr::new(
+            ExprKind::Function(Box::new(Function {
+                name: None,
+                params,
+                rest,
+                body,
+                exported: false,
+                arrow: true,
+                line: start.line,
+                span,
+            })),
+            start.line,
+            span,
+        )))
+    }
+
+    fn arrow_follows_parens(&self) -> bool {
+        let mut depth = 0usize;
+        for (offset, token) in self.tokens[self.pos..].iter().enumerate() {
+            match token.kind {
+                TokenKind::Punct("(" | "[" | "{") => depth += 1,
+                TokenKind::Punct(")" | "]" | "}") => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        return self.peek_is_punct(offset + 1, "=>");
+                    }
+                }
+                TokenKind::Eof => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    fn parse_conditional(&mut self) -> Result<Expr, ParseError> {
+        let start = self.current().clone();
+        let condition = self.parse_binary(0)?;
+        if !self.match_punct("?") {
+            return Ok(condition);
+        }
+        let then_expr = self.parse_assignment()?;
+        self.expect_punct(":", "expected ':' in conditional expression")?;
+        let else_expr = self.parse_assignment()?;
+        Ok(Expr::new(
+            ExprKind::Conditional {
+                condition: Box::new(condition),
+                then_expr: Box::new(then_expr),
+                else_expr: Box::new(else_expr),
+            },
+            start.line,
+            self.span_from(start.span),
+        ))
+    }
+
+    /// Precedence climbing over the binary operators, loosest level first.
+    fn parse_binary(&mut self, level: usize) -> Result<Expr, ParseError> {
+        const LEVELS: &[&[(&str, BinaryOp)]] = &[
+            &[("||", BinaryOp::Or)],
+            &[("&&", BinaryOp::And)],
+            &[
+                ("===", BinaryOp::Eq),
+                ("!==", BinaryOp::NotEq),
+                ("==", BinaryOp::Eq),
+                ("!=", BinaryOp::NotEq),
+            ],
+            &[
+                ("<=", BinaryOp::LtEq),
+                (">=", BinaryOp::GtEq),
+                ("<", BinaryOp::Lt),
+                (">", BinaryOp::Gt),
+            ],
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            &[
+                ("*", BinaryOp::Mul),
+                ("/", BinaryOp::Div),
+                ("%", BinaryOp::Mod),
+            ],
+        ];
+        let Some(operators) = LEVELS.get(level) else {
+            return self.parse_unary();
+        };
+        let start = self.current().clone();
+        let mut lhs = self.parse_binary(level + 1)?;
+        loop {
+            let op = match &self.current().kind {
+                TokenKind::Punct(punct) => operators
+                    .iter()
+                    .find(|(text, _)| text == punct)
+                    .map(|(_, op)| *op),
+                _ => None,
+            };
+            let Some(op) = op else {
+                if let Some(message) = self.unsupported_operator() {
+                    return Err(self.error_here(&message));
+                }
+                return Ok(lhs);
+            };
+            self.pos += 1;
+            let rhs = self.parse_binary(level + 1)?;
+            lhs = Expr::new(
+                ExprKind::Binary {
+                    op,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                },
+                start.line,
+                self.span_from(start.span),
+            );
+        }
+    }
+
+    fn unsupported_operator(&self) -> Option<String> {
+        match &self.current().kind {
+            TokenKind::Punct(op @ ("??" | "**" | "&" | "|" | "^")) => Some(format!(
+                "the '{op}' operator is not supported in the JavaScript subset"
+            )),
+            TokenKind::Ident(word) if word == "in" || word == "instanceof" => Some(format!(
+                "the '{word}' operator is not supported in the JavaScript subset"
+            )),
+            _ => None,
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+        let start = self.current().clone();
+        let op = match &start.kind {
+            TokenKind::Punct("!") => UnaryOp::Not,
+            TokenKind::Punct("-") => UnaryOp::Neg,
+            TokenKind::Punct("+") => UnaryOp::Plus,
+            TokenKind::Ident(word) if word == "typeof" => UnaryOp::TypeOf,
+            TokenKind::Punct(punct @ ("++" | "--")) => {
+                let increment = *punct == "++";
+                self.pos += 1;
+                let target = self.parse_unary()?;
+                if !is_assignable(&target) {
+                    return Err(self.error_at(&start, "invalid update target"));
+                }
+                return Ok(Expr::new(
+                    ExprKind::Update {
+                        target: Box::new(target),
+                        increment,
+                    },
+                    start.line,
+                    self.span_from(start.span),
+                ));
+            }
+            TokenKind::Punct("~") => {
+                return Err(self.error_here("the '~' operator is not supported"));
+            }
+            TokenKind::Ident(word) if matches!(word.as_str(), "void" | "delete" | "await") => {
+                return Err(self.error_here(&format!("'{word}' is not supported")));
+            }
+            _ => return self.parse_postfix(),
+        };
+        self.pos += 1;
+        let operand = self.parse_unary()?;
+        Ok(Expr::new(
+            ExprKind::Unary {
+                op,
+                operand: Box::new(operand),
+            },
+            start.line,
+            self.span_from(start.span),
+        ))
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr, ParseError> {
+        let start = self.current().clone();
+        let expr = self.parse_call_member()?;
+        let TokenKind::Punct(punct @ ("++" | "--")) = self.current().kind else {
+            return Ok(expr);
+        };
+        // A line break before `++` ends the statement instead.
+        if self.current().line > self.tokens[self.pos - 1].line {
+            return Ok(expr);
+        }
+        if !is_assignable(&expr) {
+            return Err(self.error_at(&start, "invalid update target"));
+        }
+        self.pos += 1;
+        Ok(Expr::new(
+            ExprKind::Update {
+                target: Box::new(expr),
+                increment: punct == "++",
+            },
+            start.line,
+            self.span_from(start.span),
+        ))
+    }
+
+    fn parse_call_member(&mut self) -> Result<Expr, ParseError> {
+        let start = self.current().clone();
+        let mut expr = self.parse_primary()?;
+        loop {
+            let kind = if self.match_punct(".") {
+                ExprKind::Member {
+                    object: Box::new(expr),
+                    property: self.expect_property_name()?,
+                    optional: false,
+                }
+            } else if self.match_punct("?.") {
+                if self.match_punct("[") {
+                    let index = self.parse_expr()?;
+                    self.expect_punct("]", "expected ']' after optional index")?;
+                    ExprKind::Index {
+                        object: Box::new(expr),
+                        index: Box::new(index),
+                        optional: true,
+                    }
+                } else if self.check_punct("(") {
+                    return Err(self.error_here("optional calls '?.()' are not supported"));
+                } else {
+                    ExprKind::Member {
+                        object: Box::new(expr),
+                        property: self.expect_property_name()?,
+                        optional: true,
+                    }
+                }
+            } else if self.match_punct("[") {
+                self.parse_index_or_slice(expr)?
+            } else if self.check_punct("(") {
+                ExprKind::Call {
+                    callee: Box::new(expr),
+                    args: self.parse_args()?,
+                }
+            } else if matches!(self.current().kind, TokenKind::Template(_)) {
+                return Err(self.error_here("tagged templates are not supported"));
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::new(kind, start.line, self.span_from(start.span));
+        }
+    }
+
+    /// Parses `[index]` or the RustScript-style slice forms `[a:b]`, `[:b]`,
+    /// and `[a:]` after the opening bracket.
+    fn parse_index_or_slice(&mut self, object: Expr) -> Result<ExprKind, ParseError> {
+        let start = if self.check_punct(":") {
+            None
+        } else {
+            Some(Box::new(self.parse_expr()?))
+        };
+        if self.match_punct(":") {
+            let end = if self.check_punct("]") {
+                None
+            } else {
+                Some(Box::new(self.parse_expr()?))
+            };
+            self.expect_punct("]", "expected ']' after slice")?;
+            return Ok(ExprKind::Slice {
+                object: Box::new(object),
+                start,
+                end,
+            });
+        }
+        self.expect_punct("]", "expected ']' after index")?;
+        let Some(index) = start else {
+            return Err(self.error_here("expected index expression"));
+        };
+        Ok(ExprKind::Index {
+            object: Box::new(object),
+            index,
+            optional: false,
+        })
+    }
+
+    fn parse_args(&mut self) -> Result<Vec<Element>, ParseError> {
+        self.expect_punct("(", "expected '('")?;
+        let mut args = Vec::new();
+        while !self.check_punct(")") {
+            if self.match_punct("...") {
+                args.push(Element::Spread(self.parse_assignment()?));
+            } else {
+                args.push(Element::Item(self.parse_assignment()?));
+            }
+            if !self.match_punct(",") {
+                break;
+            }
+        }
+        self.expect_punct(")", "expected ')' after arguments")?;
+        Ok(args)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, ParseError> {
+        let token = self.current().clone();
+        let kind = match &token.kind {
+            TokenKind::Int(value) => ExprKind::Int(*value),
+            TokenKind::Float(value) => ExprKind::Float(*value),
+            TokenKind::String(value) => ExprKind::String(value.clone()),
+            TokenKind::Template(template) => {
+                self.pos += 1;
+                return self.lower_template(template, &token);
+            }
+            TokenKind::Punct("(") => {
+                self.pos += 1;
+                let inner = self.parse_expr()?;
+                self.expect_punct(")", "expected ')' after expression")?;
+                return Ok(inner);
+            }
+            TokenKind::Punct("[") => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                while !self.check_punct("]") {
+                    if self.match_punct(",") {
+                        items.push(Element::Hole);
+                        continue;
+                    }
+                    if self.match_punct("...") {
+                        items.push(Element::Spread(self.parse_assignment()?));
+                    } else {
+                        items.push(Element::Item(self.parse_assignment()?));
+                    }
+                    if !self.match_punct(",") {
+                        break;
+                    }
+                }
+                self.expect_punct("]", "expected ']' after array literal")?;
+                return Ok(Expr::new(
+                    ExprKind::Array(items),
+                    token.line,
+                    self.span_from(token.span),
+                ));
+            }
+            TokenKind::Punct("{") => {
+                self.pos += 1;
+                let properties = self.parse_object_properties()?;
+                return Ok(Expr::new(
+                    ExprKind::Object(properties),
+                    token.line,
+                    self.span_from(token.span),
+                ));
+            }
+            TokenKind::Ident(word) => match word.as_str() {
+                "true" => ExprKind::Bool(true),
+                "false" => ExprKind::Bool(false),
+                "null" | "undefined" => ExprKind::Null,
+                "function" => {
+                    self.pos += 1;
+                    if matches!(self.current().kind, TokenKind::Ident(_)) {
+                        return Err(self.error_here("named function expressions are not supported"));
+                    }
+                    let (params, rest) = self.parse_params()?;
+                    let body = self.parse_function_block()?;
+                    let span = self.span_from(token.span);
+                    return Ok(Expr::new(
+                        ExprKind::Function(Box::new(Function {
+                            name: None,
+                            params,
+                            rest,
+                            body,
+                            exported: false,
+                            arrow: false,
+                            line: token.line,
+                            span,
+                        })),
+                        token.line,
+                        span,
+                    ));
+                }
+                _ if self.peek_is_punct(1, "::") => {
+                    let mut segments = vec![word.clone()];
+                    self.pos += 1;
+                    while self.match_punct("::") {
+                        segments.push(self.expect_property_name()?);
+                    }
+                    return Ok(Expr::new(
+                        ExprKind::Path(segments),
+                        token.line,
+                        self.span_from(token.span),
+                    ));
+                }
+                "this" => ExprKind::This,
+                "super" if self.peek_is_punct(1, "(") || self.peek_is_punct(1, ".") => {
+                    ExprKind::Super
+                }
+                "new" => {
+                    self.pos += 1;
+                    if self.check_punct(".") {
+                        return Err(self.error_here("'new.target' is not supported"));
+                    }
+                    let class = self.expect_binding_name("expected class name after 'new'")?;
+                    if self.check_punct(".") {
+                        return Err(self.error_here("only 'new ClassName(...)' is supported"));
+                    }
+                    let args = if self.check_punct("(") {
+                        self.parse_args()?
+                    } else {
+                        Vec::new()
+                    };
+                    return Ok(Expr::new(
+                        ExprKind::New { class, args },
+                        token.line,
+                        self.span_from(token.span),
+                    ));
+                }
+                "class" | "super" => {
+                    return Err(self.error_here(&format!(
+                        "'{word}' is not supported in the JavaScript subset"
+                    )));
+                }
+                _ => {
+                    self.ensure_binding_name(word)?;
+                    ExprKind::Ident(word.clone())
+                }
+            },
+            TokenKind::Eof => return Err(self.error_here("unexpected end of input")),
+            TokenKind::Punct(_) => return Err(self.error_here("expected expression")),
+        };
+        self.pos += 1;
+        Ok(Expr::new(kind, token.line, token.span))
+    }
+
+    fn parse_object_properties(&mut self) -> Result<Vec<ObjectMember>, ParseError> {
+        let mut members = Vec::new();
+        while !self.check_punct("}") {
+            if self.match_punct("...") {
+                members.push(ObjectMember::Spread(self.parse_assignment()?));
+                if !self.match_punct(",") {
+                    break;
+                }
+                continue;
+            }
+            let key_token = self.current().clone();
+            let key = self.parse_property_key()?;
+            if self.match_punct(":") {
+                let value = self.parse_assignment()?;
+                members.push(ObjectMember::Property { key, value });
+            } else if self.check_punct("(") {
+                return Err(self.error_here("object methods are not supported"));
+            } else {
+                let PropertyKey::Name(name) = &key else {
+                    return Err(self.error_here("expected ':' after property name"));
+                };
+                self.ensure_binding_name(name)?;
+                let name = Expr::new(
+                    ExprKind::Ident(name.clone()),
+                    key_token.line,
+                    key_token.span,
+                );
+                if self.match_punct("=") {
+                    let default = self.parse_assignment()?;
+                    members.push(ObjectMember::ShorthandDefault { name, default });
+                } else {
+                    members.push(ObjectMember::Property { key, value: name });
+                }
+            }
+            if !self.match_punct(",") {
+                break;
+            }
+        }
+        self.expect_punct("}", "expected '}' after object literal")?;
+        Ok(members)
+    }
+
+    /// Parses an object literal or pattern key: a name, string, integer, or
+    /// `[computed]` expression.
+    fn parse_property_key(&mut self) -> Result<PropertyKey, ParseError> {
+        let key = match &self.current().kind {
+            TokenKind::Ident(name) => PropertyKey::Name(name.clone()),
+            TokenKind::String(value) => PropertyKey::String(value.clone()),
+            TokenKind::Int(value) => PropertyKey::Int(*value),
+            TokenKind::Punct("[") => {
+                self.pos += 1;
+                let key = self.parse_assignment()?;
+                self.expect_punct("]", "expected ']' after computed key")?;
+                return Ok(PropertyKey::Computed(key));
+            }
+            _ => return Err(self.error_here("expected property name")),
+        };
+        self.pos += 1;
+        Ok(key)
+    }
+
+    /// Parses each `${...}` substitution of a template on its own token stream.
+    fn lower_template(&mut self, template: &Template, token: &Token) -> Result<Expr, ParseError> {
+        let mut substitutions = Vec::new();
+        for tokens in &template.substitutions {
+            let mut parser = JsParser::new(tokens.clone());
+            if parser.at_eof() {
+                return Err(parser.error_here("empty '${}' in template literal"));
+            }
+            let expr = parser.parse_expr()?;
+            if !parser.at_eof() {
+                return Err(parser.error_here("expected '}' after template substitution"));
+            }
+            substitutions.push(expr);
+        }
+        Ok(Expr::new(
+            ExprKind::Template {
+                quasis: template.quasis.clone(),
+                substitutions,
+            },
+            token.line,
+            token.span,
+        ))
+    }
+
+    fn consume_terminator(&mut self) -> Result<(), ParseError> {
+        if self.match_punct(";") || self.check_punct("}") || self.at_eof() {
+            return Ok(());
+        }
+        // Automatic semicolon insertion: a line break ends the statement when
+        // the next token could not continue it.
+        if self.pos > 0 && self.current().line > self.tokens[self.pos - 1].line {
+            return Ok(());
+        }
+        Err(self.error_here("expected ';' after statement"))
+    }
+
+    fn expect_binding_name(&mut self, message: &str) -> Result<String, ParseError> {
+        let TokenKind::Ident(name) = &self.current().kind else {
+            return Err(self.error_here(message));
+        };
+        let name = name.clone();
+        self.ensure_binding_name(&name)?;
+        self.pos += 1;
+        Ok(name)
+    }
+
+    fn ensure_binding_name(&self, name: &str) -> Result<(), ParseError> {
+        if RESERVED_WORDS.contains(&name) {
+            return Err(self.error_here(&format!("unexpected keyword '{name}'")));
+        }
+        Ok(())
+    }
+
+    fn expect_property_name(&mut self) -> Result<String, ParseError> {
+        let TokenKind::Ident(name) = &self.current().kind else {
+            return Err(self.error_here("expected property name"));
+        };
+        let name = name.clone();
+        self.pos += 1;
+        Ok(name)
+    }
+
+    fn current(&self) -> &Token {
+        &self.tokens[self.pos.min(self.tokens.len() - 1)]
+    }
+
+    fn at_eof(&self) -> bool {
+        matches!(self.current().kind, TokenKind::Eof)
+    }
+
+    fn peek_is_punct(&self, offset: usize, punct: &str) -> bool {
+        matches!(
+            self.tokens.get(self.pos + offset).map(|token| &token.kind),
+            Some(TokenKind::Punct(found)) if *found == punct
+        )
+    }
+
+    fn peek_is_ident(&self, offset: usize, word: &str) -> bool {
+        matches!(
+            self.tokens.get(self.pos + offset).map(|token| &token.kind),
+            Some(TokenKind::Ident(found)) if found == word
+        )
+    }
+
+    fn check_punct(&self, punct: &str) -> bool {
+        self.peek_is_punct(0, punct)
+    }
+
+    fn check_ident(&self, word: &str) -> bool {
+        self.peek_is_ident(0, word)
+    }
+
+    fn match_punct(&mut self, punct: &str) -> bool {
+        let matched = self.check_punct(punct);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn match_ident(&mut self, word: &str) -> bool {
+        let matched = self.check_ident(word);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn expect_punct(&mut self, punct: &str, message: &str) -> Result<(), ParseError> {
+        if self.match_punct(punct) {
+            Ok(())
+        } else {
+            Err(self.error_here(message))
+        }
+    }
+
+    /// The span from `start` to the end of the last consumed token.
+    fn span_from(&self, start: Span) -> Span {
+        let end = self
+            .pos
+            .checked_sub(1)
+            .and_then(|index| self.tokens.get(index))
+            .map_or(start.hi, |token| token.span.hi);
+        Span::new(JS_SOURCE_ID, start.lo, end.max(start.lo))
+    }
+
+    fn error_here(&self, message: &str) -> ParseError {
+        self.error_at(self.current(), message)
+    }
+
+    fn error_at(&self, token: &Token, message: &str) -> ParseError {
+        ParseError {
+            span: Some(token.span),
+            code: None,
+            line: token.line,
+            message: message.to_string(),
+        }
+    }
+}
+
+fn is_assignable(expr: &Expr) -> bool {
+    matches!(
+        expr.kind,
+        ExprKind::Ident(_)
+            | ExprKind::Member {
+                optional: false,
+                ..
+            }
+            | ExprKind::Index {
+                optional: false,
+                ..
+            }
+    )
+}
+
+/// Reinterprets an array or object literal on the left of `=` as an
+/// assignment pattern.
+fn expr_to_pattern(expr: Expr) -> Result<Pattern, ParseError> {
+    match expr.kind {
+        ExprKind::Array(items) => {
+            let mut elements = Vec::new();
+            let mut rest = None;
+            let count = items.len();
+            for (index, item) in items.into_iter().enumerate() {
+                match item {
+                    Element::Hole => elements.push(None),
+                    Element::Item(item) => elements.push(Some(expr_to_pattern_element(item)?)),
+                    Element::Spread(target) if index + 1 == count => {
+                        rest = Some(Box::new(expr_to_pattern(target)?));
+                    }
+                    Element::Spread(target) => {
+                        return Err(pattern_error(&target, "a rest element must be last"));
+                    }
+                }
+            }
+            Ok(Pattern::Array { elements, rest })
+        }
+        ExprKind::Object(members) => {
+            let mut properties = Vec::new();
+            let mut rest = None;
+            let count = members.len();
+            for (index, member) in members.into_iter().enumerate() {
+                match member {
+                    ObjectMember::Property { key, value } => {
+                        let element = expr_to_pattern_element(value)?;
+                        properties.push(PatternProperty { key, element });
+                    }
+                    ObjectMember::ShorthandDefault { name, default } => {
+                        let ExprKind::Ident(key) = &name.kind else {
+                            return Err(pattern_error(&name, "invalid destructuring target"));
+                        };
+                        properties.push(PatternProperty {
+                            key: PropertyKey::Name(key.clone()),
+                            element: PatternElement {
+                                pattern: Pattern::Target(name),
+                                default: Some(default),
+                            },
+                        });
+                    }
+                    ObjectMember::Spread(target) if index + 1 == count => {
+                        if !is_assignable(&target) {
+                            return Err(pattern_error(&target, "invalid destructuring target"));
+                        }
+                        rest = Some(Box::new(target));
+                    }
+                    ObjectMember::Spread(target) => {
+                        return Err(pattern_error(&target, "a rest element must be last"));
+                    }
+                }
+            }
+            Ok(Pattern::Object { properties, rest })
+        }
+        _ if is_assignable(&expr) => Ok(Pattern::Target(expr)),
+        _ => Err(pattern_error(&expr, "invalid destructuring target")),
+    }
+}
+
+fn expr_to_pattern_element(expr: Expr) -> Result<PatternElement, ParseError> {
+    match expr.kind {
+        ExprKind::Assign {
+            target,
+            op: None,
+            value,
+        } => Ok(PatternElement {
+            pattern: expr_to_pattern(*target)?,
+            default: Some(*value),
+        }),
+        ExprKind::Destructure { pattern, value } => Ok(PatternElement {
+            pattern: *pattern,
+            default: Some(*value),
+        }),
+        _ => Ok(PatternElement {
+            pattern: expr_to_pattern(expr)?,
+            default: None,
+        }),
+    }
+}
+
+fn pattern_error(expr: &Expr, message: &str) -> ParseError {
+    ParseError {
+        span: Some(expr.span),
+        code: None,
+        line: expr.line,
+        message: message.to_string(),
+    }
+}
+
+/// Maps the binding of `const ... = require("spec")` onto an import clause:
+/// a plain name imports the namespace and `{ a, b: c }` imports members.
+fn require_binding_clause(pattern: &Pattern) -> Option<ImportClause> {
+    match pattern {
+        Pattern::Target(Expr {
+            kind: ExprKind::Ident(name),
+            ..
+        }) => Some(ImportClause::Namespace(name.clone())),
+        Pattern::Object {
+            properties,
+            rest: None,
+        } => properties
+            .iter()
+            .map(|property| match (&property.key, &property.element) {
+                (
+                    PropertyKey::Name(imported),
+                    PatternElement {
+                        pattern:
+                            Pattern::Target(Expr {
+                                kind: ExprKind::Ident(local),
+                                ..
+                            }),
+                        default: None,
+                    },
+                ) => Some((imported.clone(), local.clone())),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(ImportClause::Named),
+        _ => None,
+    }
+}
+
+/// Returns the specifier of a `require("spec")` call.
+fn require_spec(expr: &Expr) -> Option<String> {
+    let ExprKind::Call { callee, args } = &expr.kind else {
+        return None;
+    };
+    match (&callee.kind, args.as_slice()) {
+        (
+            ExprKind::Ident(name),
+            [
+                Element::Item(Expr {
+                    kind: ExprKind::String(spec),
+                    ..
+                }),
+            ],
+        ) if name == "require" => Some(spec.clone()),
+        _ => None,
+    }
+}
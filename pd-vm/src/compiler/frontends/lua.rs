@@ -1,6 +1,11 @@
 use super::super::ParseError;
 use super::super::ir::{Expr, FrontendIr, Stmt};
-use super::{is_ident_continue, is_ident_start};
+use super::lua_metatables::{LuaTableFunctions, is_lua_name, parse_table_function_header};
+use super::lua_multi_values::{LuaMultiValues, LuaValueList, parse_function_header};
+use super::{
+    SORT_BY_HELPER, SORT_BY_HELPER_NAME, is_ident_continue, is_ident_start, push_source_byte,
+};
+use crate::builtins::BuiltinFunction;
 use crate::compiler::source_map::LoweredSource;
 use std::collections::HashMap;
 use std::collections::HashSet;
@@ -11,30 +16,43 @@ enum LuaBlock {
     While,
     Do,
     Repeat,
-    FunctionDecl,
+    /// Body of a function, which returns an array of all of its results when
+    /// `returns_values` is set.
+    FunctionDecl {
+        returns_values: bool,
+    },
+    /// Body of a table function, which returns its result paired with
+    /// `carrier`, the value of its first parameter.
+    TableFunction {
+        carrier: String,
+    },
 }
 
 #[derive(Default)]
 struct LuaLoweringContext {
     needs_string_sub_helpers: bool,
     needs_table_len_helper: bool,
-    next_temp_id: u32,
-}
-
-impl LuaLoweringContext {
-    fn fresh_temp(&mut self, prefix: &str) -> String {
-        let id = self.next_temp_id;
-        self.next_temp_id = self.next_temp_id.saturating_add(1);
-        format!("__lua_{prefix}_{id}")
-    }
+    needs_sort_by_helper: bool,
+    needs_pattern_helpers: bool,
+    gmatch_loops: usize,
+    table_functions: LuaTableFunctions,
+    /// Set while lowering a metamethod body, whose `tostring` calls stay raw
+    /// so handlers do not dispatch back into themselves.
+    in_metamethod: bool,
+    multi_values: LuaMultiValues,
 }
 
 pub(super) fn lower_to_ir(source: &str) -> Result<FrontendIr, ParseError> {
     if let Some(ir) = try_lower_direct_subset_to_ir(source)? {
         return Ok(ir);
     }
-    let lowered = lower(source)?;
-    super::parse_lowered_with_mapping(source, lowered, false, false)
+    let (lowered, table_functions) = lower(source)?;
+    let mut ir = super::parse_lowered_with_mapping(source, lowered, false, false)?;
+    table_functions.rewrite_ir(&mut ir);
+    if super::table_slot::calls_builtin(&mut ir, BuiltinFunction::LuaSetMetatable) {
+        super::table_slot::hide_reserved_slot(&mut ir, true);
+    }
+    Ok(ir)
 }
 
 fn try_lower_direct_subset_to_ir(source: &str) -> Result<Option<FrontendIr>, ParseError> {
@@ -318,8 +336,10 @@ impl LuaDirectIrBuilder {
             stmts,
             locals: self.next_local as usize,
             local_bindings,
+            scoped_bindings: Vec::new(),
             functions: Vec::new(),
             function_impls: HashMap::new(),
+            warnings: Vec::new(),
         }
     }
 
@@ -673,7 +693,7 @@ fn tokenize_lua_direct_expr(input: &str) -> Option<Vec<LuaDirectToken>> {
                 if ch == quote {
                     break;
                 }
-                text.push(ch as char);
+                push_source_byte(&mut text, bytes, i - 1);
             }
             if escaped {
                 return None;
@@ -758,16 +778,30 @@ fn tokenize_lua_direct_expr(input: &str) -> Option<Vec<LuaDirectToken>> {
     Some(out)
 }
 
-pub(super) fn lower(source: &str) -> Result<LoweredSource, ParseError> {
+pub(super) fn lower(source: &str) -> Result<(LoweredSource, LuaTableFunctions), ParseError> {
     let cleaned_source = remove_lua_comments(source)?;
+    let split_lines = split_lua_function_literal_lines(&cleaned_source);
+    let lines = split_lines
+        .iter()
+        .map(|(_, line)| line.as_str())
+        .collect::<Vec<_>>();
     let mut out = Vec::new();
     let mut blocks = Vec::new();
-    let mut lowering_context = LuaLoweringContext::default();
+    let mut lowering_context = LuaLoweringContext {
+        table_functions: LuaTableFunctions::scan(lines.iter().copied()),
+        ..LuaLoweringContext::default()
+    };
     let mut vm_namespace_aliases = HashSet::new();
     let mut vm_import_emitted = false;
 
-    for (index, raw_line) in cleaned_source.lines().enumerate() {
-        let line_no = index + 1;
+    // Output index where the statements split from the previous line start.
+    let mut continuation = None;
+    for (index, raw_line) in lines.iter().enumerate() {
+        merge_split_statements(&mut out, continuation.take());
+        let line_no = split_lines[index].0;
+        if index > 0 && split_lines[index - 1].0 == line_no {
+            continuation = Some(out.len());
+        }
         let trimmed_raw = raw_line.trim();
         if trimmed_raw.is_empty() {
             out.push(String::new());
@@ -788,9 +822,68 @@ pub(super) fn lower(source: &str) -> Result<LoweredSource, ParseError> {
             out.push(String::new());
             continue;
         }
+        if let Some(header) = parse_table_function_header(trimmed_raw) {
+            let Some(function) = lowering_context
+                .table_functions
+                .find(header.table, header.field)
+            else {
+                unreachable!("table functions are collected before lowering");
+            };
+            let (signature, carrier) = (function.signature(), function.carrier().to_string());
+            lowering_context.in_metamethod = header.field.starts_with("__");
+            let declaration = format!(
+                "{}.{} = \"{}\"; fn {signature}",
+                header.table,
+                header.field,
+                function.tag()
+            );
+            match header.inline_return {
+                Some(value) => {
+                    let value = rewrite_lua_expr(
+                        value,
+                        &vm_namespace_aliases,
+                        &mut lowering_context,
+                        line_no,
+                    )?;
+                    out.push(format!("{declaration} {{ return [{value}, {carrier}]; }}"));
+                    lowering_context.in_metamethod = false;
+                }
+                None => {
+                    out.push(format!("{declaration} {{"));
+                    lowering_context.multi_values.enter_table_function();
+                    blocks.push(LuaBlock::TableFunction { carrier });
+                }
+            }
+            continue;
+        }
+        if let Some(table) = parse_lua_self_index_assignment(trimmed_raw) {
+            // `T.__index = T` would store a snapshot of `T` taken before its
+            // methods are declared; `true` makes lookups use the metatable itself.
+            out.push(format!("{table}.__index = true;"));
+            continue;
+        }
+        if let Some(stmt) = lower_lua_table_call_stmt(
+            trimmed_raw,
+            &vm_namespace_aliases,
+            &mut lowering_context,
+            line_no,
+        )? {
+            out.push(stmt);
+            continue;
+        }
+
         let rewritten = rewrite_lua_inline_function_literal(trimmed_raw, line_no)?;
         let trimmed = rewritten.trim();
 
+        if let Some(header) = parse_function_header(trimmed) {
+            let returns_values = lowering_context
+                .multi_values
+                .enter_function(&header, &lines[index + 1..]);
+            out.push(format!("fn {}({}) {{", header.name, header.rust_params()));
+            blocks.push(LuaBlock::FunctionDecl { returns_values });
+            continue;
+        }
+
         if let Some(rest) = trimmed.strip_prefix("local function ") {
             let signature = rest.trim().trim_end_matches(';').trim();
             if !signature.ends_with(')') {
@@ -802,11 +895,24 @@ pub(super) fn lower(source: &str) -> Result<LoweredSource, ParseError> {
                 });
             }
             out.push(format!("fn {signature} {{"));
-            blocks.push(LuaBlock::FunctionDecl);
+            blocks.push(LuaBlock::FunctionDecl {
+                returns_values: false,
+            });
             continue;
         }
 
         if let Some(rest) = trimmed.strip_prefix("local ") {
+            let rest = rest.trim().trim_end_matches(';').trim();
+            if let Some(stmt) = lower_lua_multiple_assignment(
+                rest,
+                true,
+                &vm_namespace_aliases,
+                &mut lowering_context,
+                line_no,
+            )? {
+                out.push(stmt);
+                continue;
+            }
             out.push(format!(
                 "let {};",
                 rewrite_lua_expr(
@@ -833,7 +939,9 @@ pub(super) fn lower(source: &str) -> Result<LoweredSource, ParseError> {
                 out.push(format!("fn {signature};"));
             } else {
                 out.push(format!("fn {signature} {{"));
-                blocks.push(LuaBlock::FunctionDecl);
+                blocks.push(LuaBlock::FunctionDecl {
+                    returns_values: false,
+                });
             }
             continue;
         }
@@ -873,71 +981,57 @@ pub(super) fn lower(source: &str) -> Result<LoweredSource, ParseError> {
         if let Some(rest) = trimmed.strip_prefix("for ")
             && let Some(header) = rest.strip_suffix(" do")
         {
+            if let Some(gmatch) = parse_lua_gmatch_for_header(header) {
+                // `gmatch` is collected eagerly into one capture row per match,
+                // and each row is unpacked into the loop variables.
+                let mut args = Vec::new();
+                for arg in std::iter::once(gmatch.subject).chain(gmatch.args) {
+                    args.push(rewrite_lua_expr(
+                        arg.trim(),
+                        &vm_namespace_aliases,
+                        &mut lowering_context,
+                        line_no,
+                    )?);
+                }
+                if !(2..=3).contains(&args.len()) {
+                    return Err(ParseError {
+                        span: None,
+                        code: None,
+                        line: line_no,
+                        message: "lua 'gmatch' expects a pattern and an optional init".to_string(),
+                    });
+                }
+                let row = format!("__lua_gmatch_{}", lowering_context.gmatch_loops);
+                lowering_context.gmatch_loops += 1;
+                let mut header = format!("for (_, {row}) in lua::gmatch({}) {{", args.join(", "));
+                for (index, name) in gmatch.names.iter().enumerate() {
+                    header.push_str(&format!(" let {name} = {row}[{index}];"));
+                }
+                out.push(header);
+                blocks.push(LuaBlock::For);
+                continue;
+            }
+
             if let Some(generic) = parse_lua_generic_for_header(header) {
-                match generic.kind {
-                    LuaIteratorKind::Ipairs => {
-                        let iterable = rewrite_lua_expr(
-                            generic.iterable.trim(),
-                            &vm_namespace_aliases,
-                            &mut lowering_context,
-                            line_no,
-                        )?;
-                        lowering_context.needs_table_len_helper = true;
-                        let table_temp = lowering_context.fresh_temp("ipairs_table");
-                        out.push(format!("let {table_temp} = {iterable};"));
-                        if let Some(value_name) = generic.value_name {
-                            out.push(format!(
-                                "for (let {} = 0; {} < __lua_len({table_temp}); {} = {} + 1) {{",
-                                generic.key_name,
-                                generic.key_name,
-                                generic.key_name,
-                                generic.key_name
-                            ));
-                            out.push(format!(
-                                "let {value_name} = ({table_temp})[{}];",
-                                generic.key_name
-                            ));
-                        } else {
-                            out.push(format!(
-                                "for (let {} = 0; {} < __lua_len({table_temp}); {} = {} + 1) {{",
-                                generic.key_name,
-                                generic.key_name,
-                                generic.key_name,
-                                generic.key_name
-                            ));
-                        }
-                        blocks.push(LuaBlock::For);
-                        continue;
-                    }
-                    LuaIteratorKind::Pairs => {
-                        let iterable = rewrite_lua_expr(
-                            generic.iterable.trim(),
-                            &vm_namespace_aliases,
-                            &mut lowering_context,
-                            line_no,
-                        )?;
-                        let table_temp = lowering_context.fresh_temp("pairs_table");
-                        let keys_temp = lowering_context.fresh_temp("pairs_keys");
-                        let iter_temp = lowering_context.fresh_temp("pairs_i");
-                        out.push(format!("let {table_temp} = {iterable};"));
-                        out.push(format!("let {keys_temp} = ({table_temp}).keys;"));
-                        out.push(format!(
-                            "for (let {iter_temp} = 0; {iter_temp} < ({keys_temp}).length; {iter_temp} = {iter_temp} + 1) {{"
-                        ));
-                        out.push(format!(
-                            "let {} = ({keys_temp})[{iter_temp}];",
-                            generic.key_name
-                        ));
-                        if let Some(value_name) = generic.value_name {
-                            out.push(format!(
-                                "let {value_name} = ({table_temp})[{}];",
-                                generic.key_name
-                            ));
-                        }
-                        blocks.push(LuaBlock::For);
-                        continue;
-                    }
+                // Both iterators lower to a native for-in loop. `ipairs` walks the
+                // sequence prefix, whose keys are the (zero-based) array indices.
+                let mut iterable = rewrite_lua_expr(
+                    generic.iterable.trim(),
+                    &vm_namespace_aliases,
+                    &mut lowering_context,
+                    line_no,
+                )?;
+                if generic.sequence_only {
+                    lowering_context.needs_table_len_helper = true;
+                    iterable = format!("__lua_ipairs({iterable})");
                 }
+                let value_name = generic.value_name.as_deref().unwrap_or("_");
+                out.push(format!(
+                    "for ({}, {value_name}) in {iterable} {{",
+                    generic.key_name
+                ));
+                blocks.push(LuaBlock::For);
+                continue;
             }
 
             let eq_index = header.find('=').ok_or(ParseError {
@@ -1089,11 +1183,18 @@ pub(super) fn lower(source: &str) -> Result<LoweredSource, ParseError> {
                         message: "lua 'repeat' block must be closed with 'until'".to_string(),
                     });
                 }
-                LuaBlock::FunctionDecl
-                | LuaBlock::If
-                | LuaBlock::For
-                | LuaBlock::While
-                | LuaBlock::Do => out.push("}".to_string()),
+                LuaBlock::TableFunction { carrier } => {
+                    lowering_context.in_metamethod = false;
+                    lowering_context.multi_values.exit_function();
+                    out.push(format!("[null, {carrier}]; }}"));
+                }
+                LuaBlock::FunctionDecl { returns_values } => {
+                    lowering_context.multi_values.exit_function();
+                    out.push(if returns_values { "[]; }" } else { "}" }.to_string());
+                }
+                LuaBlock::If | LuaBlock::For | LuaBlock::While | LuaBlock::Do => {
+                    out.push("}".to_string())
+                }
             }
             continue;
         }
@@ -1108,16 +1209,83 @@ pub(super) fn lower(source: &str) -> Result<LoweredSource, ParseError> {
             continue;
         }
 
+        if let Some(table_stmt) = lower_lua_table_library_stmt(
+            trimmed.trim_end_matches(';').trim(),
+            &vm_namespace_aliases,
+            &mut lowering_context,
+            line_no,
+        )? {
+            out.push(table_stmt);
+            continue;
+        }
+
+        let in_function = blocks.iter().any(|block| {
+            matches!(
+                block,
+                LuaBlock::FunctionDecl { .. } | LuaBlock::TableFunction { .. }
+            )
+        });
+        // Table functions return their result paired with the carrier.
+        let carrier = blocks.iter().rev().find_map(|block| match block {
+            LuaBlock::FunctionDecl { .. } => Some(None),
+            LuaBlock::TableFunction { carrier } => Some(Some(carrier.as_str())),
+            _ => None,
+        });
+        let returns_values = blocks.iter().rev().find_map(|block| match block {
+            LuaBlock::FunctionDecl { returns_values } => Some(*returns_values),
+            LuaBlock::TableFunction { .. } => Some(false),
+            _ => None,
+        }) == Some(true);
+        if in_function && (trimmed == "return" || trimmed == "return;") {
+            match carrier.flatten() {
+                Some(carrier) => out.push(format!("return [null, {carrier}];")),
+                None if returns_values => out.push("return [];".to_string()),
+                None => out.push("return;".to_string()),
+            }
+            continue;
+        }
+
         if let Some(rest) = trimmed.strip_prefix("return ") {
-            out.push(format!(
-                "{};",
-                rewrite_lua_expr(
-                    rest.trim().trim_end_matches(';').trim(),
-                    &vm_namespace_aliases,
-                    &mut lowering_context,
-                    line_no
-                )?,
-            ));
+            let items = split_top_level_csv(rest.trim().trim_end_matches(';').trim());
+            let values = rewrite_lua_expr_list(
+                &items,
+                &vm_namespace_aliases,
+                &mut lowering_context,
+                line_no,
+            )?;
+            let single = values.single().map(str::to_string);
+            // Chunk-level returns leave their value as the program result, with
+            // several values collected into an array.
+            if let Some(carrier) = carrier.flatten() {
+                let Some(value) = single else {
+                    return Err(ParseError {
+                        span: None,
+                        code: None,
+                        line: line_no,
+                        message: "lua table functions return a single value".to_string(),
+                    });
+                };
+                out.push(format!("return [{value}, {carrier}];"));
+            } else if returns_values {
+                out.push(format!("return {};", values.array()));
+            } else if in_function {
+                let value = single.unwrap_or_else(|| values.array());
+                out.push(format!("return {value};"));
+            } else {
+                let value = single.unwrap_or_else(|| values.array());
+                out.push(format!("{value};"));
+            }
+            continue;
+        }
+
+        if let Some(stmt) = lower_lua_multiple_assignment(
+            trimmed.trim_end_matches(';').trim(),
+            false,
+            &vm_namespace_aliases,
+            &mut lowering_context,
+            line_no,
+        )? {
+            out.push(stmt);
             continue;
         }
 
@@ -1131,6 +1299,7 @@ pub(super) fn lower(source: &str) -> Result<LoweredSource, ParseError> {
             )?
         ));
     }
+    merge_split_statements(&mut out, continuation);
 
     if !blocks.is_empty() {
         return Err(ParseError {
@@ -1148,20 +1317,257 @@ pub(super) fn lower(source: &str) -> Result<LoweredSource, ParseError> {
         out = combined;
     }
 
-    Ok(LoweredSource::identity(out.join("\n")))
+    Ok((
+        LoweredSource::identity(out.join("\n")),
+        lowering_context.table_functions,
+    ))
 }
 
-#[derive(Copy, Clone)]
-enum LuaIteratorKind {
-    Pairs,
-    Ipairs,
+/// Lowers `a, b = f()` and `local a, b = ...`-style statements: the value
+/// list is collected into an array first, so every value is evaluated before
+/// any target is assigned, and missing values assign `nil`. Returns `None` for
+/// assignments of a single value to a single target.
+fn lower_lua_multiple_assignment(
+    statement: &str,
+    local: bool,
+    vm_namespace_aliases: &HashSet<String>,
+    lowering_context: &mut LuaLoweringContext,
+    line_no: usize,
+) -> Result<Option<String>, ParseError> {
+    let (targets_raw, values_raw) = match find_lua_assignment(statement) {
+        Some(index) => (&statement[..index], Some(&statement[index + 1..])),
+        None if local => (statement, None),
+        None => return Ok(None),
+    };
+    let targets = split_top_level_csv(targets_raw);
+    let items = values_raw.map(split_top_level_csv).unwrap_or_default();
+    if targets.len() < 2 && items.len() < 2 {
+        return Ok(None);
+    }
+    if local && !targets.iter().all(|target| is_lua_name(target)) {
+        return Err(ParseError {
+            span: None,
+            code: None,
+            line: line_no,
+            message: "lua 'local' expects a list of names".to_string(),
+        });
+    }
+    let values = rewrite_lua_expr_list(&items, vm_namespace_aliases, lowering_context, line_no)?;
+    let temp = lowering_context.multi_values.next_temp();
+    let mut stmts = vec![format!("let {temp} = {};", values.array())];
+    for (index, target) in targets.iter().enumerate() {
+        let value = format!("lua::rawget({temp}, {index})");
+        if local {
+            stmts.push(format!("let {target} = {value};"));
+        } else {
+            let target = rewrite_lua_expr(target, vm_namespace_aliases, lowering_context, line_no)?;
+            stmts.push(format!("{target} = {value};"));
+        }
+    }
+    Ok(Some(stmts.join(" ")))
+}
+
+/// Index of the `=` of an assignment statement, outside brackets and strings.
+fn find_lua_assignment(statement: &str) -> Option<usize> {
+    let bytes = statement.as_bytes();
+    let mut depth = 0usize;
+    let mut string_delim: Option<u8> = None;
+    let mut escaped = false;
+    for (index, &b) in bytes.iter().enumerate() {
+        if let Some(delim) = string_delim {
+            if escaped {
+                escaped = false;
+            } else if b == b'\\' {
+                escaped = true;
+            } else if b == delim {
+                string_delim = None;
+            }
+            continue;
+        }
+        match b {
+            b'"' | b'\'' => string_delim = Some(b),
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => depth = depth.saturating_sub(1),
+            b'=' if depth == 0 => {
+                let before = index.checked_sub(1).map(|before| bytes[before]);
+                let after = bytes.get(index + 1).copied();
+                if !matches!(before, Some(b'=' | b'~' | b'<' | b'>')) && after != Some(b'=') {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Matches `T.__index = T`, returning `T`.
+fn parse_lua_self_index_assignment(line: &str) -> Option<&str> {
+    let (target, value) = line.trim_end_matches(';').split_once('=')?;
+    let table = target.trim().strip_suffix(".__index")?;
+    (is_lua_name(table) && value.trim() == table).then_some(table)
+}
+
+/// Lowers statements that update a table in place: `setmetatable(t, mt)`,
+/// `rawset(t, k, v)`, and table-function method calls (`obj:m(...)`, also as
+/// the value of `local x =` or `x =`), whose receiver gets the updated `self`
+/// written back.
+fn lower_lua_table_call_stmt(
+    line: &str,
+    vm_namespace_aliases: &HashSet<String>,
+    lowering_context: &mut LuaLoweringContext,
+    line_no: usize,
+) -> Result<Option<String>, ParseError> {
+    let statement = line.trim().trim_end_matches(';').trim_end();
+    let (binding, call) = match statement.strip_prefix("local ") {
+        Some(rest) => match rest.split_once('=') {
+            Some((name, value)) if is_lua_name(name.trim()) => {
+                (Some(format!("let {}", name.trim())), value.trim())
+            }
+            _ => return Ok(None),
+        },
+        None => match statement.split_once('=') {
+            Some((name, value))
+                if is_lua_path(name.trim())
+                    && !value.starts_with('=')
+                    && !name.ends_with(['~', '<', '>']) =>
+            {
+                (Some(name.trim().to_string()), value.trim())
+            }
+            _ => (None, statement),
+        },
+    };
+    let Some(open) = call.find('(') else {
+        return Ok(None);
+    };
+    let callee = call[..open].trim_end();
+    let method_call = callee
+        .split_once(':')
+        .map(|(receiver, method)| (receiver.trim(), method.trim()))
+        .filter(|(receiver, method)| {
+            is_lua_path(receiver) && lowering_context.table_functions.has_field(method)
+        });
+    let updates_table = binding.is_none() && matches!(callee, "setmetatable" | "rawset");
+    if method_call.is_none() && !updates_table {
+        return Ok(None);
+    }
+    let (args_raw, next_index) = parse_balanced_call_args(call, open, line_no)?;
+    if next_index != call.len() {
+        return Ok(None);
+    }
+    let mut args = Vec::new();
+    for arg in split_top_level_csv(&args_raw) {
+        args.push(rewrite_lua_expr(
+            arg.trim(),
+            vm_namespace_aliases,
+            lowering_context,
+            line_no,
+        )?);
+    }
+
+    let Some((receiver, method)) = method_call else {
+        let Some(target) = args.first().filter(|target| is_lua_path(target)) else {
+            return Ok(None);
+        };
+        return Ok(Some(format!(
+            "{target} = lua::{callee}({});",
+            args.join(", ")
+        )));
+    };
+    let pair = lowering_context
+        .table_functions
+        .send(receiver, method, &args);
+    let Some(binding) = binding else {
+        return Ok(Some(format!("{receiver} = {pair}[1];")));
+    };
+    let temp = lowering_context.table_functions.next_write_back();
+    Ok(Some(format!(
+        "let {temp} = {pair}; {receiver} = {temp}[1]; {binding} = {temp}[0];"
+    )))
+}
+
+/// `name` or a dotted field path such as `self.items`.
+fn is_lua_path(input: &str) -> bool {
+    input.split('.').all(is_lua_name)
+}
+
+fn lower_lua_table_library_stmt(
+    statement: &str,
+    vm_namespace_aliases: &HashSet<String>,
+    lowering_context: &mut LuaLoweringContext,
+    line_no: usize,
+) -> Result<Option<String>, ParseError> {
+    let Some(rest) = statement.strip_prefix("table.") else {
+        return Ok(None);
+    };
+    let Some(open_index) = rest.find('(') else {
+        return Ok(None);
+    };
+    let function = rest[..open_index].trim();
+    if !matches!(function, "sort" | "remove" | "insert") {
+        return Ok(None);
+    }
+    let (args_raw, next_index) = parse_balanced_call_args(rest, open_index, line_no)?;
+    if !rest[next_index..].trim().is_empty() {
+        return Ok(None);
+    }
+
+    let mut args = Vec::new();
+    for arg in split_top_level_csv(&args_raw) {
+        let arg = arg.trim();
+        let arg = if let Some(after_keyword) = arg.strip_prefix("function")
+            && after_keyword.trim_start().starts_with('(')
+        {
+            let (params, body) =
+                parse_lua_function_literal_tail(after_keyword.trim_start(), line_no)?;
+            format!("|{params}| {body}")
+        } else {
+            arg.to_string()
+        };
+        args.push(rewrite_lua_expr(
+            &arg,
+            vm_namespace_aliases,
+            lowering_context,
+            line_no,
+        )?);
+    }
+
+    let lowered = match (function, args.as_slice()) {
+        ("sort", [table]) => format!("{table} = array::sort({table});"),
+        ("sort", [table, less]) => {
+            lowering_context.needs_sort_by_helper = true;
+            format!("{table} = {SORT_BY_HELPER_NAME}({table}, {less});")
+        }
+        ("remove", [table]) => format!("{table} = array::pop({table});"),
+        ("remove", [table, pos]) => format!("{table} = array::remove({table}, {pos});"),
+        ("insert", [table, value]) => {
+            format!("{table} = array::insert({table}, ({table}).length, {value});")
+        }
+        ("insert", [table, pos, value]) => {
+            format!("{table} = array::insert({table}, {pos}, {value});")
+        }
+        _ => {
+            let expected = match function {
+                "sort" => "(t [, comp])",
+                "remove" => "(t [, pos])",
+                _ => "(t, [pos,] value)",
+            };
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line: line_no,
+                message: format!("lua 'table.{function}' expects arguments {expected}"),
+            });
+        }
+    };
+    Ok(Some(lowered))
 }
 
 struct LuaGenericFor {
     key_name: String,
     value_name: Option<String>,
     iterable: String,
-    kind: LuaIteratorKind,
+    sequence_only: bool,
 }
 
 fn parse_lua_generic_for_header(header: &str) -> Option<LuaGenericFor> {
@@ -1178,22 +1584,68 @@ fn parse_lua_generic_for_header(header: &str) -> Option<LuaGenericFor> {
         return None;
     }
 
-    let (kind, iterable) = parse_lua_iterator_call(iter_raw.trim())?;
+    let (sequence_only, iterable) = parse_lua_iterator_call(iter_raw.trim())?;
     Some(LuaGenericFor {
         key_name: vars[0].clone(),
         value_name: vars.get(1).cloned(),
         iterable,
-        kind,
+        sequence_only,
     })
 }
 
-fn parse_lua_iterator_call(input: &str) -> Option<(LuaIteratorKind, String)> {
-    let (kind, call_head) = if let Some(rest) = input.strip_prefix("pairs") {
-        (LuaIteratorKind::Pairs, rest)
-    } else if let Some(rest) = input.strip_prefix("ipairs") {
-        (LuaIteratorKind::Ipairs, rest)
+struct LuaGmatchFor {
+    names: Vec<String>,
+    subject: String,
+    args: Vec<String>,
+}
+
+/// Parses `a, b in s:gmatch(p)` / `a, b in string.gmatch(s, p)`.
+fn parse_lua_gmatch_for_header(header: &str) -> Option<LuaGmatchFor> {
+    let (vars_raw, iter_raw) = split_once_top_level_keyword(header, "in")?;
+    let names = split_top_level_csv(vars_raw);
+    if names.is_empty() || !names.iter().all(|name| is_valid_lua_ident(name)) {
+        return None;
+    }
+    let iter_raw = iter_raw.trim();
+    let (subject, call) = if let Some(call) = iter_raw.strip_prefix("string.gmatch") {
+        (None, call.trim_start())
     } else {
+        let (receiver, call) = iter_raw.split_once(':')?;
+        let receiver = receiver.trim();
+        if !is_valid_lua_ident(receiver) {
+            return None;
+        }
+        (
+            Some(receiver.to_string()),
+            call.trim_start().strip_prefix("gmatch")?.trim_start(),
+        )
+    };
+    if !call.starts_with('(') {
+        return None;
+    }
+    let (args_raw, next_index) = parse_balanced_call_args(call, 0, 0).ok()?;
+    if next_index != call.len() {
         return None;
+    }
+    let mut args = split_top_level_csv(&args_raw);
+    let subject = match subject {
+        Some(subject) => subject,
+        None if !args.is_empty() => args.remove(0),
+        None => return None,
+    };
+    Some(LuaGmatchFor {
+        names,
+        subject,
+        args,
+    })
+}
+
+/// Parses `pairs(t)` / `ipairs(t)`, returning whether it is `ipairs` and `t`.
+fn parse_lua_iterator_call(input: &str) -> Option<(bool, String)> {
+    let (sequence_only, call_head) = if let Some(rest) = input.strip_prefix("pairs") {
+        (false, rest)
+    } else {
+        (true, input.strip_prefix("ipairs")?)
     };
     let call_head = call_head.trim_start();
     if !call_head.starts_with('(') {
@@ -1240,7 +1692,7 @@ fn parse_lua_iterator_call(input: &str) -> Option<(LuaIteratorKind, String)> {
                 if iterable.is_empty() {
                     return None;
                 }
-                return Some((kind, iterable.to_string()));
+                return Some((sequence_only, iterable.to_string()));
             }
             continue;
         }
@@ -1424,6 +1876,142 @@ fn parse_lua_local_assignment(line: &str) -> Option<(&str, &str)> {
     }
 }
 
+/// Source lines with each statement-bodied `local NAME = function(...)`
+/// literal rewritten as `local function NAME(...)`. A body that shares the
+/// literal's line is split into one entry per statement; every entry keeps
+/// the line number it came from.
+fn split_lua_function_literal_lines(source: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line_no = index + 1;
+        match parse_lua_local_function_literal(line.trim()) {
+            Some((name, params, body)) => {
+                lines.push((line_no, format!("local function {name}({params})")));
+                lines.extend(
+                    split_lua_statements(body)
+                        .into_iter()
+                        .map(|statement| (line_no, statement)),
+                );
+            }
+            None => lines.push((line_no, line.to_string())),
+        }
+    }
+    lines
+}
+
+/// Name, parameters and same-line body of `local NAME = function(PARAMS)`,
+/// unless the literal is a single `return <expr> end`, which lowers to a
+/// closure, or a same-line body that never returns.
+fn parse_lua_local_function_literal(line: &str) -> Option<(&str, &str, &str)> {
+    let (name, rhs) = parse_lua_local_assignment(line)?;
+    let after_keyword = rhs.strip_prefix("function")?.trim_start();
+    if !after_keyword.starts_with('(') {
+        return None;
+    }
+    let close = after_keyword.find(')')?;
+    let params = after_keyword[1..close].trim();
+    let body = after_keyword[close + 1..].trim().trim_end_matches(';');
+    let statements = split_lua_statements(body);
+    if statements.len() == 2 && statements[0].starts_with("return") && statements[1] == "end" {
+        return None;
+    }
+    if !statements.is_empty()
+        && !statements
+            .iter()
+            .any(|s| s == "return" || s.starts_with("return "))
+    {
+        return None;
+    }
+    Some((name, params, body))
+}
+
+/// Splits one line of Lua into statements: around `then`, `do`, `else`,
+/// `end` and `repeat`, and before a keyword that starts a statement.
+/// Bracketed text and function literals are kept whole.
+fn split_lua_statements(line: &str) -> Vec<String> {
+    fn push(statements: &mut Vec<String>, text: &str) {
+        let text = text.trim();
+        if !text.is_empty() {
+            statements.push(text.to_string());
+        }
+    }
+
+    let bytes = line.as_bytes();
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut depth = 0usize;
+    let mut literal_depth = 0usize;
+    let mut at_statement_start = true;
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b == b'"' || b == b'\'' {
+            i = parse_lua_string_end(line, i, 0).unwrap_or(bytes.len());
+            at_statement_start = false;
+            continue;
+        }
+        match b {
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if depth > 0 || !is_ident_start(b as char) {
+            at_statement_start &= b.is_ascii_whitespace();
+            i += 1;
+            continue;
+        }
+        let word_end = line[i..]
+            .find(|ch: char| !is_ident_continue(ch))
+            .map_or(bytes.len(), |len| i + len);
+        let word = &line[i..word_end];
+        if literal_depth > 0 {
+            match word {
+                "function" | "if" | "do" => literal_depth += 1,
+                "end" => literal_depth -= 1,
+                _ => {}
+            }
+        } else {
+            match word {
+                "function" if !at_statement_start => literal_depth = 1,
+                "end" | "else" | "elseif" | "until" | "return" | "local" | "if" | "while"
+                | "for" | "repeat" | "break" | "goto"
+                    if !at_statement_start =>
+                {
+                    push(&mut statements, &line[start..i]);
+                    start = i;
+                }
+                _ => {}
+            }
+            if matches!(word, "then" | "do" | "else" | "end" | "repeat") {
+                push(&mut statements, &line[start..word_end]);
+                start = word_end;
+                at_statement_start = true;
+                i = word_end;
+                continue;
+            }
+        }
+        at_statement_start = false;
+        i = word_end;
+    }
+    push(&mut statements, &line[start..]);
+    statements
+}
+
+/// Appends the output of statements split from one source line, from
+/// `start` on, to the entry before them, so the output keeps one entry per
+/// source line.
+fn merge_split_statements(out: &mut Vec<String>, start: Option<usize>) {
+    let Some(start) = start.filter(|start| *start > 0) else {
+        return;
+    };
+    let tail = out.split_off(start);
+    let merged = &mut out[start - 1];
+    for text in tail.iter().filter(|text| !text.is_empty()) {
+        merged.push(' ');
+        merged.push_str(text);
+    }
+}
+
 fn rewrite_lua_inline_function_literal(line: &str, line_no: usize) -> Result<String, ParseError> {
     let Some(function_index) = line.find("function") else {
         return Ok(line.to_string());
@@ -1447,7 +2035,19 @@ fn rewrite_lua_inline_function_literal(line: &str, line_no: usize) -> Result<Str
     if !after_keyword.starts_with('(') {
         return Ok(line.to_string());
     }
+    let (params, body) = parse_lua_function_literal_tail(after_keyword, line_no)?;
 
+    if params.is_empty() {
+        Ok(format!("{prefix}| | {body}"))
+    } else {
+        Ok(format!("{prefix}|{params}| {body}"))
+    }
+}
+
+fn parse_lua_function_literal_tail(
+    after_keyword: &str,
+    line_no: usize,
+) -> Result<(&str, &str), ParseError> {
     let mut depth = 0usize;
     let mut close_index = None;
     for (idx, ch) in after_keyword.char_indices() {
@@ -1520,11 +2120,7 @@ fn rewrite_lua_inline_function_literal(line: &str, line_no: usize) -> Result<Str
         });
     }
 
-    if params.is_empty() {
-        Ok(format!("{prefix}| | {body}"))
-    } else {
-        Ok(format!("{prefix}|{params}| {body}"))
-    }
+    Ok((params, body))
 }
 
 fn rewrite_lua_expr(
@@ -1533,12 +2129,56 @@ fn rewrite_lua_expr(
     lowering_context: &mut LuaLoweringContext,
     line_no: usize,
 ) -> Result<String, ParseError> {
-    let method_rewritten = rewrite_lua_method_calls(expr, lowering_context, line_no)?;
+    let (rewritten, _) =
+        rewrite_lua_expr_stages(expr, false, vm_namespace_aliases, lowering_context, line_no)?;
+    Ok(rewritten)
+}
+
+/// Rewrites a Lua expression list, whose last expression keeps all of its
+/// values when it is a multiple-results call, `...`, or `table.unpack`.
+fn rewrite_lua_expr_list(
+    items: &[String],
+    vm_namespace_aliases: &HashSet<String>,
+    lowering_context: &mut LuaLoweringContext,
+    line_no: usize,
+) -> Result<LuaValueList, ParseError> {
+    let mut values = Vec::with_capacity(items.len());
+    let mut tail = None;
+    for (index, item) in items.iter().enumerate() {
+        let (rewritten, expanded) = rewrite_lua_expr_stages(
+            item,
+            index + 1 == items.len(),
+            vm_namespace_aliases,
+            lowering_context,
+            line_no,
+        )?;
+        if expanded {
+            tail = Some(rewritten);
+        } else {
+            values.push(rewritten);
+        }
+    }
+    Ok(LuaValueList { values, tail })
+}
+
+fn rewrite_lua_expr_stages(
+    expr: &str,
+    expand: bool,
+    vm_namespace_aliases: &HashSet<String>,
+    lowering_context: &mut LuaLoweringContext,
+    line_no: usize,
+) -> Result<(String, bool), ParseError> {
+    let format_rewritten = rewrite_lua_string_format_calls(expr, line_no)?;
+    let method_rewritten = rewrite_lua_method_calls(&format_rewritten, lowering_context, line_no)?;
     let length_rewritten =
         rewrite_lua_length_operator(&method_rewritten, lowering_context, line_no)?;
-    Ok(rewrite_lua_expr_tokens(
-        &length_rewritten,
-        vm_namespace_aliases,
+    let (values_rewritten, expanded) =
+        lowering_context
+            .multi_values
+            .rewrite(&length_rewritten, expand, line_no)?;
+    Ok((
+        rewrite_lua_expr_tokens(&values_rewritten, vm_namespace_aliases),
+        expanded,
     ))
 }
 
@@ -1556,7 +2196,7 @@ fn rewrite_lua_length_operator(
     while i < bytes.len() {
         let b = bytes[i];
         if let Some(delim) = string_delim {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             if escaped {
                 escaped = false;
             } else if b == b'\\' {
@@ -1569,7 +2209,7 @@ fn rewrite_lua_length_operator(
         }
 
         if b == b'"' || b == b'\'' {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             string_delim = Some(b);
             escaped = false;
             i += 1;
@@ -1577,7 +2217,7 @@ fn rewrite_lua_length_operator(
         }
 
         if b != b'#' {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             i += 1;
             continue;
         }
@@ -1779,6 +2419,202 @@ fn parse_lua_string_end(input: &str, start: usize, line_no: usize) -> Result<usi
     })
 }
 
+fn rewrite_lua_string_format_calls(expr: &str, line_no: usize) -> Result<String, ParseError> {
+    const STRING_FORMAT: &str = "string.format";
+
+    let bytes = expr.as_bytes();
+    let mut out = String::with_capacity(expr.len());
+    let mut i = 0usize;
+    let mut string_delim: Option<u8> = None;
+    let mut escaped = false;
+
+    while i < bytes.len() {
+        let b = bytes[i];
+        if let Some(delim) = string_delim {
+            push_source_byte(&mut out, bytes, i);
+            if escaped {
+                escaped = false;
+            } else if b == b'\\' {
+                escaped = true;
+            } else if b == delim {
+                string_delim = None;
+            }
+            i += 1;
+            continue;
+        }
+
+        if b == b'"' || b == b'\'' {
+            push_source_byte(&mut out, bytes, i);
+            string_delim = Some(b);
+            escaped = false;
+            i += 1;
+            continue;
+        }
+
+        let at_boundary = i == 0 || !is_ident_continue(bytes[i - 1] as char);
+        if !at_boundary || !expr[i..].starts_with(STRING_FORMAT) {
+            push_source_byte(&mut out, bytes, i);
+            i += 1;
+            continue;
+        }
+        let open_index = skip_inline_whitespace(bytes, i + STRING_FORMAT.len());
+        if bytes.get(open_index) != Some(&b'(') {
+            push_source_byte(&mut out, bytes, i);
+            i += 1;
+            continue;
+        }
+
+        let (args_raw, next_index) = parse_balanced_call_args(expr, open_index, line_no)?;
+        let args = split_top_level_csv(&args_raw);
+        let Some(template) = args
+            .first()
+            .and_then(|arg| parse_lua_string_literal(arg.trim()))
+        else {
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line: line_no,
+                message: "lua 'string.format' requires a constant format string in this subset"
+                    .to_string(),
+            });
+        };
+        let template = lua_format_to_template(&template, line_no)?;
+        let rest = args[1..]
+            .iter()
+            .map(|arg| rewrite_lua_string_format_calls(arg.trim(), line_no))
+            .collect::<Result<Vec<_>, _>>()?;
+        out.push_str("format!(\"");
+        out.push_str(&template);
+        out.push('"');
+        for arg in rest {
+            out.push_str(", ");
+            out.push_str(&arg);
+        }
+        out.push(')');
+        i = next_index;
+    }
+
+    Ok(out)
+}
+
+// Returns the literal's contents as they should appear between double quotes.
+fn parse_lua_string_literal(literal: &str) -> Option<String> {
+    let delim = literal
+        .chars()
+        .next()
+        .filter(|ch| *ch == '"' || *ch == '\'')?;
+    let body = literal.strip_prefix(delim)?.strip_suffix(delim)?;
+    let mut out = String::with_capacity(body.len());
+    let mut escaped = false;
+    for ch in body.chars() {
+        if escaped {
+            if ch != '\'' {
+                out.push('\\');
+            }
+            out.push(ch);
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if ch == delim {
+            return None;
+        } else {
+            if ch == '"' {
+                out.push('\\');
+            }
+            out.push(ch);
+        }
+    }
+    (!escaped).then_some(out)
+}
+
+fn lua_format_to_template(format: &str, line_no: usize) -> Result<String, ParseError> {
+    let unsupported = |message: String| ParseError {
+        span: None,
+        code: None,
+        line: line_no,
+        message,
+    };
+    let mut out = String::with_capacity(format.len());
+    let mut chars = format.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' => out.push_str("{{"),
+            '}' => out.push_str("}}"),
+            '%' => {
+                let mut left_align = false;
+                let mut zero_pad = false;
+                while let Some(flag) = chars.next_if(|flag| matches!(flag, '-' | '0')) {
+                    left_align |= flag == '-';
+                    zero_pad |= flag == '0';
+                }
+                let mut width = String::new();
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    width.push(digit);
+                }
+                let mut precision = None;
+                if chars.next_if_eq(&'.').is_some() {
+                    let mut digits = String::new();
+                    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(digit);
+                    }
+                    precision = Some(if digits.is_empty() {
+                        "0".to_string()
+                    } else {
+                        digits
+                    });
+                }
+                let Some(conversion) = chars.next() else {
+                    return Err(unsupported(
+                        "lua 'string.format' string ends with an incomplete '%' conversion"
+                            .to_string(),
+                    ));
+                };
+                let (precision, debug) = match conversion {
+                    '%' => {
+                        out.push('%');
+                        continue;
+                    }
+                    'd' | 'i' | 's' | 'g' => (precision, false),
+                    'f' => (precision.or_else(|| Some("6".to_string())), false),
+                    'q' => (precision, true),
+                    other => {
+                        return Err(unsupported(format!(
+                            "lua 'string.format' conversion '%{other}' is not supported in this subset"
+                        )));
+                    }
+                };
+                let mut spec = String::new();
+                if !width.is_empty() {
+                    if left_align {
+                        spec.push('<');
+                    } else if zero_pad {
+                        spec.push('0');
+                    } else {
+                        spec.push('>');
+                    }
+                    spec.push_str(&width);
+                }
+                if let Some(precision) = precision {
+                    spec.push('.');
+                    spec.push_str(&precision);
+                }
+                if debug {
+                    spec.push('?');
+                }
+                if spec.is_empty() {
+                    out.push_str("{}");
+                } else {
+                    out.push_str("{:");
+                    out.push_str(&spec);
+                    out.push('}');
+                }
+            }
+            _ => out.push(ch),
+        }
+    }
+    Ok(out)
+}
+
 fn rewrite_lua_method_calls(
     expr: &str,
     lowering_context: &mut LuaLoweringContext,
@@ -1793,7 +2629,7 @@ fn rewrite_lua_method_calls(
     while i < bytes.len() {
         let b = bytes[i];
         if let Some(delim) = string_delim {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             if escaped {
                 escaped = false;
             } else if b == b'\\' {
@@ -1806,7 +2642,7 @@ fn rewrite_lua_method_calls(
         }
 
         if b == b'"' || b == b'\'' {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             string_delim = Some(b);
             escaped = false;
             i += 1;
@@ -1814,7 +2650,7 @@ fn rewrite_lua_method_calls(
         }
 
         if !is_ident_start(b as char) {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             i += 1;
             continue;
         }
@@ -1826,6 +2662,62 @@ fn rewrite_lua_method_calls(
         }
         let receiver = &expr[receiver_start..i];
         let mut cursor = skip_inline_whitespace(bytes, i);
+        if receiver == "string"
+            && let Some((name, open_index)) = match_lua_string_pattern_function(expr, cursor)
+        {
+            let (args_raw, next_index) = parse_balanced_call_args(expr, open_index, line_no)?;
+            let mut args = Vec::new();
+            for arg in split_top_level_csv(&args_raw) {
+                args.push(rewrite_lua_method_calls(
+                    arg.trim(),
+                    lowering_context,
+                    line_no,
+                )?);
+            }
+            out.push_str(&rewrite_lua_pattern_call(
+                name,
+                &args,
+                lowering_context,
+                line_no,
+            )?);
+            i = next_index;
+            continue;
+        }
+        let preceding = out.trim_end();
+        if preceding.ends_with(':') || (preceding.ends_with('.') && !preceding.ends_with("..")) {
+            out.push_str(receiver);
+            continue;
+        }
+        // Extend the receiver over a dotted field path such as `self.items`.
+        let mut path_end = i;
+        while path_end + 1 < bytes.len()
+            && bytes[path_end] == b'.'
+            && is_ident_start(bytes[path_end + 1] as char)
+        {
+            path_end += 2;
+            while path_end < bytes.len() && is_ident_continue(bytes[path_end] as char) {
+                path_end += 1;
+            }
+        }
+        let receiver = &expr[receiver_start..path_end];
+        i = path_end;
+        cursor = skip_inline_whitespace(bytes, i);
+        if cursor < bytes.len() && bytes[cursor] == b'(' {
+            let (args_raw, next_index) = parse_balanced_call_args(expr, cursor, line_no)?;
+            let mut args = Vec::new();
+            for arg in split_top_level_csv(&args_raw) {
+                args.push(rewrite_lua_method_calls(
+                    arg.trim(),
+                    lowering_context,
+                    line_no,
+                )?);
+            }
+            if let Some(call) = rewrite_lua_table_call(receiver, &args, lowering_context) {
+                out.push_str(&call);
+                i = next_index;
+                continue;
+            }
+        }
         if cursor >= bytes.len() || bytes[cursor] != b':' {
             out.push_str(receiver);
             continue;
@@ -1854,8 +2746,24 @@ fn rewrite_lua_method_calls(
         }
 
         let (args_raw, next_index) = parse_balanced_call_args(expr, cursor, line_no)?;
-        let rewritten =
-            rewrite_lua_method_invocation(receiver, method, &args_raw, lowering_context, line_no)?;
+        let rewritten = if lowering_context.table_functions.has_field(method) {
+            let mut args = Vec::new();
+            for arg in split_top_level_csv(&args_raw) {
+                args.push(rewrite_lua_method_calls(
+                    arg.trim(),
+                    lowering_context,
+                    line_no,
+                )?);
+            }
+            format!(
+                "{}[0]",
+                lowering_context
+                    .table_functions
+                    .send(receiver, method, &args)
+            )
+        } else {
+            rewrite_lua_method_invocation(receiver, method, &args_raw, lowering_context, line_no)?
+        };
         out.push_str(&rewritten);
         i = next_index;
     }
@@ -1863,6 +2771,47 @@ fn rewrite_lua_method_calls(
     Ok(out)
 }
 
+/// Lowers calls of Lua globals backed by the metatable builtins, calls
+/// through table-function fields, and `__call` on table values. Returns `None`
+/// for calls that lower as written.
+fn rewrite_lua_table_call(
+    callee: &str,
+    args: &[String],
+    lowering_context: &mut LuaLoweringContext,
+) -> Option<String> {
+    let table_functions = &mut lowering_context.table_functions;
+    let Some((table, field)) = callee.rsplit_once('.') else {
+        return match callee {
+            "setmetatable" | "getmetatable" | "rawget" | "rawset" | "rawequal" => {
+                Some(format!("lua::{callee}({})", args.join(", ")))
+            }
+            // The optional level argument only affects Lua's position prefix.
+            "error" => Some(format!(
+                "lua::error({})",
+                args.first().map_or("null", String::as_str)
+            )),
+            "tostring" if args.len() == 1 => {
+                Some(table_functions.tostring(&args[0], lowering_context.in_metamethod))
+            }
+            _ if table_functions.is_value_call(callee) => {
+                Some(table_functions.value_call(callee, args))
+            }
+            _ => None,
+        };
+    };
+    if let Some(call) = table_functions.static_call(table, field, args) {
+        return Some(format!("{call}[0]"));
+    }
+    let root = table.split('.').next().unwrap_or(table);
+    if LuaTableFunctions::is_library_table(root) || !table_functions.has_field(field) {
+        return None;
+    }
+    Some(format!(
+        "{}[0]",
+        table_functions.field_call(table, field, args)
+    ))
+}
+
 fn rewrite_lua_method_invocation(
     receiver: &str,
     method: &str,
@@ -1909,16 +2858,19 @@ fn rewrite_lua_method_invocation(
                 });
             }
         },
-        "find" | "match" | "gsub" => {
+        "format" => {
             return Err(ParseError {
                 span: None,
                 code: None,
                 line: line_no,
-                message: format!(
-                    "lua string method ':{method}' (Lua pattern API) is not supported in this subset yet"
-                ),
+                message: "lua string method ':format' is not supported; use string.format with a constant format string".to_string(),
             });
         }
+        "find" | "match" | "gsub" | "gmatch" => {
+            let mut call_args = vec![receiver.to_string()];
+            call_args.extend(args);
+            return rewrite_lua_pattern_call(method, &call_args, lowering_context, line_no);
+        }
         _ => {
             if args.is_empty() {
                 format!("{method}({receiver})")
@@ -1930,6 +2882,57 @@ fn rewrite_lua_method_invocation(
     Ok(rewritten)
 }
 
+/// Matches `.find(` / `.match(` / `.gsub(` / `.gmatch(` after `string`,
+/// returning the function name and the index of its `(`.
+fn match_lua_string_pattern_function(expr: &str, cursor: usize) -> Option<(&str, usize)> {
+    let rest = expr.get(cursor..)?.strip_prefix('.')?;
+    let name = ["find", "match", "gsub", "gmatch"]
+        .into_iter()
+        .find(|name| rest.starts_with(name))?;
+    let after_name = cursor + 1 + name.len();
+    let open_index = skip_inline_whitespace(expr.as_bytes(), after_name);
+    (expr.as_bytes().get(open_index) == Some(&b'(')).then_some((name, open_index))
+}
+
+/// Lowers `string.find` / `match` / `gsub` to the `lua::*` pattern builtins.
+/// Those return every Lua result in an array; in expression position only the
+/// first one is kept, as Lua does.
+fn rewrite_lua_pattern_call(
+    name: &str,
+    args: &[String],
+    lowering_context: &mut LuaLoweringContext,
+    line_no: usize,
+) -> Result<String, ParseError> {
+    let (required, optional) = match name {
+        "find" => (2, 2),
+        "match" => (2, 1),
+        "gsub" => (3, 1),
+        "gmatch" => {
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line: line_no,
+                message: "lua 'gmatch' is only supported as the iterator of a generic for loop in this subset".to_string(),
+            });
+        }
+        _ => unreachable!("not a lua pattern function"),
+    };
+    if args.len() < required || args.len() > required + optional {
+        return Err(ParseError {
+            span: None,
+            code: None,
+            line: line_no,
+            message: format!(
+                "lua 'string.{name}' expects {} to {} arguments",
+                required,
+                required + optional
+            ),
+        });
+    }
+    lowering_context.needs_pattern_helpers = true;
+    Ok(format!("__lua_first(lua::{name}({}))", args.join(", ")))
+}
+
 fn parse_balanced_call_args(
     input: &str,
     open_paren_index: usize,
@@ -2017,7 +3020,7 @@ fn rewrite_lua_expr_tokens(expr: &str, vm_namespace_aliases: &HashSet<String>) -
         let b = bytes[i];
         if let Some(delim) = string_delim {
             if escaped {
-                out.push(b as char);
+                push_source_byte(&mut out, bytes, i);
                 escaped = false;
             } else if b == b'\\' {
                 out.push('\\');
@@ -2028,7 +3031,7 @@ fn rewrite_lua_expr_tokens(expr: &str, vm_namespace_aliases: &HashSet<String>) -
             } else if delim == b'\'' && b == b'"' {
                 out.push_str("\\\"");
             } else {
-                out.push(b as char);
+                push_source_byte(&mut out, bytes, i);
             }
             i += 1;
             continue;
@@ -2187,19 +3190,15 @@ fn __lua_string_sub_range(value, start_raw, end_raw) {
     (value)[start:end_exclusive];
 }"#;
 
-const LUA_TABLE_LEN_HELPER: &str = r#"fn __lua_has_key(container, key) {
-    let available_keys = (container).keys;
-    let found = false;
-    let i = 0;
-    while i < (available_keys).length {
-        if (available_keys)[i] == key {
-            found = true;
-            i = (available_keys).length;
-        } else {
-            i = i + 1;
-        }
+const LUA_PATTERN_HELPERS: &str = r#"fn __lua_first(values) {
+    if values == null {
+        return null;
     }
-    found;
+    values[0];
+}"#;
+
+const LUA_TABLE_LEN_HELPER: &str = r#"fn __lua_has_key(container, key) {
+    map::has_key(container, key);
 }
 
 fn __lua_len(value) {
@@ -2217,11 +3216,24 @@ fn __lua_len(value) {
         out = (value).length;
     }
     out;
+}
+
+fn __lua_ipairs(value) {
+    if type(value) == "array" {
+        return value;
+    }
+    let sequence = [];
+    while __lua_has_key(value, (sequence).length) {
+        sequence[(sequence).length] = (value)[(sequence).length];
+    }
+    sequence;
 }"#;
 
 fn emit_lua_helpers(lowering_context: &LuaLoweringContext) -> Vec<String> {
     let mut helper_lines = Vec::new();
-    if lowering_context.needs_table_len_helper {
+    if lowering_context.needs_table_len_helper
+        || lowering_context.multi_values.needs_table_len_helper()
+    {
         helper_lines.extend(LUA_TABLE_LEN_HELPER.lines().map(str::to_string));
         helper_lines.push(String::new());
     }
@@ -2229,10 +3241,20 @@ fn emit_lua_helpers(lowering_context: &LuaLoweringContext) -> Vec<String> {
         helper_lines.extend(LUA_STRING_SUB_HELPERS.lines().map(str::to_string));
         helper_lines.push(String::new());
     }
+    if lowering_context.needs_pattern_helpers {
+        helper_lines.extend(LUA_PATTERN_HELPERS.lines().map(str::to_string));
+        helper_lines.push(String::new());
+    }
+    if lowering_context.needs_sort_by_helper {
+        helper_lines.extend(SORT_BY_HELPER.lines().map(str::to_string));
+        helper_lines.push(String::new());
+    }
+    helper_lines.extend(lowering_context.multi_values.emit_helpers());
+    helper_lines.extend(lowering_context.table_functions.emit_helpers());
     helper_lines
 }
 
-fn split_top_level_csv(input: &str) -> Vec<String> {
+pub(super) fn split_top_level_csv(input: &str) -> Vec<String> {
     let mut out = Vec::new();
     let mut current = String::new();
     let mut paren_depth = 0usize;
@@ -2240,6 +3262,9 @@ fn split_top_level_csv(input: &str) -> Vec<String> {
     let mut brace_depth = 0usize;
     let mut string_delim: Option<char> = None;
     let mut escaped = false;
+    // Function literals are rewritten to `|a, b| body` before values are
+    // split, so a list starting with `|` is a parameter list.
+    let mut in_closure_params = false;
 
     for ch in input.chars() {
         if let Some(delim) = string_delim {
@@ -2253,8 +3278,17 @@ fn split_top_level_csv(input: &str) -> Vec<String> {
             }
             continue;
         }
+        if in_closure_params {
+            in_closure_params = ch != '|';
+            current.push(ch);
+            continue;
+        }
 
         match ch {
+            '|' if current.trim().is_empty() => {
+                in_closure_params = true;
+                current.push(ch);
+            }
             '"' | '\'' => {
                 string_delim = Some(ch);
                 current.push(ch);
@@ -2335,7 +3369,7 @@ fn remove_lua_comments(source: &str) -> Result<String, ParseError> {
         }
 
         if let Some(delim) = string_delim {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             if escaped {
                 escaped = false;
             } else if b == b'\\' {
@@ -2362,7 +3396,7 @@ fn remove_lua_comments(source: &str) -> Result<String, ParseError> {
 
         if b == b'"' || b == b'\'' {
             string_delim = Some(b);
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             i += 1;
             continue;
         }
@@ -2370,7 +3404,7 @@ fn remove_lua_comments(source: &str) -> Result<String, ParseError> {
         if b == b'\n' {
             line += 1;
         }
-        out.push(b as char);
+        push_source_byte(&mut out, bytes, i);
         i += 1;
     }
 
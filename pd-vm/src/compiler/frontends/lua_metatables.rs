@@ -0,0 +1,656 @@
+//! Lua table functions and metatable dispatch.
+//!
+//! Functions stored in table fields (`function T.f`, `function T:m`, and
+//! `T.f = function`) lower to RustScript functions named `T__f`, and the field
+//! itself holds a `"function: T.f"` tag string. Functions are not VM values, so
+//! calls that go through a table field at runtime (methods found via
+//! `__index`, metamethod handlers) compare the tag against every table
+//! function of that name and call the match.
+//!
+//! A table function takes its own table as a leading implicit parameter and
+//! returns `[result, first argument]`. Method call statements write the second
+//! element back to the receiver, which is how `self` mutations survive value
+//! semantics.
+//!
+//! `setmetatable` keeps the metatable in the table's reserved slot, so chunks
+//! that call it are rewritten by `table_slot` before compilation.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use super::super::STDLIB_PRINT_NAME;
+use super::super::ir::{Expr, FrontendIr};
+use super::table_slot::IrRewriter;
+use super::{is_ident_continue, is_ident_start};
+use crate::builtins::BuiltinFunction;
+
+/// Lua library tables whose dotted calls are never table-function calls.
+const LUA_LIBRARY_TABLES: &[&str] = &["string", "table", "math", "os", "io", "utf8", "coroutine"];
+
+/// Metamethods dispatched by rewriting operators in the parsed IR, with the
+/// helper each one lowers to.
+const OPERATOR_METAMETHODS: &[(&str, &str)] = &[
+    ("__add", "__lua_mm_add"),
+    ("__sub", "__lua_mm_sub"),
+    ("__mul", "__lua_mm_mul"),
+    ("__div", "__lua_mm_div"),
+    ("__mod", "__lua_mm_mod"),
+    ("__unm", "__lua_mm_unm"),
+    ("__eq", "__lua_mm_eq"),
+    ("__lt", "__lua_mm_lt"),
+    ("__index", "__lua_mm_index"),
+    ("__newindex", "__lua_mm_newindex"),
+    ("__tostring", "__lua_print_value"),
+];
+
+pub(super) struct LuaTableFunction {
+    pub(super) table: String,
+    pub(super) field: String,
+    /// Declared parameters, with the implicit `self` first for `:` methods.
+    pub(super) params: Vec<String>,
+}
+
+impl LuaTableFunction {
+    pub(super) fn rust_name(&self) -> String {
+        format!("{}__{}", self.table, self.field)
+    }
+
+    pub(super) fn tag(&self) -> String {
+        format!("function: {}.{}", self.table, self.field)
+    }
+
+    /// Value returned alongside the result, written back by method calls.
+    pub(super) fn carrier(&self) -> &str {
+        self.params.first().map_or("null", String::as_str)
+    }
+
+    pub(super) fn signature(&self) -> String {
+        let mut params = vec![self.table.clone()];
+        params.extend(self.params.iter().cloned());
+        format!("{}({})", self.rust_name(), params.join(", "))
+    }
+
+    /// Calls the function with `owner` as its table, padding missing
+    /// arguments with `null` and dropping extra ones as Lua does.
+    fn call(&self, owner: &str, args: &[String]) -> String {
+        let mut call_args = vec![owner.to_string()];
+        for index in 0..self.params.len() {
+            call_args.push(
+                args.get(index)
+                    .cloned()
+                    .unwrap_or_else(|| "null".to_string()),
+            );
+        }
+        format!("{}({})", self.rust_name(), call_args.join(", "))
+    }
+}
+
+/// `function T.f(...)`, `function T:m(...)` or `T.f = function(...)`.
+pub(super) struct LuaTableFunctionHeader<'a> {
+    pub(super) table: &'a str,
+    pub(super) field: &'a str,
+    pub(super) params: Vec<String>,
+    /// Expression of a one-line `T.f = function(...) return <expr> end`.
+    pub(super) inline_return: Option<&'a str>,
+}
+
+pub(super) fn parse_table_function_header(line: &str) -> Option<LuaTableFunctionHeader<'_>> {
+    let line = line.trim().trim_end_matches(';').trim_end();
+    if let Some(rest) = line.strip_prefix("function ") {
+        let open = rest.find('(')?;
+        let name = rest[..open].trim();
+        let (table, field, method) = if let Some((table, field)) = name.split_once(':') {
+            (table, field, true)
+        } else {
+            let (table, field) = name.split_once('.')?;
+            (table, field, false)
+        };
+        if !is_lua_name(table) || !is_lua_name(field) {
+            return None;
+        }
+        let params_raw = rest[open + 1..].trim_end().strip_suffix(')')?;
+        let mut params = if method {
+            vec!["self".to_string()]
+        } else {
+            Vec::new()
+        };
+        params.extend(split_params(params_raw)?);
+        return Some(LuaTableFunctionHeader {
+            table,
+            field,
+            params,
+            inline_return: None,
+        });
+    }
+
+    let (target, value) = line.split_once('=')?;
+    let (table, field) = target.trim().split_once('.')?;
+    if !is_lua_name(table) || !is_lua_name(field) {
+        return None;
+    }
+    let literal = value.trim().strip_prefix("function")?.trim_start();
+    let literal = literal.strip_prefix('(')?;
+    let close = literal.find(')')?;
+    let params = split_params(&literal[..close])?;
+    let tail = literal[close + 1..].trim();
+    let inline_return = if tail.is_empty() {
+        None
+    } else {
+        let body = tail.strip_suffix("end")?.trim();
+        let expr = body.strip_prefix("return")?;
+        if !expr.starts_with(char::is_whitespace) {
+            return None;
+        }
+        Some(expr.trim().trim_end_matches(';').trim_end())
+    };
+    Some(LuaTableFunctionHeader {
+        table,
+        field,
+        params,
+        inline_return,
+    })
+}
+
+fn split_params(raw: &str) -> Option<Vec<String>> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Some(Vec::new());
+    }
+    raw.split(',')
+        .map(|param| {
+            let param = param.trim();
+            is_lua_name(param).then(|| param.to_string())
+        })
+        .collect()
+}
+
+pub(super) fn is_lua_name(input: &str) -> bool {
+    let mut chars = input.chars();
+    chars.next().is_some_and(is_ident_start) && chars.all(is_ident_continue)
+}
+
+/// Every table function of a chunk, collected before lowering so calls can
+/// dispatch to functions declared further down, plus the dispatch helpers the
+/// lowered calls asked for.
+#[derive(Default)]
+pub(super) struct LuaTableFunctions {
+    functions: Vec<LuaTableFunction>,
+    /// Locals bound to something other than a function; with a `__call`
+    /// metamethod in the chunk, calling one of them dispatches through it.
+    value_locals: HashSet<String>,
+    sends: BTreeSet<(String, usize)>,
+    field_calls: BTreeSet<(String, usize)>,
+    value_calls: BTreeSet<usize>,
+    needs_tostring: bool,
+    write_backs: usize,
+}
+
+impl LuaTableFunctions {
+    pub(super) fn scan<'a>(lines: impl Iterator<Item = &'a str>) -> Self {
+        let mut registry = Self::default();
+        let mut function_locals = HashSet::new();
+        for line in lines {
+            let line = line.trim();
+            if let Some(header) = parse_table_function_header(line) {
+                if registry.find(header.table, header.field).is_none() {
+                    registry.functions.push(LuaTableFunction {
+                        table: header.table.to_string(),
+                        field: header.field.to_string(),
+                        params: header.params,
+                    });
+                }
+                continue;
+            }
+            if let Some(rest) = line.strip_prefix("local function ") {
+                if let Some((name, _)) = rest.split_once('(') {
+                    function_locals.insert(name.trim().to_string());
+                }
+                continue;
+            }
+            if let Some(rest) = line.strip_prefix("local ")
+                && let Some((name, value)) = rest.split_once('=')
+                && is_lua_name(name.trim())
+                && !value.trim_start().starts_with('=')
+            {
+                if value.trim_start().starts_with("function") {
+                    function_locals.insert(name.trim().to_string());
+                } else {
+                    registry.value_locals.insert(name.trim().to_string());
+                }
+            }
+        }
+        registry
+            .value_locals
+            .retain(|name| !function_locals.contains(name));
+        registry
+    }
+
+    pub(super) fn find(&self, table: &str, field: &str) -> Option<&LuaTableFunction> {
+        self.functions
+            .iter()
+            .find(|function| function.table == table && function.field == field)
+    }
+
+    pub(super) fn has_field(&self, field: &str) -> bool {
+        self.functions
+            .iter()
+            .any(|function| function.field == field)
+    }
+
+    fn candidates<'a>(&'a self, field: &'a str) -> impl Iterator<Item = &'a LuaTableFunction> {
+        self.functions
+            .iter()
+            .filter(move |function| function.field == field)
+    }
+
+    pub(super) fn is_library_table(name: &str) -> bool {
+        LUA_LIBRARY_TABLES.contains(&name)
+    }
+
+    pub(super) fn is_value_call(&self, callee: &str) -> bool {
+        self.has_field("__call") && self.value_locals.contains(callee)
+    }
+
+    /// `T.f(args)` on a table known to declare `f`; yields the result pair.
+    pub(super) fn static_call(&self, table: &str, field: &str, args: &[String]) -> Option<String> {
+        self.find(table, field)
+            .map(|function| function.call(table, args))
+    }
+
+    /// `receiver:m(args)`; yields the result pair.
+    pub(super) fn send(&mut self, receiver: &str, method: &str, args: &[String]) -> String {
+        let mut call_args = vec![receiver.to_string()];
+        call_args.extend(args.iter().cloned());
+        if let Some(call) = self.static_call(receiver, method, &call_args) {
+            return call;
+        }
+        self.sends.insert((method.to_string(), args.len()));
+        format!(
+            "__lua_send_{method}_{}({})",
+            args.len(),
+            call_args.join(", ")
+        )
+    }
+
+    /// `table.f(args)` where `table` is only known at runtime; yields the
+    /// result pair.
+    pub(super) fn field_call(&mut self, table: &str, field: &str, args: &[String]) -> String {
+        self.field_calls.insert((field.to_string(), args.len()));
+        let mut call_args = vec![table.to_string()];
+        call_args.extend(args.iter().cloned());
+        format!(
+            "__lua_call_{field}_{}({})",
+            args.len(),
+            call_args.join(", ")
+        )
+    }
+
+    /// `value(args)` on a table with a `__call` metamethod; yields the result.
+    pub(super) fn value_call(&mut self, callee: &str, args: &[String]) -> String {
+        self.value_calls.insert(args.len());
+        let mut call_args = vec![callee.to_string()];
+        call_args.extend(args.iter().cloned());
+        format!("__lua_value_call_{}({})", args.len(), call_args.join(", "))
+    }
+
+    /// `tostring(value)`; `raw` skips `__tostring`.
+    pub(super) fn tostring(&mut self, value: &str, raw: bool) -> String {
+        self.needs_tostring = true;
+        if raw {
+            format!("__lua_tostring_raw({value})")
+        } else {
+            format!("__lua_tostring({value})")
+        }
+    }
+
+    /// Fresh local holding a method call's result pair during write-back.
+    pub(super) fn next_write_back(&mut self) -> String {
+        let name = format!("__lua_pair_{}", self.write_backs);
+        self.write_backs += 1;
+        name
+    }
+
+    /// Prototypes for every table function followed by the dispatch helpers,
+    /// so calls resolve regardless of declaration order.
+    pub(super) fn emit_helpers(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        if self.functions.is_empty() && !self.needs_tostring {
+            return out;
+        }
+        for function in &self.functions {
+            out.push(format!("fn {};", function.signature()));
+        }
+        if self.has_field("__eq") || self.has_field("__lt") {
+            out.push("fn __lua_truthy(value) {".to_string());
+            out.push("    value != null && value != false;".to_string());
+            out.push("}".to_string());
+        }
+        for (method, argc) in &self.sends {
+            self.emit_field_dispatch(&mut out, "send", method, *argc, "method", true);
+        }
+        for (field, argc) in &self.field_calls {
+            self.emit_field_dispatch(&mut out, "call", field, *argc, "field", false);
+        }
+        for argc in &self.value_calls {
+            let mut params = vec!["callee".to_string()];
+            params.extend((1..=*argc).map(|index| format!("arg{index}")));
+            out.push(format!(
+                "fn __lua_value_call_{argc}({}) {{",
+                params.join(", ")
+            ));
+            self.emit_metamethod_dispatch(&mut out, "callee, null", "__call", &params);
+            out.push("    lua::error(\"attempt to call a table value\");".to_string());
+            out.push("}".to_string());
+        }
+        for (name, op) in [
+            ("add", "+"),
+            ("sub", "-"),
+            ("mul", "*"),
+            ("div", "/"),
+            ("mod", "%"),
+        ] {
+            let metamethod = format!("__{name}");
+            if !self.has_field(&metamethod) {
+                continue;
+            }
+            let params = ["lhs".to_string(), "rhs".to_string()];
+            out.push(format!("fn __lua_mm_{name}(lhs, rhs) {{"));
+            self.emit_metamethod_dispatch(&mut out, "lhs, rhs", &metamethod, &params);
+            out.push(format!("    lhs {op} rhs;"));
+            out.push("}".to_string());
+        }
+        if self.has_field("__unm") {
+            let params = ["operand".to_string(), "operand".to_string()];
+            out.push("fn __lua_mm_unm(operand) {".to_string());
+            self.emit_metamethod_dispatch(&mut out, "operand, null", "__unm", &params);
+            out.push("    -operand;".to_string());
+            out.push("}".to_string());
+        }
+        if self.has_field("__eq") {
+            let params = ["lhs".to_string(), "rhs".to_string()];
+            out.push("fn __lua_mm_eq(lhs, rhs) {".to_string());
+            out.push("    if lhs == rhs {".to_string());
+            out.push("        return true;".to_string());
+            out.push("    }".to_string());
+            out.push("    if type(lhs) == \"map\" && type(rhs) == \"map\" {".to_string());
+            self.emit_truthy_dispatch(&mut out, "lhs, rhs", "__eq", &params);
+            out.push("    }".to_string());
+            out.push("    false;".to_string());
+            out.push("}".to_string());
+        }
+        if self.has_field("__lt") {
+            let params = ["lhs".to_string(), "rhs".to_string()];
+            out.push("fn __lua_mm_lt(lhs, rhs) {".to_string());
+            self.emit_truthy_dispatch(&mut out, "lhs, rhs", "__lt", &params);
+            out.push("    lhs < rhs;".to_string());
+            out.push("}".to_string());
+        }
+        if self.has_field("__index") {
+            let params = ["table".to_string(), "key".to_string()];
+            out.push("fn __lua_mm_index(table, key) {".to_string());
+            out.push("    if lua::index_owner(table, key) == null {".to_string());
+            self.emit_metamethod_dispatch(&mut out, "table, null", "__index", &params);
+            out.push("    }".to_string());
+            out.push("    table[key];".to_string());
+            out.push("}".to_string());
+        }
+        if self.has_field("__newindex") {
+            let params = ["table".to_string(), "key".to_string(), "value".to_string()];
+            out.push("fn __lua_mm_newindex(table, key, value) {".to_string());
+            out.push("    if lua::rawget(table, key) == null {".to_string());
+            out.push(
+                "        let metatable = lua::metamethod(table, null, \"__newindex\");".to_string(),
+            );
+            out.push("        if metatable != null {".to_string());
+            out.push(
+                "            let handler = lua::rawget(metatable, \"__newindex\");".to_string(),
+            );
+            for function in self.candidates("__newindex") {
+                out.push(format!(
+                    "            if handler == \"{}\" {{",
+                    function.tag()
+                ));
+                out.push(format!(
+                    "                return {}[1];",
+                    function.call("metatable", &params)
+                ));
+                out.push("            }".to_string());
+            }
+            out.push("        }".to_string());
+            out.push("    }".to_string());
+            out.push("    lua::newindex(table, key, value);".to_string());
+            out.push("}".to_string());
+        }
+        if self.needs_tostring || self.has_field("__tostring") {
+            out.push("fn __lua_print_value(value) {".to_string());
+            self.emit_metamethod_dispatch(
+                &mut out,
+                "value, null",
+                "__tostring",
+                &["value".to_string()],
+            );
+            out.push("    value;".to_string());
+            out.push("}".to_string());
+            out.push("fn __lua_tostring_raw(value) {".to_string());
+            out.push("    if value == null {".to_string());
+            out.push("        return \"nil\";".to_string());
+            out.push("    }".to_string());
+            out.push("    format!(\"{}\", value);".to_string());
+            out.push("}".to_string());
+            out.push("fn __lua_tostring(value) {".to_string());
+            out.push("    __lua_tostring_raw(__lua_print_value(value));".to_string());
+            out.push("}".to_string());
+        }
+        out
+    }
+
+    /// Emits `__lua_{prefix}_{field}_{argc}(receiver, args)`, calling the
+    /// table function stored under `field` in the receiver or its `__index`
+    /// chain. Methods also receive the receiver as `self`.
+    fn emit_field_dispatch(
+        &self,
+        out: &mut Vec<String>,
+        prefix: &str,
+        field: &str,
+        argc: usize,
+        kind: &str,
+        pass_receiver: bool,
+    ) {
+        let args = (1..=argc)
+            .map(|index| format!("arg{index}"))
+            .collect::<Vec<_>>();
+        let mut params = vec!["receiver".to_string()];
+        params.extend(args.iter().cloned());
+        let call_args = if pass_receiver { &params } else { &args };
+        out.push(format!(
+            "fn __lua_{prefix}_{field}_{argc}({}) {{",
+            params.join(", ")
+        ));
+        out.push(format!(
+            "    let owner = lua::index_owner(receiver, \"{field}\");"
+        ));
+        out.push("    if owner != null {".to_string());
+        out.push(format!(
+            "        let handler = lua::rawget(owner, \"{field}\");"
+        ));
+        for function in self.candidates(field) {
+            out.push(format!("        if handler == \"{}\" {{", function.tag()));
+            out.push(format!(
+                "            return {};",
+                function.call("owner", call_args)
+            ));
+            out.push("        }".to_string());
+        }
+        out.push("    }".to_string());
+        out.push(format!(
+            "    lua::error(\"attempt to call {kind} '{field}' (not a function)\");"
+        ));
+        out.push("}".to_string());
+    }
+
+    /// Returns the handler's result when one of `operands` has a metatable
+    /// defining `metamethod`.
+    fn emit_metamethod_dispatch(
+        &self,
+        out: &mut Vec<String>,
+        operands: &str,
+        metamethod: &str,
+        args: &[String],
+    ) {
+        self.emit_dispatch(out, operands, metamethod, args, |call| format!("{call}[0]"));
+    }
+
+    /// Like `emit_metamethod_dispatch`, converting the result to a boolean.
+    fn emit_truthy_dispatch(
+        &self,
+        out: &mut Vec<String>,
+        operands: &str,
+        metamethod: &str,
+        args: &[String],
+    ) {
+        self.emit_dispatch(out, operands, metamethod, args, |call| {
+            format!("__lua_truthy({call}[0])")
+        });
+    }
+
+    fn emit_dispatch(
+        &self,
+        out: &mut Vec<String>,
+        operands: &str,
+        metamethod: &str,
+        args: &[String],
+        wrap: impl Fn(String) -> String,
+    ) {
+        out.push(format!(
+            "    let metatable = lua::metamethod({operands}, \"{metamethod}\");"
+        ));
+        out.push("    if metatable != null {".to_string());
+        out.push(format!(
+            "        let handler = lua::rawget(metatable, \"{metamethod}\");"
+        ));
+        for function in self.candidates(metamethod) {
+            out.push(format!("        if handler == \"{}\" {{", function.tag()));
+            out.push(format!(
+                "            return {};",
+                wrap(function.call("metatable", args))
+            ));
+            out.push("        }".to_string());
+        }
+        out.push("    }".to_string());
+    }
+
+    /// Routes operators, field reads and writes, and `print` arguments through
+    /// the metamethod helpers for every metamethod the chunk defines. Helper
+    /// and metamethod bodies keep raw operations, so handlers can use the
+    /// operators they implement on plain values.
+    pub(super) fn rewrite_ir(&self, ir: &mut FrontendIr) {
+        let function_index = |name: &str| {
+            ir.functions
+                .iter()
+                .find(|decl| decl.name == name)
+                .map(|decl| decl.index)
+        };
+        let mut helpers = HashMap::new();
+        for (metamethod, helper) in OPERATOR_METAMETHODS {
+            if self.has_field(metamethod)
+                && let Some(index) = function_index(helper)
+            {
+                helpers.insert(*metamethod, index);
+            }
+        }
+        if helpers.is_empty() {
+            return;
+        }
+        let rewriter = MetamethodRewriter {
+            helpers,
+            print: function_index(STDLIB_PRINT_NAME),
+        };
+        let raw_functions = ir
+            .functions
+            .iter()
+            .filter(|decl| {
+                decl.name.starts_with("__lua_")
+                    || self.functions.iter().any(|function| {
+                        function.field.starts_with("__") && function.rust_name() == decl.name
+                    })
+            })
+            .map(|decl| decl.index)
+            .collect::<HashSet<_>>();
+        for stmt in &mut ir.stmts {
+            rewriter.stmt(stmt);
+        }
+        for (index, function_impl) in &mut ir.function_impls {
+            if !raw_functions.contains(index) {
+                rewriter.function(function_impl);
+            }
+        }
+    }
+}
+
+struct MetamethodRewriter {
+    helpers: HashMap<&'static str, u16>,
+    print: Option<u16>,
+}
+
+impl IrRewriter for MetamethodRewriter {
+    fn replacement(&self, expr: &mut Expr) -> Option<Expr> {
+        let helper = |metamethod: &str| self.helpers.get(metamethod).copied();
+        let take = |expr: &mut Expr| std::mem::replace(expr, Expr::Null);
+        let replaced = match expr {
+            // String concatenation (`..`) keeps its compile-time number formatting.
+            Expr::Add(lhs, rhs) if is_string_concat(lhs) || is_string_concat(rhs) => return None,
+            Expr::Add(lhs, rhs) => Expr::Call(helper("__add")?, vec![take(lhs), take(rhs)]),
+            Expr::Sub(lhs, rhs) => Expr::Call(helper("__sub")?, vec![take(lhs), take(rhs)]),
+            Expr::Mul(lhs, rhs) => Expr::Call(helper("__mul")?, vec![take(lhs), take(rhs)]),
+            Expr::Div(lhs, rhs) => Expr::Call(helper("__div")?, vec![take(lhs), take(rhs)]),
+            Expr::Mod(lhs, rhs) => Expr::Call(helper("__mod")?, vec![take(lhs), take(rhs)]),
+            Expr::Neg(inner) if matches!(inner.as_ref(), Expr::Int(_) | Expr::Float(_)) => {
+                return None;
+            }
+            Expr::Neg(inner) => Expr::Call(helper("__unm")?, vec![take(inner)]),
+            Expr::Eq(lhs, rhs) => Expr::Call(helper("__eq")?, vec![take(lhs), take(rhs)]),
+            Expr::Lt(lhs, rhs) => Expr::Call(helper("__lt")?, vec![take(lhs), take(rhs)]),
+            Expr::Gt(lhs, rhs) => Expr::Call(helper("__lt")?, vec![take(rhs), take(lhs)]),
+            Expr::Call(index, args)
+                if *index == BuiltinFunction::Get.call_index() && args.len() == 2 =>
+            {
+                Expr::Call(helper("__index")?, std::mem::take(args))
+            }
+            // Table constructors assign raw, as in Lua.
+            Expr::Call(index, args)
+                if *index == BuiltinFunction::Set.call_index()
+                    && args.len() == 3
+                    && !is_table_constructor(&args[0]) =>
+            {
+                Expr::Call(helper("__newindex")?, std::mem::take(args))
+            }
+            Expr::Call(index, args) if Some(*index) == self.print => {
+                let print_value = helper("__tostring")?;
+                let args = std::mem::take(args)
+                    .into_iter()
+                    .map(|arg| Expr::Call(print_value, vec![arg]))
+                    .collect();
+                Expr::Call(*index, args)
+            }
+            _ => return None,
+        };
+        Some(replaced)
+    }
+}
+
+fn is_table_constructor(expr: &Expr) -> bool {
+    match expr {
+        Expr::Call(index, _) if *index == BuiltinFunction::MapNew.call_index() => true,
+        Expr::Call(index, args) if *index == BuiltinFunction::Set.call_index() => {
+            args.first().is_some_and(is_table_constructor)
+        }
+        _ => false,
+    }
+}
+
+fn is_string_concat(expr: &Expr) -> bool {
+    match expr {
+        Expr::String(_) => true,
+        Expr::Add(lhs, rhs) => is_string_concat(lhs) || is_string_concat(rhs),
+        _ => false,
+    }
+}
@@ -0,0 +1,653 @@
+//! Lua multiple results and varargs.
+//!
+//! A function with a `return` that can produce several values (a value list,
+//! `...`, or a call that itself produces several values) returns all of them
+//! in an array. Call sites keep what Lua would keep: the first value inside an
+//! expression, or every value when the call ends an argument list, a table
+//! constructor, a `return` list, or the right-hand side of a multiple
+//! assignment. A trailing `...` parameter lowers to an array of the extra
+//! arguments, which call sites of the function build.
+
+use std::collections::{BTreeSet, HashMap};
+
+use super::super::ParseError;
+use super::lua::split_top_level_csv;
+use super::lua_metatables::{is_lua_name, parse_table_function_header};
+use super::{is_ident_continue, is_ident_start};
+
+/// Parameter holding the extra arguments of a vararg function.
+const LUA_VARARGS: &str = "__lua_varargs";
+
+const LUA_MULTI_VALUE_HELPERS: &str = r#"fn __lua_drop(values, count) {
+    if count > (values).length {
+        return [];
+    }
+    (values)[count:];
+}
+
+fn __lua_select(index, values) {
+    let start = index - 1;
+    if index < 0 {
+        start = (values).length + index;
+    }
+    if start < 0 {
+        lua::error("bad argument #1 to 'select' (index out of range)");
+    }
+    __lua_drop(values, start);
+}
+
+fn __lua_unpack(values, first, last) {
+    let stop = last;
+    if stop == null {
+        stop = __lua_len(values) - 1;
+    }
+    let out = [];
+    let index = first;
+    while index < stop + 1 {
+        out[(out).length] = lua::rawget(values, index);
+        index = index + 1;
+    }
+    out;
+}"#;
+
+/// `function f(...)` or `local function f(...)` with a plain name.
+pub(super) struct LuaFunctionHeader<'a> {
+    pub(super) name: &'a str,
+    /// Named parameters, without a trailing `...`.
+    pub(super) params: Vec<&'a str>,
+    pub(super) vararg: bool,
+}
+
+impl LuaFunctionHeader<'_> {
+    /// RustScript parameter list, with the varargs array last.
+    pub(super) fn rust_params(&self) -> String {
+        let mut params = self.params.clone();
+        if self.vararg {
+            params.push(LUA_VARARGS);
+        }
+        params.join(", ")
+    }
+}
+
+pub(super) fn parse_function_header(line: &str) -> Option<LuaFunctionHeader<'_>> {
+    let line = line.trim();
+    let rest = line
+        .strip_prefix("local function ")
+        .or_else(|| line.strip_prefix("function "))?;
+    let open = rest.find('(')?;
+    let name = rest[..open].trim();
+    if !is_lua_name(name) {
+        return None;
+    }
+    let params_raw = rest[open + 1..].trim_end().strip_suffix(')')?.trim();
+    let mut params = if params_raw.is_empty() {
+        Vec::new()
+    } else {
+        params_raw.split(',').map(str::trim).collect::<Vec<_>>()
+    };
+    let vararg = params.last() == Some(&"...");
+    if vararg {
+        params.pop();
+    }
+    if !params.iter().all(|param| is_lua_name(param)) {
+        return None;
+    }
+    Some(LuaFunctionHeader {
+        name,
+        params,
+        vararg,
+    })
+}
+
+struct LuaFunctionSignature {
+    params: Vec<String>,
+    vararg: bool,
+    /// Whether the function returns an array of all of its results.
+    returns_values: bool,
+}
+
+impl LuaFunctionSignature {
+    /// Adjusts call arguments to the parameters: missing ones are `nil`,
+    /// extra ones are dropped or collected into the varargs array.
+    fn arguments(&self, args: &[String]) -> Vec<String> {
+        let mut out = (0..self.params.len())
+            .map(|index| {
+                args.get(index)
+                    .cloned()
+                    .unwrap_or_else(|| "null".to_string())
+            })
+            .collect::<Vec<_>>();
+        if self.vararg {
+            let extra = args.get(self.params.len()..).unwrap_or_default();
+            out.push(format!("[{}]", extra.join(", ")));
+        }
+        out
+    }
+}
+
+/// An expression list whose last expression may expand to several values.
+pub(super) struct LuaValueList {
+    pub(super) values: Vec<String>,
+    /// Array of the values of a trailing call, `...`, or `table.unpack`.
+    pub(super) tail: Option<String>,
+}
+
+impl LuaValueList {
+    /// Array of every value in the list.
+    pub(super) fn array(&self) -> String {
+        let head = format!("[{}]", self.values.join(", "));
+        match &self.tail {
+            None => head,
+            Some(tail) if self.values.is_empty() => tail.clone(),
+            Some(tail) => format!("({head} + {tail})"),
+        }
+    }
+
+    /// The single value of a list that does not expand.
+    pub(super) fn single(&self) -> Option<&str> {
+        match (self.values.as_slice(), &self.tail) {
+            ([value], None) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+pub(super) struct LuaMultiValues {
+    functions: HashMap<String, LuaFunctionSignature>,
+    /// Calls whose last argument expands, as `(function, leading arguments)`.
+    spreads: BTreeSet<(String, usize)>,
+    /// Whether the function being lowered takes `...`; `None` in the chunk.
+    vararg_scope: Option<bool>,
+    temps: usize,
+    needs_helpers: bool,
+}
+
+impl LuaMultiValues {
+    /// Registers a function before its body is lowered. `body` holds the
+    /// source lines after the header and decides whether the function returns
+    /// all of its results; returns that decision.
+    pub(super) fn enter_function(&mut self, header: &LuaFunctionHeader, body: &[&str]) -> bool {
+        let returns_values = self.body_returns_values(body);
+        self.functions.insert(
+            header.name.to_string(),
+            LuaFunctionSignature {
+                params: header
+                    .params
+                    .iter()
+                    .map(|param| param.to_string())
+                    .collect(),
+                vararg: header.vararg,
+                returns_values,
+            },
+        );
+        self.vararg_scope = Some(header.vararg);
+        returns_values
+    }
+
+    /// Table functions take no `...`.
+    pub(super) fn enter_table_function(&mut self) {
+        self.vararg_scope = Some(false);
+    }
+
+    pub(super) fn exit_function(&mut self) {
+        self.vararg_scope = None;
+    }
+
+    /// Fresh local holding the values of a multiple assignment.
+    pub(super) fn next_temp(&mut self) -> String {
+        self.temps += 1;
+        format!("__lua_values_{}", self.temps)
+    }
+
+    /// `table.unpack` measures tables with the table length helper.
+    pub(super) fn needs_table_len_helper(&self) -> bool {
+        self.needs_helpers || !self.spreads.is_empty()
+    }
+
+    fn body_returns_values(&self, body: &[&str]) -> bool {
+        // One entry per open block; `true` marks a nested function body,
+        // whose returns belong to that function.
+        let mut blocks = Vec::<bool>::new();
+        for line in body {
+            let line = line.trim().trim_end_matches(';').trim();
+            if line == "end" || line.starts_with("until ") {
+                if blocks.pop().is_none() {
+                    return false;
+                }
+                continue;
+            }
+            if opens_function(line) {
+                blocks.push(true);
+                continue;
+            }
+            if opens_block(line) {
+                blocks.push(false);
+                continue;
+            }
+            if blocks.contains(&true) {
+                continue;
+            }
+            if let Some(list) = line.strip_prefix("return ") {
+                let items = split_top_level_csv(list);
+                if items.len() > 1 || items.last().is_some_and(|item| self.expands(item)) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Whether `item` produces every value it has when it ends a list.
+    fn expands(&self, item: &str) -> bool {
+        let item = item.trim();
+        if item == "..." {
+            return true;
+        }
+        let Some((callee, args)) = split_call(item) else {
+            return false;
+        };
+        match callee {
+            "select" => split_top_level_csv(args)
+                .first()
+                .is_some_and(|selector| !is_count_selector(selector)),
+            "table.unpack" => true,
+            name => self
+                .functions
+                .get(name)
+                .is_some_and(|function| function.returns_values),
+        }
+    }
+
+    /// Rewrites calls of Lua-declared functions, `select`, `table.unpack`,
+    /// `...`, and table constructors in `expr`. With `expand`, an `expr` that
+    /// produces several values becomes an array of all of them, and `true` is
+    /// returned alongside.
+    pub(super) fn rewrite(
+        &mut self,
+        expr: &str,
+        expand: bool,
+        line_no: usize,
+    ) -> Result<(String, bool), ParseError> {
+        if expand && self.expands(expr) {
+            return Ok((self.rewrite_values(expr.trim(), line_no)?, true));
+        }
+        Ok((self.rewrite_single(expr, line_no)?, false))
+    }
+
+    /// Array of every value of an expression `item` that expands.
+    fn rewrite_values(&mut self, item: &str, line_no: usize) -> Result<String, ParseError> {
+        if item == "..." {
+            return self.varargs(line_no);
+        }
+        let Some((callee, args)) = split_call(item) else {
+            unreachable!("only calls and '...' expand");
+        };
+        let args = split_top_level_csv(args);
+        match callee {
+            "select" => self.select(&args, line_no),
+            "table.unpack" => self.unpack(&args, line_no),
+            name => self.call(name, &args, line_no),
+        }
+    }
+
+    fn rewrite_single(&mut self, expr: &str, line_no: usize) -> Result<String, ParseError> {
+        let bytes = expr.as_bytes();
+        let mut out = String::with_capacity(expr.len());
+        let mut i = 0usize;
+        while i < bytes.len() {
+            let b = bytes[i];
+            if b == b'"' || b == b'\'' {
+                let end = string_end(bytes, i);
+                out.push_str(&expr[i..end]);
+                i = end;
+                continue;
+            }
+            if expr[i..].starts_with("...") {
+                out.push_str(&format!("lua::rawget({}, 0)", self.varargs(line_no)?));
+                i += 3;
+                continue;
+            }
+            if expr[i..].starts_with("..") {
+                out.push_str("..");
+                i += 2;
+                continue;
+            }
+            if b == b'{'
+                && let Some(end) = balanced_end(expr, i, b'{', b'}')
+            {
+                out.push_str(&self.rewrite_table(&expr[i + 1..end - 1], line_no)?);
+                i = end;
+                continue;
+            }
+            if !is_ident_start(b as char) {
+                push_char(&mut out, expr, &mut i);
+                continue;
+            }
+
+            let start = i;
+            while i < bytes.len() && is_ident_continue(bytes[i] as char) {
+                i += 1;
+            }
+            let member = start > 0
+                && (bytes[start - 1] == b':'
+                    || (bytes[start - 1] == b'.' && (start < 2 || bytes[start - 2] != b'.')));
+            let mut callee_end = i;
+            if &expr[start..i] == "table"
+                && let Some(after) = expr[i..].strip_prefix(".unpack")
+                && !after.starts_with(|ch: char| is_ident_continue(ch))
+            {
+                callee_end = i + ".unpack".len();
+            }
+            let callee = &expr[start..callee_end];
+            let open = skip_spaces(bytes, callee_end);
+            let rewritten =
+                matches!(callee, "select" | "table.unpack") || self.functions.contains_key(callee);
+            if member || !rewritten || bytes.get(open) != Some(&b'(') {
+                out.push_str(&expr[start..i]);
+                continue;
+            }
+            let Some(end) = balanced_end(expr, open, b'(', b')') else {
+                out.push_str(&expr[start..i]);
+                continue;
+            };
+            let args = split_top_level_csv(&expr[open + 1..end - 1]);
+            let call = match callee {
+                "select"
+                    if args
+                        .first()
+                        .is_some_and(|selector| is_count_selector(selector)) =>
+                {
+                    format!("({}).length", self.value_list(&args[1..], line_no)?)
+                }
+                "select" => format!("lua::rawget({}, 0)", self.select(&args, line_no)?),
+                "table.unpack" => format!("lua::rawget({}, 0)", self.unpack(&args, line_no)?),
+                name => {
+                    let call = self.call(name, &args, line_no)?;
+                    if self.functions[name].returns_values {
+                        format!("lua::rawget({call}, 0)")
+                    } else {
+                        call
+                    }
+                }
+            };
+            out.push_str(&call);
+            i = end;
+        }
+        Ok(out)
+    }
+
+    /// A table constructor whose last positional field expands becomes an
+    /// array of all of its values.
+    fn rewrite_table(&mut self, inner: &str, line_no: usize) -> Result<String, ParseError> {
+        let items = split_top_level_csv(inner);
+        if !items.is_empty() && items.iter().all(|item| is_positional_field(item)) {
+            return self.value_list(&items, line_no);
+        }
+        let mut fields = Vec::with_capacity(items.len());
+        for item in &items {
+            fields.push(self.rewrite_single(item, line_no)?);
+        }
+        Ok(format!("{{{}}}", fields.join(", ")))
+    }
+
+    fn expression_list(
+        &mut self,
+        items: &[String],
+        line_no: usize,
+    ) -> Result<LuaValueList, ParseError> {
+        let mut values = Vec::with_capacity(items.len());
+        let mut tail = None;
+        for (index, item) in items.iter().enumerate() {
+            let (rewritten, expanded) = self.rewrite(item, index + 1 == items.len(), line_no)?;
+            if expanded {
+                tail = Some(rewritten);
+            } else {
+                values.push(rewritten);
+            }
+        }
+        Ok(LuaValueList { values, tail })
+    }
+
+    fn value_list(&mut self, items: &[String], line_no: usize) -> Result<String, ParseError> {
+        Ok(self.expression_list(items, line_no)?.array())
+    }
+
+    fn call(&mut self, name: &str, args: &[String], line_no: usize) -> Result<String, ParseError> {
+        let list = self.expression_list(args, line_no)?;
+        if let Some(tail) = list.tail {
+            let count = list.values.len();
+            self.spreads.insert((name.to_string(), count));
+            let mut call_args = list.values;
+            call_args.push(tail);
+            return Ok(format!(
+                "__lua_spread_{name}_{count}({})",
+                call_args.join(", ")
+            ));
+        }
+        let args = self.functions[name].arguments(&list.values);
+        Ok(format!("{name}({})", args.join(", ")))
+    }
+
+    fn select(&mut self, args: &[String], line_no: usize) -> Result<String, ParseError> {
+        let Some((index, rest)) = args.split_first() else {
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line: line_no,
+                message: "lua 'select' expects an index or '#'".to_string(),
+            });
+        };
+        self.needs_helpers = true;
+        let index = self.rewrite_single(index, line_no)?;
+        let values = self.value_list(rest, line_no)?;
+        Ok(format!("__lua_select({index}, {values})"))
+    }
+
+    fn unpack(&mut self, args: &[String], line_no: usize) -> Result<String, ParseError> {
+        if !(1..=3).contains(&args.len()) {
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line: line_no,
+                message: "lua 'table.unpack' expects arguments (t [, i [, j]])".to_string(),
+            });
+        }
+        self.needs_helpers = true;
+        let mut call_args = Vec::with_capacity(3);
+        for arg in args {
+            call_args.push(self.rewrite_single(arg, line_no)?);
+        }
+        if call_args.len() < 2 {
+            call_args.push("0".to_string());
+        }
+        if call_args.len() < 3 {
+            call_args.push("null".to_string());
+        }
+        Ok(format!("__lua_unpack({})", call_args.join(", ")))
+    }
+
+    fn varargs(&self, line_no: usize) -> Result<String, ParseError> {
+        match self.vararg_scope {
+            Some(true) => Ok(LUA_VARARGS.to_string()),
+            // The chunk runs without arguments.
+            None => Ok("[]".to_string()),
+            Some(false) => Err(ParseError {
+                span: None,
+                code: None,
+                line: line_no,
+                message: "cannot use '...' outside a vararg function".to_string(),
+            }),
+        }
+    }
+
+    /// Helpers for `select` and `table.unpack`, and the adapters that spread
+    /// a trailing value list over a function's parameters. Functions called
+    /// through an adapter are declared up front.
+    pub(super) fn emit_helpers(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        if !self.needs_helpers && self.spreads.is_empty() {
+            return out;
+        }
+        out.extend(LUA_MULTI_VALUE_HELPERS.lines().map(str::to_string));
+        let mut declared = BTreeSet::new();
+        for (name, _) in &self.spreads {
+            if declared.insert(name) {
+                let function = &self.functions[name];
+                let mut params = function.params.clone();
+                if function.vararg {
+                    params.push(LUA_VARARGS.to_string());
+                }
+                out.push(format!("fn {name}({});", params.join(", ")));
+            }
+        }
+        for (name, count) in &self.spreads {
+            let function = &self.functions[name];
+            let mut params = (1..=*count)
+                .map(|index| format!("arg{index}"))
+                .collect::<Vec<_>>();
+            let mut args = (0..function.params.len())
+                .map(|index| {
+                    params
+                        .get(index)
+                        .cloned()
+                        .unwrap_or_else(|| format!("lua::rawget(values, {})", index - count))
+                })
+                .collect::<Vec<_>>();
+            if function.vararg {
+                let skipped = function.params.len().saturating_sub(*count);
+                let rest = if skipped == 0 {
+                    "values".to_string()
+                } else {
+                    format!("__lua_drop(values, {skipped})")
+                };
+                let extra = params.get(function.params.len()..).unwrap_or_default();
+                args.push(if extra.is_empty() {
+                    rest
+                } else {
+                    format!("[{}] + {rest}", extra.join(", "))
+                });
+            }
+            params.push("values".to_string());
+            out.push(format!(
+                "fn __lua_spread_{name}_{count}({}) {{",
+                params.join(", ")
+            ));
+            out.push(format!("    {name}({});", args.join(", ")));
+            out.push("}".to_string());
+        }
+        out.push(String::new());
+        out
+    }
+}
+
+fn opens_function(line: &str) -> bool {
+    if line.starts_with("function ") || line.starts_with("local function ") {
+        return true;
+    }
+    parse_table_function_header(line).is_some_and(|header| header.inline_return.is_none())
+}
+
+fn opens_block(line: &str) -> bool {
+    (line.starts_with("if ") && line.ends_with(" then"))
+        || ((line.starts_with("for ") || line.starts_with("while ")) && line.ends_with(" do"))
+        || line == "do"
+        || line == "repeat"
+}
+
+fn is_count_selector(arg: &str) -> bool {
+    matches!(arg.trim(), "'#'" | "\"#\"")
+}
+
+/// Splits `callee(args)` spanning all of `item`.
+fn split_call(item: &str) -> Option<(&str, &str)> {
+    let open = item.find('(')?;
+    let callee = item[..open].trim();
+    if callee != "table.unpack" && !is_lua_name(callee) {
+        return None;
+    }
+    let end = balanced_end(item, open, b'(', b')')?;
+    (end == item.len()).then(|| (callee, &item[open + 1..end - 1]))
+}
+
+/// A field without a `name =` or `[key] =` key.
+fn is_positional_field(item: &str) -> bool {
+    if item.starts_with('[') {
+        return false;
+    }
+    let bytes = item.as_bytes();
+    let mut depth = 0usize;
+    let mut i = 0usize;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' | b'\'' => {
+                i = string_end(bytes, i);
+                continue;
+            }
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => depth = depth.saturating_sub(1),
+            b'=' if depth == 0 => {
+                let before = i.checked_sub(1).map(|index| bytes[index]);
+                let after = bytes.get(i + 1).copied();
+                if !matches!(before, Some(b'=' | b'~' | b'<' | b'>' | b'!')) && after != Some(b'=')
+                {
+                    return false;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Index just past the delimiter closing the one at `open`.
+fn balanced_end(input: &str, open: usize, open_byte: u8, close_byte: u8) -> Option<usize> {
+    let bytes = input.as_bytes();
+    let mut depth = 0usize;
+    let mut i = open;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b == b'"' || b == b'\'' {
+            i = string_end(bytes, i);
+            continue;
+        }
+        if b == open_byte {
+            depth += 1;
+        } else if b == close_byte {
+            depth = depth.checked_sub(1)?;
+            if depth == 0 {
+                return Some(i + 1);
+            }
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Index just past the string literal starting at `start`.
+fn string_end(bytes: &[u8], start: usize) -> usize {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b if b == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+fn skip_spaces(bytes: &[u8], mut index: usize) -> usize {
+    while bytes.get(index).is_some_and(|b| *b == b' ' || *b == b'\t') {
+        index += 1;
+    }
+    index
+}
+
+fn push_char(out: &mut String, expr: &str, index: &mut usize) {
+    let ch = expr[*index..].chars().next().unwrap_or_default();
+    out.push(ch);
+    *index += ch.len_utf8().max(1);
+}
@@ -1,11 +1,23 @@
 mod javascript;
+mod javascript_classes;
+mod javascript_lexer;
+mod javascript_parser;
 mod lua;
+mod lua_metatables;
+mod lua_multi_values;
 mod rustscript;
 mod scheme;
+mod scheme_macros;
+mod scheme_tail_calls;
+mod table_slot;
 
 use crate::compiler::source_map::{LoweredSource, SourceMap};
 
-use super::{ParseError, SourceFlavor, ir::FrontendIr, parser::Parser};
+use super::{
+    ParseError, SourceFlavor,
+    ir::FrontendIr,
+    parser::{Parser, Token},
+};
 
 trait FrontendCompiler {
     fn lower_to_ir(&self, source: &str) -> Result<FrontendIr, ParseError>;
@@ -22,10 +34,54 @@ pub(super) fn parse_source(source: &str, flavor: SourceFlavor) -> Result<Fronten
         SourceFlavor::JavaScript => &JavaScriptCompiler,
         SourceFlavor::Lua => &LuaCompiler,
         SourceFlavor::Scheme => &SchemeCompiler,
+        SourceFlavor::Assembly => {
+            return Err(ParseError::new(
+                "assembly is assembled as written and has no frontend",
+            ));
+        }
     };
     frontend.lower_to_ir(source)
 }
 
+pub(super) const SORT_BY_HELPER_NAME: &str = "__frontend_sort_by";
+
+// Closures only exist at compile time, so comparator sorts (`arr.sort(cmp)`,
+// `table.sort(t, less)`, `(sort list less?)`) cannot call into the native
+// `array::sort` builtin. Frontends prepend this declaration whenever they lower
+// one, and the loader links in the stable merge sort `sort_by` from
+// `std::collections` as its body.
+pub(super) const SORT_BY_HELPER: &str = "fn __frontend_sort_by(values, less);";
+
+/// Namespaces whose members are VM builtins, such as `io::open` and
+/// `map::has_key`.
+pub(super) fn is_builtin_namespace(namespace: &str) -> bool {
+    matches!(
+        namespace,
+        "io" | "re" | "rand" | "uuid" | "array" | "map" | "lua"
+    )
+}
+
+/// Appends the source byte at `index` to `out` for byte-oriented rewriters.
+/// Those only interpret ASCII, so a non-ASCII character is copied whole at its
+/// leading byte and its continuation bytes are skipped.
+pub(super) fn push_source_byte(out: &mut String, bytes: &[u8], index: usize) {
+    let b = bytes[index];
+    if b.is_ascii() {
+        out.push(b as char);
+        return;
+    }
+    if b & 0xC0 == 0x80 {
+        return;
+    }
+    let len = match b {
+        0xF0.. => 4,
+        0xE0.. => 3,
+        _ => 2,
+    };
+    let end = (index + len).min(bytes.len());
+    out.push_str(&String::from_utf8_lossy(&bytes[index..end]));
+}
+
 pub(super) fn is_ident_start(ch: char) -> bool {
     ch.is_ascii_alphabetic() || ch == '_'
 }
@@ -43,8 +99,7 @@ impl FrontendCompiler for RustScriptCompiler {
 
 impl FrontendCompiler for JavaScriptCompiler {
     fn lower_to_ir(&self, source: &str) -> Result<FrontendIr, ParseError> {
-        let lowered = javascript::lower(source)?;
-        parse_lowered_with_mapping(source, lowered, false, true)
+        javascript::lower_to_ir(source)
     }
 }
 
@@ -66,19 +121,31 @@ fn parse_with_parser(
     allow_implicit_externs: bool,
     allow_implicit_semicolons: bool,
 ) -> Result<FrontendIr, ParseError> {
-    let mut parser = Parser::new(
+    let parser = Parser::new(
         source,
         source_id,
         allow_implicit_externs,
         allow_implicit_semicolons,
     )?;
+    finish_parse(parser)
+}
+
+/// Builds IR from a token stream a frontend lowered its own syntax tree into.
+/// Statements are terminated explicitly, so implicit semicolons stay off.
+fn parse_token_stream(tokens: Vec<Token>) -> Result<FrontendIr, ParseError> {
+    finish_parse(Parser::from_tokens(tokens, false, false))
+}
+
+fn finish_parse(mut parser: Parser) -> Result<FrontendIr, ParseError> {
     let stmts = parser.parse_program()?;
     Ok(FrontendIr {
         stmts,
         locals: parser.local_count(),
         local_bindings: parser.local_bindings(),
+        scoped_bindings: parser.scoped_bindings(),
         functions: parser.function_decls(),
         function_impls: parser.function_impls(),
+        warnings: parser.warnings(),
     })
 }
 
@@ -98,7 +165,10 @@ fn parse_lowered_with_mapping(
         allow_implicit_externs,
         allow_implicit_semicolons,
     ) {
-        Ok(ir) => Ok(ir),
+        Ok(mut ir) => {
+            ir.remap_lines(|line| lowered.mapping.original_line(line));
+            Ok(ir)
+        }
         Err(mut err) => {
             err = err.with_line_span_from_source(&source_map, lowered_source_id);
             if let Some(span) = err.span
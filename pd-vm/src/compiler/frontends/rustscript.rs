@@ -1,5 +1,5 @@
 use super::super::ParseError;
-use super::{is_ident_continue, is_ident_start};
+use super::{is_ident_continue, is_ident_start, push_source_byte};
 use crate::compiler::source_map::LoweredSource;
 
 pub(super) fn lower(source: &str) -> Result<LoweredSource, ParseError> {
@@ -21,7 +21,7 @@ fn rewrite_rss_print_macro(source: &str) -> String {
         let b = bytes[i];
 
         if in_block_comment {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             if b == b'*' && i + 1 < bytes.len() && bytes[i + 1] == b'/' {
                 out.push('/');
                 i += 2;
@@ -33,7 +33,7 @@ fn rewrite_rss_print_macro(source: &str) -> String {
         }
 
         if in_line_comment {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             if b == b'\n' {
                 in_line_comment = false;
             }
@@ -42,7 +42,7 @@ fn rewrite_rss_print_macro(source: &str) -> String {
         }
 
         if in_string {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             if escaped {
                 escaped = false;
             } else if b == b'\\' {
@@ -98,7 +98,7 @@ fn rewrite_rss_print_macro(source: &str) -> String {
             }
         }
 
-        out.push(b as char);
+        push_source_byte(&mut out, bytes, i);
         i += 1;
     }
 
@@ -118,7 +118,7 @@ fn rewrite_rss_aliases(source: &str) -> String {
         let b = bytes[i];
 
         if in_block_comment {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             if b == b'*' && i + 1 < bytes.len() && bytes[i + 1] == b'/' {
                 out.push('/');
                 i += 2;
@@ -130,7 +130,7 @@ fn rewrite_rss_aliases(source: &str) -> String {
         }
 
         if in_line_comment {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             if b == b'\n' {
                 in_line_comment = false;
             }
@@ -139,7 +139,7 @@ fn rewrite_rss_aliases(source: &str) -> String {
         }
 
         if in_string {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             if escaped {
                 escaped = false;
             } else if b == b'\\' {
@@ -175,7 +175,7 @@ fn rewrite_rss_aliases(source: &str) -> String {
         }
 
         if !is_ident_start(b as char) {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             i += 1;
             continue;
         }
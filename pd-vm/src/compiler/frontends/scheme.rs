@@ -3,12 +3,16 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 
 use super::super::ParseError;
 use super::super::ir::{Expr, FrontendIr, Stmt};
-use super::{is_ident_continue, is_ident_start};
+use super::scheme_macros::expand_program;
+use super::scheme_tail_calls::{TailGroups, TailProcedure, lower_loop, lower_loop_stmt};
+use super::{
+    SORT_BY_HELPER, SORT_BY_HELPER_NAME, is_builtin_namespace, is_ident_continue, is_ident_start,
+};
 use crate::compiler::source_map::{LineSpanMapping, LoweredSource};
 
 static GENSYM_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
-fn gensym(prefix: &str) -> String {
+pub(super) fn gensym(prefix: &str) -> String {
     let id = GENSYM_COUNTER
         .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
             current.checked_add(1)
@@ -38,8 +42,7 @@ pub(super) fn lower_to_ir(source: &str) -> Result<FrontendIr, ParseError> {
 }
 
 fn try_lower_direct_subset_to_ir(source: &str) -> Result<Option<FrontendIr>, ParseError> {
-    let mut parser = SchemeParser::new(source)?;
-    let forms = parser.parse_program()?;
+    let forms = parse_and_expand(source)?;
 
     let mut builder = SchemeDirectIrBuilder::new();
     let mut stmts = Vec::<Stmt>::new();
@@ -99,8 +102,10 @@ impl SchemeDirectIrBuilder {
             stmts,
             locals: self.next_local as usize,
             local_bindings,
+            scoped_bindings: Vec::new(),
             functions: Vec::new(),
             function_impls: HashMap::new(),
+            warnings: Vec::new(),
         }
     }
 
@@ -459,19 +464,43 @@ where
     lower_scheme_direct_binary(args, builder, build)
 }
 
-pub(super) fn lower(source: &str) -> Result<LoweredSource, ParseError> {
+fn parse_and_expand(source: &str) -> Result<Vec<SchemeForm>, ParseError> {
     let mut parser = SchemeParser::new(source)?;
-    let forms = parser.parse_program()?;
+    expand_program(parser.parse_program()?)
+}
+
+pub(super) fn lower(source: &str) -> Result<LoweredSource, ParseError> {
+    let forms = parse_and_expand(source)?;
 
+    let tail_groups = TailGroups::find(
+        forms
+            .iter()
+            .filter_map(TailProcedure::from_define)
+            .collect(),
+    );
     let mut out = Vec::new();
     let mut line_map = Vec::new();
     for form in &forms {
         let before = out.len();
-        lower_stmt(form, 0, &mut out)?;
+        if let Some(binding) = tail_groups.lower_binding(form)? {
+            push_line(&mut out, 0, &binding);
+        } else {
+            lower_stmt(form, 0, &mut out)?;
+        }
         let added = out.len().saturating_sub(before);
         line_map.extend(std::iter::repeat_n(form.line, added));
     }
 
+    if out.iter().any(|line| line.contains(SORT_BY_HELPER_NAME)) {
+        let helper_lines = SORT_BY_HELPER
+            .lines()
+            .map(str::to_string)
+            .chain(std::iter::once(String::new()))
+            .collect::<Vec<_>>();
+        line_map.splice(0..0, std::iter::repeat_n(1, helper_lines.len()));
+        out.splice(0..0, helper_lines);
+    }
+
     if line_map.is_empty() {
         line_map.push(1);
     }
@@ -484,20 +513,20 @@ pub(super) fn lower(source: &str) -> Result<LoweredSource, ParseError> {
 }
 
 #[derive(Clone, Debug)]
-struct SchemeForm {
-    line: usize,
-    node: SchemeNode,
+pub(super) struct SchemeForm {
+    pub(super) line: usize,
+    pub(super) node: SchemeNode,
 }
 
 impl SchemeForm {
-    fn as_symbol(&self) -> Option<&str> {
+    pub(super) fn as_symbol(&self) -> Option<&str> {
         match &self.node {
             SchemeNode::Symbol(value) => Some(value),
             _ => None,
         }
     }
 
-    fn as_list(&self) -> Option<&[SchemeForm]> {
+    pub(super) fn as_list(&self) -> Option<&[SchemeForm]> {
         match &self.node {
             SchemeNode::List(values) => Some(values),
             _ => None,
@@ -506,7 +535,7 @@ impl SchemeForm {
 }
 
 #[derive(Clone, Debug)]
-enum SchemeNode {
+pub(super) enum SchemeNode {
     Int(i64),
     Float(f64),
     Bool(bool),
@@ -942,7 +971,11 @@ impl SchemeParser {
     }
 }
 
-fn lower_stmt(form: &SchemeForm, indent: usize, out: &mut Vec<String>) -> Result<(), ParseError> {
+pub(super) fn lower_stmt(
+    form: &SchemeForm,
+    indent: usize,
+    out: &mut Vec<String>,
+) -> Result<(), ParseError> {
     if let Some(items) = form.as_list()
         && let Some(head) = items.first().and_then(|item| item.as_symbol())
     {
@@ -965,10 +998,22 @@ fn lower_stmt(form: &SchemeForm, indent: usize, out: &mut Vec<String>) -> Result
                 return lower_index_set_stmt(head, args, form.line, indent, out);
             }
             "begin" => return lower_begin_stmt(args, indent, out),
+            "let" | "let*" if args.len() >= 2 && is_plain_bindings(&args[0]) => {
+                return lower_let_stmt(args, head == "let*", indent, out);
+            }
+            "let" if args.len() >= 3 && args[0].as_symbol().is_some() => {
+                let name = args[0].as_symbol().unwrap_or_default();
+                let stmt = lower_named_let(name, &args[1], &args[2..], form.line, true)?;
+                push_line(out, indent, &stmt);
+                return Ok(());
+            }
             "declare" => return lower_declare_stmt(args, form.line, indent, out),
             "display" | "write" => return lower_display_stmt(args, form.line, indent, out),
             "newline" => return lower_newline_stmt(args, form.line, indent, out),
             "for-each" => return lower_for_each_stmt(args, form.line, indent, out),
+            "hash-table-walk" => {
+                return lower_hash_table_walk_stmt(args, form.line, indent, out);
+            }
             _ => {}
         }
     }
@@ -1383,28 +1428,102 @@ fn lower_for_each_stmt(
             message: "for-each expects (for-each proc list)".to_string(),
         });
     }
-    let func = lower_expr(&args[0])?;
     let list = lower_expr(&args[1])?;
-    let idx = gensym("fe_i");
-    let vec = gensym("fe_v");
-    let callable = if func.trim_start().starts_with('|') {
-        let f = gensym("fe_f");
-        push_line(out, indent, &format!("let {f} = {func};"));
-        f
-    } else {
-        func
-    };
-    push_line(out, indent, &format!("let {vec} = {list};"));
-    push_line(
-        out,
-        indent,
-        &format!("for (let {idx} = 0; {idx} < ({vec}).length; {idx} = {idx} + 1) {{"),
-    );
-    push_line(out, indent + 1, &format!("{callable}(({vec})[{idx}]);"));
+    if let Some((params, body)) = literal_lambda(&args[0], 1)? {
+        push_line(out, indent, &format!("for {} in {list} {{", params[0]));
+        for stmt in body {
+            lower_stmt(stmt, indent + 1, out)?;
+        }
+        push_line(out, indent, "}");
+        return Ok(());
+    }
+    let callable = lower_iteration_callable(&args[0], "fe_f", indent, out)?;
+    let item = gensym("fe_item");
+    push_line(out, indent, &format!("for {item} in {list} {{"));
+    push_line(out, indent + 1, &format!("{callable}({item});"));
+    push_line(out, indent, "}");
+    Ok(())
+}
+
+fn lower_hash_table_walk_stmt(
+    args: &[SchemeForm],
+    line: usize,
+    indent: usize,
+    out: &mut Vec<String>,
+) -> Result<(), ParseError> {
+    if args.len() != 2 {
+        return Err(ParseError {
+            span: None,
+            code: None,
+            line,
+            message: "hash-table-walk expects (hash-table-walk table proc)".to_string(),
+        });
+    }
+    let table = lower_expr(&args[0])?;
+    if let Some((params, body)) = literal_lambda(&args[1], 2)? {
+        push_line(
+            out,
+            indent,
+            &format!("for ({}, {}) in {table} {{", params[0], params[1]),
+        );
+        for stmt in body {
+            lower_stmt(stmt, indent + 1, out)?;
+        }
+        push_line(out, indent, "}");
+        return Ok(());
+    }
+    let callable = lower_iteration_callable(&args[1], "htw_f", indent, out)?;
+    let key = gensym("htw_key");
+    let value = gensym("htw_value");
+    push_line(out, indent, &format!("for ({key}, {value}) in {table} {{"));
+    push_line(out, indent + 1, &format!("{callable}({key}, {value});"));
     push_line(out, indent, "}");
     Ok(())
 }
 
+/// Parameter names and body forms of a literal lambda.
+type LambdaParts<'a> = (Vec<String>, &'a [SchemeForm]);
+
+/// Parameters and body of `form` when it is a literal lambda taking `arity`
+/// arguments. An iteration form inlines such a lambda as its loop body, so
+/// the body runs as statements and can `set!` the bindings around it.
+fn literal_lambda(form: &SchemeForm, arity: usize) -> Result<Option<LambdaParts<'_>>, ParseError> {
+    let Some(items) = form.as_list() else {
+        return Ok(None);
+    };
+    if items.first().and_then(|item| item.as_symbol()) != Some("lambda") || items.len() < 3 {
+        return Ok(None);
+    }
+    let Some(params_list) = items[1].as_list().filter(|params| params.len() == arity) else {
+        return Ok(None);
+    };
+    let mut params = Vec::new();
+    for param in params_list {
+        let Some(raw) = param.as_symbol() else {
+            return Ok(None);
+        };
+        params.push(normalize_identifier(raw, param.line, "lambda parameter")?);
+    }
+    Ok(Some((params, &items[2..])))
+}
+
+/// Lowers the procedure argument of an iteration form, binding lambdas to a
+/// local so the loop body can call them by name.
+fn lower_iteration_callable(
+    proc_form: &SchemeForm,
+    prefix: &str,
+    indent: usize,
+    out: &mut Vec<String>,
+) -> Result<String, ParseError> {
+    let func = lower_expr(proc_form)?;
+    if !func.trim_start().starts_with('|') {
+        return Ok(func);
+    }
+    let name = gensym(prefix);
+    push_line(out, indent, &format!("let {name} = {func};"));
+    Ok(name)
+}
+
 /// Helper: produce an or-chain of boolean text expressions using nested if-expressions.
 /// No `||` in the target language, so we use `if a => { true } else if b => { true } else => { false }`.
 fn lower_or_chain_text(conditions: &[String]) -> String {
@@ -1693,6 +1812,50 @@ fn lower_begin_stmt(
     Ok(())
 }
 
+/// `let` and `let*` whose value is unused lower to a block, so their body may
+/// hold statement-only forms such as `set!` and `display`.
+fn lower_let_stmt(
+    args: &[SchemeForm],
+    sequential: bool,
+    indent: usize,
+    out: &mut Vec<String>,
+) -> Result<(), ParseError> {
+    let bindings = args[0].as_list().unwrap_or_default();
+    push_line(out, indent, "if true {");
+    let mut deferred = Vec::new();
+    for binding in bindings.iter().filter_map(SchemeForm::as_list) {
+        let name = binding[0].as_symbol().unwrap_or_default();
+        let name = normalize_identifier(name, binding[0].line, "let binding")?;
+        let value = lower_expr(&binding[1])?;
+        if sequential || bindings.len() == 1 {
+            push_line(out, indent + 1, &format!("let {name} = {value};"));
+        } else {
+            let temp = gensym("let");
+            push_line(out, indent + 1, &format!("let {temp} = {value};"));
+            deferred.push((name, temp));
+        }
+    }
+    for (name, temp) in deferred {
+        push_line(out, indent + 1, &format!("let {name} = {temp};"));
+    }
+    for stmt in &args[1..] {
+        lower_stmt(stmt, indent + 1, out)?;
+    }
+    push_line(out, indent, "}");
+    Ok(())
+}
+
+/// Whether `form` is a list of `(name value)` bindings.
+pub(super) fn is_plain_bindings(form: &SchemeForm) -> bool {
+    form.as_list().is_some_and(|bindings| {
+        bindings.iter().all(|binding| {
+            binding
+                .as_list()
+                .is_some_and(|pair| pair.len() == 2 && pair[0].as_symbol().is_some())
+        })
+    })
+}
+
 fn lower_declare_stmt(
     args: &[SchemeForm],
     line: usize,
@@ -1788,6 +1951,10 @@ fn lower_import_require_stmt(
                         message: format!("{head} import requires module path and bindings"),
                     });
                 }
+                if clause[1].as_list().is_some() {
+                    // Library names like `(std strings)` are loaded as modules.
+                    continue;
+                }
                 let Some(module_spec) = clause[1].as_symbol().or_else(|| {
                     if let SchemeNode::String(spec) = &clause[1].node {
                         Some(spec.as_str())
@@ -1920,7 +2087,7 @@ fn lower_branch_body(
     lower_stmt(form, indent, out)
 }
 
-fn lower_expr(form: &SchemeForm) -> Result<String, ParseError> {
+pub(super) fn lower_expr(form: &SchemeForm) -> Result<String, ParseError> {
     match &form.node {
         SchemeNode::Int(value) => Ok(value.to_string()),
         SchemeNode::Float(value) => {
@@ -2362,6 +2529,22 @@ fn lower_list_expr(items: &[SchemeForm], line: usize) -> Result<String, ParseErr
             Ok(wrap_let_expr(&v, &list, &body))
         }
 
+        "list-tail" => {
+            if args.len() != 2 {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line,
+                    message: "list-tail expects (list-tail list k)".to_string(),
+                });
+            }
+            let list = lower_expr(&args[0])?;
+            let k = lower_expr(&args[1])?;
+            Ok(format!("({list})[({k}):]"))
+        }
+        "sort" => lower_sort_expr(args, line),
+        "format" => lower_format_expr(args, line),
+
         // Higher-order
         "map" => lower_map_expr(args, line),
         "filter" => lower_filter_expr(args, line),
@@ -2582,7 +2765,7 @@ fn lower_list_expr(items: &[SchemeForm], line: usize) -> Result<String, ParseErr
         // Statement-only forms
         "while" | "do" | "for" | "define" | "set!" | "declare" | "break" | "continue" | "begin"
         | "vector-set!" | "hash-set!" | "when" | "unless" | "cond" | "case" | "display"
-        | "write" | "newline" | "for-each" => Err(ParseError {
+        | "write" | "newline" | "for-each" | "hash-table-walk" => Err(ParseError {
             span: None,
             code: None,
             line,
@@ -2615,6 +2798,11 @@ fn lower_list_expr(items: &[SchemeForm], line: usize) -> Result<String, ParseErr
                     });
                 }
                 format!("vm::{}", segments.join("::"))
+            } else if let Some((namespace, member)) = head.split_once("::")
+                && is_builtin_namespace(namespace)
+                && is_valid_member_ident(member)
+            {
+                format!("{namespace}::{member}")
             } else {
                 let callee_head = if let Some((_, member)) = head.split_once('.') {
                     member
@@ -2659,6 +2847,24 @@ fn is_forbidden_scheme_builtin_name(name: &str) -> bool {
             | "re_replace"
             | "re_split"
             | "re_captures"
+            | "rand_int"
+            | "rand_float"
+            | "rand_choice"
+            | "rand_shuffle"
+            | "uuid_v4"
+            | "array_sort"
+            | "array_sort_by_keys"
+            | "array_binary_search"
+            | "array_index_of"
+            | "array_contains"
+            | "array_dedup"
+            | "array_remove"
+            | "array_insert"
+            | "array_pop"
+            | "map_remove"
+            | "map_has_key"
+            | "map_values"
+            | "map_entries"
     )
 }
 
@@ -2675,6 +2881,24 @@ fn scheme_builtin_syntax_hint(name: &str) -> &'static str {
         "re_is_match" | "re_find" | "re_replace" | "re_split" | "re_captures" => {
             "use re namespace syntax (for example re::match with optional flags arg)"
         }
+        "rand_int" | "rand_float" | "rand_choice" | "rand_shuffle" | "uuid_v4" => {
+            "use rand/uuid namespace syntax (for example rand::int or uuid::v4)"
+        }
+        "array_sort"
+        | "array_sort_by_keys"
+        | "array_binary_search"
+        | "array_index_of"
+        | "array_contains"
+        | "array_dedup"
+        | "array_remove"
+        | "array_insert"
+        | "array_pop"
+        | "map_remove"
+        | "map_has_key"
+        | "map_values"
+        | "map_entries" => {
+            "use (sort ...), (list-tail ...), or array/map namespace syntax (for example array::sort)"
+        }
         _ => "use Scheme frontend forms instead of VM builtin helpers",
     }
 }
@@ -2842,7 +3066,7 @@ fn lower_remainder_expr(args: &[SchemeForm], line: usize) -> Result<String, Pars
     Ok(format!("(({a}) % ({b}))"))
 }
 
-fn lower_and_expr(args: &[SchemeForm]) -> Result<String, ParseError> {
+pub(super) fn lower_and_expr(args: &[SchemeForm]) -> Result<String, ParseError> {
     if args.is_empty() {
         return Ok("true".to_string());
     }
@@ -2855,7 +3079,7 @@ fn lower_and_expr(args: &[SchemeForm]) -> Result<String, ParseError> {
     Ok(format!("if {first} => {{ {rest} }} else => {{ false }}"))
 }
 
-fn lower_or_expr(args: &[SchemeForm]) -> Result<String, ParseError> {
+pub(super) fn lower_or_expr(args: &[SchemeForm]) -> Result<String, ParseError> {
     if args.is_empty() {
         return Ok("false".to_string());
     }
@@ -2920,7 +3144,7 @@ fn lower_let_expr(
                 message: "named let expects name, bindings, and body".to_string(),
             });
         }
-        return lower_named_let_expr(name_sym, &args[1], &args[2..], line);
+        return lower_named_let(name_sym, &args[1], &args[2..], line, false);
     }
 
     let bindings = args[0].as_list().ok_or(ParseError {
@@ -2937,9 +3161,23 @@ fn lower_let_expr(
     let mut stmts = Vec::new();
 
     if letrec {
-        // letrec: lower recursive lambdas to function declarations.
+        // letrec: lower lambdas to function declarations, and recursive ones
+        // to tail-call loops.
+        let tail_groups = TailGroups::find(
+            bindings
+                .iter()
+                .filter_map(|binding| match binding.as_list()? {
+                    [name, value] => TailProcedure::from_lambda(binding, name.as_symbol()?, value),
+                    _ => None,
+                })
+                .collect(),
+        );
         let mut deferred = Vec::<(String, String)>::new();
         for binding in bindings {
+            if let Some(stmt) = tail_groups.lower_binding(binding)? {
+                stmts.push(stmt);
+                continue;
+            }
             let pair = binding.as_list().ok_or(ParseError {
                 span: None,
                 code: None,
@@ -3034,11 +3272,14 @@ fn lower_let_expr(
     Ok(wrap_statement_sequence(stmts, body))
 }
 
-fn lower_named_let_expr(
+/// Lowers `(let name (bindings...) body...)`, to a block statement when
+/// `statement` is set, since an expression cannot stand as one.
+fn lower_named_let(
     name: &str,
     bindings_form: &SchemeForm,
     body: &[SchemeForm],
     line: usize,
+    statement: bool,
 ) -> Result<String, ParseError> {
     let bindings = bindings_form.as_list().ok_or(ParseError {
         span: None,
@@ -3070,21 +3311,28 @@ fn lower_named_let_expr(
             line: pair[0].line,
             message: "named let binding name must be a symbol".to_string(),
         })?;
-        params.push(normalize_identifier(
-            name_raw,
-            pair[0].line,
-            "named let param",
-        )?);
+        normalize_identifier(name_raw, pair[0].line, "named let param")?;
+        params.push(&pair[0]);
         init_vals.push(lower_expr(&pair[1])?);
     }
 
-    let func_name = normalize_identifier(name, line, "named let name")?;
-    let body_expr = lower_body_exprs(body, line)?;
-    let function_stmt = format!("fn {func_name}({}) = {body_expr};", params.join(", "));
-
-    // fn func_name(params...) = body; func_name(init_vals...)
-    let call = format!("{}({})", func_name, init_vals.join(", "));
-    Ok(wrap_statement_sequence(vec![function_stmt], call))
+    normalize_identifier(name, line, "named let name")?;
+    if body.is_empty() {
+        return Err(ParseError {
+            span: None,
+            code: None,
+            line,
+            message: "body must have at least one expression".to_string(),
+        });
+    }
+    // The loop runs the body once per iteration, so calls to `name` in tail
+    // position restart it instead of recursing.
+    let procedure = TailProcedure::new(bindings_form, name, params, body);
+    if statement {
+        lower_loop_stmt(&[&procedure], 0, init_vals)
+    } else {
+        lower_loop(&[&procedure], 0, init_vals)
+    }
 }
 
 fn lower_letrec_lambda_binding(
@@ -3184,6 +3432,116 @@ where
     Ok(predicate_fn(val))
 }
 
+fn lower_format_expr(args: &[SchemeForm], line: usize) -> Result<String, ParseError> {
+    let format_error = |message: String| ParseError {
+        span: None,
+        code: None,
+        line,
+        message,
+    };
+    let args = match args {
+        [
+            SchemeForm {
+                node: SchemeNode::Bool(false),
+                ..
+            },
+            rest @ ..,
+        ] => rest,
+        _ => args,
+    };
+    let Some((
+        SchemeForm {
+            node: SchemeNode::String(directives),
+            ..
+        },
+        values,
+    )) = args.split_first()
+    else {
+        return Err(format_error(
+            "format expects (format [#f] \"template\" arg ...) with a constant template"
+                .to_string(),
+        ));
+    };
+
+    let mut template = String::with_capacity(directives.len());
+    let mut chars = directives.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' => template.push_str("{{"),
+            '}' => template.push_str("}}"),
+            '~' => match chars.next() {
+                Some('a' | 'A') => template.push_str("{}"),
+                Some('s' | 'S') => template.push_str("{:?}"),
+                Some('%' | 'n') => template.push('\n'),
+                Some('~') => template.push('~'),
+                Some(other) => {
+                    return Err(format_error(format!(
+                        "format directive '~{other}' is not supported in this subset"
+                    )));
+                }
+                None => {
+                    return Err(format_error(
+                        "format template ends with an incomplete '~' directive".to_string(),
+                    ));
+                }
+            },
+            other => template.push(other),
+        }
+    }
+
+    let mut out = format!("format!({}", render_string(&template));
+    for value in values {
+        out.push_str(", ");
+        out.push_str(&lower_expr(value)?);
+    }
+    out.push(')');
+    Ok(out)
+}
+
+fn lower_sort_expr(args: &[SchemeForm], line: usize) -> Result<String, ParseError> {
+    let (list, less) = match args {
+        [list] => (list, None),
+        [list, less] => (list, Some(less)),
+        _ => {
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line,
+                message: "sort expects (sort list [less?])".to_string(),
+            });
+        }
+    };
+    let list = lower_expr(list)?;
+    let Some(less) = less else {
+        return Ok(format!("array::sort({list})"));
+    };
+    if less.as_symbol() == Some("<") {
+        return Ok(format!("array::sort({list})"));
+    }
+    let predicate = if less.as_list().is_some() {
+        lower_expr(less)?
+    } else {
+        let lhs = gensym("sort_a");
+        let rhs = gensym("sort_b");
+        let call = SchemeForm {
+            line: less.line,
+            node: SchemeNode::List(vec![
+                less.clone(),
+                SchemeForm {
+                    line: less.line,
+                    node: SchemeNode::Symbol(lhs.clone()),
+                },
+                SchemeForm {
+                    line: less.line,
+                    node: SchemeNode::Symbol(rhs.clone()),
+                },
+            ]),
+        };
+        format!("|{lhs}, {rhs}| {}", lower_expr(&call)?)
+    };
+    Ok(format!("{SORT_BY_HELPER_NAME}({list}, {predicate})"))
+}
+
 fn lower_map_expr(args: &[SchemeForm], line: usize) -> Result<String, ParseError> {
     if args.len() != 2 {
         return Err(ParseError {
@@ -3281,7 +3639,11 @@ fn lower_apply_expr(args: &[SchemeForm], line: usize) -> Result<String, ParseErr
     Ok(format!("{func}({arglist})"))
 }
 
-fn normalize_identifier(name: &str, line: usize, context: &str) -> Result<String, ParseError> {
+pub(super) fn normalize_identifier(
+    name: &str,
+    line: usize,
+    context: &str,
+) -> Result<String, ParseError> {
     if name.is_empty() {
         return Err(ParseError {
             span: None,
@@ -3331,7 +3693,16 @@ fn normalize_identifier(name: &str, line: usize, context: &str) -> Result<String
 fn is_reserved_identifier(name: &str) -> bool {
     matches!(
         name,
-        "fn" | "let" | "for" | "if" | "else" | "while" | "break" | "continue" | "true" | "false"
+        "fn" | "let"
+            | "for"
+            | "if"
+            | "else"
+            | "while"
+            | "break"
+            | "continue"
+            | "return"
+            | "true"
+            | "false"
     )
 }
 
@@ -0,0 +1,599 @@
+//! `syntax-rules` macros for the Scheme frontend.
+//!
+//! Expansion runs on the parsed forms before lowering. `define-syntax`
+//! registers a macro for the rest of the enclosing list, and `let-syntax` /
+//! `letrec-syntax` scope macros to their body, which becomes `(let () ...)`.
+//! Each use is rewritten by the first rule whose pattern matches, and the
+//! result is expanded again until no macro uses remain.
+//!
+//! Names a template binds with `let`, `let*`, `letrec`, named `let`,
+//! `lambda` or `do` are renamed to fresh symbols on every expansion, so they
+//! never capture or shadow variables at the use site. Forms built from the
+//! template take the line of the use site, so diagnostics and the debugger
+//! point at user code rather than at the macro definition.
+
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use super::super::ParseError;
+use super::scheme::{SchemeForm, SchemeNode, gensym};
+
+const DEFAULT_ELLIPSIS: &str = "...";
+const MAX_EXPANSION_DEPTH: usize = 512;
+
+pub(super) fn expand_program(forms: Vec<SchemeForm>) -> Result<Vec<SchemeForm>, ParseError> {
+    MacroExpander::default().expand_sequence(forms)
+}
+
+#[derive(Default)]
+struct MacroExpander {
+    scopes: Vec<HashMap<String, Rc<SyntaxRules>>>,
+    depth: usize,
+}
+
+impl MacroExpander {
+    fn expand_sequence(&mut self, forms: Vec<SchemeForm>) -> Result<Vec<SchemeForm>, ParseError> {
+        self.scopes.push(HashMap::new());
+        let expanded = self.expand_in_scope(forms);
+        self.scopes.pop();
+        expanded
+    }
+
+    fn expand_in_scope(&mut self, forms: Vec<SchemeForm>) -> Result<Vec<SchemeForm>, ParseError> {
+        let mut out = Vec::with_capacity(forms.len());
+        for form in forms {
+            let depth = self.depth;
+            let expanded = self.expand_head(form).and_then(|form| {
+                if let Some((name, rules)) = parse_define_syntax(&form)? {
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.insert(name, Rc::new(rules));
+                    }
+                    return Ok(None);
+                }
+                self.expand_children(form).map(Some)
+            });
+            self.depth = depth;
+            if let Some(form) = expanded? {
+                out.push(form);
+            }
+        }
+        Ok(out)
+    }
+
+    /// Rewrites `form` while its head names a macro. Each rewrite counts
+    /// towards the depth limit until the caller resets it.
+    fn expand_head(&mut self, mut form: SchemeForm) -> Result<SchemeForm, ParseError> {
+        while let Some(head) = form
+            .as_list()
+            .and_then(|items| items.first())
+            .and_then(SchemeForm::as_symbol)
+        {
+            let Some(rules) = self.lookup(head) else {
+                break;
+            };
+            self.depth += 1;
+            if self.depth > MAX_EXPANSION_DEPTH {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line: form.line,
+                    message: format!(
+                        "expansion of macro '{head}' exceeds {MAX_EXPANSION_DEPTH} nested uses"
+                    ),
+                });
+            }
+            form = rules.expand(head, &form)?;
+        }
+        Ok(form)
+    }
+
+    fn expand_children(&mut self, form: SchemeForm) -> Result<SchemeForm, ParseError> {
+        let SchemeNode::List(items) = form.node else {
+            return Ok(form);
+        };
+        match items.first().and_then(SchemeForm::as_symbol) {
+            Some("quote") => Ok(SchemeForm {
+                line: form.line,
+                node: SchemeNode::List(items),
+            }),
+            Some("let-syntax" | "letrec-syntax") => self.expand_let_syntax(items, form.line),
+            _ => Ok(SchemeForm {
+                line: form.line,
+                node: SchemeNode::List(self.expand_sequence(items)?),
+            }),
+        }
+    }
+
+    fn expand_let_syntax(
+        &mut self,
+        items: Vec<SchemeForm>,
+        line: usize,
+    ) -> Result<SchemeForm, ParseError> {
+        let Some(bindings) = items.get(1).and_then(SchemeForm::as_list) else {
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line,
+                message: "let-syntax expects a list of macro bindings and a body".to_string(),
+            });
+        };
+        let mut scope = HashMap::new();
+        for binding in bindings {
+            let (name, spec) = match binding.as_list() {
+                Some([name, spec]) if name.as_symbol().is_some() => (name, spec),
+                _ => {
+                    return Err(ParseError {
+                        span: None,
+                        code: None,
+                        line: binding.line,
+                        message: "let-syntax binding must be (name (syntax-rules ...))".to_string(),
+                    });
+                }
+            };
+            let name = name.as_symbol().unwrap_or_default();
+            scope.insert(name.to_string(), Rc::new(SyntaxRules::parse(name, spec)?));
+        }
+
+        self.scopes.push(scope);
+        let body = self.expand_sequence(items.into_iter().skip(2).collect());
+        self.scopes.pop();
+        let mut let_form = vec![symbol("let", line), list(Vec::new(), line)];
+        let_form.extend(body?);
+        Ok(list(let_form, line))
+    }
+
+    fn lookup(&self, name: &str) -> Option<Rc<SyntaxRules>> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+}
+
+fn parse_define_syntax(form: &SchemeForm) -> Result<Option<(String, SyntaxRules)>, ParseError> {
+    let Some(items) = form.as_list() else {
+        return Ok(None);
+    };
+    if items.first().and_then(SchemeForm::as_symbol) != Some("define-syntax") {
+        return Ok(None);
+    }
+    match &items[1..] {
+        [name, spec] if name.as_symbol().is_some() => {
+            let name = name.as_symbol().unwrap_or_default();
+            Ok(Some((name.to_string(), SyntaxRules::parse(name, spec)?)))
+        }
+        _ => Err(ParseError {
+            span: None,
+            code: None,
+            line: form.line,
+            message: "define-syntax expects a name and a syntax-rules transformer".to_string(),
+        }),
+    }
+}
+
+struct SyntaxRules {
+    ellipsis: String,
+    literals: HashSet<String>,
+    rules: Vec<SyntaxRule>,
+}
+
+struct SyntaxRule {
+    /// Pattern elements after the macro keyword, which is never matched.
+    pattern: Vec<SchemeForm>,
+    template: SchemeForm,
+    /// Names the template binds itself; renamed on every expansion.
+    binders: Vec<String>,
+}
+
+#[derive(Clone)]
+enum MatchBinding {
+    One(SchemeForm),
+    Many(Vec<MatchBinding>),
+}
+
+type MatchBindings = HashMap<String, MatchBinding>;
+
+impl SyntaxRules {
+    fn parse(name: &str, spec: &SchemeForm) -> Result<Self, ParseError> {
+        let items = spec.as_list().unwrap_or_default();
+        if items.first().and_then(SchemeForm::as_symbol) != Some("syntax-rules") {
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line: spec.line,
+                message: format!("macro '{name}' must be defined with syntax-rules"),
+            });
+        }
+        let mut rest = &items[1..];
+        let mut ellipsis = DEFAULT_ELLIPSIS.to_string();
+        if let Some(custom) = rest.first().and_then(SchemeForm::as_symbol) {
+            ellipsis = custom.to_string();
+            rest = &rest[1..];
+        }
+        let Some((literals, rules)) = rest.split_first() else {
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line: spec.line,
+                message: format!("syntax-rules of macro '{name}' expects a literals list"),
+            });
+        };
+        let literals = literals
+            .as_list()
+            .ok_or_else(|| ParseError {
+                span: None,
+                code: None,
+                line: literals.line,
+                message: format!("syntax-rules literals of macro '{name}' must be a list"),
+            })?
+            .iter()
+            .map(|literal| {
+                literal
+                    .as_symbol()
+                    .map(str::to_string)
+                    .ok_or_else(|| ParseError {
+                        span: None,
+                        code: None,
+                        line: literal.line,
+                        message: "syntax-rules literals must be symbols".to_string(),
+                    })
+            })
+            .collect::<Result<HashSet<_>, _>>()?;
+
+        let mut syntax_rules = Self {
+            ellipsis,
+            literals,
+            rules: Vec::new(),
+        };
+        for rule in rules {
+            let (pattern, template) = match rule.as_list() {
+                Some([pattern, template]) => (pattern, template),
+                _ => {
+                    return Err(ParseError {
+                        span: None,
+                        code: None,
+                        line: rule.line,
+                        message: format!("rule of macro '{name}' must be (pattern template)"),
+                    });
+                }
+            };
+            let Some((_, pattern)) = pattern.as_list().and_then(<[_]>::split_first) else {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line: pattern.line,
+                    message: format!("pattern of macro '{name}' must be a list"),
+                });
+            };
+            syntax_rules.check_pattern(pattern, rule.line)?;
+            let mut pattern_vars = HashSet::new();
+            for element in pattern {
+                syntax_rules.collect_pattern_vars(element, &mut pattern_vars);
+            }
+            let mut binders = Vec::new();
+            collect_binders(template, &mut binders);
+            binders.retain(|binder| {
+                !pattern_vars.contains(binder) && *binder != syntax_rules.ellipsis
+            });
+            binders.sort();
+            binders.dedup();
+            syntax_rules.rules.push(SyntaxRule {
+                pattern: pattern.to_vec(),
+                template: template.clone(),
+                binders,
+            });
+        }
+        Ok(syntax_rules)
+    }
+
+    fn expand(&self, name: &str, form: &SchemeForm) -> Result<SchemeForm, ParseError> {
+        let args = form.as_list().map(|items| &items[1..]).unwrap_or_default();
+        for rule in &self.rules {
+            let mut bindings = MatchBindings::new();
+            if !self.match_elements(&rule.pattern, args, &mut bindings) {
+                continue;
+            }
+            let renames = rule
+                .binders
+                .iter()
+                .map(|binder| {
+                    (
+                        binder.clone(),
+                        gensym(&format!("macro_{}", sanitize(binder))),
+                    )
+                })
+                .collect::<HashMap<_, _>>();
+            return self.instantiate(&rule.template, &bindings, &renames, form.line);
+        }
+        Err(ParseError {
+            span: None,
+            code: None,
+            line: form.line,
+            message: format!("no syntax-rules pattern of macro '{name}' matches this use"),
+        })
+    }
+
+    fn is_ellipsis(&self, form: &SchemeForm) -> bool {
+        form.as_symbol() == Some(self.ellipsis.as_str())
+    }
+
+    fn check_pattern(&self, pattern: &[SchemeForm], line: usize) -> Result<(), ParseError> {
+        let ellipses = pattern.iter().filter(|item| self.is_ellipsis(item)).count();
+        if ellipses > 1 || pattern.first().is_some_and(|item| self.is_ellipsis(item)) {
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line,
+                message: format!(
+                    "'{}' must follow a pattern and appear at most once per list",
+                    self.ellipsis
+                ),
+            });
+        }
+        for item in pattern {
+            if let Some(items) = item.as_list() {
+                self.check_pattern(items, item.line)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn collect_pattern_vars(&self, pattern: &SchemeForm, out: &mut HashSet<String>) {
+        match &pattern.node {
+            SchemeNode::Symbol(name)
+                if name != "_" && *name != self.ellipsis && !self.literals.contains(name) =>
+            {
+                out.insert(name.clone());
+            }
+            SchemeNode::List(items) => {
+                for item in items {
+                    self.collect_pattern_vars(item, out);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn match_form(
+        &self,
+        pattern: &SchemeForm,
+        form: &SchemeForm,
+        bindings: &mut MatchBindings,
+    ) -> bool {
+        match (&pattern.node, &form.node) {
+            (SchemeNode::Symbol(name), _) if name == "_" => true,
+            (SchemeNode::Symbol(name), _) if self.literals.contains(name) => {
+                form.as_symbol() == Some(name.as_str())
+            }
+            (SchemeNode::Symbol(name), _) => {
+                bindings.insert(name.clone(), MatchBinding::One(form.clone()));
+                true
+            }
+            (SchemeNode::List(patterns), SchemeNode::List(forms)) => {
+                self.match_elements(patterns, forms, bindings)
+            }
+            (SchemeNode::Int(lhs), SchemeNode::Int(rhs)) => lhs == rhs,
+            (SchemeNode::Float(lhs), SchemeNode::Float(rhs)) => lhs == rhs,
+            (SchemeNode::Bool(lhs), SchemeNode::Bool(rhs)) => lhs == rhs,
+            (SchemeNode::Char(lhs), SchemeNode::Char(rhs)) => lhs == rhs,
+            (SchemeNode::String(lhs), SchemeNode::String(rhs)) => lhs == rhs,
+            _ => false,
+        }
+    }
+
+    fn match_elements(
+        &self,
+        patterns: &[SchemeForm],
+        forms: &[SchemeForm],
+        bindings: &mut MatchBindings,
+    ) -> bool {
+        let Some(ellipsis_at) = patterns.iter().position(|item| self.is_ellipsis(item)) else {
+            return patterns.len() == forms.len()
+                && patterns
+                    .iter()
+                    .zip(forms)
+                    .all(|(pattern, form)| self.match_form(pattern, form, bindings));
+        };
+        let before = &patterns[..ellipsis_at - 1];
+        let repeated = &patterns[ellipsis_at - 1];
+        let after = &patterns[ellipsis_at + 1..];
+        if forms.len() < before.len() + after.len() {
+            return false;
+        }
+        let repeat_end = forms.len() - after.len();
+        if !self.match_elements(before, &forms[..before.len()], bindings)
+            || !self.match_elements(after, &forms[repeat_end..], bindings)
+        {
+            return false;
+        }
+
+        let mut vars = HashSet::new();
+        self.collect_pattern_vars(repeated, &mut vars);
+        let mut matches = Vec::new();
+        for form in &forms[before.len()..repeat_end] {
+            let mut element = MatchBindings::new();
+            if !self.match_form(repeated, form, &mut element) {
+                return false;
+            }
+            matches.push(element);
+        }
+        for var in vars {
+            let sequence = matches
+                .iter_mut()
+                .filter_map(|element| element.remove(&var))
+                .collect();
+            bindings.insert(var, MatchBinding::Many(sequence));
+        }
+        true
+    }
+
+    fn instantiate(
+        &self,
+        template: &SchemeForm,
+        bindings: &MatchBindings,
+        renames: &HashMap<String, String>,
+        line: usize,
+    ) -> Result<SchemeForm, ParseError> {
+        let items = match &template.node {
+            SchemeNode::Symbol(name) => {
+                return match bindings.get(name) {
+                    Some(MatchBinding::One(form)) => Ok(form.clone()),
+                    Some(MatchBinding::Many(_)) => Err(ParseError {
+                        span: None,
+                        code: None,
+                        line,
+                        message: format!(
+                            "pattern variable '{name}' must be followed by '{}' in the template",
+                            self.ellipsis
+                        ),
+                    }),
+                    None => Ok(symbol(renames.get(name).unwrap_or(name), line)),
+                };
+            }
+            SchemeNode::List(items) => items,
+            node => {
+                return Ok(SchemeForm {
+                    line,
+                    node: node.clone(),
+                });
+            }
+        };
+
+        let mut out = Vec::with_capacity(items.len());
+        let mut index = 0;
+        while index < items.len() {
+            let item = &items[index];
+            if !items
+                .get(index + 1)
+                .is_some_and(|next| self.is_ellipsis(next))
+            {
+                out.push(self.instantiate(item, bindings, renames, line)?);
+                index += 1;
+                continue;
+            }
+            let mut vars = Vec::new();
+            collect_sequence_vars(item, bindings, &mut vars);
+            let mut lengths = vars.iter().filter_map(|var| match bindings.get(var) {
+                Some(MatchBinding::Many(sequence)) => Some(sequence.len()),
+                _ => None,
+            });
+            let Some(len) = lengths.next() else {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line,
+                    message: format!(
+                        "template before '{}' uses no pattern variable matched by '{}'",
+                        self.ellipsis, self.ellipsis
+                    ),
+                });
+            };
+            if lengths.any(|other| other != len) {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line,
+                    message: format!(
+                        "pattern variables under one '{}' matched different numbers of forms",
+                        self.ellipsis
+                    ),
+                });
+            }
+            for step in 0..len {
+                let mut step_bindings = bindings.clone();
+                for var in &vars {
+                    if let Some(MatchBinding::Many(sequence)) = bindings.get(var) {
+                        step_bindings.insert(var.clone(), sequence[step].clone());
+                    }
+                }
+                out.push(self.instantiate(item, &step_bindings, renames, line)?);
+            }
+            index += 2;
+        }
+        Ok(list(out, line))
+    }
+}
+
+/// Pattern variables in `template` bound to a sequence, i.e. the ones an
+/// ellipsis after `template` iterates over.
+fn collect_sequence_vars(template: &SchemeForm, bindings: &MatchBindings, out: &mut Vec<String>) {
+    match &template.node {
+        SchemeNode::Symbol(name)
+            if matches!(bindings.get(name), Some(MatchBinding::Many(_))) && !out.contains(name) =>
+        {
+            out.push(name.clone());
+        }
+        SchemeNode::List(items) => {
+            for item in items {
+                collect_sequence_vars(item, bindings, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Names bound by `let`-family, `lambda` and `do` forms inside `template`.
+fn collect_binders(template: &SchemeForm, out: &mut Vec<String>) {
+    let Some(items) = template.as_list() else {
+        return;
+    };
+    let args = items.get(1..).unwrap_or_default();
+    match items.first().and_then(SchemeForm::as_symbol) {
+        Some("lambda") => match args.first() {
+            Some(params) if params.as_symbol().is_some() => {
+                out.extend(params.as_symbol().map(str::to_string));
+            }
+            Some(params) => {
+                let params = params.as_list().unwrap_or_default();
+                out.extend(
+                    params
+                        .iter()
+                        .filter_map(SchemeForm::as_symbol)
+                        .map(str::to_string),
+                );
+            }
+            None => {}
+        },
+        Some("let" | "let*" | "letrec" | "letrec*" | "do") => {
+            let bindings = match args.first().and_then(SchemeForm::as_symbol) {
+                Some(name) => {
+                    out.push(name.to_string());
+                    args.get(1)
+                }
+                None => args.first(),
+            };
+            let bindings = bindings.and_then(SchemeForm::as_list).unwrap_or_default();
+            out.extend(
+                bindings
+                    .iter()
+                    .filter_map(|binding| binding.as_list()?.first()?.as_symbol())
+                    .map(str::to_string),
+            );
+        }
+        _ => {}
+    }
+    for item in items {
+        collect_binders(item, out);
+    }
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' })
+        .collect()
+}
+
+fn symbol(name: &str, line: usize) -> SchemeForm {
+    SchemeForm {
+        line,
+        node: SchemeNode::Symbol(name.to_string()),
+    }
+}
+
+fn list(items: Vec<SchemeForm>, line: usize) -> SchemeForm {
+    SchemeForm {
+        line,
+        node: SchemeNode::List(items),
+    }
+}
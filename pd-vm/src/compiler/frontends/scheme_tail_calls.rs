@@ -0,0 +1,493 @@
+//! Proper tail calls for Scheme.
+//!
+//! The compiler inlines every call, so a procedure cannot call itself. Instead,
+//! procedures that reach one another only through tail calls lower to a single
+//! `while` loop. A state local selects the running procedure, and a tail call
+//! stores its arguments, switches the state, and continues. Loops written as
+//! recursion therefore run in constant stack space. Each member of a group
+//! becomes a closure that holds its own copy of the loop, entered at its own
+//! state, so groups capture outer locals like any other closure.
+
+use std::collections::HashSet;
+
+use super::super::ParseError;
+use super::scheme::{
+    SchemeForm, gensym, is_plain_bindings, lower_and_expr, lower_expr, lower_or_expr, lower_stmt,
+    normalize_identifier,
+};
+
+/// A procedure that may take part in a tail-call group.
+pub(super) struct TailProcedure<'a> {
+    /// The `define` form or `letrec` binding introducing the procedure.
+    form: &'a SchemeForm,
+    /// The name as written, which is what calls inside the group use.
+    name: &'a str,
+    params: Vec<&'a SchemeForm>,
+    body: &'a [SchemeForm],
+}
+
+impl<'a> TailProcedure<'a> {
+    pub(super) fn new(
+        form: &'a SchemeForm,
+        name: &'a str,
+        params: Vec<&'a SchemeForm>,
+        body: &'a [SchemeForm],
+    ) -> Self {
+        Self {
+            form,
+            name,
+            params,
+            body,
+        }
+    }
+
+    /// Reads `(define (name params...) body...)` or
+    /// `(define name (lambda (params...) body...))`.
+    pub(super) fn from_define(form: &'a SchemeForm) -> Option<Self> {
+        let items = form.as_list()?;
+        if items.first()?.as_symbol()? != "define" || items.len() < 3 {
+            return None;
+        }
+        if let Some(signature) = items[1].as_list() {
+            let (name, params) = signature.split_first()?;
+            return Some(Self::new(
+                form,
+                name.as_symbol()?,
+                params.iter().collect(),
+                &items[2..],
+            ));
+        }
+        if items.len() != 3 {
+            return None;
+        }
+        Self::from_lambda(form, items[1].as_symbol()?, &items[2])
+    }
+
+    /// Reads a `(lambda (params...) body...)` bound to `name` by `form`.
+    pub(super) fn from_lambda(
+        form: &'a SchemeForm,
+        name: &'a str,
+        value: &'a SchemeForm,
+    ) -> Option<Self> {
+        let items = value.as_list()?;
+        if items.first()?.as_symbol()? != "lambda" || items.len() < 3 {
+            return None;
+        }
+        let params = items[1].as_list()?;
+        Some(Self::new(form, name, params.iter().collect(), &items[2..]))
+    }
+
+    fn param_names(&self) -> Result<Vec<String>, ParseError> {
+        self.params
+            .iter()
+            .map(|param| {
+                let raw = param.as_symbol().ok_or_else(|| ParseError {
+                    span: None,
+                    code: None,
+                    line: param.line,
+                    message: "function parameter must be a symbol".to_string(),
+                })?;
+                normalize_identifier(raw, param.line, "function parameter")
+            })
+            .collect()
+    }
+}
+
+/// The recursive groups among sibling procedures: every set of procedures
+/// that can reach one another through calls, and every procedure that calls
+/// itself.
+pub(super) struct TailGroups<'a> {
+    procedures: Vec<TailProcedure<'a>>,
+    groups: Vec<Vec<usize>>,
+}
+
+impl<'a> TailGroups<'a> {
+    pub(super) fn find(procedures: Vec<TailProcedure<'a>>) -> Self {
+        let count = procedures.len();
+        let mut reaches = vec![vec![false; count]; count];
+        for (caller, procedure) in procedures.iter().enumerate() {
+            for (callee, other) in procedures.iter().enumerate() {
+                let names = HashSet::from([other.name]);
+                reaches[caller][callee] = procedure
+                    .body
+                    .iter()
+                    .any(|form| first_mention(form, &names).is_some());
+            }
+        }
+        for via in 0..count {
+            for from in 0..count {
+                for to in 0..count {
+                    if reaches[from][via] && reaches[via][to] {
+                        reaches[from][to] = true;
+                    }
+                }
+            }
+        }
+        let mut grouped = vec![false; count];
+        let mut groups = Vec::new();
+        for start in 0..count {
+            if grouped[start] || !reaches[start][start] {
+                continue;
+            }
+            let group = (0..count)
+                .filter(|&other| reaches[start][other] && reaches[other][start])
+                .collect::<Vec<_>>();
+            for &member in &group {
+                grouped[member] = true;
+            }
+            groups.push(group);
+        }
+        Self { procedures, groups }
+    }
+
+    /// Lowers the procedure introduced by `form` to `let name = |params| ...;`
+    /// when it belongs to a recursive group.
+    pub(super) fn lower_binding(&self, form: &SchemeForm) -> Result<Option<String>, ParseError> {
+        for group in &self.groups {
+            let members = group
+                .iter()
+                .map(|&index| &self.procedures[index])
+                .collect::<Vec<_>>();
+            let Some(entry) = members
+                .iter()
+                .position(|member| std::ptr::eq(member.form, form))
+            else {
+                continue;
+            };
+            let procedure = members[entry];
+            let name = normalize_identifier(procedure.name, form.line, "function name")?;
+            let params = procedure.param_names()?;
+            let body = lower_loop(&members, entry, params.clone())?;
+            return Ok(Some(format!(
+                "let {name} = |{}| {body};",
+                params.join(", ")
+            )));
+        }
+        Ok(None)
+    }
+}
+
+/// Lowers a call of `group[entry]` with the already lowered `args` to an
+/// expression running the group's loop.
+pub(super) fn lower_loop(
+    group: &[&TailProcedure],
+    entry: usize,
+    args: Vec<String>,
+) -> Result<String, ParseError> {
+    let (out, result) = loop_lines(group, entry, args, false)?;
+    Ok(format!(
+        "if true => {{ {} {result} }} else => {{ false }}",
+        out.join(" ")
+    ))
+}
+
+/// Lowers a call of `group[entry]` whose value is unused to a block
+/// statement running the group's loop.
+pub(super) fn lower_loop_stmt(
+    group: &[&TailProcedure],
+    entry: usize,
+    args: Vec<String>,
+) -> Result<String, ParseError> {
+    let (out, _) = loop_lines(group, entry, args, true)?;
+    Ok(format!("if true {{ {} }}", out.join(" ")))
+}
+
+/// The statements running the group's loop and the local left holding its
+/// result. With `discard`, values in tail position lower as statements.
+fn loop_lines(
+    group: &[&TailProcedure],
+    entry: usize,
+    args: Vec<String>,
+    discard: bool,
+) -> Result<(Vec<String>, String), ParseError> {
+    let arity = group
+        .iter()
+        .map(|member| member.params.len())
+        .max()
+        .unwrap_or(0);
+    let lowering = GroupLowering {
+        group,
+        names: group.iter().map(|member| member.name).collect(),
+        slots: (0..arity).map(|_| gensym("tail_arg")).collect(),
+        state: (group.len() > 1).then(|| gensym("tail_state")),
+        result: gensym("tail_result"),
+        discard,
+    };
+
+    let mut out = Vec::new();
+    let mut args = args.into_iter();
+    for slot in &lowering.slots {
+        let value = args.next().unwrap_or_else(|| "null".to_string());
+        out.push(format!("let {slot} = {value};"));
+    }
+    if let Some(state) = &lowering.state {
+        out.push(format!("let {state} = {entry};"));
+    }
+    out.push(format!("let {} = null;", lowering.result));
+    out.push("while true {".to_string());
+    match &lowering.state {
+        None => lowering.member(0, &mut out)?,
+        Some(state) => {
+            for index in 0..group.len() {
+                if index == 0 {
+                    out.push(format!("if {state} == 0 {{"));
+                } else if index + 1 == group.len() {
+                    out.push("} else {".to_string());
+                } else {
+                    out.push(format!("}} else if {state} == {index} {{"));
+                }
+                lowering.member(index, &mut out)?;
+            }
+            out.push("}".to_string());
+        }
+    }
+    out.push("}".to_string());
+    Ok((out, lowering.result))
+}
+
+struct GroupLowering<'g, 'a> {
+    group: &'g [&'g TailProcedure<'a>],
+    names: HashSet<&'a str>,
+    /// Locals carrying the arguments of the next iteration.
+    slots: Vec<String>,
+    /// Index of the running member; groups of one procedure need none.
+    state: Option<String>,
+    result: String,
+    /// Whether the loop's value is unused.
+    discard: bool,
+}
+
+impl GroupLowering<'_, '_> {
+    fn member(&self, index: usize, out: &mut Vec<String>) -> Result<(), ParseError> {
+        let member = self.group[index];
+        for (param, slot) in member.param_names()?.iter().zip(&self.slots) {
+            out.push(format!("let {param} = {slot};"));
+        }
+        self.body(member.body, out)
+    }
+
+    /// Lowers a body whose last form is in tail position.
+    fn body(&self, forms: &[SchemeForm], out: &mut Vec<String>) -> Result<(), ParseError> {
+        let Some((last, prefix)) = forms.split_last() else {
+            return self.finish("false", out);
+        };
+        for form in prefix {
+            self.check_outside_tail(form)?;
+            let mut lines = Vec::new();
+            lower_stmt(form, 0, &mut lines)?;
+            out.extend(lines.iter().map(|line| line.trim().to_string()));
+        }
+        self.tail(last, out)
+    }
+
+    fn tail(&self, form: &SchemeForm, out: &mut Vec<String>) -> Result<(), ParseError> {
+        let Some(items) = form.as_list() else {
+            return self.value(form, out);
+        };
+        let Some(head) = items.first().and_then(|item| item.as_symbol()) else {
+            return self.value(form, out);
+        };
+        let args = &items[1..];
+        if let Some(target) = self.group.iter().position(|member| member.name == head) {
+            return self.call(target, args, form.line, out);
+        }
+        match head {
+            "if" if (2..=3).contains(&args.len()) => {
+                out.push(format!("if {} {{", self.condition(&args[0])?));
+                self.tail(&args[1], out)?;
+                out.push("} else {".to_string());
+                match args.get(2) {
+                    Some(otherwise) => self.tail(otherwise, out)?,
+                    None => self.finish("false", out)?,
+                }
+                out.push("}".to_string());
+                Ok(())
+            }
+            "cond" if is_tail_cond(args) => {
+                let mut has_else = false;
+                for (index, clause) in args.iter().filter_map(SchemeForm::as_list).enumerate() {
+                    let keyword = if index == 0 { "if" } else { "} else if" };
+                    if clause[0].as_symbol() == Some("else") {
+                        out.push(if index == 0 { "{" } else { "} else {" }.to_string());
+                        self.body(&clause[1..], out)?;
+                        has_else = true;
+                        break;
+                    }
+                    out.push(format!("{keyword} {} {{", self.condition(&clause[0])?));
+                    self.body(&clause[1..], out)?;
+                }
+                if !has_else {
+                    out.push("} else {".to_string());
+                    self.finish("false", out)?;
+                }
+                out.push("}".to_string());
+                Ok(())
+            }
+            "when" | "unless" if args.len() >= 2 => {
+                let condition = self.condition(&args[0])?;
+                if head == "when" {
+                    out.push(format!("if {condition} {{"));
+                } else {
+                    out.push(format!("if !({condition}) {{"));
+                }
+                self.body(&args[1..], out)?;
+                out.push("} else {".to_string());
+                self.finish("false", out)?;
+                out.push("}".to_string());
+                Ok(())
+            }
+            "begin" if !args.is_empty() => self.body(args, out),
+            "let" | "let*" if args.len() >= 2 && is_plain_bindings(&args[0]) => {
+                let bindings = args[0].as_list().unwrap_or_default();
+                out.push("if true {".to_string());
+                let mut names = Vec::new();
+                for binding in bindings.iter().filter_map(SchemeForm::as_list) {
+                    let name = binding[0].as_symbol().unwrap_or_default();
+                    let name = normalize_identifier(name, binding[0].line, "let binding")?;
+                    let value = self.condition(&binding[1])?;
+                    if head == "let*" {
+                        out.push(format!("let {name} = {value};"));
+                    } else {
+                        let temp = gensym("tail_let");
+                        out.push(format!("let {temp} = {value};"));
+                        names.push((name, temp));
+                    }
+                }
+                for (name, temp) in names {
+                    out.push(format!("let {name} = {temp};"));
+                }
+                self.body(&args[1..], out)?;
+                out.push("}".to_string());
+                Ok(())
+            }
+            "and" if args.len() >= 2 => {
+                let (last, prefix) = (&args[args.len() - 1], &args[..args.len() - 1]);
+                self.check_all_outside_tail(prefix)?;
+                out.push(format!("if {} {{", lower_and_expr(prefix)?));
+                self.tail(last, out)?;
+                out.push("} else {".to_string());
+                self.finish("false", out)?;
+                out.push("}".to_string());
+                Ok(())
+            }
+            "or" if args.len() >= 2 => {
+                let (last, prefix) = (&args[args.len() - 1], &args[..args.len() - 1]);
+                self.check_all_outside_tail(prefix)?;
+                let temp = gensym("tail_or");
+                out.push(format!("let {temp} = {};", lower_or_expr(prefix)?));
+                out.push(format!("if {temp} {{"));
+                self.finish(&temp, out)?;
+                out.push("}".to_string());
+                self.tail(last, out)
+            }
+            _ => self.value(form, out),
+        }
+    }
+
+    /// A tail call: the next iteration runs `group[target]` with `args`.
+    fn call(
+        &self,
+        target: usize,
+        args: &[SchemeForm],
+        line: usize,
+        out: &mut Vec<String>,
+    ) -> Result<(), ParseError> {
+        let member = self.group[target];
+        if args.len() != member.params.len() {
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line,
+                message: format!(
+                    "'{}' expects {} argument(s) but got {}",
+                    member.name,
+                    member.params.len(),
+                    args.len()
+                ),
+            });
+        }
+        // Every argument is evaluated before any parameter changes.
+        let mut temps = Vec::new();
+        for arg in args {
+            let temp = gensym("tail_next");
+            out.push(format!("let {temp} = {};", self.condition(arg)?));
+            temps.push(temp);
+        }
+        for (slot, temp) in self.slots.iter().zip(temps) {
+            out.push(format!("{slot} = {temp};"));
+        }
+        if let Some(state) = &self.state {
+            out.push(format!("{state} = {target};"));
+        }
+        out.push("continue;".to_string());
+        Ok(())
+    }
+
+    /// Lowers a form outside tail position, which must not call the group.
+    fn condition(&self, form: &SchemeForm) -> Result<String, ParseError> {
+        self.check_outside_tail(form)?;
+        lower_expr(form)
+    }
+
+    fn value(&self, form: &SchemeForm, out: &mut Vec<String>) -> Result<(), ParseError> {
+        if self.discard {
+            self.check_outside_tail(form)?;
+            let mut lines = Vec::new();
+            lower_stmt(form, 0, &mut lines)?;
+            out.extend(lines.iter().map(|line| line.trim().to_string()));
+            out.push("break;".to_string());
+            return Ok(());
+        }
+        let value = self.condition(form)?;
+        self.finish(&value, out)
+    }
+
+    fn finish(&self, value: &str, out: &mut Vec<String>) -> Result<(), ParseError> {
+        out.push(format!("{} = {value};", self.result));
+        out.push("break;".to_string());
+        Ok(())
+    }
+
+    fn check_all_outside_tail(&self, forms: &[SchemeForm]) -> Result<(), ParseError> {
+        forms
+            .iter()
+            .try_for_each(|form| self.check_outside_tail(form))
+    }
+
+    fn check_outside_tail(&self, form: &SchemeForm) -> Result<(), ParseError> {
+        match first_mention(form, &self.names) {
+            Some((name, line)) => Err(ParseError {
+                span: None,
+                code: None,
+                line,
+                message: format!(
+                    "'{name}' is called outside tail position; recursion is only supported through tail calls"
+                ),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The first symbol of `form` naming one of `names`, skipping quoted data.
+fn first_mention<'f>(form: &'f SchemeForm, names: &HashSet<&str>) -> Option<(&'f str, usize)> {
+    if let Some(symbol) = form.as_symbol() {
+        return names.contains(symbol).then_some((symbol, form.line));
+    }
+    let items = form.as_list()?;
+    if items.first().and_then(|item| item.as_symbol()) == Some("quote") {
+        return None;
+    }
+    items.iter().find_map(|item| first_mention(item, names))
+}
+
+/// Whether every `cond` clause has a test and a body, with `else` last.
+fn is_tail_cond(clauses: &[SchemeForm]) -> bool {
+    !clauses.is_empty()
+        && clauses.iter().enumerate().all(|(index, clause)| {
+            clause.as_list().is_some_and(|clause| {
+                clause.len() >= 2
+                    && (clause[0].as_symbol() != Some("else") || index + 1 == clauses.len())
+            })
+        })
+}
@@ -0,0 +1,260 @@
+//! The reserved table slot of Lua and JavaScript code.
+//!
+//! Lua metatables and JavaScript class tags ride along in a map as the value
+//! of its `null` key, which neither language can use as a table key. The
+//! generic builtins treat that key like any other, so RustScript and Scheme
+//! maps with `null` keys behave as written. Code lowered from a chunk that
+//! uses the slot is instead rewritten to reach lengths, key and value
+//! listings, iteration and printing through `lua::plain`, and, for Lua,
+//! table reads through the `__index`-aware `lua::index`. Only that code is
+//! rewritten: a table handed to a host function or to an imported RustScript
+//! module shows the slot as a `null`-keyed entry.
+
+use std::cell::Cell;
+
+use super::super::STDLIB_PRINT_NAME;
+use super::super::ir::{Expr, FrontendIr, FunctionImpl, MatchArm, Stmt};
+use crate::builtins::BuiltinFunction;
+
+/// Post-order rewrite of every expression in a function or statement list.
+pub(super) trait IrRewriter {
+    /// Replacement for `expr`, whose children are already rewritten.
+    fn replacement(&self, expr: &mut Expr) -> Option<Expr>;
+
+    /// Adjusts the iterable of a `for`-`in` loop after its children.
+    fn for_in_iterable(&self, _iterable: &mut Expr) {}
+
+    fn function(&self, function_impl: &mut FunctionImpl) {
+        self.stmts(&mut function_impl.body_stmts);
+        self.expr(&mut function_impl.body_expr);
+    }
+
+    fn stmts(&self, stmts: &mut [Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::Noop { .. }
+            | Stmt::FuncDecl { .. }
+            | Stmt::Break { .. }
+            | Stmt::Continue { .. } => {}
+            Stmt::Let { expr, .. }
+            | Stmt::Assign { expr, .. }
+            | Stmt::Expr { expr, .. }
+            | Stmt::Return { expr, .. } => self.expr(expr),
+            Stmt::ClosureLet { closure, .. } => self.expr(&mut closure.body),
+            Stmt::IfElse {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.expr(condition);
+                self.stmts(then_branch);
+                self.stmts(else_branch);
+            }
+            Stmt::For {
+                init,
+                condition,
+                post,
+                body,
+                ..
+            } => {
+                self.stmt(init);
+                self.expr(condition);
+                self.stmt(post);
+                self.stmts(body);
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                self.expr(condition);
+                self.stmts(body);
+            }
+            Stmt::ForIn { iterable, body, .. } => {
+                self.expr(iterable);
+                self.for_in_iterable(iterable);
+                self.stmts(body);
+            }
+        }
+    }
+
+    fn arm(&self, arm: &mut MatchArm) {
+        if let Some(guard) = &mut arm.guard {
+            self.expr(guard);
+        }
+        self.expr(&mut arm.body);
+    }
+
+    fn expr(&self, expr: &mut Expr) {
+        match expr {
+            Expr::Null
+            | Expr::Int(_)
+            | Expr::Float(_)
+            | Expr::Bool(_)
+            | Expr::String(_)
+            | Expr::FunctionRef(_)
+            | Expr::Var(_) => {}
+            Expr::Call(_, args) | Expr::LocalCall(_, args) => {
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::Closure(closure) => self.expr(&mut closure.body),
+            Expr::ClosureCall(closure, args) => {
+                self.expr(&mut closure.body);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            Expr::Add(lhs, rhs)
+            | Expr::Sub(lhs, rhs)
+            | Expr::Mul(lhs, rhs)
+            | Expr::Div(lhs, rhs)
+            | Expr::Mod(lhs, rhs)
+            | Expr::And(lhs, rhs)
+            | Expr::Or(lhs, rhs)
+            | Expr::Eq(lhs, rhs)
+            | Expr::Lt(lhs, rhs)
+            | Expr::Gt(lhs, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            Expr::Neg(inner) | Expr::Not(inner) => self.expr(inner),
+            Expr::IfElse {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                self.expr(condition);
+                self.expr(then_expr);
+                self.expr(else_expr);
+            }
+            Expr::Match {
+                value,
+                arms,
+                default,
+                ..
+            } => {
+                self.expr(value);
+                for arm in arms {
+                    self.arm(arm);
+                }
+                self.expr(default);
+            }
+            Expr::Block { stmts, expr } => {
+                self.stmts(stmts);
+                self.expr(expr);
+            }
+        }
+        if let Some(replacement) = self.replacement(expr) {
+            *expr = replacement;
+        }
+    }
+}
+
+/// Whether any statement or function of `ir` calls `builtin`.
+pub(super) fn calls_builtin(ir: &mut FrontendIr, builtin: BuiltinFunction) -> bool {
+    let finder = BuiltinFinder {
+        call_index: builtin.call_index(),
+        found: Cell::new(false),
+    };
+    finder.stmts(&mut ir.stmts);
+    for function_impl in ir.function_impls.values_mut() {
+        finder.function(function_impl);
+    }
+    finder.found.get()
+}
+
+struct BuiltinFinder {
+    call_index: u16,
+    found: Cell<bool>,
+}
+
+impl IrRewriter for BuiltinFinder {
+    fn replacement(&self, expr: &mut Expr) -> Option<Expr> {
+        if matches!(expr, Expr::Call(index, _) if *index == self.call_index) {
+            self.found.set(true);
+        }
+        None
+    }
+}
+
+/// Rewrites a chunk that stores metatables or class tags so none of its
+/// reads see the slot. `index_fallback` also sends table reads through the
+/// `__index` chain.
+pub(super) fn hide_reserved_slot(ir: &mut FrontendIr, index_fallback: bool) {
+    let rewriter = SlotRewriter {
+        index_fallback,
+        print: ir
+            .functions
+            .iter()
+            .find(|decl| decl.name == STDLIB_PRINT_NAME)
+            .map(|decl| decl.index),
+    };
+    rewriter.stmts(&mut ir.stmts);
+    for function_impl in ir.function_impls.values_mut() {
+        rewriter.function(function_impl);
+    }
+}
+
+struct SlotRewriter {
+    index_fallback: bool,
+    print: Option<u16>,
+}
+
+impl IrRewriter for SlotRewriter {
+    fn replacement(&self, expr: &mut Expr) -> Option<Expr> {
+        let Expr::Call(index, args) = expr else {
+            return None;
+        };
+        let index = *index;
+        if self.index_fallback && index == BuiltinFunction::Get.call_index() && args.len() == 2 {
+            return Some(Expr::Call(
+                BuiltinFunction::LuaIndex.call_index(),
+                std::mem::take(args),
+            ));
+        }
+        let shallow = [
+            BuiltinFunction::Len,
+            BuiltinFunction::Count,
+            BuiltinFunction::Keys,
+            BuiltinFunction::MapValues,
+            BuiltinFunction::MapEntries,
+        ];
+        if shallow.iter().any(|builtin| builtin.call_index() == index) {
+            if let Some(container) = args.first_mut() {
+                plain(container, false);
+            }
+        } else if index == BuiltinFunction::ToString.call_index() {
+            if let Some(value) = args.first_mut() {
+                plain(value, true);
+            }
+        } else if index == BuiltinFunction::Format.call_index() {
+            if let Some(format_args) = args.get_mut(1) {
+                plain(format_args, true);
+            }
+        } else if Some(index) == self.print {
+            for arg in args {
+                plain(arg, true);
+            }
+        }
+        None
+    }
+
+    fn for_in_iterable(&self, iterable: &mut Expr) {
+        plain(iterable, false);
+    }
+}
+
+/// Wraps `expr` in `lua::plain(expr, deep)`.
+fn plain(expr: &mut Expr, deep: bool) {
+    let value = std::mem::replace(expr, Expr::Null);
+    *expr = Expr::Call(
+        BuiltinFunction::LuaPlain.call_index(),
+        vec![value, Expr::Bool(deep)],
+    );
+}
@@ -1,5 +1,8 @@
 use std::collections::HashMap;
 
+use super::CompileWarning;
+use super::source_map::SourceId;
+
 /// Shared frontend-independent program representation that all source
 /// frontends lower into before bytecode emission.
 #[derive(Clone, Debug)]
@@ -7,14 +10,96 @@ pub struct ClosureExpr {
     pub param_slots: Vec<u8>,
     pub capture_copies: Vec<(u8, u8)>,
     pub body: Box<Expr>,
+    /// Local that carries the result of an early `return`; `None` when the
+    /// body never returns early.
+    pub return_slot: Option<u8>,
 }
 
 #[derive(Clone, Debug)]
 pub enum MatchPattern {
+    Wildcard,
+    /// Copies the matched value into a local.
+    Binding(u8),
     Int(i64),
+    Float(f64),
     String(String),
     Null,
+    Range(MatchRangePattern),
     Type(MatchTypePattern),
+    Array(MatchArrayPattern),
+    /// Matches maps containing every listed key; extra keys are allowed.
+    Map(Vec<(MatchFieldKey, MatchSubPattern)>),
+    Variant(MatchVariantPattern),
+    Or(Vec<MatchPattern>),
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchArm {
+    pub pattern: MatchPattern,
+    pub guard: Option<Expr>,
+    pub body: Expr,
+}
+
+/// Pattern applied to a component of the matched value. The component is
+/// first stored in `slot`, which doubles as the local for a plain binding.
+#[derive(Clone, Debug)]
+pub struct MatchSubPattern {
+    pub slot: u8,
+    pub pattern: MatchPattern,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum MatchRangeBound {
+    Int(i64),
+    Float(f64),
+}
+
+#[derive(Clone, Debug)]
+pub struct MatchRangePattern {
+    pub start: Option<MatchRangeBound>,
+    pub end: Option<MatchRangeBound>,
+    pub inclusive_end: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum MatchArrayIndex {
+    FromStart(usize),
+    FromEnd(usize),
+}
+
+/// Matches arrays of exactly `fixed_len` elements, or at least that many when
+/// `rest` is present. Wildcard elements are omitted from `elements`.
+#[derive(Clone, Debug)]
+pub struct MatchArrayPattern {
+    pub fixed_len: usize,
+    pub elements: Vec<(MatchArrayIndex, MatchSubPattern)>,
+    pub rest: Option<MatchArrayRest>,
+}
+
+/// The `..` part of an array pattern, starting at `start`; `binding`
+/// receives the elements it spans as an array.
+#[derive(Clone, Debug)]
+pub struct MatchArrayRest {
+    pub start: usize,
+    pub binding: Option<u8>,
+}
+
+/// Map key under which struct and enum values record their type, e.g.
+/// `"Point"` or `"Shape::Circle"`. It is always the first entry of the map.
+pub const TYPE_TAG_KEY: &str = "__type";
+
+/// Matches a struct or enum value by its type tag, then each listed payload
+/// field against its sub-pattern.
+#[derive(Clone, Debug)]
+pub struct MatchVariantPattern {
+    pub tag: String,
+    pub fields: Vec<(MatchFieldKey, MatchSubPattern)>,
+}
+
+#[derive(Clone, Debug)]
+pub enum MatchFieldKey {
+    Position(usize),
+    Name(String),
 }
 
 #[derive(Clone, Debug)]
@@ -62,7 +147,8 @@ pub enum Expr {
         value_slot: u8,
         result_slot: u8,
         value: Box<Expr>,
-        arms: Vec<(MatchPattern, Expr)>,
+        arms: Vec<MatchArm>,
+        /// Result when no arm matches.
         default: Box<Expr>,
     },
     Block {
@@ -119,12 +205,37 @@ pub enum Stmt {
         body: Vec<Stmt>,
         line: u32,
     },
+    /// Iterates a snapshot of an array's elements or a map's entries, binding
+    /// the index/key and element/value of each step.
+    ForIn {
+        iterable: Expr,
+        key_slot: Option<u8>,
+        value_slot: Option<u8>,
+        state: ForInState,
+        body: Vec<Stmt>,
+        line: u32,
+    },
     Break {
         line: u32,
     },
     Continue {
         line: u32,
     },
+    /// Leaves the innermost inlined function or closure with `expr`.
+    Return {
+        expr: Expr,
+        line: u32,
+    },
+}
+
+/// Hidden locals driving a `Stmt::ForIn` loop. `keys` is only written when
+/// the loop binds a key, so frontends may alias it to `values` otherwise.
+#[derive(Clone, Copy, Debug)]
+pub struct ForInState {
+    pub keys: u8,
+    pub values: u8,
+    pub index: u8,
+    pub len: u8,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -141,6 +252,11 @@ pub struct FunctionImpl {
     pub param_slots: Vec<u8>,
     pub body_stmts: Vec<Stmt>,
     pub body_expr: Expr,
+    /// Line of `body_expr`, or 0 when the body falls off its end.
+    pub body_line: u32,
+    /// Local that carries the result of an early `return`; `None` when the
+    /// body never returns early.
+    pub return_slot: Option<u8>,
 }
 
 #[derive(Clone, Debug)]
@@ -148,16 +264,194 @@ pub struct FrontendIr {
     pub stmts: Vec<Stmt>,
     pub locals: usize,
     pub local_bindings: Vec<(String, u8)>,
+    /// Names of function parameters and of locals bound inside functions,
+    /// closures, and match arms, which all live in hidden slots.
+    pub scoped_bindings: Vec<(String, u8)>,
     pub functions: Vec<FunctionDecl>,
     pub function_impls: HashMap<u16, FunctionImpl>,
+    pub warnings: Vec<CompileWarning>,
+}
+
+/// A file that contributed code to a [`LinkedIr`]. Its index in
+/// `LinkedIr::sources` is the id its statements and functions carry.
+#[derive(Clone, Debug)]
+pub struct LinkedSource {
+    pub name: String,
+    pub text: String,
 }
 
 #[derive(Clone, Debug)]
 pub struct LinkedIr {
-    pub source: String,
+    /// Entry file first, then the modules it imports.
+    pub sources: Vec<LinkedSource>,
     pub stmts: Vec<Stmt>,
+    /// Source of each entry in `stmts`.
+    pub stmt_sources: Vec<SourceId>,
+    /// Source each function implementation was written in.
+    pub function_sources: HashMap<u16, SourceId>,
     pub locals: usize,
     pub local_bindings: Vec<(String, u8)>,
+    /// Names of function parameters and of locals bound inside functions,
+    /// closures, and match arms, which all live in hidden slots.
+    pub scoped_bindings: Vec<(String, u8)>,
     pub functions: Vec<FunctionDecl>,
     pub function_impls: HashMap<u16, FunctionImpl>,
+    pub warnings: Vec<CompileWarning>,
+}
+
+impl FrontendIr {
+    /// Rewrites every statement and warning line through `map`, e.g. from
+    /// lowered text back to the source a frontend was given.
+    pub(super) fn remap_lines(&mut self, map: impl Fn(usize) -> usize) {
+        let map_line = |line: &mut u32| {
+            let mapped = map(*line as usize);
+            *line = u32::try_from(mapped).unwrap_or(u32::MAX);
+        };
+        remap_stmt_lines(&mut self.stmts, &map_line);
+        for function in self.function_impls.values_mut() {
+            remap_stmt_lines(&mut function.body_stmts, &map_line);
+            remap_expr_lines(&mut function.body_expr, &map_line);
+            if function.body_line != 0 {
+                map_line(&mut function.body_line);
+            }
+        }
+        for warning in &mut self.warnings {
+            warning.line = map(warning.line);
+        }
+    }
+}
+
+fn remap_stmt_lines(stmts: &mut [Stmt], map: &impl Fn(&mut u32)) {
+    for stmt in stmts {
+        remap_stmt_line(stmt, map);
+    }
+}
+
+fn remap_stmt_line(stmt: &mut Stmt, map: &impl Fn(&mut u32)) {
+    match stmt {
+        Stmt::Noop { line }
+        | Stmt::FuncDecl { line, .. }
+        | Stmt::Break { line }
+        | Stmt::Continue { line } => map(line),
+        Stmt::Let { expr, line, .. }
+        | Stmt::Assign { expr, line, .. }
+        | Stmt::Expr { expr, line }
+        | Stmt::Return { expr, line } => {
+            map(line);
+            remap_expr_lines(expr, map);
+        }
+        Stmt::ClosureLet { line, closure } => {
+            map(line);
+            remap_expr_lines(&mut closure.body, map);
+        }
+        Stmt::IfElse {
+            condition,
+            then_branch,
+            else_branch,
+            line,
+        } => {
+            map(line);
+            remap_expr_lines(condition, map);
+            remap_stmt_lines(then_branch, map);
+            remap_stmt_lines(else_branch, map);
+        }
+        Stmt::For {
+            init,
+            condition,
+            post,
+            body,
+            line,
+        } => {
+            map(line);
+            remap_stmt_line(init, map);
+            remap_expr_lines(condition, map);
+            remap_stmt_line(post, map);
+            remap_stmt_lines(body, map);
+        }
+        Stmt::While {
+            condition,
+            body,
+            line,
+        } => {
+            map(line);
+            remap_expr_lines(condition, map);
+            remap_stmt_lines(body, map);
+        }
+        Stmt::ForIn {
+            iterable,
+            body,
+            line,
+            ..
+        } => {
+            map(line);
+            remap_expr_lines(iterable, map);
+            remap_stmt_lines(body, map);
+        }
+    }
+}
+
+fn remap_expr_lines(expr: &mut Expr, map: &impl Fn(&mut u32)) {
+    match expr {
+        Expr::Null
+        | Expr::Int(_)
+        | Expr::Float(_)
+        | Expr::Bool(_)
+        | Expr::String(_)
+        | Expr::FunctionRef(_)
+        | Expr::Var(_) => {}
+        Expr::Call(_, args) | Expr::LocalCall(_, args) => {
+            for arg in args {
+                remap_expr_lines(arg, map);
+            }
+        }
+        Expr::Closure(closure) => remap_expr_lines(&mut closure.body, map),
+        Expr::ClosureCall(closure, args) => {
+            remap_expr_lines(&mut closure.body, map);
+            for arg in args {
+                remap_expr_lines(arg, map);
+            }
+        }
+        Expr::Add(lhs, rhs)
+        | Expr::Sub(lhs, rhs)
+        | Expr::Mul(lhs, rhs)
+        | Expr::Div(lhs, rhs)
+        | Expr::Mod(lhs, rhs)
+        | Expr::And(lhs, rhs)
+        | Expr::Or(lhs, rhs)
+        | Expr::Eq(lhs, rhs)
+        | Expr::Lt(lhs, rhs)
+        | Expr::Gt(lhs, rhs) => {
+            remap_expr_lines(lhs, map);
+            remap_expr_lines(rhs, map);
+        }
+        Expr::Neg(inner) | Expr::Not(inner) => remap_expr_lines(inner, map),
+        Expr::IfElse {
+            condition,
+            then_expr,
+            else_expr,
+        } => {
+            remap_expr_lines(condition, map);
+            remap_expr_lines(then_expr, map);
+            remap_expr_lines(else_expr, map);
+        }
+        Expr::Match {
+            value,
+            arms,
+            default,
+            ..
+        } => {
+            remap_expr_lines(value, map);
+            for arm in arms {
+                if let Some(guard) = &mut arm.guard {
+                    remap_expr_lines(guard, map);
+                }
+                remap_expr_lines(&mut arm.body, map);
+            }
+            remap_expr_lines(default, map);
+        }
+        Expr::Block { stmts, expr } => {
+            remap_stmt_lines(stmts, map);
+            remap_expr_lines(expr, map);
+        }
+    }
 }
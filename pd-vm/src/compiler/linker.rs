@@ -5,12 +5,18 @@ use crate::builtins::BuiltinFunction;
 
 use super::{
     ParseError, SourceError, SourcePathError,
-    ir::{Expr, FrontendIr, FunctionDecl, FunctionImpl, LinkedIr, Stmt},
+    ir::{
+        Expr, FrontendIr, FunctionDecl, FunctionImpl, LinkedIr, LinkedSource, MatchPattern,
+        MatchSubPattern, Stmt,
+    },
+    source_map::SourceId,
 };
 
 pub(super) struct ParsedUnit {
     pub(super) parsed: FrontendIr,
     pub(super) scope_prefix: Option<String>,
+    pub(super) source_name: String,
+    pub(super) source_text: String,
 }
 
 pub(super) fn sanitize_scope_prefix(path: &Path) -> String {
@@ -28,18 +34,36 @@ pub(super) fn sanitize_scope_prefix(path: &Path) -> String {
         .collect()
 }
 
-pub(super) fn merge_units(
-    root_source: String,
-    units: Vec<ParsedUnit>,
-) -> Result<LinkedIr, SourcePathError> {
+pub(super) fn merge_units(units: Vec<ParsedUnit>) -> Result<LinkedIr, SourcePathError> {
+    // The entry unit (the one without a scope prefix) is always source 0.
+    let mut sources = Vec::with_capacity(units.len());
+    if let Some(root) = units.iter().find(|unit| unit.scope_prefix.is_none()) {
+        sources.push(LinkedSource {
+            name: root.source_name.clone(),
+            text: root.source_text.clone(),
+        });
+    }
     let mut merged_stmts = Vec::new();
+    let mut stmt_sources = Vec::new();
+    let mut function_sources = HashMap::<u16, SourceId>::new();
     let mut merged_local_bindings = Vec::new();
+    let mut merged_scoped_bindings = Vec::new();
     let mut merged_functions = Vec::new();
     let mut merged_function_impls = HashMap::<u16, FunctionImpl>::new();
     let mut function_index_by_name = HashMap::<String, u16>::new();
+    let mut merged_warnings = Vec::new();
     let mut local_base = 0usize;
 
     for unit in units {
+        let source_id = if unit.scope_prefix.is_none() {
+            0
+        } else {
+            sources.push(LinkedSource {
+                name: unit.source_name,
+                text: unit.source_text,
+            });
+            (sources.len() - 1) as SourceId
+        };
         let function_map = remap_functions(
             &unit.parsed.functions,
             &mut merged_functions,
@@ -52,6 +76,7 @@ pub(super) fn merge_units(
         for stmt in &mut remapped_stmts {
             remap_stmt_indices(stmt, unit_local_base, &function_map)?;
         }
+        stmt_sources.extend(std::iter::repeat_n(source_id, remapped_stmts.len()));
         merged_stmts.extend(remapped_stmts);
 
         for (name, index) in unit.parsed.local_bindings {
@@ -63,6 +88,16 @@ pub(super) fn merge_units(
             };
             merged_local_bindings.push((scoped_name, remapped_index));
         }
+        for (name, index) in unit.parsed.scoped_bindings {
+            merged_scoped_bindings.push((name, remap_local_index(index, unit_local_base)?));
+        }
+
+        merged_warnings.extend(unit.parsed.warnings.into_iter().map(|mut warning| {
+            if let Some(prefix) = &unit.scope_prefix {
+                warning.message = format!("in module '{prefix}': {}", warning.message);
+            }
+            warning
+        }));
 
         for (unit_index, mut function_impl) in unit.parsed.function_impls {
             let merged_index = function_map.get(&unit_index).copied().ok_or_else(|| {
@@ -77,10 +112,14 @@ pub(super) fn merge_units(
             for param_slot in &mut function_impl.param_slots {
                 *param_slot = remap_local_index(*param_slot, unit_local_base)?;
             }
+            if let Some(return_slot) = &mut function_impl.return_slot {
+                *return_slot = remap_local_index(*return_slot, unit_local_base)?;
+            }
             for stmt in &mut function_impl.body_stmts {
                 remap_stmt_indices(stmt, unit_local_base, &function_map)?;
             }
             remap_expr_indices(&mut function_impl.body_expr, unit_local_base, &function_map)?;
+            function_sources.insert(merged_index, source_id);
             if merged_function_impls
                 .insert(merged_index, function_impl)
                 .is_some()
@@ -114,12 +153,16 @@ pub(super) fn merge_units(
     }
 
     Ok(LinkedIr {
-        source: root_source,
+        sources,
         stmts: merged_stmts,
+        stmt_sources,
+        function_sources,
         locals: local_base,
         local_bindings: merged_local_bindings,
+        scoped_bindings: merged_scoped_bindings,
         functions: merged_functions,
         function_impls: merged_function_impls,
+        warnings: merged_warnings,
     })
 }
 
@@ -171,6 +214,56 @@ fn remap_functions(
     Ok(map)
 }
 
+fn remap_match_pattern_indices(
+    pattern: &mut MatchPattern,
+    local_base: usize,
+) -> Result<(), SourcePathError> {
+    match pattern {
+        MatchPattern::Binding(slot) => {
+            *slot = remap_local_index(*slot, local_base)?;
+        }
+        MatchPattern::Array(array) => {
+            for (_, sub) in &mut array.elements {
+                remap_match_sub_pattern_indices(sub, local_base)?;
+            }
+            if let Some(slot) = array.rest.as_mut().and_then(|rest| rest.binding.as_mut()) {
+                *slot = remap_local_index(*slot, local_base)?;
+            }
+        }
+        MatchPattern::Map(entries) => {
+            for (_, sub) in entries {
+                remap_match_sub_pattern_indices(sub, local_base)?;
+            }
+        }
+        MatchPattern::Variant(variant) => {
+            for (_, sub) in &mut variant.fields {
+                remap_match_sub_pattern_indices(sub, local_base)?;
+            }
+        }
+        MatchPattern::Or(alternatives) => {
+            for alternative in alternatives {
+                remap_match_pattern_indices(alternative, local_base)?;
+            }
+        }
+        MatchPattern::Wildcard
+        | MatchPattern::Int(_)
+        | MatchPattern::Float(_)
+        | MatchPattern::String(_)
+        | MatchPattern::Null
+        | MatchPattern::Range(_)
+        | MatchPattern::Type(_) => {}
+    }
+    Ok(())
+}
+
+fn remap_match_sub_pattern_indices(
+    sub: &mut MatchSubPattern,
+    local_base: usize,
+) -> Result<(), SourcePathError> {
+    sub.slot = remap_local_index(sub.slot, local_base)?;
+    remap_match_pattern_indices(&mut sub.pattern, local_base)
+}
+
 fn remap_local_index(index: u8, local_base: usize) -> Result<u8, SourcePathError> {
     let remapped = (index as usize).checked_add(local_base).ok_or_else(|| {
         SourcePathError::Source(SourceError::Parse(ParseError {
@@ -252,7 +345,34 @@ fn remap_stmt_indices(
                 remap_stmt_indices(stmt, local_base, function_map)?;
             }
         }
+        Stmt::ForIn {
+            iterable,
+            key_slot,
+            value_slot,
+            state,
+            body,
+            ..
+        } => {
+            remap_expr_indices(iterable, local_base, function_map)?;
+            for slot in [key_slot, value_slot].into_iter().flatten() {
+                *slot = remap_local_index(*slot, local_base)?;
+            }
+            for slot in [
+                &mut state.keys,
+                &mut state.values,
+                &mut state.index,
+                &mut state.len,
+            ] {
+                *slot = remap_local_index(*slot, local_base)?;
+            }
+            for stmt in body {
+                remap_stmt_indices(stmt, local_base, function_map)?;
+            }
+        }
         Stmt::Break { .. } | Stmt::Continue { .. } => {}
+        Stmt::Return { expr, .. } => {
+            remap_expr_indices(expr, local_base, function_map)?;
+        }
     }
     Ok(())
 }
@@ -303,6 +423,9 @@ fn remap_expr_indices(
             for param_slot in &mut closure.param_slots {
                 *param_slot = remap_local_index(*param_slot, local_base)?;
             }
+            if let Some(return_slot) = &mut closure.return_slot {
+                *return_slot = remap_local_index(*return_slot, local_base)?;
+            }
             for (source_index, captured_slot) in &mut closure.capture_copies {
                 *source_index = remap_local_index(*source_index, local_base)?;
                 *captured_slot = remap_local_index(*captured_slot, local_base)?;
@@ -313,6 +436,9 @@ fn remap_expr_indices(
             for param_slot in &mut closure.param_slots {
                 *param_slot = remap_local_index(*param_slot, local_base)?;
             }
+            if let Some(return_slot) = &mut closure.return_slot {
+                *return_slot = remap_local_index(*return_slot, local_base)?;
+            }
             for (source_index, captured_slot) in &mut closure.capture_copies {
                 *source_index = remap_local_index(*source_index, local_base)?;
                 *captured_slot = remap_local_index(*captured_slot, local_base)?;
@@ -360,8 +486,12 @@ fn remap_expr_indices(
             *value_slot = remap_local_index(*value_slot, local_base)?;
             *result_slot = remap_local_index(*result_slot, local_base)?;
             remap_expr_indices(value, local_base, function_map)?;
-            for (_, arm_expr) in arms {
-                remap_expr_indices(arm_expr, local_base, function_map)?;
+            for arm in arms {
+                remap_match_pattern_indices(&mut arm.pattern, local_base)?;
+                if let Some(guard) = &mut arm.guard {
+                    remap_expr_indices(guard, local_base, function_map)?;
+                }
+                remap_expr_indices(&mut arm.body, local_base, function_map)?;
             }
             remap_expr_indices(default, local_base, function_map)?;
         }
@@ -1,12 +1,12 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
-use self::source_map::{SourceMap, Span};
-use crate::assembler::{Assembler, AssemblerError};
+use self::source_map::{SourceId, SourceMap, Span};
+use crate::assembler::{Assembler, AssemblerError, assemble};
 use crate::builtins::BuiltinFunction;
 #[cfg(feature = "runtime")]
 use crate::vm::Vm;
-use crate::{HostImport, Program, Value};
+use crate::{HostImport, OpCode, Program, Value};
 
 #[derive(Debug)]
 pub enum CompileError {
@@ -18,6 +18,7 @@ pub enum CompileError {
     CallableArityMismatch { expected: usize, got: usize },
     BreakOutsideLoop,
     ContinueOutsideLoop,
+    ReturnOutsideFunction,
     InlineFunctionRecursion(String),
 }
 
@@ -29,6 +30,20 @@ pub struct ParseError {
     pub code: Option<String>,
 }
 
+/// Non-fatal diagnostic reported while compiling, such as an unreachable
+/// `match` arm. Compilation still produces a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileWarning {
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for CompileWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "warning: line {}: {}", self.line, self.message)
+    }
+}
+
 impl ParseError {
     pub fn new(message: impl Into<String>) -> Self {
         Self {
@@ -114,6 +129,7 @@ pub enum SourcePathError {
     ImportCycle(PathBuf),
     NonRustScriptModule(PathBuf),
     ImportWithoutParent(PathBuf),
+    UnresolvedModule(String),
     InvalidImportSyntax {
         path: PathBuf,
         line: usize,
@@ -129,7 +145,7 @@ impl std::fmt::Display for SourcePathError {
             SourcePathError::MissingExtension => write!(f, "source file must have an extension"),
             SourcePathError::UnsupportedExtension(ext) => write!(
                 f,
-                "unsupported source extension '.{ext}', expected .rss, .js, .lua, or .scm"
+                "unsupported source extension '.{ext}', expected .rss, .js, .lua, .scm, or .pda"
             ),
             SourcePathError::ImportCycle(path) => {
                 write!(f, "import cycle detected at '{}'", path.display())
@@ -142,6 +158,10 @@ impl std::fmt::Display for SourcePathError {
                 "cannot resolve import from '{}': missing parent directory",
                 path.display()
             ),
+            SourcePathError::UnresolvedModule(spec) => write!(
+                f,
+                "cannot resolve module '{spec}' without a source file; only std modules are available"
+            ),
             SourcePathError::InvalidImportSyntax {
                 path,
                 line,
@@ -178,6 +198,8 @@ pub enum SourceFlavor {
     JavaScript,
     Lua,
     Scheme,
+    /// Textual assembly (`.pda`), assembled as written rather than compiled.
+    Assembly,
 }
 
 impl SourceFlavor {
@@ -187,10 +209,24 @@ impl SourceFlavor {
             "js" => Some(Self::JavaScript),
             "lua" => Some(Self::Lua),
             "scm" => Some(Self::Scheme),
+            "pda" => Some(Self::Assembly),
             _ => None,
         }
     }
 
+    /// Parses a flavor's name (`rustscript`, `javascript`, `lua`, `scheme`,
+    /// `assembly`) or file extension.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "rustscript" => Some(Self::RustScript),
+            "javascript" => Some(Self::JavaScript),
+            "lua" => Some(Self::Lua),
+            "scheme" => Some(Self::Scheme),
+            "assembly" => Some(Self::Assembly),
+            other => Self::from_extension(other),
+        }
+    }
+
     fn from_path(path: &Path) -> Result<Self, SourcePathError> {
         let ext = path
             .extension()
@@ -211,18 +247,24 @@ mod linker;
 mod parser;
 mod source_loader;
 pub mod source_map;
+mod stdlib;
+pub mod transpile;
 
 use ir::LinkedIr;
-use linker::merge_units;
+use linker::{ParsedUnit, merge_units};
 
 pub use ir::{
-    ClosureExpr, Expr, FrontendIr, FunctionDecl, FunctionImpl, MatchPattern, MatchTypePattern, Stmt,
+    ClosureExpr, Expr, ForInState, FrontendIr, FunctionDecl, FunctionImpl, MatchArm,
+    MatchArrayIndex, MatchArrayPattern, MatchArrayRest, MatchFieldKey, MatchPattern,
+    MatchRangeBound, MatchRangePattern, MatchSubPattern, MatchTypePattern, MatchVariantPattern,
+    Stmt, TYPE_TAG_KEY,
 };
 
 pub struct CompiledProgram {
     pub program: Program,
     pub locals: usize,
     pub functions: Vec<FunctionDecl>,
+    pub warnings: Vec<CompileWarning>,
 }
 
 impl CompiledProgram {
@@ -234,12 +276,16 @@ impl CompiledProgram {
 
 fn compile_parsed_output(parsed: LinkedIr) -> Result<CompiledProgram, SourceError> {
     let LinkedIr {
-        source,
+        sources,
         stmts,
+        stmt_sources,
+        function_sources,
         locals,
         local_bindings,
+        scoped_bindings,
         functions,
         function_impls,
+        warnings,
     } = parsed;
 
     let mut runtime_import_functions: Vec<FunctionDecl> = functions
@@ -267,7 +313,11 @@ fn compile_parsed_output(parsed: LinkedIr) -> Result<CompiledProgram, SourceErro
         .collect::<Vec<_>>();
 
     let mut compiler = Compiler::new();
-    compiler.set_source(source);
+    for source in sources {
+        compiler.add_source(source.name, source.text);
+    }
+    compiler.set_stmt_sources(stmt_sources);
+    compiler.set_function_sources(function_sources);
     compiler.set_function_impls(function_impls);
     compiler.set_call_index_remap(call_index_remap);
     for func in &functions {
@@ -276,6 +326,9 @@ fn compile_parsed_output(parsed: LinkedIr) -> Result<CompiledProgram, SourceErro
     for (name, index) in local_bindings {
         compiler.add_local_debug(name, index);
     }
+    for (name, index) in scoped_bindings {
+        compiler.add_scoped_local_debug(name, index);
+    }
     let mut program = compiler
         .compile_program(&stmts)
         .map_err(SourceError::Compile)?;
@@ -290,9 +343,24 @@ fn compile_parsed_output(parsed: LinkedIr) -> Result<CompiledProgram, SourceErro
         program,
         locals,
         functions: visible_runtime_import_functions,
+        warnings,
     })
 }
 
+fn match_range_bound_value(bound: MatchRangeBound) -> Value {
+    match bound {
+        MatchRangeBound::Int(value) => Value::Int(value),
+        MatchRangeBound::Float(value) => Value::Float(value),
+    }
+}
+
+fn match_field_key_value(key: &MatchFieldKey) -> Value {
+    match key {
+        MatchFieldKey::Position(position) => Value::Int(*position as i64),
+        MatchFieldKey::Name(name) => Value::String(name.clone()),
+    }
+}
+
 fn is_compiler_primitive_import(name: &str) -> bool {
     name.starts_with("__prim_")
 }
@@ -313,35 +381,139 @@ fn compile_source_with_flavor_impl(
     source: &str,
     flavor: SourceFlavor,
 ) -> Result<CompiledProgram, SourceError> {
+    if flavor == SourceFlavor::Assembly {
+        return compile_assembly(source);
+    }
+    compile_parsed_output(link_source(source, flavor)?)
+}
+
+/// Parses `source`, and the embedded modules it imports, into one program.
+fn link_source(source: &str, flavor: SourceFlavor) -> Result<LinkedIr, SourceError> {
+    if source_loader::has_module_imports(source, flavor) {
+        return link_source_with_imports(source, flavor);
+    }
     let mut source_map = SourceMap::new();
     let source_id = source_map.add_source("<source>", source.to_string());
     let parsed = frontends::parse_source(source, flavor).map_err(|err| {
         SourceError::Parse(err.with_line_span_from_source(&source_map, source_id))
     })?;
-    compile_parsed_output(LinkedIr {
-        source: source.to_string(),
-        stmts: parsed.stmts,
-        locals: parsed.locals,
-        local_bindings: parsed.local_bindings,
-        functions: parsed.functions,
-        function_impls: parsed.function_impls,
+    let mut units = vec![ParsedUnit {
+        parsed,
+        scope_prefix: None,
+        source_name: "<source>".to_string(),
+        source_text: source.to_string(),
+    }];
+    source_loader::link_frontend_helpers(&mut units)
+        .and_then(|()| merge_units(units))
+        .map_err(source_path_error_to_source)
+}
+
+/// Assembly has no imports or IR; its `.import` directives become the host
+/// functions the program calls.
+fn compile_assembly(source: &str) -> Result<CompiledProgram, SourceError> {
+    let program = assemble(source)
+        .map_err(|err| SourceError::Parse(ParseError::at_line(err.line, err.message)))?;
+    let functions = program
+        .imports
+        .iter()
+        .enumerate()
+        .map(|(index, import)| FunctionDecl {
+            name: import.name.clone(),
+            arity: import.arity,
+            index: index as u16,
+            args: Vec::new(),
+            exported: false,
+        })
+        .collect();
+    Ok(CompiledProgram {
+        locals: assembled_local_count(&program.code),
+        program,
+        functions,
+        warnings: Vec::new(),
     })
 }
 
+/// One past the highest local index a `ldloc` / `stloc` in `code` uses.
+fn assembled_local_count(code: &[u8]) -> usize {
+    let mut locals = 0;
+    let mut ip = 0;
+    while let Some(opcode) = code.get(ip).copied().and_then(OpCode::from_byte) {
+        ip += 1;
+        match opcode {
+            OpCode::Ldloc | OpCode::Stloc => {
+                if let Some(index) = code.get(ip) {
+                    locals = locals.max(*index as usize + 1);
+                }
+                ip += 1;
+            }
+            OpCode::Ldc | OpCode::Br | OpCode::Brfalse => ip += 4,
+            OpCode::Call => ip += 3,
+            _ => {}
+        }
+    }
+    locals
+}
+
+/// Source text has no directory to import from, so its imports can only name
+/// the embedded `std` modules.
+fn link_source_with_imports(source: &str, flavor: SourceFlavor) -> Result<LinkedIr, SourceError> {
+    let resolver = source_loader::ModuleResolver::embedded_only();
+    source_loader::load_units_for_source_file(Path::new("<source>"), flavor, source, &resolver)
+        .and_then(merge_units)
+        .map_err(source_path_error_to_source)
+}
+
+/// Reports a loader error for source text, which has no file of its own, as a
+/// parse error.
+fn source_path_error_to_source(err: SourcePathError) -> SourceError {
+    match err {
+        SourcePathError::Source(err) => err,
+        SourcePathError::InvalidImportSyntax { line, message, .. } => {
+            SourceError::Parse(ParseError::at_line(line, message))
+        }
+        other => SourceError::Parse(ParseError::at_line(1, other.to_string())),
+    }
+}
+
 pub fn compile_source_file(path: impl AsRef<Path>) -> Result<CompiledProgram, SourcePathError> {
+    compile_source_file_with_search_paths(path, Vec::new())
+}
+
+/// Compiles `path`, looking up imports that are not found next to the
+/// importing file in each of `search_paths` in order.
+pub fn compile_source_file_with_search_paths(
+    path: impl AsRef<Path>,
+    search_paths: Vec<PathBuf>,
+) -> Result<CompiledProgram, SourcePathError> {
     let path = path.as_ref().to_path_buf();
-    run_with_compiler_stack(move || compile_source_file_impl(&path))
+    run_with_compiler_stack(move || compile_source_file_impl(&path, search_paths))
 }
 
-fn compile_source_file_impl(path: &Path) -> Result<CompiledProgram, SourcePathError> {
+fn compile_source_file_impl(
+    path: &Path,
+    search_paths: Vec<PathBuf>,
+) -> Result<CompiledProgram, SourcePathError> {
     let flavor = SourceFlavor::from_path(path)?;
     let source_raw = std::fs::read_to_string(path)?;
-    let (root_source, units) =
-        source_loader::load_units_for_source_file(path, flavor, &source_raw)?;
-    let merged = merge_units(root_source, units)?;
+    if flavor == SourceFlavor::Assembly {
+        return compile_assembly(&source_raw).map_err(SourcePathError::Source);
+    }
+    let merged = link_source_file(path, flavor, &source_raw, search_paths)?;
     compile_parsed_output(merged).map_err(SourcePathError::Source)
 }
 
+/// Parses the file at `path` and every module it imports into one program.
+fn link_source_file(
+    path: &Path,
+    flavor: SourceFlavor,
+    source: &str,
+    search_paths: Vec<PathBuf>,
+) -> Result<LinkedIr, SourcePathError> {
+    let resolver = source_loader::ModuleResolver::new(search_paths);
+    let units = source_loader::load_units_for_source_file(path, flavor, source, &resolver)?;
+    merge_units(units)
+}
+
 fn run_with_compiler_stack<T, F>(f: F) -> T
 where
     T: Send + 'static,
@@ -371,10 +543,16 @@ pub struct Compiler {
     assembler: Assembler,
     next_label_id: u32,
     loop_stack: Vec<LoopContext>,
+    return_stack: Vec<ReturnContext>,
     function_impls: HashMap<u16, FunctionImpl>,
     call_index_remap: HashMap<u16, u16>,
     inline_call_stack: Vec<u16>,
     callable_bindings: HashMap<u8, CallableBinding>,
+    stmt_sources: Vec<SourceId>,
+    function_sources: HashMap<u16, SourceId>,
+    function_names: HashMap<u16, String>,
+    /// Names of the hidden slots function parameters and locals live in.
+    scoped_local_names: HashMap<u8, String>,
 }
 
 struct LoopContext {
@@ -382,6 +560,12 @@ struct LoopContext {
     break_label: String,
 }
 
+/// Exit of an inlined function or closure body that contains early returns.
+struct ReturnContext {
+    slot: u8,
+    end_label: String,
+}
+
 #[derive(Clone)]
 enum CallableBinding {
     Closure(ClosureExpr),
@@ -400,10 +584,15 @@ impl Compiler {
             assembler: Assembler::new(),
             next_label_id: 0,
             loop_stack: Vec::new(),
+            return_stack: Vec::new(),
             function_impls: HashMap::new(),
             call_index_remap: HashMap::new(),
             inline_call_stack: Vec::new(),
             callable_bindings: HashMap::new(),
+            stmt_sources: Vec::new(),
+            function_sources: HashMap::new(),
+            function_names: HashMap::new(),
+            scoped_local_names: HashMap::new(),
         }
     }
 
@@ -411,7 +600,22 @@ impl Compiler {
         self.assembler.set_source(source);
     }
 
+    /// Registers a file for debug info; the first one added is the main source.
+    pub fn add_source(&mut self, name: String, text: String) -> SourceId {
+        self.assembler.add_source(name, Some(text))
+    }
+
+    /// Source of each top-level statement passed to [`Compiler::compile_program`].
+    pub fn set_stmt_sources(&mut self, stmt_sources: Vec<SourceId>) {
+        self.stmt_sources = stmt_sources;
+    }
+
+    pub fn set_function_sources(&mut self, function_sources: HashMap<u16, SourceId>) {
+        self.function_sources = function_sources;
+    }
+
     pub fn add_function_debug(&mut self, func: &FunctionDecl) {
+        self.function_names.insert(func.index, func.name.clone());
         self.assembler
             .add_function(func.name.clone(), func.args.clone());
     }
@@ -420,6 +624,12 @@ impl Compiler {
         self.assembler.add_local(name, index);
     }
 
+    /// Names a hidden slot; inlined calls of a function list the named
+    /// slots it binds as the locals of their frame.
+    pub fn add_scoped_local_debug(&mut self, name: String, index: u8) {
+        self.scoped_local_names.entry(index).or_insert(name);
+    }
+
     pub fn set_function_impls(&mut self, function_impls: HashMap<u16, FunctionImpl>) {
         self.function_impls = function_impls;
     }
@@ -429,7 +639,12 @@ impl Compiler {
     }
 
     pub fn compile_program(mut self, stmts: &[Stmt]) -> Result<Program, CompileError> {
-        self.compile_stmts(stmts)?;
+        for (index, stmt) in stmts.iter().enumerate() {
+            if let Some(source_id) = self.stmt_sources.get(index).copied() {
+                self.assembler.set_current_source(source_id);
+            }
+            self.compile_stmt(stmt)?;
+        }
         self.assembler.ret();
         self.assembler
             .finish_program()
@@ -549,6 +764,58 @@ impl Compiler {
                     .map_err(CompileError::Assembler)?;
                 self.callable_bindings = callable_snapshot;
             }
+            Stmt::ForIn {
+                iterable,
+                key_slot,
+                value_slot,
+                state,
+                body,
+                line,
+            } => {
+                let callable_snapshot = self.callable_bindings.clone();
+                self.assembler.mark_line(*line);
+                self.compile_for_in_setup(iterable, *key_slot, *value_slot, *state)?;
+                let start_label = self.fresh_label("for_in_start");
+                let continue_label = self.fresh_label("for_in_continue");
+                let end_label = self.fresh_label("for_in_end");
+                self.assembler
+                    .label(&start_label)
+                    .map_err(CompileError::Assembler)?;
+                self.assembler.ldloc(state.index);
+                self.assembler.ldloc(state.len);
+                self.assembler.clt();
+                self.assembler.brfalse_label(&end_label);
+                if let Some(key_slot) = key_slot {
+                    self.assembler.ldloc(state.keys);
+                    self.assembler.ldloc(state.index);
+                    self.assembler.call(BuiltinFunction::Get.call_index(), 2);
+                    self.assembler.stloc(*key_slot);
+                }
+                if let Some(value_slot) = value_slot {
+                    self.assembler.ldloc(state.values);
+                    self.assembler.ldloc(state.index);
+                    self.assembler.call(BuiltinFunction::Get.call_index(), 2);
+                    self.assembler.stloc(*value_slot);
+                }
+                self.loop_stack.push(LoopContext {
+                    continue_label: continue_label.clone(),
+                    break_label: end_label.clone(),
+                });
+                self.compile_stmts(body)?;
+                self.loop_stack.pop();
+                self.assembler
+                    .label(&continue_label)
+                    .map_err(CompileError::Assembler)?;
+                self.assembler.ldloc(state.index);
+                self.assembler.push_const(Value::Int(1));
+                self.assembler.add();
+                self.assembler.stloc(state.index);
+                self.assembler.br_label(&start_label);
+                self.assembler
+                    .label(&end_label)
+                    .map_err(CompileError::Assembler)?;
+                self.callable_bindings = callable_snapshot;
+            }
             Stmt::Break { line } => {
                 self.assembler.mark_line(*line);
                 let loop_ctx = self
@@ -565,6 +832,22 @@ impl Compiler {
                     .ok_or(CompileError::ContinueOutsideLoop)?;
                 self.assembler.br_label(&loop_ctx.continue_label);
             }
+            Stmt::Return { expr, line } => {
+                self.assembler.mark_line(*line);
+                let slot = self
+                    .return_stack
+                    .last()
+                    .map(|ctx| ctx.slot)
+                    .ok_or(CompileError::ReturnOutsideFunction)?;
+                self.compile_expr(expr)?;
+                self.assembler.stloc(slot);
+                let end_label = &self
+                    .return_stack
+                    .last()
+                    .expect("return context checked above")
+                    .end_label;
+                self.assembler.br_label(end_label);
+            }
         }
         Ok(())
     }
@@ -726,11 +1009,14 @@ impl Compiler {
                 self.compile_expr(value)?;
                 self.assembler.stloc(*value_slot);
                 let end_label = self.fresh_label("match_end");
-                for (pattern, arm_expr) in arms {
+                for arm in arms {
                     let next_label = self.fresh_label("match_next");
-                    self.compile_match_pattern_condition(*value_slot, pattern)?;
-                    self.assembler.brfalse_label(&next_label);
-                    self.compile_expr(arm_expr)?;
+                    self.compile_match_pattern(*value_slot, &arm.pattern, &next_label)?;
+                    if let Some(guard) = &arm.guard {
+                        self.compile_expr(guard)?;
+                        self.assembler.brfalse_label(&next_label);
+                    }
+                    self.compile_expr(&arm.body)?;
                     self.assembler.stloc(*result_slot);
                     self.assembler.br_label(&end_label);
                     self.assembler
@@ -816,10 +1102,37 @@ impl Compiler {
             )));
         }
         self.inline_call_stack.push(index);
-        let result = (|| -> Result<(), CompileError> {
-            self.compile_stmts(&function_impl.body_stmts)?;
-            self.compile_expr(&function_impl.body_expr)
-        })();
+        let caller_source = self.assembler.current_source();
+        let caller_line = self.assembler.last_marked_line();
+        let function_name = self
+            .function_names
+            .get(&index)
+            .cloned()
+            .unwrap_or_else(|| format!("fn#{index}"));
+        self.assembler
+            .enter_inline_frame(function_name, caller_line.unwrap_or(0));
+        let mut slots = function_impl.param_slots.clone();
+        bound_slots(&function_impl.body_stmts, &mut slots);
+        for slot in slots {
+            if let Some(name) = self.scoped_local_names.get(&slot) {
+                self.assembler.add_inline_local(name.clone(), slot);
+            }
+        }
+        if let Some(source_id) = self.function_sources.get(&index).copied() {
+            self.assembler.set_current_source(source_id);
+        }
+        let result = self.compile_inline_body(
+            function_impl.return_slot,
+            &function_impl.body_stmts,
+            &function_impl.body_expr,
+            Some(function_impl.body_line).filter(|line| *line != 0),
+        );
+        self.assembler.exit_inline_frame();
+        // Code after the call belongs to the caller's line again.
+        self.assembler.set_current_source(caller_source);
+        if let Some(line) = caller_line {
+            self.assembler.mark_line(line);
+        }
         self.inline_call_stack.pop();
         self.callable_bindings = callable_snapshot;
         result
@@ -840,11 +1153,50 @@ impl Compiler {
         for (arg, slot) in args.iter().zip(closure.param_slots.iter()) {
             self.assign_expr_to_slot(*slot, arg)?;
         }
-        let result = self.compile_expr(&closure.body);
+        let result = self.compile_inline_body(closure.return_slot, &[], &closure.body, None);
         self.callable_bindings = callable_snapshot;
         result
     }
 
+    /// Emits an inlined function or closure body leaving its result on the
+    /// stack. Bodies with early returns funnel every exit through
+    /// `return_slot` and a shared end label.
+    fn compile_inline_body(
+        &mut self,
+        return_slot: Option<u8>,
+        stmts: &[Stmt],
+        expr: &Expr,
+        expr_line: Option<u32>,
+    ) -> Result<(), CompileError> {
+        let Some(slot) = return_slot else {
+            self.compile_stmts(stmts)?;
+            if let Some(line) = expr_line {
+                self.assembler.mark_line(line);
+            }
+            return self.compile_expr(expr);
+        };
+        let end_label = self.fresh_label("return_end");
+        self.return_stack.push(ReturnContext {
+            slot,
+            end_label: end_label.clone(),
+        });
+        let result = (|| -> Result<(), CompileError> {
+            self.compile_stmts(stmts)?;
+            if let Some(line) = expr_line {
+                self.assembler.mark_line(line);
+            }
+            self.compile_expr(expr)?;
+            self.assembler.stloc(slot);
+            self.assembler
+                .label(&end_label)
+                .map_err(CompileError::Assembler)?;
+            self.assembler.ldloc(slot);
+            Ok(())
+        })();
+        self.return_stack.pop();
+        result
+    }
+
     fn compile_direct_call(&mut self, index: u16, args: &[Expr]) -> Result<(), CompileError> {
         for arg in args {
             self.compile_expr(arg)?;
@@ -871,34 +1223,190 @@ impl Compiler {
         }
     }
 
-    fn compile_match_pattern_condition(
+    /// Emits code that falls through with the pattern's bindings stored when
+    /// the value in `value_slot` matches, and jumps to `fail_label` otherwise.
+    fn compile_match_pattern(
         &mut self,
         value_slot: u8,
         pattern: &MatchPattern,
+        fail_label: &str,
     ) -> Result<(), CompileError> {
         match pattern {
-            MatchPattern::Int(v) => {
+            MatchPattern::Wildcard => {}
+            MatchPattern::Binding(slot) => {
                 self.assembler.ldloc(value_slot);
-                self.assembler.push_const(Value::Int(*v));
-                self.assembler.ceq();
+                self.assembler.stloc(*slot);
+            }
+            MatchPattern::Int(v) => {
+                self.compile_match_equals(value_slot, Value::Int(*v), fail_label);
+            }
+            MatchPattern::Float(v) => {
+                self.compile_match_equals(value_slot, Value::Float(*v), fail_label);
             }
             MatchPattern::String(v) => {
+                self.compile_match_equals(value_slot, Value::String(v.clone()), fail_label);
+            }
+            MatchPattern::Null => {
+                self.compile_match_equals(value_slot, Value::Null, fail_label);
+            }
+            MatchPattern::Range(range) => {
+                self.compile_match_type_pattern_condition(value_slot, &MatchTypePattern::Number)?;
+                self.assembler.brfalse_label(fail_label);
+                if let Some(start) = range.start {
+                    // value >= start, i.e. !(value < start)
+                    self.assembler.ldloc(value_slot);
+                    self.assembler.push_const(match_range_bound_value(start));
+                    self.assembler.clt();
+                    self.assembler.push_const(Value::Bool(false));
+                    self.assembler.ceq();
+                    self.assembler.brfalse_label(fail_label);
+                }
+                if let Some(end) = range.end {
+                    self.assembler.ldloc(value_slot);
+                    self.assembler.push_const(match_range_bound_value(end));
+                    if range.inclusive_end {
+                        self.assembler.cgt();
+                        self.assembler.push_const(Value::Bool(false));
+                        self.assembler.ceq();
+                    } else {
+                        self.assembler.clt();
+                    }
+                    self.assembler.brfalse_label(fail_label);
+                }
+            }
+            MatchPattern::Type(type_pattern) => {
+                self.compile_match_type_pattern_condition(value_slot, type_pattern)?;
+                self.assembler.brfalse_label(fail_label);
+            }
+            MatchPattern::Array(array) => {
+                self.compile_type_name_equals(value_slot, "array");
+                self.assembler.brfalse_label(fail_label);
                 self.assembler.ldloc(value_slot);
-                self.assembler.push_const(Value::String(v.clone()));
+                self.assembler.call(BuiltinFunction::Len.call_index(), 1);
+                self.assembler
+                    .push_const(Value::Int(array.fixed_len as i64));
+                if array.rest.is_some() {
+                    self.assembler.clt();
+                    self.assembler.push_const(Value::Bool(false));
+                }
                 self.assembler.ceq();
+                self.assembler.brfalse_label(fail_label);
+
+                for (index, sub) in &array.elements {
+                    self.assembler.ldloc(value_slot);
+                    match index {
+                        MatchArrayIndex::FromStart(position) => {
+                            self.assembler.push_const(Value::Int(*position as i64));
+                        }
+                        MatchArrayIndex::FromEnd(offset) => {
+                            self.assembler.ldloc(value_slot);
+                            self.assembler.call(BuiltinFunction::Len.call_index(), 1);
+                            self.assembler.push_const(Value::Int(*offset as i64));
+                            self.assembler.sub();
+                        }
+                    }
+                    self.assembler.call(BuiltinFunction::Get.call_index(), 2);
+                    self.assembler.stloc(sub.slot);
+                    self.compile_match_pattern(sub.slot, &sub.pattern, fail_label)?;
+                }
+                if let Some(MatchArrayRest {
+                    start,
+                    binding: Some(slot),
+                }) = &array.rest
+                {
+                    self.assembler.ldloc(value_slot);
+                    self.assembler.push_const(Value::Int(*start as i64));
+                    self.assembler.ldloc(value_slot);
+                    self.assembler.call(BuiltinFunction::Len.call_index(), 1);
+                    self.assembler
+                        .push_const(Value::Int(array.fixed_len as i64));
+                    self.assembler.sub();
+                    self.assembler.call(BuiltinFunction::Slice.call_index(), 3);
+                    self.assembler.stloc(*slot);
+                }
             }
-            MatchPattern::Null => {
+            MatchPattern::Map(entries) => {
+                self.compile_type_name_equals(value_slot, "map");
+                self.assembler.brfalse_label(fail_label);
+                for (key, sub) in entries {
+                    let key = match_field_key_value(key);
+                    self.assembler.ldloc(value_slot);
+                    self.assembler.push_const(key.clone());
+                    self.assembler
+                        .call(BuiltinFunction::MapHasKey.call_index(), 2);
+                    self.assembler.brfalse_label(fail_label);
+                    self.compile_match_field_load(value_slot, key, sub, fail_label)?;
+                }
+            }
+            MatchPattern::Variant(variant) => {
+                self.compile_type_name_equals(value_slot, "map");
+                self.assembler.brfalse_label(fail_label);
                 self.assembler.ldloc(value_slot);
-                self.assembler.push_const(Value::Null);
+                self.assembler
+                    .push_const(Value::String(TYPE_TAG_KEY.to_string()));
+                self.assembler
+                    .call(BuiltinFunction::MapHasKey.call_index(), 2);
+                self.assembler.brfalse_label(fail_label);
+                self.assembler.ldloc(value_slot);
+                self.assembler
+                    .push_const(Value::String(TYPE_TAG_KEY.to_string()));
+                self.assembler.call(BuiltinFunction::Get.call_index(), 2);
+                self.assembler
+                    .push_const(Value::String(variant.tag.clone()));
                 self.assembler.ceq();
+                self.assembler.brfalse_label(fail_label);
+                for (key, sub) in &variant.fields {
+                    self.compile_match_field_load(
+                        value_slot,
+                        match_field_key_value(key),
+                        sub,
+                        fail_label,
+                    )?;
+                }
             }
-            MatchPattern::Type(type_pattern) => {
-                self.compile_match_type_pattern_condition(value_slot, type_pattern)?;
+            MatchPattern::Or(alternatives) => {
+                let matched_label = self.fresh_label("match_or_matched");
+                let (last, rest) = alternatives
+                    .split_last()
+                    .expect("or-pattern has at least one alternative");
+                for alternative in rest {
+                    let next_alternative_label = self.fresh_label("match_or_next");
+                    self.compile_match_pattern(value_slot, alternative, &next_alternative_label)?;
+                    self.assembler.br_label(&matched_label);
+                    self.assembler
+                        .label(&next_alternative_label)
+                        .map_err(CompileError::Assembler)?;
+                }
+                self.compile_match_pattern(value_slot, last, fail_label)?;
+                self.assembler
+                    .label(&matched_label)
+                    .map_err(CompileError::Assembler)?;
             }
         }
         Ok(())
     }
 
+    fn compile_match_equals(&mut self, value_slot: u8, expected: Value, fail_label: &str) {
+        self.assembler.ldloc(value_slot);
+        self.assembler.push_const(expected);
+        self.assembler.ceq();
+        self.assembler.brfalse_label(fail_label);
+    }
+
+    fn compile_match_field_load(
+        &mut self,
+        value_slot: u8,
+        key: Value,
+        sub: &MatchSubPattern,
+        fail_label: &str,
+    ) -> Result<(), CompileError> {
+        self.assembler.ldloc(value_slot);
+        self.assembler.push_const(key);
+        self.assembler.call(BuiltinFunction::Get.call_index(), 2);
+        self.assembler.stloc(sub.slot);
+        self.compile_match_pattern(sub.slot, &sub.pattern, fail_label)
+    }
+
     fn compile_match_type_pattern_condition(
         &mut self,
         value_slot: u8,
@@ -931,6 +1439,43 @@ impl Compiler {
         Ok(())
     }
 
+    /// Snapshots the iterable before a `for`-`in` loop so each step is a pair
+    /// of indexed reads: keys come from `keys` (array indices or map keys in
+    /// insertion order) and values from the array itself or the map's values.
+    fn compile_for_in_setup(
+        &mut self,
+        iterable: &Expr,
+        key_slot: Option<u8>,
+        value_slot: Option<u8>,
+        state: ForInState,
+    ) -> Result<(), CompileError> {
+        self.compile_expr(iterable)?;
+        self.assembler.stloc(state.values);
+        if key_slot.is_some() {
+            self.assembler.ldloc(state.values);
+            self.assembler.call(BuiltinFunction::Keys.call_index(), 1);
+            self.assembler.stloc(state.keys);
+        }
+        if value_slot.is_some() {
+            let ready_label = self.fresh_label("for_in_ready");
+            self.compile_type_name_equals(state.values, "map");
+            self.assembler.brfalse_label(&ready_label);
+            self.assembler.ldloc(state.values);
+            self.assembler
+                .call(BuiltinFunction::MapValues.call_index(), 1);
+            self.assembler.stloc(state.values);
+            self.assembler
+                .label(&ready_label)
+                .map_err(CompileError::Assembler)?;
+        }
+        self.assembler.ldloc(state.values);
+        self.assembler.call(BuiltinFunction::Len.call_index(), 1);
+        self.assembler.stloc(state.len);
+        self.assembler.push_const(Value::Int(0));
+        self.assembler.stloc(state.index);
+        Ok(())
+    }
+
     fn compile_type_name_equals(&mut self, value_slot: u8, expected: &str) {
         self.assembler.ldloc(value_slot);
         self.assembler.call(BuiltinFunction::TypeOf.call_index(), 1);
@@ -992,6 +1537,38 @@ impl Compiler {
     }
 }
 
+/// Slots that `let` statements and `for`-`in` loops in `stmts` bind.
+fn bound_slots(stmts: &[Stmt], slots: &mut Vec<u8>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Let { index, .. } => slots.push(*index),
+            Stmt::IfElse {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                bound_slots(then_branch, slots);
+                bound_slots(else_branch, slots);
+            }
+            Stmt::For { init, body, .. } => {
+                bound_slots(std::slice::from_ref(init.as_ref()), slots);
+                bound_slots(body, slots);
+            }
+            Stmt::While { body, .. } => bound_slots(body, slots),
+            Stmt::ForIn {
+                key_slot,
+                value_slot,
+                body,
+                ..
+            } => {
+                slots.extend(key_slot.iter().chain(value_slot));
+                bound_slots(body, slots);
+            }
+            _ => {}
+        }
+    }
+}
+
 fn shift_amount_for_power_of_two(value: i64) -> Option<u32> {
     if value <= 0 {
         return None;
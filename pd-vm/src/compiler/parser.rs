@@ -1,15 +1,21 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 use crate::builtins::BuiltinFunction;
 use crate::compiler::source_map::{SourceId, Span};
+use crate::format::{format_requirements, parse_format_template};
 
 use super::{
-    ParseError, STDLIB_PRINT_ARITY, STDLIB_PRINT_NAME,
-    ir::{ClosureExpr, Expr, FunctionDecl, FunctionImpl, MatchPattern, MatchTypePattern, Stmt},
+    CompileWarning, ParseError, STDLIB_PRINT_ARITY, STDLIB_PRINT_NAME,
+    ir::{
+        ClosureExpr, Expr, ForInState, FunctionDecl, FunctionImpl, MatchArm, MatchArrayIndex,
+        MatchArrayPattern, MatchArrayRest, MatchFieldKey, MatchPattern, MatchRangeBound,
+        MatchRangePattern, MatchSubPattern, MatchTypePattern, MatchVariantPattern, Stmt,
+        TYPE_TAG_KEY,
+    },
 };
 
 #[derive(Debug, Clone, PartialEq)]
-enum TokenKind {
+pub(super) enum TokenKind {
     Ident(String),
     Int(i64),
     Float(f64),
@@ -29,6 +35,7 @@ enum TokenKind {
     While,
     Break,
     Continue,
+    Return,
     Bang,
     BangEqual,
     Plus,
@@ -59,10 +66,10 @@ enum TokenKind {
 }
 
 #[derive(Debug, Clone, PartialEq)]
-struct Token {
-    kind: TokenKind,
-    line: usize,
-    span: Span,
+pub(super) struct Token {
+    pub(super) kind: TokenKind,
+    pub(super) line: usize,
+    pub(super) span: Span,
 }
 
 enum NumberLiteral {
@@ -243,6 +250,7 @@ impl<'a> Lexer<'a> {
                     "while" => TokenKind::While,
                     "break" => TokenKind::Break,
                     "continue" => TokenKind::Continue,
+                    "return" => TokenKind::Return,
                     "true" => TokenKind::True,
                     "false" => TokenKind::False,
                     "null" => TokenKind::Null,
@@ -455,6 +463,20 @@ impl<'a> Lexer<'a> {
     }
 }
 
+pub(super) fn tokenize(source: &str, source_id: SourceId) -> Result<Vec<Token>, ParseError> {
+    let mut lexer = Lexer::new(source, source_id);
+    let mut tokens = Vec::new();
+    loop {
+        let token = lexer.next_token()?;
+        let is_eof = matches!(token.kind, TokenKind::Eof);
+        tokens.push(token);
+        if is_eof {
+            break;
+        }
+    }
+    Ok(tokens)
+}
+
 fn is_ident_start(ch: char) -> bool {
     ch.is_ascii_alphabetic() || ch == '_'
 }
@@ -473,13 +495,27 @@ pub(super) struct Parser {
     function_impls: HashMap<u16, FunctionImpl>,
     next_function: u16,
     closure_scopes: Vec<HashMap<String, u8>>,
+    /// Names of the hidden locals function, closure, and match scopes bound.
+    scoped_bindings: Vec<(String, u8)>,
     closure_capture_contexts: Vec<ClosureCaptureContext>,
     allow_implicit_externs: bool,
     allow_implicit_semicolons: bool,
+    /// Accepts `|params| => { ... }` closure bodies, which token-stream
+    /// frontends emit for block-bodied functions; RustScript source has none.
+    allow_block_closures: bool,
     loop_depth: usize,
+    /// Early-`return` counts for the function and closure bodies being parsed.
+    return_scopes: Vec<usize>,
+    /// Hidden local shared by every early `return` in this unit; the value is
+    /// reloaded as soon as the inlined body exits, so nesting cannot clobber it.
+    return_slot: Option<u8>,
     vm_namespace_aliases: HashSet<String>,
     vm_named_imports: HashMap<String, String>,
     vm_wildcard_import: bool,
+    struct_types: HashMap<String, Vec<String>>,
+    enum_types: HashMap<String, Vec<EnumVariantDecl>>,
+    local_struct_types: HashMap<u8, String>,
+    warnings: Vec<CompileWarning>,
 }
 
 struct ClosureCaptureContext {
@@ -487,6 +523,143 @@ struct ClosureCaptureContext {
     capture_copies: Vec<(u8, u8)>,
 }
 
+#[derive(Default)]
+struct MatchBindings {
+    /// Names bound by the pattern parsed so far, visible in the arm.
+    bound: HashMap<String, u8>,
+    /// Slots handed out per name, reused by later `|` alternatives.
+    slots: HashMap<String, u8>,
+}
+
+impl MatchBindings {
+    fn names_bound_since(&self, base: &HashMap<String, u8>) -> BTreeSet<String> {
+        self.bound
+            .keys()
+            .filter(|name| !base.contains_key(*name))
+            .cloned()
+            .collect()
+    }
+}
+
+/// What the arms of a `match` seen so far are known to cover, used for the
+/// unreachable-arm and enum exhaustiveness warnings.
+#[derive(Default)]
+struct MatchCoverage {
+    catch_all: bool,
+    /// Literal keys and variant tags matched unconditionally by earlier arms.
+    covered: HashSet<String>,
+    arm_enum: ArmEnum,
+}
+
+/// Enum named by the refutable arms of a `match` seen so far.
+#[derive(Default)]
+enum ArmEnum {
+    #[default]
+    Unknown,
+    One(String),
+    Mixed,
+}
+
+impl MatchCoverage {
+    fn enum_name(&self) -> Option<&str> {
+        match &self.arm_enum {
+            ArmEnum::One(name) => Some(name),
+            ArmEnum::Unknown | ArmEnum::Mixed => None,
+        }
+    }
+
+    fn covers(&self, pattern: &MatchPattern) -> bool {
+        match pattern {
+            MatchPattern::Or(alternatives) => alternatives.iter().all(|alt| self.covers(alt)),
+            pattern => coverage_key(pattern).is_some_and(|key| self.covered.contains(&key)),
+        }
+    }
+
+    fn record(&mut self, pattern: &MatchPattern, guarded: bool) {
+        if !matches!(pattern, MatchPattern::Wildcard | MatchPattern::Binding(_)) {
+            self.arm_enum = match (
+                std::mem::take(&mut self.arm_enum),
+                pattern_enum_name(pattern),
+            ) {
+                (ArmEnum::Unknown, Some(name)) => ArmEnum::One(name),
+                (ArmEnum::One(current), Some(name)) if current == name => ArmEnum::One(current),
+                _ => ArmEnum::Mixed,
+            };
+        }
+        if guarded {
+            return;
+        }
+        if pattern_is_irrefutable(pattern) {
+            self.catch_all = true;
+        }
+        let alternatives = match pattern {
+            MatchPattern::Or(alternatives) => alternatives.as_slice(),
+            pattern => std::slice::from_ref(pattern),
+        };
+        for alternative in alternatives {
+            let fully_matched = match alternative {
+                MatchPattern::Variant(variant) => variant
+                    .fields
+                    .iter()
+                    .all(|(_, sub)| pattern_is_irrefutable(&sub.pattern)),
+                _ => true,
+            };
+            if fully_matched && let Some(key) = coverage_key(alternative) {
+                self.covered.insert(key);
+            }
+        }
+    }
+}
+
+fn coverage_key(pattern: &MatchPattern) -> Option<String> {
+    match pattern {
+        MatchPattern::Int(value) => Some(format!("int:{value}")),
+        MatchPattern::Float(value) => Some(format!("float:{}", value.to_bits())),
+        MatchPattern::String(value) => Some(format!("string:{value}")),
+        MatchPattern::Null => Some("null".to_string()),
+        MatchPattern::Variant(variant) => Some(variant.tag.clone()),
+        _ => None,
+    }
+}
+
+fn pattern_enum_name(pattern: &MatchPattern) -> Option<String> {
+    match pattern {
+        MatchPattern::Variant(variant) => variant
+            .tag
+            .split_once("::")
+            .map(|(enum_name, _)| enum_name.to_string()),
+        MatchPattern::Or(alternatives) => {
+            let mut names = alternatives.iter().map(pattern_enum_name);
+            let first = names.next()??;
+            names
+                .all(|name| name.as_deref() == Some(first.as_str()))
+                .then_some(first)
+        }
+        _ => None,
+    }
+}
+
+fn pattern_is_irrefutable(pattern: &MatchPattern) -> bool {
+    match pattern {
+        MatchPattern::Wildcard | MatchPattern::Binding(_) => true,
+        MatchPattern::Or(alternatives) => alternatives.iter().any(pattern_is_irrefutable),
+        _ => false,
+    }
+}
+
+#[derive(Clone)]
+struct EnumVariantDecl {
+    name: String,
+    shape: EnumVariantShape,
+}
+
+#[derive(Clone)]
+enum EnumVariantShape {
+    Unit,
+    Tuple(usize),
+    Struct(Vec<String>),
+}
+
 impl Parser {
     pub(super) fn new(
         source: &str,
@@ -494,17 +667,22 @@ impl Parser {
         allow_implicit_externs: bool,
         allow_implicit_semicolons: bool,
     ) -> Result<Self, ParseError> {
-        let mut lexer = Lexer::new(source, source_id);
-        let mut tokens = Vec::new();
-        loop {
-            let token = lexer.next_token()?;
-            let is_eof = matches!(token.kind, TokenKind::Eof);
-            tokens.push(token);
-            if is_eof {
-                break;
-            }
-        }
-        Ok(Self {
+        let tokens = tokenize(source, source_id)?;
+        let mut parser =
+            Self::from_tokens(tokens, allow_implicit_externs, allow_implicit_semicolons);
+        parser.allow_block_closures = false;
+        Ok(parser)
+    }
+
+    /// Builds a parser over an already lexed stream ending in `Eof`. Frontends
+    /// with their own syntax tree lower into this stream so the IR is built by
+    /// the same rules while tokens keep pointing at the original source.
+    pub(super) fn from_tokens(
+        tokens: Vec<Token>,
+        allow_implicit_externs: bool,
+        allow_implicit_semicolons: bool,
+    ) -> Self {
+        Self {
             tokens,
             pos: 0,
             locals: HashMap::new(),
@@ -514,14 +692,22 @@ impl Parser {
             function_impls: HashMap::new(),
             next_function: 0,
             closure_scopes: Vec::new(),
+            scoped_bindings: Vec::new(),
             closure_capture_contexts: Vec::new(),
             allow_implicit_externs,
             allow_implicit_semicolons,
+            allow_block_closures: true,
             loop_depth: 0,
+            return_scopes: Vec::new(),
+            return_slot: None,
             vm_namespace_aliases: HashSet::new(),
             vm_named_imports: HashMap::new(),
             vm_wildcard_import: false,
-        })
+            struct_types: HashMap::new(),
+            enum_types: HashMap::new(),
+            local_struct_types: HashMap::new(),
+            warnings: Vec::new(),
+        }
     }
 
     pub(super) fn parse_program(&mut self) -> Result<Vec<Stmt>, ParseError> {
@@ -537,13 +723,25 @@ impl Parser {
             if self.match_kind(&TokenKind::Fn) {
                 return self.parse_fn_decl(true);
             }
+            if self.match_type_decl_start("struct") {
+                return self.parse_struct_decl();
+            }
+            if self.match_type_decl_start("enum") {
+                return self.parse_enum_decl();
+            }
             return Err(ParseError {
                 span: None,
                 code: None,
                 line: self.current_line(),
-                message: "expected 'fn' after 'pub'".to_string(),
+                message: "expected 'fn', 'struct', or 'enum' after 'pub'".to_string(),
             });
         }
+        if self.match_type_decl_start("struct") {
+            return self.parse_struct_decl();
+        }
+        if self.match_type_decl_start("enum") {
+            return self.parse_enum_decl();
+        }
         if self.match_kind(&TokenKind::Use) {
             return self.parse_use_stmt();
         }
@@ -568,6 +766,9 @@ impl Parser {
         if self.match_kind(&TokenKind::Continue) {
             return self.parse_loop_control_stmt(false);
         }
+        if self.match_kind(&TokenKind::Return) {
+            return self.parse_return_stmt();
+        }
         if self.check_index_assignment_start() {
             return self.parse_index_assign_with_terminator(true);
         }
@@ -607,6 +808,43 @@ impl Parser {
         })
     }
 
+    fn parse_return_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let line = self.last_line();
+        if self.return_scopes.is_empty() {
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line: line as usize,
+                message: "'return' is only allowed inside functions and closures".to_string(),
+            });
+        }
+        let expr = if self.check(&TokenKind::Semicolon)
+            || self.check(&TokenKind::RBrace)
+            || (self.allow_implicit_semicolons && self.current_line() > line as usize)
+        {
+            Expr::Null
+        } else {
+            self.parse_expr()?
+        };
+        self.consume_stmt_terminator("expected ';' after return")?;
+        if self.return_slot.is_none() {
+            self.return_slot = Some(self.allocate_hidden_local()?);
+        }
+        if let Some(count) = self.return_scopes.last_mut() {
+            *count += 1;
+        }
+        Ok(Stmt::Return { expr, line })
+    }
+
+    /// Ends a function or closure body scope, returning the slot its early
+    /// returns store into when any remain.
+    fn finish_return_scope(&mut self) -> Option<u8> {
+        match self.return_scopes.pop() {
+            Some(count) if count > 0 => self.return_slot,
+            _ => None,
+        }
+    }
+
     fn parse_use_stmt(&mut self) -> Result<Stmt, ParseError> {
         let line = self.last_line();
         let namespace = self.expect_ident("expected namespace after 'use'")?;
@@ -713,7 +951,15 @@ impl Parser {
             line: self.current_line(),
             message: "function arity too large".to_string(),
         })?;
-        if self.functions.contains_key(&name) {
+        // A body-less declaration may be completed by a later definition with the
+        // same arity, so mutually dependent functions can be declared up front.
+        let prototype_index = self.functions.get(&name).and_then(|existing| {
+            let completes = existing.arity == arity
+                && !self.function_impls.contains_key(&existing.index)
+                && (self.check(&TokenKind::LBrace) || self.check(&TokenKind::Equal));
+            completes.then_some(existing.index)
+        });
+        if self.functions.contains_key(&name) && prototype_index.is_none() {
             return Err(ParseError {
                 span: None,
                 code: None,
@@ -729,13 +975,19 @@ impl Parser {
                 message: format!("name '{name}' already used by a local binding"),
             });
         }
-        let index = self.next_function;
-        self.next_function = self.next_function.checked_add(1).ok_or(ParseError {
-            span: None,
-            code: None,
-            line: self.current_line(),
-            message: "function index overflow".to_string(),
-        })?;
+        let index = match prototype_index {
+            Some(index) => index,
+            None => {
+                let index = self.next_function;
+                self.next_function = self.next_function.checked_add(1).ok_or(ParseError {
+                    span: None,
+                    code: None,
+                    line: self.current_line(),
+                    message: "function index overflow".to_string(),
+                })?;
+                index
+            }
+        };
         let decl = FunctionDecl {
             name: name.clone(),
             arity,
@@ -744,7 +996,17 @@ impl Parser {
             exported,
         };
         self.functions.insert(name.clone(), decl.clone());
-        self.function_list.push(decl.clone());
+        match self
+            .function_list
+            .iter_mut()
+            .find(|existing| existing.index == index)
+        {
+            Some(existing) => {
+                existing.args = decl.args.clone();
+                existing.exported |= exported;
+            }
+            None => self.function_list.push(decl.clone()),
+        }
 
         if self.match_kind(&TokenKind::Equal) {
             let function_impl = self.parse_function_impl_expr(&params)?;
@@ -776,7 +1038,10 @@ impl Parser {
     }
 
     fn parse_function_impl_expr(&mut self, params: &[String]) -> Result<FunctionImpl, ParseError> {
-        self.parse_function_impl(params, |parser| Ok((Vec::new(), parser.parse_expr()?)))
+        self.parse_function_impl(params, |parser| {
+            let line = parser.current_line_u32();
+            Ok((Vec::new(), parser.parse_expr()?, line))
+        })
     }
 
     fn parse_function_impl_block(&mut self, params: &[String]) -> Result<FunctionImpl, ParseError> {
@@ -802,15 +1067,29 @@ impl Parser {
                     message: "function body must end with an expression statement".to_string(),
                 });
             };
-            let body_expr = if let Stmt::Expr { expr, .. } = last_stmt {
-                expr
-            } else {
-                return Err(ParseError {
-                    span: None,
-                    code: None,
-                    line: parser.current_line(),
-                    message: "function body must end with an expression statement".to_string(),
-                });
+            let (body_expr, body_line) = match last_stmt {
+                Stmt::Expr { expr, line } => (expr, line),
+                Stmt::Return { expr, line } => {
+                    // A trailing `return` is just the body's result.
+                    if let Some(count) = parser.return_scopes.last_mut() {
+                        *count -= 1;
+                    }
+                    (expr, line)
+                }
+                // Bodies that leave through `return` may end in any statement;
+                // falling off the end yields null.
+                other if parser.return_scopes.last().is_some_and(|count| *count > 0) => {
+                    body_stmts.push(other);
+                    (Expr::Null, 0)
+                }
+                _ => {
+                    return Err(ParseError {
+                        span: None,
+                        code: None,
+                        line: parser.current_line(),
+                        message: "function body must end with an expression statement".to_string(),
+                    });
+                }
             };
 
             if body_stmts
@@ -825,7 +1104,7 @@ impl Parser {
                 });
             }
 
-            Ok((body_stmts, body_expr))
+            Ok((body_stmts, body_expr, body_line))
         })
     }
 
@@ -835,7 +1114,7 @@ impl Parser {
         parse_body: F,
     ) -> Result<FunctionImpl, ParseError>
     where
-        F: FnOnce(&mut Self) -> Result<(Vec<Stmt>, Expr), ParseError>,
+        F: FnOnce(&mut Self) -> Result<(Vec<Stmt>, Expr, u32), ParseError>,
     {
         let mut param_scope = HashMap::new();
         let mut param_slots = Vec::new();
@@ -857,7 +1136,10 @@ impl Parser {
             by_name: HashMap::new(),
             capture_copies: Vec::new(),
         });
-        let (body_stmts, body_expr) = parse_body(self)?;
+        self.return_scopes.push(0);
+        let body = parse_body(self);
+        let return_slot = self.finish_return_scope();
+        let (body_stmts, body_expr, body_line) = body?;
         let capture_context = self
             .closure_capture_contexts
             .pop()
@@ -867,7 +1149,7 @@ impl Parser {
                 line: self.current_line(),
                 message: "internal function capture state error".to_string(),
             })?;
-        self.closure_scopes.pop();
+        self.pop_closure_scope();
         if !capture_context.capture_copies.is_empty() {
             return Err(ParseError {
                 span: None,
@@ -880,145 +1162,615 @@ impl Parser {
             param_slots,
             body_stmts,
             body_expr,
+            body_line,
+            return_slot,
         })
     }
 
-    fn parse_let_with_terminator(&mut self, expect_terminator: bool) -> Result<Stmt, ParseError> {
-        let line = self.last_line();
-        let name = self.expect_ident("expected identifier after 'let'")?;
-        self.expect(&TokenKind::Equal, "expected '=' after identifier")?;
-        let expr = self.parse_expr()?;
-        if expect_terminator {
-            self.consume_stmt_terminator("expected ';' after let")?;
-        }
-
-        if !self.closure_scopes.is_empty() {
-            if let Some(index) = self
-                .closure_scopes
-                .last()
-                .and_then(|scope| scope.get(&name))
-                .copied()
-            {
-                return Ok(Stmt::Let { index, expr, line });
-            }
-            let index = self.allocate_hidden_local()?;
-            if let Some(scope) = self.closure_scopes.last_mut() {
-                scope.insert(name, index);
-            }
-            return Ok(Stmt::Let { index, expr, line });
-        }
-
-        let index = self.get_or_assign_local(&name)?;
-        Ok(Stmt::Let { index, expr, line })
-    }
-
-    fn parse_assign_with_terminator(
-        &mut self,
-        expect_terminator: bool,
-    ) -> Result<Stmt, ParseError> {
-        let line = self.current_line_u32();
-        let name = self.expect_ident("expected identifier before '='")?;
-        self.expect(&TokenKind::Equal, "expected '=' after identifier")?;
-        let expr = self.parse_expr()?;
-        if expect_terminator {
-            self.consume_stmt_terminator("expected ';' after assignment")?;
+    fn match_type_decl_start(&mut self, keyword: &str) -> bool {
+        let is_decl = matches!(
+            (self.tokens.get(self.pos), self.tokens.get(self.pos + 1)),
+            (
+                Some(Token {
+                    kind: TokenKind::Ident(head),
+                    ..
+                }),
+                Some(Token {
+                    kind: TokenKind::Ident(_),
+                    ..
+                })
+            ) if head == keyword
+        );
+        if is_decl {
+            self.pos += 1;
         }
-
-        let index = self.get_local(&name)?;
-        Ok(Stmt::Assign { index, expr, line })
-    }
-
-    fn parse_for(&mut self) -> Result<Stmt, ParseError> {
-        let line = self.last_line();
-        self.expect(&TokenKind::LParen, "expected '(' after 'for'")?;
-
-        let init = if self.match_kind(&TokenKind::Let) {
-            self.parse_let_with_terminator(false)?
-        } else if self.check_assignment_start() {
-            self.parse_assign_with_terminator(false)?
-        } else {
-            let init_line = self.current_line_u32();
-            let expr = self.parse_expr()?;
-            Stmt::Expr {
-                expr,
-                line: init_line,
-            }
-        };
-        self.expect(&TokenKind::Semicolon, "expected ';' after for initializer")?;
-
-        let condition = self.parse_expr()?;
-        self.expect(&TokenKind::Semicolon, "expected ';' after for condition")?;
-
-        let post = if self.check_assignment_start() {
-            self.parse_assign_with_terminator(false)?
-        } else {
-            let post_line = self.current_line_u32();
-            let expr = self.parse_expr()?;
-            Stmt::Expr {
-                expr,
-                line: post_line,
-            }
-        };
-        self.expect(&TokenKind::RParen, "expected ')' after for clauses")?;
-        self.loop_depth += 1;
-        let body = self.parse_block("expected '{' after for clauses")?;
-        self.loop_depth -= 1;
-        Ok(Stmt::For {
-            init: Box::new(init),
-            condition,
-            post: Box::new(post),
-            body,
-            line,
-        })
+        is_decl
     }
 
-    fn parse_if(&mut self) -> Result<Stmt, ParseError> {
+    fn parse_struct_decl(&mut self) -> Result<Stmt, ParseError> {
         let line = self.last_line();
-        let condition = self.parse_expr()?;
-        let then_branch = self.parse_block("expected '{' after if condition")?;
-        let else_branch = if self.match_kind(&TokenKind::Else) {
-            if self.match_kind(&TokenKind::If) {
-                vec![self.parse_if()?]
-            } else {
-                self.parse_block("expected '{' after else")?
-            }
-        } else {
-            Vec::new()
-        };
-        Ok(Stmt::IfElse {
-            condition,
-            then_branch,
-            else_branch,
-            line,
-        })
+        let name = self.expect_ident("expected struct name after 'struct'")?;
+        self.ensure_type_name_available(&name)?;
+        if self.check(&TokenKind::LParen) {
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line: self.current_line(),
+                message: format!(
+                    "tuple struct '{name}' is not supported; declare named fields with 'struct {name} {{ field, ... }}'"
+                ),
+            });
+        }
+        self.expect(&TokenKind::LBrace, "expected '{' after struct name")?;
+        let fields = self.parse_declared_fields(&name)?;
+        self.struct_types.insert(name, fields);
+        Ok(Stmt::Noop { line })
     }
 
-    fn parse_while(&mut self) -> Result<Stmt, ParseError> {
+    fn parse_enum_decl(&mut self) -> Result<Stmt, ParseError> {
         let line = self.last_line();
-        let condition = self.parse_expr()?;
-        self.loop_depth += 1;
-        let body = self.parse_block("expected '{' after while condition")?;
-        self.loop_depth -= 1;
-        Ok(Stmt::While {
-            condition,
-            body,
-            line,
-        })
-    }
+        let name = self.expect_ident("expected enum name after 'enum'")?;
+        self.ensure_type_name_available(&name)?;
+        self.expect(&TokenKind::LBrace, "expected '{' after enum name")?;
 
-    fn parse_block(&mut self, message: &str) -> Result<Vec<Stmt>, ParseError> {
-        self.expect(&TokenKind::LBrace, message)?;
-        let mut stmts = Vec::new();
+        let mut variants = Vec::<EnumVariantDecl>::new();
         while !self.check(&TokenKind::RBrace) {
-            if self.check(&TokenKind::Eof) {
+            let variant = self.expect_ident(&format!("expected variant name in enum '{name}'"))?;
+            if variants.iter().any(|existing| existing.name == variant) {
                 return Err(ParseError {
                     span: None,
                     code: None,
                     line: self.current_line(),
-                    message: "unexpected end of input in block".to_string(),
+                    message: format!("duplicate variant '{variant}' in enum '{name}'"),
                 });
             }
-            stmts.push(self.parse_stmt()?);
+            let shape = if self.match_kind(&TokenKind::LParen) {
+                let mut arity = 0usize;
+                while !self.check(&TokenKind::RParen) {
+                    self.skip_type_annotation()?;
+                    arity += 1;
+                    if !self.match_kind(&TokenKind::Comma) {
+                        break;
+                    }
+                }
+                self.expect(
+                    &TokenKind::RParen,
+                    "expected ')' after enum variant payload types",
+                )?;
+                EnumVariantShape::Tuple(arity)
+            } else if self.match_kind(&TokenKind::LBrace) {
+                EnumVariantShape::Struct(self.parse_declared_fields(&format!("{name}::{variant}"))?)
+            } else {
+                EnumVariantShape::Unit
+            };
+            variants.push(EnumVariantDecl {
+                name: variant,
+                shape,
+            });
+            if !self.match_kind(&TokenKind::Comma) {
+                break;
+            }
+        }
+        self.expect(&TokenKind::RBrace, "expected '}' after enum variants")?;
+        self.enum_types.insert(name, variants);
+        Ok(Stmt::Noop { line })
+    }
+
+    fn ensure_type_name_available(&self, name: &str) -> Result<(), ParseError> {
+        if self.struct_types.contains_key(name) || self.enum_types.contains_key(name) {
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line: self.current_line(),
+                message: format!("type '{name}' is already declared"),
+            });
+        }
+        Ok(())
+    }
+
+    fn parse_declared_fields(&mut self, owner: &str) -> Result<Vec<String>, ParseError> {
+        let mut fields = Vec::<String>::new();
+        while !self.check(&TokenKind::RBrace) {
+            let field = self.expect_ident(&format!("expected field name in '{owner}'"))?;
+            if field == TYPE_TAG_KEY {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line: self.current_line(),
+                    message: format!("field name '{TYPE_TAG_KEY}' is reserved for the type tag"),
+                });
+            }
+            if fields.contains(&field) {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line: self.current_line(),
+                    message: format!("duplicate field '{field}' in '{owner}'"),
+                });
+            }
+            if self.match_kind(&TokenKind::Colon) {
+                self.skip_type_annotation()?;
+            }
+            fields.push(field);
+            if !self.match_kind(&TokenKind::Comma) {
+                break;
+            }
+        }
+        self.expect(
+            &TokenKind::RBrace,
+            &format!("expected '}}' after fields of '{owner}'"),
+        )?;
+        Ok(fields)
+    }
+
+    // Type annotations are accepted for readability but not checked, so they
+    // are skipped up to the next ',' or closing delimiter at the same depth.
+    fn skip_type_annotation(&mut self) -> Result<(), ParseError> {
+        let start = self.pos;
+        let mut depth = 0usize;
+        loop {
+            match self.tokens.get(self.pos).map(|token| &token.kind) {
+                None | Some(TokenKind::Eof) => {
+                    return Err(ParseError {
+                        span: None,
+                        code: None,
+                        line: self.current_line(),
+                        message: "unexpected end of input in type annotation".to_string(),
+                    });
+                }
+                Some(TokenKind::Comma | TokenKind::RBrace | TokenKind::RParen) if depth == 0 => {
+                    break;
+                }
+                Some(TokenKind::Less | TokenKind::LParen | TokenKind::LBracket) => depth += 1,
+                Some(TokenKind::Greater | TokenKind::RParen | TokenKind::RBracket) => {
+                    depth = depth.saturating_sub(1);
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line: self.current_line(),
+                message: "expected type after ':'".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn check_struct_literal_start(&self) -> bool {
+        if !self.check(&TokenKind::LBrace) {
+            return false;
+        }
+        match (
+            self.tokens.get(self.pos + 1).map(|token| &token.kind),
+            self.tokens.get(self.pos + 2).map(|token| &token.kind),
+            self.tokens.get(self.pos + 3).map(|token| &token.kind),
+        ) {
+            (Some(TokenKind::RBrace), _, _) => true,
+            (Some(TokenKind::Ident(_)), Some(TokenKind::Comma | TokenKind::RBrace), _) => true,
+            (Some(TokenKind::Ident(_)), Some(TokenKind::Colon), next) => {
+                !matches!(next, Some(TokenKind::Colon))
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_struct_literal(&mut self, name: &str) -> Result<Expr, ParseError> {
+        self.expect(&TokenKind::LBrace, "expected '{' after struct name")?;
+        let fields = self.struct_types.get(name).cloned().unwrap_or_default();
+        let values = self.parse_named_field_values(&format!("struct '{name}'"), &fields)?;
+        let entries = fields.into_iter().map(Expr::String).zip(values).collect();
+        self.build_tagged_map_expr(name.to_string(), entries)
+    }
+
+    fn parse_enum_variant_expr(&mut self, enum_name: &str) -> Result<Expr, ParseError> {
+        self.match_path_separator();
+        let variant_name = self.expect_ident("expected variant name after '::'")?;
+        let variant = self.lookup_enum_variant(enum_name, &variant_name)?;
+        let tag = format!("{enum_name}::{variant_name}");
+        let entries = match variant.shape {
+            EnumVariantShape::Unit => Vec::new(),
+            EnumVariantShape::Tuple(arity) => {
+                self.expect(
+                    &TokenKind::LParen,
+                    &format!("enum variant '{tag}' expects {arity} payload value(s) in '(...)'"),
+                )?;
+                let args = self.parse_call_args()?;
+                if args.len() != arity {
+                    return Err(ParseError {
+                        span: None,
+                        code: None,
+                        line: self.current_line(),
+                        message: format!(
+                            "enum variant '{tag}' expects {arity} payload value(s) but {} were supplied",
+                            args.len()
+                        ),
+                    });
+                }
+                args.into_iter()
+                    .enumerate()
+                    .map(|(position, arg)| (Expr::Int(position as i64), arg))
+                    .collect()
+            }
+            EnumVariantShape::Struct(fields) => {
+                self.expect(
+                    &TokenKind::LBrace,
+                    &format!("expected '{{' after struct-like enum variant '{tag}'"),
+                )?;
+                let values = self.parse_named_field_values(&format!("variant '{tag}'"), &fields)?;
+                fields.into_iter().map(Expr::String).zip(values).collect()
+            }
+        };
+        self.build_tagged_map_expr(tag, entries)
+    }
+
+    fn lookup_enum_variant(
+        &self,
+        enum_name: &str,
+        variant_name: &str,
+    ) -> Result<EnumVariantDecl, ParseError> {
+        self.enum_types
+            .get(enum_name)
+            .and_then(|variants| variants.iter().find(|variant| variant.name == variant_name))
+            .cloned()
+            .ok_or_else(|| ParseError {
+                span: None,
+                code: None,
+                line: self.current_line(),
+                message: format!("enum '{enum_name}' has no variant '{variant_name}'"),
+            })
+    }
+
+    /// Parses `field: expr` and shorthand `field` entries up to the closing
+    /// brace and returns the values in declaration order.
+    fn parse_named_field_values(
+        &mut self,
+        owner: &str,
+        fields: &[String],
+    ) -> Result<Vec<Expr>, ParseError> {
+        let mut values = vec![None::<Expr>; fields.len()];
+        while !self.check(&TokenKind::RBrace) {
+            let field = self.expect_ident(&format!("expected field name in {owner} literal"))?;
+            let Some(position) = fields.iter().position(|declared| *declared == field) else {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line: self.current_line(),
+                    message: format!("{owner} has no field '{field}'"),
+                });
+            };
+            if values[position].is_some() {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line: self.current_line(),
+                    message: format!(
+                        "field '{field}' is specified more than once in {owner} literal"
+                    ),
+                });
+            }
+            let value = if self.match_kind(&TokenKind::Colon) {
+                self.parse_expr()?
+            } else {
+                Expr::Var(self.get_local(&field)?)
+            };
+            values[position] = Some(value);
+            if !self.match_kind(&TokenKind::Comma) {
+                break;
+            }
+        }
+        self.expect(
+            &TokenKind::RBrace,
+            &format!("expected '}}' after {owner} literal fields"),
+        )?;
+
+        let missing = fields
+            .iter()
+            .zip(&values)
+            .filter(|(_, value)| value.is_none())
+            .map(|(field, _)| field.as_str())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line: self.current_line(),
+                message: format!("missing field(s) {} in {owner} literal", missing.join(", ")),
+            });
+        }
+        Ok(values.into_iter().flatten().collect())
+    }
+
+    fn build_tagged_map_expr(
+        &mut self,
+        tag: String,
+        entries: Vec<(Expr, Expr)>,
+    ) -> Result<Expr, ParseError> {
+        let mut out = self.build_builtin_call_expr(BuiltinFunction::MapNew, Vec::new())?;
+        out = self.build_builtin_call_expr(
+            BuiltinFunction::Set,
+            vec![
+                out,
+                Expr::String(TYPE_TAG_KEY.to_string()),
+                Expr::String(tag),
+            ],
+        )?;
+        for (key, value) in entries {
+            out = self.build_builtin_call_expr(BuiltinFunction::Set, vec![out, key, value])?;
+        }
+        Ok(out)
+    }
+
+    /// Returns the struct type statically known for `expr`: a struct literal,
+    /// a field update of one, or a local last assigned one.
+    fn struct_type_of_expr(&self, expr: &Expr) -> Option<String> {
+        match expr {
+            Expr::Var(index) => self.local_struct_types.get(index).cloned(),
+            Expr::Call(index, args) if *index == BuiltinFunction::Set.call_index() => {
+                match args.as_slice() {
+                    [
+                        Expr::Call(inner, inner_args),
+                        Expr::String(key),
+                        Expr::String(tag),
+                    ] if *inner == BuiltinFunction::MapNew.call_index()
+                        && inner_args.is_empty()
+                        && key == TYPE_TAG_KEY =>
+                    {
+                        self.struct_types.contains_key(tag).then(|| tag.clone())
+                    }
+                    [target, _, _] => self.struct_type_of_expr(target),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn track_local_struct_type(&mut self, index: u8, expr: &Expr) {
+        match self.struct_type_of_expr(expr) {
+            Some(type_name) => {
+                self.local_struct_types.insert(index, type_name);
+            }
+            None => {
+                self.local_struct_types.remove(&index);
+            }
+        }
+    }
+
+    fn struct_declares_field(&self, type_name: &str, field: &str) -> bool {
+        self.struct_types
+            .get(type_name)
+            .is_some_and(|fields| fields.iter().any(|declared| declared == field))
+    }
+
+    fn check_struct_field(&self, type_name: &str, field: &str) -> Result<(), ParseError> {
+        if self.struct_declares_field(type_name, field) {
+            return Ok(());
+        }
+        Err(ParseError {
+            span: None,
+            code: None,
+            line: self.current_line(),
+            message: format!("struct '{type_name}' has no field '{field}'"),
+        })
+    }
+
+    fn parse_let_with_terminator(&mut self, expect_terminator: bool) -> Result<Stmt, ParseError> {
+        let line = self.last_line();
+        let name = self.expect_ident("expected identifier after 'let'")?;
+        self.expect(&TokenKind::Equal, "expected '=' after identifier")?;
+        let expr = self.parse_expr()?;
+        if expect_terminator {
+            self.consume_stmt_terminator("expected ';' after let")?;
+        }
+
+        let index = self.declare_let_binding(name)?;
+        self.track_local_struct_type(index, &expr);
+        Ok(Stmt::Let { index, expr, line })
+    }
+
+    /// Resolves the slot a `let`-style binding writes: the innermost function
+    /// or closure scope when inside one, otherwise the program locals.
+    fn declare_let_binding(&mut self, name: String) -> Result<u8, ParseError> {
+        if self.closure_scopes.is_empty() {
+            return self.get_or_assign_local(&name);
+        }
+        if let Some(index) = self
+            .closure_scopes
+            .last()
+            .and_then(|scope| scope.get(&name))
+            .copied()
+        {
+            return Ok(index);
+        }
+        let index = self.allocate_hidden_local()?;
+        if let Some(scope) = self.closure_scopes.last_mut() {
+            scope.insert(name, index);
+        }
+        Ok(index)
+    }
+
+    fn parse_assign_with_terminator(
+        &mut self,
+        expect_terminator: bool,
+    ) -> Result<Stmt, ParseError> {
+        let line = self.current_line_u32();
+        let name = self.expect_ident("expected identifier before '='")?;
+        self.expect(&TokenKind::Equal, "expected '=' after identifier")?;
+        let expr = self.parse_expr()?;
+        if expect_terminator {
+            self.consume_stmt_terminator("expected ';' after assignment")?;
+        }
+
+        let index = self.get_local(&name)?;
+        self.track_local_struct_type(index, &expr);
+        Ok(Stmt::Assign { index, expr, line })
+    }
+
+    fn parse_for(&mut self) -> Result<Stmt, ParseError> {
+        let line = self.last_line();
+        if let Some((key_name, value_name)) = self.match_for_in_bindings() {
+            return self.parse_for_in(line, key_name, value_name);
+        }
+        self.expect(&TokenKind::LParen, "expected '(' after 'for'")?;
+
+        let init = if self.match_kind(&TokenKind::Let) {
+            self.parse_let_with_terminator(false)?
+        } else if self.check_assignment_start() {
+            self.parse_assign_with_terminator(false)?
+        } else {
+            let init_line = self.current_line_u32();
+            let expr = self.parse_expr()?;
+            Stmt::Expr {
+                expr,
+                line: init_line,
+            }
+        };
+        self.expect(&TokenKind::Semicolon, "expected ';' after for initializer")?;
+
+        let condition = self.parse_expr()?;
+        self.expect(&TokenKind::Semicolon, "expected ';' after for condition")?;
+
+        let post = if self.check_assignment_start() {
+            self.parse_assign_with_terminator(false)?
+        } else {
+            let post_line = self.current_line_u32();
+            let expr = self.parse_expr()?;
+            Stmt::Expr {
+                expr,
+                line: post_line,
+            }
+        };
+        self.expect(&TokenKind::RParen, "expected ')' after for clauses")?;
+        self.loop_depth += 1;
+        let body = self.parse_block("expected '{' after for clauses")?;
+        self.loop_depth -= 1;
+        Ok(Stmt::For {
+            init: Box::new(init),
+            condition,
+            post: Box::new(post),
+            body,
+            line,
+        })
+    }
+
+    /// Consumes `x in` or `(k, v) in` after `for`, returning the optional key
+    /// name and the optional element name (`_` binds nothing).
+    fn match_for_in_bindings(&mut self) -> Option<(Option<String>, Option<String>)> {
+        let kind_at = |offset: usize| self.tokens.get(self.pos + offset).map(|t| &t.kind);
+        let is_in =
+            |kind: Option<&TokenKind>| matches!(kind, Some(TokenKind::Ident(name)) if name == "in");
+        let binding = |kind: Option<&TokenKind>| match kind {
+            Some(TokenKind::Ident(name)) => Some((name != "_").then(|| name.clone())),
+            _ => None,
+        };
+        if let Some(value) = binding(kind_at(0))
+            && is_in(kind_at(1))
+        {
+            self.pos += 2;
+            return Some((None, value));
+        }
+        if matches!(kind_at(0), Some(TokenKind::LParen))
+            && let Some(key) = binding(kind_at(1))
+            && matches!(kind_at(2), Some(TokenKind::Comma))
+            && let Some(value) = binding(kind_at(3))
+            && matches!(kind_at(4), Some(TokenKind::RParen))
+            && is_in(kind_at(5))
+        {
+            self.pos += 6;
+            return Some((key, value));
+        }
+        None
+    }
+
+    fn parse_for_in(
+        &mut self,
+        line: u32,
+        key_name: Option<String>,
+        value_name: Option<String>,
+    ) -> Result<Stmt, ParseError> {
+        let iterable = self.parse_expr()?;
+        let key_slot = key_name
+            .map(|name| self.declare_let_binding(name))
+            .transpose()?;
+        let value_slot = value_name
+            .map(|name| self.declare_let_binding(name))
+            .transpose()?;
+        let values = self.allocate_hidden_local()?;
+        let keys = if key_slot.is_some() {
+            self.allocate_hidden_local()?
+        } else {
+            values
+        };
+        let state = ForInState {
+            keys,
+            values,
+            index: self.allocate_hidden_local()?,
+            len: self.allocate_hidden_local()?,
+        };
+        self.loop_depth += 1;
+        let body = self.parse_block("expected '{' after for-in iterable")?;
+        self.loop_depth -= 1;
+        Ok(Stmt::ForIn {
+            iterable,
+            key_slot,
+            value_slot,
+            state,
+            body,
+            line,
+        })
+    }
+
+    fn parse_if(&mut self) -> Result<Stmt, ParseError> {
+        let line = self.last_line();
+        let condition = self.parse_expr()?;
+        let then_branch = self.parse_block("expected '{' after if condition")?;
+        let else_branch = if self.match_kind(&TokenKind::Else) {
+            if self.match_kind(&TokenKind::If) {
+                vec![self.parse_if()?]
+            } else {
+                self.parse_block("expected '{' after else")?
+            }
+        } else {
+            Vec::new()
+        };
+        Ok(Stmt::IfElse {
+            condition,
+            then_branch,
+            else_branch,
+            line,
+        })
+    }
+
+    fn parse_while(&mut self) -> Result<Stmt, ParseError> {
+        let line = self.last_line();
+        let condition = self.parse_expr()?;
+        self.loop_depth += 1;
+        let body = self.parse_block("expected '{' after while condition")?;
+        self.loop_depth -= 1;
+        Ok(Stmt::While {
+            condition,
+            body,
+            line,
+        })
+    }
+
+    fn parse_block(&mut self, message: &str) -> Result<Vec<Stmt>, ParseError> {
+        self.expect(&TokenKind::LBrace, message)?;
+        let mut stmts = Vec::new();
+        while !self.check(&TokenKind::RBrace) {
+            if self.check(&TokenKind::Eof) {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line: self.current_line(),
+                    message: "unexpected end of input in block".to_string(),
+                });
+            }
+            stmts.push(self.parse_stmt()?);
         }
         self.expect(&TokenKind::RBrace, "expected '}' to close block")?;
         Ok(stmts)
@@ -1144,6 +1896,21 @@ impl Parser {
             return self.parse_closure_literal();
         }
         if let Some(name) = self.match_ident() {
+            if name == "format" && self.check_macro_bang() {
+                let mut expr = self.parse_format_macro()?;
+                expr = self.parse_postfix_access(expr)?;
+                return Ok(expr);
+            }
+            if self.enum_types.contains_key(&name) && self.check_path_separator() {
+                let mut expr = self.parse_enum_variant_expr(&name)?;
+                expr = self.parse_postfix_access(expr)?;
+                return Ok(expr);
+            }
+            if self.struct_types.contains_key(&name) && self.check_struct_literal_start() {
+                let mut expr = self.parse_struct_literal(&name)?;
+                expr = self.parse_postfix_access(expr)?;
+                return Ok(expr);
+            }
             if self.match_path_separator() {
                 let mut path_segments = Vec::new();
                 path_segments
@@ -1176,20 +1943,28 @@ impl Parser {
                     && let Some(builtin) = self.try_re_namespace_builtin_call(&member, &mut args)?
                 {
                     let expr = self.build_builtin_call_expr(builtin, args)?;
-                    return Ok(expr);
+                    return self.parse_postfix_access(expr);
+                }
+                if subpath.is_empty()
+                    && name == "lua"
+                    && let Some(builtin) =
+                        self.try_lua_namespace_builtin_call(&member, &mut args)?
+                {
+                    let expr = self.build_builtin_call_expr(builtin, args)?;
+                    return self.parse_postfix_access(expr);
                 }
                 if subpath.is_empty()
                     && let Some(builtin) = self.resolve_builtin_namespace_call(&name, &member)
                 {
                     let expr = self.build_builtin_call_expr(builtin, args)?;
-                    return Ok(expr);
+                    return self.parse_postfix_access(expr);
                 }
                 let host_name = self
                     .resolve_vm_namespace_call_target(&name, &member, &subpath)
                     .ok_or_else(|| ParseError { span: None, code: None,
                         line: self.current_line(),
                         message: format!(
-                            "unknown namespace call '{}::{}'; supported namespaces are io:: and re:: (builtins), and vm:: (host imports via 'use vm;', 'use vm::*;', or 'use vm as <alias>;')",
+                            "unknown namespace call '{}::{}'; supported namespaces are io::, re::, lua::, rand::, uuid::, array::, and map:: (builtins), and vm:: (host imports via 'use vm;', 'use vm::*;', or 'use vm as <alias>;')",
                             name,
                             path_segments.join("::")
                         ),
@@ -1281,9 +2056,16 @@ impl Parser {
     }
 
     fn parse_if_expr_branch(&mut self) -> Result<Expr, ParseError> {
+        self.parse_expr_block("if expression branch")
+    }
+
+    /// Parses a `{ stmt; ...; expr }` body after `=>`, as used by if-expression
+    /// branches and frontend block-bodied closures; `context` names it in
+    /// errors.
+    fn parse_expr_block(&mut self, context: &str) -> Result<Expr, ParseError> {
         self.expect(
             &TokenKind::LBrace,
-            "expected '{' after '=>' in if expression branch",
+            &format!("expected '{{' after '=>' in {context}"),
         )?;
 
         let mut stmts = Vec::<Stmt>::new();
@@ -1294,7 +2076,7 @@ impl Parser {
                     span: None,
                     code: None,
                     line: self.current_line(),
-                    message: "unexpected end of input in if expression branch".to_string(),
+                    message: format!("unexpected end of input in {context}"),
                 });
             }
 
@@ -1311,14 +2093,14 @@ impl Parser {
             }
             self.expect(
                 &TokenKind::Semicolon,
-                "expected ';' after expression in if expression branch",
+                &format!("expected ';' after expression in {context}"),
             )?;
             stmts.push(Stmt::Expr { expr, line });
         }
 
         self.expect(
             &TokenKind::RBrace,
-            "expected '}' to close if expression branch",
+            &format!("expected '}}' to close {context}"),
         )?;
 
         let expr = if let Some(expr) = trailing_expr {
@@ -1329,18 +2111,24 @@ impl Parser {
                     span: None,
                     code: None,
                     line: self.current_line(),
-                    message: "if expression branch must end with an expression".to_string(),
+                    message: format!("{context} must end with an expression"),
                 });
             };
-            if let Stmt::Expr { expr, .. } = last_stmt {
-                expr
-            } else {
-                return Err(ParseError {
-                    span: None,
-                    code: None,
-                    line: self.current_line(),
-                    message: "if expression branch must end with an expression".to_string(),
-                });
+            match last_stmt {
+                Stmt::Expr { expr, .. } => expr,
+                // The branch never produces a value; it leaves the enclosing body.
+                Stmt::Return { .. } => {
+                    stmts.push(last_stmt);
+                    Expr::Null
+                }
+                _ => {
+                    return Err(ParseError {
+                        span: None,
+                        code: None,
+                        line: self.current_line(),
+                        message: format!("{context} must end with an expression"),
+                    });
+                }
             }
         };
 
@@ -1355,13 +2143,14 @@ impl Parser {
     }
 
     fn parse_match_expr(&mut self) -> Result<Expr, ParseError> {
+        let match_line = self.last_line() as usize;
         let value = self.parse_expr()?;
         self.expect(&TokenKind::LBrace, "expected '{' after match value")?;
 
         let value_slot = self.allocate_hidden_local()?;
         let result_slot = self.allocate_hidden_local()?;
-        let mut arms = Vec::<(MatchPattern, Expr)>::new();
-        let mut default: Option<Expr> = None;
+        let mut arms = Vec::<MatchArm>::new();
+        let mut coverage = MatchCoverage::default();
 
         while !self.check(&TokenKind::RBrace) {
             if self.check(&TokenKind::Eof) {
@@ -1373,102 +2162,481 @@ impl Parser {
                 });
             }
 
-            let pattern_token_line = self.current_line();
-            let pattern = self.parse_match_pattern()?;
-            self.expect(&TokenKind::FatArrow, "expected '=>' in match arm")?;
-            let arm_expr = self.parse_expr()?;
+            let arm_line = self.current_line();
+            let mut bindings = MatchBindings::default();
+            let pattern = self.parse_match_pattern(&mut bindings)?;
+            let has_bindings = !bindings.bound.is_empty();
+            if has_bindings {
+                self.closure_scopes.push(bindings.bound);
+            }
+            let arm_tail = self.parse_match_arm_tail();
+            if has_bindings {
+                self.pop_closure_scope();
+            }
+            let (guard, body) = arm_tail?;
+
+            if self.match_arm_is_unreachable(&coverage, &pattern) {
+                self.warnings.push(CompileWarning {
+                    line: arm_line,
+                    message:
+                        "unreachable match arm: earlier arms already match every value it matches"
+                            .to_string(),
+                });
+            }
+            coverage.record(&pattern, guard.is_some());
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                body,
+            });
 
-            match pattern {
-                Some(pattern) => {
-                    if default.is_some() {
-                        return Err(ParseError {
-                            span: None,
-                            code: None,
-                            line: pattern_token_line,
-                            message: "non-wildcard match arm cannot appear after '_' arm"
-                                .to_string(),
-                        });
-                    }
-                    arms.push((pattern, arm_expr));
+            if self.match_kind(&TokenKind::Comma) {
+                if self.check(&TokenKind::RBrace) {
+                    break;
                 }
-                None => {
-                    if default.is_some() {
-                        return Err(ParseError {
-                            span: None,
-                            code: None,
-                            line: pattern_token_line,
-                            message: "duplicate '_' match arm".to_string(),
-                        });
+                continue;
+            }
+            if !self.check(&TokenKind::RBrace) {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line: self.current_line(),
+                    message: "expected ',' or '}' after match arm".to_string(),
+                });
+            }
+        }
+        self.expect(&TokenKind::RBrace, "expected '}' after match expression")?;
+
+        if !coverage.catch_all && !self.match_enum_fully_covered(&coverage) {
+            let Some(enum_name) = coverage.enum_name() else {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line: self.current_line(),
+                    message: "match expression requires a wildcard arm '_ => ...' (or a catch-all binding) unless it covers every variant of an enum".to_string(),
+                });
+            };
+            let missing = self
+                .enum_types
+                .get(enum_name)
+                .map(|variants| {
+                    variants
+                        .iter()
+                        .map(|variant| format!("{enum_name}::{}", variant.name))
+                        .filter(|tag| !coverage.covered.contains(tag))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            self.warnings.push(CompileWarning {
+                line: match_line,
+                message: format!(
+                    "non-exhaustive match on enum '{enum_name}': {} not covered (unmatched values produce null)",
+                    missing.join(", ")
+                ),
+            });
+        }
+
+        Ok(Expr::Match {
+            value_slot,
+            result_slot,
+            value: Box::new(value),
+            arms,
+            default: Box::new(Expr::Null),
+        })
+    }
+
+    fn parse_match_arm_tail(&mut self) -> Result<(Option<Expr>, Expr), ParseError> {
+        let guard = if self.match_kind(&TokenKind::If) {
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+        self.expect(&TokenKind::FatArrow, "expected '=>' in match arm")?;
+        Ok((guard, self.parse_expr()?))
+    }
+
+    fn match_arm_is_unreachable(&self, coverage: &MatchCoverage, pattern: &MatchPattern) -> bool {
+        coverage.catch_all || self.match_enum_fully_covered(coverage) || coverage.covers(pattern)
+    }
+
+    fn match_enum_fully_covered(&self, coverage: &MatchCoverage) -> bool {
+        coverage
+            .enum_name()
+            .and_then(|enum_name| {
+                self.enum_types.get(enum_name).map(|variants| {
+                    variants.iter().all(|variant| {
+                        coverage
+                            .covered
+                            .contains(&format!("{enum_name}::{}", variant.name))
+                    })
+                })
+            })
+            .unwrap_or(false)
+    }
+
+    /// Parses a pattern with optional `|` alternatives. Every alternative must
+    /// bind the same names, which share slots so the arm body sees one local.
+    fn parse_match_pattern(
+        &mut self,
+        bindings: &mut MatchBindings,
+    ) -> Result<MatchPattern, ParseError> {
+        let base = bindings.bound.clone();
+        let first = self.parse_match_pattern_atom(bindings)?;
+        if !self.check(&TokenKind::Pipe) {
+            return Ok(first);
+        }
+
+        let first_names = bindings.names_bound_since(&base);
+        let mut alternatives = vec![first];
+        while self.match_kind(&TokenKind::Pipe) {
+            bindings.bound = base.clone();
+            alternatives.push(self.parse_match_pattern_atom(bindings)?);
+            if bindings.names_bound_since(&base) != first_names {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line: self.current_line(),
+                    message: "all alternatives of a '|' pattern must bind the same names"
+                        .to_string(),
+                });
+            }
+        }
+        Ok(MatchPattern::Or(alternatives))
+    }
+
+    fn parse_match_pattern_atom(
+        &mut self,
+        bindings: &mut MatchBindings,
+    ) -> Result<MatchPattern, ParseError> {
+        if self.match_kind(&TokenKind::LParen) {
+            let pattern = self.parse_match_pattern(bindings)?;
+            self.expect(
+                &TokenKind::RParen,
+                "expected ')' after parenthesized match pattern",
+            )?;
+            return Ok(pattern);
+        }
+        if self.match_kind(&TokenKind::LBracket) {
+            return self.parse_array_pattern(bindings);
+        }
+        if self.match_kind(&TokenKind::LBrace) {
+            return self.parse_map_pattern(bindings);
+        }
+        if let Some(inclusive_end) = self.match_range_operator() {
+            let end = self.match_pattern_number().ok_or_else(|| ParseError {
+                span: None,
+                code: None,
+                line: self.current_line(),
+                message: "expected an int or float bound after '..' in range pattern".to_string(),
+            })?;
+            return self.build_range_pattern(None, Some(end), inclusive_end);
+        }
+        if let Some(start) = self.match_pattern_number() {
+            if let Some(inclusive_end) = self.match_range_operator() {
+                let end = self.match_pattern_number();
+                if end.is_none() && inclusive_end {
+                    return Err(ParseError {
+                        span: None,
+                        code: None,
+                        line: self.current_line(),
+                        message: "inclusive range pattern '..=' requires an end bound".to_string(),
+                    });
+                }
+                return self.build_range_pattern(Some(start), end, inclusive_end);
+            }
+            return Ok(match start {
+                MatchRangeBound::Int(value) => MatchPattern::Int(value),
+                MatchRangeBound::Float(value) => MatchPattern::Float(value),
+            });
+        }
+        if let Some(value) = self.match_string() {
+            return Ok(MatchPattern::String(value));
+        }
+        if self.match_kind(&TokenKind::Null) {
+            return Ok(MatchPattern::Null);
+        }
+        if let Some(name) = self.match_ident() {
+            if name == "_" {
+                return Ok(MatchPattern::Wildcard);
+            }
+            if self.enum_types.contains_key(&name) && self.match_path_separator() {
+                return self.parse_enum_variant_pattern(&name, bindings);
+            }
+            if self.struct_types.contains_key(&name) && self.match_kind(&TokenKind::LBrace) {
+                let fields = self.struct_types.get(&name).cloned().unwrap_or_default();
+                let fields = self.parse_named_field_patterns(
+                    &format!("struct '{name}'"),
+                    &fields,
+                    bindings,
+                )?;
+                return Ok(MatchPattern::Variant(MatchVariantPattern {
+                    tag: name,
+                    fields,
+                }));
+            }
+            if let Some(type_pattern) = self.parse_match_type_constructor_pattern(&name)? {
+                return Ok(MatchPattern::Type(type_pattern));
+            }
+            if self.check_path_separator()
+                || self.check(&TokenKind::LParen)
+                || self.check(&TokenKind::LBrace)
+            {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line: self.current_line(),
+                    message: format!("unknown struct or enum '{name}' in match pattern"),
+                });
+            }
+            let slot = self.bind_match_pattern_name(name, bindings)?;
+            return Ok(MatchPattern::Binding(slot));
+        }
+        Err(ParseError { span: None, code: None,
+            line: self.current_line(),
+            message:
+                "match patterns currently support int/string/null literals, type patterns via Some(TypeName) or Option::Some(TypeName), ranges, array/map/struct/enum destructuring, bindings, and '_'"
+                    .to_string(),
+        })
+    }
+
+    /// Parses a pattern for a component of the matched value. Wildcards need
+    /// no slot and yield `None`; plain bindings load straight into their local.
+    fn parse_match_sub_pattern(
+        &mut self,
+        bindings: &mut MatchBindings,
+    ) -> Result<Option<MatchSubPattern>, ParseError> {
+        Ok(match self.parse_match_pattern(bindings)? {
+            MatchPattern::Wildcard => None,
+            MatchPattern::Binding(slot) => Some(MatchSubPattern {
+                slot,
+                pattern: MatchPattern::Wildcard,
+            }),
+            pattern => Some(MatchSubPattern {
+                slot: self.allocate_hidden_local()?,
+                pattern,
+            }),
+        })
+    }
+
+    fn parse_array_pattern(
+        &mut self,
+        bindings: &mut MatchBindings,
+    ) -> Result<MatchPattern, ParseError> {
+        let mut prefix = Vec::<Option<MatchSubPattern>>::new();
+        let mut suffix = Vec::<Option<MatchSubPattern>>::new();
+        let mut rest = None::<Option<u8>>;
+        while !self.check(&TokenKind::RBracket) {
+            if self.check_array_rest_start() {
+                self.pos += 2;
+                if rest.is_some() {
+                    return Err(ParseError {
+                        span: None,
+                        code: None,
+                        line: self.current_line(),
+                        message: "an array pattern can contain at most one '..'".to_string(),
+                    });
+                }
+                let binding = match self.match_ident() {
+                    Some(name) if name != "_" => {
+                        Some(self.bind_match_pattern_name(name, bindings)?)
                     }
-                    default = Some(arm_expr);
+                    _ => None,
+                };
+                rest = Some(binding);
+            } else {
+                let item = self.parse_match_sub_pattern(bindings)?;
+                if rest.is_some() {
+                    suffix.push(item);
+                } else {
+                    prefix.push(item);
                 }
             }
+            if !self.match_kind(&TokenKind::Comma) {
+                break;
+            }
+        }
+        self.expect(&TokenKind::RBracket, "expected ']' after array pattern")?;
+
+        let prefix_len = prefix.len();
+        let suffix_len = suffix.len();
+        let mut elements = Vec::new();
+        for (position, item) in prefix.into_iter().enumerate() {
+            if let Some(sub) = item {
+                elements.push((MatchArrayIndex::FromStart(position), sub));
+            }
+        }
+        for (position, item) in suffix.into_iter().enumerate() {
+            if let Some(sub) = item {
+                elements.push((MatchArrayIndex::FromEnd(suffix_len - position), sub));
+            }
+        }
+        Ok(MatchPattern::Array(MatchArrayPattern {
+            fixed_len: prefix_len + suffix_len,
+            elements,
+            rest: rest.map(|binding| MatchArrayRest {
+                start: prefix_len,
+                binding,
+            }),
+        }))
+    }
+
+    fn check_array_rest_start(&self) -> bool {
+        matches!(
+            (
+                self.tokens.get(self.pos).map(|token| &token.kind),
+                self.tokens.get(self.pos + 1).map(|token| &token.kind),
+                self.tokens.get(self.pos + 2).map(|token| &token.kind),
+            ),
+            (Some(TokenKind::Dot), Some(TokenKind::Dot), next)
+                if !matches!(
+                    next,
+                    Some(
+                        TokenKind::Equal | TokenKind::Int(_) | TokenKind::Float(_) | TokenKind::Minus
+                    )
+                )
+        )
+    }
+
+    fn parse_map_pattern(
+        &mut self,
+        bindings: &mut MatchBindings,
+    ) -> Result<MatchPattern, ParseError> {
+        let mut entries = Vec::<(MatchFieldKey, MatchSubPattern)>::new();
+        while !self.check(&TokenKind::RBrace) {
+            if self.match_rest_pattern()? {
+                break;
+            }
+            let (key, shorthand) = if let Some(name) = self.match_ident() {
+                (MatchFieldKey::Name(name.clone()), Some(name))
+            } else if let Some(name) = self.match_string() {
+                (MatchFieldKey::Name(name), None)
+            } else if let Some(position) = self.match_int() {
+                let position = usize::try_from(position).map_err(|_| ParseError {
+                    span: None,
+                    code: None,
+                    line: self.current_line(),
+                    message: "map pattern keys must be identifiers, strings, or non-negative ints"
+                        .to_string(),
+                })?;
+                (MatchFieldKey::Position(position), None)
+            } else {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line: self.current_line(),
+                    message: "map pattern keys must be identifiers, strings, or non-negative ints"
+                        .to_string(),
+                });
+            };
+            let duplicate = entries.iter().any(|(existing, _)| match (existing, &key) {
+                (MatchFieldKey::Name(lhs), MatchFieldKey::Name(rhs)) => lhs == rhs,
+                (MatchFieldKey::Position(lhs), MatchFieldKey::Position(rhs)) => lhs == rhs,
+                _ => false,
+            });
+            if duplicate {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line: self.current_line(),
+                    message: "map pattern lists the same key more than once".to_string(),
+                });
+            }
 
-            if self.match_kind(&TokenKind::Comma) {
-                if self.check(&TokenKind::RBrace) {
-                    break;
-                }
-                continue;
-            }
-            if !self.check(&TokenKind::RBrace) {
+            let sub = if self.match_kind(&TokenKind::Colon) {
+                self.parse_match_sub_pattern(bindings)?
+            } else if let Some(name) = shorthand {
+                let slot = self.bind_match_pattern_name(name, bindings)?;
+                Some(MatchSubPattern {
+                    slot,
+                    pattern: MatchPattern::Wildcard,
+                })
+            } else {
                 return Err(ParseError {
                     span: None,
                     code: None,
                     line: self.current_line(),
-                    message: "expected ',' or '}' after match arm".to_string(),
+                    message: "expected ':' and a pattern after map pattern key".to_string(),
                 });
+            };
+            // The key must exist even when its value is ignored, so wildcards
+            // still get a slot to load into.
+            let sub = match sub {
+                Some(sub) => sub,
+                None => MatchSubPattern {
+                    slot: self.allocate_hidden_local()?,
+                    pattern: MatchPattern::Wildcard,
+                },
+            };
+            entries.push((key, sub));
+            if !self.match_kind(&TokenKind::Comma) {
+                break;
             }
         }
-        self.expect(&TokenKind::RBrace, "expected '}' after match expression")?;
-
-        let default = default.ok_or_else(|| ParseError {
-            span: None,
-            code: None,
-            line: self.current_line(),
-            message: "match expression requires a wildcard arm '_ => ...'".to_string(),
-        })?;
+        self.expect(&TokenKind::RBrace, "expected '}' after map pattern")?;
+        Ok(MatchPattern::Map(entries))
+    }
 
-        Ok(Expr::Match {
-            value_slot,
-            result_slot,
-            value: Box::new(value),
-            arms,
-            default: Box::new(default),
-        })
+    fn match_range_operator(&mut self) -> Option<bool> {
+        let is_range = matches!(
+            (
+                self.tokens.get(self.pos).map(|token| &token.kind),
+                self.tokens.get(self.pos + 1).map(|token| &token.kind),
+            ),
+            (Some(TokenKind::Dot), Some(TokenKind::Dot))
+        );
+        if !is_range {
+            return None;
+        }
+        self.pos += 2;
+        Some(self.match_kind(&TokenKind::Equal))
     }
 
-    fn parse_match_pattern(&mut self) -> Result<Option<MatchPattern>, ParseError> {
-        if self.match_kind(&TokenKind::LParen) {
-            let pattern = self.parse_match_pattern()?;
-            self.expect(
-                &TokenKind::RParen,
-                "expected ')' after parenthesized match pattern",
-            )?;
-            return Ok(pattern);
+    fn match_pattern_number(&mut self) -> Option<MatchRangeBound> {
+        let negative = self.check(&TokenKind::Minus)
+            && matches!(
+                self.tokens.get(self.pos + 1).map(|token| &token.kind),
+                Some(TokenKind::Int(_) | TokenKind::Float(_))
+            );
+        if negative {
+            self.pos += 1;
         }
         if let Some(value) = self.match_int() {
-            return Ok(Some(MatchPattern::Int(value)));
-        }
-        if let Some(value) = self.match_string() {
-            return Ok(Some(MatchPattern::String(value)));
-        }
-        if self.match_kind(&TokenKind::Null) {
-            return Ok(Some(MatchPattern::Null));
+            return Some(MatchRangeBound::Int(if negative {
+                value.wrapping_neg()
+            } else {
+                value
+            }));
         }
-        if let Some(name) = self.match_ident() {
-            if name == "_" {
-                return Ok(None);
-            }
-            if let Some(type_pattern) = self.parse_match_type_constructor_pattern(&name)? {
-                return Ok(Some(MatchPattern::Type(type_pattern)));
+        self.match_float()
+            .map(|value| MatchRangeBound::Float(if negative { -value } else { value }))
+    }
+
+    fn build_range_pattern(
+        &self,
+        start: Option<MatchRangeBound>,
+        end: Option<MatchRangeBound>,
+        inclusive_end: bool,
+    ) -> Result<MatchPattern, ParseError> {
+        let as_f64 = |bound: MatchRangeBound| match bound {
+            MatchRangeBound::Int(value) => value as f64,
+            MatchRangeBound::Float(value) => value,
+        };
+        if let (Some(start), Some(end)) = (start, end) {
+            let (lo, hi) = (as_f64(start), as_f64(end));
+            if lo > hi || (!inclusive_end && lo == hi) {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line: self.current_line(),
+                    message: "range pattern is empty; the start must be below the end".to_string(),
+                });
             }
         }
-        Err(ParseError { span: None, code: None,
-            line: self.current_line(),
-            message:
-                "match patterns currently support int/string/null literals, type patterns via Some(TypeName) or Option::Some(TypeName), and '_'"
-                    .to_string(),
-        })
+        Ok(MatchPattern::Range(MatchRangePattern {
+            start,
+            end,
+            inclusive_end,
+        }))
     }
 
     fn parse_match_type_constructor_pattern(
@@ -1518,6 +2686,179 @@ impl Parser {
         Ok(Some(type_pattern))
     }
 
+    fn parse_enum_variant_pattern(
+        &mut self,
+        enum_name: &str,
+        bindings: &mut MatchBindings,
+    ) -> Result<MatchPattern, ParseError> {
+        let variant_name =
+            self.expect_ident("expected variant name after '::' in match pattern")?;
+        let variant = self.lookup_enum_variant(enum_name, &variant_name)?;
+        let tag = format!("{enum_name}::{variant_name}");
+        let fields = match variant.shape {
+            EnumVariantShape::Unit => Vec::new(),
+            EnumVariantShape::Tuple(arity) => {
+                self.expect(
+                    &TokenKind::LParen,
+                    &format!("expected '(' after enum variant '{tag}' in match pattern"),
+                )?;
+                let mut fields = Vec::new();
+                let mut position = 0usize;
+                let mut has_rest = false;
+                while !self.check(&TokenKind::RParen) {
+                    if self.match_rest_pattern()? {
+                        has_rest = true;
+                        break;
+                    }
+                    if let Some(sub) = self.parse_match_sub_pattern(bindings)? {
+                        fields.push((MatchFieldKey::Position(position), sub));
+                    }
+                    position += 1;
+                    if !self.match_kind(&TokenKind::Comma) {
+                        break;
+                    }
+                }
+                self.expect(
+                    &TokenKind::RParen,
+                    "expected ')' after variant pattern fields",
+                )?;
+                if position > arity || (!has_rest && position != arity) {
+                    return Err(ParseError {
+                        span: None,
+                        code: None,
+                        line: self.current_line(),
+                        message: format!(
+                            "pattern for enum variant '{tag}' has {position} field(s) but the variant has {arity}"
+                        ),
+                    });
+                }
+                fields
+            }
+            EnumVariantShape::Struct(declared) => {
+                self.expect(
+                    &TokenKind::LBrace,
+                    &format!("expected '{{' after enum variant '{tag}' in match pattern"),
+                )?;
+                self.parse_named_field_patterns(&format!("variant '{tag}'"), &declared, bindings)?
+            }
+        };
+        Ok(MatchPattern::Variant(MatchVariantPattern { tag, fields }))
+    }
+
+    /// Parses `field`, `field: pattern`, and a trailing `..` up to the closing
+    /// brace. Every declared field must be listed unless `..` is present.
+    fn parse_named_field_patterns(
+        &mut self,
+        owner: &str,
+        fields: &[String],
+        bindings: &mut MatchBindings,
+    ) -> Result<Vec<(MatchFieldKey, MatchSubPattern)>, ParseError> {
+        let mut field_patterns = Vec::new();
+        let mut seen = Vec::<String>::new();
+        let mut has_rest = false;
+        while !self.check(&TokenKind::RBrace) {
+            if self.match_rest_pattern()? {
+                has_rest = true;
+                break;
+            }
+            let field = self.expect_ident(&format!("expected field name in {owner} pattern"))?;
+            if !fields.contains(&field) {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line: self.current_line(),
+                    message: format!("{owner} has no field '{field}'"),
+                });
+            }
+            if seen.contains(&field) {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line: self.current_line(),
+                    message: format!("field '{field}' is listed more than once in {owner} pattern"),
+                });
+            }
+            let sub = if self.match_kind(&TokenKind::Colon) {
+                self.parse_match_sub_pattern(bindings)?
+            } else {
+                let slot = self.bind_match_pattern_name(field.clone(), bindings)?;
+                Some(MatchSubPattern {
+                    slot,
+                    pattern: MatchPattern::Wildcard,
+                })
+            };
+            if let Some(sub) = sub {
+                field_patterns.push((MatchFieldKey::Name(field.clone()), sub));
+            }
+            seen.push(field);
+            if !self.match_kind(&TokenKind::Comma) {
+                break;
+            }
+        }
+        self.expect(&TokenKind::RBrace, "expected '}' after field patterns")?;
+
+        if !has_rest {
+            let missing = fields
+                .iter()
+                .filter(|field| !seen.contains(field))
+                .map(String::as_str)
+                .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line: self.current_line(),
+                    message: format!(
+                        "{owner} pattern does not mention field(s) {}; add '..' to ignore them",
+                        missing.join(", ")
+                    ),
+                });
+            }
+        }
+        Ok(field_patterns)
+    }
+
+    fn match_rest_pattern(&mut self) -> Result<bool, ParseError> {
+        if !self.match_kind(&TokenKind::Dot) {
+            return Ok(false);
+        }
+        self.expect(&TokenKind::Dot, "expected '..' in pattern")?;
+        if !(self.check(&TokenKind::RBrace) || self.check(&TokenKind::RParen)) {
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line: self.current_line(),
+                message: "'..' must be the last element of a pattern".to_string(),
+            });
+        }
+        Ok(true)
+    }
+
+    fn bind_match_pattern_name(
+        &mut self,
+        name: String,
+        bindings: &mut MatchBindings,
+    ) -> Result<u8, ParseError> {
+        if bindings.bound.contains_key(&name) {
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line: self.current_line(),
+                message: format!("identifier '{name}' is bound more than once in the same pattern"),
+            });
+        }
+        let slot = match bindings.slots.get(&name) {
+            Some(&slot) => slot,
+            None => {
+                let slot = self.allocate_hidden_local()?;
+                bindings.slots.insert(name.clone(), slot);
+                slot
+            }
+        };
+        bindings.bound.insert(name, slot);
+        Ok(slot)
+    }
+
     fn parse_postfix_access(&mut self, mut expr: Expr) -> Result<Expr, ParseError> {
         loop {
             if self.match_kind(&TokenKind::LBracket) {
@@ -1550,11 +2891,20 @@ impl Parser {
             }
             if self.match_kind(&TokenKind::Dot) {
                 let member = self.expect_ident("expected member name after '.'")?;
-                if member == "length" {
+                let struct_type = self.struct_type_of_expr(&expr);
+                // A struct field of the same name wins over the builtin
+                // `length` / `keys` properties, which struct values keep.
+                let is_field = struct_type
+                    .as_deref()
+                    .is_some_and(|type_name| self.struct_declares_field(type_name, &member));
+                if !is_field && member == "length" {
                     expr = self.build_builtin_call_expr(BuiltinFunction::Len, vec![expr])?;
-                } else if member == "keys" {
+                } else if !is_field && member == "keys" {
                     expr = self.build_builtin_call_expr(BuiltinFunction::Keys, vec![expr])?;
                 } else {
+                    if let Some(type_name) = &struct_type {
+                        self.check_struct_field(type_name, &member)?;
+                    }
                     expr = self.build_builtin_call_expr(
                         BuiltinFunction::Get,
                         vec![expr, Expr::String(member)],
@@ -1736,6 +3086,144 @@ impl Parser {
         })
     }
 
+    fn check_macro_bang(&self) -> bool {
+        matches!(
+            (self.tokens.get(self.pos), self.tokens.get(self.pos + 1)),
+            (
+                Some(Token {
+                    kind: TokenKind::Bang,
+                    ..
+                }),
+                Some(Token {
+                    kind: TokenKind::LParen,
+                    ..
+                })
+            )
+        )
+    }
+
+    fn parse_format_macro(&mut self) -> Result<Expr, ParseError> {
+        self.expect(&TokenKind::Bang, "expected '!' after format")?;
+        self.expect(&TokenKind::LParen, "expected '(' after format!")?;
+        let line = self.current_line();
+        let template = self.parse_expr()?;
+
+        let mut positional = Vec::new();
+        let mut named = Vec::<(String, Expr)>::new();
+        while self.match_kind(&TokenKind::Comma) {
+            if self.check(&TokenKind::RParen) {
+                break;
+            }
+            let named_arg = match (self.tokens.get(self.pos), self.tokens.get(self.pos + 1)) {
+                (
+                    Some(Token {
+                        kind: TokenKind::Ident(name),
+                        ..
+                    }),
+                    Some(Token {
+                        kind: TokenKind::Equal,
+                        ..
+                    }),
+                ) => Some(name.clone()),
+                _ => None,
+            };
+            if let Some(name) = named_arg {
+                self.pos += 2;
+                if named.iter().any(|(existing, _)| *existing == name) {
+                    return Err(ParseError {
+                        span: None,
+                        code: None,
+                        line: self.current_line(),
+                        message: format!("duplicate named format argument '{name}'"),
+                    });
+                }
+                let value = self.parse_expr()?;
+                named.push((name, value));
+                continue;
+            }
+            if !named.is_empty() {
+                return Err(ParseError {
+                    span: None,
+                    code: None,
+                    line: self.current_line(),
+                    message: "positional format arguments must come before named arguments"
+                        .to_string(),
+                });
+            }
+            positional.push(self.parse_expr()?);
+        }
+        self.expect(&TokenKind::RParen, "expected ')' after format! arguments")?;
+
+        if let Expr::String(text) = &template {
+            self.check_format_template_arguments(text, positional.len(), &named, line)?;
+        }
+
+        let args = if named.is_empty() {
+            let mut out = self.build_builtin_call_expr(BuiltinFunction::ArrayNew, Vec::new())?;
+            for value in positional {
+                out = self.build_builtin_call_expr(BuiltinFunction::ArrayPush, vec![out, value])?;
+            }
+            out
+        } else {
+            let mut out = self.build_builtin_call_expr(BuiltinFunction::MapNew, Vec::new())?;
+            let keys = (0i64..)
+                .map(Expr::Int)
+                .zip(positional)
+                .chain(
+                    named
+                        .into_iter()
+                        .map(|(name, value)| (Expr::String(name), value)),
+                )
+                .collect::<Vec<_>>();
+            for (key, value) in keys {
+                out = self.build_builtin_call_expr(BuiltinFunction::Set, vec![out, key, value])?;
+            }
+            out
+        };
+        self.build_builtin_call_expr(BuiltinFunction::Format, vec![template, args])
+    }
+
+    fn check_format_template_arguments(
+        &self,
+        template: &str,
+        positional: usize,
+        named: &[(String, Expr)],
+        line: usize,
+    ) -> Result<(), ParseError> {
+        let error = |message: String| ParseError {
+            span: None,
+            code: None,
+            line,
+            message,
+        };
+        let pieces = parse_format_template(template).map_err(error)?;
+        let requirements = format_requirements(&pieces);
+        if requirements.positional != positional {
+            return Err(error(format!(
+                "format string expects {} positional argument(s) but {} were supplied",
+                requirements.positional, positional
+            )));
+        }
+        if let Some(missing) = requirements
+            .named
+            .iter()
+            .find(|name| !named.iter().any(|(supplied, _)| supplied == *name))
+        {
+            return Err(error(format!(
+                "format string references named argument '{missing}' which was not supplied"
+            )));
+        }
+        if let Some((unused, _)) = named
+            .iter()
+            .find(|(supplied, _)| !requirements.named.contains(supplied))
+        {
+            return Err(error(format!(
+                "named format argument '{unused}' is never used"
+            )));
+        }
+        Ok(())
+    }
+
     fn parse_array_literal(&mut self) -> Result<Expr, ParseError> {
         let mut out = self.build_builtin_call_expr(BuiltinFunction::ArrayNew, Vec::new())?;
         if !self.check(&TokenKind::RBracket) {
@@ -1912,7 +3400,19 @@ impl Parser {
                 | TokenKind::False
                 | TokenKind::Null
         );
-        let is_delim = matches!(next.kind, TokenKind::Colon | TokenKind::Equal);
+        // `ns::call()` is a value, not a `ns: ...` entry.
+        let is_path = matches!(
+            self.tokens.get(self.pos + 2),
+            Some(Token {
+                kind: TokenKind::Colon,
+                ..
+            })
+        );
+        let is_delim = match next.kind {
+            TokenKind::Colon => !is_path,
+            TokenKind::Equal => true,
+            _ => false,
+        };
         is_key && is_delim
     }
 
@@ -2042,6 +3542,36 @@ impl Parser {
                 "captures" => Some(BuiltinFunction::ReCaptures),
                 _ => None,
             },
+            "rand" => match member {
+                "int" => Some(BuiltinFunction::RandInt),
+                "float" => Some(BuiltinFunction::RandFloat),
+                "choice" => Some(BuiltinFunction::RandChoice),
+                "shuffle" => Some(BuiltinFunction::RandShuffle),
+                _ => None,
+            },
+            "uuid" => match member {
+                "v4" => Some(BuiltinFunction::UuidV4),
+                _ => None,
+            },
+            "array" => match member {
+                "sort" => Some(BuiltinFunction::ArraySort),
+                "sort_by_keys" => Some(BuiltinFunction::ArraySortByKeys),
+                "binary_search" => Some(BuiltinFunction::ArrayBinarySearch),
+                "index_of" => Some(BuiltinFunction::ArrayIndexOf),
+                "contains" => Some(BuiltinFunction::ArrayContains),
+                "dedup" => Some(BuiltinFunction::ArrayDedup),
+                "remove" => Some(BuiltinFunction::ArrayRemove),
+                "insert" => Some(BuiltinFunction::ArrayInsert),
+                "pop" => Some(BuiltinFunction::ArrayPop),
+                _ => None,
+            },
+            "map" => match member {
+                "remove" => Some(BuiltinFunction::MapRemove),
+                "has_key" => Some(BuiltinFunction::MapHasKey),
+                "values" => Some(BuiltinFunction::MapValues),
+                "entries" => Some(BuiltinFunction::MapEntries),
+                _ => None,
+            },
             _ => None,
         }
     }
@@ -2094,6 +3624,54 @@ impl Parser {
         })
     }
 
+    /// `lua::find(s, pattern, init?, plain?)`, `lua::match(s, pattern, init?)`,
+    /// `lua::gmatch(s, pattern, init?)`, and `lua::gsub(s, pattern, repl, max?)`;
+    /// omitted trailing arguments default to `null`. The metatable builtins
+    /// (`lua::setmetatable`, `lua::rawget`, `lua::metamethod`, ...) back the
+    /// Lua frontend's table and metamethod lowering; `lua::index` and
+    /// `lua::plain(value, deep?)` are how Lua and JavaScript code reads tables
+    /// that carry a metatable or class tag.
+    fn try_lua_namespace_builtin_call(
+        &mut self,
+        member: &str,
+        args: &mut Vec<Expr>,
+    ) -> Result<Option<BuiltinFunction>, ParseError> {
+        let (builtin, required) = match member {
+            "find" => (BuiltinFunction::LuaFind, 2usize),
+            "match" => (BuiltinFunction::LuaMatch, 2usize),
+            "gmatch" => (BuiltinFunction::LuaGmatch, 2usize),
+            "gsub" => (BuiltinFunction::LuaGsub, 3usize),
+            "setmetatable" => (BuiltinFunction::LuaSetMetatable, 2usize),
+            "getmetatable" => (BuiltinFunction::LuaGetMetatable, 1usize),
+            "rawget" => (BuiltinFunction::LuaRawGet, 2usize),
+            "rawset" => (BuiltinFunction::LuaRawSet, 3usize),
+            "rawequal" => (BuiltinFunction::LuaRawEqual, 2usize),
+            "metamethod" => (BuiltinFunction::LuaMetamethod, 3usize),
+            "index_owner" => (BuiltinFunction::LuaIndexOwner, 2usize),
+            "newindex" => (BuiltinFunction::LuaNewIndex, 3usize),
+            "error" => (BuiltinFunction::LuaError, 1usize),
+            "index" => (BuiltinFunction::LuaIndex, 2usize),
+            "plain" => (BuiltinFunction::LuaPlain, 1usize),
+            _ => return Ok(None),
+        };
+        let arity = builtin.arity() as usize;
+        if args.len() < required || args.len() > arity {
+            let expected = if required == arity {
+                arity.to_string()
+            } else {
+                format!("{required} to {arity}")
+            };
+            return Err(ParseError {
+                span: None,
+                code: None,
+                line: self.current_line(),
+                message: format!("function 'lua::{member}' expects {expected} arguments"),
+            });
+        }
+        args.resize(arity, Expr::Null);
+        Ok(Some(builtin))
+    }
+
     fn apply_regex_flags_to_pattern_expr(
         &mut self,
         pattern: Expr,
@@ -2116,6 +3694,7 @@ impl Parser {
     ) -> Result<Stmt, ParseError> {
         let line = self.current_line_u32();
         let name = self.expect_ident("expected identifier before indexed assignment")?;
+        let is_member = self.check(&TokenKind::Dot);
         let key = if self.match_kind(&TokenKind::LBracket) {
             let key = self.parse_expr()?;
             self.expect(&TokenKind::RBracket, "expected ']' after assignment index")?;
@@ -2140,6 +3719,12 @@ impl Parser {
         }
 
         let index = self.get_local(&name)?;
+        if is_member
+            && let (Some(type_name), Expr::String(field)) =
+                (self.local_struct_types.get(&index), &key)
+        {
+            self.check_struct_field(type_name, field)?;
+        }
         let expr =
             self.build_builtin_call_expr(BuiltinFunction::Set, vec![Expr::Var(index), key, value])?;
         Ok(Stmt::Assign { index, expr, line })
@@ -2242,7 +3827,14 @@ impl Parser {
             by_name: HashMap::new(),
             capture_copies: Vec::new(),
         });
-        let body = self.parse_expr()?;
+        self.return_scopes.push(0);
+        let body = if self.allow_block_closures && self.match_kind(&TokenKind::FatArrow) {
+            self.parse_expr_block("closure body")
+        } else {
+            self.parse_expr()
+        };
+        let return_slot = self.finish_return_scope();
+        let body = body?;
         let capture_context = self
             .closure_capture_contexts
             .pop()
@@ -2252,11 +3844,12 @@ impl Parser {
                 line: self.current_line(),
                 message: "internal closure capture state error".to_string(),
             })?;
-        self.closure_scopes.pop();
+        self.pop_closure_scope();
         Ok(Expr::Closure(ClosureExpr {
             param_slots,
             capture_copies: capture_context.capture_copies,
             body: Box::new(body),
+            return_slot,
         }))
     }
 
@@ -2314,6 +3907,12 @@ impl Parser {
         }
     }
 
+    fn pop_closure_scope(&mut self) {
+        if let Some(scope) = self.closure_scopes.pop() {
+            self.scoped_bindings.extend(scope);
+        }
+    }
+
     fn get_local(&mut self, name: &str) -> Result<u8, ParseError> {
         for scope in self.closure_scopes.iter().rev() {
             if let Some(&index) = scope.get(name) {
@@ -2334,6 +3933,7 @@ impl Parser {
                 self.closure_capture_contexts[capture_idx]
                     .capture_copies
                     .push((source_index, captured_slot));
+                self.scoped_bindings.push((name.to_string(), captured_slot));
                 return Ok(captured_slot);
             }
             return Ok(source_index);
@@ -2711,6 +4311,7 @@ impl Parser {
             || self.check(&TokenKind::While)
             || self.check(&TokenKind::Break)
             || self.check(&TokenKind::Continue)
+            || self.check(&TokenKind::Return)
             || self.check_assignment_start()
             || self.check_index_assignment_start()
         {
@@ -2817,6 +4418,16 @@ impl Parser {
         self.function_impls.clone()
     }
 
+    pub(super) fn warnings(&self) -> Vec<CompileWarning> {
+        self.warnings.clone()
+    }
+
+    pub(super) fn scoped_bindings(&self) -> Vec<(String, u8)> {
+        let mut bindings = self.scoped_bindings.clone();
+        bindings.sort_by_key(|(_, index)| *index);
+        bindings
+    }
+
     pub(super) fn local_bindings(&self) -> Vec<(String, u8)> {
         let mut locals: Vec<(String, u8)> = self
             .locals
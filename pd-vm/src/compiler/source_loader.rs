@@ -3,10 +3,12 @@ use std::path::{Path, PathBuf};
 
 use crate::compiler::source_map::SourceMap;
 
-use super::frontends::{is_ident_continue, is_ident_start};
+use super::frontends::{is_ident_continue, is_ident_start, push_source_byte};
 use super::{
     SourceError, SourceFlavor, SourcePathError, frontends,
+    ir::FunctionDecl,
     linker::{ParsedUnit, sanitize_scope_prefix},
+    stdlib,
 };
 
 #[derive(Clone, Debug)]
@@ -32,58 +34,147 @@ struct ModuleImport {
 
 const VM_HOST_NAMESPACE_SPEC: &str = "vm";
 
+/// Finds imported modules: next to the importing file first, then under each
+/// search path, then among the embedded `std` modules.
+pub(super) struct ModuleResolver {
+    search_paths: Vec<PathBuf>,
+    /// Source text compiled without a file has no directory to look in, so
+    /// only embedded modules resolve.
+    embedded_only: bool,
+}
+
+impl ModuleResolver {
+    pub(super) fn new(search_paths: Vec<PathBuf>) -> Self {
+        Self {
+            search_paths,
+            embedded_only: false,
+        }
+    }
+
+    pub(super) fn embedded_only() -> Self {
+        Self {
+            search_paths: Vec::new(),
+            embedded_only: true,
+        }
+    }
+
+    fn resolve(&self, base_path: &Path, spec: &str) -> Result<PathBuf, SourcePathError> {
+        if self.embedded_only {
+            return stdlib::embedded_module_path(spec)
+                .ok_or_else(|| SourcePathError::UnresolvedModule(spec.to_string()));
+        }
+        if Path::new(spec).is_absolute() {
+            return with_module_extension(PathBuf::from(spec));
+        }
+        let parent = base_path
+            .parent()
+            .ok_or_else(|| SourcePathError::ImportWithoutParent(base_path.to_path_buf()))?;
+        let relative = with_module_extension(parent.join(spec))?;
+        let explicit = spec.starts_with("./") || spec.starts_with("../");
+        if explicit || relative.is_file() {
+            return Ok(relative);
+        }
+        for dir in &self.search_paths {
+            let candidate = with_module_extension(dir.join(spec))?;
+            if candidate.is_file() {
+                return Ok(candidate);
+            }
+        }
+        // A missing module keeps its relative path so reading it reports
+        // the file that was expected.
+        Ok(stdlib::embedded_module_path(spec).unwrap_or(relative))
+    }
+
+    fn read(&self, path: &Path) -> Result<String, SourcePathError> {
+        match stdlib::embedded_module_source(path) {
+            Some(source) => Ok(source.to_string()),
+            None => Ok(std::fs::read_to_string(path)?),
+        }
+    }
+}
+
+/// Whether `source` imports any module, so it needs the loader to compile.
+pub(super) fn has_module_imports(source: &str, flavor: SourceFlavor) -> bool {
+    parse_module_imports(source, flavor, Path::new(""))
+        .map(|imports| {
+            imports
+                .iter()
+                .any(|import| is_module_specifier(&import.spec))
+        })
+        .unwrap_or(false)
+}
+
 pub(super) fn load_units_for_source_file(
     path: &Path,
     flavor: SourceFlavor,
     source_raw: &str,
-) -> Result<(String, Vec<ParsedUnit>), SourcePathError> {
+    resolver: &ModuleResolver,
+) -> Result<Vec<ParsedUnit>, SourcePathError> {
     let root_imports = parse_module_imports(source_raw, flavor, path)?;
     let source = strip_import_directives(source_raw, flavor);
 
     let mut units = Vec::new();
     let mut visiting = vec![path.to_path_buf()];
-    let mut seen = HashSet::new();
     let mut module_exports = HashMap::<PathBuf, HashMap<String, u8>>::new();
     collect_module_units(
         path,
         source_raw,
         flavor,
         &mut visiting,
-        &mut seen,
         &mut units,
         &mut module_exports,
+        resolver,
     )?;
 
-    let rewritten_root_source =
-        rewrite_imported_call_sites(&source, flavor, path, &root_imports, &module_exports)?;
-    let root_parse_source = match flavor {
+    let rewritten_root_source = rewrite_imported_call_sites(
+        &source,
+        flavor,
+        path,
+        &root_imports,
+        &module_exports,
+        resolver,
+    )?;
+    let prelude = match flavor {
         SourceFlavor::Scheme => {
-            let mut prelude = build_scheme_import_prelude(path, &root_imports, &module_exports)?;
-            prelude.push_str(&rewritten_root_source);
-            prelude
+            build_scheme_import_prelude(path, &root_imports, &module_exports, resolver)?
+        }
+        SourceFlavor::JavaScript => {
+            build_javascript_import_prelude(path, &root_imports, &module_exports, resolver)?
         }
-        SourceFlavor::RustScript | SourceFlavor::JavaScript | SourceFlavor::Lua => {
-            let mut prelude =
-                build_rustscript_import_prelude(path, &root_imports, &module_exports)?;
-            prelude.push_str(&rewritten_root_source);
-            prelude
+        SourceFlavor::RustScript | SourceFlavor::Lua => {
+            build_rustscript_import_prelude(path, &root_imports, &module_exports, resolver)?
         }
+        SourceFlavor::Assembly => String::new(),
     };
+    let root_parse_source = format!("{prelude}{rewritten_root_source}");
+    // The prelude sits above the file's first line; shift lines back so they
+    // point into the file as written.
+    let prelude_lines = prelude.matches('\n').count();
+    let to_file_line = |line: usize| line.saturating_sub(prelude_lines).max(1);
 
     let mut root_source_map = SourceMap::new();
-    let root_source_id =
-        root_source_map.add_source(path.display().to_string(), root_parse_source.clone());
-    let root_parsed = frontends::parse_source(&root_parse_source, flavor)
-        .map_err(|err| {
+    let root_source_id = root_source_map.add_source(path.display().to_string(), source_raw);
+    let mut root_parsed = frontends::parse_source(&root_parse_source, flavor)
+        .map_err(|mut err| {
+            if prelude_lines > 0 {
+                err.line = to_file_line(err.line);
+                err.span = None;
+            }
             SourceError::Parse(err.with_line_span_from_source(&root_source_map, root_source_id))
         })
         .map_err(SourcePathError::Source)?;
+    if prelude_lines > 0 {
+        root_parsed.remap_lines(to_file_line);
+    }
     units.push(ParsedUnit {
         parsed: root_parsed,
         scope_prefix: None,
+        source_name: path.display().to_string(),
+        source_text: source_raw.to_string(),
     });
+    link_frontend_helpers(&mut units)?;
 
-    Ok((root_parse_source, units))
+    Ok(units)
 }
 
 fn parse_module_imports(
@@ -91,12 +182,29 @@ fn parse_module_imports(
     flavor: SourceFlavor,
     path: &Path,
 ) -> Result<Vec<ModuleImport>, SourcePathError> {
-    match flavor {
-        SourceFlavor::RustScript => parse_rustscript_imports(source, path),
-        SourceFlavor::JavaScript => Ok(parse_js_imports(source)),
-        SourceFlavor::Lua => Ok(parse_lua_imports(source)),
-        SourceFlavor::Scheme => parse_scheme_imports(source, path),
+    let mut imports = match flavor {
+        SourceFlavor::RustScript => parse_rustscript_imports(source, path)?,
+        SourceFlavor::JavaScript => parse_js_imports(source),
+        SourceFlavor::Lua => parse_lua_imports(source),
+        SourceFlavor::Scheme => parse_scheme_imports(source, path)?,
+        SourceFlavor::Assembly => Vec::new(),
+    };
+    for import in &mut imports {
+        import.spec = normalize_std_spec(&import.spec);
+    }
+    Ok(imports)
+}
+
+/// Spells the `std.strings` and `std/strings` forms of a std import the way
+/// `use std::strings` produces them, as `std/strings.rss`.
+fn normalize_std_spec(spec: &str) -> String {
+    if let Some(name) = spec.strip_prefix("std.") {
+        return format!("{}{}.rss", stdlib::STD_SPEC_PREFIX, name.replace('.', "/"));
     }
+    if spec.starts_with(stdlib::STD_SPEC_PREFIX) && Path::new(spec).extension().is_none() {
+        return format!("{spec}.rss");
+    }
+    spec.to_string()
 }
 
 fn parse_rustscript_imports(
@@ -251,7 +359,7 @@ fn rustscript_use_module_to_spec(
                 return Err(SourcePathError::InvalidImportSyntax {
                     path: path.to_path_buf(),
                     line,
-                    message: "crate:: paths are not supported; use relative module paths"
+                    message: "crate:: paths are not supported; use relative or std:: module paths"
                         .to_string(),
                 });
             }
@@ -754,12 +862,22 @@ fn parse_scheme_import_set(
             *pos += 1;
             Ok(())
         }
+        Some(SchemeImportToken::LParen) if matches!(tokens.get(*pos + 1), Some(SchemeImportToken::Symbol(head)) if head == "std") =>
+        {
+            let spec = expect_scheme_module(path, line, tokens, pos)?;
+            imports.push(ModuleImport {
+                spec,
+                clause: ImportClause::AllPublic,
+                line,
+            });
+            Ok(())
+        }
         Some(SchemeImportToken::LParen) => {
             *pos += 1;
             let keyword = expect_scheme_symbol(path, line, tokens, pos)?;
             match (form_head, keyword.as_str()) {
                 ("import", "only") | ("require", "only-in") => {
-                    let spec = expect_scheme_string(path, line, tokens, pos)?;
+                    let spec = expect_scheme_module(path, line, tokens, pos)?;
                     let mut named = Vec::new();
                     while !matches!(tokens.get(*pos), Some(SchemeImportToken::RParen)) {
                         let symbol = expect_scheme_symbol(path, line, tokens, pos)?;
@@ -784,7 +902,7 @@ fn parse_scheme_import_set(
                     Ok(())
                 }
                 ("import", "rename") | ("require", "rename-in") => {
-                    let spec = expect_scheme_string(path, line, tokens, pos)?;
+                    let spec = expect_scheme_module(path, line, tokens, pos)?;
                     let mut named = Vec::new();
                     while !matches!(tokens.get(*pos), Some(SchemeImportToken::RParen)) {
                         expect_scheme_token(path, line, tokens, pos, SchemeImportToken::LParen)?;
@@ -810,7 +928,7 @@ fn parse_scheme_import_set(
                     Ok(())
                 }
                 ("import", "prefix") => {
-                    let spec = expect_scheme_string(path, line, tokens, pos)?;
+                    let spec = expect_scheme_module(path, line, tokens, pos)?;
                     let prefix = expect_scheme_symbol(path, line, tokens, pos)?;
                     expect_scheme_token(path, line, tokens, pos, SchemeImportToken::RParen)?;
                     imports.push(ModuleImport {
@@ -822,7 +940,7 @@ fn parse_scheme_import_set(
                 }
                 ("import", "prefix-in") | ("require", "prefix-in") => {
                     let prefix = expect_scheme_symbol(path, line, tokens, pos)?;
-                    let spec = expect_scheme_string(path, line, tokens, pos)?;
+                    let spec = expect_scheme_module(path, line, tokens, pos)?;
                     expect_scheme_token(path, line, tokens, pos, SchemeImportToken::RParen)?;
                     imports.push(ModuleImport {
                         spec,
@@ -832,7 +950,7 @@ fn parse_scheme_import_set(
                     Ok(())
                 }
                 (_, "library") | (_, "module") => {
-                    let spec = expect_scheme_string(path, line, tokens, pos)?;
+                    let spec = expect_scheme_module(path, line, tokens, pos)?;
                     expect_scheme_token(path, line, tokens, pos, SchemeImportToken::RParen)?;
                     imports.push(ModuleImport {
                         spec,
@@ -922,6 +1040,36 @@ fn expect_scheme_string(
     })
 }
 
+/// A module in an import set: a string path, or a library name such as
+/// `(std strings)`, which names `std/strings.rss`.
+fn expect_scheme_module(
+    path: &Path,
+    line: usize,
+    tokens: &[SchemeImportToken],
+    pos: &mut usize,
+) -> Result<String, SourcePathError> {
+    if !matches!(tokens.get(*pos), Some(SchemeImportToken::LParen)) {
+        return expect_scheme_string(path, line, tokens, pos);
+    }
+    *pos += 1;
+    let mut segments = Vec::new();
+    while !matches!(tokens.get(*pos), Some(SchemeImportToken::RParen)) {
+        segments.push(expect_scheme_symbol(path, line, tokens, pos)?);
+    }
+    *pos += 1;
+    if segments.first().map(String::as_str) != Some("std") || segments.len() < 2 {
+        return Err(SourcePathError::InvalidImportSyntax {
+            path: path.to_path_buf(),
+            line,
+            message: format!(
+                "unsupported scheme library name '({})'; use (std <module>) or a string path",
+                segments.join(" ")
+            ),
+        });
+    }
+    Ok(format!("{}.rss", segments.join("/")))
+}
+
 fn parse_require_spec(line: &str) -> Option<String> {
     let require_idx = line.find("require(")?;
     let tail = &line[require_idx + "require(".len()..];
@@ -958,15 +1106,7 @@ fn is_module_specifier(spec: &str) -> bool {
         || spec.starts_with('/')
 }
 
-fn resolve_module_path(base_path: &Path, spec: &str) -> Result<PathBuf, SourcePathError> {
-    let parent = base_path
-        .parent()
-        .ok_or_else(|| SourcePathError::ImportWithoutParent(base_path.to_path_buf()))?;
-    let mut path = if Path::new(spec).is_absolute() {
-        PathBuf::from(spec)
-    } else {
-        parent.join(spec)
-    };
+fn with_module_extension(mut path: PathBuf) -> Result<PathBuf, SourcePathError> {
     if path.extension().is_none() {
         path.set_extension("rss");
     }
@@ -992,7 +1132,7 @@ fn strip_import_directives(source: &str, flavor: SourceFlavor) -> String {
             .collect::<Vec<_>>()
             .join("\n"),
         SourceFlavor::Scheme => source.to_string(),
-        SourceFlavor::JavaScript | SourceFlavor::Lua => source.to_string(),
+        SourceFlavor::JavaScript | SourceFlavor::Lua | SourceFlavor::Assembly => source.to_string(),
     }
 }
 
@@ -1019,9 +1159,9 @@ fn collect_module_units(
     source: &str,
     flavor: SourceFlavor,
     visiting: &mut Vec<PathBuf>,
-    seen: &mut HashSet<PathBuf>,
     units: &mut Vec<ParsedUnit>,
     module_exports: &mut HashMap<PathBuf, HashMap<String, u8>>,
+    resolver: &ModuleResolver,
 ) -> Result<(), SourcePathError> {
     let imports = parse_module_imports(source, flavor, path)?;
     for import in imports {
@@ -1029,52 +1169,100 @@ fn collect_module_units(
         if !is_module_specifier(&spec) {
             continue;
         }
-        let resolved = resolve_module_path(path, &spec)?;
+        let resolved = resolver.resolve(path, &spec)?;
         let key = resolved.clone();
         if visiting.contains(&key) {
             return Err(SourcePathError::ImportCycle(key));
         }
-        if seen.contains(&key) {
+        if module_exports.contains_key(&key) {
             continue;
         }
 
-        let module_source_raw = std::fs::read_to_string(&resolved)?;
+        let module_source_raw = resolver.read(&resolved)?;
         visiting.push(key.clone());
         collect_module_units(
             &resolved,
             &module_source_raw,
             SourceFlavor::RustScript,
             visiting,
-            seen,
             units,
             module_exports,
+            resolver,
         )?;
         visiting.pop();
 
-        let module_source = strip_import_directives(&module_source_raw, SourceFlavor::RustScript);
-        let mut module_source_map = SourceMap::new();
-        let module_source_id =
-            module_source_map.add_source(resolved.display().to_string(), module_source.clone());
-        let parsed = frontends::parse_source(&module_source, SourceFlavor::RustScript)
-            .map_err(|err| {
-                SourceError::Parse(
-                    err.with_line_span_from_source(&module_source_map, module_source_id),
-                )
-            })
-            .map_err(SourcePathError::Source)?;
-        let exports = parsed
+        let unit = parse_module_unit(&resolved, module_source_raw)?;
+        let exports = unit
+            .parsed
             .functions
             .iter()
             .filter(|func| func.exported)
             .map(|func| (func.name.clone(), func.arity))
             .collect::<HashMap<_, _>>();
-        units.push(ParsedUnit {
-            parsed,
-            scope_prefix: Some(sanitize_scope_prefix(&resolved)),
-        });
-        module_exports.insert(key.clone(), exports);
-        seen.insert(key);
+        units.push(unit);
+        module_exports.insert(key, exports);
+    }
+    Ok(())
+}
+
+fn parse_module_unit(path: &Path, source_raw: String) -> Result<ParsedUnit, SourcePathError> {
+    let source = strip_import_directives(&source_raw, SourceFlavor::RustScript);
+    let mut source_map = SourceMap::new();
+    let source_id = source_map.add_source(path.display().to_string(), source.clone());
+    let parsed = frontends::parse_source(&source, SourceFlavor::RustScript)
+        .map_err(|err| SourceError::Parse(err.with_line_span_from_source(&source_map, source_id)))
+        .map_err(SourcePathError::Source)?;
+    Ok(ParsedUnit {
+        parsed,
+        scope_prefix: Some(sanitize_scope_prefix(path)),
+        source_name: path.display().to_string(),
+        source_text: source_raw,
+    })
+}
+
+/// Frontends lower comparator sorts (`arr.sort(cmp)`, `table.sort(t, less)`,
+/// `(sort list less?)`) to a call of a body-less `__frontend_sort_by`. When a
+/// unit declares it, `sort_by` from `std::collections` is loaded under that
+/// name. The module's other functions are left out, so they cannot clash with
+/// the program's own.
+pub(super) fn link_frontend_helpers(units: &mut Vec<ParsedUnit>) -> Result<(), SourcePathError> {
+    let declared = |unit: &ParsedUnit| {
+        unit.parsed
+            .functions
+            .iter()
+            .find(|func| func.name == frontends::SORT_BY_HELPER_NAME)
+            .map(|func| unit.parsed.function_impls.contains_key(&func.index))
+    };
+    let needed = units.iter().any(|unit| declared(unit) == Some(false))
+        && !units.iter().any(|unit| declared(unit) == Some(true));
+    if !needed {
+        return Ok(());
     }
+
+    let resolver = ModuleResolver::embedded_only();
+    let path = resolver.resolve(Path::new(""), "std/collections.rss")?;
+    let mut unit = parse_module_unit(&path, resolver.read(&path)?)?;
+    let sort_by = unit
+        .parsed
+        .functions
+        .iter()
+        .find(|func| func.name == "sort_by")
+        .cloned()
+        .ok_or_else(|| SourcePathError::InvalidImportSyntax {
+            path: path.clone(),
+            line: 1,
+            message: "module 'std::collections' has no public function 'sort_by'".to_string(),
+        })?;
+    unit.parsed
+        .function_impls
+        .retain(|index, _| *index == sort_by.index);
+    unit.parsed.functions = vec![FunctionDecl {
+        name: frontends::SORT_BY_HELPER_NAME.to_string(),
+        exported: false,
+        ..sort_by
+    }];
+    unit.parsed.stmts.clear();
+    units.push(unit);
     Ok(())
 }
 
@@ -1082,8 +1270,9 @@ fn build_rustscript_import_prelude(
     path: &Path,
     imports: &[ModuleImport],
     module_exports: &HashMap<PathBuf, HashMap<String, u8>>,
+    resolver: &ModuleResolver,
 ) -> Result<String, SourcePathError> {
-    let declared = collect_imported_module_functions(path, imports, module_exports)?;
+    let declared = collect_imported_module_functions(path, imports, module_exports, resolver)?;
     let mut prelude = String::new();
     for (name, arity) in declared {
         let args = (0..arity)
@@ -1095,12 +1284,31 @@ fn build_rustscript_import_prelude(
     Ok(prelude)
 }
 
+fn build_javascript_import_prelude(
+    path: &Path,
+    imports: &[ModuleImport],
+    module_exports: &HashMap<PathBuf, HashMap<String, u8>>,
+    resolver: &ModuleResolver,
+) -> Result<String, SourcePathError> {
+    let declared = collect_imported_module_functions(path, imports, module_exports, resolver)?;
+    let mut prelude = String::new();
+    for (name, arity) in declared {
+        let args = (0..arity)
+            .map(|idx| format!("arg{idx}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        prelude.push_str(&format!("declare function {name}({args});\n"));
+    }
+    Ok(prelude)
+}
+
 fn build_scheme_import_prelude(
     path: &Path,
     imports: &[ModuleImport],
     module_exports: &HashMap<PathBuf, HashMap<String, u8>>,
+    resolver: &ModuleResolver,
 ) -> Result<String, SourcePathError> {
-    let declared = collect_imported_module_functions(path, imports, module_exports)?;
+    let declared = collect_imported_module_functions(path, imports, module_exports, resolver)?;
     let mut prelude = String::new();
     for (name, arity) in declared {
         let args = (0..arity)
@@ -1116,6 +1324,7 @@ fn collect_imported_module_functions(
     path: &Path,
     imports: &[ModuleImport],
     module_exports: &HashMap<PathBuf, HashMap<String, u8>>,
+    resolver: &ModuleResolver,
 ) -> Result<Vec<(String, u8)>, SourcePathError> {
     let mut imported_functions = HashMap::<String, u8>::new();
 
@@ -1124,7 +1333,7 @@ fn collect_imported_module_functions(
             continue;
         }
 
-        let resolved = resolve_module_path(path, &import.spec)?;
+        let resolved = resolver.resolve(path, &import.spec)?;
         let exports =
             module_exports
                 .get(&resolved)
@@ -1175,6 +1384,7 @@ fn rewrite_imported_call_sites(
     path: &Path,
     imports: &[ModuleImport],
     module_exports: &HashMap<PathBuf, HashMap<String, u8>>,
+    resolver: &ModuleResolver,
 ) -> Result<String, SourcePathError> {
     let mut alias_calls = HashMap::<String, String>::new();
     let mut namespace_calls = HashMap::<String, HashSet<String>>::new();
@@ -1201,7 +1411,7 @@ fn rewrite_imported_call_sites(
             continue;
         }
 
-        let resolved = resolve_module_path(path, &import.spec)?;
+        let resolved = resolver.resolve(path, &import.spec)?;
         let Some(exports) = module_exports.get(&resolved) else {
             continue;
         };
@@ -1290,7 +1500,7 @@ fn rewrite_function_call_paths(
         let b = bytes[i];
 
         if let Some(delim) = string_delim {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             if escaped {
                 escaped = false;
             } else if b == b'\\' {
@@ -1303,7 +1513,7 @@ fn rewrite_function_call_paths(
         }
 
         if in_line_comment {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             if b == b'\n' {
                 in_line_comment = false;
             }
@@ -1312,7 +1522,7 @@ fn rewrite_function_call_paths(
         }
 
         if in_block_comment {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             if b == b'*' && i + 1 < bytes.len() && bytes[i + 1] == b'/' {
                 out.push('/');
                 i += 2;
@@ -1340,7 +1550,7 @@ fn rewrite_function_call_paths(
         }
 
         if b == b'"' || b == b'\'' || b == b'`' {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             i += 1;
             string_delim = Some(b);
             escaped = false;
@@ -1469,7 +1679,7 @@ fn rewrite_function_call_paths(
             continue;
         }
 
-        out.push(b as char);
+        push_source_byte(&mut out, bytes, i);
         i += 1;
     }
 
@@ -1494,7 +1704,7 @@ fn rewrite_scheme_call_heads(
         let b = bytes[i];
 
         if in_line_comment {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             if b == b'\n' {
                 in_line_comment = false;
             }
@@ -1503,7 +1713,7 @@ fn rewrite_scheme_call_heads(
         }
 
         if in_string {
-            out.push(b as char);
+            push_source_byte(&mut out, bytes, i);
             if escaped {
                 escaped = false;
             } else if b == b'\\' {
@@ -1534,7 +1744,7 @@ fn rewrite_scheme_call_heads(
             out.push('(');
             i += 1;
             while i < bytes.len() && bytes[i].is_ascii_whitespace() {
-                out.push(bytes[i] as char);
+                push_source_byte(&mut out, bytes, i);
                 i += 1;
             }
 
@@ -1590,7 +1800,7 @@ fn rewrite_scheme_call_heads(
             continue;
         }
 
-        out.push(b as char);
+        push_source_byte(&mut out, bytes, i);
         i += 1;
     }
 
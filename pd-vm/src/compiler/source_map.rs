@@ -159,6 +159,14 @@ impl LineSpanMapping {
         }
     }
 
+    /// Original source line for a 1-based line of the lowered text.
+    pub fn original_line(&self, lowered_line: usize) -> usize {
+        *self
+            .lowered_to_original_line
+            .get(lowered_line.saturating_sub(1))
+            .unwrap_or(&lowered_line)
+    }
+
     pub fn map_span(
         &self,
         source_map: &SourceMap,
@@ -171,10 +179,7 @@ impl LineSpanMapping {
         }
         let (lowered_line, lowered_col) =
             source_map.line_col_for_offset(lowered_source_id, lowered_span.lo)?;
-        let original_line = *self
-            .lowered_to_original_line
-            .get(lowered_line.saturating_sub(1))
-            .unwrap_or(&lowered_line);
+        let original_line = self.original_line(lowered_line);
         let original_file = source_map.file(original_source_id)?;
         let line_range = original_file.line_span(original_line)?;
         let lo = original_file
@@ -0,0 +1,41 @@
+use std::path::{Path, PathBuf};
+
+/// Standard library modules compiled into the crate, so `std` imports resolve
+/// without the repository or a search path on disk.
+const EMBEDDED_MODULES: &[(&str, &str)] = &[
+    (
+        "collections",
+        include_str!("../../stdlib/rss/collections.rss"),
+    ),
+    ("io", include_str!("../../stdlib/rss/io.rss")),
+    ("iter", include_str!("../../stdlib/rss/iter.rss")),
+    ("math", include_str!("../../stdlib/rss/math.rss")),
+    ("path", include_str!("../../stdlib/rss/path.rss")),
+    ("strings", include_str!("../../stdlib/rss/strings.rss")),
+];
+
+/// Directory of the paths embedded modules resolve to. The angle brackets keep
+/// it apart from anything a relative import can name.
+const EMBEDDED_ROOT: &str = "<std>";
+
+pub(super) const STD_SPEC_PREFIX: &str = "std/";
+
+/// Path of the embedded module a `std/<name>.rss` spec names, if any.
+pub(super) fn embedded_module_path(spec: &str) -> Option<PathBuf> {
+    let name = spec.strip_prefix(STD_SPEC_PREFIX)?.strip_suffix(".rss")?;
+    EMBEDDED_MODULES
+        .iter()
+        .any(|(module, _)| *module == name)
+        .then(|| Path::new(EMBEDDED_ROOT).join(format!("{name}.rss")))
+}
+
+pub(super) fn embedded_module_source(path: &Path) -> Option<&'static str> {
+    if path.parent()? != Path::new(EMBEDDED_ROOT) {
+        return None;
+    }
+    let name = path.file_stem()?.to_str()?;
+    EMBEDDED_MODULES
+        .iter()
+        .find(|(module, _)| *module == name)
+        .map(|(_, source)| *source)
+}
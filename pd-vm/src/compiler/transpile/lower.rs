@@ -0,0 +1,1404 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    BinaryOp, Callee, Closure, Expr, FIRST_TEMP, Function, Program, Stmt, TranspileError, VarId,
+};
+use crate::builtins::BuiltinFunction;
+use crate::compiler::SourceFlavor;
+use crate::compiler::ir::{
+    self, ClosureExpr, LinkedIr, MatchArm, MatchArrayIndex, MatchArrayRest, MatchFieldKey,
+    MatchPattern, MatchRangeBound, MatchTypePattern, TYPE_TAG_KEY,
+};
+
+pub(super) fn lower_program(
+    ir: &LinkedIr,
+    flavor: SourceFlavor,
+) -> Result<Program, TranspileError> {
+    let mut lowerer = Lowerer {
+        ir,
+        flavor,
+        next_temp: FIRST_TEMP,
+        functions: Vec::new(),
+        lowered: HashSet::new(),
+        inlining: false,
+        temp_names: HashMap::new(),
+    };
+    let body = lowerer.block(&ir.stmts)?;
+    Ok(Program {
+        functions: lowerer.functions,
+        body,
+        temp_names: lowerer.temp_names,
+    })
+}
+
+struct Lowerer<'ir> {
+    ir: &'ir LinkedIr,
+    flavor: SourceFlavor,
+    next_temp: VarId,
+    functions: Vec<Function>,
+    lowered: HashSet<u16>,
+    /// Set while an immediately called closure's body is spliced into the
+    /// caller, where a `return` would leave the caller instead.
+    inlining: bool,
+    temp_names: HashMap<VarId, &'static str>,
+}
+
+/// One step of testing a `match` arm's pattern, run in order; a failed test
+/// skips the rest.
+enum Step {
+    Test(Expr),
+    Bind(VarId, Expr),
+    Run(Vec<Stmt>),
+}
+
+struct LoweredArm {
+    steps: Vec<Step>,
+    body_pre: Vec<Stmt>,
+    body: Expr,
+}
+
+impl Lowerer<'_> {
+    fn unsupported(&self, construct: impl Into<String>) -> TranspileError {
+        TranspileError::Unsupported {
+            flavor: self.flavor,
+            construct: construct.into(),
+        }
+    }
+
+    fn temp(&mut self) -> VarId {
+        let temp = self.next_temp;
+        self.next_temp += 1;
+        temp
+    }
+
+    /// A temporary with a name that says what it holds.
+    fn named_temp(&mut self, name: &'static str) -> VarId {
+        let temp = self.temp();
+        self.temp_names.insert(temp, name);
+        temp
+    }
+
+    fn block(&mut self, stmts: &[ir::Stmt]) -> Result<Vec<Stmt>, TranspileError> {
+        let mut out = Vec::new();
+        self.stmts(stmts, &mut out)?;
+        Ok(out)
+    }
+
+    fn stmts(&mut self, stmts: &[ir::Stmt], out: &mut Vec<Stmt>) -> Result<(), TranspileError> {
+        for stmt in stmts {
+            self.stmt(stmt, out)?;
+        }
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &ir::Stmt, out: &mut Vec<Stmt>) -> Result<(), TranspileError> {
+        match stmt {
+            ir::Stmt::Noop { .. } | ir::Stmt::FuncDecl { .. } => {}
+            ir::Stmt::Let { index, expr, .. } | ir::Stmt::Assign { index, expr, .. } => {
+                self.assign(*index as VarId, expr, out)?;
+            }
+            ir::Stmt::ClosureLet { .. } => return Err(self.unsupported("a closure binding")),
+            ir::Stmt::Expr { expr, .. } => self.expr_stmt(expr, out)?,
+            ir::Stmt::IfElse {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                match condition {
+                    ir::Expr::Bool(true) => return self.stmts(then_branch, out),
+                    ir::Expr::Bool(false) => return self.stmts(else_branch, out),
+                    _ => {}
+                }
+                let condition = self.expr(condition, out)?;
+                let then_branch = self.block(then_branch)?;
+                let else_branch = self.block(else_branch)?;
+                out.push(Stmt::If(condition, then_branch, else_branch));
+            }
+            ir::Stmt::While {
+                condition, body, ..
+            } => {
+                let mut pre = Vec::new();
+                let condition = self.expr(condition, &mut pre)?;
+                let body = self.block(body)?;
+                out.push(loop_with_condition(pre, condition, body));
+            }
+            ir::Stmt::For {
+                init,
+                condition,
+                post,
+                body,
+                ..
+            } => {
+                let mut init_stmts = Vec::new();
+                self.stmt(init, &mut init_stmts)?;
+                let mut pre = Vec::new();
+                let condition = self.expr(condition, &mut pre)?;
+                let mut post_stmts = Vec::new();
+                self.stmt(post, &mut post_stmts)?;
+                let mut body = self.block(body)?;
+                if let ([init @ Stmt::Assign(..)], [post @ Stmt::Assign(..)]) =
+                    (init_stmts.as_slice(), post_stmts.as_slice())
+                    && pre.is_empty()
+                {
+                    out.push(Stmt::For(
+                        Box::new(init.clone()),
+                        condition,
+                        Box::new(post.clone()),
+                        body,
+                    ));
+                    return Ok(());
+                }
+                // A `continue` would skip the step once it moves into the body.
+                if continues_loop(&body) {
+                    return Err(
+                        self.unsupported("a `continue` in a for loop with a complex header")
+                    );
+                }
+                out.extend(init_stmts);
+                body.extend(post_stmts);
+                out.push(loop_with_condition(pre, condition, body));
+            }
+            ir::Stmt::ForIn {
+                iterable,
+                key_slot,
+                value_slot,
+                body,
+                ..
+            } => {
+                let iterable = self.expr(iterable, out)?;
+                let key = key_slot.map(VarId::from);
+                let mut value = value_slot.map(VarId::from);
+                let body = self.block(body)?;
+                if self.flavor == SourceFlavor::Scheme {
+                    self.desugar_for_in(key, value, iterable, body, out);
+                    return Ok(());
+                }
+                if key.is_none() && value.is_none() {
+                    value = Some(self.temp());
+                }
+                out.push(Stmt::ForIn {
+                    key,
+                    value,
+                    iterable,
+                    body,
+                });
+            }
+            ir::Stmt::Break { .. } => out.push(Stmt::Break),
+            ir::Stmt::Continue { .. } => out.push(Stmt::Continue),
+            ir::Stmt::Return { expr, .. } => {
+                if self.inlining {
+                    return Err(self.unsupported("a `return` in an immediately called closure"));
+                }
+                let value = self.expr(expr, out)?;
+                out.push(Stmt::Return(value));
+            }
+        }
+        Ok(())
+    }
+
+    /// An expression statement leaves its value on the stack, so an `if`
+    /// whose branches need statements can push from each branch instead.
+    fn expr_stmt(&mut self, expr: &ir::Expr, out: &mut Vec<Stmt>) -> Result<(), TranspileError> {
+        if let ir::Expr::IfElse {
+            condition,
+            then_expr,
+            else_expr,
+        } = expr
+            && !matches!(condition.as_ref(), ir::Expr::Bool(_))
+        {
+            let mut pre = Vec::new();
+            let condition_value = self.expr(condition, &mut pre)?;
+            let mut then_branch = Vec::new();
+            let then_value = self.expr(then_expr, &mut then_branch)?;
+            let mut else_branch = Vec::new();
+            let else_value = self.expr(else_expr, &mut else_branch)?;
+            out.extend(pre);
+            if self.flavor == SourceFlavor::Lua
+                || !then_branch.is_empty()
+                || !else_branch.is_empty()
+            {
+                then_branch.push(Stmt::Expr(then_value));
+                else_branch.push(Stmt::Expr(else_value));
+                out.push(Stmt::If(condition_value, then_branch, else_branch));
+            } else {
+                out.push(Stmt::Expr(Expr::IfElse(
+                    Box::new(condition_value),
+                    Box::new(then_value),
+                    Box::new(else_value),
+                )));
+            }
+            return Ok(());
+        }
+        let value = self.expr(expr, out)?;
+        out.push(Stmt::Expr(value));
+        Ok(())
+    }
+
+    fn assign(
+        &mut self,
+        var: VarId,
+        expr: &ir::Expr,
+        out: &mut Vec<Stmt>,
+    ) -> Result<(), TranspileError> {
+        if let ir::Expr::Call(index, args) = expr
+            && BuiltinFunction::from_call_index(*index) == Some(BuiltinFunction::Set)
+            && let [ir::Expr::Var(target), key, value] = args.as_slice()
+            && VarId::from(*target) == var
+        {
+            let mut operands = self.operands(&[key.clone(), value.clone()], out)?;
+            let value = operands.pop().expect("two operands");
+            let key = operands.pop().expect("two operands");
+            out.push(Stmt::SetIndex(var, key, value));
+            return Ok(());
+        }
+        let value = match expr {
+            ir::Expr::IfElse {
+                condition,
+                then_expr,
+                else_expr,
+            } => self.if_else(condition, then_expr, else_expr, Some(var), out)?,
+            ir::Expr::Match {
+                value_slot,
+                result_slot,
+                value,
+                arms,
+                default,
+            } => self.match_expr(
+                *value_slot,
+                *result_slot,
+                value,
+                arms,
+                default,
+                Some(var),
+                out,
+            )?,
+            ir::Expr::Closure(closure) => Expr::Closure(self.closure(closure)?),
+            _ => self.expr(expr, out)?,
+        };
+        // Hoisted `if` and `match` expressions already assigned `var`.
+        if !matches!(value, Expr::Var(written) if written == var) {
+            out.push(Stmt::Assign(var, value));
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &ir::Expr, out: &mut Vec<Stmt>) -> Result<Expr, TranspileError> {
+        Ok(match expr {
+            ir::Expr::Null => Expr::Null,
+            ir::Expr::Int(value) => Expr::Int(*value),
+            ir::Expr::Float(value) => Expr::Float(*value),
+            ir::Expr::Bool(value) => Expr::Bool(*value),
+            ir::Expr::String(value) => Expr::String(value.clone()),
+            ir::Expr::Var(slot) => Expr::Var(VarId::from(*slot)),
+            ir::Expr::FunctionRef(index) => {
+                self.function(*index)?;
+                Expr::Function(*index)
+            }
+            ir::Expr::Call(index, args) => self.call(*index, args, out)?,
+            ir::Expr::LocalCall(slot, args) => {
+                Expr::Call(Callee::Local(VarId::from(*slot)), self.operands(args, out)?)
+            }
+            // Lua only reads a function literal as the whole right-hand side
+            // of an assignment.
+            ir::Expr::Closure(closure) if self.flavor == SourceFlavor::Lua => {
+                let closure = Expr::Closure(self.closure(closure)?);
+                let temp = self.temp();
+                out.push(Stmt::Assign(temp, closure));
+                Expr::Var(temp)
+            }
+            ir::Expr::Closure(closure) => Expr::Closure(self.closure(closure)?),
+            ir::Expr::ClosureCall(closure, args) => self.inline_closure(closure, args, out)?,
+            ir::Expr::Add(lhs, rhs) => self.binary(BinaryOp::Add, lhs, rhs, out)?,
+            ir::Expr::Sub(lhs, rhs) => self.binary(BinaryOp::Sub, lhs, rhs, out)?,
+            ir::Expr::Mul(lhs, rhs) => self.binary(BinaryOp::Mul, lhs, rhs, out)?,
+            ir::Expr::Div(lhs, rhs) => self.binary(BinaryOp::Div, lhs, rhs, out)?,
+            ir::Expr::Mod(lhs, rhs) => self.binary(BinaryOp::Mod, lhs, rhs, out)?,
+            ir::Expr::Eq(lhs, rhs) => {
+                if let Some((map, key)) = key_probe(lhs, rhs) {
+                    let args = self.operands(&[map.clone(), key.clone()], out)?;
+                    return Ok(Expr::Call(
+                        Callee::Builtin(BuiltinFunction::MapHasKey),
+                        args,
+                    ));
+                }
+                self.binary(BinaryOp::Eq, lhs, rhs, out)?
+            }
+            ir::Expr::Lt(lhs, rhs) => self.binary(BinaryOp::Lt, lhs, rhs, out)?,
+            ir::Expr::Gt(lhs, rhs) => self.binary(BinaryOp::Gt, lhs, rhs, out)?,
+            ir::Expr::And(lhs, rhs) => self.logic(BinaryOp::And, lhs, rhs, out)?,
+            ir::Expr::Or(lhs, rhs) => self.logic(BinaryOp::Or, lhs, rhs, out)?,
+            ir::Expr::Neg(inner) => Expr::Neg(Box::new(self.expr(inner, out)?)),
+            ir::Expr::Not(inner) => match inner.as_ref() {
+                // `!=`, `<=`, and `>=` all lower to a negated comparison.
+                ir::Expr::Eq(lhs, rhs) if key_probe(lhs, rhs).is_none() => {
+                    self.binary(BinaryOp::Ne, lhs, rhs, out)?
+                }
+                ir::Expr::Gt(lhs, rhs) => self.binary(BinaryOp::Le, lhs, rhs, out)?,
+                ir::Expr::Lt(lhs, rhs) => self.binary(BinaryOp::Ge, lhs, rhs, out)?,
+                _ => Expr::Not(Box::new(self.expr(inner, out)?)),
+            },
+            ir::Expr::IfElse {
+                condition,
+                then_expr,
+                else_expr,
+            } => self.if_else(condition, then_expr, else_expr, None, out)?,
+            ir::Expr::Match {
+                value_slot,
+                result_slot,
+                value,
+                arms,
+                default,
+            } => self.match_expr(*value_slot, *result_slot, value, arms, default, None, out)?,
+            ir::Expr::Block { stmts, expr } => {
+                for stmt in stmts {
+                    self.stmt(stmt, out)?;
+                }
+                self.expr(expr, out)?
+            }
+        })
+    }
+
+    /// Lowers `args` left to right. When an argument needs statements of its
+    /// own, the arguments before it are first saved to temporaries so they
+    /// still run first.
+    fn operands(
+        &mut self,
+        args: &[ir::Expr],
+        out: &mut Vec<Stmt>,
+    ) -> Result<Vec<Expr>, TranspileError> {
+        let mut values: Vec<Expr> = Vec::with_capacity(args.len());
+        for arg in args {
+            let mut pre = Vec::new();
+            let value = self.expr(arg, &mut pre)?;
+            if !pre.is_empty() {
+                let mut written = HashSet::new();
+                assigned_vars(&pre, &mut written);
+                for earlier in &mut values {
+                    let spill = match &*earlier {
+                        Expr::Var(var) => written.contains(var),
+                        other => !is_constant(other),
+                    };
+                    if spill {
+                        let temp = self.temp();
+                        let saved = std::mem::replace(earlier, Expr::Var(temp));
+                        out.push(Stmt::Assign(temp, saved));
+                    }
+                }
+                out.extend(pre);
+            }
+            values.push(value);
+        }
+        Ok(values)
+    }
+
+    fn binary(
+        &mut self,
+        op: BinaryOp,
+        lhs: &ir::Expr,
+        rhs: &ir::Expr,
+        out: &mut Vec<Stmt>,
+    ) -> Result<Expr, TranspileError> {
+        let mut operands = self.operands(&[lhs.clone(), rhs.clone()], out)?;
+        let rhs = operands.pop().expect("two operands");
+        let lhs = operands.pop().expect("two operands");
+        Ok(binary(op, lhs, rhs))
+    }
+
+    /// `&&` and `||` evaluate both sides, while Scheme's `and` and `or` stop
+    /// early, so a right side with effects runs first there.
+    fn logic(
+        &mut self,
+        op: BinaryOp,
+        lhs: &ir::Expr,
+        rhs: &ir::Expr,
+        out: &mut Vec<Stmt>,
+    ) -> Result<Expr, TranspileError> {
+        let mut operands = self.operands(&[lhs.clone(), rhs.clone()], out)?;
+        if self.flavor == SourceFlavor::Scheme && !is_pure(&operands[1]) {
+            for operand in &mut operands {
+                if !is_constant(operand) && !matches!(operand, Expr::Var(_)) {
+                    let temp = self.temp();
+                    let saved = std::mem::replace(operand, Expr::Var(temp));
+                    out.push(Stmt::Assign(temp, saved));
+                }
+            }
+        }
+        let rhs = operands.pop().expect("two operands");
+        let lhs = operands.pop().expect("two operands");
+        Ok(binary(op, lhs, rhs))
+    }
+
+    /// Keeps an `if` expression when both branches are plain expressions and
+    /// the flavor has one; otherwise assigns the result from an `if`
+    /// statement, to `target` when given.
+    fn if_else(
+        &mut self,
+        condition: &ir::Expr,
+        then_expr: &ir::Expr,
+        else_expr: &ir::Expr,
+        target: Option<VarId>,
+        out: &mut Vec<Stmt>,
+    ) -> Result<Expr, TranspileError> {
+        // Frontends wrap statement sequences in `if true`.
+        match condition {
+            ir::Expr::Bool(true) => return self.expr(then_expr, out),
+            ir::Expr::Bool(false) => return self.expr(else_expr, out),
+            _ => {}
+        }
+        let condition = self.expr(condition, out)?;
+        let mut then_branch = Vec::new();
+        let then_value = self.expr(then_expr, &mut then_branch)?;
+        let mut else_branch = Vec::new();
+        let else_value = self.expr(else_expr, &mut else_branch)?;
+        if then_branch.is_empty() && else_branch.is_empty() && self.flavor != SourceFlavor::Lua {
+            return Ok(Expr::IfElse(
+                Box::new(condition),
+                Box::new(then_value),
+                Box::new(else_value),
+            ));
+        }
+        let result = target.unwrap_or_else(|| self.temp());
+        then_branch.push(Stmt::Assign(result, then_value));
+        else_branch.push(Stmt::Assign(result, else_value));
+        out.push(Stmt::If(condition, then_branch, else_branch));
+        Ok(Expr::Var(result))
+    }
+
+    fn call(
+        &mut self,
+        index: u16,
+        args: &[ir::Expr],
+        out: &mut Vec<Stmt>,
+    ) -> Result<Expr, TranspileError> {
+        if self.ir.function_impls.contains_key(&index) {
+            self.function(index)?;
+            let args = self.operands(args, out)?;
+            return Ok(Expr::Call(Callee::Function(index), args));
+        }
+        if let Some(builtin) = BuiltinFunction::from_call_index(index) {
+            return self.builtin(builtin, args, out);
+        }
+        let args = self.operands(args, out)?;
+        Ok(Expr::Call(Callee::Host(index), args))
+    }
+
+    /// Puts back the surface syntax builtins are lowered from.
+    fn builtin(
+        &mut self,
+        builtin: BuiltinFunction,
+        args: &[ir::Expr],
+        out: &mut Vec<Stmt>,
+    ) -> Result<Expr, TranspileError> {
+        if let Some((root, entries)) = literal_chain(builtin, args) {
+            let mut flat = Vec::new();
+            for (key, value) in &entries {
+                if let Some(key) = key {
+                    flat.push((*key).clone());
+                }
+                flat.push((*value).clone());
+            }
+            let mut values = self.operands(&flat, out)?.into_iter();
+            return Ok(if root == BuiltinFunction::ArrayNew {
+                Expr::Array(values.collect())
+            } else {
+                let mut pairs = Vec::new();
+                while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                    pairs.push((key, value));
+                }
+                Expr::Map(pairs)
+            });
+        }
+        if builtin == BuiltinFunction::Format
+            && let [ir::Expr::String(template), format_args] = args
+            && let Some((root, entries)) = chain_of(format_args)
+        {
+            let mut positional = Vec::new();
+            let mut named = Vec::new();
+            for (position, (key, value)) in entries.into_iter().enumerate() {
+                match (root, key) {
+                    (BuiltinFunction::ArrayNew, None) => positional.push(value.clone()),
+                    (BuiltinFunction::MapNew, Some(ir::Expr::Int(index)))
+                        if named.is_empty() && *index == position as i64 =>
+                    {
+                        positional.push(value.clone())
+                    }
+                    (BuiltinFunction::MapNew, Some(ir::Expr::String(name))) => {
+                        named.push((name.clone(), value.clone()))
+                    }
+                    _ => {
+                        let args = self.operands(args, out)?;
+                        return Ok(Expr::Call(Callee::Builtin(builtin), args));
+                    }
+                }
+            }
+            let mut all = positional.clone();
+            all.extend(named.iter().map(|(_, value)| value.clone()));
+            let mut values = self.operands(&all, out)?;
+            let named_values = values.split_off(positional.len());
+            let named = named
+                .into_iter()
+                .zip(named_values)
+                .map(|((name, _), value)| (name, value))
+                .collect();
+            return Ok(Expr::Format(template.clone(), values, named));
+        }
+
+        let mut args = self.operands(args, out)?;
+        Ok(match (builtin, args.len()) {
+            (BuiltinFunction::Get, 2) => {
+                let key = args.pop().expect("two arguments");
+                let container = args.pop().expect("two arguments");
+                Expr::Index(Box::new(container), Box::new(key))
+            }
+            // `+` on two arrays appends, as a push does.
+            (BuiltinFunction::ArrayPush, 2) => {
+                let element = args.pop().expect("two arguments");
+                let array = args.pop().expect("two arguments");
+                binary(BinaryOp::Add, array, Expr::Array(vec![element]))
+            }
+            (BuiltinFunction::Concat, 2) => {
+                let rhs = args.pop().expect("two arguments");
+                let lhs = args.pop().expect("two arguments");
+                binary(BinaryOp::Add, lhs, rhs)
+            }
+            // Indexed assignment is the only syntax that sets, so a set
+            // whose result lands elsewhere works on a copy.
+            (BuiltinFunction::Set, 3) => {
+                let value = args.pop().expect("three arguments");
+                let key = args.pop().expect("three arguments");
+                let container = args.pop().expect("three arguments");
+                let temp = self.temp();
+                out.push(Stmt::Assign(temp, container));
+                out.push(Stmt::SetIndex(temp, key, value));
+                Expr::Var(temp)
+            }
+            // Slices take a length; `c[s:s + len]` asks for the same one.
+            (BuiltinFunction::Slice, 3) => {
+                let len = args.pop().expect("three arguments");
+                let mut start = args.pop().expect("three arguments");
+                let container = args.pop().expect("three arguments");
+                if !is_constant(&start) && !matches!(start, Expr::Var(_)) {
+                    let temp = self.temp();
+                    out.push(Stmt::Assign(temp, start));
+                    start = Expr::Var(temp);
+                }
+                let end = binary(BinaryOp::Add, start.clone(), len);
+                Expr::Slice(Box::new(container), Box::new(start), Some(Box::new(end)))
+            }
+            _ => Expr::Call(Callee::Builtin(builtin), args),
+        })
+    }
+
+    /// Lowers a function the first time something calls or names it, so
+    /// callees come out first and unreachable functions are left out.
+    fn function(&mut self, index: u16) -> Result<(), TranspileError> {
+        if !self.lowered.insert(index) {
+            return Ok(());
+        }
+        let ir = self.ir;
+        let implementation = &ir.function_impls[&index];
+        let inlining = std::mem::replace(&mut self.inlining, false);
+        let result = (|| -> Result<_, TranspileError> {
+            let mut body = self.block(&implementation.body_stmts)?;
+            let tail = if implementation.body_line == 0
+                && matches!(implementation.body_expr, ir::Expr::Null)
+            {
+                None
+            } else {
+                Some(self.expr(&implementation.body_expr, &mut body)?)
+            };
+            Ok((body, tail))
+        })();
+        self.inlining = inlining;
+        let (body, tail) = result?;
+        let exported = ir
+            .functions
+            .iter()
+            .any(|decl| decl.index == index && decl.exported)
+            && ir.function_sources.get(&index).copied().unwrap_or(0) == 0;
+        self.functions.push(Function {
+            index,
+            params: implementation
+                .param_slots
+                .iter()
+                .copied()
+                .map(VarId::from)
+                .collect(),
+            body,
+            tail,
+            exported,
+        });
+        Ok(())
+    }
+
+    fn closure(&mut self, closure: &ClosureExpr) -> Result<Closure, TranspileError> {
+        let inlining = std::mem::replace(&mut self.inlining, false);
+        let mut body = Vec::new();
+        let tail = self.expr(&closure.body, &mut body);
+        self.inlining = inlining;
+        let tail = tail?;
+        // A body ending in `return` has a null tail nothing reaches.
+        let tail = if matches!(body.last(), Some(Stmt::Return(_))) && matches!(tail, Expr::Null) {
+            None
+        } else {
+            Some(Box::new(tail))
+        };
+        Ok(Closure {
+            params: closure
+                .param_slots
+                .iter()
+                .copied()
+                .map(VarId::from)
+                .collect(),
+            captures: closure
+                .capture_copies
+                .iter()
+                .map(|(outer, inner)| (VarId::from(*outer), VarId::from(*inner)))
+                .collect(),
+            body,
+            tail,
+        })
+    }
+
+    /// A closure called where it is written, like Scheme's `let`, runs in
+    /// place: its parameters become assignments.
+    fn inline_closure(
+        &mut self,
+        closure: &ClosureExpr,
+        args: &[ir::Expr],
+        out: &mut Vec<Stmt>,
+    ) -> Result<Expr, TranspileError> {
+        for (outer, inner) in &closure.capture_copies {
+            out.push(Stmt::Assign(
+                VarId::from(*inner),
+                Expr::Var(VarId::from(*outer)),
+            ));
+        }
+        for (slot, arg) in closure.param_slots.iter().zip(args) {
+            self.assign(VarId::from(*slot), arg, out)?;
+        }
+        let inlining = std::mem::replace(&mut self.inlining, true);
+        let value = self.expr(&closure.body, out);
+        self.inlining = inlining;
+        value
+    }
+
+    fn desugar_for_in(
+        &mut self,
+        key: Option<VarId>,
+        value: Option<VarId>,
+        iterable: Expr,
+        body: Vec<Stmt>,
+        out: &mut Vec<Stmt>,
+    ) {
+        let items = self.named_temp("items");
+        out.push(Stmt::Assign(items, iterable));
+        let keys = key.map(|_| {
+            let keys = self.named_temp("item_keys");
+            out.push(Stmt::Assign(
+                keys,
+                Expr::Call(
+                    Callee::Builtin(BuiltinFunction::Keys),
+                    vec![Expr::Var(items)],
+                ),
+            ));
+            keys
+        });
+        if value.is_some() {
+            out.push(Stmt::If(
+                type_is(Expr::Var(items), "map"),
+                vec![Stmt::Assign(
+                    items,
+                    Expr::Call(
+                        Callee::Builtin(BuiltinFunction::MapValues),
+                        vec![Expr::Var(items)],
+                    ),
+                )],
+                Vec::new(),
+            ));
+        }
+        let len = self.named_temp("count");
+        out.push(Stmt::Assign(
+            len,
+            Expr::Call(
+                Callee::Builtin(BuiltinFunction::Len),
+                vec![Expr::Var(items)],
+            ),
+        ));
+        let index = self.named_temp("index");
+        out.push(Stmt::Assign(index, Expr::Int(0)));
+        let mut loop_body = Vec::new();
+        if let (Some(key), Some(keys)) = (key, keys) {
+            loop_body.push(Stmt::Assign(
+                key,
+                index_of(Expr::Var(keys), Expr::Var(index)),
+            ));
+        }
+        if let Some(value) = value {
+            loop_body.push(Stmt::Assign(
+                value,
+                index_of(Expr::Var(items), Expr::Var(index)),
+            ));
+        }
+        loop_body.push(Stmt::Assign(
+            index,
+            binary(BinaryOp::Add, Expr::Var(index), Expr::Int(1)),
+        ));
+        loop_body.extend(body);
+        out.push(Stmt::While(
+            binary(BinaryOp::Lt, Expr::Var(index), Expr::Var(len)),
+            loop_body,
+        ));
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn match_expr(
+        &mut self,
+        value_slot: u8,
+        result_slot: u8,
+        value: &ir::Expr,
+        arms: &[MatchArm],
+        default: &ir::Expr,
+        target: Option<VarId>,
+        out: &mut Vec<Stmt>,
+    ) -> Result<Expr, TranspileError> {
+        if arms.is_empty() {
+            if let Some(sugar) = self.access_sugar(value_slot, value, default, out)? {
+                return Ok(sugar);
+            }
+            self.assign(VarId::from(value_slot), value, out)?;
+            return self.expr(default, out);
+        }
+
+        let subject = match self.expr(value, out)? {
+            Expr::Var(var) => var,
+            other => {
+                out.push(Stmt::Assign(VarId::from(value_slot), other));
+                VarId::from(value_slot)
+            }
+        };
+        let mut lowered = Vec::new();
+        for arm in arms {
+            let mut steps = Vec::new();
+            self.pattern_steps(&arm.pattern, subject, &mut steps);
+            if let Some(guard) = &arm.guard {
+                let mut pre = Vec::new();
+                let guard = self.expr(guard, &mut pre)?;
+                if !pre.is_empty() {
+                    steps.push(Step::Run(pre));
+                }
+                steps.push(Step::Test(guard));
+            }
+            let mut body_pre = Vec::new();
+            let body = self.expr(&arm.body, &mut body_pre)?;
+            lowered.push(LoweredArm {
+                steps,
+                body_pre,
+                body,
+            });
+        }
+        let mut default_pre = Vec::new();
+        let default = self.expr(default, &mut default_pre)?;
+
+        if let Some(chain) = flat_arms(&lowered, subject) {
+            for arm in &lowered {
+                for step in &arm.steps {
+                    if let Step::Bind(var, value) = step {
+                        out.push(Stmt::Assign(*var, value.clone()));
+                    }
+                }
+            }
+            let needs_stmts =
+                lowered.iter().any(|arm| !arm.body_pre.is_empty()) || !default_pre.is_empty();
+            let mut branches = chain
+                .into_iter()
+                .zip(lowered)
+                .map(|(condition, arm)| (condition, arm.body_pre, arm.body))
+                .collect::<Vec<_>>();
+            // Arms after one that always matches never run.
+            let mut fallback = (default_pre, default);
+            if let Some(position) = branches
+                .iter()
+                .position(|(condition, ..)| condition.is_none())
+            {
+                let (_, pre, body) = branches.swap_remove(position);
+                branches.truncate(position);
+                fallback = (pre, body);
+            }
+            if !needs_stmts && self.flavor != SourceFlavor::Lua {
+                let mut value = fallback.1;
+                for (condition, _, body) in branches.into_iter().rev() {
+                    let condition = condition.expect("only the fallback arm has no test");
+                    value = Expr::IfElse(Box::new(condition), Box::new(body), Box::new(value));
+                }
+                return Ok(value);
+            }
+            let result = target.unwrap_or(VarId::from(result_slot));
+            let mut else_branch = fallback.0;
+            else_branch.push(Stmt::Assign(result, fallback.1));
+            for (condition, mut pre, body) in branches.into_iter().rev() {
+                let condition = condition.expect("only the fallback arm has no test");
+                pre.push(Stmt::Assign(result, body));
+                else_branch = vec![Stmt::If(condition, pre, else_branch)];
+            }
+            out.extend(else_branch);
+            return Ok(Expr::Var(result));
+        }
+
+        let result = target.unwrap_or(VarId::from(result_slot));
+        let matched = self.named_temp("matched");
+        out.push(Stmt::Assign(matched, Expr::Bool(false)));
+        for (position, arm) in lowered.into_iter().enumerate() {
+            let mut inner = arm.body_pre;
+            inner.push(Stmt::Assign(result, arm.body));
+            // Arms after one that always matches never run.
+            if arm.steps.is_empty() {
+                out.push(Stmt::If(not_var(matched), inner, Vec::new()));
+                return Ok(Expr::Var(result));
+            }
+            inner.push(Stmt::Assign(matched, Expr::Bool(true)));
+            let tested = nest(arm.steps, inner);
+            if position == 0 {
+                out.extend(tested);
+            } else {
+                out.push(Stmt::If(not_var(matched), tested, Vec::new()));
+            }
+        }
+        let mut fallback = default_pre;
+        fallback.push(Stmt::Assign(result, default));
+        out.push(Stmt::If(not_var(matched), fallback, Vec::new()));
+        Ok(Expr::Var(result))
+    }
+
+    /// Recognizes the arm-less matches `c[s:e]` and `c?.[k]` lower to.
+    fn access_sugar(
+        &mut self,
+        container_slot: u8,
+        container: &ir::Expr,
+        body: &ir::Expr,
+        out: &mut Vec<Stmt>,
+    ) -> Result<Option<Expr>, TranspileError> {
+        let Some((start_slot, start, inner)) = hidden_binding(body) else {
+            return Ok(None);
+        };
+        if let Some(slice_len) =
+            builtin_args(inner, BuiltinFunction::Slice).and_then(|args| match args {
+                [c, s, len] if is_var(c, container_slot) && is_var(s, start_slot) => Some(len),
+                _ => None,
+            })
+            && let ir::Expr::Sub(end, subtracted) = slice_len
+            && is_var(subtracted, start_slot)
+            && builtin_args(end, BuiltinFunction::Len)
+                .is_some_and(|args| matches!(args, [c] if is_var(c, container_slot)))
+        {
+            let mut values = self.operands(&[container.clone(), start.clone()], out)?;
+            let start = values.pop().expect("two operands");
+            let container = values.pop().expect("two operands");
+            return Ok(Some(Expr::Slice(
+                Box::new(container),
+                Box::new(start),
+                None,
+            )));
+        }
+
+        if let Some((end_slot, end, slice)) = hidden_binding(inner)
+            && let Some([c, s, len]) = builtin_args(slice, BuiltinFunction::Slice)
+            && is_var(c, container_slot)
+            && is_var(s, start_slot)
+            && let ir::Expr::Sub(adjusted, subtracted) = len
+            && is_var(subtracted, start_slot)
+            && let ir::Expr::IfElse {
+                condition,
+                else_expr,
+                ..
+            } = adjusted.as_ref()
+            && let ir::Expr::Lt(negative, zero) = condition.as_ref()
+            && is_var(negative, end_slot)
+            && matches!(zero.as_ref(), ir::Expr::Int(0))
+            && is_var(else_expr, end_slot)
+        {
+            let mut values =
+                self.operands(&[container.clone(), start.clone(), end.clone()], out)?;
+            let end = values.pop().expect("three operands");
+            let start = values.pop().expect("three operands");
+            let container = values.pop().expect("three operands");
+            return Ok(Some(Expr::Slice(
+                Box::new(container),
+                Box::new(start),
+                Some(Box::new(end)),
+            )));
+        }
+
+        // `c?.[k]` first checks for a null container.
+        if let ir::Expr::IfElse { condition, .. } = inner
+            && let ir::Expr::Eq(type_of, expected) = condition.as_ref()
+            && matches!(expected.as_ref(), ir::Expr::String(name) if name == "null")
+            && builtin_args(type_of, BuiltinFunction::TypeOf)
+                .is_some_and(|args| matches!(args, [c] if is_var(c, container_slot)))
+        {
+            if self.flavor == SourceFlavor::Scheme
+                && !(matches!(start, ir::Expr::String(name) if is_symbol_key(name))
+                    && is_optional_chain(container))
+            {
+                return Ok(None);
+            }
+            let mut values = self.operands(&[container.clone(), start.clone()], out)?;
+            let key = values.pop().expect("two operands");
+            let container = values.pop().expect("two operands");
+            return Ok(Some(Expr::OptionalIndex(
+                Box::new(container),
+                Box::new(key),
+            )));
+        }
+        Ok(None)
+    }
+
+    fn pattern_steps(&mut self, pattern: &MatchPattern, subject: VarId, steps: &mut Vec<Step>) {
+        let value = || Expr::Var(subject);
+        match pattern {
+            MatchPattern::Wildcard => {}
+            MatchPattern::Binding(slot) => steps.push(Step::Bind(VarId::from(*slot), value())),
+            MatchPattern::Int(expected) => {
+                steps.push(Step::Test(binary(
+                    BinaryOp::Eq,
+                    value(),
+                    Expr::Int(*expected),
+                )));
+            }
+            MatchPattern::Float(expected) => {
+                steps.push(Step::Test(binary(
+                    BinaryOp::Eq,
+                    value(),
+                    Expr::Float(*expected),
+                )));
+            }
+            MatchPattern::String(expected) => {
+                steps.push(Step::Test(binary(
+                    BinaryOp::Eq,
+                    value(),
+                    Expr::String(expected.clone()),
+                )));
+            }
+            MatchPattern::Null => {
+                steps.push(Step::Test(binary(BinaryOp::Eq, value(), Expr::Null)));
+            }
+            MatchPattern::Range(range) => {
+                steps.push(Step::Test(type_test(subject, &MatchTypePattern::Number)));
+                if let Some(start) = range.start {
+                    steps.push(Step::Test(binary(BinaryOp::Ge, value(), bound(start))));
+                }
+                if let Some(end) = range.end {
+                    let op = if range.inclusive_end {
+                        BinaryOp::Le
+                    } else {
+                        BinaryOp::Lt
+                    };
+                    steps.push(Step::Test(binary(op, value(), bound(end))));
+                }
+            }
+            MatchPattern::Type(type_pattern) => {
+                steps.push(Step::Test(type_test(subject, type_pattern)));
+            }
+            MatchPattern::Array(array) => {
+                steps.push(Step::Test(type_is(value(), "array")));
+                let len = || {
+                    Expr::Call(
+                        Callee::Builtin(BuiltinFunction::Len),
+                        vec![Expr::Var(subject)],
+                    )
+                };
+                let op = if array.rest.is_some() {
+                    BinaryOp::Ge
+                } else {
+                    BinaryOp::Eq
+                };
+                steps.push(Step::Test(binary(
+                    op,
+                    len(),
+                    Expr::Int(array.fixed_len as i64),
+                )));
+                for (index, sub) in &array.elements {
+                    let key = match index {
+                        MatchArrayIndex::FromStart(position) => Expr::Int(*position as i64),
+                        MatchArrayIndex::FromEnd(offset) => {
+                            binary(BinaryOp::Sub, len(), Expr::Int(*offset as i64))
+                        }
+                    };
+                    let slot = VarId::from(sub.slot);
+                    steps.push(Step::Bind(slot, index_of(value(), key)));
+                    self.pattern_steps(&sub.pattern, slot, steps);
+                }
+                if let Some(MatchArrayRest {
+                    start,
+                    binding: Some(slot),
+                }) = &array.rest
+                {
+                    // The rest stops short of the elements matched from the end.
+                    let from_end = array.fixed_len - start;
+                    let end = (from_end > 0).then(|| Box::new(Expr::Int(-(from_end as i64))));
+                    steps.push(Step::Bind(
+                        VarId::from(*slot),
+                        Expr::Slice(Box::new(value()), Box::new(Expr::Int(*start as i64)), end),
+                    ));
+                }
+            }
+            MatchPattern::Map(entries) => {
+                steps.push(Step::Test(type_is(value(), "map")));
+                for (key, sub) in entries {
+                    let key = field_key(key);
+                    steps.push(Step::Test(Expr::Call(
+                        Callee::Builtin(BuiltinFunction::MapHasKey),
+                        vec![value(), key.clone()],
+                    )));
+                    let slot = VarId::from(sub.slot);
+                    steps.push(Step::Bind(slot, index_of(value(), key)));
+                    self.pattern_steps(&sub.pattern, slot, steps);
+                }
+            }
+            MatchPattern::Variant(variant) => {
+                let tag = || Expr::String(TYPE_TAG_KEY.to_string());
+                steps.push(Step::Test(type_is(value(), "map")));
+                steps.push(Step::Test(Expr::Call(
+                    Callee::Builtin(BuiltinFunction::MapHasKey),
+                    vec![value(), tag()],
+                )));
+                steps.push(Step::Test(binary(
+                    BinaryOp::Eq,
+                    index_of(value(), tag()),
+                    Expr::String(variant.tag.clone()),
+                )));
+                for (key, sub) in &variant.fields {
+                    let slot = VarId::from(sub.slot);
+                    steps.push(Step::Bind(slot, index_of(value(), field_key(key))));
+                    self.pattern_steps(&sub.pattern, slot, steps);
+                }
+            }
+            MatchPattern::Or(alternatives) => {
+                let mut lowered = Vec::new();
+                for alternative in alternatives {
+                    let mut alternative_steps = Vec::new();
+                    self.pattern_steps(alternative, subject, &mut alternative_steps);
+                    lowered.push(alternative_steps);
+                }
+                let single_tests = lowered
+                    .iter()
+                    .all(|steps| matches!(steps.as_slice(), [Step::Test(_)]));
+                if single_tests {
+                    let test = lowered
+                        .into_iter()
+                        .filter_map(|mut steps| match steps.pop() {
+                            Some(Step::Test(test)) => Some(test),
+                            _ => None,
+                        })
+                        .reduce(|lhs, rhs| binary(BinaryOp::Or, lhs, rhs))
+                        .expect("or-pattern has at least one alternative");
+                    steps.push(Step::Test(test));
+                    return;
+                }
+                let matched = self.named_temp("matched");
+                let mut run = vec![Stmt::Assign(matched, Expr::Bool(false))];
+                for (position, alternative) in lowered.into_iter().enumerate() {
+                    let tested = nest(alternative, vec![Stmt::Assign(matched, Expr::Bool(true))]);
+                    if position == 0 {
+                        run.extend(tested);
+                    } else {
+                        run.push(Stmt::If(not_var(matched), tested, Vec::new()));
+                    }
+                }
+                steps.push(Step::Run(run));
+                steps.push(Step::Test(Expr::Var(matched)));
+            }
+        }
+    }
+}
+
+/// The condition for each arm when every arm only copies the subject and
+/// makes at most one test, so the match reads as an `if` chain; `None`
+/// marks an arm that always matches.
+fn flat_arms(arms: &[LoweredArm], subject: VarId) -> Option<Vec<Option<Expr>>> {
+    let mut chain = Vec::new();
+    for arm in arms {
+        let mut condition = None;
+        for step in &arm.steps {
+            match step {
+                Step::Bind(_, Expr::Var(var)) if *var == subject => {}
+                // Both sides of `&&` run, so only a test that cannot fail
+                // may join one.
+                Step::Test(test) => {
+                    condition = match condition {
+                        None => Some(test.clone()),
+                        Some(first) if is_pure(test) && is_pure(&first) => {
+                            Some(binary(BinaryOp::And, first, test.clone()))
+                        }
+                        Some(_) => return None,
+                    }
+                }
+                _ => return None,
+            }
+        }
+        chain.push(condition);
+    }
+    Some(chain)
+}
+
+fn nest(steps: Vec<Step>, inner: Vec<Stmt>) -> Vec<Stmt> {
+    let mut body = inner;
+    for step in steps.into_iter().rev() {
+        match step {
+            Step::Test(test) => body = vec![Stmt::If(test, body, Vec::new())],
+            Step::Bind(var, value) => body.insert(0, Stmt::Assign(var, value)),
+            Step::Run(mut stmts) => {
+                stmts.extend(body);
+                body = stmts;
+            }
+        }
+    }
+    body
+}
+
+/// `while (condition) body`, checking the condition at the top of the body
+/// when it needs statements of its own.
+fn loop_with_condition(pre: Vec<Stmt>, condition: Expr, body: Vec<Stmt>) -> Stmt {
+    if pre.is_empty() {
+        return Stmt::While(condition, body);
+    }
+    let mut stmts = pre;
+    stmts.push(Stmt::If(
+        Expr::Not(Box::new(condition)),
+        vec![Stmt::Break],
+        Vec::new(),
+    ));
+    stmts.extend(body);
+    Stmt::While(Expr::Bool(true), stmts)
+}
+
+/// Whether a `continue` in `stmts` belongs to the loop around them.
+fn continues_loop(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Stmt::Continue => true,
+        Stmt::If(_, then_branch, else_branch) => {
+            continues_loop(then_branch) || continues_loop(else_branch)
+        }
+        _ => false,
+    })
+}
+
+fn assigned_vars(stmts: &[Stmt], written: &mut HashSet<VarId>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Assign(var, _) | Stmt::SetIndex(var, ..) => {
+                written.insert(*var);
+            }
+            Stmt::If(_, then_branch, else_branch) => {
+                assigned_vars(then_branch, written);
+                assigned_vars(else_branch, written);
+            }
+            Stmt::While(_, body) => assigned_vars(body, written),
+            Stmt::For(init, _, post, body) => {
+                assigned_vars(std::slice::from_ref(init.as_ref()), written);
+                assigned_vars(std::slice::from_ref(post.as_ref()), written);
+                assigned_vars(body, written);
+            }
+            Stmt::ForIn {
+                key, value, body, ..
+            } => {
+                written.extend(key.iter().chain(value.iter()).copied());
+                assigned_vars(body, written);
+            }
+            Stmt::Expr(_) | Stmt::Break | Stmt::Continue | Stmt::Return(_) => {}
+        }
+    }
+}
+
+fn is_constant(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::Null
+            | Expr::Bool(_)
+            | Expr::Int(_)
+            | Expr::Float(_)
+            | Expr::String(_)
+            | Expr::Function(_)
+    )
+}
+
+/// Whether evaluating `expr` can neither fail nor have effects.
+fn is_pure(expr: &Expr) -> bool {
+    match expr {
+        Expr::Var(_) => true,
+        Expr::Not(inner) => is_pure(inner),
+        Expr::Binary(BinaryOp::Eq | BinaryOp::Ne | BinaryOp::And | BinaryOp::Or, lhs, rhs) => {
+            is_pure(lhs) && is_pure(rhs)
+        }
+        Expr::Call(Callee::Builtin(BuiltinFunction::TypeOf), args) => args.iter().all(is_pure),
+        other => is_constant(other),
+    }
+}
+
+fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+    Expr::Binary(op, Box::new(lhs), Box::new(rhs))
+}
+
+fn index_of(container: Expr, key: Expr) -> Expr {
+    Expr::Index(Box::new(container), Box::new(key))
+}
+
+fn not_var(var: VarId) -> Expr {
+    Expr::Not(Box::new(Expr::Var(var)))
+}
+
+fn type_is(value: Expr, name: &str) -> Expr {
+    binary(
+        BinaryOp::Eq,
+        Expr::Call(Callee::Builtin(BuiltinFunction::TypeOf), vec![value]),
+        Expr::String(name.to_string()),
+    )
+}
+
+fn type_test(subject: VarId, type_pattern: &MatchTypePattern) -> Expr {
+    let name = match type_pattern {
+        MatchTypePattern::Int => "int",
+        MatchTypePattern::Float => "float",
+        MatchTypePattern::Bool => "bool",
+        MatchTypePattern::String => "string",
+        MatchTypePattern::Array => "array",
+        MatchTypePattern::Map => "map",
+        MatchTypePattern::Number => {
+            return binary(
+                BinaryOp::Or,
+                type_is(Expr::Var(subject), "int"),
+                type_is(Expr::Var(subject), "float"),
+            );
+        }
+    };
+    type_is(Expr::Var(subject), name)
+}
+
+fn bound(bound: MatchRangeBound) -> Expr {
+    match bound {
+        MatchRangeBound::Int(value) => Expr::Int(value),
+        MatchRangeBound::Float(value) => Expr::Float(value),
+    }
+}
+
+fn field_key(key: &MatchFieldKey) -> Expr {
+    match key {
+        MatchFieldKey::Position(position) => Expr::Int(*position as i64),
+        MatchFieldKey::Name(name) => Expr::String(name.clone()),
+    }
+}
+
+fn is_var(expr: &ir::Expr, slot: u8) -> bool {
+    matches!(expr, ir::Expr::Var(var) if *var == slot)
+}
+
+fn builtin_args(expr: &ir::Expr, builtin: BuiltinFunction) -> Option<&[ir::Expr]> {
+    match expr {
+        ir::Expr::Call(index, args)
+            if BuiltinFunction::from_call_index(*index) == Some(builtin) =>
+        {
+            Some(args)
+        }
+        _ => None,
+    }
+}
+
+/// The slot, value, and body of an arm-less match, which binds a hidden
+/// local for the body.
+fn hidden_binding(expr: &ir::Expr) -> Option<(u8, &ir::Expr, &ir::Expr)> {
+    match expr {
+        ir::Expr::Match {
+            value_slot,
+            value,
+            arms,
+            default,
+            ..
+        } if arms.is_empty() => Some((*value_slot, value, default)),
+        _ => None,
+    }
+}
+
+/// Matches `len(set(map, key, null)) == len(map)`, the key test optional
+/// member access lowers to, returning the map and key.
+fn key_probe<'e>(lhs: &'e ir::Expr, rhs: &'e ir::Expr) -> Option<(&'e ir::Expr, &'e ir::Expr)> {
+    let [probed] = builtin_args(lhs, BuiltinFunction::Len)? else {
+        return None;
+    };
+    let [original] = builtin_args(rhs, BuiltinFunction::Len)? else {
+        return None;
+    };
+    match builtin_args(probed, BuiltinFunction::Set)? {
+        [ir::Expr::Var(map), key, ir::Expr::Null] if is_var(original, *map) => {
+            Some((original, key))
+        }
+        _ => None,
+    }
+}
+
+/// Scheme writes optional access as an `a?.b?.c` symbol, so only named
+/// fields along a chain rooted at a local have a spelling.
+fn is_optional_chain(container: &ir::Expr) -> bool {
+    if matches!(container, ir::Expr::Var(_)) {
+        return true;
+    }
+    let Some((_, value, body)) = hidden_binding(container) else {
+        return false;
+    };
+    matches!(hidden_binding(body), Some((_, ir::Expr::String(name), _)) if is_symbol_key(name))
+        && is_optional_chain(value)
+}
+
+fn is_symbol_key(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+/// The root builtin and `(key, value)` entries of a literal chain.
+type LiteralChain<'a> = (BuiltinFunction, Vec<(Option<&'a ir::Expr>, &'a ir::Expr)>);
+
+/// The root and `(key, value)` entries of an array or map literal, which
+/// lower to one push or set per entry onto a new array or map.
+fn chain_of(expr: &ir::Expr) -> Option<LiteralChain<'_>> {
+    let ir::Expr::Call(index, args) = expr else {
+        return None;
+    };
+    literal_chain(BuiltinFunction::from_call_index(*index)?, args)
+}
+
+fn literal_chain(builtin: BuiltinFunction, args: &[ir::Expr]) -> Option<LiteralChain<'_>> {
+    let mut entries = Vec::new();
+    let mut builtin = builtin;
+    let mut args = args;
+    let root = loop {
+        match (builtin, args) {
+            (BuiltinFunction::ArrayNew | BuiltinFunction::MapNew, []) => break builtin,
+            (BuiltinFunction::ArrayPush, [inner, element]) => {
+                entries.push((None, element));
+                let ir::Expr::Call(next, next_args) = inner else {
+                    return None;
+                };
+                builtin = BuiltinFunction::from_call_index(*next)?;
+                args = next_args;
+            }
+            (BuiltinFunction::Set, [inner, key, value]) => {
+                entries.push((Some(key), value));
+                let ir::Expr::Call(next, next_args) = inner else {
+                    return None;
+                };
+                builtin = BuiltinFunction::from_call_index(*next)?;
+                args = next_args;
+            }
+            _ => return None,
+        }
+    };
+    entries.reverse();
+    let is_array = root == BuiltinFunction::ArrayNew;
+    if entries.iter().any(|(key, _)| key.is_some() == is_array) {
+        return None;
+    }
+    Some((root, entries))
+}
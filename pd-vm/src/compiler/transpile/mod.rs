@@ -0,0 +1,320 @@
+//! Turns a linked program back into source for any of the four flavors.
+//!
+//! Transpiling runs in two passes:
+//!
+//! - [`lower`] rewrites the IR into a small flavor-neutral syntax tree. It
+//!   puts back the surface syntax frontends desugar (array and map literals,
+//!   slices, `?.`, `format!`, indexed assignment), turns `match` into `if`
+//!   chains, and hoists whatever the target flavor cannot nest into
+//!   temporaries, such as `if` expressions in Lua;
+//! - [`render`] names the locals and prints the tree in the target flavor,
+//!   handing Scheme's prefix forms to [`scheme`].
+//!
+//! Functions are printed once at the top of the output instead of being
+//! inlined, imported modules included, so the output stands on its own. It
+//! compiles to bytecode that behaves the same as the input, not to the same
+//! bytes: desugared constructs come back in their plain form, and hidden
+//! locals show up as ordinary ones.
+
+mod lower;
+mod render;
+mod scheme;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::ir::{self, LinkedIr};
+use super::{
+    SourceFlavor, SourcePathError, link_source, link_source_file, run_with_compiler_stack,
+};
+use crate::builtins::BuiltinFunction;
+use crate::bytecode::Value;
+
+#[derive(Debug)]
+pub enum TranspileError {
+    /// The input does not parse or link.
+    Source(SourcePathError),
+    /// Assembly has no IR to transpile from or to.
+    Assembly,
+    /// A construct the target flavor has no syntax for.
+    Unsupported {
+        flavor: SourceFlavor,
+        construct: String,
+    },
+}
+
+impl std::fmt::Display for TranspileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TranspileError::Source(err) => write!(f, "{err}"),
+            TranspileError::Assembly => {
+                write!(f, "assembly (.pda) cannot be transpiled; use --decompile")
+            }
+            TranspileError::Unsupported { flavor, construct } => {
+                write!(f, "cannot express {construct} in {}", flavor_name(*flavor))
+            }
+        }
+    }
+}
+
+impl std::error::Error for TranspileError {}
+
+impl From<SourcePathError> for TranspileError {
+    fn from(value: SourcePathError) -> Self {
+        TranspileError::Source(value)
+    }
+}
+
+/// Prints `ir` as `flavor` source.
+pub fn transpile_ir(ir: &LinkedIr, flavor: SourceFlavor) -> Result<String, TranspileError> {
+    if flavor == SourceFlavor::Assembly {
+        return Err(TranspileError::Assembly);
+    }
+    let program = lower::lower_program(ir, flavor)?;
+    render::render_program(ir, &program, flavor)
+}
+
+/// Transpiles `source` from one flavor to another. Like
+/// [`compile_source_with_flavor`](super::compile_source_with_flavor), its
+/// imports can only name the embedded `std` modules.
+pub fn transpile_source(
+    source: &str,
+    from: SourceFlavor,
+    to: SourceFlavor,
+) -> Result<String, TranspileError> {
+    if from == SourceFlavor::Assembly || to == SourceFlavor::Assembly {
+        return Err(TranspileError::Assembly);
+    }
+    let source = source.to_string();
+    run_with_compiler_stack(move || {
+        let ir = link_source(&source, from)
+            .map_err(|err| TranspileError::Source(SourcePathError::Source(err)))?;
+        transpile_ir(&ir, to)
+    })
+}
+
+pub fn transpile_source_file(
+    path: impl AsRef<Path>,
+    to: SourceFlavor,
+) -> Result<String, TranspileError> {
+    transpile_source_file_with_search_paths(path, Vec::new(), to)
+}
+
+pub fn transpile_source_file_with_search_paths(
+    path: impl AsRef<Path>,
+    search_paths: Vec<PathBuf>,
+    to: SourceFlavor,
+) -> Result<String, TranspileError> {
+    let path = path.as_ref().to_path_buf();
+    run_with_compiler_stack(move || {
+        let from = SourceFlavor::from_path(&path)?;
+        if from == SourceFlavor::Assembly || to == SourceFlavor::Assembly {
+            return Err(TranspileError::Assembly);
+        }
+        let source = std::fs::read_to_string(&path).map_err(SourcePathError::Io)?;
+        let ir = link_source_file(&path, from, &source, search_paths)?;
+        transpile_ir(&ir, to)
+    })
+}
+
+/// Prints one top-level local per binding, holding a copy of its value, as
+/// `flavor` source. A REPL or debugger prepends it to later input so that
+/// input sees the same names. Bindings the flavor cannot name, and values
+/// with no literal form such as `NaN` or a map with a `null` key, are left
+/// out.
+pub fn transpile_bindings(
+    bindings: &[(String, Value)],
+    flavor: SourceFlavor,
+) -> Result<String, TranspileError> {
+    if flavor == SourceFlavor::Assembly {
+        return Err(TranspileError::Assembly);
+    }
+    let mut stmts = Vec::new();
+    let mut local_bindings = Vec::new();
+    for (name, value) in bindings {
+        if !render::is_ident(name) || name.starts_with("__") || render::is_reserved(flavor, name) {
+            continue;
+        }
+        let (Some(expr), Ok(index)) = (value_literal(value), u8::try_from(local_bindings.len()))
+        else {
+            continue;
+        };
+        stmts.push(ir::Stmt::Let {
+            index,
+            expr,
+            line: 1,
+        });
+        local_bindings.push((name.clone(), index));
+    }
+    let ir = LinkedIr {
+        sources: Vec::new(),
+        stmt_sources: vec![0; stmts.len()],
+        stmts,
+        function_sources: HashMap::new(),
+        locals: local_bindings.len(),
+        local_bindings,
+        scoped_bindings: Vec::new(),
+        functions: Vec::new(),
+        function_impls: HashMap::new(),
+        warnings: Vec::new(),
+    };
+    run_with_compiler_stack(move || transpile_ir(&ir, flavor))
+}
+
+/// `value` as the literal a frontend would lower it from.
+fn value_literal(value: &Value) -> Option<ir::Expr> {
+    let call = |builtin: BuiltinFunction, args| ir::Expr::Call(builtin.call_index(), args);
+    Some(match value {
+        Value::Null => ir::Expr::Null,
+        Value::Int(value) => ir::Expr::Int(*value),
+        Value::Float(value) if value.is_finite() => ir::Expr::Float(*value),
+        Value::Float(_) => return None,
+        Value::Bool(value) => ir::Expr::Bool(*value),
+        Value::String(value) => ir::Expr::String(value.clone()),
+        Value::Array(items) => {
+            let mut array = call(BuiltinFunction::ArrayNew, Vec::new());
+            for item in items {
+                array = call(
+                    BuiltinFunction::ArrayPush,
+                    vec![array, value_literal(item)?],
+                );
+            }
+            array
+        }
+        Value::Map(entries) => {
+            let mut map = call(BuiltinFunction::MapNew, Vec::new());
+            for (key, item) in entries {
+                if matches!(key, Value::Null) {
+                    return None;
+                }
+                map = call(
+                    BuiltinFunction::Set,
+                    vec![map, value_literal(key)?, value_literal(item)?],
+                );
+            }
+            map
+        }
+    })
+}
+
+fn flavor_name(flavor: SourceFlavor) -> &'static str {
+    match flavor {
+        SourceFlavor::RustScript => "RustScript",
+        SourceFlavor::JavaScript => "JavaScript",
+        SourceFlavor::Lua => "Lua",
+        SourceFlavor::Scheme => "Scheme",
+        SourceFlavor::Assembly => "assembly",
+    }
+}
+
+/// Locals are IR slots; temporaries the transpiler introduces are numbered
+/// from `FIRST_TEMP` so they never collide with one.
+type VarId = u32;
+
+const FIRST_TEMP: VarId = 0x100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Callee {
+    /// A function printed at the top of the output.
+    Function(u16),
+    /// A host function; `print` gets each flavor's own printing syntax.
+    Host(u16),
+    /// A closure bound to a local.
+    Local(VarId),
+    Builtin(BuiltinFunction),
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Var(VarId),
+    /// A function named as a value, e.g. `let g = f;`.
+    Function(u16),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(Callee, Vec<Expr>),
+    Array(Vec<Expr>),
+    Map(Vec<(Expr, Expr)>),
+    Index(Box<Expr>, Box<Expr>),
+    /// `container?.[key]`: null when the container is null or has no such key.
+    OptionalIndex(Box<Expr>, Box<Expr>),
+    /// `container[start:end]`, up to the end when `end` is absent.
+    Slice(Box<Expr>, Box<Expr>, Option<Box<Expr>>),
+    /// A `format!` call: template, positional arguments, named arguments.
+    Format(String, Vec<Expr>, Vec<(String, Expr)>),
+    IfElse(Box<Expr>, Box<Expr>, Box<Expr>),
+    Closure(Closure),
+}
+
+#[derive(Clone, Debug)]
+struct Closure {
+    params: Vec<VarId>,
+    /// Locals copied in from the enclosing scope, as `(outer, inner)`; the
+    /// inner local goes by the outer one's name.
+    captures: Vec<(VarId, VarId)>,
+    body: Vec<Stmt>,
+    /// `None` when the body ends in a `return`.
+    tail: Option<Box<Expr>>,
+}
+
+#[derive(Clone, Debug)]
+enum Stmt {
+    /// Declares the local when this is the first assignment that every other
+    /// use of it follows; the renderer decides.
+    Assign(VarId, Expr),
+    /// `target[key] = value`.
+    SetIndex(VarId, Expr, Expr),
+    Expr(Expr),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    /// `for (init; condition; post)`; flavors without one get a `while`.
+    For(Box<Stmt>, Expr, Box<Stmt>, Vec<Stmt>),
+    ForIn {
+        key: Option<VarId>,
+        value: Option<VarId>,
+        iterable: Expr,
+        body: Vec<Stmt>,
+    },
+    Break,
+    Continue,
+    Return(Expr),
+}
+
+struct Function {
+    index: u16,
+    params: Vec<VarId>,
+    body: Vec<Stmt>,
+    /// Value the body ends with; `None` when it falls off its end.
+    tail: Option<Expr>,
+    exported: bool,
+}
+
+struct Program {
+    /// Callees come before their callers.
+    functions: Vec<Function>,
+    body: Vec<Stmt>,
+    /// Names for temporaries whose role has one, such as a `match` flag.
+    temp_names: HashMap<VarId, &'static str>,
+}
@@ -0,0 +1,1656 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+
+use super::{
+    BinaryOp, Callee, Closure, Expr, FIRST_TEMP, Function, Program, Stmt, TranspileError, VarId,
+    scheme,
+};
+use crate::builtins::BuiltinFunction;
+use crate::compiler::ir::LinkedIr;
+use crate::compiler::{STDLIB_PRINT_NAME, SourceFlavor};
+use crate::format::{FormatAlign, FormatArg, FormatPiece, FormatSpec, parse_format_template};
+
+/// Words every flavor reserves, since they all end up as RustScript.
+const KEYWORDS: &[&str] = &[
+    "pub", "use", "as", "fn", "let", "for", "in", "if", "else", "match", "while", "break",
+    "continue", "return", "true", "false", "null", "struct", "enum", "impl", "type", "typeof",
+    "assert", "format", "print", "vm", "std", "io", "re", "rand", "uuid", "array", "map", "lua",
+];
+
+const JAVASCRIPT_KEYWORDS: &[&str] = &[
+    "function",
+    "var",
+    "const",
+    "class",
+    "new",
+    "this",
+    "of",
+    "export",
+    "import",
+    "from",
+    "undefined",
+    "console",
+    "Object",
+    "delete",
+    "do",
+    "switch",
+    "case",
+    "default",
+    "try",
+    "catch",
+    "finally",
+    "throw",
+    "void",
+    "instanceof",
+    "with",
+    "yield",
+    "await",
+    "async",
+    "super",
+    "extends",
+    "static",
+];
+
+const LUA_KEYWORDS: &[&str] = &[
+    "and",
+    "or",
+    "not",
+    "end",
+    "then",
+    "do",
+    "local",
+    "function",
+    "nil",
+    "repeat",
+    "until",
+    "elseif",
+    "goto",
+    "require",
+    "pairs",
+    "ipairs",
+    "string",
+    "table",
+    "math",
+    "select",
+    "tostring",
+    "tonumber",
+    "setmetatable",
+    "getmetatable",
+];
+
+const SCHEME_KEYWORDS: &[&str] = &[
+    "define",
+    "lambda",
+    "begin",
+    "set",
+    "cond",
+    "case",
+    "when",
+    "unless",
+    "do",
+    "and",
+    "or",
+    "not",
+    "vector",
+    "hash",
+    "display",
+    "length",
+    "keys",
+    "list",
+    "car",
+    "cdr",
+    "cons",
+    "quote",
+    "filter",
+    "apply",
+    "remainder",
+    "modulo",
+    "quotient",
+    "require",
+    "only",
+    "append",
+    "reverse",
+    "sort",
+    "abs",
+    "min",
+    "max",
+    "letrec",
+];
+
+pub(super) const OR: u8 = 1;
+pub(super) const AND: u8 = 2;
+pub(super) const COMPARE: u8 = 3;
+pub(super) const TERM: u8 = 4;
+pub(super) const FACTOR: u8 = 5;
+pub(super) const UNARY: u8 = 6;
+pub(super) const POSTFIX: u8 = 7;
+pub(super) const ATOM: u8 = 8;
+
+pub(super) fn render_program(
+    ir: &LinkedIr,
+    program: &Program,
+    flavor: SourceFlavor,
+) -> Result<String, TranspileError> {
+    let globals = Globals::collect(ir, program, flavor)?;
+    let mut out = String::new();
+    if !globals.imports.is_empty() {
+        match flavor {
+            SourceFlavor::RustScript => {
+                let _ = writeln!(out, "use vm::{{{}}};", globals.imports.join(", "));
+            }
+            SourceFlavor::JavaScript => {
+                let _ = writeln!(
+                    out,
+                    "import {{ {} }} from \"vm\";",
+                    globals.imports.join(", ")
+                );
+            }
+            SourceFlavor::Lua => {
+                for name in &globals.imports {
+                    let _ = writeln!(out, "local {name} = require(\"vm\").{name}");
+                }
+            }
+            SourceFlavor::Scheme => {
+                let _ = writeln!(
+                    out,
+                    "(require (only-in \"vm\" {}))",
+                    globals.imports.join(" ")
+                );
+            }
+            SourceFlavor::Assembly => return Err(TranspileError::Assembly),
+        }
+        out.push('\n');
+    }
+    let unit = Unit::analyze(&[], &program.body, None, &globals, ir, flavor);
+    if flavor == SourceFlavor::Scheme {
+        let units = program
+            .functions
+            .iter()
+            .map(|function| {
+                let unit = Unit::analyze(
+                    &function.params,
+                    &function.body,
+                    function.tail.as_ref(),
+                    &globals,
+                    ir,
+                    flavor,
+                );
+                (function.index, unit)
+            })
+            .collect();
+        out.push_str(&scheme::render_program(program, &globals, &units, &unit)?);
+        return Ok(out);
+    }
+    for function in &program.functions {
+        let function_unit = Unit::analyze(
+            &function.params,
+            &function.body,
+            function.tail.as_ref(),
+            &globals,
+            ir,
+            flavor,
+        );
+        let mut renderer = Renderer::new(flavor, &globals, &function_unit);
+        renderer.function(function)?;
+        out.push_str(&renderer.out);
+        out.push('\n');
+    }
+    let mut renderer = Renderer::new(flavor, &globals, &unit);
+    renderer.hoisted(0);
+    renderer.block(&program.body, 0)?;
+    out.push_str(&renderer.out);
+    Ok(out)
+}
+
+pub(super) fn unsupported(flavor: SourceFlavor, construct: impl Into<String>) -> TranspileError {
+    TranspileError::Unsupported {
+        flavor,
+        construct: construct.into(),
+    }
+}
+
+/// Names shared by every function: the printed functions and the host
+/// functions the program imports.
+pub(super) struct Globals {
+    pub(super) functions: HashMap<u16, String>,
+    /// Host function names by call index; `print` maps to each flavor's
+    /// printing syntax and is not imported.
+    pub(super) hosts: HashMap<u16, String>,
+    pub(super) imports: Vec<String>,
+    taken: HashSet<String>,
+    temp_names: HashMap<VarId, &'static str>,
+}
+
+impl Globals {
+    fn collect(
+        ir: &LinkedIr,
+        program: &Program,
+        flavor: SourceFlavor,
+    ) -> Result<Self, TranspileError> {
+        let mut used_hosts = BTreeSet::new();
+        let mut visit = |expr: &Expr| {
+            if let Expr::Call(Callee::Host(index), _) = expr {
+                used_hosts.insert(*index);
+            }
+        };
+        for function in &program.functions {
+            walk_stmts(&function.body, &mut visit);
+            if let Some(tail) = &function.tail {
+                walk_expr(tail, &mut visit);
+            }
+        }
+        walk_stmts(&program.body, &mut visit);
+
+        let decl_name = |index: u16| {
+            ir.functions
+                .iter()
+                .find(|decl| decl.index == index)
+                .map(|decl| decl.name.as_str())
+                .unwrap_or("")
+        };
+        let mut taken = HashSet::new();
+        let mut hosts = HashMap::new();
+        let mut imports = BTreeSet::new();
+        for index in used_hosts {
+            let name = decl_name(index).to_string();
+            if name != STDLIB_PRINT_NAME {
+                if !is_ident(&name) || is_reserved(flavor, &name) {
+                    return Err(unsupported(
+                        flavor,
+                        format!("a call to host function '{name}'"),
+                    ));
+                }
+                imports.insert(name.clone());
+            }
+            taken.insert(name.clone());
+            hosts.insert(index, name);
+        }
+        let mut functions = HashMap::new();
+        for function in &program.functions {
+            let raw = decl_name(function.index);
+            let raw = raw
+                .rsplit("::")
+                .next()
+                .unwrap_or(raw)
+                .trim_start_matches('_');
+            let base = if is_ident(raw) && !is_reserved(flavor, raw) {
+                raw.to_string()
+            } else if is_ident(raw) {
+                format!("{raw}_fn")
+            } else {
+                "func".to_string()
+            };
+            let name = dedupe(&base, &taken);
+            taken.insert(name.clone());
+            functions.insert(function.index, name);
+        }
+        Ok(Self {
+            functions,
+            hosts,
+            imports: imports.into_iter().collect(),
+            taken,
+            temp_names: program.temp_names.clone(),
+        })
+    }
+}
+
+/// Names and declaration sites of the locals of one function, or of the
+/// program body and the closures in it.
+pub(super) struct Unit {
+    pub(super) names: HashMap<VarId, String>,
+    /// Assignments that declare their local.
+    pub(super) declares: HashSet<*const Stmt>,
+    /// Locals declared up front, as `null`, because their first use does
+    /// not dominate the rest.
+    pub(super) hoisted: Vec<VarId>,
+}
+
+impl Unit {
+    fn analyze(
+        params: &[VarId],
+        body: &[Stmt],
+        tail: Option<&Expr>,
+        globals: &Globals,
+        ir: &LinkedIr,
+        flavor: SourceFlavor,
+    ) -> Self {
+        let mut usage = Usage::default();
+        usage.predeclared.extend(params.iter().copied());
+        let mut path = Vec::new();
+        path.push(usage.next_block);
+        usage.next_block += 1;
+        for stmt in body {
+            usage.stmt(stmt, &mut path);
+        }
+        if let Some(tail) = tail {
+            usage.expr(tail, &path);
+        }
+        // A loop variable whose local is also used outside its loops is
+        // declared like any other local, and the loops shadow it.
+        for (var, loops) in &usage.loop_vars {
+            let inside_loops = usage.refs[var]
+                .iter()
+                .all(|seen| loops.iter().any(|block| seen.path.starts_with(block)));
+            if inside_loops {
+                usage.predeclared.insert(*var);
+            }
+        }
+
+        let mut declares = HashSet::new();
+        let mut hoisted = Vec::new();
+        let mut vars = usage.refs.keys().copied().collect::<Vec<_>>();
+        vars.sort_unstable();
+        for var in &vars {
+            if usage.predeclared.contains(var) {
+                continue;
+            }
+            let refs = &usage.refs[var];
+            let first = &refs[0];
+            match first.stmt {
+                Some(stmt) if refs.iter().all(|seen| seen.path.starts_with(&first.path)) => {
+                    declares.insert(stmt);
+                }
+                _ => hoisted.push(*var),
+            }
+        }
+
+        let mut source_names = HashMap::new();
+        for (name, slot) in ir.local_bindings.iter().chain(&ir.scoped_bindings) {
+            source_names
+                .entry(VarId::from(*slot))
+                .or_insert_with(|| name.clone());
+        }
+        let mut order = params.to_vec();
+        order.extend(vars.iter().copied().filter(|var| !params.contains(var)));
+        order.extend(
+            usage
+                .predeclared
+                .iter()
+                .copied()
+                .filter(|var| !usage.refs.contains_key(var) && !params.contains(var)),
+        );
+        let mut taken = globals.taken.clone();
+        let mut names = HashMap::new();
+        for var in order {
+            if usage.aliases.contains_key(&var) {
+                continue;
+            }
+            let source_name = if var < FIRST_TEMP {
+                source_names.get(&var).map(String::as_str)
+            } else {
+                globals.temp_names.get(&var).copied()
+            };
+            let base = match source_name {
+                Some(name) if is_ident(name) && !name.starts_with("__") => {
+                    if is_reserved(flavor, name) {
+                        format!("{name}_")
+                    } else {
+                        name.to_string()
+                    }
+                }
+                _ => "tmp".to_string(),
+            };
+            let name = dedupe(&base, &taken);
+            taken.insert(name.clone());
+            names.insert(var, name);
+        }
+        let mut aliases = usage.aliases.iter().collect::<Vec<_>>();
+        aliases.sort_unstable();
+        for (inner, _) in aliases {
+            let mut outer = *inner;
+            while let Some(next) = usage.aliases.get(&outer) {
+                outer = *next;
+            }
+            let name = names
+                .get(&outer)
+                .cloned()
+                .unwrap_or_else(|| "tmp".to_string());
+            names.insert(*inner, name);
+        }
+        hoisted.retain(|var| !usage.aliases.contains_key(var));
+        Self {
+            names,
+            declares,
+            hoisted,
+        }
+    }
+
+    pub(super) fn name(&self, var: VarId) -> &str {
+        self.names.get(&var).map(String::as_str).unwrap_or("tmp")
+    }
+
+    pub(super) fn declares(&self, stmt: &Stmt) -> bool {
+        self.declares.contains(&(stmt as *const Stmt))
+    }
+}
+
+struct Reference {
+    /// The assignment making this reference, if it is one.
+    stmt: Option<*const Stmt>,
+    path: Vec<usize>,
+}
+
+/// Where each local is read and written, in source order, and which blocks
+/// enclose each use; a local is declared where it is first assigned when
+/// every later use sits inside that same block.
+#[derive(Default)]
+struct Usage {
+    refs: HashMap<VarId, Vec<Reference>>,
+    /// Parameters, closure copies and loop variables used only in their
+    /// loops, which their binder declares.
+    predeclared: HashSet<VarId>,
+    /// Blocks of the `for`-`in` loops binding each loop variable.
+    loop_vars: HashMap<VarId, Vec<Vec<usize>>>,
+    /// Closure copies of captured locals, which go by the captured name.
+    aliases: HashMap<VarId, VarId>,
+    next_block: usize,
+}
+
+impl Usage {
+    fn block(&mut self, stmts: &[Stmt], path: &mut Vec<usize>) {
+        path.push(self.next_block);
+        self.next_block += 1;
+        for stmt in stmts {
+            self.stmt(stmt, path);
+        }
+        path.pop();
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, path: &mut Vec<usize>) {
+        match stmt {
+            Stmt::Assign(var, value) => {
+                self.expr(value, path);
+                self.add(*var, Some(stmt), path);
+            }
+            Stmt::SetIndex(var, key, value) => {
+                self.expr(key, path);
+                self.expr(value, path);
+                self.add(*var, None, path);
+            }
+            Stmt::Expr(value) | Stmt::Return(value) => self.expr(value, path),
+            Stmt::If(condition, then_branch, else_branch) => {
+                self.expr(condition, path);
+                self.block(then_branch, path);
+                self.block(else_branch, path);
+            }
+            Stmt::While(condition, body) => {
+                self.expr(condition, path);
+                self.block(body, path);
+            }
+            Stmt::For(init, condition, post, body) => {
+                // The header belongs to the loop, so `let i` scopes to it.
+                path.push(self.next_block);
+                self.next_block += 1;
+                self.stmt(init, path);
+                self.expr(condition, path);
+                self.stmt(post, path);
+                self.block(body, path);
+                path.pop();
+            }
+            Stmt::ForIn {
+                key,
+                value,
+                iterable,
+                body,
+            } => {
+                self.expr(iterable, path);
+                path.push(self.next_block);
+                self.next_block += 1;
+                for var in key.iter().chain(value.iter()) {
+                    self.loop_vars.entry(*var).or_default().push(path.clone());
+                    self.add(*var, None, path);
+                }
+                self.block(body, path);
+                path.pop();
+            }
+            Stmt::Break | Stmt::Continue => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr, path: &[usize]) {
+        match expr {
+            Expr::Null
+            | Expr::Bool(_)
+            | Expr::Int(_)
+            | Expr::Float(_)
+            | Expr::String(_)
+            | Expr::Function(_) => {}
+            Expr::Var(var) => self.add(*var, None, path),
+            Expr::Neg(inner) | Expr::Not(inner) => self.expr(inner, path),
+            Expr::Binary(_, lhs, rhs) | Expr::Index(lhs, rhs) | Expr::OptionalIndex(lhs, rhs) => {
+                self.expr(lhs, path);
+                self.expr(rhs, path);
+            }
+            Expr::Call(callee, args) => {
+                if let Callee::Local(var) = callee {
+                    self.add(*var, None, path);
+                }
+                for arg in args {
+                    self.expr(arg, path);
+                }
+            }
+            Expr::Array(items) => {
+                for item in items {
+                    self.expr(item, path);
+                }
+            }
+            Expr::Map(entries) => {
+                for (key, value) in entries {
+                    self.expr(key, path);
+                    self.expr(value, path);
+                }
+            }
+            Expr::Slice(container, start, end) => {
+                self.expr(container, path);
+                self.expr(start, path);
+                if let Some(end) = end {
+                    self.expr(end, path);
+                }
+            }
+            Expr::Format(_, positional, named) => {
+                for arg in positional
+                    .iter()
+                    .chain(named.iter().map(|(_, value)| value))
+                {
+                    self.expr(arg, path);
+                }
+            }
+            Expr::IfElse(condition, then_expr, else_expr) => {
+                self.expr(condition, path);
+                self.expr(then_expr, path);
+                self.expr(else_expr, path);
+            }
+            Expr::Closure(closure) => {
+                for (outer, inner) in &closure.captures {
+                    self.add(*outer, None, path);
+                    self.aliases.insert(*inner, *outer);
+                    self.predeclared.insert(*inner);
+                }
+                self.predeclared.extend(closure.params.iter().copied());
+                let mut path = path.to_vec();
+                for param in &closure.params {
+                    self.add(*param, None, &path);
+                }
+                path.push(self.next_block);
+                self.next_block += 1;
+                for stmt in &closure.body {
+                    self.stmt(stmt, &mut path);
+                }
+                if let Some(tail) = &closure.tail {
+                    self.expr(tail, &path);
+                }
+            }
+        }
+    }
+
+    fn add(&mut self, var: VarId, stmt: Option<&Stmt>, path: &[usize]) {
+        self.refs.entry(var).or_default().push(Reference {
+            stmt: stmt.map(|stmt| stmt as *const Stmt),
+            path: path.to_vec(),
+        });
+    }
+}
+
+pub(super) fn walk_stmts(stmts: &[Stmt], visit: &mut impl FnMut(&Expr)) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Assign(_, value) | Stmt::Expr(value) | Stmt::Return(value) => {
+                walk_expr(value, visit)
+            }
+            Stmt::SetIndex(_, key, value) => {
+                walk_expr(key, visit);
+                walk_expr(value, visit);
+            }
+            Stmt::If(condition, then_branch, else_branch) => {
+                walk_expr(condition, visit);
+                walk_stmts(then_branch, visit);
+                walk_stmts(else_branch, visit);
+            }
+            Stmt::While(condition, body) => {
+                walk_expr(condition, visit);
+                walk_stmts(body, visit);
+            }
+            Stmt::For(init, condition, post, body) => {
+                walk_stmts(std::slice::from_ref(init.as_ref()), visit);
+                walk_expr(condition, visit);
+                walk_stmts(std::slice::from_ref(post.as_ref()), visit);
+                walk_stmts(body, visit);
+            }
+            Stmt::ForIn { iterable, body, .. } => {
+                walk_expr(iterable, visit);
+                walk_stmts(body, visit);
+            }
+            Stmt::Break | Stmt::Continue => {}
+        }
+    }
+}
+
+pub(super) fn walk_expr(expr: &Expr, visit: &mut impl FnMut(&Expr)) {
+    visit(expr);
+    match expr {
+        Expr::Null
+        | Expr::Bool(_)
+        | Expr::Int(_)
+        | Expr::Float(_)
+        | Expr::String(_)
+        | Expr::Var(_)
+        | Expr::Function(_) => {}
+        Expr::Neg(inner) | Expr::Not(inner) => walk_expr(inner, visit),
+        Expr::Binary(_, lhs, rhs) | Expr::Index(lhs, rhs) | Expr::OptionalIndex(lhs, rhs) => {
+            walk_expr(lhs, visit);
+            walk_expr(rhs, visit);
+        }
+        Expr::Call(_, args) | Expr::Array(args) => {
+            for arg in args {
+                walk_expr(arg, visit);
+            }
+        }
+        Expr::Map(entries) => {
+            for (key, value) in entries {
+                walk_expr(key, visit);
+                walk_expr(value, visit);
+            }
+        }
+        Expr::Slice(container, start, end) => {
+            walk_expr(container, visit);
+            walk_expr(start, visit);
+            if let Some(end) = end {
+                walk_expr(end, visit);
+            }
+        }
+        Expr::Format(_, positional, named) => {
+            for arg in positional
+                .iter()
+                .chain(named.iter().map(|(_, value)| value))
+            {
+                walk_expr(arg, visit);
+            }
+        }
+        Expr::IfElse(condition, then_expr, else_expr) => {
+            walk_expr(condition, visit);
+            walk_expr(then_expr, visit);
+            walk_expr(else_expr, visit);
+        }
+        Expr::Closure(closure) => {
+            walk_stmts(&closure.body, visit);
+            if let Some(tail) = &closure.tail {
+                walk_expr(tail, visit);
+            }
+        }
+    }
+}
+
+fn dedupe(base: &str, taken: &HashSet<String>) -> String {
+    let mut name = base.to_string();
+    let mut suffix = 1;
+    while taken.contains(&name) {
+        name = format!("{base}_{suffix}");
+        suffix += 1;
+    }
+    name
+}
+
+pub(super) fn is_ident(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+pub(super) fn is_reserved(flavor: SourceFlavor, name: &str) -> bool {
+    if KEYWORDS.contains(&name) {
+        return true;
+    }
+    match flavor {
+        // JavaScript also refuses the names of the builtins it wraps.
+        SourceFlavor::JavaScript => {
+            JAVASCRIPT_KEYWORDS.contains(&name)
+                || (0..u16::MAX).any(|index| {
+                    BuiltinFunction::from_call_index(index)
+                        .is_some_and(|builtin| builtin.name() == name)
+                })
+        }
+        SourceFlavor::Lua => LUA_KEYWORDS.contains(&name),
+        SourceFlavor::Scheme => SCHEME_KEYWORDS.contains(&name),
+        SourceFlavor::RustScript | SourceFlavor::Assembly => false,
+    }
+}
+
+/// Whether `name` can follow a `.`; `length` and `keys` read the length and
+/// keys instead, and `__`-prefixed names are left to the runtime.
+pub(super) fn is_field_name(flavor: SourceFlavor, name: &str) -> bool {
+    is_ident(name)
+        && !is_reserved(flavor, name)
+        && name != "length"
+        && name != "keys"
+        && !name.starts_with("__")
+}
+
+/// Whether the printed form of `expr` is one the compiler treats as
+/// definitely a string.
+pub(super) fn is_string_surface(expr: &Expr) -> bool {
+    match expr {
+        Expr::String(_) | Expr::Format(..) => true,
+        Expr::Call(Callee::Builtin(BuiltinFunction::ToString), _) => true,
+        Expr::Binary(BinaryOp::Add, lhs, rhs) => is_string_surface(lhs) || is_string_surface(rhs),
+        _ => false,
+    }
+}
+
+pub(super) fn quote(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for ch in text.chars() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\0' => out.push_str("\\0"),
+            other => out.push(other),
+        }
+    }
+    out.push('"');
+    out
+}
+
+pub(super) fn int_literal(value: i64) -> (String, u8) {
+    match value {
+        i64::MIN => (format!("({} - 1)", i64::MIN + 1), ATOM),
+        value if value < 0 => (value.to_string(), UNARY),
+        value => (value.to_string(), ATOM),
+    }
+}
+
+pub(super) fn float_literal(
+    flavor: SourceFlavor,
+    value: f64,
+) -> Result<(String, u8), TranspileError> {
+    if !value.is_finite() {
+        return Err(unsupported(flavor, format!("the float constant {value}")));
+    }
+    let mut text = value.to_string();
+    if !text.contains('.') {
+        text.push_str(".0");
+    }
+    let prec = if text.starts_with('-') { UNARY } else { ATOM };
+    Ok((text, prec))
+}
+
+/// The value each placeholder of a `format!` template prints, in template
+/// order, for flavors whose format strings only take arguments in order.
+/// Reordering is only allowed when the arguments have no effects.
+pub(super) fn ordered_placeholders<'e>(
+    flavor: SourceFlavor,
+    template: &str,
+    positional: &'e [Expr],
+    named: &'e [(String, Expr)],
+) -> Result<Vec<TemplatePart<'e>>, TranspileError> {
+    let pieces = parse_format_template(template)
+        .map_err(|err| unsupported(flavor, format!("the format template {template:?}: {err}")))?;
+    let mut parts = Vec::new();
+    let mut next = 0usize;
+    let mut used = Vec::new();
+    for piece in pieces {
+        match piece {
+            FormatPiece::Literal(text) => parts.push(TemplatePart::Literal(text)),
+            FormatPiece::Placeholder { arg, spec } => {
+                let (slot, value) = match arg {
+                    FormatArg::Next => {
+                        next += 1;
+                        (next - 1, positional.get(next - 1))
+                    }
+                    FormatArg::Index(index) => (index, positional.get(index)),
+                    FormatArg::Name(name) => {
+                        let position = named.iter().position(|(key, _)| *key == name);
+                        (
+                            positional.len() + position.unwrap_or(usize::MAX - positional.len()),
+                            position.map(|position| &named[position].1),
+                        )
+                    }
+                };
+                let Some(value) = value else {
+                    return Err(unsupported(
+                        flavor,
+                        "a format placeholder without an argument",
+                    ));
+                };
+                used.push(slot);
+                parts.push(TemplatePart::Value(value, spec));
+            }
+        }
+    }
+    let total = positional.len() + named.len();
+    let mut sorted = used.clone();
+    sorted.sort_unstable();
+    sorted.dedup();
+    let in_order = used.windows(2).all(|pair| pair[0] < pair[1]);
+    let pure = positional
+        .iter()
+        .chain(named.iter().map(|(_, value)| value))
+        .all(|value| matches!(value, Expr::Var(_)) || is_literal(value));
+    if sorted.len() != used.len() || sorted.len() != total || !(in_order || pure) {
+        return Err(unsupported(
+            flavor,
+            "a format! call whose arguments are not each used once, in order",
+        ));
+    }
+    Ok(parts)
+}
+
+pub(super) enum TemplatePart<'e> {
+    Literal(String),
+    Value(&'e Expr, FormatSpec),
+}
+
+pub(super) fn is_literal(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::Null | Expr::Bool(_) | Expr::Int(_) | Expr::Float(_) | Expr::String(_)
+    )
+}
+
+/// `i = s; i < end; i = i + step` with a positive constant step, the shape
+/// of Lua's numeric `for` and Scheme's `for`, returning the loop local,
+/// start, exclusive end, and step.
+pub(super) fn counting_loop<'s>(
+    unit: &Unit,
+    init: &'s Stmt,
+    condition: &'s Expr,
+    post: &'s Stmt,
+) -> Option<(VarId, &'s Expr, &'s Expr, i64)> {
+    let Stmt::Assign(var, start) = init else {
+        return None;
+    };
+    if !unit.declares(init) {
+        return None;
+    }
+    let Expr::Binary(BinaryOp::Lt, counter, end) = condition else {
+        return None;
+    };
+    if !matches!(counter.as_ref(), Expr::Var(counted) if counted == var) {
+        return None;
+    }
+    let Stmt::Assign(stepped, Expr::Binary(BinaryOp::Add, lhs, rhs)) = post else {
+        return None;
+    };
+    match (lhs.as_ref(), rhs.as_ref()) {
+        (Expr::Var(current), Expr::Int(step)) if current == var && stepped == var && *step > 0 => {
+            Some((*var, start, end, *step))
+        }
+        _ => None,
+    }
+}
+
+/// Whether a `continue` in `stmts` belongs to the loop around them.
+pub(super) fn continues_loop(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Stmt::Continue => true,
+        Stmt::If(_, then_branch, else_branch) => {
+            continues_loop(then_branch) || continues_loop(else_branch)
+        }
+        _ => false,
+    })
+}
+
+/// Prints RustScript, JavaScript, and Lua, whose expression grammars line
+/// up closely enough to share one printer.
+struct Renderer<'a> {
+    flavor: SourceFlavor,
+    globals: &'a Globals,
+    unit: &'a Unit,
+    depth: usize,
+    out: String,
+}
+
+impl<'a> Renderer<'a> {
+    fn new(flavor: SourceFlavor, globals: &'a Globals, unit: &'a Unit) -> Self {
+        Self {
+            flavor,
+            globals,
+            unit,
+            depth: 0,
+            out: String::new(),
+        }
+    }
+
+    fn is(&self, flavor: SourceFlavor) -> bool {
+        self.flavor == flavor
+    }
+
+    fn unsupported(&self, construct: impl Into<String>) -> TranspileError {
+        unsupported(self.flavor, construct)
+    }
+
+    fn line(&mut self, depth: usize, text: &str) {
+        for _ in 0..depth {
+            self.out.push_str("    ");
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    /// A statement terminator; Lua needs none.
+    fn semi(&self) -> &'static str {
+        if self.is(SourceFlavor::Lua) { "" } else { ";" }
+    }
+
+    fn name(&self, var: VarId) -> String {
+        self.unit.name(var).to_string()
+    }
+
+    fn hoisted(&mut self, depth: usize) {
+        for var in &self.unit.hoisted {
+            let name = self.unit.name(*var);
+            let text = match self.flavor {
+                SourceFlavor::Lua => format!("local {name} = nil"),
+                _ => format!("let {name} = null;"),
+            };
+            self.line(depth, &text);
+        }
+    }
+
+    fn function(&mut self, function: &Function) -> Result<(), TranspileError> {
+        let name = self.globals.functions[&function.index].clone();
+        let params = function
+            .params
+            .iter()
+            .map(|param| self.name(*param))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let header = match (self.flavor, function.exported) {
+            (SourceFlavor::RustScript, false) => format!("fn {name}({params}) {{"),
+            (SourceFlavor::RustScript, true) => format!("pub fn {name}({params}) {{"),
+            (SourceFlavor::JavaScript, false) => format!("function {name}({params}) {{"),
+            (SourceFlavor::JavaScript, true) => format!("export function {name}({params}) {{"),
+            (_, false) => format!("local function {name}({params})"),
+            (_, true) => format!("function {name}({params})"),
+        };
+        self.line(0, &header);
+        self.hoisted(1);
+        self.block(&function.body, 1)?;
+        match &function.tail {
+            Some(tail) => self.tail(tail, 1)?,
+            // A RustScript body has to end in a value.
+            None if self.is(SourceFlavor::RustScript)
+                && !matches!(function.body.last(), Some(Stmt::Return(_))) =>
+            {
+                self.line(1, "null;")
+            }
+            None => {}
+        }
+        self.line(
+            0,
+            if self.is(SourceFlavor::Lua) {
+                "end"
+            } else {
+                "}"
+            },
+        );
+        Ok(())
+    }
+
+    fn tail(&mut self, tail: &Expr, depth: usize) -> Result<(), TranspileError> {
+        self.depth = depth;
+        let text = self.expr(tail, 0)?;
+        let text = match self.flavor {
+            SourceFlavor::RustScript => format!("{};", self.statement_safe(text)),
+            SourceFlavor::JavaScript => format!("return {text};"),
+            _ => format!("return {text}"),
+        };
+        self.line(depth, &text);
+        Ok(())
+    }
+
+    fn block(&mut self, stmts: &[Stmt], depth: usize) -> Result<(), TranspileError> {
+        for stmt in stmts {
+            self.stmt(stmt, depth)?;
+        }
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, depth: usize) -> Result<(), TranspileError> {
+        self.depth = depth;
+        let semi = self.semi();
+        match stmt {
+            Stmt::Assign(..) | Stmt::SetIndex(..) => {
+                let text = self.assignment(stmt)?;
+                self.line(depth, &format!("{text}{semi}"));
+            }
+            Stmt::Expr(value) => {
+                let text = self.expr(value, 0)?;
+                let text = self.statement_safe(text);
+                self.line(depth, &format!("{text}{semi}"));
+            }
+            Stmt::If(condition, then_branch, else_branch) => {
+                let condition = self.condition(condition)?;
+                let text = match self.flavor {
+                    SourceFlavor::RustScript => format!("if {condition} {{"),
+                    SourceFlavor::JavaScript => format!("if ({condition}) {{"),
+                    _ => format!("if {condition} then"),
+                };
+                self.line(depth, &text);
+                self.block(then_branch, depth + 1)?;
+                self.else_branch(else_branch, depth)?;
+            }
+            Stmt::While(condition, body) => {
+                let condition = self.condition(condition)?;
+                let text = match self.flavor {
+                    SourceFlavor::RustScript => format!("while {condition} {{"),
+                    SourceFlavor::JavaScript => format!("while ({condition}) {{"),
+                    _ => format!("while {condition} do"),
+                };
+                self.line(depth, &text);
+                self.loop_body(body, depth)?;
+            }
+            Stmt::For(init, condition, post, body) => {
+                self.for_loop(init, condition, post, body, depth)?
+            }
+            Stmt::ForIn {
+                key,
+                value,
+                iterable,
+                body,
+            } => {
+                let iterable_text = self.expr(iterable, 0)?;
+                let key = key.map(|key| self.name(key));
+                let value = value.map(|value| self.name(value));
+                let text = match (self.flavor, key, value) {
+                    (SourceFlavor::RustScript, Some(key), Some(value)) => {
+                        format!("for ({key}, {value}) in {iterable_text} {{")
+                    }
+                    (SourceFlavor::RustScript, Some(key), None) => {
+                        format!("for ({key}, _) in {iterable_text} {{")
+                    }
+                    (SourceFlavor::RustScript, None, Some(value)) => {
+                        format!("for {value} in {iterable_text} {{")
+                    }
+                    (SourceFlavor::JavaScript, Some(key), Some(value)) => {
+                        format!(
+                            "for (const [{key}, {value}] of Object.entries({iterable_text})) {{"
+                        )
+                    }
+                    (SourceFlavor::JavaScript, Some(key), None) => {
+                        format!("for (const {key} in {iterable_text}) {{")
+                    }
+                    (SourceFlavor::JavaScript, None, Some(value)) => {
+                        format!("for (const {value} of {iterable_text}) {{")
+                    }
+                    (_, Some(key), Some(value)) => {
+                        format!("for {key}, {value} in pairs({iterable_text}) do")
+                    }
+                    (_, Some(key), None) => format!("for {key} in pairs({iterable_text}) do"),
+                    (_, None, Some(value)) => {
+                        format!("for _, {value} in pairs({iterable_text}) do")
+                    }
+                    (_, None, None) => return Err(self.unsupported("a loop binding nothing")),
+                };
+                self.line(depth, &text);
+                self.loop_body(body, depth)?;
+            }
+            Stmt::Break => self.line(depth, &format!("break{semi}")),
+            Stmt::Continue => {
+                let text = if self.is(SourceFlavor::Lua) {
+                    "goto continue".to_string()
+                } else {
+                    "continue;".to_string()
+                };
+                self.line(depth, &text);
+            }
+            Stmt::Return(value) => {
+                let value = self.expr(value, 0)?;
+                self.line(depth, &format!("return {value}{semi}"));
+            }
+        }
+        Ok(())
+    }
+
+    fn assignment(&mut self, stmt: &Stmt) -> Result<String, TranspileError> {
+        Ok(match stmt {
+            Stmt::Assign(var, value) => {
+                let name = self.name(*var);
+                let value = self.expr(value, 0)?;
+                if !self.unit.declares(stmt) {
+                    format!("{name} = {value}")
+                } else if self.is(SourceFlavor::Lua) {
+                    format!("local {name} = {value}")
+                } else {
+                    format!("let {name} = {value}")
+                }
+            }
+            Stmt::SetIndex(var, key, value) => {
+                let name = self.name(*var);
+                let target = self.index_text(name, key)?;
+                let value = self.expr(value, 0)?;
+                format!("{target} = {value}")
+            }
+            _ => return Err(self.unsupported("a statement in a loop header")),
+        })
+    }
+
+    fn for_loop(
+        &mut self,
+        init: &Stmt,
+        condition: &Expr,
+        post: &Stmt,
+        body: &[Stmt],
+        depth: usize,
+    ) -> Result<(), TranspileError> {
+        if !self.is(SourceFlavor::Lua) {
+            let init = self.assignment(init)?;
+            let condition = self.expr(condition, 0)?;
+            let post = self.assignment(post)?;
+            self.line(depth, &format!("for ({init}; {condition}; {post}) {{"));
+            self.block(body, depth + 1)?;
+            self.line(depth, "}");
+            return Ok(());
+        }
+        if let Some((var, start, end, step)) = counting_loop(self.unit, init, condition, post) {
+            // Lua's bound is inclusive.
+            let last = match end {
+                Expr::Binary(BinaryOp::Add, bound, one) if matches!(one.as_ref(), Expr::Int(1)) => {
+                    self.expr(bound, 0)?
+                }
+                Expr::Int(value) if *value > i64::MIN => int_literal(value - 1).0,
+                _ => {
+                    let end = self.expr(end, TERM)?;
+                    format!("{end} - 1")
+                }
+            };
+            let name = self.name(var);
+            let start = self.expr(start, 0)?;
+            let text = if step == 1 {
+                format!("for {name} = {start}, {last} do")
+            } else {
+                format!("for {name} = {start}, {last}, {step} do")
+            };
+            self.line(depth, &text);
+            self.loop_body(body, depth)?;
+            return Ok(());
+        }
+        // The step moves into the body, where a `continue` would skip it.
+        if continues_loop(body) {
+            return Err(self.unsupported("a `continue` in a for loop Lua cannot count"));
+        }
+        let mut body = body.to_vec();
+        body.push(post.clone());
+        self.stmt(init, depth)?;
+        let condition = self.condition(condition)?;
+        self.line(depth, &format!("while {condition} do"));
+        self.loop_body(&body, depth)?;
+        Ok(())
+    }
+
+    /// Prints a loop body and its closing line; Lua spells `continue` as a
+    /// jump to a label at the end of the body.
+    fn loop_body(&mut self, body: &[Stmt], depth: usize) -> Result<(), TranspileError> {
+        self.block(body, depth + 1)?;
+        if self.is(SourceFlavor::Lua) {
+            if continues_loop(body) {
+                self.line(depth + 1, "::continue::");
+            }
+            self.line(depth, "end");
+        } else {
+            self.line(depth, "}");
+        }
+        Ok(())
+    }
+
+    fn else_branch(&mut self, else_branch: &[Stmt], depth: usize) -> Result<(), TranspileError> {
+        let lua = self.is(SourceFlavor::Lua);
+        match else_branch {
+            [] => self.line(depth, if lua { "end" } else { "}" }),
+            [Stmt::If(condition, then_branch, nested_else)] => {
+                self.depth = depth;
+                let condition = self.condition(condition)?;
+                let text = match self.flavor {
+                    SourceFlavor::RustScript => format!("}} else if {condition} {{"),
+                    SourceFlavor::JavaScript => format!("}} else if ({condition}) {{"),
+                    _ => format!("elseif {condition} then"),
+                };
+                self.line(depth, &text);
+                self.block(then_branch, depth + 1)?;
+                self.else_branch(nested_else, depth)?;
+            }
+            _ => {
+                self.line(depth, if lua { "else" } else { "} else {" });
+                self.block(else_branch, depth + 1)?;
+                self.line(depth, if lua { "end" } else { "}" });
+            }
+        }
+        Ok(())
+    }
+
+    fn condition(&mut self, condition: &Expr) -> Result<String, TranspileError> {
+        let text = self.expr(condition, 0)?;
+        Ok(self.statement_safe(text))
+    }
+
+    /// Wraps expressions a statement or condition would otherwise misread
+    /// as a block, an `if` statement, or a declaration.
+    fn statement_safe(&self, text: String) -> String {
+        let misread = match self.flavor {
+            SourceFlavor::RustScript => text.starts_with('{') || text.starts_with("if "),
+            SourceFlavor::JavaScript => text.starts_with('{') || text.starts_with("function"),
+            _ => false,
+        };
+        if misread { format!("({text})") } else { text }
+    }
+
+    /// Renders `expr`, parenthesised if it binds looser than `min`.
+    fn expr(&mut self, expr: &Expr, min: u8) -> Result<String, TranspileError> {
+        let (text, prec) = self.expr_prec(expr)?;
+        // Members and indexing only follow a name, a call, or parentheses.
+        let literal_target =
+            min == POSTFIX && (is_literal(expr) || matches!(expr, Expr::Array(_) | Expr::Map(_)));
+        Ok(if prec < min || literal_target {
+            format!("({text})")
+        } else {
+            text
+        })
+    }
+
+    fn expr_prec(&mut self, expr: &Expr) -> Result<(String, u8), TranspileError> {
+        let lua = self.is(SourceFlavor::Lua);
+        Ok(match expr {
+            Expr::Null => ((if lua { "nil" } else { "null" }).to_string(), ATOM),
+            Expr::Bool(value) => (value.to_string(), ATOM),
+            Expr::Int(value) => int_literal(*value),
+            Expr::Float(value) => float_literal(self.flavor, *value)?,
+            Expr::String(text) => (self.string(text), ATOM),
+            Expr::Var(var) => (self.name(*var), ATOM),
+            Expr::Function(index) => (self.globals.functions[index].clone(), ATOM),
+            Expr::Neg(inner) => {
+                let inner = self.expr(inner, UNARY)?;
+                let inner = if inner.starts_with('-') {
+                    format!("({inner})")
+                } else {
+                    inner
+                };
+                (format!("-{inner}"), UNARY)
+            }
+            Expr::Not(inner) => {
+                let inner = self.expr(inner, UNARY)?;
+                if lua {
+                    (format!("not {inner}"), UNARY)
+                } else {
+                    (format!("!{inner}"), UNARY)
+                }
+            }
+            Expr::Binary(op, lhs, rhs) => self.binary(*op, lhs, rhs)?,
+            Expr::Call(callee, args) => self.call(*callee, args)?,
+            Expr::Array(items) => {
+                let items = self.list(items)?;
+                if lua && !items.is_empty() {
+                    (format!("{{{items}}}"), ATOM)
+                } else {
+                    (format!("[{items}]"), ATOM)
+                }
+            }
+            Expr::Map(entries) => (self.map(entries)?, ATOM),
+            Expr::Index(container, key) => {
+                let container = self.expr(container, POSTFIX)?;
+                (self.index_text(container, key)?, POSTFIX)
+            }
+            Expr::OptionalIndex(container, key) => {
+                let container = self.expr(container, POSTFIX)?;
+                match key.as_ref() {
+                    Expr::String(field) if is_field_name(self.flavor, field) => {
+                        (format!("{container}?.{field}"), POSTFIX)
+                    }
+                    _ => (format!("{container}?.[{}]", self.expr(key, 0)?), POSTFIX),
+                }
+            }
+            Expr::Slice(container, start, end) => {
+                let container = self.expr(container, POSTFIX)?;
+                let start = self.expr(start, 0)?;
+                let end = end.as_ref().map(|end| self.expr(end, 0)).transpose()?;
+                let text = match (self.flavor, end) {
+                    (SourceFlavor::JavaScript, Some(end)) => {
+                        format!("{container}.slice({start}, {end})")
+                    }
+                    (SourceFlavor::JavaScript, None) => format!("{container}.slice({start})"),
+                    (_, Some(end)) => format!("{container}[{start}:{end}]"),
+                    (_, None) => format!("{container}[{start}:]"),
+                };
+                (text, POSTFIX)
+            }
+            Expr::Format(template, positional, named) => {
+                (self.format(template, positional, named)?, POSTFIX)
+            }
+            Expr::IfElse(..) => (self.if_expr(expr)?, 0),
+            Expr::Closure(closure) => (self.closure(closure)?, 0),
+        })
+    }
+
+    fn string(&self, text: &str) -> String {
+        if self.is(SourceFlavor::JavaScript) {
+            // `\0` before a digit would read as an octal escape.
+            quote(text).replace("\\0", "\\x00")
+        } else {
+            quote(text)
+        }
+    }
+
+    fn list(&mut self, items: &[Expr]) -> Result<String, TranspileError> {
+        let items = items
+            .iter()
+            .map(|item| self.expr(item, 0))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(items.join(", "))
+    }
+
+    fn map(&mut self, entries: &[(Expr, Expr)]) -> Result<String, TranspileError> {
+        if entries.is_empty() {
+            return Ok("{}".to_string());
+        }
+        let lua = self.is(SourceFlavor::Lua);
+        let mut items = Vec::new();
+        for (key, value) in entries {
+            let key = match key {
+                Expr::String(name) if is_ident(name) && !is_reserved(self.flavor, name) => {
+                    name.clone()
+                }
+                Expr::String(name) if !lua => self.string(name),
+                _ => format!("[{}]", self.expr(key, 0)?),
+            };
+            let value = self.expr(value, 0)?;
+            items.push(if lua {
+                format!("{key} = {value}")
+            } else {
+                format!("{key}: {value}")
+            });
+        }
+        Ok(format!("{{ {} }}", items.join(", ")))
+    }
+
+    fn index_text(&mut self, container: String, key: &Expr) -> Result<String, TranspileError> {
+        if let Expr::String(field) = key
+            && is_field_name(self.flavor, field)
+        {
+            return Ok(format!("{container}.{field}"));
+        }
+        Ok(format!("{container}[{}]", self.expr(key, 0)?))
+    }
+
+    fn if_expr(&mut self, expr: &Expr) -> Result<String, TranspileError> {
+        let Expr::IfElse(condition, then_expr, else_expr) = expr else {
+            return self.expr(expr, 0);
+        };
+        if self.is(SourceFlavor::JavaScript) {
+            let condition = self.expr(condition, OR)?;
+            let then_expr = self.expr(then_expr, OR)?;
+            let else_expr = self.expr(else_expr, 0)?;
+            return Ok(format!("{condition} ? {then_expr} : {else_expr}"));
+        }
+        if !self.is(SourceFlavor::RustScript) {
+            return Err(self.unsupported("an if expression"));
+        }
+        let condition = self.condition(condition)?;
+        let then_expr = self.expr(then_expr, 0)?;
+        let else_part = if matches!(else_expr.as_ref(), Expr::IfElse(..)) {
+            self.if_expr(else_expr)?
+        } else {
+            format!("=> {{ {} }}", self.expr(else_expr, 0)?)
+        };
+        Ok(format!(
+            "if {condition} => {{ {then_expr} }} else {else_part}"
+        ))
+    }
+
+    fn closure(&mut self, closure: &Closure) -> Result<String, TranspileError> {
+        let params = closure
+            .params
+            .iter()
+            .map(|param| self.name(*param))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let depth = self.depth;
+        if closure.body.is_empty()
+            && let Some(tail) = &closure.tail
+        {
+            let tail_text = self.expr(tail, 0)?;
+            return Ok(match self.flavor {
+                SourceFlavor::RustScript => format!("|{params}| {tail_text}"),
+                SourceFlavor::JavaScript if tail_text.starts_with('{') => {
+                    format!("({params}) => ({tail_text})")
+                }
+                SourceFlavor::JavaScript => format!("({params}) => {tail_text}"),
+                _ => format!("function({params}) return {tail_text} end"),
+            });
+        }
+        if self.is(SourceFlavor::Lua) || self.is(SourceFlavor::RustScript) {
+            return Err(self.unsupported("a function literal with statements"));
+        }
+        let saved = std::mem::take(&mut self.out);
+        self.block(&closure.body, depth + 1)?;
+        if let Some(tail) = &closure.tail {
+            self.tail(tail, depth + 1)?;
+        }
+        let body = std::mem::replace(&mut self.out, saved);
+        self.depth = depth;
+        let indent = "    ".repeat(depth);
+        Ok(format!("({params}) => {{\n{body}{indent}}}"))
+    }
+
+    fn binary(
+        &mut self,
+        op: BinaryOp,
+        lhs: &Expr,
+        rhs: &Expr,
+    ) -> Result<(String, u8), TranspileError> {
+        let lua = self.is(SourceFlavor::Lua);
+        let js = self.is(SourceFlavor::JavaScript);
+        // RustScript and Lua spell `<=` and `>=` as negated comparisons.
+        if matches!(op, BinaryOp::Le | BinaryOp::Ge) && !js {
+            let flipped = if op == BinaryOp::Le { ">" } else { "<" };
+            let lhs = self.expr(lhs, COMPARE + 1)?;
+            let rhs = self.expr(rhs, COMPARE + 1)?;
+            let not = if lua { "not " } else { "!" };
+            return Ok((format!("{not}({lhs} {flipped} {rhs})"), UNARY));
+        }
+        let (symbol, prec) = match op {
+            BinaryOp::Add if lua && (is_string_surface(lhs) || is_string_surface(rhs)) => {
+                ("..", TERM)
+            }
+            BinaryOp::Add => ("+", TERM),
+            BinaryOp::Sub => ("-", TERM),
+            BinaryOp::Mul => ("*", FACTOR),
+            BinaryOp::Div => ("/", FACTOR),
+            BinaryOp::Mod => ("%", FACTOR),
+            BinaryOp::Eq if js => ("===", COMPARE),
+            BinaryOp::Eq => ("==", COMPARE),
+            BinaryOp::Ne if js => ("!==", COMPARE),
+            BinaryOp::Ne if lua => ("~=", COMPARE),
+            BinaryOp::Ne => ("!=", COMPARE),
+            BinaryOp::Lt => ("<", COMPARE),
+            BinaryOp::Gt => (">", COMPARE),
+            BinaryOp::Le => ("<=", COMPARE),
+            BinaryOp::Ge => (">=", COMPARE),
+            BinaryOp::And if lua => ("and", AND),
+            BinaryOp::And => ("&&", AND),
+            BinaryOp::Or if lua => ("or", OR),
+            BinaryOp::Or => ("||", OR),
+        };
+        // Comparisons never chain without parentheses.
+        let lhs_min = if prec == COMPARE { COMPARE + 1 } else { prec };
+        let mut lhs_text = self.expr(lhs, lhs_min)?;
+        // Lua's `..` and `+` share a precedence here but not in Lua itself.
+        if lua
+            && prec == TERM
+            && let Expr::Binary(BinaryOp::Add, ..) = lhs
+            && !lhs_text.starts_with('(')
+            && (symbol == "..") != (is_string_surface(lhs))
+        {
+            lhs_text = format!("({lhs_text})");
+        }
+        let rhs_text = self.expr(rhs, prec + 1)?;
+        Ok((format!("{lhs_text} {symbol} {rhs_text}"), prec))
+    }
+
+    fn call(&mut self, callee: Callee, args: &[Expr]) -> Result<(String, u8), TranspileError> {
+        let builtin = match callee {
+            Callee::Function(index) => {
+                let name = self.globals.functions[&index].clone();
+                return Ok((format!("{name}({})", self.list(args)?), POSTFIX));
+            }
+            Callee::Local(var) => {
+                let name = self.name(var);
+                return Ok((format!("{name}({})", self.list(args)?), POSTFIX));
+            }
+            Callee::Host(index) => {
+                let name = self.globals.hosts[&index].clone();
+                let args = self.list(args)?;
+                let text = match self.flavor {
+                    _ if name != STDLIB_PRINT_NAME => format!("{name}({args})"),
+                    SourceFlavor::RustScript => format!("print!({args})"),
+                    SourceFlavor::JavaScript => format!("console.log({args})"),
+                    _ => format!("print({args})"),
+                };
+                return Ok((text, POSTFIX));
+            }
+            Callee::Builtin(builtin) => builtin,
+        };
+        let js = self.is(SourceFlavor::JavaScript);
+        Ok(match (builtin, args) {
+            (BuiltinFunction::Keys, [value]) if js => {
+                (format!("Object.keys({})", self.expr(value, 0)?), POSTFIX)
+            }
+            (BuiltinFunction::MapValues, [value]) if js => {
+                (format!("Object.values({})", self.expr(value, 0)?), POSTFIX)
+            }
+            (BuiltinFunction::MapEntries, [value]) if js => {
+                (format!("Object.entries({})", self.expr(value, 0)?), POSTFIX)
+            }
+            (BuiltinFunction::TypeOf, [value]) if js => {
+                (format!("typeof {}", self.expr(value, UNARY)?), UNARY)
+            }
+            (BuiltinFunction::TypeOf, [value]) => {
+                (format!("type({})", self.expr(value, 0)?), POSTFIX)
+            }
+            (BuiltinFunction::Len, [value]) => {
+                (format!("{}.length", self.expr(value, POSTFIX)?), POSTFIX)
+            }
+            (BuiltinFunction::Keys, [value]) => {
+                (format!("{}.keys", self.expr(value, POSTFIX)?), POSTFIX)
+            }
+            (BuiltinFunction::Assert, [value]) => {
+                (format!("assert({})", self.expr(value, 0)?), POSTFIX)
+            }
+            (BuiltinFunction::ToString, [value]) => {
+                let value = self.expr(value, FACTOR)?;
+                let concat = if self.is(SourceFlavor::Lua) {
+                    ".."
+                } else {
+                    "+"
+                };
+                (format!("\"\" {concat} {value}"), TERM)
+            }
+            _ => match builtin.source_path() {
+                // JavaScript has no `::` paths, and its frontend reads
+                // `map.has_key(...)` as the same builtin call.
+                Some(path) if js => (
+                    format!("{}({})", path.replace("::", "."), self.list(args)?),
+                    POSTFIX,
+                ),
+                Some(path) => (format!("{path}({})", self.list(args)?), POSTFIX),
+                None => {
+                    return Err(self.unsupported(format!(
+                        "the '{}' builtin outside the syntax that produces it",
+                        builtin.name()
+                    )));
+                }
+            },
+        })
+    }
+
+    fn format(
+        &mut self,
+        template: &str,
+        positional: &[Expr],
+        named: &[(String, Expr)],
+    ) -> Result<String, TranspileError> {
+        match self.flavor {
+            SourceFlavor::RustScript => {
+                let mut parts = vec![quote(template)];
+                for value in positional {
+                    parts.push(self.expr(value, 0)?);
+                }
+                for (name, value) in named {
+                    if !is_ident(name) || is_reserved(self.flavor, name) {
+                        return Err(self.unsupported(format!("the format argument name '{name}'")));
+                    }
+                    parts.push(format!("{name} = {}", self.expr(value, 0)?));
+                }
+                Ok(format!("format!({})", parts.join(", ")))
+            }
+            SourceFlavor::JavaScript => {
+                let parts = ordered_placeholders(self.flavor, template, positional, named)?;
+                let mut text = String::from("`");
+                for part in parts {
+                    match part {
+                        TemplatePart::Literal(literal) => {
+                            text.push_str(
+                                &literal
+                                    .replace('\\', "\\\\")
+                                    .replace('`', "\\`")
+                                    .replace("${", "\\${"),
+                            );
+                        }
+                        TemplatePart::Value(value, spec) => {
+                            if spec != FormatSpec::default() {
+                                return Err(self.unsupported("a format spec in a template literal"));
+                            }
+                            let value = self.expr(value, 0)?;
+                            let _ = write!(text, "${{{value}}}");
+                        }
+                    }
+                }
+                text.push('`');
+                Ok(text)
+            }
+            _ => {
+                let parts = ordered_placeholders(self.flavor, template, positional, named)?;
+                let mut format = String::new();
+                let mut args = Vec::new();
+                for part in parts {
+                    match part {
+                        TemplatePart::Literal(literal) => {
+                            format.push_str(&literal.replace('%', "%%"))
+                        }
+                        TemplatePart::Value(value, spec) => {
+                            format.push_str(&self.lua_conversion(&spec)?);
+                            args.push(self.expr(value, 0)?);
+                        }
+                    }
+                }
+                let mut parts = vec![quote(&format)];
+                parts.extend(args);
+                Ok(format!("string.format({})", parts.join(", ")))
+            }
+        }
+    }
+
+    /// The `string.format` conversion that lowers back to `spec`.
+    fn lua_conversion(&self, spec: &FormatSpec) -> Result<String, TranspileError> {
+        let mut text = String::from("%");
+        if let Some(width) = spec.width {
+            match (spec.align, spec.zero_pad, spec.fill) {
+                (Some(FormatAlign::Left), false, ' ') => text.push('-'),
+                (Some(FormatAlign::Right), false, ' ') => {}
+                (None, true, _) => text.push('0'),
+                _ => return Err(self.unsupported("this format spec in string.format")),
+            }
+            let _ = write!(text, "{width}");
+        } else if spec.align.is_some() || spec.zero_pad {
+            return Err(self.unsupported("this format spec in string.format"));
+        }
+        if let Some(precision) = spec.precision {
+            let _ = write!(text, ".{precision}");
+        }
+        text.push(if spec.debug { 'q' } else { 's' });
+        Ok(text)
+    }
+}
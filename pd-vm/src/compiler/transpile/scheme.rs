@@ -0,0 +1,825 @@
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use super::render::{
+    Globals, TemplatePart, Unit, continues_loop, counting_loop, float_literal, int_literal,
+    is_ident, is_string_surface, ordered_placeholders, quote, unsupported, walk_expr, walk_stmts,
+};
+use super::{BinaryOp, Callee, Closure, Expr, Function, Program, Stmt, TranspileError, VarId};
+use crate::builtins::BuiltinFunction;
+use crate::compiler::{STDLIB_PRINT_NAME, SourceFlavor};
+use crate::format::FormatSpec;
+
+/// Prints the program body, with each function it calls defined up front.
+///
+/// A Scheme `define`d function cannot call another one, since closures do
+/// not capture callable locals, so each function instead binds the functions
+/// it calls as `let*` lambdas of its own.
+pub(super) fn render_program(
+    program: &Program,
+    globals: &Globals,
+    units: &HashMap<u16, Unit>,
+    unit: &Unit,
+) -> Result<String, TranspileError> {
+    let functions = Functions {
+        program,
+        globals,
+        units,
+    };
+    let printer = Printer {
+        globals,
+        unit,
+        fresh: Cell::new(0),
+    };
+    let mut out = String::new();
+    for function in functions.called_by(&program.body, None) {
+        let printer = functions.printer(function.index);
+        let mut signature = vec![scheme_name(&globals.functions[&function.index])];
+        signature.extend(function.params.iter().map(|param| printer.name(*param)));
+        let _ = writeln!(
+            out,
+            "(define ({})\n  {})\n",
+            signature.join(" "),
+            functions.body(function)?
+        );
+    }
+    for var in &unit.hoisted {
+        let _ = writeln!(out, "(define {} null)", printer.name(*var));
+    }
+    for stmt in &program.body {
+        printer.stmt(stmt, 0, &mut out)?;
+    }
+    Ok(out)
+}
+
+struct Functions<'a> {
+    program: &'a Program,
+    globals: &'a Globals,
+    units: &'a HashMap<u16, Unit>,
+}
+
+impl<'a> Functions<'a> {
+    fn printer(&self, index: u16) -> Printer<'a> {
+        Printer {
+            globals: self.globals,
+            unit: &self.units[&index],
+            fresh: Cell::new(0),
+        }
+    }
+
+    /// The functions `stmts` and `tail` name, callees first.
+    fn called_by(&self, stmts: &[Stmt], tail: Option<&Expr>) -> Vec<&'a Function> {
+        let mut called = HashSet::new();
+        let mut visit = |expr: &Expr| match expr {
+            Expr::Call(Callee::Function(index), _) | Expr::Function(index) => {
+                called.insert(*index);
+            }
+            _ => {}
+        };
+        walk_stmts(stmts, &mut visit);
+        if let Some(tail) = tail {
+            walk_expr(tail, &mut visit);
+        }
+        self.program
+            .functions
+            .iter()
+            .filter(|function| called.contains(&function.index))
+            .collect()
+    }
+
+    fn body(&self, function: &Function) -> Result<String, TranspileError> {
+        let printer = self.printer(function.index);
+        let mut body = function.body.as_slice();
+        let mut tail = function.tail.clone();
+        // A final `return` is the value the body ends with.
+        if tail.is_none()
+            && let Some((Stmt::Return(value), rest)) = body.split_last()
+        {
+            tail = Some(value.clone());
+            body = rest;
+        }
+        let tail = tail.unwrap_or(Expr::Null);
+        let mut bindings = Vec::new();
+        for callee in self.called_by(body, Some(&tail)) {
+            let params = callee
+                .params
+                .iter()
+                .map(|param| self.printer(callee.index).name(*param))
+                .collect::<Vec<_>>();
+            bindings.push(format!(
+                "({} (lambda ({}) {}))",
+                scheme_name(&self.globals.functions[&callee.index]),
+                params.join(" "),
+                self.body(callee)?
+            ));
+        }
+        bindings.extend(
+            printer
+                .unit
+                .hoisted
+                .iter()
+                .map(|var| format!("({} null)", printer.name(*var))),
+        );
+        let text = printer.sequence(body, &tail)?;
+        Ok(if bindings.is_empty() {
+            text
+        } else {
+            format!("(let* ({}) {text})", bindings.join(" "))
+        })
+    }
+}
+
+/// Scheme spells multi-word names with dashes; the frontend maps them back.
+fn scheme_name(name: &str) -> String {
+    if name.starts_with('_') || name.ends_with('_') || name.contains("__") {
+        name.to_string()
+    } else {
+        name.replace('_', "-")
+    }
+}
+
+/// Whether `name` reads back as the same symbol, and so can name a `hash`
+/// key or an optional-chain member.
+fn is_symbol_key(name: &str) -> bool {
+    is_ident(name) && !matches!(name, "true" | "false" | "null" | "nil")
+}
+
+struct Printer<'a> {
+    globals: &'a Globals,
+    unit: &'a Unit,
+    /// Numbers the names `sequence` makes up.
+    fresh: Cell<usize>,
+}
+
+/// What a function body does once the statements `Printer::seq` is given
+/// run out.
+enum Then<'s> {
+    /// Ends with the body's value.
+    Value(&'s Expr),
+    /// Ends an `if` or loop with the locals it rebinds.
+    Join(&'s [String]),
+    /// Runs a `for` loop's step, then starts the next iteration.
+    Loop {
+        name: &'s str,
+        carried: &'s [String],
+        step: &'s [Stmt],
+    },
+    /// Runs more statements, then the next continuation.
+    Seq(&'s [Stmt], &'s Then<'s>),
+}
+
+impl Then<'_> {
+    /// The innermost loop this continuation iterates, if any.
+    fn enclosing_loop(&self) -> Option<&Self> {
+        match self {
+            Then::Loop { .. } => Some(self),
+            Then::Seq(_, next) => next.enclosing_loop(),
+            Then::Value(_) | Then::Join(_) => None,
+        }
+    }
+}
+
+/// The value an `if` or loop yields for the locals it rebinds.
+fn join(carried: &[String]) -> String {
+    match carried {
+        [] => "null".to_string(),
+        [var] => var.clone(),
+        _ => format!("(list {})", carried.join(" ")),
+    }
+}
+
+/// Whether `stmts` leave the enclosing loop or function early.
+fn jumps(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Stmt::Break | Stmt::Continue | Stmt::Return(_) => true,
+        Stmt::If(_, then_branch, else_branch) => jumps(then_branch) || jumps(else_branch),
+        _ => false,
+    })
+}
+
+impl Printer<'_> {
+    fn unsupported(&self, construct: impl Into<String>) -> TranspileError {
+        unsupported(SourceFlavor::Scheme, construct)
+    }
+
+    fn name(&self, var: VarId) -> String {
+        scheme_name(self.unit.name(var))
+    }
+
+    fn line(out: &mut String, depth: usize, text: &str) {
+        for _ in 0..depth {
+            out.push_str("  ");
+        }
+        out.push_str(text);
+        out.push('\n');
+    }
+
+    /// A function or lambda body. Scheme only allows expressions there, so
+    /// assignments become `let*` bindings, an `if` or loop that assigns
+    /// locals rebinds them from its value, and loops become named `let`s
+    /// carrying the locals they assign.
+    fn sequence(&self, stmts: &[Stmt], tail: &Expr) -> Result<String, TranspileError> {
+        self.seq(stmts, &Then::Value(tail))
+    }
+
+    fn seq(&self, stmts: &[Stmt], then: &Then) -> Result<String, TranspileError> {
+        let Some((stmt, rest)) = stmts.split_first() else {
+            return self.finish(then);
+        };
+        match stmt {
+            Stmt::Assign(..) | Stmt::Expr(_) => {
+                let mut bindings = Vec::new();
+                let mut rest = stmts;
+                while let Some((stmt, remaining)) = rest.split_first() {
+                    match stmt {
+                        Stmt::Assign(var, value) => {
+                            bindings.push(format!("({} {})", self.name(*var), self.expr(value)?));
+                        }
+                        Stmt::Expr(value) => {
+                            let effect = self.fresh("__effect");
+                            bindings.push(format!("({effect} {})", self.expr(value)?));
+                        }
+                        _ => break,
+                    }
+                    rest = remaining;
+                }
+                Ok(format!(
+                    "(let* ({}) {})",
+                    bindings.join(" "),
+                    self.seq(rest, then)?
+                ))
+            }
+            Stmt::If(condition, then_branch, else_branch) => {
+                let condition = self.expr(condition)?;
+                // A branch that leaves early cannot rejoin, so each branch
+                // carries on with the rest of the body itself.
+                if rest.is_empty() || jumps(then_branch) || jumps(else_branch) {
+                    let next = Then::Seq(rest, then);
+                    return Ok(format!(
+                        "(if {condition} {} {})",
+                        self.seq(then_branch, &next)?,
+                        self.seq(else_branch, &next)?
+                    ));
+                }
+                let carried = self.carried(&[then_branch, else_branch]);
+                let joined = Then::Join(&carried);
+                let value = format!(
+                    "(if {condition} {} {})",
+                    self.seq(then_branch, &joined)?,
+                    self.seq(else_branch, &joined)?
+                );
+                self.rebind(&carried, value, rest, then)
+            }
+            Stmt::While(condition, body) => self.loop_form(condition, body, &[], rest, then),
+            Stmt::For(init, condition, post, body) => {
+                let Stmt::Assign(var, start) = init.as_ref() else {
+                    return Err(self.unsupported("a `for` loop inside a function body"));
+                };
+                Ok(format!(
+                    "(let* (({} {})) {})",
+                    self.name(*var),
+                    self.expr(start)?,
+                    self.loop_form(condition, body, std::slice::from_ref(post), rest, then)?
+                ))
+            }
+            Stmt::Break => match then.enclosing_loop() {
+                Some(Then::Loop { carried, .. }) => Ok(join(carried)),
+                _ => Err(self.unsupported("a `break` outside a loop")),
+            },
+            Stmt::Continue => match then.enclosing_loop() {
+                Some(looped) => self.finish(looped),
+                None => Err(self.unsupported("a `continue` outside a loop")),
+            },
+            Stmt::Return(value) => match then.enclosing_loop() {
+                Some(_) => Err(self.unsupported("a `return` inside a loop")),
+                None => self.expr(value),
+            },
+            Stmt::SetIndex(..) => {
+                Err(self.unsupported("an element assignment inside a function body"))
+            }
+            Stmt::ForIn { .. } => Err(self.unsupported("a `for`-`in` loop inside a function body")),
+        }
+    }
+
+    /// A named `let` that runs `body` and `step` while `condition` holds,
+    /// then yields the locals it carried for `rest` to rebind.
+    fn loop_form(
+        &self,
+        condition: &Expr,
+        body: &[Stmt],
+        step: &[Stmt],
+        rest: &[Stmt],
+        then: &Then,
+    ) -> Result<String, TranspileError> {
+        let carried = self.carried(&[body, step]);
+        let name = self.fresh("__loop");
+        // A named `let` needs at least one binding.
+        let bindings = if carried.is_empty() {
+            "(__unused null)".to_string()
+        } else {
+            carried
+                .iter()
+                .map(|var| format!("({var} {var})"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let looped = Then::Loop {
+            name: &name,
+            carried: &carried,
+            step,
+        };
+        let value = format!(
+            "(let {name} ({bindings}) (if {} {} {}))",
+            self.expr(condition)?,
+            self.seq(body, &looped)?,
+            join(&carried)
+        );
+        self.rebind(&carried, value, rest, then)
+    }
+
+    fn finish(&self, then: &Then) -> Result<String, TranspileError> {
+        match then {
+            Then::Value(value) => self.expr(value),
+            Then::Join(carried) => Ok(join(carried)),
+            Then::Loop {
+                name,
+                carried,
+                step,
+            } => {
+                if !step.is_empty() {
+                    return self.seq(
+                        step,
+                        &Then::Loop {
+                            name,
+                            carried,
+                            step: &[],
+                        },
+                    );
+                }
+                let args = if carried.is_empty() {
+                    "null".to_string()
+                } else {
+                    carried.join(" ")
+                };
+                Ok(format!("({name} {args})"))
+            }
+            Then::Seq(stmts, next) => self.seq(stmts, next),
+        }
+    }
+
+    /// Binds the locals `value` yields back, then goes on with `rest`.
+    fn rebind(
+        &self,
+        carried: &[String],
+        value: String,
+        rest: &[Stmt],
+        then: &Then,
+    ) -> Result<String, TranspileError> {
+        let bindings = match carried {
+            [] => format!("({} {value})", self.fresh("__effect")),
+            [var] => format!("({var} {value})"),
+            _ => {
+                let joined = self.fresh("__join");
+                let mut bindings = format!("({joined} {value})");
+                for (index, var) in carried.iter().enumerate() {
+                    let _ = write!(bindings, " ({var} (vector-ref {joined} {index}))");
+                }
+                bindings
+            }
+        };
+        Ok(format!("(let* ({bindings}) {})", self.seq(rest, then)?))
+    }
+
+    /// The locals `blocks` assign without declaring them there.
+    fn carried(&self, blocks: &[&[Stmt]]) -> Vec<String> {
+        fn visit(
+            unit: &Unit,
+            stmts: &[Stmt],
+            assigned: &mut Vec<VarId>,
+            declared: &mut Vec<VarId>,
+        ) {
+            for stmt in stmts {
+                match stmt {
+                    Stmt::Assign(var, _) => {
+                        if unit.declares(stmt) {
+                            declared.push(*var);
+                        }
+                        if !assigned.contains(var) {
+                            assigned.push(*var);
+                        }
+                    }
+                    Stmt::If(_, then_branch, else_branch) => {
+                        visit(unit, then_branch, assigned, declared);
+                        visit(unit, else_branch, assigned, declared);
+                    }
+                    Stmt::While(_, body) => visit(unit, body, assigned, declared),
+                    Stmt::For(init, _, post, body) => {
+                        visit(unit, std::slice::from_ref(init), assigned, declared);
+                        visit(unit, std::slice::from_ref(post), assigned, declared);
+                        visit(unit, body, assigned, declared);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let mut assigned = Vec::new();
+        let mut declared = Vec::new();
+        for stmts in blocks {
+            visit(self.unit, stmts, &mut assigned, &mut declared);
+        }
+        assigned
+            .into_iter()
+            .filter(|var| !declared.contains(var))
+            .map(|var| self.name(var))
+            .collect()
+    }
+
+    /// A name no other binding of this body uses.
+    fn fresh(&self, prefix: &str) -> String {
+        self.fresh.set(self.fresh.get() + 1);
+        format!("{prefix}{}", self.fresh.get())
+    }
+
+    fn block(&self, stmts: &[Stmt], depth: usize, out: &mut String) -> Result<(), TranspileError> {
+        // Clauses need at least one form, even when it does nothing.
+        if stmts.is_empty() {
+            Self::line(out, depth, "(begin)");
+        }
+        for stmt in stmts {
+            self.stmt(stmt, depth, out)?;
+        }
+        Ok(())
+    }
+
+    fn stmt(&self, stmt: &Stmt, depth: usize, out: &mut String) -> Result<(), TranspileError> {
+        match stmt {
+            // `(define (f x) ...)` is shorthand for binding a lambda.
+            Stmt::Assign(var, Expr::Closure(closure)) if self.unit.declares(stmt) => {
+                let mut signature = vec![self.name(*var)];
+                signature.extend(closure.params.iter().map(|param| self.name(*param)));
+                let text = format!(
+                    "(define ({})\n{}  {})",
+                    signature.join(" "),
+                    "  ".repeat(depth),
+                    self.lambda_body(closure)?
+                );
+                Self::line(out, depth, &text);
+            }
+            Stmt::Assign(var, value) => {
+                let head = if self.unit.declares(stmt) {
+                    "define"
+                } else {
+                    "set!"
+                };
+                let text = format!("({head} {} {})", self.name(*var), self.expr(value)?);
+                Self::line(out, depth, &text);
+            }
+            Stmt::SetIndex(var, key, value) => {
+                let head = if matches!(key, Expr::String(_)) {
+                    "hash-set!"
+                } else {
+                    "vector-set!"
+                };
+                let text = format!(
+                    "({head} {} {} {})",
+                    self.name(*var),
+                    self.expr(key)?,
+                    self.expr(value)?
+                );
+                Self::line(out, depth, &text);
+            }
+            // `and` and `or` only parse as statements inside an `if`.
+            Stmt::Expr(value @ Expr::Binary(BinaryOp::And | BinaryOp::Or, ..)) => {
+                Self::line(out, depth, &format!("(if {} #t #f)", self.expr(value)?));
+            }
+            Stmt::Expr(value) => Self::line(out, depth, &self.expr(value)?),
+            Stmt::If(condition, then_branch, else_branch) => {
+                self.if_stmt(condition, then_branch, else_branch, depth, out)?
+            }
+            Stmt::While(condition, body) => {
+                Self::line(out, depth, &format!("(while {}", self.expr(condition)?));
+                self.block(body, depth + 1, out)?;
+                close(out);
+            }
+            Stmt::For(init, condition, post, body) => {
+                if let Some((var, start, end, step)) =
+                    counting_loop(self.unit, init, condition, post)
+                {
+                    let mut header = format!(
+                        "(for ({} {} {}",
+                        self.name(var),
+                        self.expr(start)?,
+                        self.expr(end)?
+                    );
+                    if step != 1 {
+                        let _ = write!(header, " {step}");
+                    }
+                    header.push(')');
+                    Self::line(out, depth, &header);
+                    self.block(body, depth + 1, out)?;
+                    close(out);
+                } else {
+                    // The step moves into the body, where a `continue` would
+                    // skip it.
+                    if continues_loop(body) {
+                        return Err(
+                            self.unsupported("a `continue` in a for loop Scheme cannot count")
+                        );
+                    }
+                    self.stmt(init, depth, out)?;
+                    Self::line(out, depth, &format!("(while {}", self.expr(condition)?));
+                    self.block(body, depth + 1, out)?;
+                    self.stmt(post, depth + 1, out)?;
+                    close(out);
+                }
+            }
+            Stmt::ForIn { .. } => return Err(self.unsupported("a for-in loop")),
+            Stmt::Break => Self::line(out, depth, "(break)"),
+            Stmt::Continue => Self::line(out, depth, "(continue)"),
+            Stmt::Return(_) => return Err(self.unsupported("an early return")),
+        }
+        Ok(())
+    }
+
+    fn if_stmt(
+        &self,
+        condition: &Expr,
+        then_branch: &[Stmt],
+        else_branch: &[Stmt],
+        depth: usize,
+        out: &mut String,
+    ) -> Result<(), TranspileError> {
+        let condition = self.expr(condition)?;
+        if else_branch.is_empty() {
+            Self::line(out, depth, &format!("(when {condition}"));
+            self.block(then_branch, depth + 1, out)?;
+            close(out);
+            return Ok(());
+        }
+        if !matches!(else_branch, [Stmt::If(..)]) {
+            Self::line(out, depth, &format!("(if {condition}"));
+            for branch in [then_branch, else_branch] {
+                Self::line(out, depth + 1, "(begin");
+                self.block(branch, depth + 2, out)?;
+                close(out);
+            }
+            close(out);
+            return Ok(());
+        }
+        // An `else if` chain reads better as one `cond`.
+        Self::line(out, depth, "(cond");
+        Self::line(out, depth + 1, &format!("({condition}"));
+        self.block(then_branch, depth + 2, out)?;
+        close(out);
+        let mut rest = else_branch;
+        loop {
+            match rest {
+                [] => break,
+                [Stmt::If(condition, then_branch, else_branch)] => {
+                    Self::line(out, depth + 1, &format!("({}", self.expr(condition)?));
+                    self.block(then_branch, depth + 2, out)?;
+                    close(out);
+                    rest = else_branch;
+                }
+                _ => {
+                    Self::line(out, depth + 1, "(else");
+                    self.block(rest, depth + 2, out)?;
+                    close(out);
+                    break;
+                }
+            }
+        }
+        close(out);
+        Ok(())
+    }
+
+    fn exprs(&self, exprs: &[Expr]) -> Result<Vec<String>, TranspileError> {
+        exprs.iter().map(|expr| self.expr(expr)).collect()
+    }
+
+    fn form(&self, head: &str, args: &[Expr]) -> Result<String, TranspileError> {
+        let mut parts = vec![head.to_string()];
+        parts.extend(self.exprs(args)?);
+        Ok(format!("({})", parts.join(" ")))
+    }
+
+    fn expr(&self, expr: &Expr) -> Result<String, TranspileError> {
+        Ok(match expr {
+            Expr::Null => "null".to_string(),
+            Expr::Bool(true) => "#t".to_string(),
+            Expr::Bool(false) => "#f".to_string(),
+            Expr::Int(i64::MIN) => format!("(- {} 1)", i64::MIN + 1),
+            Expr::Int(value) => int_literal(*value).0,
+            Expr::Float(value) => float_literal(SourceFlavor::Scheme, *value)?.0,
+            Expr::String(text) => quote(text),
+            Expr::Var(var) => self.name(*var),
+            Expr::Function(index) => scheme_name(&self.globals.functions[index]),
+            Expr::Neg(inner) => format!("(- {})", self.expr(inner)?),
+            Expr::Not(inner) => format!("(not {})", self.expr(inner)?),
+            Expr::Binary(op, lhs, rhs) => self.binary(*op, lhs, rhs)?,
+            Expr::Call(callee, args) => self.call(*callee, args)?,
+            Expr::Array(items) => self.form("vector", items)?,
+            Expr::Map(entries) => {
+                let mut parts = vec!["hash".to_string()];
+                for (key, value) in entries {
+                    let key = match key {
+                        Expr::String(name) if is_symbol_key(name) => name.clone(),
+                        Expr::String(_) | Expr::Int(_) | Expr::Float(_) | Expr::Bool(_) => {
+                            self.expr(key)?
+                        }
+                        _ => return Err(self.unsupported("a computed hash key")),
+                    };
+                    parts.push(format!("({key} {})", self.expr(value)?));
+                }
+                format!("({})", parts.join(" "))
+            }
+            Expr::Index(container, key) => {
+                let head = if matches!(key.as_ref(), Expr::String(_)) {
+                    "hash-ref"
+                } else {
+                    "vector-ref"
+                };
+                format!("({head} {} {})", self.expr(container)?, self.expr(key)?)
+            }
+            Expr::OptionalIndex(..) => self.optional_chain(expr)?,
+            Expr::Slice(container, start, Some(end)) => {
+                format!(
+                    "(slice-range {} {} {})",
+                    self.expr(container)?,
+                    self.expr(start)?,
+                    self.expr(end)?
+                )
+            }
+            Expr::Slice(container, start, None) => {
+                format!(
+                    "(slice-from {} {})",
+                    self.expr(container)?,
+                    self.expr(start)?
+                )
+            }
+            Expr::Format(template, positional, named) => {
+                self.format(template, positional, named)?
+            }
+            Expr::IfElse(condition, then_expr, else_expr) => format!(
+                "(if {} {} {})",
+                self.expr(condition)?,
+                self.expr(then_expr)?,
+                self.expr(else_expr)?
+            ),
+            Expr::Closure(closure) => self.closure(closure)?,
+        })
+    }
+
+    fn binary(&self, op: BinaryOp, lhs: &Expr, rhs: &Expr) -> Result<String, TranspileError> {
+        let head = match op {
+            BinaryOp::Add if is_string_surface(lhs) || is_string_surface(rhs) => "string-append",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "remainder",
+            BinaryOp::Eq => "=",
+            BinaryOp::Ne => {
+                return Ok(format!("(not (= {} {}))", self.expr(lhs)?, self.expr(rhs)?));
+            }
+            BinaryOp::Lt => "<",
+            BinaryOp::Gt => ">",
+            BinaryOp::Le => "<=",
+            BinaryOp::Ge => ">=",
+            BinaryOp::And => "and",
+            BinaryOp::Or => "or",
+        };
+        // Left-nested sums and products fold into one form.
+        let mut operands = vec![rhs];
+        let mut current = lhs;
+        while let Expr::Binary(inner, inner_lhs, inner_rhs) = current
+            && *inner == op
+            && matches!(op, BinaryOp::Add | BinaryOp::Mul)
+            && (head == "string-append") == is_string_surface(current)
+        {
+            operands.push(inner_rhs);
+            current = inner_lhs;
+        }
+        operands.push(current);
+        operands.reverse();
+        let mut parts = vec![head.to_string()];
+        for operand in operands {
+            parts.push(self.expr(operand)?);
+        }
+        Ok(format!("({})", parts.join(" ")))
+    }
+
+    fn call(&self, callee: Callee, args: &[Expr]) -> Result<String, TranspileError> {
+        let builtin = match callee {
+            Callee::Function(index) => {
+                return self.form(&scheme_name(&self.globals.functions[&index]), args);
+            }
+            Callee::Local(var) => return self.form(&self.name(var), args),
+            Callee::Host(index) => {
+                let name = &self.globals.hosts[&index];
+                return if name == STDLIB_PRINT_NAME {
+                    self.form("display", args)
+                } else {
+                    self.form(name, args)
+                };
+            }
+            Callee::Builtin(builtin) => builtin,
+        };
+        match (builtin, args) {
+            (BuiltinFunction::Len, [_]) => self.form("length", args),
+            (BuiltinFunction::Keys, [_]) => self.form("keys", args),
+            (BuiltinFunction::TypeOf, [_]) => self.form("type", args),
+            (BuiltinFunction::Assert, [_]) => self.form("assert", args),
+            (BuiltinFunction::ToString, [value]) => {
+                Ok(format!("(string-append \"\" {})", self.expr(value)?))
+            }
+            _ => match builtin.source_path() {
+                Some(path) => self.form(path, args),
+                None => Err(self.unsupported(format!(
+                    "the '{}' builtin outside the syntax that produces it",
+                    builtin.name()
+                ))),
+            },
+        }
+    }
+
+    /// `a?.b?.c`, which Scheme writes as a single symbol.
+    fn optional_chain(&self, expr: &Expr) -> Result<String, TranspileError> {
+        match expr {
+            Expr::Var(var) => Ok(self.name(*var)),
+            Expr::OptionalIndex(container, key) => match key.as_ref() {
+                Expr::String(name) if is_symbol_key(name) => {
+                    Ok(format!("{}?.{name}", self.optional_chain(container)?))
+                }
+                _ => Err(self.unsupported("optional access by a computed key")),
+            },
+            _ => Err(self.unsupported("optional access on an expression")),
+        }
+    }
+
+    fn format(
+        &self,
+        template: &str,
+        positional: &[Expr],
+        named: &[(String, Expr)],
+    ) -> Result<String, TranspileError> {
+        let parts = ordered_placeholders(SourceFlavor::Scheme, template, positional, named)?;
+        let mut directives = String::new();
+        let mut args = Vec::new();
+        for part in parts {
+            match part {
+                TemplatePart::Literal(literal) => directives.push_str(&literal.replace('~', "~~")),
+                TemplatePart::Value(value, spec) => {
+                    let debug = FormatSpec {
+                        debug: true,
+                        ..FormatSpec::default()
+                    };
+                    if spec == FormatSpec::default() {
+                        directives.push_str("~a");
+                    } else if spec == debug {
+                        directives.push_str("~s");
+                    } else {
+                        return Err(self.unsupported("a format spec other than ~a and ~s"));
+                    }
+                    args.push(self.expr(value)?);
+                }
+            }
+        }
+        let mut parts = vec!["format".to_string(), quote(&directives)];
+        parts.extend(args);
+        Ok(format!("({})", parts.join(" ")))
+    }
+
+    fn closure(&self, closure: &Closure) -> Result<String, TranspileError> {
+        let params = closure
+            .params
+            .iter()
+            .map(|param| self.name(*param))
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(format!(
+            "(lambda ({params}) {})",
+            self.lambda_body(closure)?
+        ))
+    }
+
+    fn lambda_body(&self, closure: &Closure) -> Result<String, TranspileError> {
+        let mut body = closure.body.as_slice();
+        let mut tail = closure.tail.as_deref().cloned();
+        if tail.is_none()
+            && let Some((Stmt::Return(value), rest)) = body.split_last()
+        {
+            tail = Some(value.clone());
+            body = rest;
+        }
+        let tail = tail.unwrap_or(Expr::Null);
+        self.sequence(body, &tail)
+    }
+}
+
+/// Closes the form the last printed line left open.
+fn close(out: &mut String) {
+    out.pop();
+    out.push_str(")\n");
+}
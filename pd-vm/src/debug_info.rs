@@ -1,3 +1,5 @@
+use crate::compiler::source_map::SourceId;
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ArgInfo {
     pub name: String,
@@ -16,22 +18,69 @@ pub struct LocalInfo {
     pub index: u8,
 }
 
+/// Source of the program's entry file; imported modules follow it.
+pub const MAIN_SOURCE_ID: SourceId = 0;
+
+/// A file that contributed code to a program. Its position in
+/// [`DebugInfo::sources`] is the `SourceId` its line entries carry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DebugSource {
+    pub name: String,
+    pub text: Option<String>,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct LineInfo {
     pub offset: u32,
+    pub source_id: SourceId,
+    pub line: u32,
+    /// 1-based column where the statement starts, or 0 when unknown.
+    pub column: u32,
+}
+
+/// Code range `[start, end)` emitted for one inlined call of `function`,
+/// with the call site it was inlined at.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InlineFrame {
+    pub start: u32,
+    pub end: u32,
+    pub function: String,
+    pub call_source: SourceId,
+    pub call_line: u32,
+    /// Index in [`DebugInfo::inline_frames`] of the inlined call this one
+    /// was made from, if any.
+    pub parent: Option<u32>,
+    /// Parameters and locals of the inlined body, which stand in for
+    /// [`DebugInfo::locals`] while it runs.
+    pub locals: Vec<LocalInfo>,
+}
+
+/// One entry of a logical call stack: the function that is running, or
+/// `None` for top-level code, and the line it is at.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogicalFrame {
+    pub function: Option<String>,
+    pub source_id: SourceId,
     pub line: u32,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DebugInfo {
-    pub source: Option<String>,
+    pub sources: Vec<DebugSource>,
     pub lines: Vec<LineInfo>,
     pub functions: Vec<DebugFunction>,
     pub locals: Vec<LocalInfo>,
+    /// Inlined calls, each listed after the call it is nested in.
+    pub inline_frames: Vec<InlineFrame>,
 }
 
 impl DebugInfo {
     pub fn line_for_offset(&self, offset: usize) -> Option<u32> {
+        self.location_for_offset(offset).map(|info| info.line)
+    }
+
+    /// Line entry covering `offset`, in whichever file the code came from.
+    pub fn location_for_offset(&self, offset: usize) -> Option<&LineInfo> {
         let offset = offset as u32;
         if self.lines.is_empty() {
             return None;
@@ -49,38 +98,168 @@ impl DebugInfo {
         if lo == 0 {
             None
         } else {
-            Some(self.lines[lo - 1].line)
+            Some(&self.lines[lo - 1])
         }
     }
 
+    /// Line of the main source at `offset`; code from imported modules has none.
+    pub fn main_line_for_offset(&self, offset: usize) -> Option<u32> {
+        self.location_for_offset(offset)
+            .filter(|info| info.source_id == MAIN_SOURCE_ID)
+            .map(|info| info.line)
+    }
+
     pub fn offsets_for_line(&self, line: u32) -> Vec<u32> {
+        self.offsets_for_source_line(MAIN_SOURCE_ID, line)
+    }
+
+    pub fn offsets_for_source_line(&self, source_id: SourceId, line: u32) -> Vec<u32> {
         self.lines
             .iter()
-            .filter(|info| info.line == line)
+            .filter(|info| info.source_id == source_id && info.line == line)
             .map(|info| info.offset)
             .collect()
     }
 
+    pub fn main_source(&self) -> Option<&str> {
+        self.source_text(MAIN_SOURCE_ID)
+    }
+
+    pub fn source_text(&self, source_id: SourceId) -> Option<&str> {
+        self.sources.get(source_id as usize)?.text.as_deref()
+    }
+
+    pub fn source_name(&self, source_id: SourceId) -> Option<&str> {
+        self.sources
+            .get(source_id as usize)
+            .map(|source| source.name.as_str())
+    }
+
+    /// Source whose name is `name`, or ends with it as whole path components,
+    /// so `strings.rss` finds `<std>/strings.rss`.
+    pub fn find_source(&self, name: &str) -> Option<SourceId> {
+        let exact = self.sources.iter().position(|source| source.name == name);
+        exact
+            .or_else(|| {
+                let suffix = std::path::Path::new(name);
+                self.sources
+                    .iter()
+                    .position(|source| std::path::Path::new(&source.name).ends_with(suffix))
+            })
+            .map(|index| index as SourceId)
+    }
+
     pub fn source_line(&self, line: u32) -> Option<String> {
-        let source = self.source.as_ref()?;
+        self.source_line_in(MAIN_SOURCE_ID, line)
+    }
+
+    pub fn source_line_in(&self, source_id: SourceId, line: u32) -> Option<String> {
+        let source = self.source_text(source_id)?;
         let index = line.checked_sub(1)? as usize;
         source.lines().nth(index).map(|text| text.to_string())
     }
 
+    /// `line N` for the main source and `name:N` for imported modules.
+    pub fn describe_location(&self, info: &LineInfo) -> String {
+        self.describe_line(info.source_id, info.line)
+    }
+
+    pub fn describe_line(&self, source_id: SourceId, line: u32) -> String {
+        if source_id == MAIN_SOURCE_ID {
+            return format!("line {line}");
+        }
+        match self.source_name(source_id) {
+            Some(name) => format!("{name}:{line}"),
+            None => format!("source {source_id}:{line}"),
+        }
+    }
+
+    /// `name at file:line: text` for one logical stack entry, with `<main>`
+    /// standing in for top-level code.
+    pub fn describe_frame(&self, frame: &LogicalFrame) -> String {
+        let function = frame.function.as_deref().unwrap_or("<main>");
+        let place = self.describe_line(frame.source_id, frame.line);
+        match self.source_line_in(frame.source_id, frame.line) {
+            Some(text) => format!("{function} at {place}: {}", text.trim()),
+            None => format!("{function} at {place}"),
+        }
+    }
+
+    /// Calls that were inlined into the code at `offset`, innermost first.
+    pub fn inline_frames_at(&self, offset: usize) -> Vec<&InlineFrame> {
+        let offset = offset as u32;
+        let innermost = self
+            .inline_frames
+            .iter()
+            .rposition(|frame| frame.start <= offset && offset < frame.end);
+        let mut frames = Vec::new();
+        let mut next = innermost;
+        while let Some(index) = next {
+            let Some(frame) = self.inline_frames.get(index) else {
+                break;
+            };
+            frames.push(frame);
+            next = frame.parent.map(|parent| parent as usize);
+        }
+        frames
+    }
+
+    /// The call stack the source describes at `offset`, innermost first,
+    /// rebuilt from the inlined calls covering it.
+    pub fn logical_stack(&self, offset: usize) -> Vec<LogicalFrame> {
+        let Some(location) = self.location_for_offset(offset) else {
+            return Vec::new();
+        };
+        let inlined = self.inline_frames_at(offset);
+        let mut stack = Vec::with_capacity(inlined.len() + 1);
+        stack.push(LogicalFrame {
+            function: inlined.first().map(|frame| frame.function.clone()),
+            source_id: location.source_id,
+            line: location.line,
+        });
+        for (depth, frame) in inlined.iter().enumerate() {
+            stack.push(LogicalFrame {
+                function: inlined.get(depth + 1).map(|caller| caller.function.clone()),
+                source_id: frame.call_source,
+                line: frame.call_line,
+            });
+        }
+        stack
+    }
+
     pub fn local_index(&self, name: &str) -> Option<u8> {
         self.locals
             .iter()
             .find(|local| local.name == name)
             .map(|local| local.index)
     }
+
+    /// Locals in scope at `offset`: those of the innermost inlined call
+    /// covering it, or the top-level ones outside any.
+    pub fn locals_at(&self, offset: usize) -> &[LocalInfo] {
+        match self.inline_frames_at(offset).first() {
+            Some(frame) => &frame.locals,
+            None => &self.locals,
+        }
+    }
+
+    pub fn local_index_at(&self, offset: usize, name: &str) -> Option<u8> {
+        self.locals_at(offset)
+            .iter()
+            .find(|local| local.name == name)
+            .map(|local| local.index)
+    }
 }
 
 #[derive(Default)]
 pub struct DebugInfoBuilder {
-    source: Option<String>,
+    sources: Vec<DebugSource>,
+    current_source: SourceId,
     lines: Vec<LineInfo>,
     functions: Vec<DebugFunction>,
     locals: Vec<LocalInfo>,
+    inline_frames: Vec<InlineFrame>,
+    open_inline_frames: Vec<u32>,
     last_offset: Option<u32>,
 }
 
@@ -89,8 +268,31 @@ impl DebugInfoBuilder {
         Self::default()
     }
 
+    /// Sets the text of the main source, naming it `<source>` if it has no
+    /// name yet.
     pub fn set_source(&mut self, source: String) {
-        self.source = Some(source);
+        match self.sources.first_mut() {
+            Some(main) => main.text = Some(source),
+            None => self.sources.push(DebugSource {
+                name: "<source>".to_string(),
+                text: Some(source),
+            }),
+        }
+    }
+
+    /// Registers a file and returns the id lines marked while it is current
+    /// will carry. The first file added is the main source.
+    pub fn add_source(&mut self, name: String, text: Option<String>) -> SourceId {
+        self.sources.push(DebugSource { name, text });
+        (self.sources.len() - 1) as SourceId
+    }
+
+    pub fn set_current_source(&mut self, source_id: SourceId) {
+        self.current_source = source_id;
+    }
+
+    pub fn current_source(&self) -> SourceId {
+        self.current_source
     }
 
     pub fn add_function(&mut self, name: String, args: Vec<String>) {
@@ -116,27 +318,116 @@ impl DebugInfoBuilder {
         self.locals.push(LocalInfo { name, index });
     }
 
+    /// Names a local of the innermost open inlined call.
+    pub fn add_inline_local(&mut self, name: String, index: u8) {
+        let Some(frame) = self
+            .open_inline_frames
+            .last()
+            .and_then(|index| self.inline_frames.get_mut(*index as usize))
+        else {
+            return;
+        };
+        if frame
+            .locals
+            .iter()
+            .any(|local| local.name == name || local.index == index)
+        {
+            return;
+        }
+        frame.locals.push(LocalInfo { name, index });
+    }
+
+    pub fn last_line(&self) -> Option<u32> {
+        self.lines.last().map(|info| info.line)
+    }
+
+    /// Opens an inlined call of `function` whose body starts at `offset`,
+    /// called from `call_line` of the current source.
+    pub fn enter_inline(&mut self, function: String, call_line: u32, offset: u32) {
+        let index = self.inline_frames.len() as u32;
+        self.inline_frames.push(InlineFrame {
+            start: offset,
+            end: offset,
+            function,
+            call_source: self.current_source,
+            call_line,
+            parent: self.open_inline_frames.last().copied(),
+            locals: Vec::new(),
+        });
+        self.open_inline_frames.push(index);
+    }
+
+    /// Closes the innermost open inlined call at `offset`.
+    pub fn exit_inline(&mut self, offset: u32) {
+        if let Some(index) = self.open_inline_frames.pop()
+            && let Some(frame) = self.inline_frames.get_mut(index as usize)
+        {
+            frame.end = offset;
+        }
+    }
+
     pub fn mark_line(&mut self, offset: u32, line: u32) {
         if self.last_offset == Some(offset) {
+            // A mark in another source at the same offset, like the caller's
+            // line after an inlined body that ends without code, supersedes
+            // the entry that emitted nothing.
+            if let Some(last) = self.lines.last_mut()
+                && last.source_id != self.current_source
+            {
+                last.source_id = self.current_source;
+                last.line = line;
+            }
             return;
         }
-        self.lines.push(LineInfo { offset, line });
+        self.lines.push(LineInfo {
+            offset,
+            source_id: self.current_source,
+            line,
+            column: 0,
+        });
         self.last_offset = Some(offset);
     }
 
-    pub fn finish(self) -> Option<DebugInfo> {
-        if self.source.is_none()
+    pub fn finish(mut self) -> Option<DebugInfo> {
+        if self.sources.is_empty()
             && self.lines.is_empty()
             && self.functions.is_empty()
             && self.locals.is_empty()
         {
             return None;
         }
+        // Statements start at the first non-blank character of their line.
+        let columns = self
+            .sources
+            .iter()
+            .map(|source| {
+                source
+                    .text
+                    .as_deref()
+                    .map(|text| {
+                        text.lines()
+                            .map(|line| {
+                                let indent = line.len() - line.trim_start().len();
+                                line[..indent].chars().count() as u32 + 1
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        for info in &mut self.lines {
+            info.column = columns
+                .get(info.source_id as usize)
+                .and_then(|lines| lines.get(info.line.checked_sub(1)? as usize))
+                .copied()
+                .unwrap_or(0);
+        }
         Some(DebugInfo {
-            source: self.source,
+            sources: self.sources,
             lines: self.lines,
             functions: self.functions,
             locals: self.locals,
+            inline_frames: self.inline_frames,
         })
     }
 }
@@ -0,0 +1,1359 @@
+//! Debug Adapter Protocol transport for the [`Debugger`](super::Debugger).
+//!
+//! A [`DapSession`] serves one editor over TCP. Requests are read on a
+//! background thread and handled on the VM's thread, either between
+//! instructions (breakpoint changes, `pause`) or while the program is stopped
+//! (stack, variables, evaluation, stepping), so they drive the same
+//! breakpoints, step modes and locals as the text `pdb` protocol.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde_json::{Value as Json, json};
+
+use super::{
+    Breakpoint, BreakpointOptions, Breakpoints, StepMode, StopReason, evaluate_expression,
+    expression_flavor, format_local_value, named_locals,
+};
+use crate::compiler::source_map::SourceId;
+use crate::compiler::{SourceFlavor, TYPE_TAG_KEY};
+use crate::debug_info::{DebugInfo, MAIN_SOURCE_ID};
+use crate::vm::{Value, Vm};
+
+/// The VM runs one thread of execution.
+const THREAD_ID: i64 = 1;
+/// `variablesReference` of the locals scope; containers found while stopped
+/// are numbered after it.
+const LOCALS_REFERENCE: i64 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DapStartKind {
+    Launch,
+    Attach,
+}
+
+/// The `launch` or `attach` request an editor began its session with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DapStart {
+    pub kind: DapStartKind,
+    /// `program` from the configuration: the source file to run.
+    pub program: Option<String>,
+    /// `stopOnEntry` from the configuration, overriding the debugger's own.
+    pub stop_on_entry: Option<bool>,
+}
+
+/// Ends a [`DapSession`] from another thread, e.g. when an edge debug
+/// session is stopped while its debugger waits for an editor.
+#[derive(Clone)]
+pub struct DapHandle {
+    shared: Arc<DapShared>,
+    addr: SocketAddr,
+}
+
+pub struct DapSession {
+    listener: TcpListener,
+    shared: Arc<DapShared>,
+    client: Option<DapClient>,
+    start: Option<PendingStart>,
+    configured: bool,
+    finished: bool,
+    lines_start_at1: bool,
+    columns_start_at1: bool,
+    /// Offsets set for each imported module, replaced as a whole by
+    /// `setBreakpoints`; main-source breakpoints are line breakpoints.
+    module_breakpoints: HashMap<SourceId, Vec<usize>>,
+    step: Option<LineStep>,
+    pause_requested: bool,
+    resumed: bool,
+    /// Arrays and maps shown while stopped, by `variablesReference - 2`.
+    containers: Vec<Value>,
+}
+
+struct DapShared {
+    closed: AtomicBool,
+    stream: Mutex<Option<TcpStream>>,
+}
+
+struct DapClient {
+    writer: Arc<DapWriter>,
+    requests: Receiver<Incoming>,
+}
+
+struct DapWriter {
+    stream: Mutex<TcpStream>,
+    seq: AtomicI64,
+}
+
+enum Incoming {
+    Request(Json),
+    Closed,
+}
+
+struct PendingStart {
+    request: Json,
+    start: DapStart,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StepKind {
+    In,
+    Over,
+    Out,
+}
+
+/// A source-line step: the VM single-steps until it reaches a statement
+/// that is not on the line (or in the call) the step started from.
+#[derive(Clone, Copy, Debug)]
+struct LineStep {
+    kind: StepKind,
+    origin: Option<(SourceId, u32)>,
+    depth: usize,
+}
+
+/// What handling a request asks of the VM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DapAction {
+    Stay,
+    Resume,
+    Detach,
+}
+
+impl DapHandle {
+    /// Disconnects the editor, or stops waiting for one.
+    pub fn close(&self) {
+        self.shared.closed.store(true, Ordering::SeqCst);
+        let stream = self
+            .shared
+            .stream
+            .lock()
+            .expect("dap stream lock poisoned")
+            .take();
+        match stream {
+            Some(stream) => {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+            // Wakes a session blocked in `accept`, which then sees `closed`.
+            None => {
+                let _ = TcpStream::connect(self.addr);
+            }
+        }
+    }
+}
+
+impl DapSession {
+    pub fn bind(addr: &str) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            shared: Arc::new(DapShared {
+                closed: AtomicBool::new(false),
+                stream: Mutex::new(None),
+            }),
+            client: None,
+            start: None,
+            configured: false,
+            finished: false,
+            lines_start_at1: true,
+            columns_start_at1: true,
+            module_breakpoints: HashMap::new(),
+            step: None,
+            pause_requested: false,
+            resumed: false,
+            containers: Vec::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn handle(&self) -> io::Result<DapHandle> {
+        Ok(DapHandle {
+            shared: self.shared.clone(),
+            addr: self.local_addr()?,
+        })
+    }
+
+    /// Waits for an editor to connect and initialize, and returns the
+    /// `launch` or `attach` it sends next. The request is answered once the
+    /// program starts running under the debugger.
+    pub fn accept(&mut self) -> io::Result<DapStart> {
+        if let Some(pending) = self.start.as_ref() {
+            return Ok(pending.start.clone());
+        }
+        if self.client.is_none() {
+            self.connect()?;
+        }
+        loop {
+            let request = match self.next_request(true) {
+                Some(Incoming::Request(request)) => request,
+                Some(Incoming::Closed) | None => {
+                    self.drop_client();
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        "dap client disconnected before launch or attach",
+                    ));
+                }
+            };
+            let kind = match command(&request) {
+                "initialize" => {
+                    let args = &request["arguments"];
+                    self.lines_start_at1 = args["linesStartAt1"].as_bool().unwrap_or(true);
+                    self.columns_start_at1 = args["columnsStartAt1"].as_bool().unwrap_or(true);
+                    self.respond(&request, Ok(capabilities()));
+                    continue;
+                }
+                "launch" => DapStartKind::Launch,
+                "attach" => DapStartKind::Attach,
+                "disconnect" => {
+                    self.respond(&request, Ok(Json::Null));
+                    self.drop_client();
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        "dap client disconnected before launch or attach",
+                    ));
+                }
+                other => {
+                    self.respond(
+                        &request,
+                        Err(format!("'{other}' needs a launch or attach first")),
+                    );
+                    continue;
+                }
+            };
+            let args = &request["arguments"];
+            let start = DapStart {
+                kind,
+                program: args["program"].as_str().map(str::to_string),
+                stop_on_entry: args["stopOnEntry"].as_bool(),
+            };
+            self.start = Some(PendingStart {
+                request,
+                start: start.clone(),
+            });
+            return Ok(start);
+        }
+    }
+
+    /// Fails the pending `launch` or `attach`, e.g. because its program does
+    /// not compile, and ends the session.
+    pub fn reject(&mut self, message: &str) {
+        if let Some(pending) = self.start.take() {
+            self.respond(&pending.request, Err(message.to_string()));
+        }
+        self.event("terminated", Json::Null);
+        self.drop_client();
+        self.finished = true;
+    }
+
+    fn connect(&mut self) -> io::Result<()> {
+        let (stream, _) = self.listener.accept()?;
+        stream.set_nodelay(true)?;
+        if self.shared.closed.load(Ordering::SeqCst) {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "dap session closed",
+            ));
+        }
+        *self.shared.stream.lock().expect("dap stream lock poisoned") = Some(stream.try_clone()?);
+        let writer = Arc::new(DapWriter {
+            stream: Mutex::new(stream.try_clone()?),
+            seq: AtomicI64::new(0),
+        });
+        let requests = spawn_reader(stream, writer.clone());
+        self.client = Some(DapClient { writer, requests });
+        Ok(())
+    }
+
+    /// Handles requests that arrived while the program runs, first finishing
+    /// the launch or attach and its configuration if that has not happened.
+    /// Returns true when the editor went away.
+    pub(super) fn poll(
+        &mut self,
+        vm: &mut Vm,
+        breakpoints: &mut Breakpoints,
+        step: &mut StepMode,
+        flavor: Option<SourceFlavor>,
+    ) -> bool {
+        if self.finished {
+            return false;
+        }
+        if !self.configured {
+            if self.accept().is_err() {
+                return self.detach(breakpoints, step);
+            }
+            if self.configure(vm, breakpoints, step, flavor) {
+                return self.detach(breakpoints, step);
+            }
+        }
+        loop {
+            let request = match self.next_request(false) {
+                Some(Incoming::Request(request)) => request,
+                Some(Incoming::Closed) => return self.detach(breakpoints, step),
+                None => return false,
+            };
+            if self.handle_request(&request, vm, breakpoints, step, flavor, false)
+                == DapAction::Detach
+            {
+                return self.detach(breakpoints, step);
+            }
+        }
+    }
+
+    /// Answers the launch or attach, then applies configuration requests
+    /// until `configurationDone`. Returns true when the editor went away.
+    fn configure(
+        &mut self,
+        vm: &mut Vm,
+        breakpoints: &mut Breakpoints,
+        step: &mut StepMode,
+        flavor: Option<SourceFlavor>,
+    ) -> bool {
+        if let Some(pending) = self.start.as_ref() {
+            match pending.start.stop_on_entry {
+                Some(true) => *step = StepMode::Step,
+                Some(false) if *step == StepMode::Step => *step = StepMode::Running,
+                _ => {}
+            }
+            let request = pending.request.clone();
+            self.respond(&request, Ok(Json::Null));
+        }
+        self.event("initialized", Json::Null);
+        loop {
+            let request = match self.next_request(true) {
+                Some(Incoming::Request(request)) => request,
+                Some(Incoming::Closed) | None => return true,
+            };
+            if command(&request) == "configurationDone" {
+                self.respond(&request, Ok(Json::Null));
+                self.configured = true;
+                return false;
+            }
+            if self.handle_request(&request, vm, breakpoints, step, flavor, false)
+                == DapAction::Detach
+            {
+                return true;
+            }
+        }
+    }
+
+    /// Whether a single step has reached where the pending source-line step
+    /// should stop; instruction steps always have.
+    pub(super) fn step_finished(&self, vm: &Vm) -> bool {
+        let Some(line_step) = self.step else {
+            return true;
+        };
+        let Some(info) = vm.debug_info() else {
+            return true;
+        };
+        let ip = vm.ip();
+        let Some(location) = info.location_for_offset(ip) else {
+            return true;
+        };
+        if location.offset as usize != ip {
+            return false;
+        }
+        let here = Some((location.source_id, location.line));
+        let depth = logical_depth(vm, info);
+        match line_step.kind {
+            StepKind::In => here != line_step.origin || depth != line_step.depth,
+            StepKind::Over => {
+                depth < line_step.depth || (depth == line_step.depth && here != line_step.origin)
+            }
+            StepKind::Out => depth < line_step.depth,
+        }
+    }
+
+    /// Reports a stop and serves the editor until it resumes the program.
+    /// Returns true when the editor went away.
+    pub(super) fn stopped(
+        &mut self,
+        vm: &mut Vm,
+        reason: StopReason,
+        breakpoints: &mut Breakpoints,
+        step: &mut StepMode,
+        flavor: Option<SourceFlavor>,
+    ) -> bool {
+        if self.client.is_none() {
+            return false;
+        }
+        let reason = if std::mem::take(&mut self.pause_requested) {
+            "pause"
+        } else {
+            match reason {
+                StopReason::Breakpoint => "breakpoint",
+                StopReason::Step if !self.resumed => "entry",
+                StopReason::Step => "step",
+            }
+        };
+        self.step = None;
+        self.containers.clear();
+        self.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        );
+        loop {
+            let request = match self.next_request(true) {
+                Some(Incoming::Request(request)) => request,
+                Some(Incoming::Closed) | None => return self.detach(breakpoints, step),
+            };
+            match self.handle_request(&request, vm, breakpoints, step, flavor, true) {
+                DapAction::Stay => {}
+                DapAction::Resume => {
+                    self.resumed = true;
+                    return false;
+                }
+                DapAction::Detach => return self.detach(breakpoints, step),
+            }
+        }
+    }
+
+    pub(super) fn output(&self, message: &str) {
+        self.event(
+            "output",
+            json!({ "category": "console", "output": format!("{message}\n") }),
+        );
+    }
+
+    /// Tells the editor the program finished, with `error` as its failure,
+    /// and closes the connection.
+    pub(super) fn end(&mut self, error: Option<&str>) {
+        if self.client.is_none() {
+            return;
+        }
+        if let Some(message) = error {
+            self.event(
+                "output",
+                json!({ "category": "stderr", "output": format!("{message}\n") }),
+            );
+        }
+        self.event("exited", json!({ "exitCode": i32::from(error.is_some()) }));
+        self.event("terminated", Json::Null);
+        self.drop_client();
+        self.finished = true;
+    }
+
+    fn handle_request(
+        &mut self,
+        request: &Json,
+        vm: &mut Vm,
+        breakpoints: &mut Breakpoints,
+        step: &mut StepMode,
+        flavor: Option<SourceFlavor>,
+        stopped: bool,
+    ) -> DapAction {
+        let args = &request["arguments"];
+        let result = match command(request) {
+            "initialize" => Ok(capabilities()),
+            "configurationDone" => Ok(Json::Null),
+            "threads" => Ok(threads()),
+            "setBreakpoints" => Ok(self.set_breakpoints(vm, args, breakpoints)),
+            "setExceptionBreakpoints" => Ok(json!({ "breakpoints": [] })),
+            "source" => self.source(vm, args),
+            "pause" => {
+                if !stopped {
+                    *step = StepMode::Step;
+                    self.step = None;
+                    self.pause_requested = true;
+                }
+                Ok(Json::Null)
+            }
+            "disconnect" => {
+                self.respond(request, Ok(Json::Null));
+                return DapAction::Detach;
+            }
+            "continue" => {
+                if stopped {
+                    *step = StepMode::Running;
+                }
+                self.respond(request, Ok(json!({ "allThreadsContinued": true })));
+                return if stopped {
+                    DapAction::Resume
+                } else {
+                    DapAction::Stay
+                };
+            }
+            "next" | "stepIn" | "stepOut" if stopped => {
+                self.begin_step(vm, command(request), args, step);
+                self.respond(request, Ok(Json::Null));
+                return DapAction::Resume;
+            }
+            "next" | "stepIn" | "stepOut" | "stackTrace" | "scopes" | "variables" | "evaluate"
+            | "setVariable"
+                if !stopped =>
+            {
+                Err("the program is running".to_string())
+            }
+            "stackTrace" => Ok(self.stack_trace(vm, args)),
+            "scopes" => Ok(json!({
+                "scopes": [{
+                    "name": "Locals",
+                    "presentationHint": "locals",
+                    "variablesReference": LOCALS_REFERENCE,
+                    "expensive": false,
+                }]
+            })),
+            "variables" => Ok(self.variables(vm, args)),
+            "evaluate" => self.evaluate(vm, args, flavor),
+            "setVariable" => self.set_variable(vm, args, flavor),
+            other => Err(format!("unsupported request '{other}'")),
+        };
+        self.respond(request, result);
+        DapAction::Stay
+    }
+
+    fn begin_step(&mut self, vm: &Vm, command: &str, args: &Json, step: &mut StepMode) {
+        if args["granularity"].as_str() == Some("instruction") {
+            *step = match command {
+                "next" => StepMode::StepOver {
+                    depth: vm.call_depth(),
+                    ip: vm.ip(),
+                },
+                "stepOut" => StepMode::StepOut {
+                    depth: vm.call_depth(),
+                },
+                _ => StepMode::Step,
+            };
+            return;
+        }
+        *step = StepMode::Step;
+        let Some(info) = vm.debug_info() else {
+            return;
+        };
+        self.step = Some(LineStep {
+            kind: match command {
+                "next" => StepKind::Over,
+                "stepOut" => StepKind::Out,
+                _ => StepKind::In,
+            },
+            origin: info
+                .location_for_offset(vm.ip())
+                .map(|location| (location.source_id, location.line)),
+            depth: logical_depth(vm, info),
+        });
+    }
+
+    /// Replaces the breakpoints of one source. Lines of the main source
+    /// become line breakpoints; lines of imported modules become breakpoints
+    /// at the offsets where their statements start.
+    fn set_breakpoints(&mut self, vm: &Vm, args: &Json, breakpoints: &mut Breakpoints) -> Json {
+        let requested = args["breakpoints"].as_array().cloned().unwrap_or_default();
+        let unverified = |message: &str| {
+            let items = requested
+                .iter()
+                .map(|item| json!({ "verified": false, "line": item["line"], "message": message }))
+                .collect::<Vec<_>>();
+            json!({ "breakpoints": items })
+        };
+        let Some(info) = vm.debug_info() else {
+            return unverified("no debug info");
+        };
+        let Some(source_id) = find_source(info, &args["source"]) else {
+            return unverified("source is not part of this program");
+        };
+        if source_id == MAIN_SOURCE_ID {
+            breakpoints.lines.clear();
+        } else if let Some(offsets) = self.module_breakpoints.remove(&source_id) {
+            for offset in offsets {
+                breakpoints.offsets.remove(&offset);
+            }
+        }
+        let mut items = Vec::with_capacity(requested.len());
+        for item in &requested {
+            let client_line = item["line"].as_i64().unwrap_or(0);
+            let line = self.source_line(client_line);
+            let options = match breakpoint_options(item) {
+                Ok(options) => options,
+                Err(message) => {
+                    items.push(
+                        json!({ "verified": false, "line": client_line, "message": message }),
+                    );
+                    continue;
+                }
+            };
+            let offsets = line
+                .map(|line| info.offsets_for_source_line(source_id, line))
+                .unwrap_or_default();
+            let Some(line) = line.filter(|_| !offsets.is_empty()) else {
+                items.push(
+                    json!({ "verified": false, "line": client_line, "message": "no code on this line" }),
+                );
+                continue;
+            };
+            if source_id == MAIN_SOURCE_ID {
+                breakpoints.lines.insert(line, Breakpoint::new(options));
+            } else {
+                let owned = self.module_breakpoints.entry(source_id).or_default();
+                let breakpoint = Breakpoint::new(options);
+                for offset in offsets {
+                    let offset = offset as usize;
+                    breakpoints.offsets.insert(offset, breakpoint.clone());
+                    owned.push(offset);
+                }
+            }
+            items.push(json!({ "verified": true, "line": client_line }));
+        }
+        json!({ "breakpoints": items })
+    }
+
+    fn stack_trace(&self, vm: &Vm, args: &Json) -> Json {
+        let Some(info) = vm.debug_info() else {
+            return json!({ "stackFrames": [], "totalFrames": 0 });
+        };
+        let stack = info.logical_stack(vm.ip());
+        let column = info
+            .location_for_offset(vm.ip())
+            .map(|location| location.column.max(1))
+            .unwrap_or(1);
+        let start = args["startFrame"].as_u64().unwrap_or(0) as usize;
+        let levels = match args["levels"].as_u64().unwrap_or(0) as usize {
+            0 => stack.len(),
+            levels => levels,
+        };
+        let frames = stack
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(levels)
+            .map(|(depth, frame)| {
+                json!({
+                    "id": depth,
+                    "name": frame.function.as_deref().unwrap_or("<main>"),
+                    "source": source_json(info, frame.source_id),
+                    "line": self.client_line(frame.line),
+                    "column": self.client_column(if depth == 0 { column } else { 1 }),
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({ "stackFrames": frames, "totalFrames": stack.len() })
+    }
+
+    fn variables(&mut self, vm: &Vm, args: &Json) -> Json {
+        let reference = args["variablesReference"].as_i64().unwrap_or(0);
+        let entries = if reference == LOCALS_REFERENCE {
+            named_locals(vm)
+        } else {
+            usize::try_from(reference - LOCALS_REFERENCE - 1)
+                .ok()
+                .and_then(|index| self.containers.get(index))
+                .map(children)
+                .unwrap_or_default()
+        };
+        let variables = entries
+            .into_iter()
+            .map(|(name, value)| {
+                let mut variable = self.describe_value(&value);
+                variable["name"] = json!(name);
+                variable
+            })
+            .collect::<Vec<_>>();
+        json!({ "variables": variables })
+    }
+
+    fn evaluate(
+        &mut self,
+        vm: &Vm,
+        args: &Json,
+        flavor: Option<SourceFlavor>,
+    ) -> Result<Json, String> {
+        let expression = args["expression"].as_str().unwrap_or_default();
+        let flavor = flavor.unwrap_or_else(|| expression_flavor(vm));
+        let value = evaluate_expression(vm, expression, flavor)?;
+        let mut body = self.describe_value(&value);
+        if let Some(fields) = body.as_object_mut()
+            && let Some(result) = fields.remove("value")
+        {
+            fields.insert("result".to_string(), result);
+        }
+        Ok(body)
+    }
+
+    fn set_variable(
+        &mut self,
+        vm: &mut Vm,
+        args: &Json,
+        flavor: Option<SourceFlavor>,
+    ) -> Result<Json, String> {
+        if args["variablesReference"].as_i64() != Some(LOCALS_REFERENCE) {
+            return Err("only locals can be changed".to_string());
+        }
+        let name = args["name"].as_str().unwrap_or_default();
+        let index = vm
+            .debug_info()
+            .and_then(|info| info.local_index_at(vm.ip(), name))
+            .ok_or_else(|| format!("unknown local '{name}'"))?;
+        let flavor = flavor.unwrap_or_else(|| expression_flavor(vm));
+        let value = evaluate_expression(vm, args["value"].as_str().unwrap_or_default(), flavor)?;
+        let body = self.describe_value(&value);
+        vm.set_local(index, value).map_err(|err| err.to_string())?;
+        Ok(body)
+    }
+
+    fn source(&self, vm: &Vm, args: &Json) -> Result<Json, String> {
+        let reference = args["sourceReference"]
+            .as_i64()
+            .or_else(|| args["source"]["sourceReference"].as_i64())
+            .unwrap_or(0);
+        vm.debug_info()
+            .and_then(|info| {
+                let source_id = SourceId::try_from(reference - 1).ok()?;
+                info.source_text(source_id)
+            })
+            .map(|text| json!({ "content": text }))
+            .ok_or_else(|| "source is not available".to_string())
+    }
+
+    /// `value`, `type` and `variablesReference` of a variable or evaluation
+    /// result; non-empty arrays and maps can be expanded.
+    fn describe_value(&mut self, value: &Value) -> Json {
+        let expandable = match value {
+            Value::Array(items) => !items.is_empty(),
+            Value::Map(entries) => !entries.is_empty(),
+            _ => false,
+        };
+        let reference = if expandable {
+            self.containers.push(value.clone());
+            LOCALS_REFERENCE + self.containers.len() as i64
+        } else {
+            0
+        };
+        json!({
+            "value": display_value(value),
+            "type": type_name(value),
+            "variablesReference": reference,
+        })
+    }
+
+    /// 1-based line of a line the client sent.
+    fn source_line(&self, line: i64) -> Option<u32> {
+        let line = if self.lines_start_at1 { line } else { line + 1 };
+        u32::try_from(line).ok().filter(|line| *line > 0)
+    }
+
+    fn client_line(&self, line: u32) -> u32 {
+        if self.lines_start_at1 {
+            line
+        } else {
+            line.saturating_sub(1)
+        }
+    }
+
+    fn client_column(&self, column: u32) -> u32 {
+        if self.columns_start_at1 {
+            column
+        } else {
+            column.saturating_sub(1)
+        }
+    }
+
+    fn next_request(&self, block: bool) -> Option<Incoming> {
+        let client = self.client.as_ref()?;
+        if block {
+            return Some(client.requests.recv().unwrap_or(Incoming::Closed));
+        }
+        match client.requests.try_recv() {
+            Ok(incoming) => Some(incoming),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Incoming::Closed),
+        }
+    }
+
+    fn respond(&self, request: &Json, result: Result<Json, String>) {
+        if let Some(client) = self.client.as_ref() {
+            client.writer.respond(request, result);
+        }
+    }
+
+    fn event(&self, event: &str, body: Json) {
+        if let Some(client) = self.client.as_ref() {
+            client.writer.event(event, body);
+        }
+    }
+
+    /// Forgets the editor: its breakpoints are removed and the program runs
+    /// on without stopping. Always returns true.
+    fn detach(&mut self, breakpoints: &mut Breakpoints, step: &mut StepMode) -> bool {
+        breakpoints.lines.clear();
+        breakpoints.offsets.clear();
+        self.module_breakpoints.clear();
+        *step = StepMode::Running;
+        self.step = None;
+        self.drop_client();
+        self.finished = true;
+        true
+    }
+
+    fn drop_client(&mut self) {
+        if let Some(stream) = self
+            .shared
+            .stream
+            .lock()
+            .expect("dap stream lock poisoned")
+            .take()
+        {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        self.client = None;
+    }
+}
+
+impl DapWriter {
+    fn send(&self, mut message: Json) {
+        message["seq"] = json!(self.seq.fetch_add(1, Ordering::SeqCst) + 1);
+        let mut stream = self.stream.lock().expect("dap writer lock poisoned");
+        // A failed write means the client is gone, which the reader reports.
+        let _ = write_message(&mut *stream, &message);
+    }
+
+    fn respond(&self, request: &Json, result: Result<Json, String>) {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Json::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.send(response);
+    }
+
+    fn event(&self, event: &str, body: Json) {
+        let mut message = json!({ "type": "event", "event": event });
+        if !body.is_null() {
+            message["body"] = body;
+        }
+        self.send(message);
+    }
+}
+
+/// Reads requests on a background thread. `threads` is answered there, so
+/// an editor polling it is not kept waiting while the program runs.
+fn spawn_reader(stream: TcpStream, writer: Arc<DapWriter>) -> Receiver<Incoming> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut reader = BufReader::new(stream);
+        loop {
+            let message = match read_message(&mut reader) {
+                Ok(Some(message)) => message,
+                Ok(None) | Err(_) => {
+                    let _ = sender.send(Incoming::Closed);
+                    return;
+                }
+            };
+            if message["type"] != "request" {
+                continue;
+            }
+            if command(&message) == "threads" {
+                writer.respond(&message, Ok(threads()));
+                continue;
+            }
+            if sender.send(Incoming::Request(message)).is_err() {
+                return;
+            }
+        }
+    });
+    receiver
+}
+
+/// Reads one `Content-Length`-framed message; `None` at end of stream.
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.trim().eq_ignore_ascii_case("content-length")
+        {
+            length = Some(value.trim().parse::<usize>().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "invalid Content-Length")
+            })?);
+        }
+    }
+    let mut body = vec![0; length.unwrap_or(0)];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn write_message(writer: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    // One write per message, so small frames are not split across packets.
+    let frame = format!("Content-Length: {}\r\n\r\n{body}", body.len());
+    writer.write_all(frame.as_bytes())?;
+    writer.flush()
+}
+
+fn command(request: &Json) -> &str {
+    request["command"].as_str().unwrap_or_default()
+}
+
+fn capabilities() -> Json {
+    json!({
+        "supportsConfigurationDoneRequest": true,
+        "supportsConditionalBreakpoints": true,
+        "supportsHitConditionalBreakpoints": true,
+        "supportsLogPoints": true,
+        "supportsEvaluateForHovers": true,
+        "supportsSetVariable": true,
+        "supportsSteppingGranularity": true,
+    })
+}
+
+fn threads() -> Json {
+    json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })
+}
+
+/// Options of a DAP source breakpoint. Log messages already use the
+/// debugger's `{expr}` syntax; a hit condition must be a count, optionally
+/// written `>= n`.
+fn breakpoint_options(item: &Json) -> Result<BreakpointOptions, String> {
+    let text = |key: &str| {
+        item[key]
+            .as_str()
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+    let hit_count = match text("hitCondition") {
+        Some(condition) => Some(
+            condition
+                .trim_start_matches(">=")
+                .trim()
+                .parse::<u32>()
+                .ok()
+                .filter(|count| *count > 0)
+                .ok_or_else(|| format!("hit condition '{condition}' must be a positive count"))?,
+        ),
+        None => None,
+    };
+    Ok(BreakpointOptions {
+        condition: text("condition"),
+        hit_count,
+        log_message: text("logMessage"),
+    })
+}
+
+/// Logical call depth: VM calls plus the inlined calls covering the ip.
+fn logical_depth(vm: &Vm, info: &DebugInfo) -> usize {
+    vm.call_depth() + info.logical_stack(vm.ip()).len()
+}
+
+/// Debug-info source a DAP `Source` names, by reference, path or file name.
+fn find_source(info: &DebugInfo, source: &Json) -> Option<SourceId> {
+    if let Some(reference) = source["sourceReference"]
+        .as_i64()
+        .filter(|value| *value > 0)
+    {
+        let source_id = SourceId::try_from(reference - 1).ok()?;
+        return info.source_name(source_id).map(|_| source_id);
+    }
+    if let Some(path) = source["path"].as_str() {
+        if let Ok(wanted) = Path::new(path).canonicalize() {
+            let found = (0..info.sources.len() as SourceId)
+                .find(|source_id| source_path(info, *source_id).as_ref() == Some(&wanted));
+            if found.is_some() {
+                return found;
+            }
+        }
+        if let Some(found) = info.find_source(path) {
+            return Some(found);
+        }
+    }
+    let name = source["name"].as_str().or_else(|| {
+        source["path"]
+            .as_str()
+            .and_then(|path| Path::new(path).file_name())
+            .and_then(|name| name.to_str())
+    })?;
+    info.find_source(name)
+}
+
+/// A source as DAP reports it: by path when the file is on disk, otherwise
+/// by a reference the editor fetches the text through.
+fn source_json(info: &DebugInfo, source_id: SourceId) -> Json {
+    let name = info.source_name(source_id).unwrap_or("<unknown>");
+    let short_name = Path::new(name)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(name);
+    if let Some(path) = source_path(info, source_id) {
+        return json!({ "name": short_name, "path": path.display().to_string() });
+    }
+    if info.source_text(source_id).is_some() {
+        return json!({ "name": short_name, "sourceReference": source_id + 1 });
+    }
+    json!({ "name": short_name })
+}
+
+/// Where a source lives on disk; imported modules are also looked for next
+/// to the main source.
+fn source_path(info: &DebugInfo, source_id: SourceId) -> Option<PathBuf> {
+    let path = Path::new(info.source_name(source_id)?);
+    if path.is_file() {
+        return path.canonicalize().ok();
+    }
+    if source_id == MAIN_SOURCE_ID || path.is_absolute() {
+        return None;
+    }
+    let main = Path::new(info.source_name(MAIN_SOURCE_ID)?);
+    let candidate = main.parent()?.join(path);
+    if candidate.is_file() {
+        candidate.canonicalize().ok()
+    } else {
+        None
+    }
+}
+
+fn children(value: &Value) -> Vec<(String, Value)> {
+    match value {
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| (format!("[{index}]"), item.clone()))
+            .collect(),
+        Value::Map(entries) => {
+            let fields = match entries.first() {
+                Some((Value::String(key), _)) if key == TYPE_TAG_KEY => &entries[1..],
+                _ => &entries[..],
+            };
+            fields
+                .iter()
+                .map(|(key, item)| {
+                    let name = match key {
+                        Value::String(text) => text.clone(),
+                        other => display_value(other),
+                    };
+                    (name, item.clone())
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Int(value) => value.to_string(),
+        Value::Float(value) => value.to_string(),
+        Value::Bool(value) => value.to_string(),
+        Value::String(text) => format!("{text:?}"),
+        other => format_local_value(other),
+    }
+}
+
+fn type_name(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Int(_) => "int".to_string(),
+        Value::Float(_) => "float".to_string(),
+        Value::Bool(_) => "bool".to_string(),
+        Value::String(_) => "string".to_string(),
+        Value::Array(_) => "array".to_string(),
+        Value::Map(entries) => match entries.first() {
+            Some((Value::String(key), Value::String(tag))) if key == TYPE_TAG_KEY => tag.clone(),
+            _ => "map".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::debugger::Debugger;
+    use crate::vm::VmStatus;
+
+    const COUNTING_LOOP: &str =
+        "let total = 0;\nlet i = 0;\nwhile i < 5 {\n    total = total + i;\n    i = i + 1;\n}\n";
+
+    struct TestClient {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+        seq: i64,
+        events: VecDeque<Json>,
+    }
+
+    impl TestClient {
+        fn connect(addr: SocketAddr) -> Self {
+            let writer = TcpStream::connect(addr).expect("client should connect");
+            writer
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .expect("read timeout should apply");
+            let reader = BufReader::new(writer.try_clone().expect("stream should clone"));
+            Self {
+                reader,
+                writer,
+                seq: 0,
+                events: VecDeque::new(),
+            }
+        }
+
+        fn send(&mut self, command: &str, arguments: Json) -> i64 {
+            self.seq += 1;
+            let request = json!({
+                "seq": self.seq,
+                "type": "request",
+                "command": command,
+                "arguments": arguments,
+            });
+            write_message(&mut self.writer, &request).expect("request should send");
+            self.seq
+        }
+
+        fn read(&mut self) -> Json {
+            read_message(&mut self.reader)
+                .expect("message should read")
+                .expect("adapter should not hang up")
+        }
+
+        fn request(&mut self, command: &str, arguments: Json) -> Json {
+            let seq = self.send(command, arguments);
+            loop {
+                let message = self.read();
+                if message["type"] == "response" && message["request_seq"] == seq {
+                    return message;
+                }
+                self.events.push_back(message);
+            }
+        }
+
+        fn event(&mut self, name: &str) -> Json {
+            if let Some(position) = self.events.iter().position(|event| event["event"] == name) {
+                return self.events.remove(position).expect("event should exist");
+            }
+            loop {
+                let message = self.read();
+                if message["event"] == name {
+                    return message;
+                }
+                self.events.push_back(message);
+            }
+        }
+    }
+
+    fn write_counting_loop() -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "pd-vm-dap-{}-{:?}.rss",
+            std::process::id(),
+            thread::current().id()
+        ));
+        std::fs::write(&path, COUNTING_LOOP).expect("source should write");
+        path.canonicalize().expect("source path should resolve")
+    }
+
+    fn run_counting_loop(path: PathBuf, session: DapSession) -> (VmStatus, Vec<Value>, bool) {
+        let compiled = crate::compiler::compile_source_file(&path).expect("source should compile");
+        let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+        let mut debugger = Debugger::with_dap(session);
+        let status = vm.run_with_debugger(&mut debugger).expect("vm should run");
+        let detached = debugger.take_detach_event();
+        debugger.end_session(None);
+        (status, vm.locals().to_vec(), detached)
+    }
+
+    #[test]
+    fn messages_use_content_length_framing() {
+        let message = json!({ "seq": 1, "type": "event", "event": "initialized" });
+        let mut bytes = Vec::new();
+        write_message(&mut bytes, &message).expect("message should write");
+        assert!(bytes.starts_with(b"Content-Length: "));
+
+        let mut reader = io::Cursor::new(bytes);
+        assert_eq!(
+            read_message(&mut reader).expect("message should read"),
+            Some(message)
+        );
+        assert_eq!(read_message(&mut reader).expect("eof should read"), None);
+    }
+
+    #[test]
+    fn source_breakpoints_map_to_breakpoint_options() {
+        let options = breakpoint_options(&json!({
+            "line": 4,
+            "condition": "i > 1",
+            "hitCondition": ">= 3",
+            "logMessage": "i={i}",
+        }))
+        .expect("options should parse");
+        assert_eq!(
+            options,
+            BreakpointOptions {
+                condition: Some("i > 1".to_string()),
+                hit_count: Some(3),
+                log_message: Some("i={i}".to_string()),
+            }
+        );
+        assert!(breakpoint_options(&json!({ "line": 4, "hitCondition": "% 2" })).is_err());
+        assert!(breakpoint_options(&json!({ "line": 4, "hitCondition": "0" })).is_err());
+    }
+
+    #[test]
+    fn launch_stops_at_breakpoints_and_edits_locals() {
+        let path = write_counting_loop();
+        let session = DapSession::bind("127.0.0.1:0").expect("session should bind");
+        let addr = session
+            .local_addr()
+            .expect("session should have an address");
+        let runner = {
+            let path = path.clone();
+            thread::spawn(move || run_counting_loop(path, session))
+        };
+
+        let mut client = TestClient::connect(addr);
+        let initialized = client.request("initialize", json!({ "adapterID": "pd-vm" }));
+        assert_eq!(initialized["body"]["supportsConditionalBreakpoints"], true);
+        let launch = client.send("launch", json!({ "program": path, "stopOnEntry": false }));
+        client.event("initialized");
+        let set = client.request(
+            "setBreakpoints",
+            json!({
+                "source": { "path": path },
+                "breakpoints": [{ "line": 4, "condition": "i == 2" }, { "line": 40 }],
+            }),
+        );
+        assert_eq!(set["body"]["breakpoints"][0]["verified"], true);
+        assert_eq!(set["body"]["breakpoints"][1]["verified"], false);
+        client.request("configurationDone", Json::Null);
+        let launched = client
+            .events
+            .iter()
+            .find(|message| message["request_seq"] == launch)
+            .cloned()
+            .unwrap_or_else(|| client.read());
+        assert_eq!(launched["success"], true);
+
+        let stopped = client.event("stopped");
+        assert_eq!(stopped["body"]["reason"], "breakpoint");
+        let trace = client.request("stackTrace", json!({ "threadId": THREAD_ID }));
+        let frame = &trace["body"]["stackFrames"][0];
+        assert_eq!(frame["line"], 4);
+        assert_eq!(frame["source"]["path"], path.display().to_string());
+        let variables = client.request("variables", json!({ "variablesReference": 1 }));
+        assert_eq!(
+            variables["body"]["variables"],
+            json!([
+                { "name": "total", "value": "1", "type": "int", "variablesReference": 0 },
+                { "name": "i", "value": "2", "type": "int", "variablesReference": 0 },
+            ])
+        );
+        let changed = client.request(
+            "setVariable",
+            json!({ "variablesReference": 1, "name": "total", "value": "total + 100" }),
+        );
+        assert_eq!(changed["body"]["value"], "101");
+        let evaluated = client.request("evaluate", json!({ "expression": "[total, i]" }));
+        assert_eq!(evaluated["body"]["type"], "array");
+        let reference = evaluated["body"]["variablesReference"].clone();
+        let items = client.request("variables", json!({ "variablesReference": reference }));
+        assert_eq!(items["body"]["variables"][1]["name"], "[1]");
+        assert_eq!(items["body"]["variables"][1]["value"], "2");
+
+        client.request("next", json!({ "threadId": THREAD_ID }));
+        assert_eq!(client.event("stopped")["body"]["reason"], "step");
+        let trace = client.request("stackTrace", json!({ "threadId": THREAD_ID }));
+        assert_eq!(trace["body"]["stackFrames"][0]["line"], 5);
+
+        client.request(
+            "setBreakpoints",
+            json!({ "source": { "path": path }, "breakpoints": [] }),
+        );
+        client.request("continue", json!({ "threadId": THREAD_ID }));
+        assert_eq!(client.event("exited")["body"]["exitCode"], 0);
+        client.event("terminated");
+
+        let (status, locals, detached) = runner.join().expect("vm thread should finish");
+        assert_eq!(status, VmStatus::Halted);
+        assert_eq!(locals[0], Value::Int(110));
+        assert!(!detached);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn attach_reports_logpoints_and_detaches_on_disconnect() {
+        let path = write_counting_loop();
+        let session = DapSession::bind("127.0.0.1:0").expect("session should bind");
+        let addr = session
+            .local_addr()
+            .expect("session should have an address");
+        let runner = {
+            let path = path.clone();
+            thread::spawn(move || run_counting_loop(path, session))
+        };
+
+        let mut client = TestClient::connect(addr);
+        client.request("initialize", json!({ "adapterID": "pd-vm" }));
+        client.send("attach", json!({ "stopOnEntry": true }));
+        client.event("initialized");
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .expect("file name")
+            .to_string();
+        client.request(
+            "setBreakpoints",
+            json!({
+                "source": { "name": file_name },
+                "breakpoints": [
+                    { "line": 4, "condition": "i == 4" },
+                    { "line": 5, "logMessage": "i={i}", "hitCondition": "4" },
+                ],
+            }),
+        );
+        client.request("configurationDone", Json::Null);
+        assert_eq!(client.event("stopped")["body"]["reason"], "entry");
+        client.request("continue", json!({ "threadId": THREAD_ID }));
+        let output = client.event("output");
+        assert_eq!(output["body"]["output"], "i=3\n");
+        assert_eq!(client.event("stopped")["body"]["reason"], "breakpoint");
+        client.request("disconnect", Json::Null);
+
+        let (status, locals, detached) = runner.join().expect("vm thread should finish");
+        assert_eq!(status, VmStatus::Halted);
+        assert_eq!(locals[0], Value::Int(10));
+        assert!(detached);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn closing_a_handle_stops_waiting_for_an_editor() {
+        let session = DapSession::bind("127.0.0.1:0").expect("session should bind");
+        let handle = session.handle().expect("session should have a handle");
+        let runner = thread::spawn(move || {
+            let mut vm = Vm::with_locals(
+                crate::compiler::compile_source(COUNTING_LOOP)
+                    .expect("source should compile")
+                    .program,
+                2,
+            );
+            let mut debugger = Debugger::with_dap(session);
+            let status = vm.run_with_debugger(&mut debugger).expect("vm should run");
+            (status, debugger.take_detach_event())
+        });
+        thread::sleep(Duration::from_millis(50));
+        handle.close();
+        let (status, detached) = runner.join().expect("vm thread should finish");
+        assert_eq!(status, VmStatus::Halted);
+        assert!(detached);
+    }
+}
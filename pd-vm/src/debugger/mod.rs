@@ -0,0 +1,3635 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, BufRead, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::compiler::transpile::transpile_bindings;
+use crate::compiler::{SourceFlavor, TYPE_TAG_KEY, compile_source_with_flavor};
+use crate::debug_info::{DebugInfo, MAIN_SOURCE_ID};
+use crate::vm::{Program, Value, Vm, VmStatus};
+
+mod dap;
+
+pub use dap::{DapHandle, DapSession, DapStart, DapStartKind};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepMode {
+    Running,
+    Step,
+    StepOver { depth: usize, ip: usize },
+    StepOut { depth: usize },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VmRecordingFrame {
+    pub ip: usize,
+    pub call_depth: usize,
+    pub stack: Vec<Value>,
+    pub locals: Vec<Value>,
+}
+
+#[derive(Clone, Debug)]
+pub struct VmRecording {
+    pub program: Program,
+    pub frames: Vec<VmRecordingFrame>,
+    pub terminal_status: Option<VmStatus>,
+    /// Builtin RNG seed of the recorded VM; `None` for recordings made before seeds were captured.
+    pub rng_seed: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct VmRecordingReplayState {
+    pub cursor: usize,
+    pub offset_breakpoints: HashSet<usize>,
+    pub line_breakpoints: HashSet<u32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct VmRecordingReplayResponse {
+    pub output: String,
+    pub current_line: Option<u32>,
+    /// Name of the imported module `current_line` is in; `None` for the main source.
+    pub current_source: Option<String>,
+    pub at_end: bool,
+    pub exited: bool,
+}
+
+#[derive(Debug)]
+pub enum VmRecordingError {
+    Io(io::Error),
+    Wire(crate::vmbc::WireError),
+    InvalidFormat(&'static str),
+    Message(String),
+}
+
+struct VmRecordingBuilder {
+    recording: VmRecording,
+}
+
+/// Logpoint messages kept for a client that has not seen them yet; older
+/// ones are dropped.
+const MAX_PENDING_LOG_MESSAGES: usize = 100;
+
+/// Conditions attached to a breakpoint. A `condition` is an expression in
+/// the program's flavor, evaluated against its locals; only hits where it
+/// is truthy are counted. With `hit_count` the breakpoint stops from that
+/// hit on. With `log_message` it is a logpoint: it prints the message, with
+/// each `{expr}` replaced by the expression's value, and keeps running.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BreakpointOptions {
+    pub condition: Option<String>,
+    pub hit_count: Option<u32>,
+    pub log_message: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Breakpoint {
+    options: BreakpointOptions,
+    /// Clones share the count, so the offsets of one `file:line` breakpoint
+    /// count hits for the line as a whole.
+    hits: Arc<AtomicU32>,
+    expressions: ExpressionCache,
+}
+
+/// Condition and log expressions of a breakpoint, compiled on their first
+/// evaluation and reused while the locals in scope stay the same.
+#[derive(Clone, Debug, Default)]
+struct ExpressionCache {
+    compiled: HashMap<String, CachedExpression>,
+}
+
+#[derive(Clone, Debug)]
+struct CachedExpression {
+    flavor: SourceFlavor,
+    scope: Vec<(String, u8)>,
+    expression: Result<CompiledExpression, String>,
+}
+
+/// An expression compiled after a prelude that declares the locals in scope;
+/// each run copies their current values into the prelude's locals.
+#[derive(Clone, Debug)]
+struct CompiledExpression {
+    program: Program,
+    locals: usize,
+    /// Offset where the expression starts, after the prelude.
+    start: usize,
+    /// Program local and scratch local for each local in scope.
+    slots: Vec<(u8, u8)>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Breakpoints {
+    offsets: HashMap<usize, Breakpoint>,
+    /// Main-source lines, hit where a statement on the line starts.
+    lines: HashMap<u32, Breakpoint>,
+}
+
+enum BreakpointHit {
+    Skip,
+    Stop,
+    Log(String),
+    /// The condition could not be evaluated; the debugger stops and reports it.
+    Failed(String),
+}
+
+/// Why the debugger stopped, for transports that report it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StopReason {
+    Breakpoint,
+    Step,
+}
+
+pub struct Debugger {
+    breakpoints: Breakpoints,
+    step_mode: StepMode,
+    source_flavor: Option<SourceFlavor>,
+    server: Option<DebugServer>,
+    bridge: Option<DebugCommandBridge>,
+    dap: Option<DapSession>,
+    recording: Option<VmRecordingBuilder>,
+    client_detached: bool,
+}
+
+#[derive(Clone)]
+pub struct DebugCommandBridge {
+    inner: Arc<DebugCommandBridgeInner>,
+}
+
+struct DebugCommandBridgeInner {
+    state: Mutex<DebugCommandBridgeState>,
+    changed: Condvar,
+}
+
+struct DebugCommandBridgeState {
+    attached: bool,
+    current_line: Option<u32>,
+    current_source: Option<String>,
+    log_messages: VecDeque<String>,
+    closed: bool,
+    next_request_id: u64,
+    pending_request: Option<DebugCommandBridgeRequest>,
+    pending_response: Option<DebugCommandBridgeResponseInternal>,
+}
+
+struct DebugCommandBridgeRequest {
+    request_id: u64,
+    command: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct DebugCommandBridgeResponse {
+    pub output: String,
+    pub current_line: Option<u32>,
+    /// Name of the imported module `current_line` is in; `None` for the main source.
+    pub current_source: Option<String>,
+    pub attached: bool,
+    pub resumed: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DebugCommandBridgeStatus {
+    pub attached: bool,
+    pub current_line: Option<u32>,
+    pub current_source: Option<String>,
+    /// Most recent logpoint messages, oldest first.
+    pub log_messages: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DebugCommandBridgeError {
+    NotAttached,
+    Timeout,
+    Closed,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VmRecordingFrame {
+    fn from_vm(vm: &Vm) -> Self {
+        Self {
+            ip: vm.ip(),
+            call_depth: vm.call_depth(),
+            stack: vm.stack().to_vec(),
+            locals: vm.locals().to_vec(),
+        }
+    }
+}
+
+impl VmRecordingBuilder {
+    fn new(program: Program) -> Self {
+        Self {
+            recording: VmRecording {
+                program,
+                frames: Vec::new(),
+                terminal_status: None,
+                rng_seed: None,
+            },
+        }
+    }
+
+    fn record_state(&mut self, vm: &Vm) {
+        if self.recording.rng_seed.is_none() {
+            self.recording.rng_seed = Some(vm.rng_seed());
+        }
+        let frame = VmRecordingFrame::from_vm(vm);
+        if self.recording.frames.last() == Some(&frame) {
+            return;
+        }
+        self.recording.frames.push(frame);
+    }
+
+    fn on_terminal_status(&mut self, vm: &Vm, status: VmStatus) {
+        self.record_state(vm);
+        self.recording.terminal_status = Some(status);
+    }
+
+    fn finish(self) -> VmRecording {
+        self.recording
+    }
+}
+
+impl VmRecording {
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), VmRecordingError> {
+        let bytes = self.encode()?;
+        std::fs::write(path, bytes).map_err(VmRecordingError::Io)
+    }
+
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, VmRecordingError> {
+        let bytes = std::fs::read(path).map_err(VmRecordingError::Io)?;
+        Self::decode(&bytes)
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, VmRecordingError> {
+        const MAGIC: [u8; 4] = *b"PDRC";
+        const VERSION: u16 = 2;
+
+        let mut out = Vec::new();
+        out.extend_from_slice(&MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+
+        let program_bytes =
+            crate::vmbc::encode_program(&self.program).map_err(VmRecordingError::Wire)?;
+        write_u32_len(program_bytes.len(), &mut out)?;
+        out.extend_from_slice(&program_bytes);
+
+        let status_tag = match self.terminal_status {
+            Some(VmStatus::Halted) => 1u8,
+            Some(VmStatus::Yielded) => 2u8,
+            None => 0u8,
+        };
+        out.push(status_tag);
+
+        match self.rng_seed {
+            Some(seed) => {
+                out.push(1);
+                out.extend_from_slice(&seed.to_le_bytes());
+            }
+            None => out.push(0),
+        }
+
+        write_u32_len(self.frames.len(), &mut out)?;
+        for frame in &self.frames {
+            write_u32_from_usize(frame.ip, &mut out)?;
+            write_u32_from_usize(frame.call_depth, &mut out)?;
+
+            write_u32_len(frame.stack.len(), &mut out)?;
+            for value in &frame.stack {
+                encode_value(value, &mut out)?;
+            }
+
+            write_u32_len(frame.locals.len(), &mut out)?;
+            for value in &frame.locals {
+                encode_value(value, &mut out)?;
+            }
+        }
+
+        Ok(out)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, VmRecordingError> {
+        const MAGIC: [u8; 4] = *b"PDRC";
+        const LEGACY_VERSION: u16 = 1;
+        const VERSION: u16 = 2;
+
+        let mut cursor = RecordingCursor::new(bytes);
+
+        let magic = cursor.read_exact(4)?;
+        if magic != MAGIC {
+            return Err(VmRecordingError::InvalidFormat("invalid recording magic"));
+        }
+
+        let version = cursor.read_u16()?;
+        if version != VERSION && version != LEGACY_VERSION {
+            return Err(VmRecordingError::Message(format!(
+                "unsupported recording version {version}"
+            )));
+        }
+
+        let program_len = cursor.read_u32()? as usize;
+        let program_bytes = cursor.read_exact(program_len)?;
+        let program = crate::vmbc::decode_program(program_bytes).map_err(VmRecordingError::Wire)?;
+
+        let terminal_status = match cursor.read_u8()? {
+            0 => None,
+            1 => Some(VmStatus::Halted),
+            2 => Some(VmStatus::Yielded),
+            _ => {
+                return Err(VmRecordingError::InvalidFormat(
+                    "invalid terminal status tag",
+                ));
+            }
+        };
+
+        let rng_seed = if version == LEGACY_VERSION {
+            None
+        } else {
+            match cursor.read_u8()? {
+                0 => None,
+                1 => Some(cursor.read_u64()?),
+                _ => return Err(VmRecordingError::InvalidFormat("invalid rng seed tag")),
+            }
+        };
+
+        let frame_count = cursor.read_u32()? as usize;
+        let mut frames = Vec::with_capacity(frame_count);
+        for _ in 0..frame_count {
+            let ip = cursor.read_u32()? as usize;
+            let call_depth = cursor.read_u32()? as usize;
+
+            let stack_len = cursor.read_u32()? as usize;
+            let mut stack = Vec::with_capacity(stack_len);
+            for _ in 0..stack_len {
+                stack.push(decode_value(&mut cursor)?);
+            }
+
+            let locals_len = cursor.read_u32()? as usize;
+            let mut locals = Vec::with_capacity(locals_len);
+            for _ in 0..locals_len {
+                locals.push(decode_value(&mut cursor)?);
+            }
+
+            frames.push(VmRecordingFrame {
+                ip,
+                call_depth,
+                stack,
+                locals,
+            });
+        }
+
+        if !cursor.is_eof() {
+            return Err(VmRecordingError::InvalidFormat(
+                "trailing bytes in recording payload",
+            ));
+        }
+
+        Ok(Self {
+            program,
+            frames,
+            terminal_status,
+            rng_seed,
+        })
+    }
+}
+
+impl std::fmt::Display for VmRecordingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VmRecordingError::Io(err) => write!(f, "{err}"),
+            VmRecordingError::Wire(err) => write!(f, "{err}"),
+            VmRecordingError::InvalidFormat(message) => write!(f, "{message}"),
+            VmRecordingError::Message(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for VmRecordingError {}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: Breakpoints::default(),
+            step_mode: StepMode::Running,
+            source_flavor: None,
+            server: None,
+            bridge: None,
+            dap: None,
+            recording: None,
+            client_detached: false,
+        }
+    }
+
+    pub fn with_tcp(addr: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(false)?;
+        Ok(Self {
+            breakpoints: Breakpoints::default(),
+            step_mode: StepMode::Running,
+            source_flavor: None,
+            server: Some(DebugServer::new(listener)),
+            bridge: None,
+            dap: None,
+            recording: None,
+            client_detached: false,
+        })
+    }
+
+    pub fn with_command_bridge(bridge: DebugCommandBridge) -> Self {
+        Self {
+            breakpoints: Breakpoints::default(),
+            step_mode: StepMode::Running,
+            source_flavor: None,
+            server: None,
+            bridge: Some(bridge),
+            dap: None,
+            recording: None,
+            client_detached: false,
+        }
+    }
+
+    /// Debugs over the Debug Adapter Protocol. The first instruction waits
+    /// for an editor to launch or attach and finish configuring.
+    pub fn with_dap(session: DapSession) -> Self {
+        Self {
+            breakpoints: Breakpoints::default(),
+            step_mode: StepMode::Running,
+            source_flavor: None,
+            server: None,
+            bridge: None,
+            dap: Some(session),
+            recording: None,
+            client_detached: false,
+        }
+    }
+
+    pub fn with_recording(program: Program) -> Self {
+        Self {
+            breakpoints: Breakpoints::default(),
+            step_mode: StepMode::Running,
+            source_flavor: None,
+            server: None,
+            bridge: None,
+            dap: None,
+            recording: Some(VmRecordingBuilder::new(program)),
+            client_detached: false,
+        }
+    }
+
+    pub fn stop_on_entry(&mut self) {
+        self.step_mode = StepMode::Step;
+    }
+
+    pub fn add_breakpoint(&mut self, offset: usize) {
+        self.add_breakpoint_with_options(offset, BreakpointOptions::default());
+    }
+
+    pub fn add_breakpoint_with_options(&mut self, offset: usize, options: BreakpointOptions) {
+        self.breakpoints
+            .offsets
+            .insert(offset, Breakpoint::new(options));
+    }
+
+    pub fn remove_breakpoint(&mut self, offset: usize) {
+        self.breakpoints.offsets.remove(&offset);
+    }
+
+    /// Breaks where a statement on `line` of the main source starts.
+    pub fn add_line_breakpoint(&mut self, line: u32, options: BreakpointOptions) {
+        self.breakpoints
+            .lines
+            .insert(line, Breakpoint::new(options));
+    }
+
+    pub fn remove_line_breakpoint(&mut self, line: u32) {
+        self.breakpoints.lines.remove(&line);
+    }
+
+    /// Flavor breakpoint conditions and log expressions are written in.
+    /// Without one it is taken from the main source's file extension,
+    /// falling back to RustScript.
+    pub fn set_source_flavor(&mut self, flavor: SourceFlavor) {
+        self.source_flavor = Some(flavor);
+    }
+
+    pub fn on_instruction(&mut self, vm: &mut Vm) {
+        if let Some(recording) = self.recording.as_mut() {
+            recording.record_state(vm);
+        }
+        if let Some(dap) = self.dap.as_mut()
+            && dap.poll(
+                vm,
+                &mut self.breakpoints,
+                &mut self.step_mode,
+                self.source_flavor,
+            )
+        {
+            self.client_detached = true;
+        }
+
+        let ip = vm.ip();
+        let mut stop = None;
+        for hit in self.breakpoints.check(vm, self.source_flavor) {
+            match hit {
+                BreakpointHit::Skip => {}
+                BreakpointHit::Stop => stop = Some(StopReason::Breakpoint),
+                BreakpointHit::Log(message) => self.emit_log(&message),
+                BreakpointHit::Failed(message) => {
+                    self.emit_log(&message);
+                    stop = Some(StopReason::Breakpoint);
+                }
+            }
+        }
+
+        if stop.is_none() {
+            let stepped = match self.step_mode {
+                StepMode::Step => true,
+                StepMode::StepOver {
+                    depth,
+                    ip: start_ip,
+                } => vm.call_depth() <= depth && ip != start_ip,
+                StepMode::StepOut { depth } => vm.call_depth() < depth,
+                StepMode::Running => false,
+            };
+            // A DAP source-line step keeps single-stepping until it leaves the line.
+            if stepped && self.dap.as_ref().is_none_or(|dap| dap.step_finished(vm)) {
+                stop = Some(StopReason::Step);
+            }
+        }
+        if let Some(reason) = stop {
+            self.step_mode = StepMode::Running;
+            self.client_detached = self.repl(vm, reason);
+        }
+    }
+
+    pub fn on_vm_status(&mut self, vm: &Vm, status: VmStatus) {
+        if let Some(recording) = self.recording.as_mut() {
+            recording.on_terminal_status(vm, status);
+        }
+    }
+
+    pub fn take_recording(&mut self) -> Option<VmRecording> {
+        self.recording.take().map(VmRecordingBuilder::finish)
+    }
+
+    pub fn take_detach_event(&mut self) -> bool {
+        std::mem::take(&mut self.client_detached)
+    }
+
+    /// Reports the end of the program to a DAP editor, with `error` as the
+    /// reason it failed, and ends the session. Other transports ignore it.
+    pub fn end_session(&mut self, error: Option<&str>) {
+        if let Some(dap) = self.dap.as_mut() {
+            dap.end(error);
+        }
+    }
+
+    fn repl(&mut self, vm: &mut Vm, reason: StopReason) -> bool {
+        if let Some(dap) = self.dap.as_mut() {
+            return dap.stopped(
+                vm,
+                reason,
+                &mut self.breakpoints,
+                &mut self.step_mode,
+                self.source_flavor,
+            );
+        }
+        if let Some(server) = self.server.as_mut() {
+            return server.repl(
+                vm,
+                &mut self.breakpoints,
+                &mut self.step_mode,
+                self.source_flavor,
+            );
+        }
+        if let Some(bridge) = self.bridge.as_ref() {
+            return bridge.repl(
+                vm,
+                &mut self.breakpoints,
+                &mut self.step_mode,
+                self.source_flavor,
+            );
+        }
+        repl_stdio(
+            vm,
+            &mut self.breakpoints,
+            &mut self.step_mode,
+            self.source_flavor,
+        );
+        false
+    }
+
+    fn emit_log(&mut self, message: &str) {
+        if let Some(dap) = self.dap.as_ref() {
+            dap.output(message);
+        } else if let Some(server) = self.server.as_mut() {
+            server.log(message);
+        } else if let Some(bridge) = self.bridge.as_ref() {
+            bridge.push_log(message);
+        } else {
+            println!("{message}");
+        }
+    }
+}
+
+impl Breakpoint {
+    fn new(options: BreakpointOptions) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+
+    fn hits(&self) -> u32 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    fn check(&mut self, vm: &Vm, flavor: SourceFlavor, location: &str) -> BreakpointHit {
+        if let Some(condition) = self.options.condition.as_deref() {
+            match self.expressions.evaluate(vm, condition, flavor) {
+                Ok(value) if is_truthy(&value) => {}
+                Ok(_) => return BreakpointHit::Skip,
+                Err(message) => {
+                    return BreakpointHit::Failed(format!(
+                        "breakpoint condition error at {location}: {message}"
+                    ));
+                }
+            }
+        }
+        let hits = self.hits().saturating_add(1);
+        self.hits.store(hits, Ordering::Relaxed);
+        if hits < self.options.hit_count.unwrap_or(0) {
+            return BreakpointHit::Skip;
+        }
+        match self.options.log_message.as_deref() {
+            Some(message) => BreakpointHit::Log(interpolate_log_message(message, |expr| {
+                self.expressions.evaluate(vm, expr, flavor)
+            })),
+            None => BreakpointHit::Stop,
+        }
+    }
+}
+
+impl ExpressionCache {
+    fn evaluate(&mut self, vm: &Vm, expr: &str, flavor: SourceFlavor) -> Result<Value, String> {
+        let scope = locals_in_scope(vm);
+        let cached = self
+            .compiled
+            .get(expr)
+            .filter(|cached| cached.flavor == flavor && cached.scope == scope);
+        if cached.is_none() {
+            let expression = compile_expression(&scope, expr, flavor);
+            self.compiled.insert(
+                expr.to_string(),
+                CachedExpression {
+                    flavor,
+                    scope,
+                    expression,
+                },
+            );
+        }
+        match &self.compiled[expr].expression {
+            Ok(expression) => run_expression(expression, vm),
+            Err(message) => Err(message.clone()),
+        }
+    }
+}
+
+impl Breakpoints {
+    /// Checks the breakpoints at the current instruction, counting their hits.
+    fn check(&mut self, vm: &Vm, flavor: Option<SourceFlavor>) -> Vec<BreakpointHit> {
+        let ip = vm.ip();
+        let line = vm.debug_info().and_then(|info| statement_line_at(info, ip));
+        let at_offset = self.offsets.contains_key(&ip);
+        let at_line = line.is_some_and(|line| self.lines.contains_key(&line));
+        if !at_offset && !at_line {
+            return Vec::new();
+        }
+        let flavor = flavor.unwrap_or_else(|| expression_flavor(vm));
+        let mut hits = Vec::new();
+        if let Some(breakpoint) = self.offsets.get_mut(&ip) {
+            hits.push(breakpoint.check(vm, flavor, &ip.to_string()));
+        }
+        if let Some(line) = line
+            && let Some(breakpoint) = self.lines.get_mut(&line)
+        {
+            hits.push(breakpoint.check(vm, flavor, &format!("line {line}")));
+        }
+        hits
+    }
+}
+
+impl DebugCommandBridge {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(DebugCommandBridgeInner {
+                state: Mutex::new(DebugCommandBridgeState {
+                    attached: false,
+                    current_line: None,
+                    current_source: None,
+                    log_messages: VecDeque::new(),
+                    closed: false,
+                    next_request_id: 0,
+                    pending_request: None,
+                    pending_response: None,
+                }),
+                changed: Condvar::new(),
+            }),
+        }
+    }
+
+    pub fn status(&self) -> DebugCommandBridgeStatus {
+        let state = self
+            .inner
+            .state
+            .lock()
+            .expect("debug command bridge lock poisoned");
+        DebugCommandBridgeStatus {
+            attached: state.attached,
+            current_line: state.current_line,
+            current_source: state.current_source.clone(),
+            log_messages: state.log_messages.iter().cloned().collect(),
+        }
+    }
+
+    fn push_log(&self, message: &str) {
+        let mut state = self
+            .inner
+            .state
+            .lock()
+            .expect("debug command bridge lock poisoned");
+        push_pending_log(&mut state.log_messages, message);
+    }
+
+    pub fn close(&self) {
+        let mut state = self
+            .inner
+            .state
+            .lock()
+            .expect("debug command bridge lock poisoned");
+        state.closed = true;
+        state.attached = false;
+        state.current_line = None;
+        state.current_source = None;
+        state.log_messages.clear();
+        state.pending_request = None;
+        state.pending_response = None;
+        self.inner.changed.notify_all();
+    }
+
+    pub fn execute(
+        &self,
+        command: impl Into<String>,
+        timeout: Duration,
+    ) -> Result<DebugCommandBridgeResponse, DebugCommandBridgeError> {
+        let mut state = self
+            .inner
+            .state
+            .lock()
+            .expect("debug command bridge lock poisoned");
+        if state.closed {
+            return Err(DebugCommandBridgeError::Closed);
+        }
+        if !state.attached {
+            return Err(DebugCommandBridgeError::NotAttached);
+        }
+
+        state.next_request_id = state.next_request_id.saturating_add(1);
+        let request_id = state.next_request_id;
+        state.pending_request = Some(DebugCommandBridgeRequest {
+            request_id,
+            command: command.into(),
+        });
+        self.inner.changed.notify_all();
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            if state.closed {
+                return Err(DebugCommandBridgeError::Closed);
+            }
+            if let Some(response) = state.pending_response.clone()
+                && response.request_id == request_id
+            {
+                state.pending_response = None;
+                return Ok(DebugCommandBridgeResponse {
+                    output: response.output,
+                    current_line: response.current_line,
+                    current_source: response.current_source,
+                    attached: response.attached,
+                    resumed: response.resumed,
+                });
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(DebugCommandBridgeError::Timeout);
+            }
+            let wait_for = deadline.saturating_duration_since(now);
+            let (next_state, wait_result) = self
+                .inner
+                .changed
+                .wait_timeout(state, wait_for)
+                .expect("debug command bridge lock poisoned");
+            state = next_state;
+            if wait_result.timed_out() {
+                return Err(DebugCommandBridgeError::Timeout);
+            }
+        }
+    }
+
+    fn repl(
+        &self,
+        vm: &mut Vm,
+        breakpoints: &mut Breakpoints,
+        step: &mut StepMode,
+        flavor: Option<SourceFlavor>,
+    ) -> bool {
+        {
+            let mut state = self
+                .inner
+                .state
+                .lock()
+                .expect("debug command bridge lock poisoned");
+            state.closed = false;
+            state.attached = true;
+            (state.current_line, state.current_source) = current_location(vm);
+            state.pending_request = None;
+            // A `continue` caller may not have read its response yet when the
+            // program stops again; responses are matched by request id.
+            self.inner.changed.notify_all();
+        }
+
+        loop {
+            let request = {
+                let mut state = self
+                    .inner
+                    .state
+                    .lock()
+                    .expect("debug command bridge lock poisoned");
+                while !state.closed && state.pending_request.is_none() {
+                    state = self
+                        .inner
+                        .changed
+                        .wait(state)
+                        .expect("debug command bridge lock poisoned");
+                }
+                if state.closed {
+                    state.attached = false;
+                    state.current_line = None;
+                    state.current_source = None;
+                    state.pending_request = None;
+                    state.pending_response = None;
+                    self.inner.changed.notify_all();
+                    return true;
+                }
+                state
+                    .pending_request
+                    .take()
+                    .expect("debug command request missing")
+            };
+
+            let mut output = Vec::<u8>::new();
+            let action =
+                handle_command(&request.command, vm, breakpoints, step, flavor, &mut output);
+            let resumed = action.is_break();
+            let (current_line, current_source) = if resumed {
+                (None, None)
+            } else {
+                current_location(vm)
+            };
+            let attached = !resumed;
+            let output = String::from_utf8_lossy(&output).to_string();
+
+            let mut state = self
+                .inner
+                .state
+                .lock()
+                .expect("debug command bridge lock poisoned");
+            state.attached = attached;
+            state.current_line = current_line;
+            state.current_source = current_source.clone();
+            state.pending_response = Some(DebugCommandBridgeResponseInternal {
+                request_id: request.request_id,
+                output,
+                current_line,
+                current_source,
+                attached,
+                resumed,
+            });
+            self.inner.changed.notify_all();
+
+            if resumed {
+                return false;
+            }
+        }
+    }
+}
+
+impl Default for DebugCommandBridge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Display for DebugCommandBridgeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DebugCommandBridgeError::NotAttached => write!(f, "debugger is not attached"),
+            DebugCommandBridgeError::Timeout => write!(f, "timed out waiting for debugger"),
+            DebugCommandBridgeError::Closed => write!(f, "debugger bridge is closed"),
+        }
+    }
+}
+
+impl std::error::Error for DebugCommandBridgeError {}
+
+#[derive(Clone)]
+struct DebugCommandBridgeResponseInternal {
+    request_id: u64,
+    output: String,
+    current_line: Option<u32>,
+    current_source: Option<String>,
+    attached: bool,
+    resumed: bool,
+}
+
+struct DebugServer {
+    listener: TcpListener,
+    stream: Option<TcpStream>,
+    /// Logpoint messages emitted before a client connected.
+    pending_logs: VecDeque<String>,
+}
+
+impl DebugServer {
+    fn new(listener: TcpListener) -> Self {
+        Self {
+            listener,
+            stream: None,
+            pending_logs: VecDeque::new(),
+        }
+    }
+
+    fn log(&mut self, message: &str) {
+        match self.stream.as_mut() {
+            Some(stream) => {
+                if writeln!(stream, "{message}").is_err() {
+                    self.stream = None;
+                }
+            }
+            None => push_pending_log(&mut self.pending_logs, message),
+        }
+    }
+
+    fn ensure_client(&mut self) -> io::Result<()> {
+        if self.stream.is_none() {
+            let (stream, _) = self.listener.accept()?;
+            self.stream = Some(stream);
+        }
+        Ok(())
+    }
+
+    fn repl(
+        &mut self,
+        vm: &mut Vm,
+        breakpoints: &mut Breakpoints,
+        step: &mut StepMode,
+        flavor: Option<SourceFlavor>,
+    ) -> bool {
+        if self.ensure_client().is_err() {
+            return false;
+        }
+        let Some(stream) = self.stream.as_mut() else {
+            return false;
+        };
+        let _ = writeln!(stream, "debugger attached. type 'help' for commands");
+        for message in self.pending_logs.drain(..) {
+            let _ = writeln!(stream, "{message}");
+        }
+        let Ok(clone) = stream.try_clone() else {
+            self.stream = None;
+            return true;
+        };
+        let mut reader = io::BufReader::new(clone);
+        loop {
+            if write_prompt(stream).is_err() {
+                self.stream = None;
+                return true;
+            }
+            let mut line = String::new();
+            match reader.read_line(&mut line) {
+                Ok(0) => {
+                    self.stream = None;
+                    return true;
+                }
+                Ok(_) => {}
+                Err(_) => {
+                    self.stream = None;
+                    return true;
+                }
+            }
+            if handle_command(&line, vm, breakpoints, step, flavor, stream).is_break() {
+                return false;
+            }
+        }
+    }
+}
+
+fn repl_stdio(
+    vm: &mut Vm,
+    breakpoints: &mut Breakpoints,
+    step: &mut StepMode,
+    flavor: Option<SourceFlavor>,
+) {
+    let stdin = io::stdin();
+    let mut input = String::new();
+    loop {
+        input.clear();
+        print!("(pdb) ");
+        let _ = io::stdout().flush();
+        if stdin.read_line(&mut input).is_err() {
+            break;
+        }
+        if handle_command(&input, vm, breakpoints, step, flavor, &mut io::stdout()).is_break() {
+            break;
+        }
+    }
+}
+
+fn push_pending_log(messages: &mut VecDeque<String>, message: &str) {
+    if messages.len() == MAX_PENDING_LOG_MESSAGES {
+        messages.pop_front();
+    }
+    messages.push_back(message.to_string());
+}
+
+fn write_prompt(stream: &mut TcpStream) -> io::Result<()> {
+    stream.write_all(b"(pdb) ")?;
+    stream.flush()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ReplAction {
+    Continue,
+    Break,
+}
+
+impl ReplAction {
+    fn is_break(self) -> bool {
+        matches!(self, ReplAction::Break)
+    }
+}
+
+fn handle_command(
+    line: &str,
+    vm: &mut Vm,
+    breakpoints: &mut Breakpoints,
+    step: &mut StepMode,
+    flavor: Option<SourceFlavor>,
+    out: &mut dyn Write,
+) -> ReplAction {
+    let mut parts = line.split_whitespace();
+    let Some(cmd) = parts.next() else {
+        return ReplAction::Continue;
+    };
+    match cmd {
+        "c" | "continue" => return ReplAction::Break,
+        "s" | "step" | "stepi" => {
+            *step = StepMode::Step;
+            return ReplAction::Break;
+        }
+        "n" | "next" => {
+            *step = StepMode::StepOver {
+                depth: vm.call_depth(),
+                ip: vm.ip(),
+            };
+            return ReplAction::Break;
+        }
+        "finish" | "out" => {
+            *step = StepMode::StepOut {
+                depth: vm.call_depth(),
+            };
+            return ReplAction::Break;
+        }
+        "b" | "break" => {
+            let (arg, rest) = split_token(split_token(line).1);
+            if arg.is_empty() {
+                let _ = writeln!(
+                    out,
+                    "usage: break <offset|file:line> [hits <n>] [if <condition>] [log <message>]"
+                );
+                return ReplAction::Continue;
+            }
+            if arg == "line" {
+                let (number, rest) = split_token(rest);
+                set_line_breakpoint(number, rest, "break line <number>", breakpoints, out);
+                return ReplAction::Continue;
+            }
+            let options = match parse_breakpoint_options(rest) {
+                Ok(options) => options,
+                Err(message) => {
+                    let _ = writeln!(out, "{message}");
+                    return ReplAction::Continue;
+                }
+            };
+            let description = describe_breakpoint_options(&options);
+            if let Some(resolved) = resolve_source_breakpoint(vm.debug_info(), arg) {
+                match resolved {
+                    Ok(SourceBreakpoint {
+                        name,
+                        line,
+                        offsets,
+                    }) => {
+                        let breakpoint = Breakpoint::new(options);
+                        for offset in offsets {
+                            breakpoints
+                                .offsets
+                                .insert(offset as usize, breakpoint.clone());
+                        }
+                        let _ = writeln!(out, "breakpoint set at {name}:{line}{description}");
+                    }
+                    Err(message) => {
+                        let _ = writeln!(out, "{message}");
+                    }
+                }
+                return ReplAction::Continue;
+            }
+            if let Ok(offset) = arg.parse::<usize>() {
+                breakpoints.offsets.insert(offset, Breakpoint::new(options));
+                let _ = writeln!(out, "breakpoint set at {offset}{description}");
+            } else {
+                let _ = writeln!(out, "expected instruction offset");
+            }
+        }
+        "bl" => {
+            let (number, rest) = split_token(split_token(line).1);
+            set_line_breakpoint(number, rest, "bl <line>", breakpoints, out);
+        }
+        "clear" => {
+            if let Some(arg) = parts.next() {
+                if arg == "line" {
+                    if let Some(line) = parse_u32(parts.next()) {
+                        breakpoints.lines.remove(&line);
+                        let _ = writeln!(out, "line breakpoint cleared at {line}");
+                    } else {
+                        let _ = writeln!(out, "usage: clear line <number>");
+                    }
+                    return ReplAction::Continue;
+                }
+                if let Some(resolved) = resolve_source_breakpoint(vm.debug_info(), arg) {
+                    match resolved {
+                        Ok(SourceBreakpoint {
+                            name,
+                            line,
+                            offsets,
+                        }) => {
+                            for offset in offsets {
+                                breakpoints.offsets.remove(&(offset as usize));
+                            }
+                            let _ = writeln!(out, "breakpoint cleared at {name}:{line}");
+                        }
+                        Err(message) => {
+                            let _ = writeln!(out, "{message}");
+                        }
+                    }
+                    return ReplAction::Continue;
+                }
+                if let Ok(offset) = arg.parse::<usize>() {
+                    breakpoints.offsets.remove(&offset);
+                    let _ = writeln!(out, "breakpoint cleared at {offset}");
+                } else {
+                    let _ = writeln!(out, "expected instruction offset");
+                }
+            } else {
+                let _ = writeln!(out, "usage: clear <offset|file:line>");
+            }
+        }
+        "cl" => {
+            if let Some(line) = parse_u32(parts.next()) {
+                breakpoints.lines.remove(&line);
+                let _ = writeln!(out, "line breakpoint cleared at {line}");
+            } else {
+                let _ = writeln!(out, "usage: cl <line>");
+            }
+        }
+        "breaks" => write_breakpoints(breakpoints, out),
+        "stack" => {
+            let _ = writeln!(out, "stack: {:?}", vm.stack());
+            if let Some(info) = vm.debug_info() {
+                write_call_stack(info, vm.ip(), out);
+            }
+        }
+        "locals" => {
+            print_locals(vm, out);
+        }
+        "p" | "print" => {
+            if let Some(name) = parts.next() {
+                print_local_by_name(vm, name, out);
+            } else {
+                let _ = writeln!(out, "usage: print <local_name>");
+            }
+        }
+        "e" | "eval" => {
+            let (mut expr, mut flavor) = (split_token(line).1, flavor);
+            if let ("--flavor", rest) = split_token(expr) {
+                let (name, rest) = split_token(rest);
+                match SourceFlavor::from_name(name).filter(|f| *f != SourceFlavor::Assembly) {
+                    Some(named) => flavor = Some(named),
+                    None => {
+                        let _ = writeln!(out, "unknown expression flavor '{name}'");
+                        return ReplAction::Continue;
+                    }
+                }
+                expr = rest;
+            }
+            if expr.is_empty() {
+                let _ = writeln!(out, "usage: eval [--flavor <name>] <expression>");
+                return ReplAction::Continue;
+            }
+            let flavor = flavor.unwrap_or_else(|| expression_flavor(vm));
+            match evaluate_expression(vm, expr, flavor) {
+                Ok(value) => {
+                    let _ = writeln!(out, "=> {}", format_local_value(&value));
+                }
+                Err(err) => {
+                    let _ = writeln!(out, "eval failed: {err}");
+                }
+            }
+        }
+        "set" => set_local_command(split_token(line).1, vm, flavor, out),
+        "ip" => {
+            let _ = writeln!(out, "ip: {}", vm.ip());
+        }
+        "where" => {
+            if let Some(info) = vm.debug_info() {
+                write_where(info, vm.ip(), out);
+            } else {
+                let _ = writeln!(out, "no debug info");
+            }
+        }
+        "funcs" => {
+            if let Some(info) = vm.debug_info() {
+                for func in &info.functions {
+                    let _ = writeln!(out, "fn {}({})", func.name, format_args_list(func));
+                }
+            } else {
+                let _ = writeln!(out, "no debug info");
+            }
+        }
+        "help" => {
+            let _ = writeln!(
+                out,
+                "commands: break, break line, bl, clear, clear line, cl, breaks, continue, step, next, out, stack, locals, print, eval, set, ip, where, funcs, help"
+            );
+            let _ = writeln!(
+                out,
+                "expressions: eval [--flavor <name>] <expr>, set <local> = <expr>"
+            );
+            let _ = writeln!(
+                out,
+                "breakpoint options: [hits <n>] [if <condition>] [log <message with {{expr}}>]"
+            );
+        }
+        _ => {
+            let _ = writeln!(out, "unknown command");
+        }
+    }
+    ReplAction::Continue
+}
+
+/// Handles `set <local> = <expr>`: evaluates the expression against the
+/// current locals and stores the result in the named live local.
+fn set_local_command(args: &str, vm: &mut Vm, flavor: Option<SourceFlavor>, out: &mut dyn Write) {
+    let Some((name, expr)) = args
+        .split_once('=')
+        .map(|(name, expr)| (name.trim(), expr.trim()))
+        .filter(|(name, expr)| !name.is_empty() && !expr.is_empty() && !expr.starts_with('='))
+    else {
+        let _ = writeln!(out, "usage: set <local> = <expression>");
+        return;
+    };
+    let Some(info) = vm.debug_info() else {
+        let _ = writeln!(out, "no debug info");
+        return;
+    };
+    let Some(index) = info.local_index_at(vm.ip(), name) else {
+        let _ = writeln!(out, "unknown local '{name}'");
+        return;
+    };
+    let flavor = flavor.unwrap_or_else(|| expression_flavor(vm));
+    let value = match evaluate_expression(vm, expr, flavor) {
+        Ok(value) => value,
+        Err(err) => {
+            let _ = writeln!(out, "eval failed: {err}");
+            return;
+        }
+    };
+    let rendered = format_local_value(&value);
+    match vm.set_local(index, value) {
+        Ok(()) => {
+            let _ = writeln!(out, "{name} = {rendered}");
+        }
+        Err(_) => {
+            let _ = writeln!(out, "local '{name}' is out of range for this VM instance");
+        }
+    }
+}
+
+/// Splits off the first whitespace-separated token of `text`.
+fn split_token(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    match text.find(char::is_whitespace) {
+        Some(end) => (&text[..end], text[end..].trim_start()),
+        None => (text, ""),
+    }
+}
+
+fn set_line_breakpoint(
+    number: &str,
+    options: &str,
+    usage: &str,
+    breakpoints: &mut Breakpoints,
+    out: &mut dyn Write,
+) {
+    let Some(line) = parse_u32(Some(number)) else {
+        let _ = writeln!(
+            out,
+            "usage: {usage} [hits <n>] [if <condition>] [log <message>]"
+        );
+        return;
+    };
+    match parse_breakpoint_options(options) {
+        Ok(options) => {
+            let description = describe_breakpoint_options(&options);
+            breakpoints.lines.insert(line, Breakpoint::new(options));
+            let _ = writeln!(out, "line breakpoint set at {line}{description}");
+        }
+        Err(message) => {
+            let _ = writeln!(out, "{message}");
+        }
+    }
+}
+
+/// Parses the `[hits <n>] [if <condition>] [log <message>]` that follows a
+/// breakpoint's location. A condition runs up to a standalone `log`, or is
+/// wrapped in braces to mention one; the message takes the rest of the line.
+fn parse_breakpoint_options(text: &str) -> Result<BreakpointOptions, String> {
+    let mut options = BreakpointOptions::default();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let (keyword, tail) = split_token(rest);
+        match keyword {
+            "hits" => {
+                let (count, tail) = split_token(tail);
+                match count.parse::<u32>() {
+                    Ok(count) if count > 0 => options.hit_count = Some(count),
+                    _ => return Err("hits expects a positive count".to_string()),
+                }
+                rest = tail;
+            }
+            "if" => {
+                let (condition, tail) = if tail.starts_with('{') {
+                    let end = closing_brace(tail)
+                        .ok_or_else(|| "if expects a closing '}'".to_string())?;
+                    (tail[1..end].trim(), tail[end + 1..].trim_start())
+                } else {
+                    match find_keyword(tail, "log") {
+                        Some(at) => (tail[..at].trim(), &tail[at..]),
+                        None => (tail.trim(), ""),
+                    }
+                };
+                if condition.is_empty() {
+                    return Err("if expects a condition".to_string());
+                }
+                options.condition = Some(condition.to_string());
+                rest = tail;
+            }
+            "log" => {
+                if tail.is_empty() {
+                    return Err("log expects a message".to_string());
+                }
+                options.log_message = Some(tail.to_string());
+                rest = "";
+            }
+            other => {
+                return Err(format!("unexpected '{other}'; expected hits, if or log"));
+            }
+        }
+    }
+    Ok(options)
+}
+
+/// Byte offset of the first occurrence of `keyword` as a whole token.
+fn find_keyword(text: &str, keyword: &str) -> Option<usize> {
+    text.match_indices(keyword).map(|(at, _)| at).find(|&at| {
+        let before = text[..at].chars().next_back();
+        let after = text[at + keyword.len()..].chars().next();
+        before.is_none_or(char::is_whitespace) && after.is_none_or(char::is_whitespace)
+    })
+}
+
+/// Byte offset of the `}` closing the `{` that `text` starts with, skipping
+/// braces inside string literals.
+fn closing_brace(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (at, ch) in text.char_indices() {
+        if in_string {
+            match ch {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match ch {
+            '"' => in_string = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(at);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Options in the syntax `break` accepts them, with a leading space.
+fn describe_breakpoint_options(options: &BreakpointOptions) -> String {
+    let mut out = String::new();
+    if let Some(count) = options.hit_count {
+        out.push_str(&format!(" hits {count}"));
+    }
+    if let Some(condition) = &options.condition {
+        if find_keyword(condition, "log").is_some() || condition.starts_with('{') {
+            out.push_str(&format!(" if {{{condition}}}"));
+        } else {
+            out.push_str(&format!(" if {condition}"));
+        }
+    }
+    if let Some(message) = &options.log_message {
+        out.push_str(&format!(" log {message}"));
+    }
+    out
+}
+
+fn write_breakpoints(breakpoints: &Breakpoints, out: &mut dyn Write) {
+    let mut offsets = breakpoints.offsets.iter().collect::<Vec<_>>();
+    offsets.sort_unstable_by_key(|(offset, _)| **offset);
+    let mut lines = breakpoints.lines.iter().collect::<Vec<_>>();
+    lines.sort_unstable_by_key(|(line, _)| **line);
+    let _ = writeln!(
+        out,
+        "breakpoints: {:?}",
+        offsets
+            .iter()
+            .map(|(offset, _)| **offset)
+            .collect::<Vec<_>>()
+    );
+    let _ = writeln!(
+        out,
+        "line breakpoints: {:?}",
+        lines.iter().map(|(line, _)| **line).collect::<Vec<_>>()
+    );
+    let described = offsets
+        .iter()
+        .map(|(offset, breakpoint)| (offset.to_string(), *breakpoint))
+        .chain(
+            lines
+                .iter()
+                .map(|(line, breakpoint)| (format!("line {line}"), *breakpoint)),
+        );
+    for (location, breakpoint) in described {
+        if breakpoint.options != BreakpointOptions::default() {
+            let _ = writeln!(
+                out,
+                "  {location}{} ({} hits)",
+                describe_breakpoint_options(&breakpoint.options),
+                breakpoint.hits()
+            );
+        }
+    }
+}
+
+/// Main-source line of the statement that starts at `ip`, if one does.
+fn statement_line_at(info: &DebugInfo, ip: usize) -> Option<u32> {
+    info.location_for_offset(ip)
+        .filter(|location| location.offset as usize == ip && location.source_id == MAIN_SOURCE_ID)
+        .map(|location| location.line)
+}
+
+/// Flavor named by the main source's file extension, or RustScript.
+fn expression_flavor(vm: &Vm) -> SourceFlavor {
+    vm.debug_info()
+        .and_then(|info| info.source_name(MAIN_SOURCE_ID))
+        .and_then(|name| Path::new(name).extension())
+        .and_then(|ext| ext.to_str())
+        .and_then(SourceFlavor::from_extension)
+        .filter(|flavor| *flavor != SourceFlavor::Assembly)
+        .unwrap_or(SourceFlavor::RustScript)
+}
+
+/// Instructions an expression may run before it is abandoned, so that a
+/// condition or `eval` that never finishes cannot hang the program.
+const EXPRESSION_STEP_BUDGET: usize = 1_000_000;
+
+/// Compiles `expr` against the program's named locals, runs it on a scratch
+/// VM, and returns the value it leaves on top of the stack. The program's VM
+/// is left untouched.
+fn evaluate_expression(vm: &Vm, expr: &str, flavor: SourceFlavor) -> Result<Value, String> {
+    run_expression(&compile_expression(&locals_in_scope(vm), expr, flavor)?, vm)
+}
+
+/// Compiles `expr` after a prelude declaring each local of `scope`, so that
+/// it can run against their values at any stop with the same locals.
+fn compile_expression(
+    scope: &[(String, u8)],
+    expr: &str,
+    flavor: SourceFlavor,
+) -> Result<CompiledExpression, String> {
+    let placeholders = scope
+        .iter()
+        .map(|(name, _)| (name.clone(), Value::Null))
+        .collect::<Vec<_>>();
+    let prelude = transpile_bindings(&placeholders, flavor).map_err(|err| err.to_string())?;
+    let expr = expr.trim();
+    let statement = match flavor {
+        SourceFlavor::RustScript | SourceFlavor::JavaScript if !expr.ends_with(';') => {
+            format!("{expr};")
+        }
+        _ => expr.to_string(),
+    };
+    let prelude = prelude.trim_end();
+    let prelude_lines = prelude.lines().count() as u32;
+    let source = if prelude.is_empty() {
+        statement
+    } else {
+        format!("{prelude}\n{statement}")
+    };
+    let compiled = compile_source_with_flavor(&source, flavor).map_err(|err| err.to_string())?;
+    if let Some(function) = compiled.functions.first() {
+        return Err(format!(
+            "expression cannot call host function '{}'",
+            function.name
+        ));
+    }
+    let Some(info) = compiled.program.debug.as_ref() else {
+        return Err("expression compiled without debug info".to_string());
+    };
+    let start = info
+        .lines
+        .iter()
+        .find(|line| line.source_id == MAIN_SOURCE_ID && line.line > prelude_lines)
+        .map_or(0, |line| line.offset as usize);
+    let prelude_locals = &info.locals;
+    let slots = scope
+        .iter()
+        .filter_map(|(name, index)| {
+            let local = prelude_locals.iter().find(|local| local.name == *name)?;
+            Some((*index, local.index))
+        })
+        .collect();
+    Ok(CompiledExpression {
+        program: compiled.program,
+        locals: compiled.locals,
+        start,
+        slots,
+    })
+}
+
+/// Runs `expression` on a scratch VM holding copies of `vm`'s locals, within
+/// [`EXPRESSION_STEP_BUDGET`] instructions.
+fn run_expression(expression: &CompiledExpression, vm: &Vm) -> Result<Value, String> {
+    let mut scratch = Vm::with_locals(expression.program.clone(), expression.locals);
+    scratch.set_rng_seed(vm.rng_seed());
+    let mut budget = EXPRESSION_STEP_BUDGET;
+    // The prelude stores placeholders; the copies go in once it has run.
+    while scratch.ip() < expression.start && budget > 0 {
+        budget -= 1;
+        if scratch
+            .run_with_budget(&mut 1)
+            .map_err(|err| err.to_string())?
+            .is_some()
+        {
+            break;
+        }
+    }
+    for (index, slot) in &expression.slots {
+        if let Some(value) = vm.locals().get(*index as usize) {
+            scratch
+                .set_local(*slot, value.clone())
+                .map_err(|err| err.to_string())?;
+        }
+    }
+    loop {
+        match scratch.run_with_budget(&mut budget) {
+            Ok(Some(VmStatus::Halted)) => break,
+            Ok(Some(VmStatus::Yielded)) => continue,
+            Ok(None) => {
+                return Err(format!(
+                    "did not finish within {EXPRESSION_STEP_BUDGET} instructions"
+                ));
+            }
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+    Ok(scratch.stack().last().cloned().unwrap_or(Value::Null))
+}
+
+/// Name and index of each named local in scope, in declaration order.
+fn locals_in_scope(vm: &Vm) -> Vec<(String, u8)> {
+    let mut locals = Vec::<(String, u8)>::new();
+    if let Some(info) = vm.debug_info() {
+        for local in info.locals_at(vm.ip()) {
+            // Like `print`, a name declared more than once means its first local.
+            if locals.iter().any(|(name, _)| *name == local.name) {
+                continue;
+            }
+            if (local.index as usize) < vm.locals().len() {
+                locals.push((local.name.clone(), local.index));
+            }
+        }
+    }
+    locals
+}
+
+/// Current value of each named local in scope, in declaration order.
+fn named_locals(vm: &Vm) -> Vec<(String, Value)> {
+    locals_in_scope(vm)
+        .into_iter()
+        .map(|(name, index)| (name, vm.locals()[index as usize].clone()))
+        .collect()
+}
+
+fn is_truthy(value: &Value) -> bool {
+    !matches!(value, Value::Null | Value::Bool(false))
+}
+
+/// Replaces each `{expr}` in a logpoint message with its value as `evaluate`
+/// computes it; `{{` and `}}` stand for literal braces.
+fn interpolate_log_message(
+    template: &str,
+    mut evaluate: impl FnMut(&str) -> Result<Value, String>,
+) -> String {
+    let mut out = String::new();
+    let mut chars = template.char_indices().peekable();
+    while let Some((start, ch)) = chars.next() {
+        match ch {
+            '{' if chars.peek().is_some_and(|(_, next)| *next == '{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek().is_some_and(|(_, next)| *next == '}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut depth = 1usize;
+                let mut end = None;
+                for (index, ch) in chars.by_ref() {
+                    match ch {
+                        '{' => depth += 1,
+                        '}' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                end = Some(index);
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                let Some(end) = end else {
+                    out.push_str(&template[start..]);
+                    break;
+                };
+                match evaluate(&template[start + 1..end]) {
+                    Ok(value) => out.push_str(&format_log_value(&value)),
+                    Err(message) => out.push_str(&format!("<error: {message}>")),
+                }
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Scalars print bare in log messages; containers print like `locals`.
+fn format_log_value(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Int(value) => value.to_string(),
+        Value::Float(value) => value.to_string(),
+        Value::Bool(value) => value.to_string(),
+        Value::String(text) => text.clone(),
+        other => format_local_value(other),
+    }
+}
+
+fn format_args_list(func: &crate::debug_info::DebugFunction) -> String {
+    let mut parts = Vec::new();
+    for arg in &func.args {
+        parts.push(format!("{}:{}", arg.position, arg.name));
+    }
+    parts.join(", ")
+}
+
+fn print_locals(vm: &Vm, out: &mut dyn Write) {
+    let Some(info) = vm.debug_info() else {
+        let _ = writeln!(out, "locals: {:?}", vm.locals());
+        return;
+    };
+
+    let named = info.locals_at(vm.ip());
+    if named.is_empty() {
+        let _ = writeln!(out, "locals: {:?}", vm.locals());
+        return;
+    }
+
+    for local in named {
+        match vm.locals().get(local.index as usize) {
+            Some(value) => {
+                let _ = writeln!(out, "{} = {}", local.name, format_local_value(value));
+            }
+            None => {
+                let _ = writeln!(out, "{} = <unavailable>", local.name);
+            }
+        }
+    }
+}
+
+fn print_local_by_name(vm: &Vm, name: &str, out: &mut dyn Write) {
+    let Some(info) = vm.debug_info() else {
+        let _ = writeln!(out, "no debug info");
+        return;
+    };
+
+    let Some(index) = info.local_index_at(vm.ip(), name) else {
+        let _ = writeln!(out, "unknown local '{name}'");
+        return;
+    };
+
+    match vm.locals().get(index as usize) {
+        Some(value) => {
+            let _ = writeln!(out, "{name} = {}", format_local_value(value));
+        }
+        None => {
+            let _ = writeln!(out, "local '{name}' is out of range for this VM instance");
+        }
+    }
+}
+
+/// Renders a local like `{:?}`, except that struct and enum values are shown
+/// by their declared shape (`Point { x: Int(1) }`, `Shape::Circle(Int(2))`)
+/// rather than as the tagged maps they are stored as.
+fn format_local_value(value: &Value) -> String {
+    let mut out = String::new();
+    write_local_value(value, &mut out);
+    out
+}
+
+fn write_local_value(value: &Value, out: &mut String) {
+    match value {
+        Value::Array(items) => {
+            out.push_str("Array([");
+            for (position, item) in items.iter().enumerate() {
+                if position > 0 {
+                    out.push_str(", ");
+                }
+                write_local_value(item, out);
+            }
+            out.push_str("])");
+        }
+        Value::Map(entries) => match entries.first() {
+            Some((Value::String(key), Value::String(tag))) if key == TYPE_TAG_KEY => {
+                write_tagged_value(tag, &entries[1..], out);
+            }
+            _ => {
+                out.push_str("Map([");
+                for (position, (key, item)) in entries.iter().enumerate() {
+                    if position > 0 {
+                        out.push_str(", ");
+                    }
+                    out.push('(');
+                    write_local_value(key, out);
+                    out.push_str(", ");
+                    write_local_value(item, out);
+                    out.push(')');
+                }
+                out.push_str("])");
+            }
+        },
+        other => out.push_str(&format!("{other:?}")),
+    }
+}
+
+fn write_tagged_value(tag: &str, fields: &[(Value, Value)], out: &mut String) {
+    out.push_str(tag);
+    if fields.is_empty() {
+        return;
+    }
+    let is_tuple = fields
+        .iter()
+        .enumerate()
+        .all(|(position, (key, _))| matches!(key, Value::Int(index) if *index == position as i64));
+    if is_tuple {
+        out.push('(');
+        for (position, (_, item)) in fields.iter().enumerate() {
+            if position > 0 {
+                out.push_str(", ");
+            }
+            write_local_value(item, out);
+        }
+        out.push(')');
+        return;
+    }
+    out.push_str(" { ");
+    for (position, (key, item)) in fields.iter().enumerate() {
+        if position > 0 {
+            out.push_str(", ");
+        }
+        match key {
+            Value::String(name) => out.push_str(name),
+            other => write_local_value(other, out),
+        }
+        out.push_str(": ");
+        write_local_value(item, out);
+    }
+    out.push_str(" }");
+}
+
+pub fn attach_with_debugger(vm: &mut Vm, debugger: &mut Debugger) {
+    debugger.on_instruction(vm);
+}
+
+pub fn debug_info_from_vm(vm: &Vm) -> Option<&DebugInfo> {
+    vm.debug_info()
+}
+
+/// Current line in whichever file it belongs to, with the file's name when
+/// that is an imported module rather than the main source.
+fn current_location(vm: &Vm) -> (Option<u32>, Option<String>) {
+    vm.debug_info()
+        .map(|info| location_at(info, vm.ip()))
+        .unwrap_or((None, None))
+}
+
+fn location_at(info: &DebugInfo, ip: usize) -> (Option<u32>, Option<String>) {
+    match info.location_for_offset(ip) {
+        Some(location) if location.source_id != MAIN_SOURCE_ID => (
+            Some(location.line),
+            info.source_name(location.source_id).map(str::to_string),
+        ),
+        Some(location) => (Some(location.line), None),
+        None => (None, None),
+    }
+}
+
+fn write_where(info: &DebugInfo, ip: usize, out: &mut dyn Write) {
+    let Some(location) = info.location_for_offset(ip) else {
+        let _ = writeln!(out, "line: unknown");
+        return;
+    };
+    let place = info.describe_location(location);
+    match info.source_line_in(location.source_id, location.line) {
+        Some(text) => {
+            let _ = writeln!(out, "{place}: {text}");
+        }
+        None if location.source_id == MAIN_SOURCE_ID => {
+            let _ = writeln!(out, "line: {}", location.line);
+        }
+        None => {
+            let _ = writeln!(out, "{place}");
+        }
+    }
+}
+
+struct SourceBreakpoint {
+    name: String,
+    line: u32,
+    offsets: Vec<u32>,
+}
+
+/// Logical call stack at `ip`, innermost first; inlined calls leave no
+/// frames on the VM, so this comes from the debug info alone.
+fn write_call_stack(info: &DebugInfo, ip: usize, out: &mut dyn Write) {
+    let stack = info.logical_stack(ip);
+    if stack.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "calls:");
+    for (depth, frame) in stack.iter().enumerate() {
+        let _ = writeln!(out, "  #{depth} {}", info.describe_frame(frame));
+    }
+}
+
+/// Offsets of a `file:line` breakpoint spec, or `None` when `spec` is not one.
+fn resolve_source_breakpoint(
+    info: Option<&DebugInfo>,
+    spec: &str,
+) -> Option<Result<SourceBreakpoint, String>> {
+    let (file, line) = spec.rsplit_once(':')?;
+    let line = line.parse::<u32>().ok()?;
+    let Some(info) = info else {
+        return Some(Err("no debug info".to_string()));
+    };
+    let Some(source_id) = info.find_source(file) else {
+        return Some(Err(format!("unknown source '{file}'")));
+    };
+    let name = info.source_name(source_id).unwrap_or(file).to_string();
+    let offsets = info.offsets_for_source_line(source_id, line);
+    if offsets.is_empty() {
+        return Some(Err(format!("no code at {name}:{line}")));
+    }
+    Some(Ok(SourceBreakpoint {
+        name,
+        line,
+        offsets,
+    }))
+}
+
+fn parse_u32(token: Option<&str>) -> Option<u32> {
+    token.and_then(|value| value.parse::<u32>().ok())
+}
+
+pub fn replay_recording_stdio(recording: &VmRecording) {
+    if recording.frames.is_empty() {
+        println!("recording has no captured frames");
+        return;
+    }
+
+    let mut cursor = 0usize;
+    let mut replay_breakpoints = ReplayBreakpoints::default();
+    println!(
+        "recording loaded: frames={}, terminal={:?}",
+        recording.frames.len(),
+        recording.terminal_status
+    );
+    if let Some(seed) = recording.rng_seed {
+        println!("recording rng seed: {seed} (rerun with --seed {seed} to reproduce)");
+    }
+    let _ = write_replay_position(recording, cursor, &mut io::stdout());
+
+    let stdin = io::stdin();
+    let mut input = String::new();
+    loop {
+        input.clear();
+        if replay_at_end(recording, cursor) {
+            print!("(pdb-rec:end) ");
+        } else {
+            print!("(pdb-rec) ");
+        }
+        let _ = io::stdout().flush();
+        if stdin.read_line(&mut input).is_err() {
+            break;
+        }
+        if handle_replay_command(
+            &input,
+            recording,
+            &mut cursor,
+            &mut replay_breakpoints,
+            &mut io::stdout(),
+        )
+        .is_exit()
+        {
+            break;
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ReplayAction {
+    Continue,
+    Exit,
+}
+
+impl ReplayAction {
+    fn is_exit(self) -> bool {
+        matches!(self, ReplayAction::Exit)
+    }
+}
+
+fn handle_replay_command(
+    line: &str,
+    recording: &VmRecording,
+    cursor: &mut usize,
+    replay_breakpoints: &mut ReplayBreakpoints,
+    out: &mut dyn Write,
+) -> ReplayAction {
+    let mut parts = line.split_whitespace();
+    let Some(cmd) = parts.next() else {
+        return ReplayAction::Continue;
+    };
+    match cmd {
+        "q" | "quit" | "exit" => return ReplayAction::Exit,
+        "c" | "continue" => {
+            if let Some(next) =
+                replay_breakpoints.next_pause_frame(recording, cursor.saturating_add(1))
+            {
+                *cursor = next;
+                replay_breakpoints.consume_pause_at_frame(recording, *cursor);
+            } else {
+                *cursor = recording.frames.len().saturating_sub(1);
+            }
+            let _ = write_replay_position(recording, *cursor, out);
+            return ReplayAction::Continue;
+        }
+        "s" | "step" | "stepi" => {
+            if *cursor + 1 < recording.frames.len() {
+                *cursor += 1;
+            }
+            let _ = write_replay_position(recording, *cursor, out);
+            return ReplayAction::Continue;
+        }
+        "n" | "next" => {
+            *cursor = replay_step_over(recording, *cursor);
+            let _ = write_replay_position(recording, *cursor, out);
+            return ReplayAction::Continue;
+        }
+        "finish" | "out" => {
+            *cursor = replay_step_out(recording, *cursor);
+            let _ = write_replay_position(recording, *cursor, out);
+            return ReplayAction::Continue;
+        }
+        "b" | "break" => {
+            if let Some(arg) = parts.next() {
+                if arg == "line" {
+                    if let Some(line) = parse_u32(parts.next()) {
+                        replay_breakpoints.line_breakpoints.insert(line);
+                        let _ = writeln!(out, "replay pause point set at line {line}");
+                    } else {
+                        let _ = writeln!(out, "usage: break line <number>");
+                    }
+                    return ReplayAction::Continue;
+                }
+                if let Some(resolved) =
+                    resolve_source_breakpoint(recording.program.debug.as_ref(), arg)
+                {
+                    match resolved {
+                        Ok(SourceBreakpoint {
+                            name,
+                            line,
+                            offsets,
+                        }) => {
+                            replay_breakpoints
+                                .offset_breakpoints
+                                .extend(offsets.iter().map(|offset| *offset as usize));
+                            let _ = writeln!(out, "replay pause point set at {name}:{line}");
+                        }
+                        Err(message) => {
+                            let _ = writeln!(out, "{message}");
+                        }
+                    }
+                    return ReplayAction::Continue;
+                }
+                if let Ok(offset) = arg.parse::<usize>() {
+                    replay_breakpoints.offset_breakpoints.insert(offset);
+                    let _ = writeln!(out, "replay pause point set at offset {offset}");
+                } else {
+                    let _ = writeln!(out, "expected instruction offset");
+                }
+            } else {
+                let _ = writeln!(out, "usage: break <offset|file:line>");
+            }
+            return ReplayAction::Continue;
+        }
+        "bl" => {
+            if let Some(line) = parse_u32(parts.next()) {
+                replay_breakpoints.line_breakpoints.insert(line);
+                let _ = writeln!(out, "replay pause point set at line {line}");
+            } else {
+                let _ = writeln!(out, "usage: bl <line>");
+            }
+            return ReplayAction::Continue;
+        }
+        "clear" => {
+            if let Some(arg) = parts.next() {
+                if arg == "line" {
+                    if let Some(line) = parse_u32(parts.next()) {
+                        replay_breakpoints.line_breakpoints.remove(&line);
+                        let _ = writeln!(out, "replay pause point cleared at line {line}");
+                    } else {
+                        let _ = writeln!(out, "usage: clear line <number>");
+                    }
+                    return ReplayAction::Continue;
+                }
+                if let Some(resolved) =
+                    resolve_source_breakpoint(recording.program.debug.as_ref(), arg)
+                {
+                    match resolved {
+                        Ok(SourceBreakpoint {
+                            name,
+                            line,
+                            offsets,
+                        }) => {
+                            for offset in offsets {
+                                replay_breakpoints
+                                    .offset_breakpoints
+                                    .remove(&(offset as usize));
+                            }
+                            let _ = writeln!(out, "replay pause point cleared at {name}:{line}");
+                        }
+                        Err(message) => {
+                            let _ = writeln!(out, "{message}");
+                        }
+                    }
+                    return ReplayAction::Continue;
+                }
+                if let Ok(offset) = arg.parse::<usize>() {
+                    replay_breakpoints.offset_breakpoints.remove(&offset);
+                    let _ = writeln!(out, "replay pause point cleared at offset {offset}");
+                } else {
+                    let _ = writeln!(out, "expected instruction offset");
+                }
+            } else {
+                let _ = writeln!(out, "usage: clear <offset|file:line>");
+            }
+            return ReplayAction::Continue;
+        }
+        "cl" => {
+            if let Some(line) = parse_u32(parts.next()) {
+                replay_breakpoints.line_breakpoints.remove(&line);
+                let _ = writeln!(out, "replay pause point cleared at line {line}");
+            } else {
+                let _ = writeln!(out, "usage: cl <line>");
+            }
+            return ReplayAction::Continue;
+        }
+        "breaks" => {
+            let _ = writeln!(
+                out,
+                "replay pause offsets: {:?}",
+                replay_breakpoints.offset_breakpoints
+            );
+            let _ = writeln!(
+                out,
+                "replay pause lines: {:?}",
+                replay_breakpoints.line_breakpoints
+            );
+            return ReplayAction::Continue;
+        }
+        _ => {}
+    }
+
+    let frame = &recording.frames[*cursor];
+    match cmd {
+        "stack" => {
+            let _ = writeln!(out, "stack: {:?}", frame.stack);
+            if let Some(info) = recording.program.debug.as_ref() {
+                write_call_stack(info, frame.ip, out);
+            }
+        }
+        "locals" => {
+            print_replay_locals(recording, frame, out);
+        }
+        "p" | "print" => {
+            if let Some(name) = parts.next() {
+                print_replay_local_by_name(recording, frame, name, out);
+            } else {
+                let _ = writeln!(out, "usage: print <local_name>");
+            }
+        }
+        "ip" => {
+            let _ = writeln!(out, "ip: {}", frame.ip);
+        }
+        "where" => {
+            print_replay_where(recording, frame, out);
+        }
+        "funcs" => {
+            if let Some(info) = recording.program.debug.as_ref() {
+                for func in &info.functions {
+                    let _ = writeln!(out, "fn {}({})", func.name, format_args_list(func));
+                }
+            } else {
+                let _ = writeln!(out, "no debug info");
+            }
+        }
+        "help" => {
+            let _ = writeln!(
+                out,
+                "commands: break, break line, bl, clear, clear line, cl, breaks, continue, step, next, out, stack, locals, print, ip, where, funcs, help, quit"
+            );
+        }
+        _ => {
+            let _ = writeln!(out, "unknown command");
+        }
+    }
+    ReplayAction::Continue
+}
+
+fn replay_step_over(recording: &VmRecording, cursor: usize) -> usize {
+    if cursor + 1 >= recording.frames.len() {
+        return cursor;
+    }
+    let start = &recording.frames[cursor];
+    for index in (cursor + 1)..recording.frames.len() {
+        let frame = &recording.frames[index];
+        if frame.call_depth <= start.call_depth && frame.ip != start.ip {
+            return index;
+        }
+    }
+    recording.frames.len().saturating_sub(1)
+}
+
+fn replay_step_out(recording: &VmRecording, cursor: usize) -> usize {
+    if cursor + 1 >= recording.frames.len() {
+        return cursor;
+    }
+    let start = &recording.frames[cursor];
+    for index in (cursor + 1)..recording.frames.len() {
+        let frame = &recording.frames[index];
+        if frame.call_depth < start.call_depth {
+            return index;
+        }
+    }
+    recording.frames.len().saturating_sub(1)
+}
+
+fn write_replay_position(
+    recording: &VmRecording,
+    cursor: usize,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let last = recording.frames.len().saturating_sub(1);
+    let frame = &recording.frames[cursor];
+    write!(out, "frame {cursor}/{last}")?;
+    if replay_at_end(recording, cursor) {
+        match recording.terminal_status {
+            Some(status) => write!(out, " [end:{status:?}]")?,
+            None => write!(out, " [end]")?,
+        }
+    }
+    write!(out, ": ip={} depth={}", frame.ip, frame.call_depth)?;
+    if let Some(info) = recording.program.debug.as_ref()
+        && let Some(location) = info.location_for_offset(frame.ip)
+    {
+        let place = info.describe_location(location);
+        if let Some(text) = info.source_line_in(location.source_id, location.line) {
+            writeln!(out, " {place}: {text}")?;
+            return Ok(());
+        }
+        if location.source_id == MAIN_SOURCE_ID {
+            writeln!(out, " line: {}", location.line)?;
+        } else {
+            writeln!(out, " {place}")?;
+        }
+        return Ok(());
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+fn replay_at_end(recording: &VmRecording, cursor: usize) -> bool {
+    cursor + 1 >= recording.frames.len()
+}
+
+#[derive(Default)]
+struct ReplayBreakpoints {
+    offset_breakpoints: HashSet<usize>,
+    line_breakpoints: HashSet<u32>,
+}
+
+impl ReplayBreakpoints {
+    fn next_pause_frame(&self, recording: &VmRecording, start_index: usize) -> Option<usize> {
+        if self.offset_breakpoints.is_empty() && self.line_breakpoints.is_empty() {
+            return None;
+        }
+        for index in start_index..recording.frames.len() {
+            let frame = &recording.frames[index];
+            if self.offset_breakpoints.contains(&frame.ip) {
+                return Some(index);
+            }
+            if let Some(info) = recording.program.debug.as_ref()
+                && let Some(line) = info.main_line_for_offset(frame.ip)
+                && self.line_breakpoints.contains(&line)
+            {
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    fn consume_pause_at_frame(&mut self, recording: &VmRecording, frame_index: usize) {
+        let Some(frame) = recording.frames.get(frame_index) else {
+            return;
+        };
+        self.offset_breakpoints.remove(&frame.ip);
+        if let Some(info) = recording.program.debug.as_ref()
+            && let Some(line) = info.main_line_for_offset(frame.ip)
+        {
+            self.line_breakpoints.remove(&line);
+        }
+    }
+}
+
+pub fn run_recording_replay_command(
+    recording: &VmRecording,
+    state: &mut VmRecordingReplayState,
+    command: &str,
+) -> VmRecordingReplayResponse {
+    if recording.frames.is_empty() {
+        return VmRecordingReplayResponse {
+            output: "recording has no captured frames".to_string(),
+            current_line: None,
+            current_source: None,
+            at_end: true,
+            exited: false,
+        };
+    }
+
+    if state.cursor >= recording.frames.len() {
+        state.cursor = recording.frames.len().saturating_sub(1);
+    }
+
+    let mut replay_breakpoints = ReplayBreakpoints {
+        offset_breakpoints: state.offset_breakpoints.clone(),
+        line_breakpoints: state.line_breakpoints.clone(),
+    };
+    let mut output = Vec::<u8>::new();
+    let action = handle_replay_command(
+        command,
+        recording,
+        &mut state.cursor,
+        &mut replay_breakpoints,
+        &mut output,
+    );
+
+    state.offset_breakpoints = replay_breakpoints.offset_breakpoints;
+    state.line_breakpoints = replay_breakpoints.line_breakpoints;
+    let (current_line, current_source) = replay_current_location(recording, state.cursor);
+
+    VmRecordingReplayResponse {
+        output: String::from_utf8_lossy(&output).to_string(),
+        current_line,
+        current_source,
+        at_end: replay_at_end(recording, state.cursor),
+        exited: action.is_exit(),
+    }
+}
+
+fn replay_current_location(
+    recording: &VmRecording,
+    cursor: usize,
+) -> (Option<u32>, Option<String>) {
+    match (
+        recording.frames.get(cursor),
+        recording.program.debug.as_ref(),
+    ) {
+        (Some(frame), Some(info)) => location_at(info, frame.ip),
+        _ => (None, None),
+    }
+}
+
+fn print_replay_locals(recording: &VmRecording, frame: &VmRecordingFrame, out: &mut dyn Write) {
+    let Some(info) = recording.program.debug.as_ref() else {
+        let _ = writeln!(out, "locals: {:?}", frame.locals);
+        return;
+    };
+
+    let named = info.locals_at(frame.ip);
+    if named.is_empty() {
+        let _ = writeln!(out, "locals: {:?}", frame.locals);
+        return;
+    }
+
+    for local in named {
+        match frame.locals.get(local.index as usize) {
+            Some(value) => {
+                let _ = writeln!(out, "{} = {}", local.name, format_local_value(value));
+            }
+            None => {
+                let _ = writeln!(out, "{} = <unavailable>", local.name);
+            }
+        }
+    }
+}
+
+fn print_replay_local_by_name(
+    recording: &VmRecording,
+    frame: &VmRecordingFrame,
+    name: &str,
+    out: &mut dyn Write,
+) {
+    let Some(info) = recording.program.debug.as_ref() else {
+        let _ = writeln!(out, "no debug info");
+        return;
+    };
+
+    let Some(index) = info.local_index_at(frame.ip, name) else {
+        let _ = writeln!(out, "unknown local '{name}'");
+        return;
+    };
+
+    match frame.locals.get(index as usize) {
+        Some(value) => {
+            let _ = writeln!(out, "{name} = {}", format_local_value(value));
+        }
+        None => {
+            let _ = writeln!(out, "local '{name}' is out of range for this frame");
+        }
+    }
+}
+
+fn print_replay_where(recording: &VmRecording, frame: &VmRecordingFrame, out: &mut dyn Write) {
+    if let Some(info) = recording.program.debug.as_ref() {
+        write_where(info, frame.ip, out);
+    } else {
+        let _ = writeln!(out, "no debug info");
+    }
+}
+
+fn write_u32_len(len: usize, out: &mut Vec<u8>) -> Result<(), VmRecordingError> {
+    let value = u32::try_from(len)
+        .map_err(|_| VmRecordingError::Message(format!("length too large: {len}")))?;
+    out.extend_from_slice(&value.to_le_bytes());
+    Ok(())
+}
+
+fn write_u32_from_usize(value: usize, out: &mut Vec<u8>) -> Result<(), VmRecordingError> {
+    let value = u32::try_from(value)
+        .map_err(|_| VmRecordingError::Message(format!("value too large: {value}")))?;
+    out.extend_from_slice(&value.to_le_bytes());
+    Ok(())
+}
+
+fn encode_value(value: &Value, out: &mut Vec<u8>) -> Result<(), VmRecordingError> {
+    match value {
+        Value::Null => out.push(0),
+        Value::Int(value) => {
+            out.push(1);
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        Value::Float(value) => {
+            out.push(2);
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        Value::Bool(value) => {
+            out.push(3);
+            out.push(u8::from(*value));
+        }
+        Value::String(value) => {
+            out.push(4);
+            write_u32_len(value.len(), out)?;
+            out.extend_from_slice(value.as_bytes());
+        }
+        Value::Array(values) => {
+            out.push(5);
+            write_u32_len(values.len(), out)?;
+            for value in values {
+                encode_value(value, out)?;
+            }
+        }
+        Value::Map(entries) => {
+            out.push(6);
+            write_u32_len(entries.len(), out)?;
+            for (key, value) in entries {
+                encode_value(key, out)?;
+                encode_value(value, out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn decode_value(cursor: &mut RecordingCursor<'_>) -> Result<Value, VmRecordingError> {
+    let tag = cursor.read_u8()?;
+    match tag {
+        0 => Ok(Value::Null),
+        1 => Ok(Value::Int(cursor.read_i64()?)),
+        2 => Ok(Value::Float(cursor.read_f64()?)),
+        3 => match cursor.read_u8()? {
+            0 => Ok(Value::Bool(false)),
+            1 => Ok(Value::Bool(true)),
+            _ => Err(VmRecordingError::InvalidFormat("invalid bool value")),
+        },
+        4 => {
+            let len = cursor.read_u32()? as usize;
+            let bytes = cursor.read_exact(len)?;
+            let text = String::from_utf8(bytes.to_vec())
+                .map_err(|_| VmRecordingError::InvalidFormat("invalid utf-8 string"))?;
+            Ok(Value::String(text))
+        }
+        5 => {
+            let len = cursor.read_u32()? as usize;
+            let mut values = Vec::with_capacity(len);
+            for _ in 0..len {
+                values.push(decode_value(cursor)?);
+            }
+            Ok(Value::Array(values))
+        }
+        6 => {
+            let len = cursor.read_u32()? as usize;
+            let mut entries = Vec::with_capacity(len);
+            for _ in 0..len {
+                let key = decode_value(cursor)?;
+                let value = decode_value(cursor)?;
+                entries.push((key, value));
+            }
+            Ok(Value::Map(entries))
+        }
+        _ => Err(VmRecordingError::InvalidFormat("invalid value tag")),
+    }
+}
+
+struct RecordingCursor<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> RecordingCursor<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, offset: 0 }
+    }
+
+    fn is_eof(&self) -> bool {
+        self.offset == self.bytes.len()
+    }
+
+    fn read_exact(&mut self, len: usize) -> Result<&'a [u8], VmRecordingError> {
+        if self.offset.saturating_add(len) > self.bytes.len() {
+            return Err(VmRecordingError::InvalidFormat(
+                "unexpected end of recording",
+            ));
+        }
+        let start = self.offset;
+        self.offset += len;
+        Ok(&self.bytes[start..self.offset])
+    }
+
+    fn read_u8(&mut self) -> Result<u8, VmRecordingError> {
+        Ok(self.read_exact(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, VmRecordingError> {
+        let bytes = self.read_exact(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, VmRecordingError> {
+        let bytes = self.read_exact(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, VmRecordingError> {
+        let bytes = self.read_exact(8)?;
+        Ok(u64::from_le_bytes([
+            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+        ]))
+    }
+
+    fn read_i64(&mut self) -> Result<i64, VmRecordingError> {
+        let bytes = self.read_exact(8)?;
+        Ok(i64::from_le_bytes([
+            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+        ]))
+    }
+
+    fn read_f64(&mut self) -> Result<f64, VmRecordingError> {
+        let bytes = self.read_exact(8)?;
+        Ok(f64::from_le_bytes([
+            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::debug_info::{DebugInfo, LocalInfo};
+    use crate::vm::{Program, Value, Vm, VmStatus};
+
+    use super::{
+        BreakpointOptions, Breakpoints, DebugCommandBridge, Debugger, EXPRESSION_STEP_BUDGET,
+        ReplAction, ReplayBreakpoints, StepMode, VmRecording, VmRecordingFrame,
+        describe_breakpoint_options, handle_command, handle_replay_command,
+        parse_breakpoint_options,
+    };
+
+    fn vm_with_named_local(name: &str, value: Value) -> Vm {
+        let program = Program::with_debug(
+            vec![value],
+            vec![
+                crate::vm::OpCode::Ldc as u8,
+                0,
+                0,
+                0,
+                0,
+                crate::vm::OpCode::Stloc as u8,
+                0,
+                crate::vm::OpCode::Ret as u8,
+            ],
+            Some(DebugInfo {
+                sources: Vec::new(),
+                lines: vec![],
+                functions: vec![],
+                locals: vec![LocalInfo {
+                    name: name.to_string(),
+                    index: 0,
+                }],
+                inline_frames: Vec::new(),
+            }),
+        );
+        let mut vm = Vm::with_locals(program, 1);
+        let status = vm.run().expect("vm should run");
+        assert_eq!(status, crate::vm::VmStatus::Halted);
+        vm
+    }
+
+    fn vm_with_named_unassigned_local(name: &str) -> Vm {
+        let program = Program::with_debug(
+            vec![],
+            vec![crate::vm::OpCode::Ret as u8],
+            Some(DebugInfo {
+                sources: Vec::new(),
+                lines: vec![],
+                functions: vec![],
+                locals: vec![LocalInfo {
+                    name: name.to_string(),
+                    index: 0,
+                }],
+                inline_frames: Vec::new(),
+            }),
+        );
+        Vm::with_locals(program, 1)
+    }
+
+    #[test]
+    fn print_local_by_name_uses_debug_name() {
+        let mut vm = vm_with_named_local("counter", Value::Int(42));
+        let mut out = Vec::<u8>::new();
+        let mut breakpoints = Breakpoints::default();
+        let mut step_mode = StepMode::Running;
+
+        let action = handle_command(
+            "print counter",
+            &mut vm,
+            &mut breakpoints,
+            &mut step_mode,
+            None,
+            &mut out,
+        );
+        assert_eq!(action, ReplAction::Continue);
+        let text = String::from_utf8(out).expect("output should be utf-8");
+        assert!(text.contains("counter = Int(42)"));
+    }
+
+    #[test]
+    fn locals_show_struct_and_enum_values_by_shape() {
+        let tagged = |tag: &str, fields: Vec<(Value, Value)>| {
+            let mut entries = vec![(
+                Value::String("__type".to_string()),
+                Value::String(tag.to_string()),
+            )];
+            entries.extend(fields);
+            Value::Map(entries)
+        };
+        let point = tagged(
+            "Point",
+            vec![
+                (Value::String("x".to_string()), Value::Int(1)),
+                (Value::String("y".to_string()), Value::Int(2)),
+            ],
+        );
+        let value = Value::Array(vec![
+            tagged("Shape::Circle", vec![(Value::Int(0), point)]),
+            tagged("Color::Red", Vec::new()),
+            Value::Map(vec![(Value::String("plain".to_string()), Value::Null)]),
+        ]);
+        let mut vm = vm_with_named_local("shapes", value);
+        let mut out = Vec::<u8>::new();
+        let mut breakpoints = Breakpoints::default();
+        let mut step_mode = StepMode::Running;
+
+        handle_command(
+            "locals",
+            &mut vm,
+            &mut breakpoints,
+            &mut step_mode,
+            None,
+            &mut out,
+        );
+        let text = String::from_utf8(out).expect("output should be utf-8");
+        assert!(
+            text.contains(
+                "shapes = Array([Shape::Circle(Point { x: Int(1), y: Int(2) }), Color::Red, Map([(String(\"plain\"), Null)])])"
+            ),
+            "{text}"
+        );
+    }
+
+    #[test]
+    fn print_local_by_name_reports_unknown_local() {
+        let mut vm = vm_with_named_local("counter", Value::Int(42));
+        let mut out = Vec::<u8>::new();
+        let mut breakpoints = Breakpoints::default();
+        let mut step_mode = StepMode::Running;
+
+        handle_command(
+            "p missing",
+            &mut vm,
+            &mut breakpoints,
+            &mut step_mode,
+            None,
+            &mut out,
+        );
+        let text = String::from_utf8(out).expect("output should be utf-8");
+        assert!(text.contains("unknown local 'missing'"));
+    }
+
+    #[test]
+    fn print_local_by_name_shows_null_for_unassigned_local() {
+        let mut vm = vm_with_named_unassigned_local("counter");
+        let mut out = Vec::<u8>::new();
+        let mut breakpoints = Breakpoints::default();
+        let mut step_mode = StepMode::Running;
+
+        handle_command(
+            "p counter",
+            &mut vm,
+            &mut breakpoints,
+            &mut step_mode,
+            None,
+            &mut out,
+        );
+        let text = String::from_utf8(out).expect("output should be utf-8");
+        assert!(text.contains("counter = Null"));
+    }
+
+    #[test]
+    fn where_and_break_resolve_imported_module_lines() {
+        let program = Program::with_debug(
+            vec![Value::Int(1)],
+            vec![
+                crate::vm::OpCode::Ldc as u8,
+                0,
+                0,
+                0,
+                0,
+                crate::vm::OpCode::Ret as u8,
+            ],
+            Some(DebugInfo {
+                sources: vec![
+                    crate::debug_info::DebugSource {
+                        name: "app/main.rss".to_string(),
+                        text: Some("use helpers;\nlet y = double(21);".to_string()),
+                    },
+                    crate::debug_info::DebugSource {
+                        name: "app/helpers.rss".to_string(),
+                        text: Some("pub fn double(x) {\n    x * 2;\n}".to_string()),
+                    },
+                ],
+                lines: vec![
+                    crate::debug_info::LineInfo {
+                        offset: 0,
+                        source_id: 1,
+                        line: 2,
+                        column: 5,
+                    },
+                    crate::debug_info::LineInfo {
+                        offset: 5,
+                        source_id: 0,
+                        line: 2,
+                        column: 1,
+                    },
+                ],
+                functions: vec![],
+                locals: vec![],
+                inline_frames: Vec::new(),
+            }),
+        );
+        let mut vm = Vm::new(program);
+        let mut out = Vec::new();
+        let mut breakpoints = Breakpoints::default();
+        let mut step_mode = StepMode::Running;
+
+        for command in ["where", "break helpers.rss:2", "break missing.rss:1"] {
+            handle_command(
+                command,
+                &mut vm,
+                &mut breakpoints,
+                &mut step_mode,
+                None,
+                &mut out,
+            );
+        }
+        let text = String::from_utf8(out).expect("output should be utf-8");
+        assert!(text.contains("app/helpers.rss:2:     x * 2;"), "{text}");
+        assert!(
+            text.contains("breakpoint set at app/helpers.rss:2"),
+            "{text}"
+        );
+        assert!(text.contains("unknown source 'missing.rss'"), "{text}");
+        assert_eq!(
+            breakpoints.offsets.keys().copied().collect::<HashSet<_>>(),
+            HashSet::from([0])
+        );
+        assert!(breakpoints.lines.is_empty());
+        assert_eq!(
+            super::current_location(&vm),
+            (Some(2), Some("app/helpers.rss".to_string()))
+        );
+        assert_eq!(
+            super::statement_line_at(vm.debug_info().expect("debug info"), vm.ip()),
+            None
+        );
+    }
+
+    #[test]
+    fn stack_lists_inlined_calls_innermost_first() {
+        let program = Program::with_debug(
+            vec![],
+            vec![crate::vm::OpCode::Ret as u8],
+            Some(DebugInfo {
+                sources: vec![crate::debug_info::DebugSource {
+                    name: "<source>".to_string(),
+                    text: Some("fn inner() = 1;\nfn outer() = inner();\nouter();".to_string()),
+                }],
+                lines: vec![crate::debug_info::LineInfo {
+                    offset: 0,
+                    source_id: 0,
+                    line: 1,
+                    column: 1,
+                }],
+                functions: vec![],
+                locals: vec![],
+                inline_frames: vec![
+                    crate::debug_info::InlineFrame {
+                        start: 0,
+                        end: 1,
+                        function: "outer".to_string(),
+                        call_source: 0,
+                        call_line: 3,
+                        parent: None,
+                        locals: vec![],
+                    },
+                    crate::debug_info::InlineFrame {
+                        start: 0,
+                        end: 1,
+                        function: "inner".to_string(),
+                        call_source: 0,
+                        call_line: 2,
+                        parent: Some(0),
+                        locals: vec![],
+                    },
+                ],
+            }),
+        );
+        let mut vm = Vm::new(program);
+        let mut out = Vec::new();
+        let mut breakpoints = Breakpoints::default();
+        let mut step_mode = StepMode::Running;
+
+        handle_command(
+            "stack",
+            &mut vm,
+            &mut breakpoints,
+            &mut step_mode,
+            None,
+            &mut out,
+        );
+        let text = String::from_utf8(out).expect("output should be utf-8");
+        assert!(
+            text.contains(
+                "calls:\n  #0 inner at line 1: fn inner() = 1;\n  #1 outer at line 2: fn outer() = inner();\n  #2 <main> at line 3: outer();"
+            ),
+            "{text}"
+        );
+    }
+
+    #[test]
+    fn recording_encode_decode_roundtrip() {
+        let program = Program::with_debug(
+            vec![Value::Int(1)],
+            vec![crate::vm::OpCode::Ret as u8],
+            Some(DebugInfo {
+                sources: vec![crate::debug_info::DebugSource {
+                    name: "<source>".to_string(),
+                    text: Some("let x = 1;".to_string()),
+                }],
+                lines: vec![crate::debug_info::LineInfo {
+                    offset: 0,
+                    source_id: 0,
+                    line: 1,
+                    column: 0,
+                }],
+                functions: vec![],
+                locals: vec![LocalInfo {
+                    name: "x".to_string(),
+                    index: 0,
+                }],
+                inline_frames: Vec::new(),
+            }),
+        );
+        let recording = VmRecording {
+            program: program.clone(),
+            frames: vec![
+                VmRecordingFrame {
+                    ip: 0,
+                    call_depth: 0,
+                    stack: vec![Value::Array(vec![Value::Int(7), Value::Bool(true)])],
+                    locals: vec![Value::Map(vec![(
+                        Value::String("k".to_string()),
+                        Value::Int(9),
+                    )])],
+                },
+                VmRecordingFrame {
+                    ip: 1,
+                    call_depth: 0,
+                    stack: vec![Value::Int(42)],
+                    locals: vec![Value::Int(42)],
+                },
+            ],
+            terminal_status: Some(VmStatus::Halted),
+            rng_seed: Some(0xDEAD_BEEF),
+        };
+
+        let bytes = recording.encode().expect("encode should succeed");
+        let decoded = VmRecording::decode(&bytes).expect("decode should succeed");
+
+        assert_eq!(decoded.frames, recording.frames);
+        assert_eq!(decoded.terminal_status, recording.terminal_status);
+        assert_eq!(decoded.rng_seed, Some(0xDEAD_BEEF));
+        assert_eq!(decoded.program.code, program.code);
+        assert_eq!(decoded.program.constants, program.constants);
+        assert_eq!(decoded.program.imports, program.imports);
+        assert_eq!(decoded.program.debug, program.debug);
+    }
+
+    #[test]
+    fn recording_debugger_captures_initial_and_terminal_frames() {
+        let program = Program::new(vec![], vec![crate::vm::OpCode::Ret as u8]);
+        let mut vm = Vm::new(program.clone());
+        vm.set_rng_seed(7);
+        let mut debugger = Debugger::with_recording(program);
+
+        let status = vm
+            .run_with_debugger(&mut debugger)
+            .expect("recorded run should succeed");
+        assert_eq!(status, VmStatus::Halted);
+
+        let recording = debugger
+            .take_recording()
+            .expect("recording should be available");
+        assert!(recording.frames.len() >= 2);
+        assert_eq!(recording.frames.first().expect("first frame").ip, 0);
+        assert_eq!(recording.terminal_status, Some(VmStatus::Halted));
+        assert_eq!(recording.rng_seed, Some(7));
+    }
+
+    #[test]
+    fn replay_break_sets_pause_point_for_continue() {
+        let recording = VmRecording {
+            program: Program::new(vec![], vec![]),
+            frames: vec![
+                VmRecordingFrame {
+                    ip: 0,
+                    call_depth: 0,
+                    stack: vec![],
+                    locals: vec![],
+                },
+                VmRecordingFrame {
+                    ip: 1,
+                    call_depth: 0,
+                    stack: vec![],
+                    locals: vec![],
+                },
+                VmRecordingFrame {
+                    ip: 2,
+                    call_depth: 0,
+                    stack: vec![],
+                    locals: vec![],
+                },
+            ],
+            terminal_status: Some(VmStatus::Halted),
+            rng_seed: None,
+        };
+        let mut cursor = 0usize;
+        let mut replay_breakpoints = ReplayBreakpoints::default();
+        let mut out = Vec::<u8>::new();
+
+        handle_replay_command(
+            "break 1",
+            &recording,
+            &mut cursor,
+            &mut replay_breakpoints,
+            &mut out,
+        );
+        out.clear();
+        handle_replay_command(
+            "continue",
+            &recording,
+            &mut cursor,
+            &mut replay_breakpoints,
+            &mut out,
+        );
+        let text = String::from_utf8(out).expect("output should be utf-8");
+        assert!(text.contains("frame 1/2"));
+        assert_eq!(cursor, 1);
+    }
+
+    #[test]
+    fn replay_continue_marks_end_of_recording() {
+        let recording = VmRecording {
+            program: Program::new(vec![], vec![]),
+            frames: vec![
+                VmRecordingFrame {
+                    ip: 0,
+                    call_depth: 0,
+                    stack: vec![],
+                    locals: vec![],
+                },
+                VmRecordingFrame {
+                    ip: 1,
+                    call_depth: 0,
+                    stack: vec![],
+                    locals: vec![],
+                },
+            ],
+            terminal_status: Some(VmStatus::Halted),
+            rng_seed: None,
+        };
+        let mut cursor = 0usize;
+        let mut replay_breakpoints = ReplayBreakpoints::default();
+        let mut out = Vec::<u8>::new();
+
+        handle_replay_command(
+            "continue",
+            &recording,
+            &mut cursor,
+            &mut replay_breakpoints,
+            &mut out,
+        );
+
+        let text = String::from_utf8(out).expect("output should be utf-8");
+        assert!(text.contains("[end:Halted]"));
+        assert_eq!(cursor, 1);
+    }
+
+    #[test]
+    fn replay_break_line_sets_pause_point_for_continue() {
+        let program = Program::with_debug(
+            vec![],
+            vec![],
+            Some(DebugInfo {
+                sources: Vec::new(),
+                lines: vec![
+                    crate::debug_info::LineInfo {
+                        offset: 0,
+                        source_id: 0,
+                        line: 1,
+                        column: 0,
+                    },
+                    crate::debug_info::LineInfo {
+                        offset: 5,
+                        source_id: 0,
+                        line: 2,
+                        column: 0,
+                    },
+                ],
+                functions: vec![],
+                locals: vec![],
+                inline_frames: Vec::new(),
+            }),
+        );
+        let recording = VmRecording {
+            program,
+            frames: vec![
+                VmRecordingFrame {
+                    ip: 0,
+                    call_depth: 0,
+                    stack: vec![],
+                    locals: vec![],
+                },
+                VmRecordingFrame {
+                    ip: 5,
+                    call_depth: 0,
+                    stack: vec![],
+                    locals: vec![],
+                },
+                VmRecordingFrame {
+                    ip: 9,
+                    call_depth: 0,
+                    stack: vec![],
+                    locals: vec![],
+                },
+            ],
+            terminal_status: Some(VmStatus::Halted),
+            rng_seed: None,
+        };
+        let mut cursor = 0usize;
+        let mut replay_breakpoints = ReplayBreakpoints::default();
+        let mut out = Vec::<u8>::new();
+
+        handle_replay_command(
+            "break line 2",
+            &recording,
+            &mut cursor,
+            &mut replay_breakpoints,
+            &mut out,
+        );
+        out.clear();
+        handle_replay_command(
+            "continue",
+            &recording,
+            &mut cursor,
+            &mut replay_breakpoints,
+            &mut out,
+        );
+
+        let text = String::from_utf8(out).expect("output should be utf-8");
+        assert!(text.contains("frame 1/2"));
+        assert_eq!(cursor, 1);
+    }
+
+    #[test]
+    fn public_replay_api_updates_cursor_and_reports_line() {
+        let program = Program::with_debug(
+            vec![],
+            vec![],
+            Some(DebugInfo {
+                sources: Vec::new(),
+                lines: vec![crate::debug_info::LineInfo {
+                    offset: 0,
+                    source_id: 0,
+                    line: 11,
+                    column: 0,
+                }],
+                functions: vec![],
+                locals: vec![],
+                inline_frames: Vec::new(),
+            }),
+        );
+        let recording = VmRecording {
+            program,
+            frames: vec![
+                VmRecordingFrame {
+                    ip: 0,
+                    call_depth: 0,
+                    stack: vec![],
+                    locals: vec![],
+                },
+                VmRecordingFrame {
+                    ip: 5,
+                    call_depth: 0,
+                    stack: vec![],
+                    locals: vec![],
+                },
+            ],
+            terminal_status: Some(VmStatus::Halted),
+            rng_seed: None,
+        };
+        let mut state = super::VmRecordingReplayState::default();
+
+        let response = super::run_recording_replay_command(&recording, &mut state, "step");
+        assert!(!response.exited);
+        assert_eq!(state.cursor, 1);
+        assert!(response.at_end);
+        assert_eq!(response.current_line, Some(11));
+    }
+
+    const COUNTING_LOOP: &str =
+        "let total = 0;\nlet i = 0;\nwhile i < 5 {\n    total = total + i;\n    i = i + 1;\n}\n";
+
+    fn counting_loop_vm() -> Vm {
+        let compiled =
+            crate::compiler::compile_source(COUNTING_LOOP).expect("source should compile");
+        Vm::with_locals(compiled.program, compiled.locals)
+    }
+
+    #[test]
+    fn breakpoint_options_parse_in_any_order() {
+        assert_eq!(
+            parse_breakpoint_options("hits 2 if i > 1 && total < 9 log i is {i}"),
+            Ok(BreakpointOptions {
+                condition: Some("i > 1 && total < 9".to_string()),
+                hit_count: Some(2),
+                log_message: Some("i is {i}".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_breakpoint_options("if catalog > 1"),
+            Ok(BreakpointOptions {
+                condition: Some("catalog > 1".to_string()),
+                ..BreakpointOptions::default()
+            })
+        );
+        assert_eq!(
+            parse_breakpoint_options(""),
+            Ok(BreakpointOptions::default())
+        );
+        assert_eq!(
+            parse_breakpoint_options(r#"if {log > 1 && name != "}"} log log={log}"#),
+            Ok(BreakpointOptions {
+                condition: Some(r#"log > 1 && name != "}""#.to_string()),
+                log_message: Some("log={log}".to_string()),
+                ..BreakpointOptions::default()
+            })
+        );
+        assert!(parse_breakpoint_options("if {log > 1").is_err());
+        assert!(parse_breakpoint_options("hits 0").is_err());
+        assert!(parse_breakpoint_options("if").is_err());
+        assert!(parse_breakpoint_options("log").is_err());
+        assert!(parse_breakpoint_options("when i > 1").is_err());
+    }
+
+    #[test]
+    fn break_command_reports_and_lists_breakpoint_options() {
+        let mut vm = counting_loop_vm();
+        let mut out = Vec::new();
+        let mut breakpoints = Breakpoints::default();
+        let mut step_mode = StepMode::Running;
+
+        for command in ["break line 4 hits 3 if i > 0", "bl 5 log i={i}", "breaks"] {
+            handle_command(
+                command,
+                &mut vm,
+                &mut breakpoints,
+                &mut step_mode,
+                None,
+                &mut out,
+            );
+        }
+        let text = String::from_utf8(out).expect("output should be utf-8");
+        assert!(
+            text.contains("line breakpoint set at 4 hits 3 if i > 0"),
+            "{text}"
+        );
+        assert!(
+            text.contains("line breakpoint set at 5 log i={i}"),
+            "{text}"
+        );
+        assert!(text.contains("line breakpoints: [4, 5]"), "{text}");
+        assert!(text.contains("  line 4 hits 3 if i > 0 (0 hits)"), "{text}");
+    }
+
+    #[test]
+    fn logpoints_interpolate_locals_without_stopping() {
+        let mut vm = counting_loop_vm();
+        let bridge = DebugCommandBridge::new();
+        let mut debugger = Debugger::with_command_bridge(bridge.clone());
+        debugger.add_line_breakpoint(
+            4,
+            BreakpointOptions {
+                condition: Some("i % 2 == 0".to_string()),
+                log_message: Some("i={i} total={total + 0} {{done}}".to_string()),
+                ..BreakpointOptions::default()
+            },
+        );
+
+        let status = vm.run_with_debugger(&mut debugger).expect("vm should run");
+        assert_eq!(status, VmStatus::Halted);
+        assert_eq!(
+            bridge.status().log_messages,
+            vec![
+                "i=0 total=0 {done}".to_string(),
+                "i=2 total=1 {done}".to_string(),
+                "i=4 total=6 {done}".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn conditional_breakpoint_stops_once_hit_count_is_reached() {
+        let bridge = DebugCommandBridge::new();
+        let mut debugger = Debugger::with_command_bridge(bridge.clone());
+        debugger.add_line_breakpoint(
+            4,
+            BreakpointOptions {
+                condition: Some("i > 1".to_string()),
+                hit_count: Some(2),
+                log_message: None,
+            },
+        );
+
+        let runner = std::thread::spawn(move || {
+            counting_loop_vm()
+                .run_with_debugger(&mut debugger)
+                .expect("vm should run")
+        });
+        let timeout = std::time::Duration::from_secs(5);
+        let deadline = std::time::Instant::now() + timeout;
+        while !bridge.status().attached {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "debugger never stopped"
+            );
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        let printed = bridge
+            .execute("print i", timeout)
+            .expect("print should run");
+        assert!(printed.output.contains("i = Int(3)"), "{}", printed.output);
+        assert_eq!(printed.current_line, Some(4));
+        let resumed = bridge
+            .execute("continue", timeout)
+            .expect("continue should run");
+        assert!(resumed.resumed);
+
+        // Past the threshold every hit where the condition holds stops.
+        while !bridge.status().attached {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "debugger never stopped"
+            );
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        let printed = bridge
+            .execute("print i", timeout)
+            .expect("print should run");
+        assert!(printed.output.contains("i = Int(4)"), "{}", printed.output);
+        bridge
+            .execute("continue", timeout)
+            .expect("continue should run");
+
+        let status = runner.join().expect("vm thread should finish");
+        assert_eq!(status, VmStatus::Halted);
+    }
+
+    #[test]
+    fn failing_condition_stops_and_reports_the_error() {
+        let bridge = DebugCommandBridge::new();
+        let mut debugger = Debugger::with_command_bridge(bridge.clone());
+        debugger.add_line_breakpoint(
+            4,
+            BreakpointOptions {
+                condition: Some("missing > 1".to_string()),
+                ..BreakpointOptions::default()
+            },
+        );
+
+        let runner = std::thread::spawn(move || {
+            counting_loop_vm()
+                .run_with_debugger(&mut debugger)
+                .map(|_| ())
+        });
+        let timeout = std::time::Duration::from_secs(5);
+        let deadline = std::time::Instant::now() + timeout;
+        while !bridge.status().attached {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "debugger never stopped"
+            );
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        let messages = bridge.status().log_messages;
+        assert_eq!(messages.len(), 1);
+        assert!(
+            messages[0].starts_with("breakpoint condition error at line 4:"),
+            "{messages:?}"
+        );
+        bridge
+            .execute("clear line 4", timeout)
+            .expect("clear should run");
+        bridge
+            .execute("continue", timeout)
+            .expect("continue should run");
+        runner
+            .join()
+            .expect("vm thread should finish")
+            .expect("vm should run");
+    }
+
+    #[test]
+    fn runaway_condition_stops_with_a_condition_error() {
+        let bridge = DebugCommandBridge::new();
+        let mut debugger = Debugger::with_command_bridge(bridge.clone());
+        debugger.add_line_breakpoint(
+            4,
+            BreakpointOptions {
+                condition: Some("while true {} true".to_string()),
+                ..BreakpointOptions::default()
+            },
+        );
+
+        let runner = std::thread::spawn(move || {
+            counting_loop_vm()
+                .run_with_debugger(&mut debugger)
+                .map(|_| ())
+        });
+        let timeout = std::time::Duration::from_secs(30);
+        let deadline = std::time::Instant::now() + timeout;
+        while !bridge.status().attached {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "debugger never stopped"
+            );
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert_eq!(
+            bridge.status().log_messages,
+            vec![format!(
+                "breakpoint condition error at line 4: did not finish within {EXPRESSION_STEP_BUDGET} instructions"
+            )]
+        );
+        bridge
+            .execute("clear line 4", timeout)
+            .expect("clear should run");
+        bridge
+            .execute("continue", timeout)
+            .expect("continue should run");
+        runner
+            .join()
+            .expect("vm thread should finish")
+            .expect("vm should run");
+    }
+
+    #[test]
+    fn braced_condition_can_name_a_local_log() {
+        let compiled = crate::compiler::compile_source(
+            "let log = 0;\nwhile log < 3 {\n    log = log + 1;\n}\n",
+        )
+        .expect("source should compile");
+        let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+        let bridge = DebugCommandBridge::new();
+        let mut debugger = Debugger::with_command_bridge(bridge.clone());
+        let options =
+            parse_breakpoint_options("if {log > 1} log log={log}").expect("options should parse");
+        assert_eq!(
+            describe_breakpoint_options(&options),
+            " if {log > 1} log log={log}"
+        );
+        debugger.add_line_breakpoint(3, options);
+
+        let status = vm.run_with_debugger(&mut debugger).expect("vm should run");
+        assert_eq!(status, VmStatus::Halted);
+        assert_eq!(bridge.status().log_messages, vec!["log=2".to_string()]);
+    }
+
+    #[test]
+    fn source_breakpoint_counts_hits_for_the_whole_line() {
+        // Line 4 holds two statements, so the breakpoint covers two offsets.
+        let compiled = crate::compiler::compile_source(
+            "let total = 0;\nlet i = 0;\nwhile i < 5 {\n    total = total + i; i = i + 1;\n}\n",
+        )
+        .expect("source should compile");
+        let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+        let bridge = DebugCommandBridge::new();
+        let mut debugger = Debugger::with_command_bridge(bridge.clone());
+        let mut out = Vec::new();
+        handle_command(
+            "break <source>:4 hits 3 log i={i}",
+            &mut vm,
+            &mut debugger.breakpoints,
+            &mut debugger.step_mode,
+            None,
+            &mut out,
+        );
+        let text = String::from_utf8(out).expect("output should be utf-8");
+        assert!(text.contains("breakpoint set at <source>:4"), "{text}");
+        assert_eq!(debugger.breakpoints.offsets.len(), 2);
+
+        let status = vm.run_with_debugger(&mut debugger).expect("vm should run");
+        assert_eq!(status, VmStatus::Halted);
+        assert_eq!(
+            bridge.status().log_messages,
+            ["i=1", "i=1", "i=2", "i=2", "i=3", "i=3", "i=4", "i=4"]
+                .map(str::to_string)
+                .to_vec()
+        );
+    }
+
+    #[test]
+    fn eval_and_set_use_the_current_locals() {
+        let mut vm = vm_with_named_local("counter", Value::Int(42));
+        let mut out = Vec::new();
+        let mut breakpoints = Breakpoints::default();
+        let mut step_mode = StepMode::Running;
+
+        for command in [
+            "eval counter + 1",
+            "eval --flavor lua counter * 2",
+            "eval --flavor cobol 1",
+            "set counter = counter - 2",
+            "set missing = 1",
+            "set counter == 1",
+        ] {
+            handle_command(
+                command,
+                &mut vm,
+                &mut breakpoints,
+                &mut step_mode,
+                None,
+                &mut out,
+            );
+        }
+        let text = String::from_utf8(out).expect("output should be utf-8");
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                "=> Int(43)",
+                "=> Int(84)",
+                "unknown expression flavor 'cobol'",
+                "counter = Int(40)",
+                "unknown local 'missing'",
+                "usage: set <local> = <expression>",
+            ]
+        );
+        assert_eq!(vm.locals()[0], Value::Int(40));
+    }
+
+    #[test]
+    fn set_changes_a_live_local_before_continuing() {
+        let bridge = DebugCommandBridge::new();
+        let mut debugger = Debugger::with_command_bridge(bridge.clone());
+        debugger.add_line_breakpoint(
+            4,
+            BreakpointOptions {
+                condition: Some("i == 2".to_string()),
+                ..BreakpointOptions::default()
+            },
+        );
+
+        let runner = std::thread::spawn(move || {
+            let mut vm = counting_loop_vm();
+            let status = vm.run_with_debugger(&mut debugger).expect("vm should run");
+            (status, vm.locals().to_vec())
+        });
+        let timeout = std::time::Duration::from_secs(5);
+        let deadline = std::time::Instant::now() + timeout;
+        while !bridge.status().attached {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "debugger never stopped"
+            );
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        let set = bridge
+            .execute("set total = total + 100", timeout)
+            .expect("set should run");
+        assert_eq!(set.output.trim(), "total = Int(101)");
+        assert!(!set.resumed);
+        let evaluated = bridge
+            .execute("eval --flavor lua total + i", timeout)
+            .expect("eval should run");
+        assert_eq!(evaluated.output.trim(), "=> Int(103)");
+        bridge
+            .execute("clear line 4", timeout)
+            .expect("clear should run");
+        bridge
+            .execute("continue", timeout)
+            .expect("continue should run");
+
+        let (status, locals) = runner.join().expect("vm thread should finish");
+        assert_eq!(status, VmStatus::Halted);
+        assert_eq!(locals[0], Value::Int(110));
+    }
+
+    #[test]
+    fn eval_inside_an_inlined_function_sees_its_parameters() {
+        let source =
+            "let a = 7;\nfn divide(p, r) {\n    let q = p / r;\n    q;\n}\nlet b = divide(a, 2);\n";
+        let bridge = DebugCommandBridge::new();
+        let mut debugger = Debugger::with_command_bridge(bridge.clone());
+        debugger.add_line_breakpoint(3, BreakpointOptions::default());
+
+        let runner = std::thread::spawn(move || {
+            let compiled = crate::compiler::compile_source(source).expect("source should compile");
+            let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+            let status = vm.run_with_debugger(&mut debugger).expect("vm should run");
+            (status, vm.locals().to_vec())
+        });
+        let timeout = std::time::Duration::from_secs(5);
+        let deadline = std::time::Instant::now() + timeout;
+        while !bridge.status().attached {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "debugger never stopped"
+            );
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        let locals = bridge
+            .execute("locals", timeout)
+            .expect("locals should run");
+        assert!(locals.output.contains("p = Int(7)"), "{}", locals.output);
+        assert!(locals.output.contains("r = Int(2)"), "{}", locals.output);
+        assert!(!locals.output.contains("a = "), "{}", locals.output);
+        let evaluated = bridge
+            .execute("eval p + r", timeout)
+            .expect("eval should run");
+        assert_eq!(evaluated.output.trim(), "=> Int(9)");
+        let set = bridge
+            .execute("set r = 1", timeout)
+            .expect("set should run");
+        assert_eq!(set.output.trim(), "r = Int(1)");
+        bridge
+            .execute("clear line 3", timeout)
+            .expect("clear should run");
+        bridge
+            .execute("continue", timeout)
+            .expect("continue should run");
+
+        let (status, locals) = runner.join().expect("vm thread should finish");
+        assert_eq!(status, VmStatus::Halted);
+        assert!(locals.contains(&Value::Int(7)));
+    }
+}
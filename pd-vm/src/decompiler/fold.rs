@@ -0,0 +1,495 @@
+//! Folds the locals the compiler introduces back into the expressions they
+//! came from.
+//!
+//! Slices, `match` results, inlined calls, and operands spilled around a store
+//! all go through locals the source never named. Printed as they are, each
+//! becomes a `let`, and recompiling the output would need more local slots
+//! than the original program had. Locals named in the debug info are left
+//! alone.
+
+use std::collections::{HashMap, HashSet};
+
+use super::{BinaryOp, Callee, Expr, Stmt, VarId};
+use crate::builtins::BuiltinFunction;
+use crate::bytecode::Value;
+
+pub(super) fn fold_locals(body: &mut Vec<Stmt>, named: &HashSet<VarId>) {
+    let mut reads = HashMap::new();
+    let mut writes = HashMap::new();
+    count_block(body, &mut reads, &mut writes);
+    let mut folder = Folder {
+        named,
+        scoped: scoped_locals(body, named),
+        reads,
+        writes,
+    };
+    folder.fold_block(body);
+}
+
+struct Folder<'a> {
+    named: &'a HashSet<VarId>,
+    /// Unnamed locals whose values never leave the block they are assigned in.
+    scoped: HashSet<VarId>,
+    reads: HashMap<VarId, usize>,
+    writes: HashMap<VarId, usize>,
+}
+
+impl Folder<'_> {
+    fn fold_block(&mut self, stmts: &mut Vec<Stmt>) {
+        for stmt in stmts.iter_mut() {
+            match stmt {
+                Stmt::If(_, then_branch, else_branch) => {
+                    self.fold_block(then_branch);
+                    self.fold_block(else_branch);
+                }
+                Stmt::While(_, body) => self.fold_block(body),
+                _ => {}
+            }
+        }
+        let mut index = 0;
+        while index < stmts.len() {
+            if !(self.fold_slice(stmts, index)
+                || self.fold_copy(stmts, index)
+                || self.fold_single_use(stmts, index))
+            {
+                index += 1;
+            }
+        }
+    }
+
+    /// `c[s:e]` stores its operands in hidden locals right before the
+    /// statement that slices them.
+    fn fold_slice(&mut self, stmts: &mut Vec<Stmt>, index: usize) -> bool {
+        let mut operands = Vec::new();
+        for stmt in stmts.iter().skip(index).take(3) {
+            match stmt {
+                Stmt::Assign(var, _) if self.scoped.contains(var) && !operands.contains(var) => {
+                    operands.push(*var)
+                }
+                _ => break,
+            }
+        }
+        for count in [3, 2] {
+            if operands.len() < count || index + count >= stmts.len() {
+                continue;
+            }
+            let operands = &operands[..count];
+            let mut values = stmts[index..index + count].iter().map(|stmt| match stmt {
+                Stmt::Assign(_, value) => Box::new(value.clone()),
+                _ => unreachable!("slice operands are assignments"),
+            });
+            let container = values.next().expect("container");
+            let start = values.next().expect("start");
+            let mut folded = Some(Expr::Slice(container, start, values.next()));
+            let mut sliced = stmts[index + count].clone();
+            let Some(head) = head_mut(&mut sliced) else {
+                continue;
+            };
+            for_each_expr_mut(head, &mut |expr| {
+                if folded.is_some() && is_lowered_slice(expr, operands) {
+                    *expr = folded.take().expect("checked above");
+                    return true;
+                }
+                false
+            });
+            let leftover = mentioned(std::slice::from_ref(&sliced));
+            if folded.is_some() || operands.iter().any(|var| leftover.contains(var)) {
+                continue;
+            }
+            stmts[index + count] = sliced;
+            stmts.drain(index..index + count);
+            return true;
+        }
+        false
+    }
+
+    /// `let a = b;` where both are written once, `b` earlier in this block,
+    /// and `a` is only read later in it: reads of `a` become reads of `b`.
+    /// Calls inlined once bind their arguments this way.
+    fn fold_copy(&mut self, stmts: &mut Vec<Stmt>, index: usize) -> bool {
+        let Stmt::Assign(copy, Expr::Var(source)) = &stmts[index] else {
+            return false;
+        };
+        let (copy, source) = (*copy, *source);
+        if copy == source
+            || self.named.contains(&copy)
+            || self.writes.get(&copy) != Some(&1)
+            || self.writes.get(&source) != Some(&1)
+        {
+            return false;
+        }
+        let assigned_before = stmts[..index]
+            .iter()
+            .any(|stmt| matches!(stmt, Stmt::Assign(var, _) if *var == source));
+        let (later, _) = mentions(&stmts[index + 1..]);
+        if !assigned_before || later.get(&copy) != self.reads.get(&copy) {
+            return false;
+        }
+        stmts.remove(index);
+        for stmt in &mut stmts[index..] {
+            rename_in_stmt(stmt, copy, source);
+        }
+        let copied = self.reads.remove(&copy).unwrap_or(0);
+        *self.reads.entry(source).or_default() += copied;
+        *self.reads.entry(source).or_default() -= 1;
+        true
+    }
+
+    /// A scoped local read once, by the next statement that mentions it, is
+    /// replaced by its value. A value with side effects only moves into the
+    /// very next statement, and only ahead of anything else with effects.
+    fn fold_single_use(&mut self, stmts: &mut Vec<Stmt>, index: usize) -> bool {
+        let Stmt::Assign(var, value) = &stmts[index] else {
+            return false;
+        };
+        let var = *var;
+        // `x[k] = v` updates a local rather than computing a value to move.
+        let mut inputs = HashMap::new();
+        count_reads(value, &mut inputs);
+        if !self.scoped.contains(&var) || inputs.contains_key(&var) {
+            return false;
+        }
+        let Some(target) = (index + 1..stmts.len()).find(|target| {
+            let (reads, writes) = mentions(std::slice::from_ref(&stmts[*target]));
+            reads.contains_key(&var) || writes.contains(&var)
+        }) else {
+            return false;
+        };
+        let (reads, writes) = mentions(std::slice::from_ref(&stmts[target]));
+        if reads.get(&var) != Some(&1) || writes.contains(&var) {
+            return false;
+        }
+        let Some(head) = head(&stmts[target]) else {
+            return false;
+        };
+        if is_pure(value) {
+            let (_, between) = mentions(&stmts[index + 1..target]);
+            if between.iter().any(|written| inputs.contains_key(written)) {
+                return false;
+            }
+        } else if target != index + 1 || reach(head, var) != Reach::Found(true) {
+            return false;
+        }
+        let Stmt::Assign(_, value) = stmts.remove(index) else {
+            unreachable!("checked above");
+        };
+        let head = head_mut(&mut stmts[target - 1]).expect("checked above");
+        let mut value = Some(value);
+        for_each_expr_mut(head, &mut |expr| {
+            if *expr == Expr::Var(var)
+                && let Some(value) = value.take()
+            {
+                *expr = value;
+            }
+            false
+        });
+        self.reads.remove(&var);
+        true
+    }
+}
+
+/// The expression a statement evaluates once, before anything else it does.
+fn head(stmt: &Stmt) -> Option<&Expr> {
+    match stmt {
+        Stmt::Assign(_, value) | Stmt::Expr(value) | Stmt::If(value, _, _) => Some(value),
+        Stmt::While(..) | Stmt::Break | Stmt::Continue => None,
+    }
+}
+
+fn head_mut(stmt: &mut Stmt) -> Option<&mut Expr> {
+    match stmt {
+        Stmt::Assign(_, value) | Stmt::Expr(value) | Stmt::If(value, _, _) => Some(value),
+        Stmt::While(..) | Stmt::Break | Stmt::Continue => None,
+    }
+}
+
+/// Unnamed locals whose every read is in a statement head and takes the
+/// value assigned by the closest earlier statement of the same block that
+/// mentions the local. Such a value never reaches a nested block, another
+/// loop iteration, or a join, so it can move to where it is read.
+fn scoped_locals(body: &[Stmt], named: &HashSet<VarId>) -> HashSet<VarId> {
+    let mut seen = HashSet::new();
+    let mut escaping = named.clone();
+    scope_block(body, &mut seen, &mut escaping);
+    seen.retain(|var| !escaping.contains(var));
+    seen
+}
+
+fn scope_block(stmts: &[Stmt], seen: &mut HashSet<VarId>, escaping: &mut HashSet<VarId>) {
+    let mut assigned = HashSet::new();
+    for stmt in stmts {
+        let mut head_reads = HashMap::new();
+        if let Some(head) = head(stmt) {
+            count_reads(head, &mut head_reads);
+        }
+        for var in head_reads.keys() {
+            seen.insert(*var);
+            if !assigned.remove(var) {
+                escaping.insert(*var);
+            }
+        }
+        let nested = match stmt {
+            Stmt::If(_, then_branch, else_branch) => {
+                scope_block(then_branch, seen, escaping);
+                scope_block(else_branch, seen, escaping);
+                let mut nested = mentioned(then_branch);
+                nested.extend(mentioned(else_branch));
+                nested
+            }
+            Stmt::While(condition, body) => {
+                scope_block(body, seen, escaping);
+                let mut reads = HashMap::new();
+                count_reads(condition, &mut reads);
+                escaping.extend(reads.keys());
+                let mut nested = mentioned(body);
+                nested.extend(reads.into_keys());
+                nested
+            }
+            _ => HashSet::new(),
+        };
+        for var in nested {
+            seen.insert(var);
+            assigned.remove(&var);
+        }
+        if let Stmt::Assign(var, _) = stmt {
+            seen.insert(*var);
+            assigned.insert(*var);
+        }
+    }
+}
+
+fn count_block(
+    stmts: &[Stmt],
+    reads: &mut HashMap<VarId, usize>,
+    writes: &mut HashMap<VarId, usize>,
+) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Assign(var, value) => {
+                *writes.entry(*var).or_default() += 1;
+                count_reads(value, reads);
+            }
+            Stmt::Expr(value) => count_reads(value, reads),
+            Stmt::If(condition, then_branch, else_branch) => {
+                count_reads(condition, reads);
+                count_block(then_branch, reads, writes);
+                count_block(else_branch, reads, writes);
+            }
+            Stmt::While(condition, body) => {
+                count_reads(condition, reads);
+                count_block(body, reads, writes);
+            }
+            Stmt::Break | Stmt::Continue => {}
+        }
+    }
+}
+
+/// How often `stmts` read each local, and which locals they assign.
+fn mentions(stmts: &[Stmt]) -> (HashMap<VarId, usize>, HashSet<VarId>) {
+    let mut reads = HashMap::new();
+    let mut writes = HashMap::new();
+    count_block(stmts, &mut reads, &mut writes);
+    (reads, writes.into_keys().collect())
+}
+
+fn mentioned(stmts: &[Stmt]) -> HashSet<VarId> {
+    let (reads, mut writes) = mentions(stmts);
+    writes.extend(reads.into_keys());
+    writes
+}
+
+fn count_reads(expr: &Expr, reads: &mut HashMap<VarId, usize>) {
+    match expr {
+        Expr::Const(_) => {}
+        Expr::Var(var) => *reads.entry(*var).or_default() += 1,
+        Expr::Neg(inner) | Expr::Coerce(inner) => count_reads(inner, reads),
+        Expr::Binary(_, lhs, rhs) => {
+            count_reads(lhs, reads);
+            count_reads(rhs, reads);
+        }
+        Expr::Call(_, args) => {
+            for arg in args {
+                count_reads(arg, reads);
+            }
+        }
+        Expr::IfElse(condition, then_expr, else_expr) => {
+            count_reads(condition, reads);
+            count_reads(then_expr, reads);
+            count_reads(else_expr, reads);
+        }
+        Expr::Slice(container, start, end) => {
+            count_reads(container, reads);
+            count_reads(start, reads);
+            if let Some(end) = end {
+                count_reads(end, reads);
+            }
+        }
+    }
+}
+
+fn for_each_expr_mut(expr: &mut Expr, visit: &mut impl FnMut(&mut Expr) -> bool) {
+    if visit(expr) {
+        return;
+    }
+    match expr {
+        Expr::Const(_) | Expr::Var(_) => {}
+        Expr::Neg(inner) | Expr::Coerce(inner) => for_each_expr_mut(inner, visit),
+        Expr::Binary(_, lhs, rhs) => {
+            for_each_expr_mut(lhs, visit);
+            for_each_expr_mut(rhs, visit);
+        }
+        Expr::Call(_, args) => {
+            for arg in args {
+                for_each_expr_mut(arg, visit);
+            }
+        }
+        Expr::IfElse(condition, then_expr, else_expr) => {
+            for_each_expr_mut(condition, visit);
+            for_each_expr_mut(then_expr, visit);
+            for_each_expr_mut(else_expr, visit);
+        }
+        Expr::Slice(container, start, end) => {
+            for_each_expr_mut(container, visit);
+            for_each_expr_mut(start, visit);
+            if let Some(end) = end {
+                for_each_expr_mut(end, visit);
+            }
+        }
+    }
+}
+
+fn rename_in_stmt(stmt: &mut Stmt, from: VarId, to: VarId) {
+    let mut rename = |expr: &mut Expr| {
+        if *expr == Expr::Var(from) {
+            *expr = Expr::Var(to);
+        }
+        false
+    };
+    match stmt {
+        Stmt::Assign(_, value) | Stmt::Expr(value) => for_each_expr_mut(value, &mut rename),
+        Stmt::If(condition, then_branch, else_branch) => {
+            for_each_expr_mut(condition, &mut rename);
+            for stmt in then_branch.iter_mut().chain(else_branch) {
+                rename_in_stmt(stmt, from, to);
+            }
+        }
+        Stmt::While(condition, body) => {
+            for_each_expr_mut(condition, &mut rename);
+            for stmt in body {
+                rename_in_stmt(stmt, from, to);
+            }
+        }
+        Stmt::Break | Stmt::Continue => {}
+    }
+}
+
+/// `slice(c, s, len(c) - s)` for `c[s:]`, and for `c[s:e]`
+/// `slice(c, s, (if e < 0 => { len(c) + e } else => { e }) - s)`.
+fn is_lowered_slice(expr: &Expr, operands: &[VarId]) -> bool {
+    let Expr::Call(Callee::Builtin(BuiltinFunction::Slice), args) = expr else {
+        return false;
+    };
+    let [
+        Expr::Var(container),
+        Expr::Var(start),
+        Expr::Binary(BinaryOp::Sub, stop, minus),
+    ] = args.as_slice()
+    else {
+        return false;
+    };
+    if [*container, *start] != operands[..2] || **minus != Expr::Var(*start) {
+        return false;
+    }
+    let len = Expr::Call(
+        Callee::Builtin(BuiltinFunction::Len),
+        vec![Expr::Var(*container)],
+    );
+    let Some(end) = operands.get(2) else {
+        return **stop == len;
+    };
+    let end = Expr::Var(*end);
+    let adjusted = Expr::IfElse(
+        Box::new(Expr::Binary(
+            BinaryOp::Lt,
+            Box::new(end.clone()),
+            Box::new(Expr::Const(Value::Int(0))),
+        )),
+        Box::new(Expr::Binary(
+            BinaryOp::Add,
+            Box::new(len),
+            Box::new(end.clone()),
+        )),
+        Box::new(end),
+    );
+    **stop == adjusted
+}
+
+#[derive(Debug, PartialEq)]
+enum Reach {
+    /// The local is read; `true` when it is read unconditionally and nothing
+    /// with side effects is evaluated before it.
+    Found(bool),
+    /// The local is not read; `true` when nothing has side effects.
+    Missing(bool),
+}
+
+fn reach(expr: &Expr, var: VarId) -> Reach {
+    let children: Vec<(&Expr, bool)> = match expr {
+        Expr::Const(_) => return Reach::Missing(true),
+        Expr::Var(read) => {
+            return if *read == var {
+                Reach::Found(true)
+            } else {
+                Reach::Missing(true)
+            };
+        }
+        Expr::Neg(inner) | Expr::Coerce(inner) => vec![(inner, false)],
+        Expr::Binary(_, lhs, rhs) => vec![(lhs, false), (rhs, false)],
+        Expr::Call(_, args) => args.iter().map(|arg| (arg, false)).collect(),
+        Expr::IfElse(condition, then_expr, else_expr) => {
+            vec![(condition, false), (then_expr, true), (else_expr, true)]
+        }
+        Expr::Slice(container, start, end) => [Some(container), Some(start), end.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(|child| (child.as_ref(), false))
+            .collect(),
+    };
+    let mut pure_so_far = true;
+    for (child, conditional) in children {
+        match reach(child, var) {
+            Reach::Found(first) => return Reach::Found(first && pure_so_far && !conditional),
+            Reach::Missing(pure) => pure_so_far &= pure,
+        }
+    }
+    let effects = matches!(expr, Expr::Call(callee, _) if has_effects(*callee));
+    Reach::Missing(pure_so_far && !effects)
+}
+
+/// Whether evaluating `expr` can only read locals and fail, so moving it
+/// later changes nothing but when an error is raised.
+fn is_pure(expr: &Expr) -> bool {
+    matches!(reach(expr, VarId::MAX), Reach::Missing(true))
+}
+
+fn has_effects(callee: Callee) -> bool {
+    match callee {
+        Callee::Import(_) => true,
+        Callee::Builtin(builtin) => matches!(
+            builtin,
+            BuiltinFunction::IoOpen
+                | BuiltinFunction::IoPopen
+                | BuiltinFunction::IoReadAll
+                | BuiltinFunction::IoReadLine
+                | BuiltinFunction::IoWrite
+                | BuiltinFunction::IoFlush
+                | BuiltinFunction::IoClose
+                | BuiltinFunction::IoExists
+                | BuiltinFunction::RandInt
+                | BuiltinFunction::RandFloat
+                | BuiltinFunction::RandChoice
+                | BuiltinFunction::RandShuffle
+                | BuiltinFunction::UuidV4
+        ),
+    }
+}
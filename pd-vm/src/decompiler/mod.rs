@@ -0,0 +1,1202 @@
+//! Turns bytecode back into RustScript.
+//!
+//! Decompiling runs in three passes:
+//!
+//! - a value-flow pass follows the operand stack along every path and records
+//!   which pushed values are later popped. Expression statements leave their
+//!   value on the stack, so a value nobody pops is an expression statement
+//!   rather than an operand still waiting for its instruction;
+//! - a structuring pass walks the code in layout order, rebuilding expressions
+//!   from a symbolic stack and recognising the shapes the compiler lowers
+//!   `if`/`else`, `while`, `break`, and `continue` to. Jumps that fit no shape
+//!   are wrapped in a `while true { ... break; }` block they can break out of;
+//! - [`render`] names the locals (from [`DebugInfo::locals`] when present) and
+//!   prints the statements.
+//!
+//! The output recompiles to bytecode that behaves the same, not to the same
+//! bytes: functions were inlined, hidden locals show up as ordinary ones, and
+//! operands that must be evaluated before a statement are kept in temporaries.
+//!
+//! [`DebugInfo::locals`]: crate::debug_info::DebugInfo::locals
+
+mod fold;
+mod render;
+
+use std::collections::HashMap;
+
+use crate::builtins::BuiltinFunction;
+use crate::bytecode::{OpCode, Program, Value};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecompileError {
+    /// The code does not decode, or a jump lands inside an instruction.
+    InvalidCode {
+        offset: usize,
+    },
+    StackUnderflow {
+        offset: usize,
+    },
+    /// Paths that meet disagree on what the operand stack holds.
+    UnbalancedStack {
+        offset: usize,
+    },
+    /// A jump that cannot be expressed with `if`, `while`, `break`, or `continue`.
+    UnstructuredJump {
+        offset: usize,
+        target: usize,
+    },
+    /// A `ret` before the end of the program.
+    EarlyReturn {
+        offset: usize,
+    },
+    /// A call, operator, or constant RustScript has no syntax for.
+    Unsupported(String),
+}
+
+impl std::fmt::Display for DecompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecompileError::InvalidCode { offset } => {
+                write!(f, "invalid code at offset {offset}")
+            }
+            DecompileError::StackUnderflow { offset } => {
+                write!(f, "stack underflow at offset {offset}")
+            }
+            DecompileError::UnbalancedStack { offset } => {
+                write!(f, "unbalanced stack at offset {offset}")
+            }
+            DecompileError::UnstructuredJump { offset, target } => {
+                write!(
+                    f,
+                    "jump at offset {offset} to {target} has no structured equivalent"
+                )
+            }
+            DecompileError::EarlyReturn { offset } => {
+                write!(f, "ret at offset {offset} before the end of the program")
+            }
+            DecompileError::Unsupported(what) => write!(f, "cannot decompile {what}"),
+        }
+    }
+}
+
+impl std::error::Error for DecompileError {}
+
+/// Decompiles `program` to RustScript source.
+pub fn decompile_program(program: &Program) -> Result<String, DecompileError> {
+    let insns = decode(program)?;
+    let flow = ValueFlow::analyze(&insns, program.code.len())?;
+    let mut structurer = Structurer::new(program, insns, flow);
+    let mut body = structurer.decompile()?;
+    let named = program
+        .debug
+        .iter()
+        .flat_map(|debug| &debug.locals)
+        .map(|local| local.index as VarId)
+        .collect();
+    fold::fold_locals(&mut body, &named);
+    render::render_program(program, &body)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    Lt,
+    Gt,
+    And,
+    Or,
+    Shl,
+    Shr,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    Nop,
+    Ret,
+    Ldc(u32),
+    Binary(BinaryOp),
+    Neg,
+    Br(usize),
+    Brfalse(usize),
+    Pop,
+    Dup,
+    Ldloc(u8),
+    Stloc(u8),
+    Call(u16, u8),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Insn {
+    offset: usize,
+    end: usize,
+    op: Op,
+}
+
+fn decode(program: &Program) -> Result<Vec<Insn>, DecompileError> {
+    let code = &program.code;
+    let mut insns = Vec::new();
+    let mut ip = 0usize;
+    while ip < code.len() {
+        let offset = ip;
+        let invalid = DecompileError::InvalidCode { offset };
+        let opcode = OpCode::from_byte(code[ip]).ok_or(invalid.clone())?;
+        ip += 1;
+        let start = ip;
+        let operand = |len: usize| {
+            code.get(start..start + len)
+                .ok_or(DecompileError::InvalidCode { offset })
+        };
+        let read_u32 = |bytes: &[u8]| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let op = match opcode {
+            OpCode::Nop => Op::Nop,
+            OpCode::Ret => Op::Ret,
+            OpCode::Ldc => {
+                let index = read_u32(operand(4)?);
+                if index as usize >= program.constants.len() {
+                    return Err(invalid);
+                }
+                ip += 4;
+                Op::Ldc(index)
+            }
+            OpCode::Add => Op::Binary(BinaryOp::Add),
+            OpCode::Sub => Op::Binary(BinaryOp::Sub),
+            OpCode::Mul => Op::Binary(BinaryOp::Mul),
+            OpCode::Div => Op::Binary(BinaryOp::Div),
+            OpCode::Mod => Op::Binary(BinaryOp::Mod),
+            OpCode::Ceq => Op::Binary(BinaryOp::Eq),
+            OpCode::Clt => Op::Binary(BinaryOp::Lt),
+            OpCode::Cgt => Op::Binary(BinaryOp::Gt),
+            OpCode::And => Op::Binary(BinaryOp::And),
+            OpCode::Or => Op::Binary(BinaryOp::Or),
+            OpCode::Shl => Op::Binary(BinaryOp::Shl),
+            OpCode::Shr => Op::Binary(BinaryOp::Shr),
+            OpCode::Neg => Op::Neg,
+            OpCode::Br | OpCode::Brfalse => {
+                let target = read_u32(operand(4)?) as usize;
+                ip += 4;
+                if opcode == OpCode::Br {
+                    Op::Br(target)
+                } else {
+                    Op::Brfalse(target)
+                }
+            }
+            OpCode::Pop => Op::Pop,
+            OpCode::Dup => Op::Dup,
+            OpCode::Ldloc | OpCode::Stloc => {
+                let slot = operand(1)?[0];
+                ip += 1;
+                if opcode == OpCode::Ldloc {
+                    Op::Ldloc(slot)
+                } else {
+                    Op::Stloc(slot)
+                }
+            }
+            OpCode::Call => {
+                let bytes = operand(3)?;
+                let index = u16::from_le_bytes([bytes[0], bytes[1]]);
+                let argc = bytes[2];
+                ip += 3;
+                Op::Call(index, argc)
+            }
+        };
+        insns.push(Insn {
+            offset,
+            end: ip,
+            op,
+        });
+    }
+
+    let starts = insns
+        .iter()
+        .map(|insn| insn.offset)
+        .collect::<std::collections::HashSet<_>>();
+    for insn in &insns {
+        if let Op::Br(target) | Op::Brfalse(target) = insn.op
+            && !starts.contains(&target)
+        {
+            return Err(DecompileError::InvalidCode {
+                offset: insn.offset,
+            });
+        }
+    }
+    Ok(insns)
+}
+
+/// Where a value was pushed: the instruction offset and, for `dup`, which copy.
+type Site = (usize, u8);
+
+/// Which pushed values are ever popped. Values that meet at a join are the
+/// same value, so they are merged and popping either counts for both.
+struct ValueFlow {
+    parent: Vec<usize>,
+    consumed: Vec<bool>,
+    sites: HashMap<Site, usize>,
+    /// Operand stack depth on entry to each reachable instruction, counting
+    /// only values that are popped later.
+    depths: Vec<Option<usize>>,
+}
+
+impl ValueFlow {
+    fn analyze(insns: &[Insn], code_len: usize) -> Result<Self, DecompileError> {
+        let mut flow = ValueFlow {
+            parent: Vec::new(),
+            consumed: Vec::new(),
+            sites: HashMap::new(),
+            depths: Vec::new(),
+        };
+        let index_of = insns
+            .iter()
+            .enumerate()
+            .map(|(index, insn)| (insn.offset, index))
+            .collect::<HashMap<_, _>>();
+        let mut entry: Vec<Option<Vec<usize>>> = vec![None; insns.len()];
+        let mut worklist = vec![(0usize, Vec::new())];
+        while let Some((index, mut stack)) = worklist.pop() {
+            let Some(insn) = insns.get(index) else {
+                continue;
+            };
+            if let Some(existing) = &entry[index] {
+                let existing = existing.clone();
+                for (lhs, rhs) in existing.iter().rev().zip(stack.iter().rev()) {
+                    flow.union(*lhs, *rhs);
+                }
+                continue;
+            }
+            entry[index] = Some(stack.clone());
+
+            let offset = insn.offset;
+            let (pops, pushes) = match insn.op {
+                Op::Nop | Op::Br(_) => (0, 0),
+                Op::Ret => continue,
+                Op::Ldc(_) | Op::Ldloc(_) => (0, 1),
+                Op::Binary(_) => (2, 1),
+                Op::Neg => (1, 1),
+                Op::Brfalse(_) | Op::Pop | Op::Stloc(_) => (1, 0),
+                Op::Dup => (1, 2),
+                Op::Call(_, argc) => (argc as usize, 1),
+            };
+            for _ in 0..pops {
+                let value = stack
+                    .pop()
+                    .ok_or(DecompileError::StackUnderflow { offset })?;
+                let root = flow.find(value);
+                flow.consumed[root] = true;
+            }
+            for copy in 0..pushes {
+                let value = flow.parent.len();
+                flow.parent.push(value);
+                flow.consumed.push(false);
+                flow.sites.insert((offset, copy), value);
+                stack.push(value);
+            }
+
+            let next = (insn.end < code_len).then(|| index + 1);
+            match insn.op {
+                Op::Br(target) => worklist.push((index_of[&target], stack)),
+                Op::Brfalse(target) => {
+                    worklist.push((index_of[&target], stack.clone()));
+                    if let Some(next) = next {
+                        worklist.push((next, stack));
+                    }
+                }
+                _ => {
+                    if let Some(next) = next {
+                        worklist.push((next, stack));
+                    }
+                }
+            }
+        }
+        // Values nothing pops become statements, never stack operands.
+        flow.depths = entry
+            .iter()
+            .map(|stack| {
+                let stack = stack.as_ref()?;
+                Some(
+                    stack
+                        .iter()
+                        .filter(|value| {
+                            let root = flow.find(**value);
+                            flow.consumed[root]
+                        })
+                        .count(),
+                )
+            })
+            .collect();
+        Ok(flow)
+    }
+
+    fn find(&mut self, mut value: usize) -> usize {
+        while self.parent[value] != value {
+            self.parent[value] = self.parent[self.parent[value]];
+            value = self.parent[value];
+        }
+        value
+    }
+
+    fn union(&mut self, lhs: usize, rhs: usize) {
+        let lhs = self.find(lhs);
+        let rhs = self.find(rhs);
+        if lhs != rhs {
+            self.parent[rhs] = lhs;
+            self.consumed[lhs] |= self.consumed[rhs];
+        }
+    }
+
+    fn is_consumed(&mut self, site: Site) -> bool {
+        match self.sites.get(&site) {
+            Some(value) => {
+                let root = self.find(*value);
+                self.consumed[root]
+            }
+            None => true,
+        }
+    }
+}
+
+/// A local slot below 256, or a temporary the decompiler introduced above it.
+type VarId = usize;
+
+const FIRST_TEMP: VarId = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Callee {
+    Builtin(BuiltinFunction),
+    Import(u16),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Const(Value),
+    Var(VarId),
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(Callee, Vec<Expr>),
+    /// An operand the compiler converts to a string when it is a number, as it
+    /// does for the non-string side of a string `+`.
+    Coerce(Box<Expr>),
+    IfElse(Box<Expr>, Box<Expr>, Box<Expr>),
+    /// `container[start:end]`, folded back from its lowering by [`fold`].
+    Slice(Box<Expr>, Box<Expr>, Option<Box<Expr>>),
+}
+
+impl Expr {
+    fn is_trivial(&self) -> bool {
+        matches!(self, Expr::Const(_) | Expr::Var(_))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Stmt {
+    Assign(VarId, Expr),
+    Expr(Expr),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    Break,
+    Continue,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Exit {
+    /// Control reaches the end of the block.
+    Falls,
+    /// The block ends in `break` or `continue`.
+    Jumps,
+}
+
+struct Block {
+    stmts: Vec<Stmt>,
+    stack: Vec<Expr>,
+    exit: Exit,
+}
+
+enum Scope {
+    Loop {
+        header: usize,
+        exit: usize,
+        /// Start of the branch-free code before the back edge; a jump into it
+        /// (a `for` loop's `continue`) repeats that code and continues.
+        tail: usize,
+        latch: usize,
+        /// A jump out past `exit` to an enclosing one-shot's landing, taken
+        /// by setting the flag and breaking; the loop is followed by a check.
+        escape: Option<(usize, VarId)>,
+    },
+    /// A `while true { ... break; }` wrapper whose `break` lands on `target`
+    /// with the operands it was entered with still on the stack.
+    OneShot { target: usize, stack: Vec<Expr> },
+    /// The then-branch of an `if`/`else` whose checks may each jump to the
+    /// else-branch, as match arms do. Such a jump sets `flag` instead.
+    Guard {
+        target: usize,
+        region_end: usize,
+        flag: VarId,
+        used: bool,
+    },
+}
+
+enum Flow {
+    Next,
+    Exit(Exit),
+}
+
+enum Fail {
+    Goto { offset: usize, target: usize },
+    Error(DecompileError),
+}
+
+impl From<DecompileError> for Fail {
+    fn from(err: DecompileError) -> Self {
+        Fail::Error(err)
+    }
+}
+
+struct Structurer<'a> {
+    program: &'a Program,
+    insns: Vec<Insn>,
+    index_of: HashMap<usize, usize>,
+    back_edges: HashMap<usize, Vec<usize>>,
+    flow: ValueFlow,
+    next_temp: VarId,
+}
+
+impl<'a> Structurer<'a> {
+    fn new(program: &'a Program, insns: Vec<Insn>, flow: ValueFlow) -> Self {
+        let index_of = insns
+            .iter()
+            .enumerate()
+            .map(|(index, insn)| (insn.offset, index))
+            .collect::<HashMap<_, _>>();
+        let mut back_edges = HashMap::<usize, Vec<usize>>::new();
+        for insn in &insns {
+            if let Op::Br(target) = insn.op
+                && target <= insn.offset
+            {
+                back_edges.entry(target).or_default().push(insn.offset);
+            }
+        }
+        Self {
+            program,
+            insns,
+            index_of,
+            back_edges,
+            flow,
+            next_temp: FIRST_TEMP,
+        }
+    }
+
+    fn decompile(&mut self) -> Result<Vec<Stmt>, DecompileError> {
+        let end = self.program.code.len();
+        let block = match self.block(0, end, end, &mut Vec::new(), Vec::new()) {
+            Ok(block) => block,
+            Err(Fail::Goto { offset, target }) => {
+                return Err(DecompileError::UnstructuredJump { offset, target });
+            }
+            Err(Fail::Error(err)) => return Err(err),
+        };
+        let mut stmts = block.stmts;
+        stmts.extend(block.stack.into_iter().map(Stmt::Expr));
+        Ok(stmts)
+    }
+
+    fn temp(&mut self) -> VarId {
+        let temp = self.next_temp;
+        self.next_temp += 1;
+        temp
+    }
+
+    fn insn_before(&self, offset: usize) -> Option<Insn> {
+        let index = *self.index_of.get(&offset)?;
+        index.checked_sub(1).map(|index| self.insns[index])
+    }
+
+    /// Decompiles `start..end`. Control leaving the end continues at `follow`.
+    fn block(
+        &mut self,
+        start: usize,
+        end: usize,
+        follow: usize,
+        scopes: &mut Vec<Scope>,
+        mut stack: Vec<Expr>,
+    ) -> Result<Block, Fail> {
+        let mut stmts = Vec::new();
+        let mut ip = start;
+        while ip < end {
+            let construct = ip;
+            let mark = stmts.len();
+            let saved = stack.clone();
+            match self.construct(&mut ip, end, follow, scopes, &mut stack, &mut stmts) {
+                Ok(Flow::Next) => {}
+                Ok(Flow::Exit(exit)) => return Ok(Block { stmts, stack, exit }),
+                Err(Fail::Goto { target, .. })
+                    if target > construct
+                        && target <= end
+                        && (construct != start || target != end)
+                        && (target == end || self.index_of.contains_key(&target)) =>
+                {
+                    stmts.truncate(mark);
+                    stack = saved;
+                    self.spill(&mut stack, &mut stmts, |_| true);
+                    // The operands still live at the landing are the bottom
+                    // of those live here.
+                    let kept = self
+                        .index_of
+                        .get(&target)
+                        .and_then(|index| self.flow.depths[*index])
+                        .unwrap_or(0);
+                    if kept > stack.len() {
+                        return Err(DecompileError::UnbalancedStack { offset: target }.into());
+                    }
+                    let landing = stack[..kept].to_vec();
+                    scopes.push(Scope::OneShot {
+                        target,
+                        stack: landing.clone(),
+                    });
+                    let body = self.block(construct, target, target, scopes, stack);
+                    scopes.pop();
+                    let mut body = body?;
+                    if body.exit == Exit::Falls {
+                        if body.stack != landing {
+                            return Err(DecompileError::UnbalancedStack { offset: target }.into());
+                        }
+                        body.stmts.push(Stmt::Break);
+                    }
+                    stmts.push(Stmt::While(Expr::Const(Value::Bool(true)), body.stmts));
+                    stack = landing;
+                    ip = target;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(Block {
+            stmts,
+            stack,
+            exit: Exit::Falls,
+        })
+    }
+
+    fn construct(
+        &mut self,
+        ip: &mut usize,
+        end: usize,
+        follow: usize,
+        scopes: &mut Vec<Scope>,
+        stack: &mut Vec<Expr>,
+        stmts: &mut Vec<Stmt>,
+    ) -> Result<Flow, Fail> {
+        if let Some(latch) = self.loop_latch(*ip, end, scopes) {
+            *ip = self.structure_loop(*ip, latch, scopes, stack, stmts)?;
+            return Ok(Flow::Next);
+        }
+
+        let index = self.index_of[ip];
+        let insn = self.insns[index];
+        let offset = insn.offset;
+        *ip = insn.end;
+        match insn.op {
+            Op::Nop => {}
+            Op::Ret => {
+                if insn.end == self.program.code.len() && scopes.is_empty() {
+                    return Ok(Flow::Exit(Exit::Falls));
+                }
+                return Err(DecompileError::EarlyReturn { offset }.into());
+            }
+            Op::Ldc(index) => {
+                let value = self.program.constants[index as usize].clone();
+                self.push(stack, stmts, (offset, 0), Expr::Const(value));
+            }
+            Op::Ldloc(slot) => {
+                self.push(stack, stmts, (offset, 0), Expr::Var(slot as VarId));
+            }
+            Op::Stloc(slot) => {
+                let value = pop(stack, offset)?;
+                let slot = slot as VarId;
+                self.spill(stack, stmts, |var| var == slot);
+                stmts.push(Stmt::Assign(slot, value));
+            }
+            Op::Binary(op) => {
+                let rhs = pop(stack, offset)?;
+                let lhs = pop(stack, offset)?;
+                let value = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+                self.push(stack, stmts, (offset, 0), value);
+            }
+            Op::Neg => {
+                let value = Expr::Neg(Box::new(pop(stack, offset)?));
+                self.push(stack, stmts, (offset, 0), value);
+            }
+            Op::Pop => {
+                let value = pop(stack, offset)?;
+                if !value.is_trivial() {
+                    self.spill(stack, stmts, |_| false);
+                    let temp = self.temp();
+                    stmts.push(Stmt::Assign(temp, value));
+                }
+            }
+            Op::Dup => {
+                if let Some(done) = self.concat_coercion(index) {
+                    let value = pop(stack, offset)?;
+                    stack.push(Expr::Coerce(Box::new(value)));
+                    *ip = done;
+                    return Ok(Flow::Next);
+                }
+                let mut value = pop(stack, offset)?;
+                if !value.is_trivial() {
+                    self.spill(stack, stmts, |_| false);
+                    let temp = self.temp();
+                    stmts.push(Stmt::Assign(temp, value));
+                    value = Expr::Var(temp);
+                }
+                self.push(stack, stmts, (offset, 0), value.clone());
+                self.push(stack, stmts, (offset, 1), value);
+            }
+            Op::Call(index, argc) => {
+                let callee = match BuiltinFunction::from_call_index(index) {
+                    Some(builtin) => Callee::Builtin(builtin),
+                    None if (index as usize) < self.program.imports.len() => Callee::Import(index),
+                    None => {
+                        return Err(DecompileError::Unsupported(format!(
+                            "call to unknown import {index} at offset {offset}"
+                        ))
+                        .into());
+                    }
+                };
+                if stack.len() < argc as usize {
+                    return Err(DecompileError::StackUnderflow { offset }.into());
+                }
+                let args = stack.split_off(stack.len() - argc as usize);
+                self.push(stack, stmts, (offset, 0), Expr::Call(callee, args));
+            }
+            Op::Br(target) => {
+                if let Some(jump) = self.scope_jump(offset, target, stack, scopes)? {
+                    stmts.extend(jump);
+                    return Ok(Flow::Exit(Exit::Jumps));
+                }
+                if target == end || target == follow {
+                    return Ok(Flow::Exit(Exit::Falls));
+                }
+                return Err(Fail::Goto { offset, target });
+            }
+            Op::Brfalse(target) => {
+                let condition = pop(stack, offset)?;
+                return self.branch(
+                    ip, offset, condition, target, end, follow, scopes, stack, stmts,
+                );
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    /// Pushes `value`, or emits it as an expression statement when nothing
+    /// ever pops it.
+    fn push(&mut self, stack: &mut Vec<Expr>, stmts: &mut Vec<Stmt>, site: Site, value: Expr) {
+        if self.flow.is_consumed(site) {
+            stack.push(value);
+        } else {
+            self.spill(stack, stmts, |_| false);
+            stmts.push(Stmt::Expr(value));
+        }
+    }
+
+    /// Moves pending operands into temporaries before a statement runs, so
+    /// they are still evaluated first. Constants never need it; locals only
+    /// when `written` says the statement may change them.
+    fn spill(
+        &mut self,
+        stack: &mut [Expr],
+        stmts: &mut Vec<Stmt>,
+        written: impl Fn(VarId) -> bool,
+    ) {
+        for value in stack.iter_mut() {
+            let keep = match value {
+                Expr::Const(_) => true,
+                Expr::Var(var) => !written(*var),
+                _ => false,
+            };
+            if keep {
+                continue;
+            }
+            let temp = self.temp();
+            let value = std::mem::replace(value, Expr::Var(temp));
+            stmts.push(Stmt::Assign(temp, value));
+        }
+    }
+
+    /// Locals stored anywhere in `start..end`.
+    fn written_in(&self, start: usize, end: usize) -> Vec<VarId> {
+        let first = self
+            .index_of
+            .get(&start)
+            .copied()
+            .unwrap_or(self.insns.len());
+        self.insns[first..]
+            .iter()
+            .take_while(|insn| insn.offset < end)
+            .filter_map(|insn| match insn.op {
+                Op::Stloc(slot) => Some(slot as VarId),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The back edge closing a loop that starts at `header`, if any.
+    fn loop_latch(&self, header: usize, end: usize, scopes: &[Scope]) -> Option<usize> {
+        let is_open = scopes
+            .iter()
+            .any(|scope| matches!(scope, Scope::Loop { header: open, .. } if *open == header));
+        if is_open {
+            return None;
+        }
+        self.back_edges
+            .get(&header)?
+            .iter()
+            .copied()
+            .filter(|latch| *latch < end)
+            .max()
+    }
+
+    fn structure_loop(
+        &mut self,
+        header: usize,
+        latch: usize,
+        scopes: &mut Vec<Scope>,
+        stack: &mut [Expr],
+        stmts: &mut Vec<Stmt>,
+    ) -> Result<usize, Fail> {
+        let latch_index = self.index_of[&latch];
+        let exit = self.insns[latch_index].end;
+        let written = self.written_in(header, exit);
+        self.spill(stack, stmts, |var| written.contains(&var));
+
+        let mut tail_index = latch_index;
+        while tail_index > 0
+            && self.insns[tail_index - 1].offset >= header
+            && !matches!(
+                self.insns[tail_index - 1].op,
+                Op::Br(_) | Op::Brfalse(_) | Op::Ret
+            )
+        {
+            tail_index -= 1;
+        }
+        // `continue` jumps land where the stack is empty; a join inside an
+        // expression is not one.
+        while tail_index < latch_index && self.flow.depths[tail_index] != Some(0) {
+            tail_index += 1;
+        }
+        let tail = self.insns[tail_index].offset;
+
+        scopes.push(Scope::Loop {
+            header,
+            exit,
+            tail,
+            latch,
+            escape: None,
+        });
+        let result = self.loop_body(header, latch, exit, scopes);
+        let escape = match scopes.pop() {
+            Some(Scope::Loop { escape, .. }) => escape,
+            _ => None,
+        };
+        let (condition, mut body) = result?;
+        if body.exit == Exit::Falls && !body.stack.is_empty() {
+            return Err(DecompileError::UnbalancedStack { offset: latch }.into());
+        }
+        tidy_loop_tail(&mut body.stmts);
+        let Some((target, flag)) = escape else {
+            stmts.push(Stmt::While(condition, body.stmts));
+            return Ok(exit);
+        };
+        let jump = self
+            .scope_jump(latch, target, stack, scopes)?
+            .ok_or(Fail::Goto {
+                offset: latch,
+                target,
+            })?;
+        stmts.push(Stmt::Assign(flag, Expr::Const(Value::Bool(false))));
+        stmts.push(Stmt::While(condition, body.stmts));
+        stmts.push(Stmt::If(Expr::Var(flag), jump, Vec::new()));
+        Ok(exit)
+    }
+
+    fn loop_body(
+        &mut self,
+        header: usize,
+        latch: usize,
+        exit: usize,
+        scopes: &mut Vec<Scope>,
+    ) -> Result<(Expr, Block), Fail> {
+        let first = self.index_of[&header];
+        let test = self.insns[first..]
+            .iter()
+            .take_while(|insn| insn.offset < latch)
+            .find(|insn| matches!(insn.op, Op::Br(_) | Op::Brfalse(_)))
+            .copied();
+        if let Some(test) = test
+            && matches!(test.op, Op::Brfalse(target) if target == exit)
+        {
+            let head = self.block(header, test.offset, test.offset, scopes, Vec::new())?;
+            if head.exit == Exit::Falls && head.stmts.is_empty() && head.stack.len() == 1 {
+                let condition = head.stack.into_iter().next().expect("one value");
+                let body = self.block(test.end, latch, latch, scopes, Vec::new())?;
+                return Ok((condition, body));
+            }
+        }
+        let body = self.block(header, latch, latch, scopes, Vec::new())?;
+        Ok((Expr::Const(Value::Bool(true)), body))
+    }
+
+    /// The statements a jump to `target` becomes inside the open scopes, if
+    /// it leaves or restarts one of them. The jump at `offset` must leave the
+    /// operand stack as the scope expects it.
+    fn scope_jump(
+        &mut self,
+        offset: usize,
+        target: usize,
+        stack: &[Expr],
+        scopes: &mut Vec<Scope>,
+    ) -> Result<Option<Vec<Stmt>>, Fail> {
+        let balanced = |expected: &[Expr]| {
+            if stack == expected {
+                Ok(())
+            } else {
+                Err(Fail::from(DecompileError::UnbalancedStack { offset }))
+            }
+        };
+        let mut tail_jump = None;
+        for depth in (0..scopes.len()).rev() {
+            let (outer, inner) = scopes.split_at_mut(depth);
+            match &mut inner[0] {
+                Scope::Loop {
+                    header,
+                    exit,
+                    tail,
+                    latch,
+                    escape,
+                } => {
+                    if target == *header {
+                        balanced(&[])?;
+                        return Ok(Some(vec![Stmt::Continue]));
+                    }
+                    if target == *exit {
+                        balanced(&[])?;
+                        return Ok(Some(vec![Stmt::Break]));
+                    }
+                    if (*tail..=*latch).contains(&target) {
+                        tail_jump = Some(*latch);
+                        break;
+                    }
+                    let lands_outside = outer.iter().any(
+                        |scope| matches!(scope, Scope::OneShot { target: landing, .. } if *landing == target),
+                    );
+                    let flag = match *escape {
+                        Some((escaped, flag)) if lands_outside && escaped == target => flag,
+                        None if lands_outside => {
+                            let flag = self.next_temp;
+                            self.next_temp += 1;
+                            *escape = Some((target, flag));
+                            flag
+                        }
+                        _ => break,
+                    };
+                    balanced(&[])?;
+                    return Ok(Some(vec![
+                        Stmt::Assign(flag, Expr::Const(Value::Bool(true))),
+                        Stmt::Break,
+                    ]));
+                }
+                Scope::OneShot {
+                    target: landing,
+                    stack: entry,
+                } => {
+                    if target != *landing {
+                        break;
+                    }
+                    balanced(entry)?;
+                    return Ok(Some(vec![Stmt::Break]));
+                }
+                Scope::Guard { .. } => {}
+            }
+        }
+        let Some(latch) = tail_jump else {
+            return Ok(None);
+        };
+        balanced(&[])?;
+        let tail = self.block(target, latch, latch, scopes, Vec::new())?;
+        if tail.exit != Exit::Falls || !tail.stack.is_empty() {
+            return Err(DecompileError::UnbalancedStack { offset: target }.into());
+        }
+        let mut stmts = tail.stmts;
+        stmts.push(Stmt::Continue);
+        Ok(Some(stmts))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn branch(
+        &mut self,
+        ip: &mut usize,
+        offset: usize,
+        condition: Expr,
+        target: usize,
+        end: usize,
+        follow: usize,
+        scopes: &mut Vec<Scope>,
+        stack: &mut Vec<Expr>,
+        stmts: &mut Vec<Stmt>,
+    ) -> Result<Flow, Fail> {
+        let next = *ip;
+        if let Some(jump) = self.scope_jump(offset, target, stack, scopes)? {
+            stmts.push(Stmt::If(condition, Vec::new(), jump));
+            return Ok(Flow::Next);
+        }
+
+        if let Some(Scope::Guard {
+            target: else_start,
+            region_end,
+            flag,
+            used,
+        }) = scopes.last_mut()
+            && *else_start == target
+            && *region_end == end
+        {
+            if !stack.is_empty() {
+                return Err(DecompileError::UnbalancedStack { offset }.into());
+            }
+            *used = true;
+            let flag = *flag;
+            let rest = self.block(next, end, follow, scopes, Vec::new())?;
+            if rest.exit == Exit::Falls && !rest.stack.is_empty() {
+                return Err(DecompileError::UnbalancedStack { offset: end }.into());
+            }
+            stmts.push(Stmt::If(
+                condition,
+                rest.stmts,
+                vec![Stmt::Assign(flag, Expr::Const(Value::Bool(true)))],
+            ));
+            return Ok(Flow::Exit(Exit::Falls));
+        }
+
+        if target == follow && follow > end {
+            let rest = self.block(next, end, follow, scopes, stack.clone())?;
+            let skip = Block {
+                stmts: Vec::new(),
+                stack: stack.clone(),
+                exit: Exit::Falls,
+            };
+            self.merge(offset, condition, rest, skip, stack, stmts)?;
+            return Ok(Flow::Exit(Exit::Falls));
+        }
+
+        if target < next || target > end {
+            return Err(Fail::Goto { offset, target });
+        }
+
+        let else_range = match self.insn_before(target) {
+            Some(Insn {
+                offset: br_offset,
+                op: Op::Br(join),
+                ..
+            }) if br_offset >= next && join >= target && join <= end => Some((br_offset, join)),
+            _ => None,
+        };
+        let join = else_range.map_or(target, |(_, join)| join);
+        let written = self.written_in(next, join);
+        self.spill(stack, stmts, |var| written.contains(&var));
+
+        let Some((then_end, join)) = else_range else {
+            let then_block = self.block(next, target, target, scopes, stack.clone())?;
+            let skip = Block {
+                stmts: Vec::new(),
+                stack: stack.clone(),
+                exit: Exit::Falls,
+            };
+            *ip = target;
+            return self.merge(offset, condition, then_block, skip, stack, stmts);
+        };
+
+        let flag = self.temp();
+        scopes.push(Scope::Guard {
+            target,
+            region_end: then_end,
+            flag,
+            used: false,
+        });
+        let then_block = self.block(next, then_end, join, scopes, stack.clone());
+        let guard_used = matches!(scopes.pop(), Some(Scope::Guard { used: true, .. }));
+        let then_block = then_block?;
+        let else_block = self.block(target, join, join, scopes, stack.clone())?;
+        *ip = join;
+
+        if !guard_used {
+            return self.merge(offset, condition, then_block, else_block, stack, stmts);
+        }
+        let balanced = stack.is_empty()
+            && [&then_block, &else_block]
+                .iter()
+                .all(|block| block.exit == Exit::Jumps || block.stack.is_empty());
+        if !balanced {
+            return Err(DecompileError::UnbalancedStack { offset }.into());
+        }
+        stmts.push(Stmt::Assign(flag, Expr::Const(Value::Bool(false))));
+        stmts.push(Stmt::If(
+            condition,
+            then_block.stmts,
+            vec![Stmt::Assign(flag, Expr::Const(Value::Bool(true)))],
+        ));
+        stmts.push(Stmt::If(Expr::Var(flag), else_block.stmts, Vec::new()));
+        Ok(Flow::Next)
+    }
+
+    /// Joins the two arms of a conditional. Operands the arms leave behind
+    /// differently become an `if` expression, or temporaries when the arms
+    /// also run statements.
+    fn merge(
+        &mut self,
+        offset: usize,
+        condition: Expr,
+        mut then_block: Block,
+        mut else_block: Block,
+        stack: &mut Vec<Expr>,
+        stmts: &mut Vec<Stmt>,
+    ) -> Result<Flow, Fail> {
+        match (then_block.exit, else_block.exit) {
+            (Exit::Jumps, Exit::Jumps) => {
+                stmts.push(Stmt::If(condition, then_block.stmts, else_block.stmts));
+                return Ok(Flow::Exit(Exit::Jumps));
+            }
+            (Exit::Jumps, Exit::Falls) => {
+                *stack = else_block.stack;
+                stmts.push(Stmt::If(condition, then_block.stmts, else_block.stmts));
+                return Ok(Flow::Next);
+            }
+            (Exit::Falls, Exit::Jumps) => {
+                *stack = then_block.stack;
+                stmts.push(Stmt::If(condition, then_block.stmts, else_block.stmts));
+                return Ok(Flow::Next);
+            }
+            (Exit::Falls, Exit::Falls) => {}
+        }
+
+        let len = then_block.stack.len();
+        if else_block.stack.len() != len {
+            return Err(DecompileError::UnbalancedStack { offset }.into());
+        }
+        let shared = then_block
+            .stack
+            .iter()
+            .zip(&else_block.stack)
+            .take_while(|(lhs, rhs)| lhs == rhs)
+            .count();
+        if shared == len {
+            *stack = then_block.stack;
+            stmts.push(Stmt::If(condition, then_block.stmts, else_block.stmts));
+            return Ok(Flow::Next);
+        }
+        if shared + 1 == len && then_block.stmts.is_empty() && else_block.stmts.is_empty() {
+            let then_value = then_block.stack.pop().expect("differing value");
+            let else_value = else_block.stack.pop().expect("differing value");
+            *stack = then_block.stack;
+            stack.push(Expr::IfElse(
+                Box::new(condition),
+                Box::new(then_value),
+                Box::new(else_value),
+            ));
+            return Ok(Flow::Next);
+        }
+
+        let mut merged = then_block.stack[..shared].to_vec();
+        let then_values = then_block.stack.split_off(shared);
+        let else_values = else_block.stack.split_off(shared);
+        for (then_value, else_value) in then_values.into_iter().zip(else_values) {
+            let temp = self.temp();
+            then_block.stmts.push(Stmt::Assign(temp, then_value));
+            else_block.stmts.push(Stmt::Assign(temp, else_value));
+            merged.push(Expr::Var(temp));
+        }
+        *stack = merged;
+        stmts.push(Stmt::If(condition, then_block.stmts, else_block.stmts));
+        Ok(Flow::Next)
+    }
+
+    /// Recognises the sequence the compiler emits to turn a number operand of
+    /// a string `+` into a string, returning the offset just past it.
+    fn concat_coercion(&self, index: usize) -> Option<usize> {
+        let insns = self.insns.get(index..index + 14)?;
+        let type_of = BuiltinFunction::TypeOf.call_index();
+        let to_string = BuiltinFunction::ToString.call_index();
+        let is_type_name = |constant: u32, name: &str| {
+            matches!(
+                self.program.constants.get(constant as usize),
+                Some(Value::String(value)) if value == name
+            )
+        };
+        let done = insns[13].end;
+        match insns
+            .iter()
+            .map(|insn| insn.op)
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            [
+                Op::Dup,
+                Op::Call(check_int, 1),
+                Op::Ldc(int_name),
+                Op::Binary(BinaryOp::Eq),
+                Op::Brfalse(not_int),
+                Op::Call(int_to_string, 1),
+                Op::Br(int_done),
+                Op::Dup,
+                Op::Call(check_float, 1),
+                Op::Ldc(float_name),
+                Op::Binary(BinaryOp::Eq),
+                Op::Brfalse(not_float),
+                Op::Call(float_to_string, 1),
+                Op::Br(float_done),
+            ] if *check_int == type_of
+                && *check_float == type_of
+                && *int_to_string == to_string
+                && *float_to_string == to_string
+                && is_type_name(*int_name, "int")
+                && is_type_name(*float_name, "float")
+                && *not_int == insns[7].offset
+                && *not_float == done
+                && *int_done == done
+                && *float_done == done =>
+            {
+                Some(done)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Cleans up the end of a loop body, where falling through continues the
+/// loop. An `if`/`else` whose branches both jump back to the loop comes out
+/// as an empty then-branch, an else-branch ending in `continue`, and the
+/// then-branch after the `if`; the branches are put back together.
+fn tidy_loop_tail(stmts: &mut Vec<Stmt>) {
+    if stmts.last() == Some(&Stmt::Continue) {
+        stmts.pop();
+    }
+    let split = stmts.iter().rposition(|stmt| {
+        matches!(stmt, Stmt::If(_, then_branch, else_branch)
+            if then_branch.is_empty() && else_branch.last() == Some(&Stmt::Continue))
+    });
+    if let Some(index) = split
+        && index + 1 < stmts.len()
+    {
+        let rest = stmts.split_off(index + 1);
+        if let Some(Stmt::If(_, then_branch, _)) = stmts.last_mut() {
+            *then_branch = rest;
+        }
+    }
+    if let Some(Stmt::If(_, then_branch, else_branch)) = stmts.last_mut() {
+        tidy_loop_tail(then_branch);
+        tidy_loop_tail(else_branch);
+    }
+}
+
+fn pop(stack: &mut Vec<Expr>, offset: usize) -> Result<Expr, DecompileError> {
+    stack.pop().ok_or(DecompileError::StackUnderflow { offset })
+}
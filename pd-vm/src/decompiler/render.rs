@@ -0,0 +1,831 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+
+use super::{BinaryOp, Callee, DecompileError, Expr, FIRST_TEMP, Stmt, VarId};
+use crate::builtins::BuiltinFunction;
+use crate::bytecode::{Program, Value};
+
+const KEYWORDS: &[&str] = &[
+    "pub", "use", "as", "fn", "let", "for", "in", "if", "else", "match", "while", "break",
+    "continue", "return", "true", "false", "null", "struct", "enum", "impl", "type", "typeof",
+    "assert", "format", "print", "vm", "std", "io", "re", "rand", "uuid", "array", "map", "lua",
+];
+
+const OR: u8 = 1;
+const AND: u8 = 2;
+const COMPARE: u8 = 3;
+const TERM: u8 = 4;
+const FACTOR: u8 = 5;
+const UNARY: u8 = 6;
+const POSTFIX: u8 = 7;
+const ATOM: u8 = 8;
+
+pub(super) fn render_program(program: &Program, body: &[Stmt]) -> Result<String, DecompileError> {
+    let mut usage = Usage::default();
+    usage.block(body, &mut Vec::new());
+
+    let mut imports = BTreeSet::new();
+    let mut wildcard = false;
+    let mut import_paths = HashMap::new();
+    for index in &usage.imports {
+        let name = &program.imports[*index as usize].name;
+        let path = if is_ident(name) && (!is_reserved(name) || name == "print") {
+            imports.insert(name.clone());
+            name.clone()
+        } else if name.split("::").all(is_ident) {
+            wildcard = true;
+            format!("vm::{name}")
+        } else {
+            return Err(DecompileError::Unsupported(format!(
+                "a call to host function '{name}'"
+            )));
+        };
+        import_paths.insert(*index, path);
+    }
+
+    let names = local_names(program, usage.refs.keys().copied(), &imports);
+    let mut declared_at = HashSet::new();
+    let mut hoisted = Vec::new();
+    let mut vars = usage.refs.keys().copied().collect::<Vec<_>>();
+    vars.sort_unstable();
+    for var in vars {
+        let refs = &usage.refs[&var];
+        let first = &refs[0];
+        if first.declares && refs.iter().all(|seen| seen.path.starts_with(&first.path)) {
+            declared_at.insert(first.order);
+        } else {
+            hoisted.push(var);
+        }
+    }
+
+    let mut renderer = Renderer {
+        names,
+        import_paths,
+        declared_at,
+        order: 0,
+        out: String::new(),
+    };
+    if wildcard {
+        renderer.out.push_str("use vm::*;\n");
+    }
+    if !imports.is_empty() {
+        let list = imports.into_iter().collect::<Vec<_>>().join(", ");
+        let _ = writeln!(renderer.out, "use vm::{{{list}}};");
+    }
+    if !renderer.out.is_empty() {
+        renderer.out.push('\n');
+    }
+    for var in hoisted {
+        let _ = writeln!(renderer.out, "let {} = null;", renderer.names[&var]);
+    }
+    renderer.block(body, 0)?;
+    Ok(renderer.out)
+}
+
+struct Reference {
+    order: usize,
+    path: Vec<usize>,
+    declares: bool,
+}
+
+/// Where each local is read and written, in source order, and which blocks
+/// enclose each use; a local is declared where it is first assigned when
+/// every later use sits inside that same block.
+#[derive(Default)]
+struct Usage {
+    refs: HashMap<VarId, Vec<Reference>>,
+    imports: BTreeSet<u16>,
+    order: usize,
+    next_block: usize,
+}
+
+impl Usage {
+    fn block(&mut self, stmts: &[Stmt], path: &mut Vec<usize>) {
+        path.push(self.next_block);
+        self.next_block += 1;
+        for stmt in stmts {
+            self.stmt(stmt, path);
+        }
+        path.pop();
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, path: &mut Vec<usize>) {
+        let order = self.order;
+        self.order += 1;
+        match stmt {
+            Stmt::Assign(var, value) => {
+                self.expr(value, order, path);
+                self.add(*var, order, path, true);
+            }
+            Stmt::Expr(value) => self.expr(value, order, path),
+            Stmt::If(condition, then_branch, else_branch) => {
+                self.expr(condition, order, path);
+                self.block(then_branch, path);
+                self.block(else_branch, path);
+            }
+            Stmt::While(condition, body) => {
+                self.expr(condition, order, path);
+                self.block(body, path);
+            }
+            Stmt::Break | Stmt::Continue => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr, order: usize, path: &[usize]) {
+        match expr {
+            Expr::Const(_) => {}
+            Expr::Var(var) => self.add(*var, order, path, false),
+            Expr::Neg(inner) | Expr::Coerce(inner) => self.expr(inner, order, path),
+            Expr::Binary(_, lhs, rhs) => {
+                self.expr(lhs, order, path);
+                self.expr(rhs, order, path);
+            }
+            Expr::Call(callee, args) => {
+                if let Callee::Import(index) = callee {
+                    self.imports.insert(*index);
+                }
+                for arg in args {
+                    self.expr(arg, order, path);
+                }
+            }
+            Expr::IfElse(condition, then_expr, else_expr) => {
+                self.expr(condition, order, path);
+                self.expr(then_expr, order, path);
+                self.expr(else_expr, order, path);
+            }
+            Expr::Slice(container, start, end) => {
+                self.expr(container, order, path);
+                self.expr(start, order, path);
+                if let Some(end) = end {
+                    self.expr(end, order, path);
+                }
+            }
+        }
+    }
+
+    fn add(&mut self, var: VarId, order: usize, path: &[usize], declares: bool) {
+        self.refs.entry(var).or_default().push(Reference {
+            order,
+            path: path.to_vec(),
+            declares,
+        });
+    }
+}
+
+/// Names slots after their debug-info locals, including those of inlined
+/// calls, falling back to `local{slot}`, and numbers the decompiler's
+/// temporaries `tmp{n}`.
+fn local_names(
+    program: &Program,
+    vars: impl Iterator<Item = VarId>,
+    imports: &BTreeSet<String>,
+) -> HashMap<VarId, String> {
+    let mut debug_names = HashMap::new();
+    if let Some(debug) = &program.debug {
+        let inline_locals = debug.inline_frames.iter().flat_map(|frame| &frame.locals);
+        for local in debug.locals.iter().chain(inline_locals) {
+            debug_names
+                .entry(local.index as VarId)
+                .or_insert_with(|| local.name.clone());
+        }
+    }
+
+    let mut vars = vars.collect::<Vec<_>>();
+    vars.sort_unstable();
+    let mut taken = imports.iter().cloned().collect::<HashSet<_>>();
+    let mut names = HashMap::new();
+    let mut temps = 0usize;
+    for var in vars {
+        let base = if var >= FIRST_TEMP {
+            temps += 1;
+            format!("tmp{}", temps - 1)
+        } else {
+            match debug_names.get(&var) {
+                Some(name) if is_ident(name) && !is_reserved(name) => name.clone(),
+                _ => format!("local{var}"),
+            }
+        };
+        let mut name = base.clone();
+        let mut suffix = 1;
+        while taken.contains(&name) {
+            name = format!("{base}_{suffix}");
+            suffix += 1;
+        }
+        taken.insert(name.clone());
+        names.insert(var, name);
+    }
+    names
+}
+
+fn is_ident(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+fn is_reserved(name: &str) -> bool {
+    KEYWORDS.contains(&name)
+}
+
+fn is_field_name(name: &str) -> bool {
+    is_ident(name) && !is_reserved(name) && name != "length" && name != "keys"
+}
+
+struct Renderer {
+    names: HashMap<VarId, String>,
+    import_paths: HashMap<u16, String>,
+    declared_at: HashSet<usize>,
+    order: usize,
+    out: String,
+}
+
+impl Renderer {
+    fn block(&mut self, stmts: &[Stmt], depth: usize) -> Result<(), DecompileError> {
+        for stmt in stmts {
+            self.stmt(stmt, depth)?;
+        }
+        Ok(())
+    }
+
+    fn line(&mut self, depth: usize, text: &str) {
+        for _ in 0..depth {
+            self.out.push_str("    ");
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, depth: usize) -> Result<(), DecompileError> {
+        let order = self.order;
+        self.order += 1;
+        match stmt {
+            Stmt::Assign(var, value) => {
+                let name = self.names[var].clone();
+                if self.declared_at.contains(&order) {
+                    let value = self.expr(value, 0)?;
+                    self.line(depth, &format!("let {name} = {value};"));
+                    return Ok(());
+                }
+                if let Expr::Call(Callee::Builtin(BuiltinFunction::Set), args) = value
+                    && let [Expr::Var(target), key, element] = args.as_slice()
+                    && target == var
+                {
+                    let target = self.index_target(&name, key)?;
+                    let element = self.expr(element, 0)?;
+                    self.line(depth, &format!("{target} = {element};"));
+                    return Ok(());
+                }
+                let value = self.expr(value, 0)?;
+                self.line(depth, &format!("{name} = {value};"));
+            }
+            Stmt::Expr(value) => {
+                let text = self.expr(value, 0)?;
+                let text = statement_safe(text);
+                self.line(depth, &format!("{text};"));
+            }
+            Stmt::If(condition, then_branch, else_branch) => {
+                let condition = statement_safe(self.expr(condition, 0)?);
+                self.line(depth, &format!("if {condition} {{"));
+                self.block(then_branch, depth + 1)?;
+                self.else_branch(else_branch, depth)?;
+            }
+            Stmt::While(condition, body) => {
+                let condition = statement_safe(self.expr(condition, 0)?);
+                self.line(depth, &format!("while {condition} {{"));
+                self.block(body, depth + 1)?;
+                self.line(depth, "}");
+            }
+            Stmt::Break => self.line(depth, "break;"),
+            Stmt::Continue => self.line(depth, "continue;"),
+        }
+        Ok(())
+    }
+
+    fn else_branch(&mut self, else_branch: &[Stmt], depth: usize) -> Result<(), DecompileError> {
+        match else_branch {
+            [] => self.line(depth, "}"),
+            [Stmt::If(condition, then_branch, nested_else)] => {
+                self.order += 1;
+                let condition = statement_safe(self.expr(condition, 0)?);
+                self.line(depth, &format!("}} else if {condition} {{"));
+                self.block(then_branch, depth + 1)?;
+                self.else_branch(nested_else, depth)?;
+            }
+            _ => {
+                self.line(depth, "} else {");
+                self.block(else_branch, depth + 1)?;
+                self.line(depth, "}");
+            }
+        }
+        Ok(())
+    }
+
+    fn index_target(&mut self, name: &str, key: &Expr) -> Result<String, DecompileError> {
+        if let Expr::Const(Value::String(field)) = key
+            && is_field_name(field)
+        {
+            return Ok(format!("{name}.{field}"));
+        }
+        Ok(format!("{name}[{}]", self.expr(key, 0)?))
+    }
+
+    /// Renders `expr`, parenthesised if it binds looser than `min`.
+    fn expr(&mut self, expr: &Expr, min: u8) -> Result<String, DecompileError> {
+        let (text, prec) = self.expr_prec(expr)?;
+        // Members and indexing only follow a name, a call, or parentheses.
+        let literal_target = min == POSTFIX && matches!(expr, Expr::Const(_));
+        Ok(if prec < min || literal_target {
+            format!("({text})")
+        } else {
+            text
+        })
+    }
+
+    fn expr_prec(&mut self, expr: &Expr) -> Result<(String, u8), DecompileError> {
+        Ok(match expr {
+            Expr::Const(value) => literal(value)?,
+            Expr::Var(var) => (self.names[var].clone(), ATOM),
+            Expr::Neg(inner) => {
+                let inner = self.expr(inner, UNARY)?;
+                let inner = if inner.starts_with('-') {
+                    format!("({inner})")
+                } else {
+                    inner
+                };
+                (format!("-{inner}"), UNARY)
+            }
+            Expr::Binary(op, lhs, rhs) => self.binary(*op, lhs, rhs)?,
+            Expr::Coerce(inner) => {
+                let inner = self.expr(inner, FACTOR)?;
+                (format!("\"\" + {inner}"), TERM)
+            }
+            Expr::IfElse(..) => (self.if_expr(expr)?, 0),
+            Expr::Call(callee, args) => self.call(*callee, args)?,
+            Expr::Slice(container, start, end) => {
+                let container = self.expr(container, POSTFIX)?;
+                let start = self.expr(start, 0)?;
+                let end = match end {
+                    Some(end) => self.expr(end, 0)?,
+                    None => String::new(),
+                };
+                (format!("{container}[{start}:{end}]"), POSTFIX)
+            }
+        })
+    }
+
+    fn if_expr(&mut self, expr: &Expr) -> Result<String, DecompileError> {
+        let Expr::IfElse(condition, then_expr, else_expr) = expr else {
+            return self.expr(expr, 0);
+        };
+        let condition = statement_safe(self.expr(condition, 0)?);
+        let then_expr = self.expr(then_expr, 0)?;
+        let else_part = if matches!(else_expr.as_ref(), Expr::IfElse(..)) {
+            self.if_expr(else_expr)?
+        } else {
+            format!("=> {{ {} }}", self.expr(else_expr, 0)?)
+        };
+        Ok(format!(
+            "if {condition} => {{ {then_expr} }} else {else_part}"
+        ))
+    }
+
+    fn binary(
+        &mut self,
+        op: BinaryOp,
+        lhs: &Expr,
+        rhs: &Expr,
+    ) -> Result<(String, u8), DecompileError> {
+        if op == BinaryOp::Eq
+            && let Some((map, key)) = key_probe(lhs, rhs)
+        {
+            let map = self.expr(map, 0)?;
+            let key = self.expr(key, 0)?;
+            return Ok((format!("map::has_key({map}, {key})"), POSTFIX));
+        }
+        let (symbol, prec) = match op {
+            BinaryOp::Add => return self.add(lhs, rhs),
+            BinaryOp::Eq if matches!(rhs, Expr::Const(Value::Bool(false))) => {
+                if let Expr::Binary(BinaryOp::Eq, eq_lhs, eq_rhs) = lhs
+                    && !matches!(eq_rhs.as_ref(), Expr::Const(Value::Bool(false)))
+                {
+                    let eq_lhs = self.expr(eq_lhs, COMPARE)?;
+                    let eq_rhs = self.expr(eq_rhs, COMPARE + 1)?;
+                    return Ok((format!("{eq_lhs} != {eq_rhs}"), COMPARE));
+                }
+                let inner = self.expr(lhs, UNARY)?;
+                return Ok((format!("!{inner}"), UNARY));
+            }
+            BinaryOp::Shl => {
+                let Expr::Const(Value::Int(shift @ 0..=62)) = rhs else {
+                    return Err(DecompileError::Unsupported(
+                        "a shift by a non-constant amount".to_string(),
+                    ));
+                };
+                let lhs = self.expr(lhs, FACTOR)?;
+                return Ok((format!("{lhs} * {}", 1i64 << shift), FACTOR));
+            }
+            BinaryOp::Shr => {
+                return Err(DecompileError::Unsupported("a right shift".to_string()));
+            }
+            BinaryOp::Mul => {
+                // A power-of-two literal would recompile to a shift.
+                let lhs = self.mul_operand(lhs, FACTOR)?;
+                let rhs = self.mul_operand(rhs, FACTOR + 1)?;
+                return Ok((format!("{lhs} * {rhs}"), FACTOR));
+            }
+            BinaryOp::Sub => ("-", TERM),
+            BinaryOp::Div => ("/", FACTOR),
+            BinaryOp::Mod => ("%", FACTOR),
+            BinaryOp::Eq => ("==", COMPARE),
+            BinaryOp::Lt => ("<", COMPARE),
+            BinaryOp::Gt => (">", COMPARE),
+            BinaryOp::And => ("&&", AND),
+            BinaryOp::Or => ("||", OR),
+        };
+        let lhs = self.expr(lhs, prec)?;
+        let rhs = self.expr(rhs, prec + 1)?;
+        Ok((format!("{lhs} {symbol} {rhs}"), prec))
+    }
+
+    fn mul_operand(&mut self, expr: &Expr, min: u8) -> Result<String, DecompileError> {
+        match expr {
+            Expr::Const(Value::Int(value)) if *value > 0 && (*value as u64).is_power_of_two() => {
+                Ok(format!("({value} + 0)"))
+            }
+            _ => self.expr(expr, min),
+        }
+    }
+
+    fn add(&mut self, lhs: &Expr, rhs: &Expr) -> Result<(String, u8), DecompileError> {
+        let (lhs, rhs) = add_operands(lhs, rhs);
+        let lhs = self.expr(lhs, TERM)?;
+        let rhs = self.expr(rhs, TERM + 1)?;
+        Ok((format!("{lhs} + {rhs}"), TERM))
+    }
+
+    fn args(&mut self, args: &[Expr]) -> Result<String, DecompileError> {
+        let args = args
+            .iter()
+            .map(|arg| self.expr(arg, 0))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(args.join(", "))
+    }
+
+    fn call(&mut self, callee: Callee, args: &[Expr]) -> Result<(String, u8), DecompileError> {
+        let builtin = match callee {
+            Callee::Import(index) => {
+                let path = self.import_paths[&index].clone();
+                return Ok((format!("{path}({})", self.args(args)?), POSTFIX));
+            }
+            Callee::Builtin(builtin) => builtin,
+        };
+        if let Some(path) = builtin.source_path() {
+            return Ok((format!("{path}({})", self.args(args)?), POSTFIX));
+        }
+        let unsupported = || {
+            DecompileError::Unsupported(format!(
+                "the '{}' builtin outside the syntax that produces it",
+                builtin.name()
+            ))
+        };
+        Ok(match (builtin, args) {
+            (BuiltinFunction::Len | BuiltinFunction::Count, [value]) => {
+                (format!("{}.length", self.expr(value, POSTFIX)?), POSTFIX)
+            }
+            (BuiltinFunction::Keys, [value]) => {
+                (format!("{}.keys", self.expr(value, POSTFIX)?), POSTFIX)
+            }
+            (BuiltinFunction::Get, [container, key]) => {
+                let container = self.expr(container, POSTFIX)?;
+                match key {
+                    Expr::Const(Value::String(field)) if is_field_name(field) => {
+                        (format!("{container}.{field}"), POSTFIX)
+                    }
+                    _ => (format!("{container}[{}]", self.expr(key, 0)?), POSTFIX),
+                }
+            }
+            (BuiltinFunction::Slice, [container, start, len]) => {
+                self.slice(container, start, len)?
+            }
+            (BuiltinFunction::TypeOf, [value]) => {
+                (format!("type({})", self.expr(value, 0)?), POSTFIX)
+            }
+            (BuiltinFunction::Assert, [value]) => {
+                (format!("assert({})", self.expr(value, 0)?), POSTFIX)
+            }
+            (BuiltinFunction::ToString, [value]) => {
+                self.expr_prec(&Expr::Coerce(Box::new(value.clone())))?
+            }
+            (BuiltinFunction::Concat, [lhs, rhs]) => {
+                let lhs = self.expr(lhs, TERM)?;
+                let rhs = self.expr(rhs, TERM + 1)?;
+                (format!("{lhs} + {rhs}"), TERM)
+            }
+            (BuiltinFunction::ArrayNew | BuiltinFunction::MapNew | BuiltinFunction::Set, _) => {
+                match self.literal_chain(builtin, args)? {
+                    Some(text) => (text, ATOM),
+                    None => return Err(unsupported()),
+                }
+            }
+            (BuiltinFunction::ArrayPush, [array, element]) => {
+                if let Some(text) = self.literal_chain(builtin, args)? {
+                    (text, ATOM)
+                } else {
+                    // `+` on two arrays appends, as a push does.
+                    let array = self.expr(array, TERM)?;
+                    let element = self.expr(element, 0)?;
+                    (format!("{array} + [{element}]"), TERM)
+                }
+            }
+            (BuiltinFunction::Format, [template, format_args]) => {
+                match self.format_call(template, format_args)? {
+                    Some(text) => (text, POSTFIX),
+                    None => return Err(unsupported()),
+                }
+            }
+            _ => return Err(unsupported()),
+        })
+    }
+
+    /// A slice whose operands did not fold back into `c[s:e]` takes a
+    /// length; `c[s:s + len]` asks for the same one.
+    fn slice(
+        &mut self,
+        container: &Expr,
+        start: &Expr,
+        len: &Expr,
+    ) -> Result<(String, u8), DecompileError> {
+        if !start.is_trivial() {
+            return Err(DecompileError::Unsupported(
+                "a slice with a computed start".to_string(),
+            ));
+        }
+        let container = self.expr(container, POSTFIX)?;
+        let start_text = self.expr(start, 0)?;
+        let end = self.add(start, len)?.0;
+        Ok((format!("{container}[{start_text}:{end}]"), POSTFIX))
+    }
+
+    /// `[a, b]` and `{k: v}` lower to a push or set per element onto an
+    /// empty array or map.
+    fn literal_chain(
+        &mut self,
+        builtin: BuiltinFunction,
+        args: &[Expr],
+    ) -> Result<Option<String>, DecompileError> {
+        let mut entries = Vec::new();
+        let mut builtin = builtin;
+        let mut args = args;
+        let root = loop {
+            match (builtin, args) {
+                (BuiltinFunction::ArrayNew | BuiltinFunction::MapNew, []) => break builtin,
+                (BuiltinFunction::ArrayPush, [inner, element]) => {
+                    entries.push((None, element));
+                    let Expr::Call(Callee::Builtin(next), next_args) = inner else {
+                        return Ok(None);
+                    };
+                    builtin = *next;
+                    args = next_args;
+                }
+                (BuiltinFunction::Set, [inner, key, value]) => {
+                    entries.push((Some(key), value));
+                    let Expr::Call(Callee::Builtin(next), next_args) = inner else {
+                        return Ok(None);
+                    };
+                    builtin = *next;
+                    args = next_args;
+                }
+                _ => return Ok(None),
+            }
+        };
+        entries.reverse();
+        let is_array = root == BuiltinFunction::ArrayNew;
+        if entries.iter().any(|(key, _)| key.is_some() == is_array) {
+            return Ok(None);
+        }
+
+        let mut items = Vec::new();
+        for (key, value) in entries {
+            let value = self.expr(value, 0)?;
+            items.push(match key {
+                None => value,
+                Some(key) => format!("{}: {value}", self.map_key(key)?),
+            });
+        }
+        Ok(Some(if is_array {
+            format!("[{}]", items.join(", "))
+        } else if items.is_empty() {
+            "{}".to_string()
+        } else {
+            format!("{{ {} }}", items.join(", "))
+        }))
+    }
+
+    fn map_key(&mut self, key: &Expr) -> Result<String, DecompileError> {
+        Ok(match key {
+            Expr::Const(Value::String(name)) if is_ident(name) && !is_reserved(name) => {
+                name.clone()
+            }
+            Expr::Const(Value::String(name)) => quote(name),
+            _ => format!("[{}]", self.expr(key, 0)?),
+        })
+    }
+
+    /// `format!(template, args...)` passes its arguments as an array, or as a
+    /// map when some are named.
+    fn format_call(
+        &mut self,
+        template: &Expr,
+        args: &Expr,
+    ) -> Result<Option<String>, DecompileError> {
+        let mut entries = Vec::new();
+        let mut current = args;
+        let root = loop {
+            match current {
+                Expr::Call(Callee::Builtin(BuiltinFunction::ArrayPush), chain) => {
+                    let [inner, element] = chain.as_slice() else {
+                        return Ok(None);
+                    };
+                    entries.push((None, element));
+                    current = inner;
+                }
+                Expr::Call(Callee::Builtin(BuiltinFunction::Set), chain) => {
+                    let [inner, key, value] = chain.as_slice() else {
+                        return Ok(None);
+                    };
+                    entries.push((Some(key), value));
+                    current = inner;
+                }
+                Expr::Call(
+                    Callee::Builtin(root @ (BuiltinFunction::ArrayNew | BuiltinFunction::MapNew)),
+                    chain,
+                ) if chain.is_empty() => {
+                    break *root;
+                }
+                _ => return Ok(None),
+            }
+        };
+        entries.reverse();
+
+        let mut parts = vec![self.expr(template, 0)?];
+        let mut named = false;
+        for (position, (key, value)) in entries.into_iter().enumerate() {
+            let value = self.expr(value, 0)?;
+            match (root, key) {
+                (BuiltinFunction::ArrayNew, None) => parts.push(value),
+                (BuiltinFunction::MapNew, Some(Expr::Const(Value::Int(index))))
+                    if !named && *index == position as i64 =>
+                {
+                    parts.push(value)
+                }
+                (BuiltinFunction::MapNew, Some(Expr::Const(Value::String(name))))
+                    if is_ident(name) && !is_reserved(name) =>
+                {
+                    named = true;
+                    parts.push(format!("{name} = {value}"));
+                }
+                _ => return Ok(None),
+            }
+        }
+        Ok(Some(format!("format!({})", parts.join(", "))))
+    }
+}
+
+/// Matches `len(set(map, key, null)) == len(map)`, the key test optional
+/// member access lowers to, returning the map and key.
+fn key_probe<'e>(lhs: &'e Expr, rhs: &'e Expr) -> Option<(&'e Expr, &'e Expr)> {
+    let Expr::Call(Callee::Builtin(BuiltinFunction::Len), probed) = lhs else {
+        return None;
+    };
+    let Expr::Call(Callee::Builtin(BuiltinFunction::Len), original) = rhs else {
+        return None;
+    };
+    let [Expr::Call(Callee::Builtin(BuiltinFunction::Set), set_args)] = probed.as_slice() else {
+        return None;
+    };
+    match (set_args.as_slice(), original.as_slice()) {
+        ([map, key, Expr::Const(Value::Null)], [original]) if map == original => Some((map, key)),
+        _ => None,
+    }
+}
+
+/// The operands to print for `lhs + rhs`. The compiler converts a number
+/// operand to a string when the other side is a string literal or literal
+/// concatenation; that conversion is dropped when the printed operands
+/// still trigger it.
+fn add_operands<'e>(lhs: &'e Expr, rhs: &'e Expr) -> (&'e Expr, &'e Expr) {
+    match (lhs, rhs) {
+        (lhs, Expr::Coerce(inner)) if is_string_surface(lhs) => (lhs, inner),
+        (Expr::Coerce(inner), rhs) if is_string_surface(rhs) && !is_string_surface(inner) => {
+            (inner, rhs)
+        }
+        _ => (lhs, rhs),
+    }
+}
+
+/// Whether the printed form of `expr` is one the compiler treats as
+/// definitely a string.
+fn is_string_surface(expr: &Expr) -> bool {
+    match expr {
+        Expr::Const(Value::String(_)) => true,
+        Expr::Coerce(inner) => is_string_surface(inner) || is_const_int_surface(inner),
+        Expr::Binary(BinaryOp::Add, lhs, rhs) => {
+            let (lhs, rhs) = add_operands(lhs, rhs);
+            (is_string_surface(lhs) && (is_string_surface(rhs) || is_const_int_surface(rhs)))
+                || (is_const_int_surface(lhs) && is_string_surface(rhs))
+        }
+        _ => false,
+    }
+}
+
+fn is_const_int_surface(expr: &Expr) -> bool {
+    match expr {
+        Expr::Const(Value::Int(_)) => true,
+        Expr::Neg(inner) => is_const_int_surface(inner),
+        Expr::Binary(BinaryOp::Add | BinaryOp::Sub | BinaryOp::Div, lhs, rhs) => {
+            is_const_int_surface(lhs) && is_const_int_surface(rhs)
+        }
+        Expr::Binary(BinaryOp::Shl, lhs, rhs) => {
+            is_const_int_surface(lhs) && matches!(rhs.as_ref(), Expr::Const(Value::Int(0..=62)))
+        }
+        _ => false,
+    }
+}
+
+/// Wraps expressions a statement or condition would otherwise misread as a
+/// block or an `if` statement.
+fn statement_safe(text: String) -> String {
+    if text.starts_with('{') || text.starts_with("if ") {
+        format!("({text})")
+    } else {
+        text
+    }
+}
+
+fn literal(value: &Value) -> Result<(String, u8), DecompileError> {
+    Ok(match value {
+        Value::Null => ("null".to_string(), ATOM),
+        Value::Bool(value) => (value.to_string(), ATOM),
+        Value::Int(i64::MIN) => (format!("({} - 1)", i64::MIN + 1), ATOM),
+        Value::Int(value) if *value < 0 => (value.to_string(), UNARY),
+        Value::Int(value) => (value.to_string(), ATOM),
+        Value::Float(value) if !value.is_finite() => {
+            return Err(DecompileError::Unsupported(format!(
+                "the float constant {value}"
+            )));
+        }
+        Value::Float(value) => {
+            let mut text = value.to_string();
+            if !text.contains('.') {
+                text.push_str(".0");
+            }
+            let prec = if text.starts_with('-') { UNARY } else { ATOM };
+            (text, prec)
+        }
+        Value::String(text) => (quote(text), ATOM),
+        Value::Array(values) => {
+            let items = values
+                .iter()
+                .map(|value| literal(value).map(|(text, _)| text))
+                .collect::<Result<Vec<_>, _>>()?;
+            (format!("[{}]", items.join(", ")), ATOM)
+        }
+        Value::Map(entries) => {
+            if entries.is_empty() {
+                return Ok(("{}".to_string(), ATOM));
+            }
+            let items = entries
+                .iter()
+                .map(|(key, value)| {
+                    let key = match key {
+                        Value::String(name) if is_ident(name) && !is_reserved(name) => name.clone(),
+                        Value::String(name) => quote(name),
+                        other => format!("[{}]", literal(other)?.0),
+                    };
+                    Ok(format!("{key}: {}", literal(value)?.0))
+                })
+                .collect::<Result<Vec<_>, DecompileError>>()?;
+            (format!("{{ {} }}", items.join(", ")), ATOM)
+        }
+    })
+}
+
+fn quote(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for ch in text.chars() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\0' => out.push_str("\\0"),
+            other => out.push(other),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,245 @@
+//! Template parsing shared by the `format` builtin and the compile-time
+//! placeholder checks in the frontends.
+//!
+//! Templates follow a small subset of Rust's `format!` syntax:
+//!
+//! - `{}` takes the next positional argument, `{1}` a specific one, and
+//!   `{name}` a named argument
+//! - `{{` and `}}` produce literal braces
+//! - an optional `:spec` after the argument is `[[fill]align][0][width][.precision][?]`,
+//!   where `align` is one of `<`, `^`, `>` and `?` selects the debug representation;
+//!   width and precision are at most 65535
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum FormatArg {
+    Next,
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum FormatAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct FormatSpec {
+    pub(crate) fill: char,
+    pub(crate) align: Option<FormatAlign>,
+    pub(crate) zero_pad: bool,
+    pub(crate) width: Option<usize>,
+    pub(crate) precision: Option<usize>,
+    pub(crate) debug: bool,
+}
+
+impl Default for FormatSpec {
+    fn default() -> Self {
+        Self {
+            fill: ' ',
+            align: None,
+            zero_pad: false,
+            width: None,
+            precision: None,
+            debug: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum FormatPiece {
+    Literal(String),
+    Placeholder { arg: FormatArg, spec: FormatSpec },
+}
+
+/// Arguments a template refers to, used to validate call sites.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct FormatRequirements {
+    pub(crate) positional: usize,
+    pub(crate) named: Vec<String>,
+}
+
+pub(crate) fn parse_format_template(template: &str) -> Result<Vec<FormatPiece>, String> {
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.char_indices().peekable();
+
+    while let Some((offset, ch)) = chars.next() {
+        match ch {
+            '{' if chars.peek().map(|(_, next)| *next) == Some('{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek().map(|(_, next)| *next) == Some('}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '}' => {
+                return Err(format!(
+                    "unmatched '}}' at offset {offset} in format string (use '}}}}' for a literal brace)"
+                ));
+            }
+            '{' => {
+                let mut body = String::new();
+                let mut closed = false;
+                for (_, next) in chars.by_ref() {
+                    if next == '}' {
+                        closed = true;
+                        break;
+                    }
+                    body.push(next);
+                }
+                if !closed {
+                    return Err(format!(
+                        "unterminated placeholder at offset {offset} in format string (use '{{{{' for a literal brace)"
+                    ));
+                }
+                if !literal.is_empty() {
+                    pieces.push(FormatPiece::Literal(std::mem::take(&mut literal)));
+                }
+                pieces.push(parse_placeholder(&body)?);
+            }
+            _ => literal.push(ch),
+        }
+    }
+
+    if !literal.is_empty() {
+        pieces.push(FormatPiece::Literal(literal));
+    }
+    Ok(pieces)
+}
+
+pub(crate) fn format_requirements(pieces: &[FormatPiece]) -> FormatRequirements {
+    let mut requirements = FormatRequirements::default();
+    let mut next_positional = 0usize;
+    for piece in pieces {
+        let FormatPiece::Placeholder { arg, .. } = piece else {
+            continue;
+        };
+        match arg {
+            FormatArg::Next => {
+                next_positional += 1;
+                requirements.positional = requirements.positional.max(next_positional);
+            }
+            FormatArg::Index(index) => {
+                requirements.positional = requirements.positional.max(index + 1);
+            }
+            FormatArg::Name(name) => {
+                if !requirements.named.contains(name) {
+                    requirements.named.push(name.clone());
+                }
+            }
+        }
+    }
+    requirements
+}
+
+fn parse_placeholder(body: &str) -> Result<FormatPiece, String> {
+    let (arg_text, spec_text) = match body.split_once(':') {
+        Some((arg, spec)) => (arg.trim(), Some(spec)),
+        None => (body.trim(), None),
+    };
+
+    let arg = if arg_text.is_empty() {
+        FormatArg::Next
+    } else if arg_text.bytes().all(|b| b.is_ascii_digit()) {
+        FormatArg::Index(
+            arg_text
+                .parse()
+                .map_err(|_| format!("invalid placeholder index '{arg_text}'"))?,
+        )
+    } else if arg_text
+        .chars()
+        .next()
+        .is_some_and(|ch| ch.is_ascii_alphabetic() || ch == '_')
+        && arg_text
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+    {
+        FormatArg::Name(arg_text.to_string())
+    } else {
+        return Err(format!("invalid placeholder '{{{body}}}' in format string"));
+    };
+
+    let spec = match spec_text {
+        Some(spec) => parse_spec(spec).map_err(|message| {
+            format!("invalid format spec ':{spec}' in placeholder '{{{body}}}': {message}")
+        })?,
+        None => FormatSpec::default(),
+    };
+    Ok(FormatPiece::Placeholder { arg, spec })
+}
+
+/// Largest width or precision a spec may ask for, matching Rust's own limit, so
+/// a template cannot make the VM pad a string to an arbitrary size.
+const MAX_SPEC_COUNT: usize = u16::MAX as usize;
+
+fn parse_spec_count(digits: &[char], what: &str) -> Result<usize, String> {
+    digits
+        .iter()
+        .collect::<String>()
+        .parse::<usize>()
+        .ok()
+        .filter(|count| *count <= MAX_SPEC_COUNT)
+        .ok_or_else(|| format!("{what} is too large (at most {MAX_SPEC_COUNT})"))
+}
+
+fn parse_spec(spec: &str) -> Result<FormatSpec, String> {
+    let chars = spec.chars().collect::<Vec<_>>();
+    let mut out = FormatSpec::default();
+    let mut i = 0usize;
+
+    let align_of = |ch: char| match ch {
+        '<' => Some(FormatAlign::Left),
+        '^' => Some(FormatAlign::Center),
+        '>' => Some(FormatAlign::Right),
+        _ => None,
+    };
+    if let Some(align) = chars.get(1).copied().and_then(align_of) {
+        out.fill = chars[0];
+        out.align = Some(align);
+        i = 2;
+    } else if let Some(align) = chars.first().copied().and_then(align_of) {
+        out.align = Some(align);
+        i = 1;
+    }
+
+    if chars.get(i) == Some(&'0') {
+        out.zero_pad = true;
+        i += 1;
+    }
+
+    let width_start = i;
+    while chars.get(i).is_some_and(char::is_ascii_digit) {
+        i += 1;
+    }
+    if i > width_start {
+        out.width = Some(parse_spec_count(&chars[width_start..i], "width")?);
+    }
+
+    if chars.get(i) == Some(&'.') {
+        i += 1;
+        let precision_start = i;
+        while chars.get(i).is_some_and(char::is_ascii_digit) {
+            i += 1;
+        }
+        if i == precision_start {
+            return Err("expected digits after '.'".to_string());
+        }
+        out.precision = Some(parse_spec_count(&chars[precision_start..i], "precision")?);
+    }
+
+    if chars.get(i) == Some(&'?') {
+        out.debug = true;
+        i += 1;
+    }
+
+    if i != chars.len() {
+        return Err(format!(
+            "unexpected '{}'",
+            chars[i..].iter().collect::<String>()
+        ));
+    }
+    Ok(out)
+}
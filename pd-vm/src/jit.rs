@@ -118,6 +118,9 @@ pub struct JitTrace {
     pub has_call: bool,
     pub has_yielding_call: bool,
     pub steps: Vec<TraceStep>,
+    /// Bytecode offset of the instruction each step was recorded from, so
+    /// native code can hand a step it cannot run back to the interpreter.
+    pub step_ips: Vec<usize>,
     pub terminal: JitTraceTerminal,
     pub executions: u64,
 }
@@ -339,97 +342,98 @@ impl TraceJitEngine {
     fn compile_trace(&mut self, program: &Program, root_ip: usize) -> Result<usize, JitNyiReason> {
         let code = &program.code;
         let mut ip = root_ip;
-        let mut steps = Vec::new();
+        let mut steps = RecordedSteps::default();
 
-        while steps.len() < self.config.max_trace_len {
+        while steps.steps.len() < self.config.max_trace_len {
+            let op_ip = ip;
             let opcode = *code
                 .get(ip)
                 .ok_or(JitNyiReason::InvalidJumpTarget { target: ip })?;
             ip = ip.saturating_add(1);
 
             if opcode == OpCode::Nop as u8 {
-                steps.push(TraceStep::Nop);
+                steps.push(TraceStep::Nop, op_ip);
                 continue;
             }
             if opcode == OpCode::Ret as u8 {
-                steps.push(TraceStep::Ret);
+                steps.push(TraceStep::Ret, op_ip);
                 return Ok(self.finish_trace(program, root_ip, steps, JitTraceTerminal::Halt));
             }
             if opcode == OpCode::Ldc as u8 {
                 let value = read_u32(code, &mut ip).ok_or(JitNyiReason::InvalidImmediate("ldc"))?;
-                steps.push(TraceStep::Ldc(value));
+                steps.push(TraceStep::Ldc(value), op_ip);
                 continue;
             }
             if opcode == OpCode::Add as u8 {
-                steps.push(TraceStep::Add);
+                steps.push(TraceStep::Add, op_ip);
                 continue;
             }
             if opcode == OpCode::Sub as u8 {
-                steps.push(TraceStep::Sub);
+                steps.push(TraceStep::Sub, op_ip);
                 continue;
             }
             if opcode == OpCode::Mul as u8 {
-                steps.push(TraceStep::Mul);
+                steps.push(TraceStep::Mul, op_ip);
                 continue;
             }
             if opcode == OpCode::Div as u8 {
-                steps.push(TraceStep::Div);
+                steps.push(TraceStep::Div, op_ip);
                 continue;
             }
             if opcode == OpCode::Mod as u8 {
-                steps.push(TraceStep::Mod);
+                steps.push(TraceStep::Mod, op_ip);
                 continue;
             }
             if opcode == OpCode::Shl as u8 {
-                steps.push(TraceStep::Shl);
+                steps.push(TraceStep::Shl, op_ip);
                 continue;
             }
             if opcode == OpCode::Shr as u8 {
-                steps.push(TraceStep::Shr);
+                steps.push(TraceStep::Shr, op_ip);
                 continue;
             }
             if opcode == OpCode::And as u8 {
-                steps.push(TraceStep::And);
+                steps.push(TraceStep::And, op_ip);
                 continue;
             }
             if opcode == OpCode::Or as u8 {
-                steps.push(TraceStep::Or);
+                steps.push(TraceStep::Or, op_ip);
                 continue;
             }
             if opcode == OpCode::Neg as u8 {
-                steps.push(TraceStep::Neg);
+                steps.push(TraceStep::Neg, op_ip);
                 continue;
             }
             if opcode == OpCode::Ceq as u8 {
-                steps.push(TraceStep::Ceq);
+                steps.push(TraceStep::Ceq, op_ip);
                 continue;
             }
             if opcode == OpCode::Clt as u8 {
-                steps.push(TraceStep::Clt);
+                steps.push(TraceStep::Clt, op_ip);
                 continue;
             }
             if opcode == OpCode::Cgt as u8 {
-                steps.push(TraceStep::Cgt);
+                steps.push(TraceStep::Cgt, op_ip);
                 continue;
             }
             if opcode == OpCode::Pop as u8 {
-                steps.push(TraceStep::Pop);
+                steps.push(TraceStep::Pop, op_ip);
                 continue;
             }
             if opcode == OpCode::Dup as u8 {
-                steps.push(TraceStep::Dup);
+                steps.push(TraceStep::Dup, op_ip);
                 continue;
             }
             if opcode == OpCode::Ldloc as u8 {
                 let index =
                     read_u8(code, &mut ip).ok_or(JitNyiReason::InvalidImmediate("ldloc"))?;
-                steps.push(TraceStep::Ldloc(index));
+                steps.push(TraceStep::Ldloc(index), op_ip);
                 continue;
             }
             if opcode == OpCode::Stloc as u8 {
                 let index =
                     read_u8(code, &mut ip).ok_or(JitNyiReason::InvalidImmediate("stloc"))?;
-                steps.push(TraceStep::Stloc(index));
+                steps.push(TraceStep::Stloc(index), op_ip);
                 continue;
             }
             if opcode == OpCode::Brfalse as u8 {
@@ -442,7 +446,7 @@ impl TraceJitEngine {
                 if target >= code.len() {
                     return Err(JitNyiReason::InvalidJumpTarget { target });
                 }
-                steps.push(TraceStep::GuardFalse { exit_ip: target });
+                steps.push(TraceStep::GuardFalse { exit_ip: target }, op_ip);
                 continue;
             }
             if opcode == OpCode::Br as u8 {
@@ -453,7 +457,7 @@ impl TraceJitEngine {
                     return Err(JitNyiReason::InvalidJumpTarget { target });
                 }
                 if target == root_ip {
-                    steps.push(TraceStep::JumpToRoot);
+                    steps.push(TraceStep::JumpToRoot, op_ip);
                     return Ok(self.finish_trace(
                         program,
                         root_ip,
@@ -462,7 +466,7 @@ impl TraceJitEngine {
                     ));
                 }
                 if target < ip {
-                    steps.push(TraceStep::JumpToIp { target_ip: target });
+                    steps.push(TraceStep::JumpToIp { target_ip: target }, op_ip);
                     return Ok(self.finish_trace(
                         program,
                         root_ip,
@@ -480,11 +484,14 @@ impl TraceJitEngine {
                     read_u16(code, &mut ip).ok_or(JitNyiReason::InvalidImmediate("call index"))?;
                 let argc =
                     read_u8(code, &mut ip).ok_or(JitNyiReason::InvalidImmediate("call argc"))?;
-                steps.push(TraceStep::Call {
-                    index,
-                    argc,
-                    call_ip,
-                });
+                steps.push(
+                    TraceStep::Call {
+                        index,
+                        argc,
+                        call_ip,
+                    },
+                    op_ip,
+                );
                 continue;
             }
 
@@ -500,9 +507,10 @@ impl TraceJitEngine {
         &mut self,
         program: &Program,
         root_ip: usize,
-        steps: Vec<TraceStep>,
+        steps: RecordedSteps,
         terminal: JitTraceTerminal,
     ) -> usize {
+        let RecordedSteps { steps, ips } = steps;
         let id = self.traces.len();
         let start_line = program
             .debug
@@ -525,6 +533,7 @@ impl TraceJitEngine {
             has_call,
             has_yielding_call,
             steps,
+            step_ips: ips,
             terminal,
             executions: 0,
         });
@@ -541,6 +550,19 @@ impl TraceJitEngine {
     }
 }
 
+#[derive(Default)]
+struct RecordedSteps {
+    steps: Vec<TraceStep>,
+    ips: Vec<usize>,
+}
+
+impl RecordedSteps {
+    fn push(&mut self, step: TraceStep, ip: usize) {
+        self.steps.push(step);
+        self.ips.push(ip);
+    }
+}
+
 fn read_u8(code: &[u8], ip: &mut usize) -> Option<u8> {
     let value = *code.get(*ip)?;
     *ip = ip.saturating_add(1);
@@ -595,10 +617,8 @@ fn scan_loop_headers(program: &Program) -> HashSet<usize> {
         let instr_ip = ip;
         ip = ip.saturating_add(1);
         match opcode {
-            x if x == OpCode::Ldc as u8 => {
-                if read_u32(code, &mut ip).is_none() {
-                    break;
-                }
+            x if x == OpCode::Ldc as u8 && read_u32(code, &mut ip).is_none() => {
+                break;
             }
             x if x == OpCode::Br as u8 || x == OpCode::Brfalse as u8 => {
                 let Some(target_u32) = read_u32(code, &mut ip) else {
@@ -609,10 +629,10 @@ fn scan_loop_headers(program: &Program) -> HashSet<usize> {
                     headers.insert(target);
                 }
             }
-            x if x == OpCode::Ldloc as u8 || x == OpCode::Stloc as u8 => {
-                if read_u8(code, &mut ip).is_none() {
-                    break;
-                }
+            x if (x == OpCode::Ldloc as u8 || x == OpCode::Stloc as u8)
+                && read_u8(code, &mut ip).is_none() =>
+            {
+                break;
             }
             x if x == OpCode::Call as u8 => {
                 if read_u16(code, &mut ip).is_none() {
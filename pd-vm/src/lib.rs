@@ -1,4 +1,6 @@
 mod builtins;
+mod format;
+mod lua_pattern;
 
 pub mod assembler;
 pub mod bytecode;
@@ -6,6 +8,7 @@ pub mod compiler;
 pub mod debug_info;
 #[cfg(feature = "runtime")]
 pub mod debugger;
+pub mod decompiler;
 #[cfg(feature = "runtime")]
 pub mod jit;
 #[cfg(feature = "runtime")]
@@ -17,19 +20,27 @@ pub use assembler::{AsmParseError, Assembler, AssemblerError, BytecodeBuilder, a
 pub use bytecode::{HostImport, OpCode, Program, Value};
 pub use compiler::diagnostics::render_source_error;
 pub use compiler::source_map::{LineSpanMapping, LoweredSource, SourceId, SourceMap, Span};
+pub use compiler::transpile::{
+    TranspileError, transpile_bindings, transpile_ir, transpile_source, transpile_source_file,
+    transpile_source_file_with_search_paths,
+};
 pub use compiler::{
-    CompileError, CompiledProgram, Compiler, Expr, FunctionDecl, ParseError, SourceError,
-    SourceFlavor, SourcePathError, Stmt, compile_source, compile_source_file,
-    compile_source_with_flavor,
+    CompileError, CompileWarning, CompiledProgram, Compiler, Expr, FunctionDecl, ParseError,
+    SourceError, SourceFlavor, SourcePathError, Stmt, compile_source, compile_source_file,
+    compile_source_file_with_search_paths, compile_source_with_flavor,
+};
+pub use debug_info::{
+    ArgInfo, DebugFunction, DebugInfo, DebugSource, InlineFrame, LineInfo, LocalInfo, LogicalFrame,
+    MAIN_SOURCE_ID,
 };
-pub use debug_info::{ArgInfo, DebugFunction, DebugInfo, LineInfo, LocalInfo};
 #[cfg(feature = "runtime")]
 pub use debugger::{
-    DebugCommandBridge, DebugCommandBridgeError, DebugCommandBridgeResponse,
-    DebugCommandBridgeStatus, Debugger, StepMode, VmRecording, VmRecordingError, VmRecordingFrame,
-    VmRecordingReplayResponse, VmRecordingReplayState, replay_recording_stdio,
-    run_recording_replay_command,
+    BreakpointOptions, DapHandle, DapSession, DapStart, DapStartKind, DebugCommandBridge,
+    DebugCommandBridgeError, DebugCommandBridgeResponse, DebugCommandBridgeStatus, Debugger,
+    StepMode, VmRecording, VmRecordingError, VmRecordingFrame, VmRecordingReplayResponse,
+    VmRecordingReplayState, replay_recording_stdio, run_recording_replay_command,
 };
+pub use decompiler::{DecompileError, decompile_program};
 #[cfg(feature = "runtime")]
 pub use jit::{
     JitAttempt, JitConfig, JitNyiDoc, JitNyiReason, JitSnapshot, JitTrace, JitTraceTerminal,
@@ -0,0 +1,570 @@
+//! Lua 5.4 pattern matching shared by the `lua_find`, `lua_match`,
+//! `lua_gmatch`, and `lua_gsub` builtins.
+//!
+//! This is a port of the matcher in Lua's `lstrlib.c`. It supports character
+//! classes (`%a`, `%d`, `[%w_]`, `[^...]`, ranges), the `*`, `+`, `-`, and `?`
+//! quantifiers, `^` / `$` anchors, captures (including position captures `()`
+//! and back-references `%1`-`%9`), balanced matches `%b()`, and frontier
+//! patterns `%f[set]`.
+//!
+//! The matcher walks characters rather than bytes so positions agree with the
+//! VM's string length and slicing, which are character based. Classes follow
+//! the C locale: non-ASCII characters only ever match themselves, `.`, or a
+//! negated class.
+
+const MAX_CAPTURES: usize = 32;
+const MAX_MATCH_DEPTH: usize = 200;
+const SPECIALS: &[char] = &['^', '$', '*', '+', '?', '.', '(', ')', '[', ']', '%', '-'];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Capture {
+    Text(String),
+    /// One-based character position produced by an empty `()` capture.
+    Position(usize),
+}
+
+/// Result of `string.find`: `start..end` is the zero-based, end-exclusive
+/// character range of the match; `captures` only holds explicit captures.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PatternFind {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) captures: Vec<Capture>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CaptureLen {
+    Unfinished,
+    Position,
+    Closed(usize),
+}
+
+struct MatchState<'a> {
+    src: &'a [char],
+    pat: &'a [char],
+    level: usize,
+    captures: [(usize, CaptureLen); MAX_CAPTURES],
+    depth: usize,
+}
+
+impl<'a> MatchState<'a> {
+    fn new(src: &'a [char], pat: &'a [char]) -> Self {
+        Self {
+            src,
+            pat,
+            level: 0,
+            captures: [(0, CaptureLen::Unfinished); MAX_CAPTURES],
+            depth: MAX_MATCH_DEPTH,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.level = 0;
+        self.depth = MAX_MATCH_DEPTH;
+    }
+
+    fn class_end(&self, mut p: usize) -> Result<usize, String> {
+        let c = self.pat[p];
+        p += 1;
+        if c == '%' {
+            if p >= self.pat.len() {
+                return Err("malformed pattern (ends with '%')".to_string());
+            }
+            return Ok(p + 1);
+        }
+        if c == '[' {
+            if self.pat.get(p) == Some(&'^') {
+                p += 1;
+            }
+            // The first character of a set is never its terminator, so `[]]` is valid.
+            loop {
+                if p >= self.pat.len() {
+                    return Err("malformed pattern (missing ']')".to_string());
+                }
+                let c = self.pat[p];
+                p += 1;
+                if c == '%' && p < self.pat.len() {
+                    p += 1;
+                }
+                if self.pat.get(p) == Some(&']') {
+                    return Ok(p + 1);
+                }
+            }
+        }
+        Ok(p)
+    }
+
+    /// `p` points at `[`, `ec` at the closing `]`.
+    fn match_bracket_class(&self, c: char, mut p: usize, ec: usize) -> bool {
+        let mut sig = true;
+        if self.pat[p + 1] == '^' {
+            sig = false;
+            p += 1;
+        }
+        p += 1;
+        while p < ec {
+            if self.pat[p] == '%' {
+                p += 1;
+                if match_class(c, self.pat[p]) {
+                    return sig;
+                }
+            } else if self.pat[p + 1] == '-' && p + 2 < ec {
+                if self.pat[p] <= c && c <= self.pat[p + 2] {
+                    return sig;
+                }
+                p += 2;
+            } else if self.pat[p] == c {
+                return sig;
+            }
+            p += 1;
+        }
+        !sig
+    }
+
+    fn single_match(&self, s: usize, p: usize, ep: usize) -> bool {
+        let Some(&c) = self.src.get(s) else {
+            return false;
+        };
+        match self.pat[p] {
+            '.' => true,
+            '%' => match_class(c, self.pat[p + 1]),
+            '[' => self.match_bracket_class(c, p, ep - 1),
+            literal => literal == c,
+        }
+    }
+
+    fn do_match(&mut self, mut s: usize, mut p: usize) -> Result<Option<usize>, String> {
+        if self.depth == 0 {
+            return Err("pattern too complex".to_string());
+        }
+        self.depth -= 1;
+        let result = loop {
+            if p == self.pat.len() {
+                break Some(s);
+            }
+            match (self.pat[p], self.pat.get(p + 1).copied()) {
+                ('(', Some(')')) => break self.start_capture(s, p + 2, CaptureLen::Position)?,
+                ('(', _) => break self.start_capture(s, p + 1, CaptureLen::Unfinished)?,
+                (')', _) => break self.end_capture(s, p + 1)?,
+                ('$', None) => break (s == self.src.len()).then_some(s),
+                ('%', Some('b')) => match self.match_balance(s, p + 2)? {
+                    Some(next) => {
+                        s = next;
+                        p += 4;
+                        continue;
+                    }
+                    None => break None,
+                },
+                ('%', Some('f')) => {
+                    p += 2;
+                    if self.pat.get(p) != Some(&'[') {
+                        return Err("missing '[' after '%f' in pattern".to_string());
+                    }
+                    let ep = self.class_end(p)?;
+                    let previous = if s == 0 { '\0' } else { self.src[s - 1] };
+                    let current = self.src.get(s).copied().unwrap_or('\0');
+                    if !self.match_bracket_class(previous, p, ep - 1)
+                        && self.match_bracket_class(current, p, ep - 1)
+                    {
+                        p = ep;
+                        continue;
+                    }
+                    break None;
+                }
+                ('%', Some(digit)) if digit.is_ascii_digit() => {
+                    match self.match_back_reference(s, digit)? {
+                        Some(next) => {
+                            s = next;
+                            p += 2;
+                            continue;
+                        }
+                        None => break None,
+                    }
+                }
+                _ => {}
+            }
+
+            let ep = self.class_end(p)?;
+            let suffix = self.pat.get(ep).copied();
+            if !self.single_match(s, p, ep) {
+                if matches!(suffix, Some('*' | '?' | '-')) {
+                    p = ep + 1;
+                    continue;
+                }
+                break None;
+            }
+            match suffix {
+                Some('?') => {
+                    if let Some(end) = self.do_match(s + 1, ep + 1)? {
+                        break Some(end);
+                    }
+                    p = ep + 1;
+                }
+                Some('+') => break self.max_expand(s + 1, p, ep)?,
+                Some('*') => break self.max_expand(s, p, ep)?,
+                Some('-') => break self.min_expand(s, p, ep)?,
+                _ => {
+                    s += 1;
+                    p = ep;
+                }
+            }
+        };
+        self.depth += 1;
+        Ok(result)
+    }
+
+    fn max_expand(&mut self, s: usize, p: usize, ep: usize) -> Result<Option<usize>, String> {
+        let mut count = 0usize;
+        while self.single_match(s + count, p, ep) {
+            count += 1;
+        }
+        loop {
+            if let Some(end) = self.do_match(s + count, ep + 1)? {
+                return Ok(Some(end));
+            }
+            if count == 0 {
+                return Ok(None);
+            }
+            count -= 1;
+        }
+    }
+
+    fn min_expand(&mut self, mut s: usize, p: usize, ep: usize) -> Result<Option<usize>, String> {
+        loop {
+            if let Some(end) = self.do_match(s, ep + 1)? {
+                return Ok(Some(end));
+            }
+            if !self.single_match(s, p, ep) {
+                return Ok(None);
+            }
+            s += 1;
+        }
+    }
+
+    fn start_capture(
+        &mut self,
+        s: usize,
+        p: usize,
+        len: CaptureLen,
+    ) -> Result<Option<usize>, String> {
+        if self.level >= MAX_CAPTURES {
+            return Err("too many captures".to_string());
+        }
+        self.captures[self.level] = (s, len);
+        self.level += 1;
+        let result = self.do_match(s, p)?;
+        if result.is_none() {
+            self.level -= 1;
+        }
+        Ok(result)
+    }
+
+    fn end_capture(&mut self, s: usize, p: usize) -> Result<Option<usize>, String> {
+        let index = (0..self.level)
+            .rev()
+            .find(|index| self.captures[*index].1 == CaptureLen::Unfinished)
+            .ok_or_else(|| "invalid pattern capture".to_string())?;
+        self.captures[index].1 = CaptureLen::Closed(s - self.captures[index].0);
+        let result = self.do_match(s, p)?;
+        if result.is_none() {
+            self.captures[index].1 = CaptureLen::Unfinished;
+        }
+        Ok(result)
+    }
+
+    fn match_balance(&self, s: usize, p: usize) -> Result<Option<usize>, String> {
+        if p + 1 >= self.pat.len() {
+            return Err("malformed pattern (missing arguments to '%b')".to_string());
+        }
+        let (open, close) = (self.pat[p], self.pat[p + 1]);
+        if self.src.get(s) != Some(&open) {
+            return Ok(None);
+        }
+        let mut depth = 1usize;
+        for (offset, &c) in self.src[s + 1..].iter().enumerate() {
+            if c == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(Some(s + offset + 2));
+                }
+            } else if c == open {
+                depth += 1;
+            }
+        }
+        Ok(None)
+    }
+
+    fn match_back_reference(&self, s: usize, digit: char) -> Result<Option<usize>, String> {
+        let index = self.check_capture(digit)?;
+        let (start, len) = self.captures[index];
+        let CaptureLen::Closed(len) = len else {
+            // Position captures never match as back-references, as in Lua.
+            return Ok(None);
+        };
+        let matches =
+            self.src.len() - s >= len && self.src[start..start + len] == self.src[s..s + len];
+        Ok(matches.then_some(s + len))
+    }
+
+    fn check_capture(&self, digit: char) -> Result<usize, String> {
+        let index = (digit as usize).wrapping_sub('1' as usize);
+        if index >= self.level || self.captures[index].1 == CaptureLen::Unfinished {
+            return Err(format!("invalid capture index %{}", index.wrapping_add(1)));
+        }
+        Ok(index)
+    }
+
+    fn capture(&self, index: usize, s: usize, e: usize) -> Result<Capture, String> {
+        if index >= self.level {
+            if index == 0 {
+                return Ok(Capture::Text(self.src[s..e].iter().collect()));
+            }
+            return Err(format!("invalid capture index %{}", index + 1));
+        }
+        let (start, len) = self.captures[index];
+        match len {
+            CaptureLen::Unfinished => Err("unfinished capture".to_string()),
+            CaptureLen::Position => Ok(Capture::Position(start + 1)),
+            CaptureLen::Closed(len) => {
+                Ok(Capture::Text(self.src[start..start + len].iter().collect()))
+            }
+        }
+    }
+
+    /// Captures of the last match, falling back to the whole match when the
+    /// pattern has none.
+    fn captures_or_whole(&self, s: usize, e: usize) -> Result<Vec<Capture>, String> {
+        let count = if self.level == 0 { 1 } else { self.level };
+        (0..count).map(|index| self.capture(index, s, e)).collect()
+    }
+
+    fn explicit_captures(&self) -> Result<Vec<Capture>, String> {
+        (0..self.level)
+            .map(|index| self.capture(index, 0, 0))
+            .collect()
+    }
+}
+
+fn match_class(c: char, class: char) -> bool {
+    let matched = match class.to_ascii_lowercase() {
+        'a' => c.is_ascii_alphabetic(),
+        'c' => c.is_ascii_control(),
+        'd' => c.is_ascii_digit(),
+        'g' => c.is_ascii_graphic(),
+        'l' => c.is_ascii_lowercase(),
+        'p' => c.is_ascii_punctuation(),
+        's' => matches!(c, ' ' | '\t' | '\n' | '\u{0B}' | '\u{0C}' | '\r'),
+        'u' => c.is_ascii_uppercase(),
+        'w' => c.is_ascii_alphanumeric(),
+        'x' => c.is_ascii_hexdigit(),
+        'z' => c == '\0',
+        _ => return class == c,
+    };
+    if class.is_ascii_uppercase() {
+        !matched
+    } else {
+        matched
+    }
+}
+
+/// Converts a one-based, possibly negative Lua start position into a
+/// zero-based offset, or `None` when it lies past the end of the subject.
+fn start_offset(init: i64, len: usize) -> Option<usize> {
+    let len_i64 = len as i64;
+    let position = if init > 0 {
+        init
+    } else if init == 0 || init < -len_i64 {
+        1
+    } else {
+        len_i64 + init + 1
+    };
+    if position > len_i64 + 1 {
+        return None;
+    }
+    Some((position - 1) as usize)
+}
+
+fn split_anchor(pat: &[char]) -> (bool, &[char]) {
+    match pat.split_first() {
+        Some(('^', rest)) => (true, rest),
+        _ => (false, pat),
+    }
+}
+
+fn find_aux(
+    subject: &str,
+    pattern: &str,
+    init: i64,
+    plain: bool,
+) -> Result<Option<(PatternFind, Vec<Capture>)>, String> {
+    let src = subject.chars().collect::<Vec<_>>();
+    let pat = pattern.chars().collect::<Vec<_>>();
+    let Some(start) = start_offset(init, src.len()) else {
+        return Ok(None);
+    };
+
+    if plain || !pat.iter().any(|c| SPECIALS.contains(c)) {
+        let found = if pat.is_empty() {
+            Some(start)
+        } else {
+            src[start..]
+                .windows(pat.len())
+                .position(|window| window == pat.as_slice())
+                .map(|offset| start + offset)
+        };
+        return Ok(found.map(|found| {
+            let whole = Capture::Text(pattern.to_string());
+            let range = PatternFind {
+                start: found,
+                end: found + pat.len(),
+                captures: Vec::new(),
+            };
+            (range, vec![whole])
+        }));
+    }
+
+    let (anchor, pat) = split_anchor(&pat);
+    let mut state = MatchState::new(&src, pat);
+    let mut s = start;
+    loop {
+        state.reset();
+        if let Some(e) = state.do_match(s, 0)? {
+            let found = PatternFind {
+                start: s,
+                end: e,
+                captures: state.explicit_captures()?,
+            };
+            return Ok(Some((found, state.captures_or_whole(s, e)?)));
+        }
+        s += 1;
+        if anchor || s > src.len() {
+            return Ok(None);
+        }
+    }
+}
+
+/// `string.find(subject, pattern, init, plain)`.
+pub(crate) fn find(
+    subject: &str,
+    pattern: &str,
+    init: i64,
+    plain: bool,
+) -> Result<Option<PatternFind>, String> {
+    Ok(find_aux(subject, pattern, init, plain)?.map(|(found, _)| found))
+}
+
+/// `string.match(subject, pattern, init)`: the captures of the first match,
+/// or the whole match when the pattern has no captures.
+pub(crate) fn match_captures(
+    subject: &str,
+    pattern: &str,
+    init: i64,
+) -> Result<Option<Vec<Capture>>, String> {
+    Ok(find_aux(subject, pattern, init, false)?.map(|(_, captures)| captures))
+}
+
+/// `string.gmatch(subject, pattern, init)`, collected eagerly: one capture
+/// list per match.
+pub(crate) fn gmatch(subject: &str, pattern: &str, init: i64) -> Result<Vec<Vec<Capture>>, String> {
+    let src = subject.chars().collect::<Vec<_>>();
+    let pat = pattern.chars().collect::<Vec<_>>();
+    let mut s = start_offset(init, src.len()).unwrap_or(src.len());
+    let mut state = MatchState::new(&src, &pat);
+    let mut last_match = None;
+    let mut out = Vec::new();
+    while s <= src.len() {
+        state.reset();
+        match state.do_match(s, 0)? {
+            Some(e) if Some(e) != last_match => {
+                out.push(state.captures_or_whole(s, e)?);
+                last_match = Some(e);
+                s = e;
+            }
+            _ => s += 1,
+        }
+    }
+    Ok(out)
+}
+
+/// `string.gsub(subject, pattern, repl, max)`. `replace` receives the whole
+/// match and its captures and returns the replacement text, or `None` to keep
+/// the original match. Returns the new string and the number of matches.
+pub(crate) fn gsub(
+    subject: &str,
+    pattern: &str,
+    max: Option<usize>,
+    mut replace: impl FnMut(&str, &[Capture]) -> Result<Option<String>, String>,
+) -> Result<(String, usize), String> {
+    let src = subject.chars().collect::<Vec<_>>();
+    let pat = pattern.chars().collect::<Vec<_>>();
+    let (anchor, pat) = split_anchor(&pat);
+    let max = max.unwrap_or(src.len() + 1);
+    let mut state = MatchState::new(&src, pat);
+    let mut out = String::with_capacity(subject.len());
+    let mut s = 0usize;
+    let mut last_match = None;
+    let mut count = 0usize;
+    while count < max {
+        state.reset();
+        match state.do_match(s, 0)? {
+            Some(e) if Some(e) != last_match => {
+                count += 1;
+                let whole = src[s..e].iter().collect::<String>();
+                let captures = state.captures_or_whole(s, e)?;
+                match replace(&whole, &captures)? {
+                    Some(replacement) => out.push_str(&replacement),
+                    None => out.push_str(&whole),
+                }
+                s = e;
+                last_match = Some(e);
+            }
+            _ if s < src.len() => {
+                out.push(src[s]);
+                s += 1;
+            }
+            _ => break,
+        }
+        if anchor {
+            break;
+        }
+    }
+    out.extend(&src[s..]);
+    Ok((out, count))
+}
+
+/// Expands a `gsub` replacement string: `%0` is the whole match, `%1`-`%9`
+/// are captures (with `%1` standing for the whole match when there are none),
+/// and `%%` is a literal `%`.
+pub(crate) fn expand_replacement(
+    template: &str,
+    whole: &str,
+    captures: &[Capture],
+) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => out.push('%'),
+            Some('0') => out.push_str(whole),
+            Some(digit @ '1'..='9') => {
+                let index = digit as usize - '1' as usize;
+                match captures.get(index) {
+                    Some(Capture::Text(text)) => out.push_str(text),
+                    Some(Capture::Position(position)) => out.push_str(&position.to_string()),
+                    None => {
+                        return Err(format!(
+                            "invalid capture index %{} in replacement string",
+                            index + 1
+                        ));
+                    }
+                }
+            }
+            _ => return Err("invalid use of '%' in replacement string".to_string()),
+        }
+    }
+    Ok(out)
+}
@@ -6,6 +6,8 @@ use std::process::{Child, Command, Stdio};
 use regex::Regex;
 
 use crate::builtins::BuiltinFunction;
+use crate::format::{FormatAlign, FormatArg, FormatPiece, FormatSpec, parse_format_template};
+use crate::lua_pattern::{self, Capture};
 
 use super::{Value, Vm, VmError, VmResult};
 
@@ -23,6 +25,60 @@ impl Default for IoState {
     }
 }
 
+/// Seedable splitmix64 generator backing the `rand::*` and `uuid::*` builtins.
+pub(super) struct RngState {
+    pub(super) seed: u64,
+    state: u64,
+}
+
+impl RngState {
+    pub(super) fn with_seed(seed: u64) -> Self {
+        Self { seed, state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `0..bound` using rejection sampling; `bound == 0` means the full u64 range.
+    fn next_below(&mut self, bound: u64) -> u64 {
+        if bound == 0 {
+            return self.next_u64();
+        }
+        let zone = u64::MAX - (u64::MAX % bound);
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return value % bound;
+            }
+        }
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+}
+
+impl Default for RngState {
+    fn default() -> Self {
+        Self::with_seed(entropy_seed())
+    }
+}
+
+fn entropy_seed() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    if let Ok(elapsed) = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+        hasher.write_u128(elapsed.as_nanos());
+    }
+    hasher.finish()
+}
+
 pub(super) enum IoHandle {
     File(std::fs::File),
     PopenRead { child: Child },
@@ -61,6 +117,43 @@ pub(super) fn execute_builtin_call(
         BuiltinFunction::ToString => builtin_to_string(&args),
         BuiltinFunction::TypeOf => builtin_type_of(&args),
         BuiltinFunction::Assert => builtin_assert(&args),
+        BuiltinFunction::RandInt => builtin_rand_int(vm, &args),
+        BuiltinFunction::RandFloat => Ok(vec![Value::Float(vm.rng_state.next_f64())]),
+        BuiltinFunction::RandChoice => builtin_rand_choice(vm, args),
+        BuiltinFunction::RandShuffle => builtin_rand_shuffle(vm, args),
+        BuiltinFunction::UuidV4 => Ok(vec![Value::String(builtin_uuid_v4(vm))]),
+        BuiltinFunction::ArraySort => builtin_array_sort(args),
+        BuiltinFunction::ArraySortByKeys => builtin_array_sort_by_keys(args),
+        BuiltinFunction::ArrayBinarySearch => builtin_array_binary_search(&args),
+        BuiltinFunction::ArrayIndexOf => builtin_array_index_of(&args),
+        BuiltinFunction::ArrayContains => {
+            let index = array_index_of(&args, "array_contains")?;
+            Ok(vec![Value::Bool(index.is_some())])
+        }
+        BuiltinFunction::ArrayDedup => builtin_array_dedup(args),
+        BuiltinFunction::ArrayRemove => builtin_array_remove(args),
+        BuiltinFunction::ArrayInsert => builtin_array_insert(args),
+        BuiltinFunction::ArrayPop => builtin_array_pop(args),
+        BuiltinFunction::MapRemove => builtin_map_remove(args),
+        BuiltinFunction::MapHasKey => builtin_map_has_key(&args),
+        BuiltinFunction::MapValues => builtin_map_values(args),
+        BuiltinFunction::MapEntries => builtin_map_entries(args),
+        BuiltinFunction::Format => builtin_format(&args),
+        BuiltinFunction::LuaFind => builtin_lua_find(&args),
+        BuiltinFunction::LuaMatch => builtin_lua_match(&args),
+        BuiltinFunction::LuaGmatch => builtin_lua_gmatch(&args),
+        BuiltinFunction::LuaGsub => builtin_lua_gsub(&args),
+        BuiltinFunction::LuaSetMetatable => builtin_lua_setmetatable(args),
+        BuiltinFunction::LuaGetMetatable => builtin_lua_getmetatable(&args),
+        BuiltinFunction::LuaRawGet => builtin_lua_rawget(&args),
+        BuiltinFunction::LuaRawSet => builtin_lua_rawset(args),
+        BuiltinFunction::LuaRawEqual => builtin_lua_rawequal(&args),
+        BuiltinFunction::LuaMetamethod => builtin_lua_metamethod(&args),
+        BuiltinFunction::LuaIndexOwner => builtin_lua_index_owner(args),
+        BuiltinFunction::LuaNewIndex => builtin_lua_newindex(args),
+        BuiltinFunction::LuaError => builtin_lua_error(&args),
+        BuiltinFunction::LuaIndex => builtin_lua_index(args),
+        BuiltinFunction::LuaPlain => Ok(vec![builtin_lua_plain(args)]),
     }
 }
 
@@ -586,6 +679,786 @@ fn builtin_assert(args: &[Value]) -> VmResult<Vec<Value>> {
     }
 }
 
+fn builtin_rand_int(vm: &mut Vm, args: &[Value]) -> VmResult<Vec<Value>> {
+    let low = args
+        .first()
+        .ok_or_else(|| VmError::HostError("missing argument: rand_int low".to_string()))?
+        .as_int()?;
+    let high = args
+        .get(1)
+        .ok_or_else(|| VmError::HostError("missing argument: rand_int high".to_string()))?
+        .as_int()?;
+    if low > high {
+        return Err(VmError::HostError(format!(
+            "rand_int expects low <= high, got {low} > {high}"
+        )));
+    }
+    let span = high.wrapping_sub(low) as u64;
+    let offset = vm.rng_state.next_below(span.wrapping_add(1));
+    Ok(vec![Value::Int(low.wrapping_add(offset as i64))])
+}
+
+fn builtin_rand_choice(vm: &mut Vm, args: Vec<Value>) -> VmResult<Vec<Value>> {
+    let mut values = match args.into_iter().next() {
+        Some(Value::Array(values)) => values,
+        Some(_) => return Err(VmError::TypeMismatch("array")),
+        None => {
+            return Err(VmError::HostError(
+                "missing argument: rand_choice array".to_string(),
+            ));
+        }
+    };
+    if values.is_empty() {
+        return Ok(vec![Value::Null]);
+    }
+    let index = vm.rng_state.next_below(values.len() as u64) as usize;
+    Ok(vec![values.swap_remove(index)])
+}
+
+fn builtin_rand_shuffle(vm: &mut Vm, args: Vec<Value>) -> VmResult<Vec<Value>> {
+    let mut values = match args.into_iter().next() {
+        Some(Value::Array(values)) => values,
+        Some(_) => return Err(VmError::TypeMismatch("array")),
+        None => {
+            return Err(VmError::HostError(
+                "missing argument: rand_shuffle array".to_string(),
+            ));
+        }
+    };
+    for index in (1..values.len()).rev() {
+        let swap_with = vm.rng_state.next_below(index as u64 + 1) as usize;
+        values.swap(index, swap_with);
+    }
+    Ok(vec![Value::Array(values)])
+}
+
+fn builtin_uuid_v4(vm: &mut Vm) -> String {
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&vm.rng_state.next_u64().to_le_bytes());
+    bytes[8..].copy_from_slice(&vm.rng_state.next_u64().to_le_bytes());
+    bytes[6] = (bytes[6] & 0x0F) | 0x40;
+    bytes[8] = (bytes[8] & 0x3F) | 0x80;
+
+    let mut out = String::with_capacity(36);
+    for (index, byte) in bytes.iter().enumerate() {
+        if matches!(index, 4 | 6 | 8 | 10) {
+            out.push('-');
+        }
+        out.push_str(&format!("{byte:02x}"));
+    }
+    out
+}
+
+fn builtin_array_sort(args: Vec<Value>) -> VmResult<Vec<Value>> {
+    let mut values = take_array_arg(args.into_iter().next(), "array_sort values")?;
+    values.sort_by(Value::total_cmp);
+    Ok(vec![Value::Array(values)])
+}
+
+fn builtin_array_sort_by_keys(args: Vec<Value>) -> VmResult<Vec<Value>> {
+    let mut iter = args.into_iter();
+    let values = take_array_arg(iter.next(), "array_sort_by_keys values")?;
+    let keys = take_array_arg(iter.next(), "array_sort_by_keys keys")?;
+    if keys.len() != values.len() {
+        return Err(VmError::HostError(format!(
+            "array_sort_by_keys expects one key per value, got {} keys for {} values",
+            keys.len(),
+            values.len()
+        )));
+    }
+    let mut order = (0..values.len()).collect::<Vec<_>>();
+    order.sort_by(|lhs, rhs| keys[*lhs].total_cmp(&keys[*rhs]));
+    let mut slots = values.into_iter().map(Some).collect::<Vec<_>>();
+    let sorted = order
+        .into_iter()
+        .map(|index| slots[index].take().unwrap_or(Value::Null))
+        .collect();
+    Ok(vec![Value::Array(sorted)])
+}
+
+fn builtin_array_binary_search(args: &[Value]) -> VmResult<Vec<Value>> {
+    let values = match args.first() {
+        Some(Value::Array(values)) => values,
+        Some(_) => return Err(VmError::TypeMismatch("array")),
+        None => {
+            return Err(VmError::HostError(
+                "missing argument: array_binary_search values".to_string(),
+            ));
+        }
+    };
+    let needle = args.get(1).ok_or_else(|| {
+        VmError::HostError("missing argument: array_binary_search needle".to_string())
+    })?;
+    let index = match values.binary_search_by(|probe| probe.total_cmp(needle)) {
+        Ok(found) => found as i64,
+        Err(insert_at) => -(insert_at as i64) - 1,
+    };
+    Ok(vec![Value::Int(index)])
+}
+
+fn builtin_array_index_of(args: &[Value]) -> VmResult<Vec<Value>> {
+    let index = array_index_of(args, "array_index_of")?;
+    Ok(vec![Value::Int(index.map_or(-1, |index| index as i64))])
+}
+
+fn array_index_of(args: &[Value], label: &str) -> VmResult<Option<usize>> {
+    let values = match args.first() {
+        Some(Value::Array(values)) => values,
+        Some(_) => return Err(VmError::TypeMismatch("array")),
+        None => {
+            return Err(VmError::HostError(format!(
+                "missing argument: {label} values"
+            )));
+        }
+    };
+    let needle = args
+        .get(1)
+        .ok_or_else(|| VmError::HostError(format!("missing argument: {label} needle")))?;
+    Ok(values.iter().position(|value| value == needle))
+}
+
+fn builtin_array_dedup(args: Vec<Value>) -> VmResult<Vec<Value>> {
+    let values = take_array_arg(args.into_iter().next(), "array_dedup values")?;
+    let mut order = (0..values.len()).collect::<Vec<_>>();
+    order.sort_by(|lhs, rhs| values[*lhs].total_cmp(&values[*rhs]));
+
+    // Equal values are adjacent after the stable sort, with the earliest occurrence first.
+    let mut keep = vec![false; values.len()];
+    let mut run_head: Option<usize> = None;
+    for index in order {
+        if run_head.is_some_and(|head| values[head] == values[index]) {
+            continue;
+        }
+        keep[index] = true;
+        run_head = Some(index);
+    }
+    let out = values
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(value, keep)| keep.then_some(value))
+        .collect();
+    Ok(vec![Value::Array(out)])
+}
+
+fn builtin_array_remove(args: Vec<Value>) -> VmResult<Vec<Value>> {
+    let mut iter = args.into_iter();
+    let mut values = take_array_arg(iter.next(), "array_remove values")?;
+    let index = array_position_arg(iter.next(), "array_remove")?;
+    if index >= values.len() {
+        return Err(VmError::HostError(format!(
+            "array_remove index {index} out of bounds"
+        )));
+    }
+    values.remove(index);
+    Ok(vec![Value::Array(values)])
+}
+
+fn builtin_array_insert(args: Vec<Value>) -> VmResult<Vec<Value>> {
+    let mut iter = args.into_iter();
+    let mut values = take_array_arg(iter.next(), "array_insert values")?;
+    let index = array_position_arg(iter.next(), "array_insert")?;
+    if index > values.len() {
+        return Err(VmError::HostError(format!(
+            "array_insert index {index} out of bounds"
+        )));
+    }
+    let value = iter
+        .next()
+        .ok_or_else(|| VmError::HostError("missing argument: array_insert value".to_string()))?;
+    values.insert(index, value);
+    Ok(vec![Value::Array(values)])
+}
+
+fn builtin_array_pop(args: Vec<Value>) -> VmResult<Vec<Value>> {
+    let mut values = take_array_arg(args.into_iter().next(), "array_pop values")?;
+    values.pop();
+    Ok(vec![Value::Array(values)])
+}
+
+fn builtin_map_remove(args: Vec<Value>) -> VmResult<Vec<Value>> {
+    let mut iter = args.into_iter();
+    let mut entries = take_map_arg(iter.next(), "map_remove map")?;
+    let key = iter
+        .next()
+        .ok_or_else(|| VmError::HostError("missing argument: map_remove key".to_string()))?;
+    entries.retain(|(existing_key, _)| *existing_key != key);
+    Ok(vec![Value::Map(entries)])
+}
+
+fn builtin_map_has_key(args: &[Value]) -> VmResult<Vec<Value>> {
+    let entries = match args.first() {
+        Some(Value::Map(entries)) => entries,
+        Some(_) => return Err(VmError::TypeMismatch("map")),
+        None => {
+            return Err(VmError::HostError(
+                "missing argument: map_has_key map".to_string(),
+            ));
+        }
+    };
+    let key = args
+        .get(1)
+        .ok_or_else(|| VmError::HostError("missing argument: map_has_key key".to_string()))?;
+    let found = entries.iter().any(|(existing_key, _)| existing_key == key);
+    Ok(vec![Value::Bool(found)])
+}
+
+fn builtin_map_values(args: Vec<Value>) -> VmResult<Vec<Value>> {
+    let entries = take_map_arg(args.into_iter().next(), "map_values map")?;
+    let values = entries.into_iter().map(|(_, value)| value).collect();
+    Ok(vec![Value::Array(values)])
+}
+
+fn builtin_map_entries(args: Vec<Value>) -> VmResult<Vec<Value>> {
+    let entries = take_map_arg(args.into_iter().next(), "map_entries map")?;
+    let rows = entries
+        .into_iter()
+        .map(|(key, value)| Value::Array(vec![key, value]))
+        .collect();
+    Ok(vec![Value::Array(rows)])
+}
+
+fn builtin_format(args: &[Value]) -> VmResult<Vec<Value>> {
+    let template = arg_string(args, 0, "format template")?;
+    let format_args = args
+        .get(1)
+        .ok_or_else(|| VmError::HostError("missing argument: format args".to_string()))?;
+    if !matches!(format_args, Value::Array(_) | Value::Map(_)) {
+        return Err(VmError::TypeMismatch("array/map"));
+    }
+    let pieces = parse_format_template(template)
+        .map_err(|message| VmError::HostError(format!("format: {message}")))?;
+
+    let mut out = String::with_capacity(template.len());
+    let mut next_positional = 0usize;
+    for piece in pieces {
+        let (arg, spec) = match piece {
+            FormatPiece::Literal(text) => {
+                out.push_str(&text);
+                continue;
+            }
+            FormatPiece::Placeholder { arg, spec } => (arg, spec),
+        };
+        let value = match &arg {
+            FormatArg::Next => {
+                next_positional += 1;
+                format_positional_arg(format_args, next_positional - 1)
+            }
+            FormatArg::Index(index) => format_positional_arg(format_args, *index),
+            FormatArg::Name(name) => match format_args {
+                Value::Map(entries) => entries
+                    .iter()
+                    .find(|(key, _)| matches!(key, Value::String(key) if key == name))
+                    .map(|(_, value)| value),
+                _ => None,
+            },
+        }
+        .ok_or_else(|| {
+            let label = match &arg {
+                FormatArg::Next => format!("#{}", next_positional - 1),
+                FormatArg::Index(index) => format!("#{index}"),
+                FormatArg::Name(name) => format!("'{name}'"),
+            };
+            VmError::HostError(format!("format: missing argument {label}"))
+        })?;
+        write_formatted_value(&mut out, value, &spec);
+    }
+    Ok(vec![Value::String(out)])
+}
+
+fn format_positional_arg(args: &Value, index: usize) -> Option<&Value> {
+    match args {
+        Value::Array(values) => values.get(index),
+        Value::Map(entries) => {
+            let index = i64::try_from(index).ok()?;
+            entries
+                .iter()
+                .find(|(key, _)| *key == Value::Int(index))
+                .map(|(_, value)| value)
+        }
+        _ => None,
+    }
+}
+
+fn write_formatted_value(out: &mut String, value: &Value, spec: &FormatSpec) {
+    let is_number = matches!(value, Value::Int(_) | Value::Float(_));
+    let body = match (value, spec.precision) {
+        (Value::Int(v), Some(precision)) => format!("{:.precision$}", *v as f64),
+        (Value::Float(v), Some(precision)) => format!("{v:.precision$}"),
+        (Value::String(v), Some(precision)) if !spec.debug => v.chars().take(precision).collect(),
+        _ => render_format_value(value, spec.debug),
+    };
+
+    let width = spec.width.unwrap_or(0);
+    let len = body.chars().count();
+    if len >= width {
+        out.push_str(&body);
+        return;
+    }
+    let padding = width - len;
+
+    if spec.zero_pad && is_number {
+        let (sign, digits) = match body.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", body.as_str()),
+        };
+        out.push_str(sign);
+        out.extend(std::iter::repeat_n('0', padding));
+        out.push_str(digits);
+        return;
+    }
+
+    let align = spec.align.unwrap_or(if is_number {
+        FormatAlign::Right
+    } else {
+        FormatAlign::Left
+    });
+    let (before, after) = match align {
+        FormatAlign::Left => (0, padding),
+        FormatAlign::Center => (padding / 2, padding - padding / 2),
+        FormatAlign::Right => (padding, 0),
+    };
+    out.extend(std::iter::repeat_n(spec.fill, before));
+    out.push_str(&body);
+    out.extend(std::iter::repeat_n(spec.fill, after));
+}
+
+fn render_format_value(value: &Value, debug: bool) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Int(v) => v.to_string(),
+        Value::Float(v) if debug => format!("{v:?}"),
+        Value::Float(v) => v.to_string(),
+        Value::Bool(v) => v.to_string(),
+        Value::String(v) if debug => format!("{v:?}"),
+        Value::String(v) => v.clone(),
+        Value::Array(values) => {
+            let parts = values
+                .iter()
+                .map(|value| render_format_value(value, debug))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("[{parts}]")
+        }
+        Value::Map(entries) => {
+            let parts = entries
+                .iter()
+                .map(|(key, value)| {
+                    format!(
+                        "{}: {}",
+                        render_format_value(key, debug),
+                        render_format_value(value, debug)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("{{{parts}}}")
+        }
+    }
+}
+
+fn builtin_lua_find(args: &[Value]) -> VmResult<Vec<Value>> {
+    let subject = arg_string(args, 0, "lua_find subject")?;
+    let pattern = arg_string(args, 1, "lua_find pattern")?;
+    let init = lua_init_arg(args, 2)?;
+    let plain = !matches!(args.get(3), None | Some(Value::Null | Value::Bool(false)));
+    let found = lua_pattern::find(subject, pattern, init, plain).map_err(lua_pattern_error)?;
+    let value = match found {
+        Some(found) => {
+            let mut row = vec![
+                Value::Int(found.start as i64 + 1),
+                Value::Int(found.end as i64),
+            ];
+            row.extend(found.captures.into_iter().map(lua_capture_value));
+            Value::Array(row)
+        }
+        None => Value::Null,
+    };
+    Ok(vec![value])
+}
+
+fn builtin_lua_match(args: &[Value]) -> VmResult<Vec<Value>> {
+    let subject = arg_string(args, 0, "lua_match subject")?;
+    let pattern = arg_string(args, 1, "lua_match pattern")?;
+    let init = lua_init_arg(args, 2)?;
+    let captures =
+        lua_pattern::match_captures(subject, pattern, init).map_err(lua_pattern_error)?;
+    let value = match captures {
+        Some(captures) => Value::Array(captures.into_iter().map(lua_capture_value).collect()),
+        None => Value::Null,
+    };
+    Ok(vec![value])
+}
+
+fn builtin_lua_gmatch(args: &[Value]) -> VmResult<Vec<Value>> {
+    let subject = arg_string(args, 0, "lua_gmatch subject")?;
+    let pattern = arg_string(args, 1, "lua_gmatch pattern")?;
+    let init = lua_init_arg(args, 2)?;
+    let rows = lua_pattern::gmatch(subject, pattern, init).map_err(lua_pattern_error)?;
+    let rows = rows
+        .into_iter()
+        .map(|captures| Value::Array(captures.into_iter().map(lua_capture_value).collect()))
+        .collect();
+    Ok(vec![Value::Array(rows)])
+}
+
+fn builtin_lua_gsub(args: &[Value]) -> VmResult<Vec<Value>> {
+    let subject = arg_string(args, 0, "lua_gsub subject")?;
+    let pattern = arg_string(args, 1, "lua_gsub pattern")?;
+    let replacement = args
+        .get(2)
+        .ok_or_else(|| VmError::HostError("missing argument: lua_gsub replacement".to_string()))?;
+    let max = match args.get(3) {
+        None | Some(Value::Null) => None,
+        Some(value) => Some(value.as_int()?.max(0) as usize),
+    };
+    let template = match replacement {
+        Value::String(text) => Some(text.clone()),
+        Value::Int(value) => Some(value.to_string()),
+        Value::Float(value) => Some(value.to_string()),
+        Value::Map(_) => None,
+        _ => return Err(VmError::TypeMismatch("string/number/map")),
+    };
+    let (text, count) = lua_pattern::gsub(subject, pattern, max, |whole, captures| {
+        if let Some(template) = &template {
+            return lua_pattern::expand_replacement(template, whole, captures).map(Some);
+        }
+        let Value::Map(entries) = replacement else {
+            unreachable!("non-template replacements are maps");
+        };
+        let key = lua_capture_value(captures[0].clone());
+        let value = entries
+            .iter()
+            .find(|(entry_key, _)| *entry_key == key)
+            .map(|(_, value)| value);
+        match value {
+            None | Some(Value::Null | Value::Bool(false)) => Ok(None),
+            Some(Value::String(text)) => Ok(Some(text.clone())),
+            Some(Value::Int(value)) => Ok(Some(value.to_string())),
+            Some(Value::Float(value)) => Ok(Some(value.to_string())),
+            Some(Value::Bool(true)) => Err("invalid replacement value (a boolean)".to_string()),
+            Some(_) => Err("invalid replacement value (a table)".to_string()),
+        }
+    })
+    .map_err(lua_pattern_error)?;
+    Ok(vec![Value::Array(vec![
+        Value::String(text),
+        Value::Int(count as i64),
+    ])])
+}
+
+fn builtin_lua_setmetatable(args: Vec<Value>) -> VmResult<Vec<Value>> {
+    let mut iter = args.into_iter();
+    let Some(Value::Map(mut entries)) = iter.next() else {
+        return Err(lua_metatable_error("setmetatable expects a table"));
+    };
+    let metatable = match iter.next() {
+        None | Some(Value::Null) => None,
+        Some(Value::Map(metatable)) => Some(metatable),
+        Some(_) => return Err(lua_metatable_error("metatable must be a table or nil")),
+    };
+    entries.retain(|(key, _)| !is_metatable_slot(key));
+    if let Some(metatable) = metatable {
+        entries.push((Value::Null, Value::Map(metatable)));
+    }
+    Ok(vec![Value::Map(entries)])
+}
+
+fn builtin_lua_getmetatable(args: &[Value]) -> VmResult<Vec<Value>> {
+    let metatable = match args.first() {
+        Some(Value::Map(entries)) => metatable_of(entries).map(|metatable| metatable.to_vec()),
+        _ => None,
+    };
+    Ok(vec![metatable.map_or(Value::Null, Value::Map)])
+}
+
+fn builtin_lua_rawget(args: &[Value]) -> VmResult<Vec<Value>> {
+    let key = args
+        .get(1)
+        .ok_or_else(|| VmError::HostError("missing argument: lua_rawget key".to_string()))?;
+    let value = match args.first() {
+        Some(Value::Map(entries)) if !is_metatable_slot(key) => raw_map_get(entries, key).cloned(),
+        Some(Value::Map(_)) => None,
+        Some(Value::Array(values)) => usize::try_from(key.as_int()?)
+            .ok()
+            .and_then(|index| values.get(index).cloned()),
+        _ => return Err(VmError::TypeMismatch("array/map")),
+    };
+    Ok(vec![value.unwrap_or(Value::Null)])
+}
+
+fn builtin_lua_rawset(args: Vec<Value>) -> VmResult<Vec<Value>> {
+    if matches!(args.get(1), Some(Value::Null)) {
+        return Err(lua_metatable_error("table index is nil"));
+    }
+    builtin_set(args)
+}
+
+fn builtin_lua_rawequal(args: &[Value]) -> VmResult<Vec<Value>> {
+    Ok(vec![Value::Bool(args.first() == args.get(1))])
+}
+
+/// `get` for Lua tables: a key the map lacks is looked up along its
+/// `__index` chain before the read fails.
+fn builtin_lua_index(args: Vec<Value>) -> VmResult<Vec<Value>> {
+    let (Some(Value::Map(entries)), Some(key)) = (args.first(), args.get(1)) else {
+        return builtin_get(args);
+    };
+    if raw_map_get(entries, key).is_some() {
+        return builtin_get(args);
+    }
+    metatable_index(entries, key)
+        .map(|value| vec![value])
+        .ok_or_else(|| VmError::HostError("map key not found".to_string()))
+}
+
+/// The value without its reserved slot, for lengths, key and value listings,
+/// iteration and printing. `deep` also strips every nested table.
+fn builtin_lua_plain(args: Vec<Value>) -> Value {
+    let mut iter = args.into_iter();
+    let value = iter.next().unwrap_or(Value::Null);
+    let deep = matches!(iter.next(), Some(Value::Bool(true)));
+    strip_metatable_slot(value, deep)
+}
+
+fn strip_metatable_slot(value: Value, deep: bool) -> Value {
+    match value {
+        Value::Map(entries) => Value::Map(
+            entries
+                .into_iter()
+                .filter(|(key, _)| !is_metatable_slot(key))
+                .map(|(key, value)| {
+                    if deep {
+                        (key, strip_metatable_slot(value, true))
+                    } else {
+                        (key, value)
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(values) if deep => Value::Array(
+            values
+                .into_iter()
+                .map(|value| strip_metatable_slot(value, true))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// Returns the metatable of the first operand that defines `name`, the way
+/// Lua picks the handler for binary metamethods, or null when neither does.
+fn builtin_lua_metamethod(args: &[Value]) -> VmResult<Vec<Value>> {
+    let name = Value::String(arg_string(args, 2, "lua_metamethod name")?.to_string());
+    let metatable = args[..2].iter().find_map(|operand| {
+        let Value::Map(entries) = operand else {
+            return None;
+        };
+        let metatable = metatable_of(entries)?;
+        raw_map_get(metatable, &name).map(|_| metatable.to_vec())
+    });
+    Ok(vec![metatable.map_or(Value::Null, Value::Map)])
+}
+
+/// Returns the table along the `__index` chain that holds `key` (the value
+/// itself for non-tables), or null when the lookup would miss.
+fn builtin_lua_index_owner(args: Vec<Value>) -> VmResult<Vec<Value>> {
+    let mut iter = args.into_iter();
+    let container = iter
+        .next()
+        .ok_or_else(|| VmError::HostError("missing argument: lua_index_owner table".to_string()))?;
+    let key = iter
+        .next()
+        .ok_or_else(|| VmError::HostError("missing argument: lua_index_owner key".to_string()))?;
+    let Value::Map(entries) = &container else {
+        return Ok(vec![container]);
+    };
+    if raw_map_get(entries, &key).is_some() {
+        return Ok(vec![container]);
+    }
+    let owner = metatable_index_owner(entries, &key).map(|owner| Value::Map(owner.to_vec()));
+    Ok(vec![owner.unwrap_or(Value::Null)])
+}
+
+/// Assigns `key` with `__newindex` table redirection: a key missing from the
+/// table is written into the `__newindex` table of its metatable instead.
+/// Function handlers are dispatched by the Lua frontend before this runs.
+fn builtin_lua_newindex(args: Vec<Value>) -> VmResult<Vec<Value>> {
+    let mut iter = args.into_iter();
+    let container = iter
+        .next()
+        .ok_or_else(|| VmError::HostError("missing argument: lua_newindex table".to_string()))?;
+    let key = iter
+        .next()
+        .ok_or_else(|| VmError::HostError("missing argument: lua_newindex key".to_string()))?;
+    let value = iter
+        .next()
+        .ok_or_else(|| VmError::HostError("missing argument: lua_newindex value".to_string()))?;
+    if matches!(key, Value::Null) {
+        return Err(lua_metatable_error("table index is nil"));
+    }
+    let Value::Map(entries) = container else {
+        return builtin_set(vec![container, key, value]);
+    };
+    lua_newindex_map(entries, key, value, 0).map(|entries| vec![Value::Map(entries)])
+}
+
+fn lua_newindex_map(
+    mut entries: Vec<(Value, Value)>,
+    key: Value,
+    value: Value,
+    depth: usize,
+) -> VmResult<Vec<(Value, Value)>> {
+    if depth >= LUA_METATABLE_CHAIN_LIMIT {
+        return Err(lua_metatable_error(
+            "'__newindex' chain too long; possible loop",
+        ));
+    }
+    let slot = entries
+        .iter()
+        .position(|(existing_key, _)| *existing_key == key);
+    let redirect = slot.is_none()
+        && metatable_of(&entries).is_some_and(|metatable| {
+            matches!(
+                raw_map_get(metatable, &Value::String("__newindex".to_string())),
+                Some(Value::Map(_))
+            )
+        });
+    if redirect {
+        let metatable_slot = entries
+            .iter_mut()
+            .find(|(existing_key, _)| is_metatable_slot(existing_key))
+            .map(|(_, metatable)| metatable);
+        if let Some(Value::Map(metatable)) = metatable_slot
+            && let Some((_, Value::Map(target))) = metatable
+                .iter_mut()
+                .find(|(existing_key, _)| *existing_key == Value::String("__newindex".to_string()))
+        {
+            *target = lua_newindex_map(std::mem::take(target), key, value, depth + 1)?;
+        }
+        return Ok(entries);
+    }
+    match slot {
+        Some(position) => entries[position].1 = value,
+        None => entries.push((key, value)),
+    }
+    Ok(entries)
+}
+
+fn builtin_lua_error(args: &[Value]) -> VmResult<Vec<Value>> {
+    let message = args.first().map_or_else(
+        || "nil".to_string(),
+        |value| render_format_value(value, false),
+    );
+    Err(VmError::HostError(format!("lua error: {message}")))
+}
+
+/// Bound on `__index`/`__newindex` table chains, so a metatable cycle errors
+/// out instead of spinning.
+const LUA_METATABLE_CHAIN_LIMIT: usize = 64;
+
+/// Lua metatables (and JavaScript class tags) ride along in a map as the
+/// value of its `null` key, which neither language can use as a table key.
+/// Only the `lua_*` builtins treat the slot specially; code compiled from
+/// those frontends reaches lengths, listings and printing through
+/// `lua_plain`, while everything else sees an ordinary `null` key.
+fn is_metatable_slot(key: &Value) -> bool {
+    matches!(key, Value::Null)
+}
+
+fn metatable_of(entries: &[(Value, Value)]) -> Option<&[(Value, Value)]> {
+    entries.iter().find_map(|(key, value)| match (key, value) {
+        (Value::Null, Value::Map(metatable)) => Some(metatable.as_slice()),
+        _ => None,
+    })
+}
+
+fn raw_map_get<'a>(entries: &'a [(Value, Value)], key: &Value) -> Option<&'a Value> {
+    entries
+        .iter()
+        .find(|(existing_key, _)| existing_key == key)
+        .map(|(_, value)| value)
+}
+
+/// Walks the `__index` tables of a map's metatable chain for a key the map
+/// does not hold itself. An `__index` of `true` stands for the metatable
+/// itself, which is how the Lua frontend lowers `T.__index = T`.
+fn metatable_index_owner<'a>(
+    entries: &'a [(Value, Value)],
+    key: &Value,
+) -> Option<&'a [(Value, Value)]> {
+    let index_key = Value::String("__index".to_string());
+    let mut current = entries;
+    for _ in 0..LUA_METATABLE_CHAIN_LIMIT {
+        let metatable = metatable_of(current)?;
+        current = match raw_map_get(metatable, &index_key)? {
+            Value::Bool(true) => metatable,
+            Value::Map(table) => table,
+            _ => return None,
+        };
+        if raw_map_get(current, key).is_some() {
+            return Some(current);
+        }
+    }
+    None
+}
+
+fn metatable_index(entries: &[(Value, Value)], key: &Value) -> Option<Value> {
+    if is_metatable_slot(key) {
+        return None;
+    }
+    metatable_index_owner(entries, key).and_then(|owner| raw_map_get(owner, key).cloned())
+}
+
+fn lua_metatable_error(message: &str) -> VmError {
+    VmError::HostError(format!("lua metatable: {message}"))
+}
+
+fn lua_init_arg(args: &[Value], index: usize) -> VmResult<i64> {
+    match args.get(index) {
+        None | Some(Value::Null) => Ok(1),
+        Some(value) => value.as_int(),
+    }
+}
+
+fn lua_capture_value(capture: Capture) -> Value {
+    match capture {
+        Capture::Text(text) => Value::String(text),
+        Capture::Position(position) => Value::Int(position as i64),
+    }
+}
+
+fn lua_pattern_error(message: String) -> VmError {
+    VmError::HostError(format!("lua pattern: {message}"))
+}
+
+fn take_array_arg(value: Option<Value>, label: &str) -> VmResult<Vec<Value>> {
+    match value {
+        Some(Value::Array(values)) => Ok(values),
+        Some(_) => Err(VmError::TypeMismatch("array")),
+        None => Err(VmError::HostError(format!("missing argument: {label}"))),
+    }
+}
+
+fn take_map_arg(value: Option<Value>, label: &str) -> VmResult<Vec<(Value, Value)>> {
+    match value {
+        Some(Value::Map(entries)) => Ok(entries),
+        Some(_) => Err(VmError::TypeMismatch("map")),
+        None => Err(VmError::HostError(format!("missing argument: {label}"))),
+    }
+}
+
+fn array_position_arg(value: Option<Value>, label: &str) -> VmResult<usize> {
+    let index = value
+        .ok_or_else(|| VmError::HostError(format!("missing argument: {label} index")))?
+        .as_int()?;
+    if index < 0 {
+        return Err(VmError::HostError(format!(
+            "{label} index must be non-negative"
+        )));
+    }
+    usize::try_from(index).map_err(|_| VmError::HostError(format!("{label} index overflow")))
+}
+
 fn spawn_shell_command(command: &str, mode: &str) -> VmResult<Child> {
     let mut process = if cfg!(windows) {
         let mut cmd = Command::new("cmd");
@@ -2,18 +2,25 @@ use super::{Vm, VmError};
 
 pub fn render_vm_error(vm: &Vm, err: &VmError) -> String {
     let mut out = format!("runtime error: {err}");
-    let ip = vm.ip();
+    let ip = vm.instruction_ip();
     if let Some(debug) = vm.debug_info()
-        && let Some(line) = debug.line_for_offset(ip)
+        && let Some(location) = debug.location_for_offset(ip)
     {
-        out.push_str(&format!("\nat ip {ip} (line {line})"));
-        if let Some(source) = debug.source.as_ref()
-            && let Some(line_text) = source
-                .lines()
-                .nth(line.saturating_sub(1) as usize)
-                .map(str::to_string)
-        {
-            out.push_str(&format!("\n{line:>3} | {line_text}\n  | ^"));
+        let line = location.line;
+        out.push_str(&format!(
+            "\nat ip {ip} ({})",
+            debug.describe_location(location)
+        ));
+        if let Some(line_text) = debug.source_line_in(location.source_id, line) {
+            let indent = " ".repeat(location.column.saturating_sub(1) as usize);
+            out.push_str(&format!("\n{line:>3} | {line_text}\n    | {indent}^"));
+        }
+        let stack = debug.logical_stack(ip);
+        if stack.len() > 1 {
+            out.push_str("\ncall stack (innermost first):");
+            for (depth, frame) in stack.iter().enumerate() {
+                out.push_str(&format!("\n  #{depth} {}", debug.describe_frame(frame)));
+            }
         }
     } else {
         out.push_str(&format!("\nat ip {ip}"));
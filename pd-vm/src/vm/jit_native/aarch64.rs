@@ -114,7 +114,7 @@ fn emit_native_trace_bytes(trace: &crate::jit::JitTrace) -> VmResult<Vec<u8>> {
     emit_native_prologue(&mut code);
     emit_status_continue(&mut code);
 
-    for step in &trace.steps {
+    for (step_index, step) in trace.steps.iter().enumerate() {
         match step {
             crate::jit::TraceStep::Nop => {}
             crate::jit::TraceStep::Ldc(index) => {
@@ -126,6 +126,7 @@ fn emit_native_trace_bytes(trace: &crate::jit::JitTrace) -> VmResult<Vec<u8>> {
                     &mut code,
                     layout,
                     NativeBinaryNumericOp::Add,
+                    native_step_ip(trace, step_index)?,
                 )?;
                 emit_native_status_check(&mut code, &mut status_checks);
             }
@@ -134,6 +135,7 @@ fn emit_native_trace_bytes(trace: &crate::jit::JitTrace) -> VmResult<Vec<u8>> {
                     &mut code,
                     layout,
                     NativeBinaryNumericOp::Sub,
+                    native_step_ip(trace, step_index)?,
                 )?;
                 emit_native_status_check(&mut code, &mut status_checks);
             }
@@ -142,6 +144,7 @@ fn emit_native_trace_bytes(trace: &crate::jit::JitTrace) -> VmResult<Vec<u8>> {
                     &mut code,
                     layout,
                     NativeBinaryNumericOp::Mul,
+                    native_step_ip(trace, step_index)?,
                 )?;
                 emit_native_status_check(&mut code, &mut status_checks);
             }
@@ -150,11 +153,12 @@ fn emit_native_trace_bytes(trace: &crate::jit::JitTrace) -> VmResult<Vec<u8>> {
                     &mut code,
                     layout,
                     NativeBinaryNumericOp::Div,
+                    native_step_ip(trace, step_index)?,
                 )?;
                 emit_native_status_check(&mut code, &mut status_checks);
             }
             crate::jit::TraceStep::Mod => {
-                emit_native_step_mod_inline(&mut code, layout)?;
+                emit_native_step_mod_inline(&mut code, layout, native_step_ip(trace, step_index)?)?;
                 emit_native_status_check(&mut code, &mut status_checks);
             }
             crate::jit::TraceStep::Shl => {
@@ -178,7 +182,7 @@ fn emit_native_trace_bytes(trace: &crate::jit::JitTrace) -> VmResult<Vec<u8>> {
                 emit_native_status_check(&mut code, &mut status_checks);
             }
             crate::jit::TraceStep::Ceq => {
-                emit_native_step_ceq_inline(&mut code, layout)?;
+                emit_native_step_ceq_inline(&mut code, layout, native_step_ip(trace, step_index)?)?;
                 emit_native_status_check(&mut code, &mut status_checks);
             }
             crate::jit::TraceStep::Clt => {
@@ -186,6 +190,7 @@ fn emit_native_trace_bytes(trace: &crate::jit::JitTrace) -> VmResult<Vec<u8>> {
                     &mut code,
                     layout,
                     NativeBinaryNumericOp::Clt,
+                    native_step_ip(trace, step_index)?,
                 )?;
                 emit_native_status_check(&mut code, &mut status_checks);
             }
@@ -194,6 +199,7 @@ fn emit_native_trace_bytes(trace: &crate::jit::JitTrace) -> VmResult<Vec<u8>> {
                     &mut code,
                     layout,
                     NativeBinaryNumericOp::Cgt,
+                    native_step_ip(trace, step_index)?,
                 )?;
                 emit_native_status_check(&mut code, &mut status_checks);
             }
@@ -314,6 +320,7 @@ fn emit_native_step_binary_numeric_inline(
     code: &mut Vec<u8>,
     layout: NativeStackLayout,
     op: NativeBinaryNumericOp,
+    step_ip: u64,
 ) -> VmResult<()> {
     let stack_len_offset = vec_len_disp(layout.vm_stack_offset, layout.stack_vec)?;
     let stack_ptr_offset = vec_ptr_disp(layout.vm_stack_offset, layout.stack_vec)?;
@@ -538,11 +545,18 @@ fn emit_native_step_binary_numeric_inline(
 
     let err_label = code.len();
     emit_status_error(code);
+    let err_done = emit_b_placeholder(code);
+
+    // Anything but two numbers, such as string or array concatenation, leaves
+    // the trace with the operands in place so the interpreter runs this step.
+    let fallback_label = code.len();
+    emit_native_step_jump_to_ip_inline(code, layout, step_ip)?;
     let done_label = code.len();
 
     patch_b_cond_rel19(code, underflow, err_label)?;
-    patch_b_cond_rel19(code, lhs_not_float, err_label)?;
-    patch_b_cond_rel19(code, rhs_not_float, err_label)?;
+    patch_b_cond_rel19(code, lhs_not_float, fallback_label)?;
+    patch_b_cond_rel19(code, rhs_not_float, fallback_label)?;
+    patch_b_rel26(code, err_done, done_label)?;
     if let Some(patch) = int_div_zero {
         patch_b_cond_rel19(code, patch, err_label)?;
     }
@@ -678,8 +692,12 @@ fn emit_native_step_shift_inline(
     Ok(())
 }
 
-fn emit_native_step_mod_inline(code: &mut Vec<u8>, layout: NativeStackLayout) -> VmResult<()> {
-    emit_native_step_binary_numeric_inline(code, layout, NativeBinaryNumericOp::Mod)
+fn emit_native_step_mod_inline(
+    code: &mut Vec<u8>,
+    layout: NativeStackLayout,
+    step_ip: u64,
+) -> VmResult<()> {
+    emit_native_step_binary_numeric_inline(code, layout, NativeBinaryNumericOp::Mod, step_ip)
 }
 
 fn emit_native_step_and_inline(code: &mut Vec<u8>, layout: NativeStackLayout) -> VmResult<()> {
@@ -1018,7 +1036,11 @@ fn emit_native_step_stloc_inline(
     Ok(())
 }
 
-fn emit_native_step_ceq_inline(code: &mut Vec<u8>, layout: NativeStackLayout) -> VmResult<()> {
+fn emit_native_step_ceq_inline(
+    code: &mut Vec<u8>,
+    layout: NativeStackLayout,
+    step_ip: u64,
+) -> VmResult<()> {
     let stack_len_offset = vec_len_disp(layout.vm_stack_offset, layout.stack_vec)?;
     let stack_ptr_offset = vec_ptr_disp(layout.vm_stack_offset, layout.stack_vec)?;
 
@@ -1098,11 +1120,16 @@ fn emit_native_step_ceq_inline(code: &mut Vec<u8>, layout: NativeStackLayout) ->
 
     let err_label = code.len();
     emit_status_error(code);
+    let err_done = emit_b_placeholder(code);
+
+    // Strings and other heap values are compared by the interpreter.
+    let fallback_label = code.len();
+    emit_native_step_jump_to_ip_inline(code, layout, step_ip)?;
     let done_label = code.len();
 
     patch_b_cond_rel19(code, tag_is_int, int_label)?;
     patch_b_cond_rel19(code, tag_is_bool, bool_label)?;
-    patch_b_rel26(code, unknown_tag, err_label)?;
+    patch_b_rel26(code, unknown_tag, fallback_label)?;
     patch_b_cond_rel19(code, int_ne, result_label)?;
     patch_b_rel26(code, int_done, result_label)?;
     patch_b_cond_rel19(code, bool_ne, result_label)?;
@@ -1110,8 +1137,9 @@ fn emit_native_step_ceq_inline(code: &mut Vec<u8>, layout: NativeStackLayout) ->
     patch_b_cond_rel19(code, tags_not_equal, not_equal_label)?;
     patch_b_rel26(code, ne_done, result_label)?;
     patch_b_cond_rel19(code, underflow, err_label)?;
-    patch_b_cond_rel19(code, lhs_string, err_label)?;
-    patch_b_cond_rel19(code, rhs_string, err_label)?;
+    patch_b_cond_rel19(code, lhs_string, fallback_label)?;
+    patch_b_cond_rel19(code, rhs_string, fallback_label)?;
+    patch_b_rel26(code, err_done, done_label)?;
     patch_b_rel26(code, ok_done, done_label)?;
     Ok(())
 }
@@ -1168,6 +1196,14 @@ fn emit_native_step_guard_false_inline(
     Ok(())
 }
 
+fn native_step_ip(trace: &crate::jit::JitTrace, step_index: usize) -> VmResult<u64> {
+    let ip = trace.step_ips.get(step_index).copied().ok_or_else(|| {
+        VmError::JitNative(format!("trace step {} has no recorded ip", step_index))
+    })?;
+    u64::try_from(ip)
+        .map_err(|_| VmError::JitNative("trace step ip exceeds 64-bit range".to_string()))
+}
+
 fn emit_native_step_jump_to_ip_inline(
     code: &mut Vec<u8>,
     layout: NativeStackLayout,
@@ -2199,6 +2235,7 @@ mod tests {
             has_call: false,
             has_yielding_call: false,
             steps: vec![step],
+            step_ips: vec![0],
             terminal: JitTraceTerminal::LoopBack,
             executions: 0,
         }
@@ -2374,6 +2411,29 @@ mod tests {
         assert_eq!(nan_vm.stack, vec![Value::Bool(false)]);
     }
 
+    #[test]
+    fn non_numeric_operands_exit_the_trace_at_their_step() {
+        for step in [TraceStep::Add, TraceStep::Clt, TraceStep::Ceq] {
+            let mut trace = build_single_step_trace(step.clone());
+            trace.step_ips = vec![42];
+            let mut vm = Vm::new(Program::new(Vec::new(), Vec::new()));
+            vm.stack.push(Value::String("a".to_string()));
+            vm.stack.push(Value::String("b".to_string()));
+
+            let status = execute_trace(&mut vm, trace).expect("trace should run");
+            assert_eq!(status, STATUS_TRACE_EXIT, "{step:?}");
+            assert_eq!(vm.ip(), 42, "{step:?}");
+            assert_eq!(
+                vm.stack,
+                vec![
+                    Value::String("a".to_string()),
+                    Value::String("b".to_string())
+                ],
+                "{step:?}"
+            );
+        }
+    }
+
     #[test]
     fn add_step_emits_no_helper_call() {
         let trace = build_single_step_trace(TraceStep::Add);
@@ -2481,6 +2541,7 @@ mod tests {
                 TraceStep::JumpToRoot,
                 TraceStep::Ret,
             ],
+            step_ips: vec![0; 24],
             terminal: JitTraceTerminal::LoopBack,
             executions: 0,
         };
@@ -2502,6 +2563,7 @@ mod tests {
                 TraceStep::GuardFalse { exit_ip: 7 },
                 TraceStep::JumpToRoot,
             ],
+            step_ips: vec![0; 3],
             terminal: JitTraceTerminal::LoopBack,
             executions: 0,
         };
@@ -2535,6 +2597,7 @@ mod tests {
                 TraceStep::Stloc(0),
                 TraceStep::JumpToRoot,
             ],
+            step_ips: vec![0; 13],
             terminal: JitTraceTerminal::LoopBack,
             executions: 0,
         };
@@ -1,4 +1,4 @@
-use super::super::{Program, Value, Vm, VmError, VmResult};
+use super::super::{Program, Value, Vm, VmError, VmResult};
 use super::{
     NativeBackend, STATUS_CONTINUE, STATUS_ERROR, STATUS_HALTED, STATUS_TRACE_EXIT, STATUS_YIELDED,
 };
@@ -115,6 +115,7 @@ fn emit_native_trace_bytes(trace: &crate::jit::JitTrace) -> VmResult<Vec<u8>> {
                     &mut code,
                     layout,
                     NativeBinaryNumericOp::Add,
+                    native_step_ip(trace, step_index)?,
                 )?;
                 emit_native_status_check(&mut code, &mut jump_patches);
             }
@@ -123,6 +124,7 @@ fn emit_native_trace_bytes(trace: &crate::jit::JitTrace) -> VmResult<Vec<u8>> {
                     &mut code,
                     layout,
                     NativeBinaryNumericOp::Sub,
+                    native_step_ip(trace, step_index)?,
                 )?;
                 emit_native_status_check(&mut code, &mut jump_patches);
             }
@@ -131,6 +133,7 @@ fn emit_native_trace_bytes(trace: &crate::jit::JitTrace) -> VmResult<Vec<u8>> {
                     &mut code,
                     layout,
                     NativeBinaryNumericOp::Mul,
+                    native_step_ip(trace, step_index)?,
                 )?;
                 emit_native_status_check(&mut code, &mut jump_patches);
             }
@@ -139,11 +142,12 @@ fn emit_native_trace_bytes(trace: &crate::jit::JitTrace) -> VmResult<Vec<u8>> {
                     &mut code,
                     layout,
                     NativeBinaryNumericOp::Div,
+                    native_step_ip(trace, step_index)?,
                 )?;
                 emit_native_status_check(&mut code, &mut jump_patches);
             }
             crate::jit::TraceStep::Mod => {
-                emit_native_step_mod_inline(&mut code, layout)?;
+                emit_native_step_mod_inline(&mut code, layout, native_step_ip(trace, step_index)?)?;
                 emit_native_status_check(&mut code, &mut jump_patches);
             }
             crate::jit::TraceStep::Shl => {
@@ -167,7 +171,7 @@ fn emit_native_trace_bytes(trace: &crate::jit::JitTrace) -> VmResult<Vec<u8>> {
                 emit_native_status_check(&mut code, &mut jump_patches);
             }
             crate::jit::TraceStep::Ceq => {
-                emit_native_step_ceq_inline(&mut code, layout)?;
+                emit_native_step_ceq_inline(&mut code, layout, native_step_ip(trace, step_index)?)?;
                 emit_native_status_check(&mut code, &mut jump_patches);
             }
             crate::jit::TraceStep::Clt => {
@@ -175,6 +179,7 @@ fn emit_native_trace_bytes(trace: &crate::jit::JitTrace) -> VmResult<Vec<u8>> {
                     &mut code,
                     layout,
                     NativeBinaryNumericOp::Clt,
+                    native_step_ip(trace, step_index)?,
                 )?;
                 emit_native_status_check(&mut code, &mut jump_patches);
             }
@@ -183,6 +188,7 @@ fn emit_native_trace_bytes(trace: &crate::jit::JitTrace) -> VmResult<Vec<u8>> {
                     &mut code,
                     layout,
                     NativeBinaryNumericOp::Cgt,
+                    native_step_ip(trace, step_index)?,
                 )?;
                 emit_native_status_check(&mut code, &mut jump_patches);
             }
@@ -600,6 +606,7 @@ fn emit_native_step_binary_numeric_inline(
     code: &mut Vec<u8>,
     layout: NativeStackLayout,
     op: NativeBinaryNumericOp,
+    step_ip: u32,
 ) -> VmResult<()> {
     let stack_len_offset = checked_add_i32(
         layout.vm_stack_offset,
@@ -887,11 +894,13 @@ fn emit_native_step_binary_numeric_inline(
     }
     let float_done = emit_jmp_rel32(code);
 
-    let error_label = code.len();
-    emit_status_error(code);
+    // Anything but two numbers, such as string or array concatenation, leaves
+    // the trace with the operands in place so the interpreter runs this step.
+    let fallback_label = code.len();
+    emit_native_step_jump_to_ip_inline(code, layout, step_ip)?;
     let done_label = code.len();
-    patch_rel32(code, lhs_not_float, error_label)?;
-    patch_rel32(code, rhs_not_float, error_label)?;
+    patch_rel32(code, lhs_not_float, fallback_label)?;
+    patch_rel32(code, rhs_not_float, fallback_label)?;
     patch_rel32(code, int_done, done_label)?;
     patch_rel32(code, float_done, done_label)?;
     Ok(())
@@ -989,8 +998,12 @@ fn emit_native_step_shift_inline(
     Ok(())
 }
 
-fn emit_native_step_mod_inline(code: &mut Vec<u8>, layout: NativeStackLayout) -> VmResult<()> {
-    emit_native_step_binary_numeric_inline(code, layout, NativeBinaryNumericOp::Mod)
+fn emit_native_step_mod_inline(
+    code: &mut Vec<u8>,
+    layout: NativeStackLayout,
+    step_ip: u32,
+) -> VmResult<()> {
+    emit_native_step_binary_numeric_inline(code, layout, NativeBinaryNumericOp::Mod, step_ip)
 }
 
 fn emit_native_step_and_inline(code: &mut Vec<u8>, layout: NativeStackLayout) -> VmResult<()> {
@@ -1379,7 +1392,11 @@ fn emit_native_step_stloc_inline(
     Ok(())
 }
 
-fn emit_native_step_ceq_inline(code: &mut Vec<u8>, layout: NativeStackLayout) -> VmResult<()> {
+fn emit_native_step_ceq_inline(
+    code: &mut Vec<u8>,
+    layout: NativeStackLayout,
+    step_ip: u32,
+) -> VmResult<()> {
     let stack_len_offset = vec_len_disp(layout.vm_stack_offset, layout.stack_vec)?;
     let stack_ptr_offset = vec_ptr_disp(layout.vm_stack_offset, layout.stack_vec)?;
 
@@ -1460,6 +1477,11 @@ fn emit_native_step_ceq_inline(code: &mut Vec<u8>, layout: NativeStackLayout) ->
 
     let error_label = code.len();
     emit_status_error(code);
+    let error_done = emit_jmp_rel32(code);
+
+    // Strings and other heap values are compared by the interpreter.
+    let fallback_label = code.len();
+    emit_native_step_jump_to_ip_inline(code, layout, step_ip)?;
     let done_label = code.len();
 
     patch_rel32(code, cmp_int, cmp_int_label)?;
@@ -1471,9 +1493,10 @@ fn emit_native_step_ceq_inline(code: &mut Vec<u8>, layout: NativeStackLayout) ->
     patch_rel32(code, bool_ready, result_label)?;
     patch_rel32(code, ne_ready, result_label)?;
     patch_rel32(code, underflow, error_label)?;
-    patch_rel32(code, lhs_string, error_label)?;
-    patch_rel32(code, rhs_string, error_label)?;
-    patch_rel32(code, unknown_tag, error_label)?;
+    patch_rel32(code, lhs_string, fallback_label)?;
+    patch_rel32(code, rhs_string, fallback_label)?;
+    patch_rel32(code, unknown_tag, fallback_label)?;
+    patch_rel32(code, error_done, done_label)?;
     patch_rel32(code, ok_done, done_label)?;
     Ok(())
 }
@@ -1530,6 +1553,15 @@ fn emit_native_step_guard_false_inline(
     Ok(())
 }
 
+fn native_step_ip(trace: &crate::jit::JitTrace, step_index: usize) -> VmResult<u32> {
+    let ip = trace.step_ips.get(step_index).copied().ok_or_else(|| {
+        VmError::JitNative(format!("trace step {} has no recorded ip", step_index))
+    })?;
+    u32::try_from(ip).map_err(|_| {
+        VmError::JitNative(format!("trace step ip {} exceeds u32 immediate range", ip))
+    })
+}
+
 fn emit_native_step_jump_to_ip_inline(
     code: &mut Vec<u8>,
     layout: NativeStackLayout,
@@ -3089,6 +3121,7 @@ mod tests {
             has_call: false,
             has_yielding_call: false,
             steps: vec![step],
+            step_ips: vec![0],
             terminal: JitTraceTerminal::LoopBack,
             executions: 0,
         }
@@ -3261,6 +3294,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn non_numeric_operands_exit_the_trace_at_their_step() {
+        for step in [TraceStep::Add, TraceStep::Clt, TraceStep::Ceq] {
+            let mut trace = build_single_step_trace(step.clone());
+            trace.step_ips = vec![42];
+            let code = emit_native_trace_bytes(&trace).expect("trace should compile");
+            let memory = BackendExecutableMemory::from_code(&code).expect("code should map");
+            let entry = unsafe { std::mem::transmute::<*mut u8, NativeEntry>(memory.ptr) };
+            let mut vm = Vm::new(Program::new(Vec::new(), Vec::new()));
+            vm.stack.push(Value::String("a".to_string()));
+            vm.stack.push(Value::String("b".to_string()));
+
+            clear_bridge_error();
+            let status = unsafe { entry(&mut vm as *mut Vm) };
+            assert_eq!(status, STATUS_TRACE_EXIT, "{step:?}");
+            assert_eq!(vm.ip(), 42, "{step:?}");
+            assert_eq!(
+                vm.stack(),
+                &[
+                    Value::String("a".to_string()),
+                    Value::String("b".to_string())
+                ],
+                "{step:?}"
+            );
+        }
+    }
+
     #[test]
     fn call_step_bridge_executes_host_function_and_continues() {
         let mut vm = Vm::new(Program::new(Vec::new(), Vec::new()));
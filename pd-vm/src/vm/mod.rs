@@ -315,6 +315,7 @@ pub struct Vm {
     program: Program,
     program_cache_key: u64,
     ip: usize,
+    instruction_ip: usize,
     stack: Vec<Value>,
     locals: Vec<Value>,
     host_functions: Vec<VmHostFunction>,
@@ -326,6 +327,7 @@ pub struct Vm {
     native_traces: HashMap<usize, NativeTrace>,
     native_trace_exec_count: u64,
     io_state: builtin_runtime::IoState,
+    rng_state: builtin_runtime::RngState,
 }
 
 enum StepExecOutcome {
@@ -508,6 +510,7 @@ impl Vm {
             program,
             program_cache_key,
             ip: 0,
+            instruction_ip: 0,
             stack: Vec::new(),
             locals: Vec::new(),
             host_functions: Vec::new(),
@@ -519,6 +522,7 @@ impl Vm {
             native_traces: HashMap::new(),
             native_trace_exec_count: 0,
             io_state: builtin_runtime::IoState::default(),
+            rng_state: builtin_runtime::RngState::default(),
         }
     }
 
@@ -528,6 +532,7 @@ impl Vm {
             program,
             program_cache_key,
             ip: 0,
+            instruction_ip: 0,
             stack: Vec::new(),
             locals: vec![Value::Null; local_count],
             host_functions: Vec::new(),
@@ -539,6 +544,7 @@ impl Vm {
             native_traces: HashMap::new(),
             native_trace_exec_count: 0,
             io_state: builtin_runtime::IoState::default(),
+            rng_state: builtin_runtime::RngState::default(),
         }
     }
 
@@ -597,6 +603,37 @@ impl Vm {
         self.run_internal(Some(debugger), false)
     }
 
+    /// Runs at most `*budget` instructions on the interpreter, counting them off
+    /// `budget`. `Ok(None)` means the budget ran out first; running again
+    /// continues from where it stopped.
+    pub fn run_with_budget(&mut self, budget: &mut usize) -> VmResult<Option<VmStatus>> {
+        self.ensure_call_bindings()?;
+        while *budget > 0 {
+            *budget -= 1;
+            if self.ip >= self.program.code.len() {
+                return Err(VmError::BytecodeBounds);
+            }
+            self.instruction_ip = self.ip;
+            let opcode = self.read_u8()?;
+            match self.execute_interpreter_instruction(opcode)? {
+                StepExecOutcome::Continue => {}
+                StepExecOutcome::Halted => return Ok(Some(VmStatus::Halted)),
+                StepExecOutcome::Yielded => return Ok(Some(VmStatus::Yielded)),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Seed used by the `rand::*` and `uuid::*` builtins. Fresh VMs pick a random seed.
+    pub fn rng_seed(&self) -> u64 {
+        self.rng_state.seed
+    }
+
+    /// Resets the builtin random stream so runs with the same seed produce the same values.
+    pub fn set_rng_seed(&mut self, seed: u64) {
+        self.rng_state = builtin_runtime::RngState::with_seed(seed);
+    }
+
     pub fn set_jit_config(&mut self, config: crate::jit::JitConfig) {
         self.jit.set_config(config);
     }
@@ -658,6 +695,7 @@ impl Vm {
                     self.jit.observe_hot_ip(self.ip, program)
                 };
                 if let Some(trace_id) = trace_id {
+                    self.instruction_ip = self.ip;
                     match self.execute_jit_entry(trace_id)? {
                         TraceExecOutcome::Continue => continue,
                         TraceExecOutcome::Halted => {
@@ -680,6 +718,7 @@ impl Vm {
                 return Err(VmError::BytecodeBounds);
             }
 
+            self.instruction_ip = self.ip;
             let opcode = self.read_u8()?;
             match self.execute_interpreter_instruction(opcode)? {
                 StepExecOutcome::Continue => {}
@@ -1173,6 +1212,16 @@ impl Vm {
         &self.locals
     }
 
+    /// Overwrites local `index`, as the debugger's `set` command does.
+    pub fn set_local(&mut self, index: u8, value: Value) -> VmResult<()> {
+        let slot = self
+            .locals
+            .get_mut(index as usize)
+            .ok_or(VmError::InvalidLocal(index))?;
+        *slot = value;
+        Ok(())
+    }
+
     pub fn program(&self) -> &Program {
         &self.program
     }
@@ -1181,6 +1230,12 @@ impl Vm {
         self.ip
     }
 
+    /// Offset of the instruction the interpreter last started, which is the
+    /// one that failed when `run` returns an error.
+    pub fn instruction_ip(&self) -> usize {
+        self.instruction_ip
+    }
+
     pub fn debug_info(&self) -> Option<&crate::debug_info::DebugInfo> {
         self.program.debug.as_ref()
     }
@@ -1,8 +1,11 @@
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt::Write;
 
 use crate::builtins::BuiltinFunction;
-use crate::debug_info::{ArgInfo, DebugFunction, DebugInfo, LineInfo, LocalInfo};
+use crate::debug_info::{
+    ArgInfo, DebugFunction, DebugInfo, DebugSource, InlineFrame, LineInfo, LocalInfo,
+    MAIN_SOURCE_ID,
+};
 use crate::vm::{HostImport, OpCode, Program, Value};
 
 const MAGIC: [u8; 4] = *b"VMBC";
@@ -10,7 +13,11 @@ const VERSION_V1: u16 = 1;
 const VERSION_V2: u16 = 2;
 const VERSION_V3: u16 = 3;
 const VERSION_V4: u16 = 4;
-const ENCODE_VERSION: u16 = VERSION_V4;
+/// Debug info carries a source table and lines carry a source id and column.
+const VERSION_V5: u16 = 5;
+/// Debug info lists the code ranges of inlined calls and the locals they name.
+const VERSION_V6: u16 = 6;
+const ENCODE_VERSION: u16 = VERSION_V6;
 const FLAGS: u16 = 0;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -198,6 +205,8 @@ pub fn decode_program(bytes: &[u8]) -> Result<Program, WireError> {
         && version != VERSION_V2
         && version != VERSION_V3
         && version != VERSION_V4
+        && version != VERSION_V5
+        && version != VERSION_V6
     {
         return Err(WireError::UnsupportedVersion(version));
     }
@@ -300,158 +309,325 @@ pub fn disassemble_program(program: &Program) -> String {
 
 pub fn disassemble_program_with_options(program: &Program, options: DisassembleOptions) -> String {
     let mut out = String::new();
-    let _ = writeln!(&mut out, "constants ({}):", program.constants.len());
+    let _ = writeln!(&mut out, "; constants ({})", program.constants.len());
     for (index, constant) in program.constants.iter().enumerate() {
-        let _ = writeln!(&mut out, "  [{index:04}] {constant:?}");
+        match format_asm_literal(constant) {
+            Some(literal) => {
+                let _ = writeln!(&mut out, ".const k{index} {literal}");
+            }
+            None => {
+                let _ = writeln!(&mut out, "; k{index} has no literal syntax: {constant:?}");
+            }
+        }
+    }
+
+    let _ = writeln!(&mut out, "; imports ({})", program.imports.len());
+    for import in &program.imports {
+        let _ = writeln!(
+            &mut out,
+            ".import {} {}",
+            format_asm_name(&import.name),
+            import.arity
+        );
     }
 
-    let _ = writeln!(&mut out, "imports ({}):", program.imports.len());
-    for (index, import) in program.imports.iter().enumerate() {
-        let _ = writeln!(&mut out, "  [{index:04}] {}/{}", import.name, import.arity);
+    let mut directives = BTreeMap::<usize, Vec<String>>::new();
+    match &program.debug {
+        None => {
+            let _ = writeln!(&mut out, ".debug none");
+        }
+        Some(debug) => {
+            let _ = writeln!(&mut out, ".debug explicit");
+            write_debug_declarations(&mut out, debug);
+            for (offset, directive) in inline_frame_directives(debug) {
+                directives
+                    .entry(offset as usize)
+                    .or_default()
+                    .push(directive);
+            }
+            for info in &debug.lines {
+                directives
+                    .entry(info.offset as usize)
+                    .or_default()
+                    .push(format!(
+                        ".line {} {} {}",
+                        info.source_id, info.line, info.column
+                    ));
+            }
+        }
     }
-    let _ = writeln!(&mut out, "code ({} bytes):", program.code.len());
+
+    let code = &program.code;
+    // Every jump target needs a label, and labels split the code, so list it
+    // again until the labels cover the jumps of the resulting listing.
+    let mut labels = BTreeSet::<usize>::new();
+    let entries = loop {
+        let cuts = labels
+            .iter()
+            .copied()
+            .chain(directives.keys().copied())
+            .filter(|offset| *offset <= code.len())
+            .collect::<BTreeSet<_>>();
+        let entries = listing_entries(program, &cuts);
+        let targets = entries
+            .iter()
+            .filter_map(|entry| entry.jump_target)
+            .collect::<BTreeSet<_>>();
+        if targets.is_subset(&labels) {
+            break entries;
+        }
+        labels.extend(targets);
+    };
+
+    let _ = writeln!(&mut out, "; code ({} bytes)", code.len());
+    let _ = writeln!(&mut out, ".code");
     let mut source_annotations = source_annotations(program, options.show_source);
     if options.show_source && source_annotations.is_none() {
         let _ = writeln!(&mut out, "      ; source: <none>");
     }
-    let code = &program.code;
-    let mut ip = 0usize;
-    while ip < code.len() {
-        let start = ip;
+    for entry in entries {
+        let start = entry.start;
+        if labels.contains(&start) {
+            let _ = writeln!(&mut out, ".label L{start:04}");
+        }
+        for directive in directives.remove(&start).unwrap_or_default() {
+            let _ = writeln!(&mut out, "{directive}");
+        }
         if let Some(lines_at_offset) = source_annotations
             .as_mut()
             .and_then(|annotations| annotations.remove(&start))
         {
-            for (line, text) in lines_at_offset {
-                let _ = writeln!(&mut out, "      ; src {line:04}  {text}");
+            for (label, text) in lines_at_offset {
+                let _ = writeln!(&mut out, "      ; src {label}  {text}");
             }
         }
-        let opcode = code[ip];
-        ip += 1;
 
-        let mut instruction = String::new();
-        let mut truncated = false;
-        match opcode {
-            x if x == OpCode::Nop as u8 => instruction.push_str("nop"),
-            x if x == OpCode::Ret as u8 => instruction.push_str("ret"),
-            x if x == OpCode::Ldc as u8 => {
-                if let Some(index) = read_u32(code, &mut ip) {
-                    instruction.push_str(&format!("ldc {index}"));
-                    if let Some(value) = program.constants.get(index as usize) {
-                        instruction.push_str(&format!(" ; const[{index}]={value:?}"));
-                    }
-                } else {
-                    instruction.push_str("ldc <truncated>");
-                    truncated = true;
-                }
-            }
-            x if x == OpCode::Add as u8 => instruction.push_str("add"),
-            x if x == OpCode::Sub as u8 => instruction.push_str("sub"),
-            x if x == OpCode::Mul as u8 => instruction.push_str("mul"),
-            x if x == OpCode::Div as u8 => instruction.push_str("div"),
-            x if x == OpCode::Neg as u8 => instruction.push_str("neg"),
-            x if x == OpCode::Ceq as u8 => instruction.push_str("ceq"),
-            x if x == OpCode::Clt as u8 => instruction.push_str("clt"),
-            x if x == OpCode::Cgt as u8 => instruction.push_str("cgt"),
-            x if x == OpCode::Br as u8 => {
-                if let Some(target) = read_u32(code, &mut ip) {
-                    instruction.push_str(&format!("br {target}"));
-                } else {
-                    instruction.push_str("br <truncated>");
-                    truncated = true;
-                }
-            }
-            x if x == OpCode::Brfalse as u8 => {
-                if let Some(target) = read_u32(code, &mut ip) {
-                    instruction.push_str(&format!("brfalse {target}"));
-                } else {
-                    instruction.push_str("brfalse <truncated>");
-                    truncated = true;
-                }
+        let bytes = &code[start..entry.end];
+        let instruction = entry.text.unwrap_or_else(|| format_byte_directive(bytes));
+        let mut line = format!(
+            "    {instruction:<24} ; {start:04}  {}",
+            format_hex_bytes(bytes)
+        );
+        if let Some(note) = entry.note {
+            let _ = write!(&mut line, "  {note}");
+        }
+        let _ = writeln!(&mut out, "{line}");
+    }
+    if labels.contains(&code.len()) {
+        let _ = writeln!(&mut out, ".label L{:04}", code.len());
+    }
+    for directive in directives.into_values().flatten() {
+        let _ = writeln!(&mut out, "{directive}");
+    }
+
+    out
+}
+
+/// One line of the code listing: an instruction, or bytes written with
+/// `.byte` when the listing cannot spell them as one.
+struct ListingEntry {
+    start: usize,
+    end: usize,
+    text: Option<String>,
+    note: Option<String>,
+    jump_target: Option<usize>,
+}
+
+/// Splits the code into listing entries. Labels and debug directives can only
+/// sit between entries, so an instruction overlapping one of `cuts` is listed
+/// as bytes up to the cut.
+fn listing_entries(program: &Program, cuts: &BTreeSet<usize>) -> Vec<ListingEntry> {
+    let code = &program.code;
+    let mut entries = Vec::new();
+    let mut ip = 0usize;
+    while ip < code.len() {
+        let next_cut = cuts.range(ip + 1..).next().copied().unwrap_or(code.len());
+        let mut entry = decode_listing_entry(program, ip);
+        if entry.end > next_cut {
+            entry = ListingEntry {
+                start: ip,
+                end: next_cut,
+                text: None,
+                note: Some("overlaps a label or debug entry".to_string()),
+                jump_target: None,
+            };
+        }
+        ip = entry.end;
+        entries.push(entry);
+    }
+    entries
+}
+
+fn decode_listing_entry(program: &Program, start: usize) -> ListingEntry {
+    let code = &program.code;
+    let mut ip = start + 1;
+    let raw = |end: usize, note: &str| ListingEntry {
+        start,
+        end,
+        text: None,
+        note: Some(note.to_string()),
+        jump_target: None,
+    };
+    let instruction = |end: usize, text: String, note: Option<String>| ListingEntry {
+        start,
+        end,
+        text: Some(text),
+        note,
+        jump_target: None,
+    };
+    let truncated = raw(code.len(), "truncated instruction");
+    let Some(opcode) = OpCode::from_byte(code[start]) else {
+        return raw(start + 1, "invalid opcode");
+    };
+    match opcode {
+        OpCode::Ldc => {
+            let Some(index) = read_u32(code, &mut ip) else {
+                return truncated;
+            };
+            match program
+                .constants
+                .get(index as usize)
+                .map(format_asm_literal)
+            {
+                Some(Some(literal)) => instruction(ip, format!("ldc k{index}"), Some(literal)),
+                _ => raw(ip, "constant has no .const"),
             }
-            x if x == OpCode::Pop as u8 => instruction.push_str("pop"),
-            x if x == OpCode::Dup as u8 => instruction.push_str("dup"),
-            x if x == OpCode::Ldloc as u8 => {
-                if let Some(index) = read_u8(code, &mut ip) {
-                    instruction.push_str(&format!("ldloc {index}"));
-                } else {
-                    instruction.push_str("ldloc <truncated>");
-                    truncated = true;
-                }
+        }
+        OpCode::Br | OpCode::Brfalse => {
+            let Some(target) = read_u32(code, &mut ip) else {
+                return truncated;
+            };
+            if target as usize > code.len() {
+                return raw(ip, "jump target outside the code");
             }
-            x if x == OpCode::Stloc as u8 => {
-                if let Some(index) = read_u8(code, &mut ip) {
-                    instruction.push_str(&format!("stloc {index}"));
-                } else {
-                    instruction.push_str("stloc <truncated>");
-                    truncated = true;
-                }
+            ListingEntry {
+                jump_target: Some(target as usize),
+                ..instruction(ip, format!("{} L{target:04}", opcode.mnemonic()), None)
             }
-            x if x == OpCode::Call as u8 => {
-                if let Some(index) = read_u16(code, &mut ip) {
-                    if let Some(argc) = read_u8(code, &mut ip) {
-                        instruction.push_str(&format!("call {index} {argc}"));
-                        if let Some(comment) = format_call_target(program, index, argc) {
-                            instruction.push_str(&format!(" ; {comment}"));
-                        }
-                    } else {
-                        instruction.push_str("call <truncated>");
-                        truncated = true;
-                    }
-                } else {
-                    instruction.push_str("call <truncated>");
-                    truncated = true;
-                }
-            }
-            x if x == OpCode::Shl as u8 => instruction.push_str("shl"),
-            x if x == OpCode::Shr as u8 => instruction.push_str("shr"),
-            x if x == OpCode::Mod as u8 => instruction.push_str("mod"),
-            x if x == OpCode::And as u8 => instruction.push_str("and"),
-            x if x == OpCode::Or as u8 => instruction.push_str("or"),
-            other => instruction.push_str(&format!(".byte 0x{other:02X} ; invalid opcode")),
-        }
+        }
+        OpCode::Ldloc | OpCode::Stloc => {
+            let Some(index) = read_u8(code, &mut ip) else {
+                return truncated;
+            };
+            instruction(ip, format!("{} {index}", opcode.mnemonic()), None)
+        }
+        OpCode::Call => {
+            let (Some(index), Some(argc)) = (read_u16(code, &mut ip), read_u8(code, &mut ip))
+            else {
+                return truncated;
+            };
+            instruction(
+                ip,
+                format!("call {index} {argc}"),
+                format_call_target(program, index, argc),
+            )
+        }
+        _ => instruction(ip, opcode.mnemonic().to_string(), None),
+    }
+}
 
-        let encoded = format_hex_bytes(&code[start..ip]);
-        let _ = writeln!(&mut out, "{start:04}\t{encoded:<14}\t{instruction}");
-        if truncated {
-            break;
+/// Declarations of the debug info that is not tied to a code offset.
+fn write_debug_declarations(out: &mut String, debug: &DebugInfo) {
+    for (id, source) in debug.sources.iter().enumerate() {
+        let _ = write!(out, ".source {id} {}", format_asm_name(&source.name));
+        if let Some(text) = &source.text {
+            let _ = write!(out, " {}", format_asm_string(text));
         }
+        out.push('\n');
+    }
+    for function in &debug.functions {
+        let _ = write!(out, ".function {}", format_asm_name(&function.name));
+        for (index, arg) in function.args.iter().enumerate() {
+            let _ = write!(out, " {}", format_asm_name(&arg.name));
+            if arg.position as usize != index {
+                let _ = write!(out, "@{}", arg.position);
+            }
+        }
+        out.push('\n');
+    }
+    for local in &debug.locals {
+        let _ = writeln!(
+            out,
+            ".local {} {}",
+            format_asm_name(&local.name),
+            local.index
+        );
     }
+}
 
-    out
+/// `.inline` / `.endinline` directives with the offsets they go at. Frames are
+/// listed in the order they were entered, so replaying them against a stack
+/// of open frames closes each one before a frame outside it opens.
+fn inline_frame_directives(debug: &DebugInfo) -> Vec<(u32, String)> {
+    let frames = &debug.inline_frames;
+    let mut directives = Vec::new();
+    let mut open = Vec::<usize>::new();
+    for (index, frame) in frames.iter().enumerate() {
+        while open.last().map(|open| *open as u32) != frame.parent {
+            let Some(closed) = open.pop() else {
+                break;
+            };
+            directives.push((frames[closed].end, ".endinline".to_string()));
+        }
+        let mut directive = format!(
+            ".inline {} {} {}",
+            format_asm_name(&frame.function),
+            frame.call_source,
+            frame.call_line
+        );
+        for local in &frame.locals {
+            let _ = write!(
+                directive,
+                " {}@{}",
+                format_asm_name(&local.name),
+                local.index
+            );
+        }
+        directives.push((frame.start, directive));
+        open.push(index);
+    }
+    while let Some(closed) = open.pop() {
+        directives.push((frames[closed].end, ".endinline".to_string()));
+    }
+    directives
 }
 
 fn source_annotations(
     program: &Program,
     show_source: bool,
-) -> Option<BTreeMap<usize, Vec<(u32, String)>>> {
+) -> Option<BTreeMap<usize, Vec<(String, String)>>> {
     if !show_source {
         return None;
     }
     let debug = program.debug.as_ref()?;
-    let source = debug.source.as_ref()?;
-    let source_lines = source.lines().collect::<Vec<_>>();
-    let mut first_offset_by_line = HashMap::<u32, u32>::new();
+    if debug.sources.iter().all(|source| source.text.is_none()) {
+        return None;
+    }
+    let mut first_offset_by_line = HashMap::<(u32, u32), u32>::new();
     for info in &debug.lines {
-        first_offset_by_line.entry(info.line).or_insert(info.offset);
+        first_offset_by_line
+            .entry((info.source_id, info.line))
+            .or_insert(info.offset);
     }
     let mut pairs = first_offset_by_line
         .into_iter()
-        .map(|(line, offset)| (offset, line))
+        .map(|(location, offset)| (offset, location))
         .collect::<Vec<_>>();
-    pairs.sort_by_key(|(offset, line)| (*offset, *line));
-
-    let mut annotations = BTreeMap::<usize, Vec<(u32, String)>>::new();
-    for (offset, line) in pairs {
-        let text = source_lines
-            .get(line.saturating_sub(1) as usize)
-            .copied()
-            .unwrap_or("<missing source line>")
-            .to_string();
+    pairs.sort_unstable();
+
+    let mut annotations = BTreeMap::<usize, Vec<(String, String)>>::new();
+    for (offset, (source_id, line)) in pairs {
+        let text = debug
+            .source_line_in(source_id, line)
+            .unwrap_or_else(|| "<missing source line>".to_string());
+        let label = match debug.source_name(source_id) {
+            Some(name) if source_id != MAIN_SOURCE_ID => format!("{name}:{line:04}"),
+            _ => format!("{line:04}"),
+        };
         annotations
             .entry(offset as usize)
             .or_default()
-            .push((line, text));
+            .push((label, text));
     }
     Some(annotations)
 }
@@ -601,18 +777,24 @@ fn write_debug_info(out: &mut Vec<u8>, debug: Option<&DebugInfo>) -> Result<(),
         Some(debug) => {
             out.push(1);
 
-            match &debug.source {
-                None => out.push(0),
-                Some(source) => {
-                    out.push(1);
-                    write_string("debug source", source, out)?;
+            write_u32_count("debug sources", debug.sources.len(), out)?;
+            for source in &debug.sources {
+                write_string("debug source name", &source.name, out)?;
+                match &source.text {
+                    None => out.push(0),
+                    Some(text) => {
+                        out.push(1);
+                        write_string("debug source", text, out)?;
+                    }
                 }
             }
 
             write_u32_count("debug lines", debug.lines.len(), out)?;
             for line in &debug.lines {
                 out.extend_from_slice(&line.offset.to_le_bytes());
+                out.extend_from_slice(&line.source_id.to_le_bytes());
                 out.extend_from_slice(&line.line.to_le_bytes());
+                out.extend_from_slice(&line.column.to_le_bytes());
             }
 
             write_u32_count("debug functions", debug.functions.len(), out)?;
@@ -631,6 +813,27 @@ fn write_debug_info(out: &mut Vec<u8>, debug: Option<&DebugInfo>) -> Result<(),
                 out.push(local.index);
             }
 
+            write_u32_count("debug inline frames", debug.inline_frames.len(), out)?;
+            for frame in &debug.inline_frames {
+                out.extend_from_slice(&frame.start.to_le_bytes());
+                out.extend_from_slice(&frame.end.to_le_bytes());
+                write_string("debug inline function", &frame.function, out)?;
+                out.extend_from_slice(&frame.call_source.to_le_bytes());
+                out.extend_from_slice(&frame.call_line.to_le_bytes());
+                match frame.parent {
+                    None => out.push(0),
+                    Some(parent) => {
+                        out.push(1);
+                        out.extend_from_slice(&parent.to_le_bytes());
+                    }
+                }
+                write_u32_count("debug inline locals", frame.locals.len(), out)?;
+                for local in &frame.locals {
+                    write_string("debug inline local name", &local.name, out)?;
+                    out.push(local.index);
+                }
+            }
+
             Ok(())
         }
     }
@@ -641,21 +844,12 @@ fn read_debug_info(cursor: &mut Cursor<'_>, version: u16) -> Result<Option<Debug
     match flag {
         0 => Ok(None),
         1 => {
-            let source = match cursor.read_u8()? {
-                0 => None,
-                1 => Some(cursor.read_string()?),
-                other => return Err(WireError::InvalidDebugFlag(other)),
+            let (sources, lines) = if version >= VERSION_V5 {
+                read_debug_sources_and_lines(cursor)?
+            } else {
+                read_legacy_debug_source_and_lines(cursor)?
             };
 
-            let line_count = cursor.read_u32()? as usize;
-            let mut lines = Vec::with_capacity(line_count);
-            for _ in 0..line_count {
-                lines.push(LineInfo {
-                    offset: cursor.read_u32()?,
-                    line: cursor.read_u32()?,
-                });
-            }
-
             let function_count = cursor.read_u32()? as usize;
             let mut functions = Vec::with_capacity(function_count);
             for _ in 0..function_count {
@@ -685,17 +879,113 @@ fn read_debug_info(cursor: &mut Cursor<'_>, version: u16) -> Result<Option<Debug
                 Vec::new()
             };
 
+            let inline_frames = if version >= VERSION_V6 {
+                read_debug_inline_frames(cursor)?
+            } else {
+                Vec::new()
+            };
+
             Ok(Some(DebugInfo {
-                source,
+                sources,
                 lines,
                 functions,
                 locals,
+                inline_frames,
             }))
         }
         other => Err(WireError::InvalidDebugFlag(other)),
     }
 }
 
+fn read_debug_inline_frames(cursor: &mut Cursor<'_>) -> Result<Vec<InlineFrame>, WireError> {
+    let frame_count = cursor.read_u32()? as usize;
+    let mut frames = Vec::with_capacity(frame_count);
+    for _ in 0..frame_count {
+        let start = cursor.read_u32()?;
+        let end = cursor.read_u32()?;
+        let function = cursor.read_string()?;
+        let call_source = cursor.read_u32()?;
+        let call_line = cursor.read_u32()?;
+        let parent = match cursor.read_u8()? {
+            0 => None,
+            1 => Some(cursor.read_u32()?),
+            other => return Err(WireError::InvalidDebugFlag(other)),
+        };
+        let local_count = cursor.read_u32()? as usize;
+        let mut locals = Vec::with_capacity(local_count);
+        for _ in 0..local_count {
+            locals.push(LocalInfo {
+                name: cursor.read_string()?,
+                index: cursor.read_u8()?,
+            });
+        }
+        frames.push(InlineFrame {
+            start,
+            end,
+            function,
+            call_source,
+            call_line,
+            parent,
+            locals,
+        });
+    }
+    Ok(frames)
+}
+
+fn read_debug_sources_and_lines(
+    cursor: &mut Cursor<'_>,
+) -> Result<(Vec<DebugSource>, Vec<LineInfo>), WireError> {
+    let source_count = cursor.read_u32()? as usize;
+    let mut sources = Vec::with_capacity(source_count);
+    for _ in 0..source_count {
+        let name = cursor.read_string()?;
+        let text = match cursor.read_u8()? {
+            0 => None,
+            1 => Some(cursor.read_string()?),
+            other => return Err(WireError::InvalidDebugFlag(other)),
+        };
+        sources.push(DebugSource { name, text });
+    }
+
+    let line_count = cursor.read_u32()? as usize;
+    let mut lines = Vec::with_capacity(line_count);
+    for _ in 0..line_count {
+        lines.push(LineInfo {
+            offset: cursor.read_u32()?,
+            source_id: cursor.read_u32()?,
+            line: cursor.read_u32()?,
+            column: cursor.read_u32()?,
+        });
+    }
+    Ok((sources, lines))
+}
+
+/// Versions before 5 hold one optional source that every line belongs to.
+fn read_legacy_debug_source_and_lines(
+    cursor: &mut Cursor<'_>,
+) -> Result<(Vec<DebugSource>, Vec<LineInfo>), WireError> {
+    let sources = match cursor.read_u8()? {
+        0 => Vec::new(),
+        1 => vec![DebugSource {
+            name: "<source>".to_string(),
+            text: Some(cursor.read_string()?),
+        }],
+        other => return Err(WireError::InvalidDebugFlag(other)),
+    };
+
+    let line_count = cursor.read_u32()? as usize;
+    let mut lines = Vec::with_capacity(line_count);
+    for _ in 0..line_count {
+        lines.push(LineInfo {
+            offset: cursor.read_u32()?,
+            source_id: MAIN_SOURCE_ID,
+            line: cursor.read_u32()?,
+            column: 0,
+        });
+    }
+    Ok((sources, lines))
+}
+
 fn write_string(field: &'static str, value: &str, out: &mut Vec<u8>) -> Result<(), WireError> {
     write_u32_len(field, value.len(), out)?;
     out.extend_from_slice(value.as_bytes());
@@ -820,3 +1110,57 @@ fn format_call_target(program: &Program, index: u16, argc: u8) -> Option<String>
         .get(index as usize)
         .map(|import| format!("import {}/{} (argc={argc})", import.name, import.arity))
 }
+
+fn format_byte_directive(bytes: &[u8]) -> String {
+    let mut out = ".byte".to_string();
+    for byte in bytes {
+        let _ = write!(&mut out, " 0x{byte:02X}");
+    }
+    out
+}
+
+/// Literal for `.const`, or `None` for values the format has no syntax for.
+fn format_asm_literal(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => Some("null".to_string()),
+        Value::Int(value) => Some(value.to_string()),
+        // Debug formatting keeps a `.` or exponent, so the literal reads back
+        // as a float.
+        Value::Float(value) => Some(format!("{value:?}")),
+        Value::Bool(value) => Some(value.to_string()),
+        Value::String(text) => Some(format_asm_string(text)),
+        Value::Array(_) | Value::Map(_) => None,
+    }
+}
+
+fn format_asm_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for ch in text.chars() {
+        match ch {
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\0' => out.push_str("\\0"),
+            other => out.push(other),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// A name written bare, or quoted when it would not read back as one token.
+fn format_asm_name(name: &str) -> String {
+    let bare = !name.is_empty()
+        && !name.contains("//")
+        && !name
+            .chars()
+            .any(|ch| ch.is_whitespace() || matches!(ch, '"' | ';' | '#' | '@'));
+    if bare {
+        name.to_string()
+    } else {
+        format_asm_string(name)
+    }
+}
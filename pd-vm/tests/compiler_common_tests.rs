@@ -805,6 +805,177 @@ fn compile_source_file_supports_rss_modules_from_js_lua_and_scheme() {
     let _ = std::fs::remove_dir(root);
 }
 
+fn run_with_print(compiled: vm::CompiledProgram) -> Vec<Value> {
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    for func in &compiled.functions {
+        match func.name.as_str() {
+            "print" => vm.register_function(Box::new(PrintBuiltin)),
+            _ => panic!("unexpected function {}", func.name),
+        };
+    }
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    vm.stack().to_vec()
+}
+
+#[test]
+fn std_modules_are_embedded_for_every_flavor() {
+    let cases = [
+        (
+            SourceFlavor::RustScript,
+            "use std::strings;\nprint(trim(\"  hi  \"));\n",
+        ),
+        (
+            SourceFlavor::RustScript,
+            "use std::math::{max};\nprint(max(3, 7));\n",
+        ),
+        (
+            SourceFlavor::JavaScript,
+            "import { trim } from \"std/strings\";\nconsole.log(trim(\"  hi  \"));\n",
+        ),
+        (
+            SourceFlavor::Lua,
+            "local _s = require(\"std.strings\")\nprint(trim(\"  hi  \"))\n",
+        ),
+        (
+            SourceFlavor::Scheme,
+            "(import (std strings))\n(print (trim \"  hi  \"))\n",
+        ),
+        (
+            SourceFlavor::Scheme,
+            "(import (only (std math) max))\n(print (max 3 7))\n",
+        ),
+    ];
+    for (flavor, source) in cases {
+        let compiled = compile_source_with_flavor(source, flavor)
+            .unwrap_or_else(|err| panic!("{flavor:?} std import should compile: {err}"));
+        let stack = run_with_print(compiled);
+        let expected = if source.contains("max") {
+            Value::Int(7)
+        } else {
+            Value::String("hi".to_string())
+        };
+        assert_eq!(stack, vec![expected], "{flavor:?}: {source}");
+    }
+}
+
+#[test]
+fn source_text_imports_only_resolve_std_modules() {
+    let err = match compile_source("use helpers;\n1;\n") {
+        Ok(_) => panic!("relative import without a file should fail"),
+        Err(err) => err,
+    };
+    assert!(
+        err.to_string()
+            .contains("cannot resolve module 'helpers.rss' without a source file"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn compile_source_file_searches_module_paths_after_the_importing_directory() {
+    let unique = format!(
+        "vm_module_search_path_test_{}_{}",
+        std::process::id(),
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("clock should be valid")
+            .as_nanos()
+    );
+    let root = std::env::temp_dir().join(unique);
+    let app = root.join("app");
+    let lib = root.join("lib");
+    std::fs::create_dir_all(&app).expect("temp app dir should be created");
+    std::fs::create_dir_all(&lib).expect("temp lib dir should be created");
+    let main_path = app.join("main.rss");
+    let lib_module_path = lib.join("helpers.rss");
+    std::fs::write(&main_path, "use helpers;\nprint(double(21));\n")
+        .expect("main source should write");
+    std::fs::write(&lib_module_path, "pub fn double(x) { x * 2; }\n")
+        .expect("lib module should write");
+
+    let err = match compile_source_file(&main_path) {
+        Ok(_) => panic!("module outside the search paths should not resolve"),
+        Err(err) => err,
+    };
+    assert!(matches!(err, vm::SourcePathError::Io(_)), "{err:?}");
+
+    let compiled = vm::compile_source_file_with_search_paths(&main_path, vec![lib.clone()])
+        .expect("module on the search path should resolve");
+    assert_eq!(run_with_print(compiled), vec![Value::Int(42)]);
+
+    // A module next to the importing file wins over the search paths.
+    let local_module_path = app.join("helpers.rss");
+    std::fs::write(&local_module_path, "pub fn double(x) { x + x + 1; }\n")
+        .expect("local module should write");
+    let compiled = vm::compile_source_file_with_search_paths(&main_path, vec![lib.clone()])
+        .expect("local module should resolve");
+    assert_eq!(run_with_print(compiled), vec![Value::Int(43)]);
+
+    let _ = std::fs::remove_dir_all(root);
+}
+
+#[test]
+fn compile_source_file_records_module_sources_in_debug_info() {
+    let unique = format!(
+        "vm_debug_sources_test_{}_{}",
+        std::process::id(),
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("clock should be valid")
+            .as_nanos()
+    );
+    let root = std::env::temp_dir().join(unique);
+    std::fs::create_dir_all(&root).expect("temp dir should be created");
+    let main_path = root.join("main.rss");
+    std::fs::write(
+        &main_path,
+        "use helpers;\n\nlet y = double(21);\nprint(y);\n",
+    )
+    .expect("main source should write");
+    std::fs::write(
+        root.join("helpers.rss"),
+        "pub fn double(x) {\n    let twice = x * 2;\n    twice;\n}\n",
+    )
+    .expect("module should write");
+
+    let compiled = compile_source_file(&main_path);
+    let _ = std::fs::remove_dir_all(&root);
+    let compiled = compiled.expect("module import should compile");
+    let debug = compiled.program.debug.as_ref().expect("debug info");
+
+    assert_eq!(debug.sources.len(), 2);
+    assert!(debug.sources[0].name.ends_with("main.rss"));
+    assert!(
+        debug.sources[0]
+            .text
+            .as_deref()
+            .is_some_and(|text| text.starts_with("use helpers;"))
+    );
+    let module_id = debug.find_source("helpers.rss").expect("module source");
+    assert_eq!(module_id, 1);
+    // Root lines are not shifted by the import prelude, and inlined module
+    // code carries the module's own lines and columns.
+    assert!(
+        debug
+            .lines
+            .iter()
+            .any(|line| line.source_id == vm::MAIN_SOURCE_ID && line.line == 3)
+    );
+    assert!(
+        debug
+            .lines
+            .iter()
+            .any(|line| line.source_id == module_id && line.line == 2 && line.column == 5)
+    );
+    assert!(!debug.offsets_for_source_line(module_id, 3).is_empty());
+
+    let bytes = vm::encode_program(&compiled.program).expect("program should encode");
+    let decoded = vm::decode_program(&bytes).expect("program should decode");
+    assert_eq!(decoded.debug, compiled.program.debug);
+    assert_eq!(run_with_print(compiled), vec![Value::Int(42)]);
+}
+
 #[test]
 fn compile_source_file_rejects_import_cycles() {
     let unique = format!(
@@ -877,3 +1048,49 @@ fn compile_source_emits_named_locals_in_debug_info() {
     assert_eq!(debug.local_index("alpha"), Some(0));
     assert_eq!(debug.local_index("beta"), Some(1));
 }
+
+#[test]
+fn non_ascii_string_literals_survive_frontend_lowering() {
+    let cases = [
+        (SourceFlavor::RustScript, r#"let s = "héllo → wörld"; s;"#),
+        (SourceFlavor::JavaScript, r#"const s = "héllo → wörld"; s;"#),
+        (SourceFlavor::Lua, "local s = \"héllo → wörld\"\ns"),
+        (SourceFlavor::Scheme, r#"(define s "héllo → wörld") s"#),
+    ];
+    for (flavor, source) in cases {
+        let compiled = compile_source_with_flavor(source, flavor).expect("compile should succeed");
+        let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+        let status = vm.run().expect("vm should run");
+        assert_eq!(status, VmStatus::Halted);
+        assert_eq!(
+            vm.stack(),
+            &[Value::String("héllo → wörld".to_string())],
+            "{flavor:?}"
+        );
+    }
+}
+
+#[test]
+fn assembly_flavor_assembles_source_and_binds_its_imports() {
+    let source = ".import add_one 1\n.local x\n    ldc 41\n    call 0 1\n    stloc x\n    ldloc x\n    ret\n";
+
+    let compiled = compile_source_with_flavor(source, SourceFlavor::Assembly)
+        .expect("assemble should succeed");
+    assert_eq!(compiled.locals, 1);
+    assert_eq!(compiled.functions.len(), 1);
+    assert_eq!(compiled.functions[0].name, "add_one");
+
+    let mut vm = compiled.into_vm();
+    vm.bind_function("add_one", Box::new(AddOne));
+    assert_eq!(vm.run().expect("vm should run"), VmStatus::Halted);
+    assert_eq!(vm.stack(), &[Value::Int(42)]);
+
+    let err = match compile_source_with_flavor("    ldc 1\n    jump 3\n", SourceFlavor::Assembly) {
+        Ok(_) => panic!("unknown opcode should fail"),
+        Err(err) => err,
+    };
+    assert!(
+        err.to_string().contains("line 2: unknown opcode 'jump'"),
+        "{err}"
+    );
+}
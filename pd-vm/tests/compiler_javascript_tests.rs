@@ -120,21 +120,26 @@ fn javascript_empty_param_arrow_closure_is_supported() {
 }
 
 #[test]
-fn javascript_block_body_arrow_closure_is_rejected() {
+fn javascript_block_body_arrow_closures_are_supported() {
     let source = r#"
-        let inc = (value) => { value + 1; };
+        let inc = (value) => {
+            let next = value + 1;
+            if (next > 100) {
+                return 100;
+            }
+            return next;
+        };
+        let noop = () => {};
+        noop();
         inc(41);
     "#;
-    let err = match compile_source_with_flavor(source, SourceFlavor::JavaScript) {
-        Ok(_) => panic!("block-body arrow closure should fail in this subset"),
-        Err(err) => err,
-    };
-    match err {
-        vm::SourceError::Parse(parse) => {
-            assert!(parse.message.contains("block bodies are not supported"));
-        }
-        other => panic!("unexpected error: {other}"),
-    }
+    let compiled = compile_source_with_flavor(source, SourceFlavor::JavaScript)
+        .expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(vm.stack(), &[Value::Null, Value::Int(42)]);
 }
 
 #[test]
@@ -385,3 +390,474 @@ fn javascript_direct_builtin_len_call_is_rejected() {
         other => panic!("unexpected error: {other}"),
     }
 }
+
+#[test]
+fn javascript_array_sort_and_splice_are_lowered_to_native_builtins() {
+    let source = r#"
+        let names = ["cy", "al", "bo"];
+        names.sort();
+        let nums = [3, 10, 1, 2];
+        nums.sort((a, b) => b - a);
+        nums.splice(1, 1);
+        nums.splice(0, 0, 7);
+        nums.splice(1, 2, 8, 9);
+        [names, nums];
+    "#;
+
+    let compiled = compile_source_with_flavor(source, SourceFlavor::JavaScript)
+        .expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Array(vec![
+                Value::String("al".to_string()),
+                Value::String("bo".to_string()),
+                Value::String("cy".to_string()),
+            ]),
+            Value::Array(vec![
+                Value::Int(7),
+                Value::Int(8),
+                Value::Int(9),
+                Value::Int(1)
+            ]),
+        ])]
+    );
+}
+
+#[test]
+fn javascript_template_literals_lower_to_format() {
+    let source = r#"
+        const name = "edge";
+        const count = 2;
+        `${name} saw ${count + 1} {requests}`;
+    "#;
+
+    let compiled = compile_source_with_flavor(source, SourceFlavor::JavaScript)
+        .expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::String("edge saw 3 {requests}".to_string())]
+    );
+}
+
+#[test]
+fn javascript_return_exits_function_from_loops() {
+    let source = r#"
+        function firstOver(values, limit) {
+          for (let i = 0; i < values.length; i = i + 1) {
+            if (values[i] > limit) {
+              return values[i]
+            }
+          }
+          return
+        }
+        [firstOver([1, 5, 9], 4), firstOver([1], 4)];
+    "#;
+    let compiled = compile_source_with_flavor(source, SourceFlavor::JavaScript)
+        .expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![Value::Int(5), Value::Null])]
+    );
+}
+
+#[test]
+fn javascript_for_of_and_for_in_iterate_natively() {
+    let source = r#"
+        const headers = { host: "a.com", accept: "json" };
+        let out = [];
+        for (const [k, v] of Object.entries(headers)) {
+            out[out.length] = k + "=" + v;
+        }
+        let keys = [];
+        for (const k in headers) {
+            keys[keys.length] = k;
+        }
+        for (let k of Object.keys(headers)) {
+            keys[keys.length] = k;
+        }
+        let values = [];
+        for (const v of Object.values(headers)) {
+            values[values.length] = v;
+        }
+        let total = 0;
+        for (const x of [1, 2, 3]) {
+            total = total + x;
+        }
+        [out, keys, values, total];
+    "#;
+    let compiled = compile_source_with_flavor(source, SourceFlavor::JavaScript)
+        .expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    let strings = |items: &[&str]| {
+        Value::Array(
+            items
+                .iter()
+                .map(|item| Value::String(item.to_string()))
+                .collect(),
+        )
+    };
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            strings(&["host=a.com", "accept=json"]),
+            strings(&["host", "accept", "host", "accept"]),
+            strings(&["a.com", "json"]),
+            Value::Int(6),
+        ])]
+    );
+}
+
+#[test]
+fn javascript_multiline_constructs_are_parsed() {
+    let source = r#"
+        const config = {
+            name: "edge",
+            limits: [
+                1,
+                2,
+            ],
+        };
+        const label = `${config.name}:
+${config.limits.length}`;
+        const total = config.limits[0] +
+            config.limits[1];
+        let picked = total >= 3
+            ? "big"
+            : "small";
+        [label, total, picked];
+    "#;
+    let compiled = compile_source_with_flavor(source, SourceFlavor::JavaScript)
+        .expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::String("edge:\n2".to_string()),
+            Value::Int(3),
+            Value::String("big".to_string()),
+        ])]
+    );
+}
+
+#[test]
+fn javascript_keywords_inside_strings_are_left_alone() {
+    let source = r#"
+        const text = "function typeof const => console.log(x)";
+        const other = 'len(x) ${not_a_template}';
+        // console.log("in a comment");
+        /* typeof len(x) */
+        text + " | " + other;
+    "#;
+    let compiled = compile_source_with_flavor(source, SourceFlavor::JavaScript)
+        .expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::String(
+            "function typeof const => console.log(x) | len(x) ${not_a_template}".to_string()
+        )]
+    );
+}
+
+#[test]
+fn javascript_parse_errors_point_at_the_offending_token() {
+    let source = "let a = 1;\nlet b = a +* 2;\n";
+    let err = match compile_source_with_flavor(source, SourceFlavor::JavaScript) {
+        Ok(_) => panic!("malformed expression should fail"),
+        Err(err) => err,
+    };
+    match err {
+        vm::SourceError::Parse(parse) => {
+            assert_eq!(parse.line, 2);
+            let span = parse.span.expect("parse error should carry a span");
+            assert_eq!(&source[span.lo..span.hi], "*");
+        }
+        other => panic!("unexpected error: {other}"),
+    }
+
+    let unsupported = "let x = 1;\nswitch (x) {}\n";
+    let err = match compile_source_with_flavor(unsupported, SourceFlavor::JavaScript) {
+        Ok(_) => panic!("switch statements should fail"),
+        Err(err) => err,
+    };
+    match err {
+        vm::SourceError::Parse(parse) => {
+            assert_eq!(parse.line, 2);
+            assert!(
+                parse
+                    .message
+                    .contains("'switch' statements are not supported")
+            );
+            let span = parse.span.expect("parse error should carry a span");
+            assert_eq!(&unsupported[span.lo..span.hi], "switch");
+        }
+        other => panic!("unexpected error: {other}"),
+    }
+}
+
+#[test]
+fn javascript_destructuring_binds_defaults_nesting_and_rest() {
+    let source = r#"
+        const point = { x: 1, y: 2, z: 3, inner: { deep: [7, 8] } };
+        const { x, y: why, w = 10, inner: { deep: [d0, d1] }, ...others } = point;
+        const [first, , third = 99, fourth = 4, ...tail] = [1, 2, 3, 5, 6, 7];
+        let a = 1;
+        let b = 2;
+        [a, b] = [b, a];
+        let pairs = [];
+        for (const [n, label] of [[1, "a"], [2, "b"]]) {
+            pairs.push(`${n}${label}`);
+        }
+        [x, why, w, d0 + d1, others, [first, third, fourth, tail], [a, b], pairs];
+    "#;
+    let compiled = compile_source_with_flavor(source, SourceFlavor::JavaScript)
+        .expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Int(1),
+            Value::Int(2),
+            Value::Int(10),
+            Value::Int(15),
+            Value::Map(vec![(Value::String("z".to_string()), Value::Int(3))]),
+            Value::Array(vec![
+                Value::Int(1),
+                Value::Int(3),
+                Value::Int(5),
+                Value::Array(vec![Value::Int(6), Value::Int(7)]),
+            ]),
+            Value::Array(vec![Value::Int(2), Value::Int(1)]),
+            Value::Array(vec![
+                Value::String("1a".to_string()),
+                Value::String("2b".to_string()),
+            ]),
+        ])]
+    );
+}
+
+#[test]
+fn javascript_spread_rest_parameters_and_shorthand_properties() {
+    let source = r#"
+        function sum(first, ...rest) {
+            let total = first;
+            for (const value of rest) {
+                total += value;
+            }
+            return total;
+        }
+        function greet({ name, greeting = "hi" }, suffix = "!") {
+            return `${greeting} ${name}${suffix}`;
+        }
+        const xs = [2, 3];
+        const name = "bob";
+        const base = { a: 1, b: 2 };
+        const merged = { ...base, b: 20, name };
+        [
+            [1, ...xs, 4, ...xs],
+            sum(1, 2, 3, 4),
+            sum(...xs),
+            sum(10),
+            greet({ name }),
+            merged.a + merged.b,
+            merged.name,
+        ];
+    "#;
+    let compiled = compile_source_with_flavor(source, SourceFlavor::JavaScript)
+        .expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Array(vec![
+                Value::Int(1),
+                Value::Int(2),
+                Value::Int(3),
+                Value::Int(4),
+                Value::Int(2),
+                Value::Int(3),
+            ]),
+            Value::Int(10),
+            Value::Int(5),
+            Value::Int(10),
+            Value::String("hi bob!".to_string()),
+            Value::Int(21),
+            Value::String("bob".to_string()),
+        ])]
+    );
+}
+
+#[test]
+fn javascript_spread_into_host_calls_is_rejected() {
+    let err = match compile_source_with_flavor("console.log(...[1]);", SourceFlavor::JavaScript) {
+        Ok(_) => panic!("spread into a host call should fail"),
+        Err(err) => err,
+    };
+    assert!(
+        err.to_string()
+            .contains("spread arguments are only supported when calling functions declared"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn javascript_classes_dispatch_methods_getters_and_super() {
+    let source = r#"
+        class Shape {
+            constructor(name) {
+                this.name = name;
+                this.calls = 0;
+            }
+            describe() {
+                this.calls += 1;
+                return `${this.name}:${this.area}`;
+            }
+            get area() {
+                return 0;
+            }
+            static unit() {
+                return new Square(1);
+            }
+        }
+        class Square extends Shape {
+            constructor(side) {
+                super("square");
+                this.side = side;
+            }
+            get area() {
+                return this.side * this.side;
+            }
+            grow(by = 1) {
+                this.side += by;
+                return this.side;
+            }
+            describe() {
+                const base = super.describe();
+                return `[${base}]`;
+            }
+        }
+        class Circle extends Shape {}
+        const shapes = [new Square(3), new Circle("circle")];
+        let labels = [];
+        for (const shape of shapes) {
+            labels.push(shape.describe());
+        }
+        const square = shapes[0];
+        square.grow();
+        const side = square.grow(2);
+        const described = square.describe();
+        [labels, side, square.area, square.calls, Shape.unit().area, described];
+    "#;
+    let compiled = compile_source_with_flavor(source, SourceFlavor::JavaScript)
+        .expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Array(vec![
+                Value::String("[square:9]".to_string()),
+                Value::String("circle:0".to_string()),
+            ]),
+            Value::Int(6),
+            Value::Int(36),
+            Value::Int(1),
+            Value::Int(1),
+            Value::String("[square:36]".to_string()),
+        ])]
+    );
+}
+
+#[test]
+fn javascript_class_methods_write_back_this_and_bind_it_in_arrows() {
+    let source = r#"
+        class Counter {
+            constructor(start = 0) {
+                this.count = start;
+                this.log = [];
+            }
+            tick() {
+                this.count++;
+                this.log.push(this.count);
+                return this.count;
+            }
+            scaled(factor) {
+                const scale = (value) => value * factor + this.count;
+                return scale(2);
+            }
+            twice() {
+                this.tick();
+                return this.tick();
+            }
+        }
+        const counter = new Counter();
+        const first = counter.tick();
+        let second = 0;
+        second = counter.twice();
+        const holder = { c: new Counter(10) };
+        holder.c.tick();
+        [first, second, counter.log, counter.scaled(5), holder.c.count, Object.keys(counter)];
+    "#;
+    let compiled = compile_source_with_flavor(source, SourceFlavor::JavaScript)
+        .expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Int(1),
+            Value::Int(3),
+            Value::Array(vec![Value::Int(1), Value::Int(2), Value::Int(3)]),
+            Value::Int(13),
+            Value::Int(11),
+            Value::Array(vec![
+                Value::String("count".to_string()),
+                Value::String("log".to_string()),
+            ]),
+        ])]
+    );
+}
+
+#[test]
+fn javascript_this_outside_class_members_is_rejected() {
+    let source = "function f() {\n    return this;\n}\nf();\n";
+    let err = match compile_source_with_flavor(source, SourceFlavor::JavaScript) {
+        Ok(_) => panic!("'this' in a plain function should fail"),
+        Err(err) => err,
+    };
+    match err {
+        vm::SourceError::Parse(parse) => {
+            assert_eq!(parse.line, 2);
+            assert!(parse.message.contains("'this' is only available"));
+        }
+        other => panic!("unexpected error: {other}"),
+    }
+}
@@ -118,6 +118,42 @@ fn lua_function_literal_with_empty_parameter_list_is_supported() {
     assert_eq!(vm.stack(), &[Value::Int(42)]);
 }
 
+#[test]
+fn lua_statement_bodied_function_literals_lower_like_local_functions() {
+    let source = r#"
+        local g = function(x) if x then return 1 end return 2 end
+        local add = function(a, b) local s = a + b return s end
+        local pick = function(x) if x > 2 then return "big" elseif x > 0 then return "small" else return "none" end end
+        local count = function(n)
+            local c = 0
+            while c < n do
+                c = c + 1
+            end
+            return c
+        end
+        {g(true), g(false), add(1, 2), pick(3), pick(1), pick(0), count(5)}
+    "#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Lua).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    let text = |value: &str| Value::String(value.to_string());
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Int(1),
+            Value::Int(2),
+            Value::Int(3),
+            text("big"),
+            text("small"),
+            text("none"),
+            Value::Int(5),
+        ])]
+    );
+}
+
 #[test]
 fn lua_numeric_for_loop_with_negative_step_is_rejected() {
     let source = r#"
@@ -451,19 +487,106 @@ fn lua_string_method_calls_and_length_operator_are_lowered() {
 }
 
 #[test]
-fn lua_string_pattern_methods_are_rejected() {
+fn lua_string_pattern_api_lowers_to_pattern_builtins() {
     let source = r##"
         local s = "v1 id=2048 done"
-        s:find("%d+")
+        local start = s:find("%d+", 4)
+        local plain = string.find("a.b", ".", 1, true)
+        local id = s:match("id=(%d+)")
+        local missing = string.match(s, "^done")
+        local masked = s:gsub("%d", "#")
+        local greeting = string.gsub("héllo wörld", "(%w+)", "<%1>")
+        local vars = {name = "lua", version = "5.4"}
+        local expanded = string.gsub("$name is $version", "%$(%w+)", vars)
+        local words = {}
+        for word in s:gmatch("%a+") do
+            words[#words] = word
+        end
+        local pairs_text = ""
+        for k, v in string.gmatch("a=1, b=2", "(%w+)=(%w+)") do
+            pairs_text = pairs_text .. k .. ":" .. v .. ";"
+        end
+        {start, plain, id, missing == nil, masked, greeting, expanded, words, pairs_text}
     "##;
 
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Lua).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    let text = |value: &str| Value::String(value.to_string());
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Int(7),
+            Value::Int(2),
+            text("2048"),
+            Value::Bool(true),
+            text("v# id=#### done"),
+            text("<h>é<llo> <w>ö<rld>"),
+            text("lua is 5.4"),
+            Value::Map(vec![
+                (Value::Int(0), text("v")),
+                (Value::Int(1), text("id")),
+                (Value::Int(2), text("done")),
+            ]),
+            text("a:1;b:2;"),
+        ])]
+    );
+}
+
+#[test]
+fn lua_malformed_patterns_fail_at_runtime() {
+    let cases = [
+        ("(.", "unfinished capture"),
+        (".)", "invalid pattern capture"),
+        ("[a", "malformed pattern (missing ']')"),
+        ("[]", "malformed pattern (missing ']')"),
+        ("[^]", "malformed pattern (missing ']')"),
+        ("[a%]", "malformed pattern (missing ']')"),
+        ("%b", "missing arguments to '%b'"),
+        ("%ba", "missing arguments to '%b'"),
+        ("%", "malformed pattern (ends with '%')"),
+        ("%f", "missing '[' after '%f' in pattern"),
+        ("(%1)", "invalid capture index %1"),
+    ];
+    for (pattern, message) in cases {
+        let source = format!("string.find(\"a\", \"{pattern}\")");
+        let compiled =
+            compile_source_with_flavor(&source, SourceFlavor::Lua).expect("compile should succeed");
+        let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+        let err = vm.run().expect_err("malformed pattern should fail");
+        assert!(
+            err.to_string().contains(message),
+            "pattern {pattern:?}: unexpected error: {err}"
+        );
+    }
+
+    let source = r#"string.gsub("alo", ".", "%x")"#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Lua).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let err = vm.run().expect_err("bad replacement should fail");
+    assert!(
+        err.to_string()
+            .contains("invalid use of '%' in replacement string"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn lua_gmatch_outside_generic_for_is_rejected() {
+    let source = r#"
+        local s = "a b"
+        local it = s:gmatch("%a")
+    "#;
     let err = match compile_source_with_flavor(source, SourceFlavor::Lua) {
-        Ok(_) => panic!("lua pattern methods should fail in this subset"),
+        Ok(_) => panic!("gmatch outside a generic for should fail"),
         Err(err) => err,
     };
     match err {
         vm::SourceError::Parse(parse) => {
-            assert!(parse.message.contains("Lua pattern API"));
+            assert!(parse.message.contains("generic for loop"));
         }
         other => panic!("unexpected error: {other}"),
     }
@@ -576,3 +699,479 @@ fn lua_pairs_and_ipairs_support_single_loop_variable() {
     assert_eq!(status, VmStatus::Halted);
     assert_eq!(vm.stack(), &[Value::Int(42)]);
 }
+
+#[test]
+fn lua_table_sort_remove_and_insert_are_supported() {
+    let source = r#"
+        local values = {4, 1, 3, 2}
+        table.sort(values)
+        local desc = {4, 1, 3, 2}
+        table.sort(desc, function(a, b) return a > b end)
+        table.remove(desc)
+        table.remove(desc, 0)
+        table.insert(desc, 5)
+        table.insert(desc, 0, 6)
+        {values, desc}
+    "#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Lua).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Array(vec![
+                Value::Int(1),
+                Value::Int(2),
+                Value::Int(3),
+                Value::Int(4)
+            ]),
+            Value::Array(vec![
+                Value::Int(6),
+                Value::Int(3),
+                Value::Int(2),
+                Value::Int(5)
+            ]),
+        ])]
+    );
+}
+
+#[test]
+fn lua_comparator_sort_links_std_sort_by_beside_same_named_functions() {
+    let source = r#"
+        local function sort_by(t)
+            return #t
+        end
+        local function merge(a, b)
+            return a + b
+        end
+        local values = {3, 1, 2}
+        table.sort(values, function(a, b) return a > b end)
+        {values, sort_by(values), merge(1, 2)}
+    "#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Lua).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Array(vec![Value::Int(3), Value::Int(2), Value::Int(1)]),
+            Value::Int(3),
+            Value::Int(3),
+        ])]
+    );
+}
+
+#[test]
+fn lua_string_format_lowers_to_format_builtin() {
+    let source = r#"
+        local name = "edge"
+        string.format("%s:%5.1f|%-3d|%03d|%q 100%%", name, 2.25, 7, 5, "x")
+    "#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Lua).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::String("edge:  2.2|7  |005|\"x\" 100%".to_string())]
+    );
+}
+
+#[test]
+fn lua_string_format_argument_count_is_checked_at_compile_time() {
+    let source = r#"
+        string.format("%d of %d", 1)
+    "#;
+    let err = match compile_source_with_flavor(source, SourceFlavor::Lua) {
+        Ok(_) => panic!("mismatched string.format should fail to compile"),
+        Err(err) => err,
+    };
+    assert!(
+        err.to_string()
+            .contains("expects 2 positional argument(s) but 1 were supplied"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn lua_return_exits_function_from_nested_blocks() {
+    let source = r#"
+        local function find(t, target)
+          for i = 0, #t - 1 do
+            if t[i] == target then
+              return i
+            end
+          end
+          return -1
+        end
+        local function grade(score)
+          if score > 89 then
+            return "A"
+          elseif score > 69 then
+            return "B"
+          end
+          return "C"
+        end
+        return {find({4, 5, 6}, 6), find({4}, 7), grade(95), grade(70), grade(10)}
+    "#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Lua).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Int(2),
+            Value::Int(-1),
+            Value::String("A".to_string()),
+            Value::String("B".to_string()),
+            Value::String("C".to_string()),
+        ])]
+    );
+}
+
+#[test]
+fn lua_metatable_slot_is_hidden_only_from_the_lua_chunk() {
+    let unique = format!(
+        "vm_lua_metatable_module_test_{}_{}",
+        std::process::id(),
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("clock should be valid")
+            .as_nanos()
+    );
+    let root = std::env::temp_dir().join(unique);
+    std::fs::create_dir_all(&root).expect("temp module root should be created");
+
+    let module_path = root.join("sizes.rss");
+    std::fs::write(
+        &module_path,
+        r#"
+        pub fn size(t) {
+            t.length;
+        }
+    "#,
+    )
+    .expect("module source should write");
+
+    let main_path = root.join("main.lua");
+    std::fs::write(
+        &main_path,
+        r#"
+        local sizes = require("./sizes.rss")
+        local Base = {}
+        local t = setmetatable({a = 1, b = 2}, Base)
+        local count = 0
+        for k, v in pairs(t) do
+            count = count + 1
+        end
+        {count, sizes.size(t)}
+    "#,
+    )
+    .expect("lua source should write");
+
+    let compiled = compile_source_file(&main_path).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    // The RustScript module is not rewritten, so it counts the metatable slot.
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![Value::Int(2), Value::Int(3)])]
+    );
+
+    let _ = std::fs::remove_file(main_path);
+    let _ = std::fs::remove_file(module_path);
+    let _ = std::fs::remove_dir(root);
+}
+
+#[test]
+fn lua_metatable_classes_dispatch_methods_through_index() {
+    let source = r#"
+        local Account = {}
+        Account.__index = Account
+
+        function Account.new(owner, balance)
+          local self = setmetatable({}, Account)
+          self.owner = owner
+          self.balance = balance
+          return self
+        end
+
+        function Account:deposit(amount)
+          self.balance = self.balance + amount
+        end
+
+        function Account:withdraw(amount)
+          if amount > self.balance then
+            return false
+          end
+          self:deposit(-amount)
+          return true
+        end
+
+        function Account:describe()
+          return self.owner .. ": " .. tostring(self.balance)
+        end
+
+        local acc = Account.new("ann", 100)
+        acc:deposit(50)
+        local ok = acc:withdraw(30)
+        local denied = acc:withdraw(500)
+        return {ok, denied, acc:describe(), acc.balance, getmetatable(acc) == Account}
+    "#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Lua).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Bool(true),
+            Value::Bool(false),
+            Value::String("ann: 120".to_string()),
+            Value::Int(120),
+            Value::Bool(true),
+        ])]
+    );
+}
+
+#[test]
+fn lua_metatable_operators_dispatch_to_metamethods() {
+    let source = r#"
+        local Vector = {}
+        Vector.__index = Vector
+
+        function Vector.new(x, y)
+          return setmetatable({x = x, y = y}, Vector)
+        end
+        function Vector.__add(a, b)
+          return Vector.new(a.x + b.x, a.y + b.y)
+        end
+        function Vector.__unm(v)
+          return Vector.new(-v.x, -v.y)
+        end
+        function Vector.__eq(a, b)
+          return a.x == b.x and a.y == b.y
+        end
+        function Vector.__lt(a, b)
+          return a:length() < b:length()
+        end
+        function Vector.__tostring(v)
+          return "(" .. tostring(v.x) .. ", " .. tostring(v.y) .. ")"
+        end
+        function Vector:length()
+          return self.x * self.x + self.y * self.y
+        end
+
+        local a = Vector.new(1, 2)
+        local b = Vector.new(3, 4)
+        local sum = a + b
+        return {tostring(sum), tostring(-a), sum == Vector.new(4, 6), a < b, a > b, rawequal(a, b), 1 + 2}
+    "#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Lua).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::String("(4, 6)".to_string()),
+            Value::String("(-1, -2)".to_string()),
+            Value::Bool(true),
+            Value::Bool(true),
+            Value::Bool(false),
+            Value::Bool(false),
+            Value::Int(3),
+        ])]
+    );
+}
+
+#[test]
+fn lua_metatable_index_newindex_and_call_handlers_are_supported() {
+    let source = r#"
+        local Base = {}
+        Base.__index = Base
+        function Base:greet()
+          return "hello " .. self.name
+        end
+        local obj = setmetatable({name = "bob"}, {__index = Base})
+
+        local defaults = setmetatable({}, {__index = {color = "red"}})
+
+        local Proxy = {}
+        function Proxy.__index(t, k)
+          return "missing " .. k
+        end
+        local proxy = setmetatable({}, Proxy)
+
+        local Guard = {}
+        function Guard.__newindex(t, k, v)
+          rawset(t, k, v * 10)
+        end
+        local guarded = setmetatable({}, Guard)
+        guarded.x = 5
+
+        local Counter = {}
+        function Counter.__call(self, n)
+          return n * 2
+        end
+        local counter = setmetatable({}, Counter)
+
+        return {obj:greet(), defaults.color, proxy.foo, rawget(guarded, "x"), counter(21)}
+    "#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Lua).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::String("hello bob".to_string()),
+            Value::String("red".to_string()),
+            Value::String("missing foo".to_string()),
+            Value::Int(50),
+            Value::Int(42),
+        ])]
+    );
+}
+
+#[test]
+fn lua_metatable_missing_method_raises_lua_error() {
+    let source = r#"
+        local Shape = {}
+        Shape.__index = Shape
+        function Shape:area()
+          return 0
+        end
+        local plain = {}
+        return plain:area()
+    "#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Lua).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let err = vm.run().expect_err("calling a missing method should fail");
+    assert!(
+        err.to_string()
+            .contains("lua error: attempt to call method 'area' (not a function)"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn lua_multiple_returns_expand_at_list_ends_and_truncate_elsewhere() {
+    let source = r#"
+        local function pair()
+          return 1, 2
+        end
+        local function add3(a, b, c)
+          return a + b + c
+        end
+        local a, b, c = pair()
+        local first = pair()
+        local x, y = 5
+        x, y = y, x
+        local tail = {0, pair()}
+        local head = {pair(), 9}
+        return {a, b, c, first, x, y, #tail, #head, add3(10, pair()), (pair())}
+    "#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Lua).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Int(1),
+            Value::Int(2),
+            Value::Null,
+            Value::Int(1),
+            Value::Null,
+            Value::Int(5),
+            Value::Int(3),
+            Value::Int(2),
+            Value::Int(13),
+            Value::Int(1),
+        ])]
+    );
+}
+
+#[test]
+fn lua_varargs_select_and_table_unpack_are_supported() {
+    let source = r#"
+        local function sum(...)
+          local total = 0
+          for _, v in ipairs({...}) do
+            total = total + v
+          end
+          return total
+        end
+        local function count(...)
+          return select('#', ...)
+        end
+        local function rest(...)
+          return select(2, ...)
+        end
+        local function pick(n, ...)
+          local a, b = ...
+          return n, a, b
+        end
+        local p, q, r = pick(1, rest(7, 20, 30))
+        local u, v, w = table.unpack({4, 5})
+        local last = select(-1, 8, 9)
+        return {sum(1, 2, 3), sum(), sum(rest(1, 2, 3)), count(1, nil, 3), count(), p, q, r, last, u, v, w}
+    "#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Lua).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Int(6),
+            Value::Int(0),
+            Value::Int(5),
+            Value::Int(3),
+            Value::Int(0),
+            Value::Int(1),
+            Value::Int(20),
+            Value::Int(30),
+            Value::Int(9),
+            Value::Int(4),
+            Value::Int(5),
+            Value::Null,
+        ])]
+    );
+}
+
+#[test]
+fn lua_varargs_outside_vararg_function_is_rejected() {
+    let source = r#"
+        local function f(a)
+          return ...
+        end
+    "#;
+    let err = match compile_source_with_flavor(source, SourceFlavor::Lua) {
+        Ok(_) => panic!("'...' outside a vararg function should fail to compile"),
+        Err(err) => err,
+    };
+    assert!(
+        err.to_string()
+            .contains("cannot use '...' outside a vararg function"),
+        "unexpected error: {err}"
+    );
+}
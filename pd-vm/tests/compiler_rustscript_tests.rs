@@ -95,6 +95,167 @@ fn rustscript_re_namespace_supports_optional_inline_flags_across_functions() {
     assert_eq!(vm.stack(), &[Value::Int(5)]);
 }
 
+#[test]
+fn rustscript_rand_namespace_is_reproducible_with_fixed_seed() {
+    let source = r#"
+        let roll = rand::int(1, 6);
+        let unit = rand::float();
+        let pick = rand::choice(["a", "b", "c"]);
+        let shuffled = rand::shuffle([1, 2, 3, 4]);
+        let id = uuid::v4();
+        [roll, unit, pick, shuffled, id];
+    "#;
+    let compiled = compile_source(source).expect("compile should succeed");
+    let run_with_seed = |seed: u64| {
+        let mut vm = Vm::with_locals(compiled.program.clone(), compiled.locals);
+        vm.set_rng_seed(seed);
+        let status = vm.run().expect("vm should run");
+        assert_eq!(status, VmStatus::Halted);
+        vm.stack().to_vec()
+    };
+
+    let first = run_with_seed(1234);
+    assert_eq!(first, run_with_seed(1234));
+    assert_ne!(first, run_with_seed(4321));
+
+    let Some(Value::Array(values)) = first.first() else {
+        panic!("expected result array, got {first:?}");
+    };
+    assert!(matches!(values[0], Value::Int(roll) if (1..=6).contains(&roll)));
+    assert!(matches!(values[1], Value::Float(unit) if (0.0..1.0).contains(&unit)));
+    assert!(matches!(&values[2], Value::String(pick) if ["a", "b", "c"].contains(&pick.as_str())));
+    let Value::Array(shuffled) = &values[3] else {
+        panic!("expected shuffled array, got {:?}", values[3]);
+    };
+    let mut sorted = shuffled
+        .iter()
+        .map(|value| match value {
+            Value::Int(value) => *value,
+            other => panic!("expected int element, got {other:?}"),
+        })
+        .collect::<Vec<_>>();
+    sorted.sort_unstable();
+    assert_eq!(sorted, vec![1, 2, 3, 4]);
+    let Value::String(id) = &values[4] else {
+        panic!("expected uuid string, got {:?}", values[4]);
+    };
+    assert_eq!(id.len(), 36);
+    assert_eq!(id.as_bytes()[14], b'4');
+    assert!(matches!(id.as_bytes()[19], b'8' | b'9' | b'a' | b'b'));
+}
+
+#[test]
+fn rustscript_sort_and_binary_search_keep_nan_last() {
+    // `inf - inf` is a negative NaN on x86; every NaN still sorts after all numbers.
+    let source = r#"
+        let big = 10000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000.0;
+        let inf = big * big * big * big;
+        let nan = inf - inf;
+        let sorted = array::sort([1.5, nan, 3, -7, 0, 0.0 - nan, 1, 0.0 - inf, 2.5]);
+        [sorted, array::binary_search(sorted, 3), array::binary_search(sorted, 1.5)];
+    "#;
+    let compiled = compile_source(source).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+
+    let Some(Value::Array(result)) = vm.stack().last() else {
+        panic!("expected result array, got {:?}", vm.stack());
+    };
+    let Value::Array(sorted) = &result[0] else {
+        panic!("expected sorted array, got {:?}", result[0]);
+    };
+    assert_eq!(
+        &sorted[..7],
+        &[
+            Value::Float(f64::NEG_INFINITY),
+            Value::Int(-7),
+            Value::Int(0),
+            Value::Int(1),
+            Value::Float(1.5),
+            Value::Float(2.5),
+            Value::Int(3),
+        ]
+    );
+    assert!(
+        sorted[7..]
+            .iter()
+            .all(|value| matches!(value, Value::Float(nan) if nan.is_nan()))
+    );
+    assert_eq!(result[1], Value::Int(6));
+    assert_eq!(result[2], Value::Int(4));
+}
+
+#[test]
+fn rustscript_array_and_map_namespaces_run_natively() {
+    let source = r#"
+        let sorted = array::sort([3, "b", 2.5, null, 1, false]);
+        let by_keys = array::sort_by_keys(["c", "a", "b"], [3, 1, 2]);
+        let hit = array::binary_search([1, 3, 5], 3);
+        let miss = array::binary_search([1, 3, 5], 4);
+        let unique = array::dedup([3, 1, 3, 2, 1]);
+        let edited = array::insert(array::remove([1, 2, 3], 0), 1, 9);
+        let popped = array::pop([1, 2, 3]);
+        let m = {a: 1, b: 2};
+        let trimmed = map::remove(m, "a");
+        [sorted, by_keys, hit, miss, unique, edited, popped, array::contains(popped, 2),
+            array::index_of(popped, 3), map::has_key(trimmed, "a"), map::values(trimmed),
+            map::entries(trimmed)];
+    "#;
+    let compiled = compile_source(source).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+
+    let ints = |values: &[i64]| Value::Array(values.iter().copied().map(Value::Int).collect());
+    let strings = |values: &[&str]| {
+        Value::Array(
+            values
+                .iter()
+                .map(|value| Value::String((*value).to_string()))
+                .collect(),
+        )
+    };
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Array(vec![
+                Value::Null,
+                Value::Bool(false),
+                Value::Int(1),
+                Value::Float(2.5),
+                Value::Int(3),
+                Value::String("b".to_string()),
+            ]),
+            strings(&["a", "b", "c"]),
+            Value::Int(1),
+            Value::Int(-3),
+            ints(&[3, 1, 2]),
+            ints(&[2, 9, 3]),
+            ints(&[1, 2]),
+            Value::Bool(true),
+            Value::Int(-1),
+            Value::Bool(false),
+            ints(&[2]),
+            Value::Array(vec![Value::Array(vec![
+                Value::String("b".to_string()),
+                Value::Int(2),
+            ])]),
+        ])]
+    );
+}
+
+#[test]
+fn rustscript_array_remove_out_of_bounds_is_runtime_error() {
+    let compiled = compile_source("array::remove([1, 2], 5);").expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let err = vm.run().expect_err("out of bounds remove should fail");
+    assert!(
+        err.to_string().contains("out of bounds"),
+        "unexpected error: {err}"
+    );
+}
+
 #[test]
 fn rustscript_float_literal_binding_is_supported() {
     let source = r#"
@@ -938,3 +1099,670 @@ fn rustscript_null_literal_can_be_used_as_map_key() {
     assert_eq!(status, VmStatus::Halted);
     assert_eq!(vm.stack(), &[Value::Int(42)]);
 }
+
+#[test]
+fn rustscript_null_keyed_entries_are_ordinary_map_entries() {
+    let source = r#"
+        let m = {a: 1};
+        m[null] = 5;
+        let seen = 0;
+        for (_, value) in m {
+            seen = seen + value;
+        }
+        [m.length, m.keys, seen, format!("{:?}", m)];
+    "#;
+
+    let compiled = compile_source(source).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Int(2),
+            Value::Array(vec![Value::String("a".to_string()), Value::Null]),
+            Value::Int(6),
+            Value::String("{\"a\": 1, null: 5}".to_string()),
+        ])]
+    );
+
+    let source = r#"
+        let m = {a: 1};
+        m[null] = {__index: {b: 2}};
+        m.b;
+    "#;
+    let compiled = compile_source(source).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    assert!(vm.run().is_err(), "a null-keyed map is not a metatable");
+}
+
+#[test]
+fn rustscript_format_macro_supports_positional_named_and_specs() {
+    let source = r#"
+        let host = "edge";
+        let ratio = 0.5;
+        let template = "{}/{}";
+        [
+            format!("{} {host} {0}", 7, host = host),
+            format!("[{:>6.2}] [{:<4}] [{:*^7}] [{:05}]", ratio, "ab", "mid", -42),
+            format!("{:?} {:?} {{literal}}", "quoted", [1.0, null]),
+            format!(template, 1, 2)
+        ];
+    "#;
+    let compiled = compile_source(source).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::String("7 edge 7".to_string()),
+            Value::String("[  0.50] [ab  ] [**mid**] [-0042]".to_string()),
+            Value::String("\"quoted\" [1.0, null] {literal}".to_string()),
+            Value::String("1/2".to_string()),
+        ])]
+    );
+}
+
+#[test]
+fn rustscript_format_macro_reports_placeholder_mismatch_at_compile_time() {
+    for (source, expected) in [
+        (
+            r#"format!("{} and {}", 1);"#,
+            "expects 2 positional argument(s) but 1 were supplied",
+        ),
+        (
+            r#"format!("{name}", other = 1);"#,
+            "references named argument 'name'",
+        ),
+        (r#"format!("{", 1);"#, "unterminated placeholder"),
+        (
+            r#"format!("{:99999999999}", 1);"#,
+            "width is too large (at most 65535)",
+        ),
+        (
+            r#"format!("{:.70000}", 1.5);"#,
+            "precision is too large (at most 65535)",
+        ),
+    ] {
+        let err = match compile_source(source) {
+            Ok(_) => panic!("format mismatch should fail to compile: {source}"),
+            Err(err) => err,
+        };
+        assert!(
+            err.to_string().contains(expected),
+            "unexpected error for {source}: {err}"
+        );
+    }
+}
+
+#[test]
+fn rustscript_format_with_dynamic_template_reports_missing_argument_at_runtime() {
+    let source = r#"
+        let template = "{} {}";
+        format!(template, 1);
+    "#;
+    let compiled = compile_source(source).expect("dynamic template should compile");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let err = vm.run().expect_err("missing format argument should fail");
+    assert!(
+        err.to_string().contains("format: missing argument #1"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn rustscript_format_with_dynamic_template_rejects_huge_widths_at_runtime() {
+    let source = r#"
+        let template = "{:99999999999}";
+        format!(template, 1);
+    "#;
+    let compiled = compile_source(source).expect("dynamic template should compile");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let err = vm.run().expect_err("huge width should fail");
+    assert!(
+        err.to_string().contains("width is too large"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn rustscript_structs_lower_to_tagged_maps_with_field_access() {
+    let source = r#"
+        struct Point { x: i64, y: i64 }
+        let x = 3;
+        let p = Point { y: 4, x };
+        p.y = p.y + 6;
+        [p.x + p.y, p, p == Point { x: 3, y: 10 }];
+    "#;
+    let compiled = compile_source(source).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Int(13),
+            Value::Map(vec![
+                (
+                    Value::String("__type".to_string()),
+                    Value::String("Point".to_string())
+                ),
+                (Value::String("x".to_string()), Value::Int(3)),
+                (Value::String("y".to_string()), Value::Int(10)),
+            ]),
+            Value::Bool(true),
+        ])]
+    );
+}
+
+#[test]
+fn rustscript_structs_keep_builtin_length_and_keys_properties() {
+    let source = r#"
+        struct Point { x: i64, y: i64 }
+        struct Span { start: i64, length: i64 }
+        let p = Point { x: 1, y: 2 };
+        let span = Span { start: 4, length: 9 };
+        let seen = 0;
+        for (key, value) in p {
+            seen = seen + 1;
+        }
+        [p.length, p.keys, seen, span.length];
+    "#;
+    let compiled = compile_source(source).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    let text = |value: &str| Value::String(value.to_string());
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Int(3),
+            Value::Array(vec![text("__type"), text("x"), text("y")]),
+            Value::Int(3),
+            Value::Int(9),
+        ])]
+    );
+}
+
+#[test]
+fn rustscript_struct_field_errors_are_reported_at_compile_time() {
+    for (source, expected) in [
+        (
+            "struct Point { x, y }\nlet p = Point { x: 1, y: 2 };\np.z;",
+            "struct 'Point' has no field 'z'",
+        ),
+        (
+            "struct Point { x, y }\nlet p = Point { x: 1, y: 2 };\np.z = 3;",
+            "struct 'Point' has no field 'z'",
+        ),
+        (
+            "struct Point { x, y }\nPoint { x: 1, y: 2, z: 3 };",
+            "struct 'Point' has no field 'z'",
+        ),
+        (
+            "struct Point { x, y }\nPoint { x: 1 };",
+            "missing field(s) y in struct 'Point' literal",
+        ),
+        (
+            "enum Shape { Circle(r) }\nShape::Circle(1, 2);",
+            "enum variant 'Shape::Circle' expects 1 payload value(s) but 2 were supplied",
+        ),
+        (
+            "enum Shape { Circle(r) }\nShape::Square(1);",
+            "enum 'Shape' has no variant 'Square'",
+        ),
+    ] {
+        let err = match compile_source(source) {
+            Ok(_) => panic!("source should fail to compile: {source}"),
+            Err(err) => err,
+        };
+        assert!(
+            err.to_string().contains(expected),
+            "unexpected error for {source}: {err}"
+        );
+    }
+}
+
+#[test]
+fn rustscript_match_destructures_enum_variants_and_structs() {
+    let source = r#"
+        struct Point { x: i64, y: i64 }
+        pub enum Shape {
+            Circle(i64),
+            Rect { w: i64, h: i64 },
+            Line(Point, Point),
+            Empty,
+        }
+        fn area(shape) {
+            match shape {
+                Shape::Circle(r) => r * r * 3,
+                Shape::Rect { w, h: height } => w * height,
+                Shape::Line(from, _) => from.x,
+                Shape::Empty => 0,
+                Point { x, .. } => x * 100,
+                _ => -1,
+            };
+        }
+        [
+            area(Shape::Circle(2)),
+            area(Shape::Rect { w: 3, h: 5 }),
+            area(Shape::Line(Point { x: 7, y: 0 }, Point { x: 9, y: 9 })),
+            area(Shape::Empty),
+            area(Point { x: 2, y: 0 }),
+            area({ w: 3, h: 5 }),
+            area(4)
+        ];
+    "#;
+    let compiled = compile_source(source).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Int(12),
+            Value::Int(15),
+            Value::Int(7),
+            Value::Int(0),
+            Value::Int(200),
+            Value::Int(-1),
+            Value::Int(-1),
+        ])]
+    );
+}
+
+#[test]
+fn rustscript_match_variant_patterns_must_cover_declared_fields() {
+    for (source, expected) in [
+        (
+            "enum Pair { Both(a, b) }\nmatch Pair::Both(1, 2) { Pair::Both(a) => a, _ => 0 };",
+            "pattern for enum variant 'Pair::Both' has 1 field(s) but the variant has 2",
+        ),
+        (
+            "struct Point { x, y }\nmatch Point { x: 1, y: 2 } { Point { x } => x, _ => 0 };",
+            "does not mention field(s) y; add '..' to ignore them",
+        ),
+        (
+            "enum Pair { Both(a, b) }\nmatch Pair::Both(1, 2) { Pair::Both(a, a) => a, _ => 0 };",
+            "identifier 'a' is bound more than once",
+        ),
+    ] {
+        let err = match compile_source(source) {
+            Ok(_) => panic!("pattern should fail to compile: {source}"),
+            Err(err) => err,
+        };
+        assert!(
+            err.to_string().contains(expected),
+            "unexpected error for {source}: {err}"
+        );
+    }
+}
+
+#[test]
+fn rustscript_match_destructures_arrays_and_maps_with_guards() {
+    let source = r#"
+        fn route(req) {
+            match req {
+                { method: "GET", path: ["api", "users", id] } => format!("user {}", id),
+                { method: "GET" | "HEAD", path: ["static", ..rest] } => format!("static {}", rest),
+                { method, path: [first, .., last] } if method == "POST" => format!("{} {}..{}", method, first, last),
+                { path: [] } => "root",
+                _ => "404",
+            };
+        }
+        [
+            route({ method: "GET", path: ["api", "users", 7] }),
+            route({ method: "HEAD", path: ["static", "css", "app.css"] }),
+            route({ method: "POST", path: ["a", "b", "c"] }),
+            route({ method: "PUT", path: ["a"] }),
+            route({ method: "GET", path: [] }),
+            route("not a request")
+        ];
+    "#;
+    let compiled = compile_source(source).expect("compile should succeed");
+    assert!(compiled.warnings.is_empty(), "{:?}", compiled.warnings);
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::String("user 7".to_string()),
+            Value::String("static [css, app.css]".to_string()),
+            Value::String("POST a..c".to_string()),
+            Value::String("404".to_string()),
+            Value::String("root".to_string()),
+            Value::String("404".to_string()),
+        ])]
+    );
+}
+
+#[test]
+fn rustscript_match_supports_ranges_alternatives_and_nested_patterns() {
+    let source = r#"
+        enum Shape { Circle(i64), Rect { w: i64, h: i64 } }
+        fn classify(n) {
+            match n {
+                ..0 => "negative",
+                0 | 10 | 20 => "round",
+                1..=9 => "small",
+                9.5..15.5 => "teens",
+                big if big > 100 => "big",
+                _ => "other",
+            };
+        }
+        fn area(shape) {
+            match shape {
+                Shape::Circle(0) => 0,
+                Shape::Circle(r) => r * r,
+                Shape::Rect { w: 1..=2, h } => h,
+                Shape::Rect { w, h } => w * h,
+            };
+        }
+        let nested = match [1, [2, { x: 3 }]] {
+            [a, [b, { x }]] => a + b + x,
+            _ => 0,
+        };
+        [
+            classify(-4), classify(20), classify(9), classify(9.75), classify(15.5), classify(101),
+            area(Shape::Circle(0)), area(Shape::Circle(3)), area(Shape::Rect { w: 2, h: 7 }), area(Shape::Rect { w: 3, h: 3 }),
+            nested
+        ];
+    "#;
+    let compiled = compile_source(source).expect("compile should succeed");
+    assert!(compiled.warnings.is_empty(), "{:?}", compiled.warnings);
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::String("negative".to_string()),
+            Value::String("round".to_string()),
+            Value::String("small".to_string()),
+            Value::String("teens".to_string()),
+            Value::String("other".to_string()),
+            Value::String("big".to_string()),
+            Value::Int(0),
+            Value::Int(9),
+            Value::Int(7),
+            Value::Int(9),
+            Value::Int(6),
+        ])]
+    );
+}
+
+#[test]
+fn rustscript_match_warns_about_unreachable_and_non_exhaustive_arms() {
+    let source = r#"
+        enum Color { Red, Green, Blue }
+        let a = match 3 {
+            1 => "one",
+            _ => "many",
+            2 => "two",
+        };
+        let b = match Color::Blue {
+            Color::Red => 1,
+            Color::Green if a == "many" => 2,
+        };
+        let c = match Color::Red {
+            Color::Red | Color::Green => 1,
+            Color::Blue => 2,
+            _ => 3,
+        };
+        [a, b, c];
+    "#;
+    let compiled = compile_source(source).expect("warnings should not fail compilation");
+    let warnings = compiled
+        .warnings
+        .iter()
+        .map(|warning| (warning.line, warning.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(warnings.len(), 3, "{warnings:?}");
+    assert_eq!(warnings[0].0, 6);
+    assert!(warnings[0].1.contains("unreachable match arm"));
+    assert_eq!(warnings[1].0, 8);
+    assert!(
+        warnings[1].1.contains(
+            "non-exhaustive match on enum 'Color': Color::Green, Color::Blue not covered"
+        ),
+        "{warnings:?}"
+    );
+    assert_eq!(warnings[2].0, 15);
+    assert!(warnings[2].1.contains("unreachable match arm"));
+
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::String("many".to_string()),
+            Value::Null,
+            Value::Int(1),
+        ])]
+    );
+}
+
+#[test]
+fn rustscript_match_rejects_inconsistent_patterns() {
+    for (source, expected) in [
+        (
+            "match [1] { [a] | [] => 1, _ => 0 };",
+            "all alternatives of a '|' pattern must bind the same names",
+        ),
+        ("match 1 { 5..=1 => 1, _ => 0 };", "range pattern is empty"),
+        (
+            "match [1] { [.., a, ..] => a, _ => 0 };",
+            "at most one '..'",
+        ),
+        (
+            "match 1 { n if n > 0 => n };",
+            "match expression requires a wildcard arm",
+        ),
+    ] {
+        let err = match compile_source(source) {
+            Ok(_) => panic!("pattern should fail to compile: {source}"),
+            Err(err) => err,
+        };
+        assert!(
+            err.to_string().contains(expected),
+            "unexpected error for {source}: {err}"
+        );
+    }
+}
+
+#[test]
+fn rustscript_return_exits_nested_loops_of_inlined_functions() {
+    let source = r#"
+        fn find_pair(rows, target) {
+            let i = 0;
+            while i < rows.length {
+                for (let j = 0; j < rows[i].length; j = j + 1) {
+                    if rows[i][j] == target {
+                        return [i, j];
+                    }
+                }
+                i = i + 1;
+            }
+            return null;
+        }
+        fn sign(value) {
+            if value < 0 {
+                return -1;
+            }
+            if value > 0 {
+                return 1;
+            }
+            0;
+        }
+        fn describe(value) {
+            if sign(value) == 0 {
+                return "zero";
+            } else {
+                return format!("{} has sign {}", value, sign(value));
+            }
+        }
+        let rows = [[1, 2], [3, 4, 5]];
+        let hits = 0;
+        for (let k = 0; k < 3; k = k + 1) {
+            if find_pair(rows, 4) != null {
+                hits = hits + 1;
+            }
+        }
+        [find_pair(rows, 5), find_pair(rows, 9), 10 + sign(-8), describe(0), describe(-2), hits];
+    "#;
+    let compiled = compile_source(source).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Array(vec![Value::Int(1), Value::Int(2)]),
+            Value::Null,
+            Value::Int(9),
+            Value::String("zero".to_string()),
+            Value::String("-2 has sign -1".to_string()),
+            Value::Int(3),
+        ])]
+    );
+}
+
+#[test]
+fn rustscript_return_inside_closure_leaves_only_the_closure() {
+    let source = r#"
+        fn apply(value) {
+            let clamp = |x| if x > 10 => { return 10; } else => { x };
+            clamp(value) + 1;
+        }
+        [apply(3), apply(50)];
+    "#;
+    let compiled = compile_source(source).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![Value::Int(4), Value::Int(11)])]
+    );
+}
+
+#[test]
+fn rustscript_return_outside_function_is_rejected() {
+    let err = match compile_source("let x = 1;\nreturn x;") {
+        Ok(_) => panic!("top-level return should fail to compile"),
+        Err(err) => err,
+    };
+    assert!(
+        err.to_string()
+            .contains("'return' is only allowed inside functions and closures"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn rustscript_for_in_iterates_arrays_and_maps() {
+    let source = r#"
+        let headers = { host: "example.com", accept: "json", "x-id": 7 };
+        let pairs = [];
+        for (name, value) in headers {
+            pairs[pairs.length] = format!("{}={}", name, value);
+        }
+        let names = [];
+        for (name, _) in headers {
+            names[names.length] = name;
+        }
+        let values = [];
+        for value in headers {
+            values[values.length] = value;
+        }
+        let indexed = [];
+        for (i, x) in ["a", "b"] {
+            indexed[indexed.length] = format!("{}{}", i, x);
+        }
+        let total = 0;
+        for x in [1, 2, 3, 4, 5] {
+            if x == 2 {
+                continue;
+            }
+            if x == 5 {
+                break;
+            }
+            total = total + x;
+        }
+        fn first_large(items) {
+            for (key, value) in items {
+                if value > 10 {
+                    return key;
+                }
+            }
+            null;
+        }
+        [pairs, names, values, indexed, total, first_large({ a: 1, b: 20 }), first_large({})];
+    "#;
+    let compiled = compile_source(source).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    let strings = |items: &[&str]| {
+        Value::Array(
+            items
+                .iter()
+                .map(|item| Value::String(item.to_string()))
+                .collect(),
+        )
+    };
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            strings(&["host=example.com", "accept=json", "x-id=7"]),
+            strings(&["host", "accept", "x-id"]),
+            Value::Array(vec![
+                Value::String("example.com".to_string()),
+                Value::String("json".to_string()),
+                Value::Int(7),
+            ]),
+            strings(&["0a", "1b"]),
+            Value::Int(8),
+            Value::String("b".to_string()),
+            Value::Null,
+        ])]
+    );
+}
+
+#[test]
+fn rustscript_function_declaration_can_be_completed_by_later_definition() {
+    let source = r#"
+        fn twice(x);
+        fn quad(x) {
+            twice(twice(x));
+        }
+        fn twice(x) {
+            x * 2;
+        }
+        quad(3);
+    "#;
+    let compiled = compile_source(source).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(vm.stack(), &[Value::Int(12)]);
+}
+
+#[test]
+fn rustscript_brace_literal_accepts_namespaced_call_values() {
+    let source = r#"
+        let xs = {array::index_of([5, 6, 7], 7), 4};
+        xs;
+    "#;
+    let compiled = compile_source(source).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![Value::Int(2), Value::Int(4)])]
+    );
+}
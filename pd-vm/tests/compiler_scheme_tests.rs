@@ -356,3 +356,253 @@ fn scheme_direct_builtin_len_call_is_rejected() {
         other => panic!("unexpected error: {other}"),
     }
 }
+
+#[test]
+fn scheme_sort_and_list_tail_are_supported() {
+    let source = r#"
+        (define ascending (sort (list 3 1 2)))
+        (define descending (sort (list 3 1 2) >))
+        (define by-lambda (sort (list "bb" "a" "ccc") (lambda (a b) (< (string-length a) (string-length b)))))
+        (list ascending descending by-lambda (list-tail (list 1 2 3 4) 2))
+    "#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Scheme).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    let ints = |values: &[i64]| Value::Array(values.iter().copied().map(Value::Int).collect());
+    assert_eq!(
+        vm.stack().last(),
+        Some(&Value::Array(vec![
+            ints(&[1, 2, 3]),
+            ints(&[3, 2, 1]),
+            Value::Array(vec![
+                Value::String("a".to_string()),
+                Value::String("bb".to_string()),
+                Value::String("ccc".to_string()),
+            ]),
+            ints(&[3, 4]),
+        ]))
+    );
+}
+
+#[test]
+fn scheme_format_lowers_to_format_builtin() {
+    let source = r#"
+        (define name "edge")
+        (format #f "~a has ~a items: ~s~~" name 3 "x")
+    "#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Scheme).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack().last(),
+        Some(&Value::String("edge has 3 items: \"x\"~".to_string()))
+    );
+}
+
+#[test]
+fn scheme_for_each_and_hash_table_walk_iterate_natively() {
+    let source = r#"
+        (for-each (lambda (x) (print (* x 10))) (list 1 2 3))
+        (define h (hash (a 1)))
+        (hash-set! h "b" 2)
+        (hash-table-walk h (lambda (k v) (print (string-append k "=" (number->string v)))))
+    "#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Scheme).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    vm.bind_function("print", Box::new(PrintBuiltin));
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[
+            Value::Int(10),
+            Value::Int(20),
+            Value::Int(30),
+            Value::String("a=1".to_string()),
+            Value::String("b=2".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn scheme_iteration_lambdas_can_update_outer_bindings() {
+    let source = r#"
+        (define total 0)
+        (define seen (list))
+        (for-each (lambda (x) (set! total (+ total x))) (list 1 2 3))
+        (for-each (lambda (x) (begin (set! seen (append seen (list x))) (set! total (* total 2))))
+                  (list 4 5))
+        (define h (hash (a 10)))
+        (hash-table-walk h (lambda (k v) (set! total (+ total v))))
+        (list total seen)
+    "#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Scheme).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack().last(),
+        Some(&Value::Array(vec![
+            Value::Int(34),
+            Value::Array(vec![Value::Int(4), Value::Int(5)]),
+        ]))
+    );
+}
+
+#[test]
+fn scheme_tail_calls_run_self_and_mutual_recursion_as_loops() {
+    let source = r#"
+        (define step 2)
+        (define (sum-to i acc) (if (= i 0) acc (sum-to (- i 1) (+ acc i))))
+        (define (is-even n) (if (= n 0) #t (is-odd (- n 1))))
+        (define (is-odd n) (cond ((= n 0) #f) (else (is-even (- n 1)))))
+        (print (sum-to 100000 0))
+        (print (let loop ((i 0) (acc (list))) (if (< i 6) (loop (+ i step) (append acc (list i))) acc)))
+        (print (is-even 10001))
+        (print (letrec ((ev (lambda (n) (or (= n 0) (od (- n 1)))))
+                        (od (lambda (n) (and (> n 0) (ev (- n 1))))))
+                 (ev 50)))
+    "#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Scheme).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    vm.bind_function("print", Box::new(PrintBuiltin));
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[
+            Value::Int(5_000_050_000),
+            Value::Array(vec![Value::Int(0), Value::Int(2), Value::Int(4)]),
+            Value::Bool(false),
+            Value::Bool(true),
+        ]
+    );
+}
+
+#[test]
+fn scheme_named_let_runs_as_a_top_level_statement() {
+    let source = r#"
+        (define total 0)
+        (let lp ((k 0)) (if (< k 3) (lp (+ k 1)) (set! total (+ total k))))
+        (let lp ((k 0)) (when (< k 4) (set! total (+ total 10)) (lp (+ k 1))))
+        (let lp ((k 0)) (unless (> k 1) (set! total (+ total 100)) (lp (+ k 1))))
+        (let lp ((k 0)) (begin (set! total (+ total 1000)) (if (< k 1) (lp (+ k 1)))))
+        (let lp ((k 0)) (if (< k 3) (lp (+ k 1)) k))
+        (print total)
+    "#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Scheme).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    vm.bind_function("print", Box::new(PrintBuiltin));
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(vm.stack(), &[Value::Int(3), Value::Int(2243)]);
+}
+
+#[test]
+fn scheme_recursion_outside_tail_position_is_rejected() {
+    let source = "(define (fact n)\n  (if (= n 0) 1 (* n (fact (- n 1)))))\n(print (fact 5))\n";
+    let err = match compile_source_with_flavor(source, SourceFlavor::Scheme) {
+        Ok(_) => panic!("non-tail recursion should be rejected"),
+        Err(err) => err,
+    };
+    match err {
+        vm::SourceError::Parse(parse) => {
+            assert_eq!(parse.line, 2);
+            assert!(
+                parse
+                    .message
+                    .contains("'fact' is called outside tail position"),
+                "unexpected message: {}",
+                parse.message
+            );
+        }
+        other => panic!("expected parse error, got {other:?}"),
+    }
+}
+
+#[test]
+fn scheme_syntax_rules_expand_with_ellipses_literals_and_hygiene() {
+    let source = r#"
+        (define-syntax swap!
+          (syntax-rules ()
+            ((_ a b) (let ((tmp a)) (set! a b) (set! b tmp)))))
+        (define-syntax my-or
+          (syntax-rules ()
+            ((_) #f)
+            ((_ e) e)
+            ((_ e r ...) (let ((t e)) (if t t (my-or r ...))))))
+        (define-syntax my-cond
+          (syntax-rules (else)
+            ((_ (else e)) e)
+            ((_ (c e) clause ...) (if c e (my-cond clause ...)))))
+        (define tmp 1)
+        (define other 2)
+        (swap! tmp other)
+        (print tmp)
+        (print other)
+        (define t 5)
+        (print (my-or #f t))
+        (print (my-cond (#f 1) ((= t 5) 2) (else 3)))
+        (let-syntax ((sum (syntax-rules () ((_ x ...) (+ 0 x ...)))))
+          (print (sum 1 2 3)))
+    "#;
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Scheme).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    vm.bind_function("print", Box::new(PrintBuiltin));
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[
+            Value::Int(2),
+            Value::Int(1),
+            Value::Int(5),
+            Value::Int(2),
+            Value::Int(6),
+        ]
+    );
+}
+
+#[test]
+fn scheme_macro_expansions_are_attributed_to_the_use_site() {
+    let source = "(define-syntax fail-with\n  (syntax-rules ()\n    ((_ x) (car x))))\n(define n 1)\n(print (fail-with n))\n";
+    let compiled =
+        compile_source_with_flavor(source, SourceFlavor::Scheme).expect("compile should succeed");
+    let debug = compiled
+        .program
+        .debug
+        .as_ref()
+        .expect("compiled program should have debug info");
+    assert!(debug.offsets_for_line(3).is_empty());
+    assert!(!debug.offsets_for_line(5).is_empty());
+
+    let source = "(define-syntax pair-up\n  (syntax-rules ()\n    ((_ a b) (list a b))))\n\n(print (pair-up 1))\n";
+    let err = match compile_source_with_flavor(source, SourceFlavor::Scheme) {
+        Ok(_) => panic!("a use matching no rule should be rejected"),
+        Err(err) => err,
+    };
+    match err {
+        vm::SourceError::Parse(parse) => {
+            assert_eq!(parse.line, 5);
+            assert!(
+                parse
+                    .message
+                    .contains("no syntax-rules pattern of macro 'pair-up' matches"),
+                "unexpected message: {}",
+                parse.message
+            );
+        }
+        other => panic!("expected parse error, got {other:?}"),
+    }
+}
@@ -0,0 +1,237 @@
+#![cfg(feature = "runtime")]
+use std::path::Path;
+
+use vm::{
+    CallOutcome, CompiledProgram, DecompileError, FunctionDecl, HostFunction, OpCode, Program,
+    Value, Vm, VmStatus, compile_source, compile_source_file, decode_program, decompile_program,
+    encode_program,
+};
+
+struct PrintFunction;
+struct AddOneFunction;
+
+impl HostFunction for PrintFunction {
+    fn call(&mut self, _vm: &mut Vm, args: &[Value]) -> Result<CallOutcome, vm::VmError> {
+        Ok(CallOutcome::Return(args.to_vec()))
+    }
+}
+
+impl HostFunction for AddOneFunction {
+    fn call(&mut self, _vm: &mut Vm, args: &[Value]) -> Result<CallOutcome, vm::VmError> {
+        let value = match args.first() {
+            Some(Value::Int(value)) => *value,
+            _ => return Err(vm::VmError::TypeMismatch("int")),
+        };
+        Ok(CallOutcome::Return(vec![Value::Int(value + 1)]))
+    }
+}
+
+fn register_functions(vm: &mut Vm, functions: &[FunctionDecl]) {
+    for decl in functions {
+        match decl.name.as_str() {
+            "print" => {
+                vm.bind_function("print", Box::new(PrintFunction));
+            }
+            "add_one" => {
+                vm.bind_function("add_one", Box::new(AddOneFunction));
+            }
+            other => panic!("unknown function '{other}'"),
+        }
+    }
+}
+
+fn run(compiled: CompiledProgram) -> Vec<Value> {
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let mut jit_config = vm.jit_config().clone();
+    jit_config.enabled = false;
+    vm.set_jit_config(jit_config);
+    register_functions(&mut vm, &compiled.functions);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    vm.stack().to_vec()
+}
+
+/// Decompiles the program after a trip through VMBC, as the CLI does.
+fn decompile(program: &Program) -> String {
+    let bytes = encode_program(program).expect("encode should succeed");
+    let decoded = decode_program(&bytes).expect("decode should succeed");
+    decompile_program(&decoded).expect("decompile should succeed")
+}
+
+fn assert_round_trip(compiled: CompiledProgram) -> String {
+    let source = decompile(&compiled.program);
+    let recompiled = compile_source(&source)
+        .unwrap_or_else(|err| panic!("decompiled source should compile: {err:?}\n{source}"));
+    assert_eq!(run(recompiled), run(compiled), "{source}");
+    source
+}
+
+fn assert_file_round_trip(path: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(path);
+    assert_round_trip(compile_source_file(&path).expect("compile should succeed"));
+}
+
+fn assert_source_round_trip(source: &str) -> String {
+    assert_round_trip(compile_source(source).expect("compile should succeed"))
+}
+
+#[test]
+fn decompiled_examples_behave_like_the_originals() {
+    for flavor in ["rss", "js", "lua", "scm"] {
+        assert_file_round_trip(&format!("examples/example.{flavor}"));
+        assert_file_round_trip(&format!("examples/example_complex.{flavor}"));
+    }
+    assert_file_round_trip("examples/aes_128_cbc_usage.rss");
+}
+
+#[test]
+fn decompiled_stdlib_tests_behave_like_the_originals() {
+    for name in ["collections", "io", "iter", "math", "rand", "re", "strings"] {
+        assert_file_round_trip(&format!("stdlib/tests/{name}.rss"));
+    }
+}
+
+#[test]
+fn decompile_keeps_named_locals() {
+    let source = assert_source_round_trip(
+        r#"
+        let total = 0;
+        let step = 3;
+        total = total + step;
+        total;
+        "#,
+    );
+    assert!(source.contains("let total = 0;"), "{source}");
+    assert!(source.contains("total = total + step;"), "{source}");
+}
+
+#[test]
+fn decompile_names_locals_of_inlined_functions() {
+    let source = assert_source_round_trip(
+        r#"
+        fn sum_to(limit) {
+            let sum = 0;
+            let step = 1;
+            while step < limit + 1 {
+                sum = sum + step;
+                step = step + 1;
+            }
+            sum;
+        }
+        let total = sum_to(4);
+        total;
+        "#,
+    );
+    assert!(source.contains("sum = sum + step;"), "{source}");
+    assert!(!source.contains("local"), "{source}");
+}
+
+#[test]
+fn decompile_recovers_loops_with_break_and_continue() {
+    let source = assert_source_round_trip(
+        r#"
+        let sum = 0;
+        let i = 0;
+        while i < 10 {
+            i = i + 1;
+            if i == 3 {
+                continue;
+            }
+            if i == 8 {
+                break;
+            }
+            sum = sum + i;
+        }
+        for (let j = 0; j < 4; j = j + 1) {
+            if j == 1 {
+                continue;
+            }
+            sum = sum + j;
+        }
+        sum;
+        "#,
+    );
+    assert!(source.contains("while "), "{source}");
+    assert!(source.contains("continue;"), "{source}");
+    assert!(source.contains("break;"), "{source}");
+}
+
+#[test]
+fn decompile_keeps_if_else_branches_together_at_the_end_of_a_loop() {
+    let source = assert_source_round_trip(
+        r#"
+        let total = 0;
+        let i = 0;
+        while i < 10 {
+            i = i + 1;
+            if i % 2 == 0 {
+                total = total + i;
+            } else {
+                total = total - 1;
+            }
+        }
+        total;
+        "#,
+    );
+    assert!(
+        source.contains(
+            "    if i % 2 == 0 {\n        total = total + i;\n    } else {\n        total = total - 1;\n    }\n}"
+        ),
+        "{source}"
+    );
+    assert!(!source.contains("continue;"), "{source}");
+}
+
+#[test]
+fn decompile_recovers_match_slices_and_host_calls() {
+    let source = assert_source_round_trip(
+        r#"
+        use vm::{add_one};
+        let word = "decompiler";
+        let head = word[0:3];
+        let tail = word[3:];
+        let kind = match head.length {
+            3 => "three",
+            _ => "other",
+        };
+        add_one(tail.length);
+        kind;
+        "#,
+    );
+    assert!(source.contains("use vm::{add_one};"), "{source}");
+    assert!(source.contains("word[0:3]"), "{source}");
+}
+
+#[test]
+fn decompile_recovers_early_returns_from_inlined_loops() {
+    assert_source_round_trip(
+        r#"
+        fn find(items, wanted) {
+            let index = 0;
+            while index < items.length {
+                if items[index] == wanted {
+                    return index;
+                }
+                index = index + 1;
+            }
+            -1;
+        }
+        find([4, 5, 6], 6) + find([1], 9);
+        "#,
+    );
+}
+
+#[test]
+fn decompile_rejects_jumps_into_an_instruction() {
+    let mut program = compile_source("let x = 1; x;")
+        .expect("compile should succeed")
+        .program;
+    // Turn the trailing `ret` into a jump into the first `ldc` operand.
+    let ret = program.code.len() - 1;
+    program.code[ret] = OpCode::Br as u8;
+    program.code.extend_from_slice(&1u32.to_le_bytes());
+    assert_eq!(
+        decompile_program(&program),
+        Err(DecompileError::InvalidCode { offset: ret })
+    );
+}
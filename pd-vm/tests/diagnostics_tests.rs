@@ -74,3 +74,84 @@ fn render_vm_error_includes_ip_and_source_line() {
     assert!(rendered.contains("line 1"));
     assert!(rendered.contains("let value = 1 / 0;"));
 }
+
+#[test]
+fn render_vm_error_names_the_imported_module_line() {
+    let unique = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time should be monotonic")
+        .as_nanos();
+    let dir = std::env::temp_dir().join(format!("pd_vm_diag_modules_{unique}"));
+    fs::create_dir_all(&dir).expect("temp dir should be created");
+    let main_path = dir.join("main.rss");
+    fs::write(
+        &main_path,
+        "use divide;\n\nlet zero = 0;\nlet value = ratio(1, zero);\n",
+    )
+    .expect("main source should be writable");
+    fs::write(
+        dir.join("divide.rss"),
+        "pub fn ratio(a, b) {\n    let q = a / b;\n    q;\n}\n",
+    )
+    .expect("module source should be writable");
+
+    let result = compile_source_file(&main_path);
+    let _ = fs::remove_dir_all(&dir);
+    let compiled = result.expect("source should compile");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let err = vm
+        .run()
+        .expect_err("runtime should fail with division by zero");
+
+    let rendered = render_vm_error(&vm, &err);
+    assert!(rendered.contains("divide.rss:2)"), "{rendered}");
+    assert!(rendered.contains("  2 |     let q = a / b;"), "{rendered}");
+    assert!(rendered.contains("    |     ^"), "{rendered}");
+}
+
+#[test]
+fn render_vm_error_shows_the_logical_call_stack_through_inlined_calls() {
+    let unique = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time should be monotonic")
+        .as_nanos();
+    let dir = std::env::temp_dir().join(format!("pd_vm_diag_stack_{unique}"));
+    fs::create_dir_all(&dir).expect("temp dir should be created");
+    let main_path = dir.join("main.rss");
+    fs::write(
+        &main_path,
+        "use divide;\n\nfn route(total, parts) {\n    let share = ratio(total, parts);\n    share;\n}\n\nlet zero = 0;\nlet value = route(10, zero);\n",
+    )
+    .expect("main source should be writable");
+    fs::write(
+        dir.join("divide.rss"),
+        "pub fn ratio(a, b) {\n    let q = a / b;\n    q;\n}\n",
+    )
+    .expect("module source should be writable");
+
+    let result = compile_source_file(&main_path);
+    let _ = fs::remove_dir_all(&dir);
+    let compiled = result.expect("source should compile");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let err = vm
+        .run()
+        .expect_err("runtime should fail with division by zero");
+
+    let rendered = render_vm_error(&vm, &err);
+    assert!(
+        rendered.contains("call stack (innermost first):"),
+        "{rendered}"
+    );
+    assert!(
+        rendered.contains("#0 ratio at ") && rendered.contains("divide.rss:2: let q = a / b;"),
+        "{rendered}"
+    );
+    assert!(
+        rendered.contains("#1 route at line 4: let share = ratio(total, parts);"),
+        "{rendered}"
+    );
+    assert!(
+        rendered.contains("#2 <main> at line 9: let value = route(10, zero);"),
+        "{rendered}"
+    );
+}
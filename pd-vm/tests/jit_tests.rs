@@ -265,3 +265,143 @@ fn trace_jit_nested_loops_use_branch_exit_segments() {
         );
     }
 }
+
+#[test]
+fn trace_jit_compiles_for_in_loops_over_arrays_and_maps() {
+    let source = r#"
+        let sum = 0;
+        for x in [1, 2, 3, 4, 5, 6] {
+            sum = sum + x;
+        }
+        for (k, v) in { a: 10, b: 20, c: 30 } {
+            sum = sum + v;
+        }
+        sum;
+    "#;
+
+    let compiled = compile_source(source).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    vm.set_jit_config(JitConfig {
+        enabled: native_jit_supported(),
+        hot_loop_threshold: 1,
+        max_trace_len: 512,
+    });
+
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(vm.stack(), &[Value::Int(81)]);
+
+    let snapshot = vm.jit_snapshot();
+    if native_jit_supported() {
+        assert!(
+            snapshot
+                .traces
+                .iter()
+                .any(|trace| trace.terminal == JitTraceTerminal::LoopBack),
+            "expected a looping trace, dump:\n{}",
+            vm.dump_jit_info()
+        );
+    } else {
+        assert!(snapshot.traces.is_empty());
+    }
+}
+
+#[test]
+fn trace_jit_adds_strings_and_arrays_through_the_interpreter() {
+    let source = r#"
+        let i = 0;
+        let text = "";
+        let items = [];
+        while i < 20 {
+            text = text + "ab";
+            items = items + [i];
+            i = i + 1;
+        }
+        [(text).length, (items).length, items[19]];
+    "#;
+
+    let compiled = compile_source(source).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    vm.set_jit_config(JitConfig {
+        enabled: native_jit_supported(),
+        hot_loop_threshold: 1,
+        max_trace_len: 512,
+    });
+
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(
+        vm.stack(),
+        &[Value::Array(vec![
+            Value::Int(40),
+            Value::Int(20),
+            Value::Int(19)
+        ])]
+    );
+    if native_jit_supported() {
+        assert!(!vm.jit_snapshot().traces.is_empty());
+    }
+}
+
+#[test]
+fn trace_jit_compares_strings_through_the_interpreter() {
+    let source = r#"
+        let words = ["a", "b", "a", "c", "a", "b", "a", "a", "c", "a"];
+        let i = 0;
+        let hits = 0;
+        while i < words.length {
+            if words[i] == "a" {
+                hits = hits + 1;
+            }
+            i = i + 1;
+        }
+        hits;
+    "#;
+
+    let compiled = compile_source(source).expect("compile should succeed");
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    vm.set_jit_config(JitConfig {
+        enabled: native_jit_supported(),
+        hot_loop_threshold: 1,
+        max_trace_len: 512,
+    });
+
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    assert_eq!(vm.stack(), &[Value::Int(6)]);
+}
+
+#[test]
+fn trace_jit_records_the_bytecode_offset_of_every_step() {
+    let source = r#"
+        let i = 0;
+        let total = 0;
+        while i < 10 {
+            total = total + i;
+            i = i + 1;
+        }
+        total;
+    "#;
+
+    let compiled = compile_source(source).expect("compile should succeed");
+    let code_len = compiled.program.code.len();
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    vm.set_jit_config(JitConfig {
+        enabled: native_jit_supported(),
+        hot_loop_threshold: 1,
+        max_trace_len: 512,
+    });
+
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    let snapshot = vm.jit_snapshot();
+    if native_jit_supported() {
+        assert!(!snapshot.traces.is_empty());
+    }
+    for trace in &snapshot.traces {
+        assert_eq!(trace.step_ips.len(), trace.steps.len());
+        assert_eq!(trace.step_ips.first(), Some(&trace.root_ip));
+        assert!(trace.step_ips.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(trace.step_ips.iter().all(|ip| *ip < code_len));
+    }
+}
@@ -91,3 +91,19 @@ fn stdlib_re_spec_passes() {
     let stack = run_rustscript_spec(&path);
     assert_eq!(stack, Vec::<Value>::new());
 }
+
+#[test]
+fn stdlib_rand_spec_passes() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let path = root.join("stdlib/tests/rand.rss");
+    let stack = run_rustscript_spec(&path);
+    assert_eq!(stack, Vec::<Value>::new());
+}
+
+#[test]
+fn stdlib_lua_patterns_spec_passes() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let path = root.join("stdlib/tests/lua_patterns.rss");
+    let stack = run_rustscript_spec(&path);
+    assert_eq!(stack, Vec::<Value>::new());
+}
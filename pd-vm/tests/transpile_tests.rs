@@ -0,0 +1,556 @@
+#![cfg(feature = "runtime")]
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use vm::{
+    CallOutcome, CompiledProgram, FunctionDecl, HostFunction, SourceFlavor, TranspileError, Value,
+    Vm, VmStatus, compile_source_file, compile_source_with_flavor, transpile_bindings,
+    transpile_source, transpile_source_file,
+};
+
+const FLAVORS: [SourceFlavor; 4] = [
+    SourceFlavor::RustScript,
+    SourceFlavor::JavaScript,
+    SourceFlavor::Lua,
+    SourceFlavor::Scheme,
+];
+
+struct PrintFunction;
+struct AddOneFunction;
+
+impl HostFunction for PrintFunction {
+    fn call(&mut self, _vm: &mut Vm, args: &[Value]) -> Result<CallOutcome, vm::VmError> {
+        Ok(CallOutcome::Return(args.to_vec()))
+    }
+}
+
+impl HostFunction for AddOneFunction {
+    fn call(&mut self, _vm: &mut Vm, args: &[Value]) -> Result<CallOutcome, vm::VmError> {
+        let value = match args.first() {
+            Some(Value::Int(value)) => *value,
+            _ => return Err(vm::VmError::TypeMismatch("int")),
+        };
+        Ok(CallOutcome::Return(vec![Value::Int(value + 1)]))
+    }
+}
+
+fn register_functions(vm: &mut Vm, functions: &[FunctionDecl]) {
+    for decl in functions {
+        match decl.name.as_str() {
+            "print" => {
+                vm.bind_function("print", Box::new(PrintFunction));
+            }
+            "add_one" => {
+                vm.bind_function("add_one", Box::new(AddOneFunction));
+            }
+            other => panic!("unknown function '{other}'"),
+        }
+    }
+}
+
+fn run(compiled: CompiledProgram) -> Vec<Value> {
+    let mut vm = Vm::with_locals(compiled.program, compiled.locals);
+    let mut jit_config = vm.jit_config().clone();
+    jit_config.enabled = false;
+    vm.set_jit_config(jit_config);
+    register_functions(&mut vm, &compiled.functions);
+    let status = vm.run().expect("vm should run");
+    assert_eq!(status, VmStatus::Halted);
+    vm.stack().to_vec()
+}
+
+/// Checks that `js` is plain JavaScript rather than the frontend's superset:
+/// no `::` builtin paths outside literals, and, when `node` is installed, a
+/// clean `node --check` as an ES module.
+fn assert_parses_as_javascript(js: &str) {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut previous = '\0';
+    for ch in js.chars() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if ch == '\\' => escaped = true,
+            Some(open) if ch == open => quote = None,
+            Some(_) => {}
+            None if matches!(ch, '"' | '\'' | '`') => quote = Some(ch),
+            None => assert!(
+                !(previous == ':' && ch == ':'),
+                "JavaScript output uses a '::' path:\n{js}"
+            ),
+        }
+        previous = ch;
+    }
+
+    static CHECKS: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "vm_transpile_check_{}_{}.mjs",
+        std::process::id(),
+        CHECKS.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&path, js).expect("JavaScript output should write");
+    let checked = Command::new("node").arg("--check").arg(&path).output();
+    let _ = std::fs::remove_file(&path);
+    if let Ok(output) = checked {
+        assert!(
+            output.status.success(),
+            "JavaScript output does not parse: {}\n{js}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+/// Checks that `transpiled` compiles as `to` and leaves the same stack as
+/// the original program.
+fn assert_same_behavior(expected: &[Value], transpiled: &str, to: SourceFlavor) {
+    if to == SourceFlavor::JavaScript {
+        assert_parses_as_javascript(transpiled);
+    }
+    let recompiled = compile_source_with_flavor(transpiled, to)
+        .unwrap_or_else(|err| panic!("{to:?} output should compile: {err:?}\n{transpiled}"));
+    assert_eq!(run(recompiled), expected, "{to:?} output:\n{transpiled}");
+}
+
+fn assert_transpiles(source: &str, from: SourceFlavor, to: &[SourceFlavor]) -> Vec<String> {
+    let expected = run(compile_source_with_flavor(source, from).expect("original should compile"));
+    to.iter()
+        .map(|to| {
+            let transpiled = transpile_source(source, from, *to)
+                .unwrap_or_else(|err| panic!("transpile to {to:?} should succeed: {err}"));
+            assert_same_behavior(&expected, &transpiled, *to);
+            transpiled
+        })
+        .collect()
+}
+
+fn assert_file_transpiles(path: &str, to: &[SourceFlavor]) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(path);
+    let expected = run(compile_source_file(&path).expect("original should compile"));
+    for to in to {
+        let transpiled = transpile_source_file(&path, *to).unwrap_or_else(|err| {
+            panic!(
+                "transpiling {} to {to:?} should succeed: {err}",
+                path.display()
+            )
+        });
+        assert_same_behavior(&expected, &transpiled, *to);
+    }
+}
+
+#[test]
+fn transpiled_examples_behave_like_the_originals() {
+    for flavor in ["rss", "js", "lua", "scm"] {
+        assert_file_transpiles(&format!("examples/example.{flavor}"), &FLAVORS);
+    }
+    assert_file_transpiles("examples/example_complex.rss", &FLAVORS);
+    assert_file_transpiles("examples/example_complex.js", &FLAVORS);
+    assert_file_transpiles("examples/example_complex.scm", &FLAVORS);
+    assert_file_transpiles(
+        "examples/example_complex.lua",
+        &[
+            SourceFlavor::RustScript,
+            SourceFlavor::JavaScript,
+            SourceFlavor::Lua,
+        ],
+    );
+}
+
+#[test]
+fn transpiled_stdlib_tests_behave_like_the_originals() {
+    for name in ["collections", "iter", "math", "strings"] {
+        assert_file_transpiles(
+            &format!("stdlib/tests/{name}.rss"),
+            &[
+                SourceFlavor::RustScript,
+                SourceFlavor::JavaScript,
+                SourceFlavor::Lua,
+            ],
+        );
+    }
+}
+
+#[test]
+fn transpile_keeps_loops_with_break_and_continue() {
+    let outputs = assert_transpiles(
+        r#"
+        let sum = 0;
+        let i = 0;
+        while i < 10 {
+            i = i + 1;
+            if i == 3 {
+                continue;
+            }
+            if i == 8 {
+                break;
+            }
+            sum = sum + i;
+        }
+        for (let j = 0; j < 4; j = j + 1) {
+            sum = sum + j;
+        }
+        sum;
+        "#,
+        SourceFlavor::RustScript,
+        &FLAVORS,
+    );
+    assert!(
+        outputs[1].contains("for (let j = 0; j < 4; j = j + 1) {"),
+        "{}",
+        outputs[1]
+    );
+    assert!(outputs[2].contains("for j = 0, 3 do"), "{}", outputs[2]);
+    assert!(outputs[2].contains("goto continue"), "{}", outputs[2]);
+    assert!(outputs[3].contains("(for (j 0 4)"), "{}", outputs[3]);
+}
+
+#[test]
+fn transpile_keeps_loops_inside_functions() {
+    let outputs = assert_transpiles(
+        r#"
+        fn tally(n) {
+            let sum = 0;
+            let i = 0;
+            while i < n {
+                i = i + 1;
+                if i == 3 {
+                    continue;
+                }
+                if i == 8 {
+                    break;
+                }
+                sum = sum + i;
+            }
+            for (let j = 0; j < 4; j = j + 1) {
+                sum = sum + j;
+            }
+            if sum > 20 {
+                sum = sum - 20;
+            }
+            sum;
+        }
+        tally(10);
+        "#,
+        SourceFlavor::RustScript,
+        &FLAVORS,
+    );
+    assert!(outputs[3].contains("(let __loop"), "{}", outputs[3]);
+    // The length helper Lua injects counts table entries in a loop.
+    assert_transpiles(
+        r#"
+        local function size(items)
+          return #items
+        end
+        print(size({1, 2, 3}))
+        "#,
+        SourceFlavor::Lua,
+        &[SourceFlavor::Scheme],
+    );
+}
+
+#[test]
+fn transpile_from_javascript() {
+    let outputs = assert_transpiles(
+        r#"
+        function clamp(value, low, high) {
+            if (value < low) {
+                return low;
+            }
+            if (value > high) {
+                return high;
+            }
+            return value;
+        }
+        let scores = [3, 12, 7];
+        let total = 0;
+        for (const score of scores) {
+            total = total + clamp(score, 0, 10);
+        }
+        let report = { total: total, count: scores.length };
+        let label = `total=${report.total}`;
+        let size = total > 15 ? "big" : "small";
+        [total, report, label, size];
+        "#,
+        SourceFlavor::JavaScript,
+        &[
+            SourceFlavor::RustScript,
+            SourceFlavor::JavaScript,
+            SourceFlavor::Lua,
+        ],
+    );
+    assert!(
+        outputs[0].contains("fn clamp(value, low, high) {"),
+        "{}",
+        outputs[0]
+    );
+    assert!(
+        outputs[0].contains("for score in scores {"),
+        "{}",
+        outputs[0]
+    );
+    assert!(
+        outputs[1].contains("function clamp(value, low, high) {"),
+        "{}",
+        outputs[1]
+    );
+    assert!(
+        outputs[2].contains("local function clamp(value, low, high)"),
+        "{}",
+        outputs[2]
+    );
+    assert!(
+        outputs[2].contains("for _, score in pairs(scores) do"),
+        "{}",
+        outputs[2]
+    );
+}
+
+#[test]
+fn transpile_from_lua() {
+    let outputs = assert_transpiles(
+        r#"
+        local function describe(n)
+          if n % 2 == 0 then
+            return "even"
+          end
+          return "odd"
+        end
+        local sum = 0
+        for i = 0, 3 do
+          sum = sum + i
+        end
+        local info = { name = "lua", sum = sum }
+        local text = string.format("%s:%d", info.name, info.sum)
+        local kind = describe(sum)
+        local list = {text, kind, info.name .. "!"}
+        list
+        "#,
+        SourceFlavor::Lua,
+        &[
+            SourceFlavor::RustScript,
+            SourceFlavor::JavaScript,
+            SourceFlavor::Lua,
+        ],
+    );
+    assert!(
+        outputs[0].contains("format!(\"{}:{}\", info.name, info.sum)"),
+        "{}",
+        outputs[0]
+    );
+    assert!(
+        outputs[1].contains("`${info.name}:${info.sum}`"),
+        "{}",
+        outputs[1]
+    );
+    assert!(outputs[2].contains("for i = 0, 3 do"), "{}", outputs[2]);
+}
+
+#[test]
+fn transpile_from_scheme() {
+    let outputs = assert_transpiles(
+        r#"
+        (define (sum-squares a b) (+ (* a a) (* b b)))
+        (define total 0)
+        (for (i 0 5)
+          (set! total (+ total (sum-squares i 1))))
+        (define table (hash (name "scheme") (total total)))
+        (define message (format "~a=~a" (hash-ref table "name") total))
+        (define items (vector 1 2 3))
+        (vector-set! items 0 (string-length message))
+        (cond
+          ((> total 40) (set! total (- total 40)))
+          (else (set! total 0)))
+        (vector total message items)
+        "#,
+        SourceFlavor::Scheme,
+        &FLAVORS,
+    );
+    assert!(
+        outputs[0].contains("let sum_squares = |a, b| a * a + b * b;"),
+        "{}",
+        outputs[0]
+    );
+    assert!(
+        outputs[0].contains("items[0] = message.length;"),
+        "{}",
+        outputs[0]
+    );
+    assert!(
+        outputs[3].contains("(define (sum-squares a b)"),
+        "{}",
+        outputs[3]
+    );
+}
+
+#[test]
+fn transpile_rebuilds_match_slices_and_optional_access() {
+    let outputs = assert_transpiles(
+        r#"
+        let values = [1, 2, 3, 4, 5];
+        let head = values[0:2];
+        let tail = values[2:];
+        let point = { x: 3, y: 4 };
+        let label = match point {
+            { x: 0, y: 0 } => "origin",
+            { x: px, y: py } if px == py => "diagonal",
+            _ => "other",
+        };
+        let size = match values.length {
+            0 => "empty",
+            1..=3 => "small",
+            _ => "large",
+        };
+        let rest_sum = match values {
+            [first, ..rest] => first + rest.length,
+            _ => 0,
+        };
+        let nested = { inner: { value: 9 } };
+        let found = nested?.inner?.value;
+        let missing = nested?.nothing?.value;
+        let scale = 3;
+        let times = |n| n * scale;
+        let counted = 0;
+        for (k, v) in point {
+            counted = counted + times(v);
+        }
+        point.z = 5;
+        values[0] = 10;
+        let text = format!("{}-{}", label, counted);
+        [head, tail, label, size, rest_sum, found, missing, counted, point, values, text];
+        "#,
+        SourceFlavor::RustScript,
+        &FLAVORS,
+    );
+    assert!(
+        outputs[0].contains("let head = values[0:2];"),
+        "{}",
+        outputs[0]
+    );
+    assert!(
+        outputs[0].contains("nested?.inner?.value"),
+        "{}",
+        outputs[0]
+    );
+    assert!(outputs[1].contains("values.slice(2)"), "{}", outputs[1]);
+    assert!(
+        outputs[2].contains("local times = function(n) return n * scale end"),
+        "{}",
+        outputs[2]
+    );
+    assert!(
+        outputs[3].contains("(slice-range values 0 2)"),
+        "{}",
+        outputs[3]
+    );
+    assert!(
+        outputs[3].contains("nested?.inner?.value"),
+        "{}",
+        outputs[3]
+    );
+}
+
+#[test]
+fn transpile_keeps_format_specs_where_the_flavor_has_them() {
+    let outputs = assert_transpiles(
+        r#"
+        let name = "pd";
+        format!("[{:>5}] {:<4}| {:.2} {:?}", name, 7, 1.5, name);
+        "#,
+        SourceFlavor::RustScript,
+        &[SourceFlavor::RustScript, SourceFlavor::Lua],
+    );
+    assert!(
+        outputs[1].contains("string.format(\"[%5s] %-4s| %.2s %q\""),
+        "{}",
+        outputs[1]
+    );
+
+    let err = transpile_source(
+        "let name = \"pd\"; format!(\"{:>5}\", name);",
+        SourceFlavor::RustScript,
+        SourceFlavor::JavaScript,
+    )
+    .expect_err("template literals have no format specs");
+    assert!(
+        matches!(
+            err,
+            TranspileError::Unsupported {
+                flavor: SourceFlavor::JavaScript,
+                ..
+            }
+        ),
+        "{err}"
+    );
+    assert!(err.to_string().contains("in JavaScript"), "{err}");
+}
+
+#[test]
+fn transpile_rejects_assembly() {
+    let err = transpile_source("1;", SourceFlavor::RustScript, SourceFlavor::Assembly)
+        .expect_err("assembly is not a transpile target");
+    assert!(matches!(err, TranspileError::Assembly), "{err}");
+}
+
+#[test]
+fn transpile_rejects_statement_bodied_closures_in_rustscript() {
+    let source = "let f = (x) => { let y = x + 1; return y * 2; }; f(3);";
+    let err = transpile_source(source, SourceFlavor::JavaScript, SourceFlavor::RustScript)
+        .expect_err("RustScript closures have expression bodies");
+    assert!(
+        matches!(
+            err,
+            TranspileError::Unsupported {
+                flavor: SourceFlavor::RustScript,
+                ..
+            }
+        ),
+        "{err}"
+    );
+    assert!(
+        compile_source_with_flavor("let f = |x| => { x };", SourceFlavor::RustScript).is_err(),
+        "block-bodied closures are internal to frontend token streams"
+    );
+}
+
+#[test]
+fn transpiled_bindings_declare_each_value() {
+    let map = Value::Map(vec![
+        (
+            Value::String("name".to_string()),
+            Value::String("pd \"vm\"".to_string()),
+        ),
+        (Value::Int(2), Value::Null),
+    ]);
+    let bindings = vec![
+        (
+            "items".to_string(),
+            Value::Array(vec![Value::Int(1), Value::Float(2.5)]),
+        ),
+        ("entry_map".to_string(), map.clone()),
+        ("flag".to_string(), Value::Bool(true)),
+        ("__hidden".to_string(), Value::Int(1)),
+        ("nan".to_string(), Value::Float(f64::NAN)),
+    ];
+    for (flavor, tail) in [
+        (SourceFlavor::RustScript, "[items, entry_map, flag];"),
+        (SourceFlavor::JavaScript, "[items, entry_map, flag];"),
+        (
+            SourceFlavor::Lua,
+            "local all = {items, entry_map, flag}\nall",
+        ),
+        (SourceFlavor::Scheme, "(vector items entry-map flag)"),
+    ] {
+        let prelude = transpile_bindings(&bindings, flavor)
+            .unwrap_or_else(|err| panic!("{flavor:?} bindings should transpile: {err}"));
+        assert!(!prelude.contains("__hidden"), "{prelude}");
+        assert!(!prelude.contains("nan"), "{prelude}");
+        assert_same_behavior(
+            &[Value::Array(vec![
+                Value::Array(vec![Value::Int(1), Value::Float(2.5)]),
+                map.clone(),
+                Value::Bool(true),
+            ])],
+            &format!("{prelude}{tail}"),
+            flavor,
+        );
+    }
+}
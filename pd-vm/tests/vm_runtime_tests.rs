@@ -177,3 +177,73 @@ fn assemble_rejects_legacy_opcode_literals() {
     let err = assemble(source).expect_err("legacy opcodes should be rejected");
     assert!(err.message.contains("unknown opcode"));
 }
+
+#[test]
+fn value_total_cmp_orders_kinds_and_mixed_numbers() {
+    use std::cmp::Ordering;
+
+    let mut values = [
+        Value::String("a".to_string()),
+        Value::Float(f64::NAN),
+        Value::Float(1.0),
+        Value::Array(vec![Value::Int(1)]),
+        Value::Int(1),
+        Value::Bool(true),
+        Value::Null,
+        Value::Float(-0.5),
+    ];
+    values.sort_by(Value::total_cmp);
+    assert_eq!(values[0], Value::Null);
+    assert_eq!(values[1], Value::Bool(true));
+    assert_eq!(values[2], Value::Float(-0.5));
+    assert_eq!(values[3], Value::Int(1));
+    assert_eq!(values[4], Value::Float(1.0));
+    assert!(matches!(values[5], Value::Float(nan) if nan.is_nan()));
+    assert_eq!(values[6], Value::String("a".to_string()));
+    assert_eq!(values[7], Value::Array(vec![Value::Int(1)]));
+
+    assert_eq!(
+        Value::Array(vec![Value::Int(1)])
+            .total_cmp(&Value::Array(vec![Value::Int(1), Value::Null])),
+        Ordering::Less
+    );
+}
+
+#[test]
+fn value_total_cmp_puts_every_nan_after_all_numbers() {
+    use std::cmp::Ordering;
+
+    let negative_nan = Value::Float(-f64::NAN);
+    let positive_nan = Value::Float(f64::NAN);
+    for number in [
+        Value::Int(i64::MAX),
+        Value::Int(-7),
+        Value::Float(f64::INFINITY),
+        Value::Float(f64::NEG_INFINITY),
+        Value::Float(1.5),
+    ] {
+        for nan in [&negative_nan, &positive_nan] {
+            assert_eq!(
+                number.total_cmp(nan),
+                Ordering::Less,
+                "{number:?} vs {nan:?}"
+            );
+            assert_eq!(
+                nan.total_cmp(&number),
+                Ordering::Greater,
+                "{nan:?} vs {number:?}"
+            );
+        }
+    }
+
+    // Ints compare exactly against floats, even beyond f64's integer precision.
+    let above = Value::Int((1 << 53) + 1);
+    let float = Value::Float((1u64 << 53) as f64);
+    assert_eq!(above.total_cmp(&float), Ordering::Greater);
+    assert_eq!(float.total_cmp(&above), Ordering::Less);
+    assert_eq!(Value::Int(0).total_cmp(&Value::Float(-0.0)), Ordering::Less);
+    assert_eq!(
+        Value::Float(-0.0).total_cmp(&Value::Float(0.0)),
+        Ordering::Less
+    );
+}
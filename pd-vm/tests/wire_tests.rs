@@ -1,7 +1,10 @@
 #![cfg(feature = "runtime")]
+use std::path::{Path, PathBuf};
+
 use vm::{
-    ArgInfo, Assembler, BytecodeBuilder, DebugFunction, DebugInfo, DisassembleOptions, HostImport,
-    LineInfo, LocalInfo, Program, ValidationError, Value, WireError, decode_program,
+    ArgInfo, Assembler, BytecodeBuilder, DebugFunction, DebugInfo, DebugSource, DisassembleOptions,
+    HostImport, InlineFrame, LineInfo, LocalInfo, Program, SourceFlavor, ValidationError, Value,
+    WireError, assemble, compile_source_file, decode_program, disassemble_program,
     disassemble_vmbc, disassemble_vmbc_with_options, encode_program, infer_local_count,
     validate_program,
 };
@@ -21,10 +24,23 @@ fn wire_roundtrip_preserves_constants_and_code() {
             arity: 1,
         }],
         Some(DebugInfo {
-            source: Some("fn a(x);\na(1);".to_string()),
+            sources: vec![DebugSource {
+                name: "<source>".to_string(),
+                text: Some("fn a(x);\na(1);".to_string()),
+            }],
             lines: vec![
-                LineInfo { offset: 0, line: 1 },
-                LineInfo { offset: 1, line: 2 },
+                LineInfo {
+                    offset: 0,
+                    source_id: 0,
+                    line: 1,
+                    column: 0,
+                },
+                LineInfo {
+                    offset: 1,
+                    source_id: 0,
+                    line: 2,
+                    column: 0,
+                },
             ],
             functions: vec![DebugFunction {
                 name: "a".to_string(),
@@ -37,6 +53,18 @@ fn wire_roundtrip_preserves_constants_and_code() {
                 name: "v".to_string(),
                 index: 0,
             }],
+            inline_frames: vec![InlineFrame {
+                start: 1,
+                end: 2,
+                function: "a".to_string(),
+                call_source: 0,
+                call_line: 2,
+                parent: None,
+                locals: vec![LocalInfo {
+                    name: "x".to_string(),
+                    index: 1,
+                }],
+            }],
         }),
     );
 
@@ -178,12 +206,14 @@ fn disassemble_vmbc_outputs_readable_listing() {
 
     let listing = disassemble_vmbc(&bytes).expect("disassembly should succeed");
 
-    assert!(listing.contains("constants (1):"));
-    assert!(listing.contains("[0000] String(\"x\")"));
-    assert!(listing.contains("imports (1):"));
-    assert!(listing.contains("[0000] print/1"));
-    assert!(listing.contains("ldc 0 ; const[0]=String(\"x\")"));
-    assert!(listing.contains("call 0 1 ; import print/1"));
+    assert!(listing.contains("; constants (1)"));
+    assert!(listing.contains(".const k0 \"x\""));
+    assert!(listing.contains("; imports (1)"));
+    assert!(listing.contains(".import print 1"));
+    assert!(listing.contains(".debug none"));
+    assert!(listing.contains("ldc k0"));
+    assert!(listing.contains("call 0 1"));
+    assert!(listing.contains("import print/1"));
     assert!(listing.contains("ret"));
 }
 
@@ -199,14 +229,33 @@ fn disassemble_vmbc_can_include_embedded_source() {
         bc.finish(),
         vec![],
         Some(DebugInfo {
-            source: Some("let x = 1;\nx;".to_string()),
+            sources: vec![DebugSource {
+                name: "<source>".to_string(),
+                text: Some("let x = 1;\nx;".to_string()),
+            }],
             lines: vec![
-                LineInfo { offset: 0, line: 1 },
-                LineInfo { offset: 5, line: 1 },
-                LineInfo { offset: 7, line: 2 },
+                LineInfo {
+                    offset: 0,
+                    source_id: 0,
+                    line: 1,
+                    column: 0,
+                },
+                LineInfo {
+                    offset: 5,
+                    source_id: 0,
+                    line: 1,
+                    column: 0,
+                },
+                LineInfo {
+                    offset: 7,
+                    source_id: 0,
+                    line: 2,
+                    column: 0,
+                },
             ],
             functions: vec![],
             locals: vec![],
+            inline_frames: Vec::new(),
         }),
     );
     let bytes = encode_program(&program).expect("encode should succeed");
@@ -217,11 +266,13 @@ fn disassemble_vmbc_can_include_embedded_source() {
     let src1 = listing
         .find("; src 0001  let x = 1;")
         .expect("line 1 source marker");
-    let op1 = listing.find("0000\t02 00 00 00 00").expect("line 1 opcode");
+    let op1 = listing
+        .find("; 0000  02 00 00 00 00")
+        .expect("line 1 opcode");
     let src2 = listing
         .find("; src 0002  x;")
         .expect("line 2 source marker");
-    let op2 = listing.find("0007\t0F 00").expect("line 2 opcode");
+    let op2 = listing.find("; 0007  0F 00").expect("line 2 opcode");
     assert!(src1 < op1);
     assert!(src2 < op2);
 }
@@ -235,10 +286,14 @@ fn disassemble_vmbc_hides_source_without_flag() {
         bc.finish(),
         vec![],
         Some(DebugInfo {
-            source: Some("let x = 1;\nx;".to_string()),
+            sources: vec![DebugSource {
+                name: "<source>".to_string(),
+                text: Some("let x = 1;\nx;".to_string()),
+            }],
             lines: vec![],
             functions: vec![],
             locals: vec![],
+            inline_frames: Vec::new(),
         }),
     );
     let bytes = encode_program(&program).expect("encode should succeed");
@@ -246,7 +301,117 @@ fn disassemble_vmbc_hides_source_without_flag() {
     let listing = disassemble_vmbc(&bytes).expect("disassembly should succeed");
 
     assert!(!listing.contains("source:"));
-    assert!(!listing.contains("let x = 1;"));
+    assert!(!listing.contains("; src"));
+    // The text is still carried, escaped, so the listing assembles back.
+    assert!(listing.contains(".source 0 <source> \"let x = 1;\\nx;\""));
+}
+
+fn assert_listing_round_trips(program: &Program, what: &str) {
+    let bytes = encode_program(program).expect("encode should succeed");
+    let listing = disassemble_vmbc(&bytes).expect("disassembly should succeed");
+    let reassembled = assemble(&listing)
+        .unwrap_or_else(|err| panic!("listing of {what} should assemble: {err}\n{listing}"));
+    let reencoded = encode_program(&reassembled).expect("encode should succeed");
+    assert!(
+        reencoded == bytes,
+        "listing of {what} should assemble to identical VMBC\n{listing}"
+    );
+}
+
+fn example_programs(dir: &Path, out: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).expect("examples should be readable") {
+        let path = entry.expect("examples should be readable").path();
+        if path.is_dir() {
+            example_programs(&path, out);
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(SourceFlavor::from_extension)
+            .is_some()
+        {
+            out.push(path);
+        }
+    }
+}
+
+#[test]
+fn disassembly_assembles_back_to_identical_vmbc_for_every_example() {
+    let mut paths = Vec::new();
+    example_programs(
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("examples"),
+        &mut paths,
+    );
+    paths.sort();
+    assert!(
+        paths
+            .iter()
+            .any(|path| path.extension().is_some_and(|ext| ext == "pda")),
+        "examples should include an assembly program"
+    );
+
+    for path in &paths {
+        let compiled = compile_source_file(path)
+            .unwrap_or_else(|err| panic!("{} should compile: {err}", path.display()));
+        let program = decode_program(&encode_program(&compiled.program).expect("encode"))
+            .expect("decode should succeed");
+        assert_listing_round_trips(&program, &path.display().to_string());
+    }
+}
+
+#[test]
+fn disassembly_lists_undecodable_code_as_bytes() {
+    let mut bc = BytecodeBuilder::new();
+    // Jumps into its own operand, so the target splits the instruction.
+    bc.br(2);
+    bc.ldc(7);
+    bc.ret();
+    let mut code = bc.finish();
+    code.push(0xFF);
+    code.extend_from_slice(&[0x11, 0x00]);
+    let program = Program::with_imports_and_debug(vec![Value::Null], code, vec![], None);
+
+    let listing = disassemble_program(&program);
+
+    assert!(listing.contains(".byte 0x0B 0x02"), "{listing}");
+    assert!(listing.contains(".label L0002"), "{listing}");
+    assert!(listing.contains(".byte 0xFF"), "{listing}");
+    assert_listing_round_trips(&program, "undecodable code");
+}
+
+#[test]
+fn assembly_debug_directives_rebuild_inlined_call_ranges() {
+    let source = r#"
+        .debug explicit
+        .source 0 main.rss "let x = twice(2);"
+        .source 1 "lib dir/twice.rss" "fn twice(v) { v * 2 }"
+        .function twice v
+        .inline twice 0 1 v@3
+        .line 1 1 15
+            ldc 2
+            ldc 2
+            mul
+        .endinline
+        .line 0 1 1
+            ret
+    "#;
+
+    let program = assemble(source).expect("assemble should succeed");
+
+    let debug = program.debug.as_ref().expect("debug info");
+    assert_eq!(debug.sources[1].name, "lib dir/twice.rss");
+    assert_eq!(debug.inline_frames.len(), 1);
+    assert_eq!(debug.inline_frames[0].start, 0);
+    assert_eq!(debug.inline_frames[0].end, 11);
+    assert_eq!(
+        debug.inline_frames[0].locals,
+        vec![LocalInfo {
+            name: "v".to_string(),
+            index: 3,
+        }]
+    );
+    assert_eq!(debug.lines[1].offset, 11);
+    assert_eq!(debug.functions[0].args[0].name, "v");
+    assert_listing_round_trips(&program, "inlined call");
 }
 
 #[test]